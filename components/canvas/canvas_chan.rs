@@ -0,0 +1,77 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use canvas_render_task::{CanvasMsg, SendFrame};
+use servo_net::image::base::{Image, image_from_bgra};
+
+use std::comm::{channel, Sender};
+use std::sync::atomic::{AtomicUint, SeqCst};
+use sync::{Arc, Mutex};
+
+/// The contents of a canvas, which are shared rather than copied to paint it.
+pub type CanvasFrame = Arc<Box<Image>>;
+
+/// The last frame that a canvas task has made, with the number of messages it had handled then.
+pub type LastFrame = Arc<Mutex<Option<(CanvasFrame, uint)>>>;
+
+pub fn new_frame(image: Image) -> CanvasFrame {
+    Arc::new(box image)
+}
+
+/// The channel to a canvas task. It shares the last frame that the task has made, so that
+/// painting the canvas only waits for the task when it has been sent messages since.
+#[deriving(Clone)]
+pub struct CanvasChan {
+    chan: Sender<CanvasMsg>,
+    /// The number of messages sent to the canvas task, not counting `SendFrame`.
+    message_count: Arc<AtomicUint>,
+    last_frame: LastFrame,
+}
+
+impl CanvasChan {
+    /// Wraps the channel to a new canvas task, which gets the returned `LastFrame` to update.
+    pub fn new(chan: Sender<CanvasMsg>) -> (CanvasChan, LastFrame) {
+        let last_frame = Arc::new(Mutex::new(None));
+        let chan = CanvasChan {
+            chan: chan,
+            message_count: Arc::new(AtomicUint::new(0)),
+            last_frame: last_frame.clone(),
+        };
+        (chan, last_frame)
+    }
+
+    /// Sends a message to the canvas task. Messages to a canvas task that has exited are dropped.
+    pub fn send(&self, msg: CanvasMsg) {
+        if self.chan.send_opt(msg).is_ok() {
+            // Counted once sent, so that `frame` waits for the canvas task to handle the message.
+            self.message_count.fetch_add(1, SeqCst);
+        }
+    }
+
+    /// Returns the current contents of the canvas. Once the canvas task has exited, that is the
+    /// last frame it made, or an empty frame if it never made one.
+    pub fn frame(&self) -> CanvasFrame {
+        let message_count = self.message_count.load(SeqCst);
+        match *self.last_frame.lock() {
+            Some((ref frame, frame_message_count)) if frame_message_count >= message_count => {
+                return frame.clone()
+            }
+            _ => {}
+        }
+        let (sender, receiver) = channel();
+        let frame = match self.chan.send_opt(SendFrame(sender)) {
+            Ok(()) => receiver.recv_opt().ok(),
+            Err(_) => None,
+        };
+        match frame {
+            Some(frame) => frame,
+            None => {
+                match *self.last_frame.lock() {
+                    Some((ref frame, _)) => frame.clone(),
+                    None => new_frame(image_from_bgra(0, 0, vec!())),
+                }
+            }
+        }
+    }
+}
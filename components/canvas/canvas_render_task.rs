@@ -2,49 +2,218 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
+use azure::AzFloat;
 use azure::azure_hl::{DrawTarget, Color, B8G8R8A8, SkiaBackend, StrokeOptions, DrawOptions};
-use azure::azure_hl::{ColorPattern, ColorPatternRef};
+use azure::azure_hl::{ColorPattern, ColorPatternRef, DestInOp, DrawSurfaceOptions, ExtendClamp};
+use azure::azure_hl::{GradientStop, Linear, LinearGradientPattern, LinearGradientPatternRef};
+use azure::azure_hl::{Path, RadialGradientPattern, RadialGradientPatternRef, SourceOp};
+use cssparser::RGBA;
+use geom::matrix2d::Matrix2D;
+use geom::point::Point2D;
 use geom::rect::Rect;
 use geom::size::Size2D;
+use canvas_chan::{CanvasChan, CanvasFrame, LastFrame, new_frame};
+use servo_net::image::base::image_from_bgra;
+use servo_util::geometry::{invert_transform, transform_point, transform_rect};
 use servo_util::task::spawn_named;
 
+use std::cmp;
 use std::comm;
+use std::num::{Float, FloatMath};
 
 pub enum CanvasMsg {
     FillRect(Rect<f32>),
     ClearRect(Rect<f32>),
     StrokeRect(Rect<f32>),
+    BeginPath,
+    ClosePath,
+    Fill,
+    Stroke,
+    Clip,
+    MoveTo(Point2D<f32>),
+    LineTo(Point2D<f32>),
+    QuadraticCurveTo(Point2D<f32>, Point2D<f32>),
+    BezierCurveTo(Point2D<f32>, Point2D<f32>, Point2D<f32>),
+    /// Center, radius, start angle, end angle and whether the arc is drawn anticlockwise.
+    Arc(Point2D<f32>, f32, f32, f32, bool),
+    RectPath(Rect<f32>),
+    SaveContext,
+    RestoreContext,
+    SetTransform(Matrix2D<f32>),
+    SetFillStyle(FillOrStrokeStyle),
+    SetStrokeStyle(FillOrStrokeStyle),
+    SetLineWidth(f32),
+    SetGlobalAlpha(f32),
+    /// Premultiplied BGRA pixels of an image, its size, the destination rectangle and the source
+    /// rectangle within the image.
+    DrawImage(Vec<u8>, Size2D<i32>, Rect<f32>, Rect<f32>),
+    /// Sends back the current contents of the canvas. Only sent by `CanvasChan::frame`.
+    SendFrame(Sender<CanvasFrame>),
+    /// Sends back the unpremultiplied RGBA pixels of the given rectangle of the canvas.
+    GetImageData(Rect<i32>, Sender<Vec<u8>>),
+    /// Replaces the given rectangle of the canvas with unpremultiplied RGBA pixels.
+    PutImageData(Vec<u8>, Rect<i32>),
     Recreate(Size2D<i32>),
     Close,
 }
 
+/// A color stop of a `CanvasGradient`.
+#[deriving(Clone)]
+pub struct CanvasGradientStop {
+    pub offset: f64,
+    pub color: RGBA,
+}
+
+/// A gradient along the line from `(x0, y0)` to `(x1, y1)`.
+#[deriving(Clone)]
+pub struct LinearGradientStyle {
+    pub x0: f64,
+    pub y0: f64,
+    pub x1: f64,
+    pub y1: f64,
+    pub stops: Vec<CanvasGradientStop>,
+}
+
+/// A gradient between the circle centered at `(x0, y0)` with radius `r0` and the circle centered
+/// at `(x1, y1)` with radius `r1`.
+#[deriving(Clone)]
+pub struct RadialGradientStyle {
+    pub x0: f64,
+    pub y0: f64,
+    pub r0: f64,
+    pub x1: f64,
+    pub y1: f64,
+    pub r1: f64,
+    pub stops: Vec<CanvasGradientStop>,
+}
+
+/// An image that is tiled in either or both directions.
+#[deriving(Clone)]
+pub struct SurfaceStyle {
+    /// Premultiplied BGRA pixels.
+    pub surface_data: Vec<u8>,
+    pub surface_size: Size2D<i32>,
+    pub repeat_x: bool,
+    pub repeat_y: bool,
+}
+
+/// What shapes are filled or stroked with.
+#[deriving(Clone)]
+pub enum FillOrStrokeStyle {
+    ColorStyle(RGBA),
+    LinearGradientStyleValue(LinearGradientStyle),
+    RadialGradientStyleValue(RadialGradientStyle),
+    SurfaceStyleValue(SurfaceStyle),
+}
+
+/// A segment of the current path. Points are in device space, since the path is not affected by
+/// changes to the transform after a segment has been added.
+#[deriving(Clone)]
+enum PathSegment {
+    MoveToSegment(Point2D<AzFloat>),
+    LineToSegment(Point2D<AzFloat>),
+    BezierCurveToSegment(Point2D<AzFloat>, Point2D<AzFloat>, Point2D<AzFloat>),
+    ClosePathSegment,
+}
+
+/// The parts of the drawing state that are saved and restored by `save()` and `restore()`.
+#[deriving(Clone)]
+struct DrawState {
+    fill_style: FillOrStrokeStyle,
+    stroke_style: FillOrStrokeStyle,
+    line_width: AzFloat,
+    global_alpha: AzFloat,
+    transform: Matrix2D<AzFloat>,
+    /// The number of clips that have been pushed onto the draw target.
+    clip_depth: uint,
+}
+
+impl DrawState {
+    fn new() -> DrawState {
+        let black = RGBA { red: 0., green: 0., blue: 0., alpha: 1. };
+        DrawState {
+            fill_style: ColorStyle(black),
+            stroke_style: ColorStyle(black),
+            line_width: 1.0,
+            global_alpha: 1.0,
+            transform: Matrix2D::identity(),
+            clip_depth: 0,
+        }
+    }
+}
+
 pub struct CanvasRenderTask {
     drawtarget: DrawTarget,
-    fill_color: ColorPattern,
-    stroke_color: ColorPattern,
-    stroke_opts: StrokeOptions,
+    size: Size2D<i32>,
+    state: DrawState,
+    saved_states: Vec<DrawState>,
+    path: Vec<PathSegment>,
+    /// The start of the current subpath, if there is one.
+    subpath_start: Option<Point2D<AzFloat>>,
+    /// The last point of the current path, if there is one.
+    current_point: Option<Point2D<AzFloat>>,
 }
 
 impl CanvasRenderTask {
     fn new(size: Size2D<i32>) -> CanvasRenderTask {
         CanvasRenderTask {
             drawtarget: CanvasRenderTask::create(size),
-            fill_color: ColorPattern::new(Color::new(0., 0., 0., 1.)),
-            stroke_color: ColorPattern::new(Color::new(0., 0., 0., 1.)),
-            stroke_opts: StrokeOptions::new(1.0, 1.0),
+            size: size,
+            state: DrawState::new(),
+            saved_states: vec!(),
+            path: vec!(),
+            subpath_start: None,
+            current_point: None,
         }
     }
 
-    pub fn start(size: Size2D<i32>) -> Sender<CanvasMsg> {
+    pub fn start(size: Size2D<i32>) -> CanvasChan {
         let (chan, port) = comm::channel::<CanvasMsg>();
+        let (chan, last_frame) = CanvasChan::new(chan);
         spawn_named("CanvasTask", proc() {
             let mut renderer = CanvasRenderTask::new(size);
+            let mut message_count = 0u;
 
             loop {
-                match port.recv() {
+                let msg = port.recv();
+                match msg {
+                    SendFrame(_) => {}
+                    _ => message_count += 1,
+                }
+                match msg {
                     FillRect(ref rect) => renderer.fill_rect(rect),
                     StrokeRect(ref rect) => renderer.stroke_rect(rect),
                     ClearRect(ref rect) => renderer.clear_rect(rect),
+                    BeginPath => renderer.begin_path(),
+                    ClosePath => renderer.close_path(),
+                    Fill => renderer.fill(),
+                    Stroke => renderer.stroke(),
+                    Clip => renderer.clip(),
+                    MoveTo(ref point) => renderer.move_to(point),
+                    LineTo(ref point) => renderer.line_to(point),
+                    QuadraticCurveTo(ref cp, ref point) => {
+                        renderer.quadratic_curve_to(cp, point)
+                    }
+                    BezierCurveTo(ref cp1, ref cp2, ref point) => {
+                        renderer.bezier_curve_to(cp1, cp2, point)
+                    }
+                    Arc(ref center, radius, start, end, anticlockwise) => {
+                        renderer.arc(center, radius, start, end, anticlockwise)
+                    }
+                    RectPath(ref rect) => renderer.rect(rect),
+                    SaveContext => renderer.save_context(),
+                    RestoreContext => renderer.restore_context(),
+                    SetTransform(ref matrix) => renderer.set_transform(matrix),
+                    SetFillStyle(style) => renderer.state.fill_style = style,
+                    SetStrokeStyle(style) => renderer.state.stroke_style = style,
+                    SetLineWidth(width) => renderer.state.line_width = width,
+                    SetGlobalAlpha(alpha) => renderer.state.global_alpha = alpha,
+                    DrawImage(data, size, ref dest_rect, ref source_rect) => {
+                        renderer.draw_image(data, size, dest_rect, source_rect)
+                    }
+                    SendFrame(chan) => renderer.send_frame(&last_frame, message_count, chan),
+                    GetImageData(ref rect, chan) => renderer.get_image_data(rect, chan),
+                    PutImageData(data, ref rect) => renderer.put_image_data(data, rect),
                     Recreate(size) => renderer.recreate(size),
                     Close => break,
                 }
@@ -54,8 +223,9 @@ impl CanvasRenderTask {
     }
 
     fn fill_rect(&self, rect: &Rect<f32>) {
-        let drawopts = DrawOptions::new(1.0, 0);
-        self.drawtarget.fill_rect(rect, ColorPatternRef(&self.fill_color), Some(&drawopts));
+        self.draw_with_style(&self.state.fill_style, |draw_target, pattern, draw_options| {
+            draw_target.fill_rect(rect, ColorPatternRef(pattern), Some(draw_options));
+        })
     }
 
     fn clear_rect(&self, rect: &Rect<f32>) {
@@ -63,8 +233,414 @@ impl CanvasRenderTask {
     }
 
     fn stroke_rect(&self, rect: &Rect<f32>) {
-        let drawopts = DrawOptions::new(1.0, 0);
-        self.drawtarget.stroke_rect(rect, &self.stroke_color, &self.stroke_opts, &drawopts);
+        let stroke_options = StrokeOptions::new(self.state.line_width, 1.0);
+        self.draw_with_style(&self.state.stroke_style, |draw_target, pattern, draw_options| {
+            draw_target.stroke_rect(rect, pattern, &stroke_options, draw_options);
+        })
+    }
+
+    fn begin_path(&mut self) {
+        self.path.clear();
+        self.subpath_start = None;
+        self.current_point = None;
+    }
+
+    fn close_path(&mut self) {
+        match self.subpath_start {
+            None => {}
+            Some(start) => {
+                self.path.push(ClosePathSegment);
+                self.current_point = Some(start);
+            }
+        }
+    }
+
+    fn move_to(&mut self, point: &Point2D<f32>) {
+        let point = transform_point(&self.state.transform, point);
+        self.path.push(MoveToSegment(point));
+        self.subpath_start = Some(point);
+        self.current_point = Some(point);
+    }
+
+    fn line_to(&mut self, point: &Point2D<f32>) {
+        if self.current_point.is_none() {
+            return self.move_to(point)
+        }
+        let point = transform_point(&self.state.transform, point);
+        self.path.push(LineToSegment(point));
+        self.current_point = Some(point);
+    }
+
+    /// Makes sure that there is a subpath before a curve is added to it, as if `moveTo()` had
+    /// been called with the given point.
+    fn ensure_subpath(&mut self, point: &Point2D<f32>) {
+        if self.current_point.is_none() {
+            self.move_to(point)
+        }
+    }
+
+    fn quadratic_curve_to(&mut self, cp: &Point2D<f32>, point: &Point2D<f32>) {
+        self.ensure_subpath(cp);
+
+        // Quadratic curves are degree-elevated to cubic curves, which is exact. This is done in
+        // device space, which is fine since affine transforms preserve Bezier curves.
+        let start = self.current_point.unwrap();
+        let cp = transform_point(&self.state.transform, cp);
+        let end = transform_point(&self.state.transform, point);
+        let cp1 = Point2D(start.x + (cp.x - start.x) * 2.0 / 3.0,
+                          start.y + (cp.y - start.y) * 2.0 / 3.0);
+        let cp2 = Point2D(end.x + (cp.x - end.x) * 2.0 / 3.0,
+                          end.y + (cp.y - end.y) * 2.0 / 3.0);
+        self.path.push(BezierCurveToSegment(cp1, cp2, end));
+        self.current_point = Some(end);
+    }
+
+    fn bezier_curve_to(&mut self, cp1: &Point2D<f32>, cp2: &Point2D<f32>, point: &Point2D<f32>) {
+        self.ensure_subpath(cp1);
+
+        let transform = &self.state.transform;
+        let end = transform_point(transform, point);
+        self.path.push(BezierCurveToSegment(transform_point(transform, cp1),
+                                            transform_point(transform, cp2),
+                                            end));
+        self.current_point = Some(end);
+    }
+
+    /// Adds an arc to the path as a series of cubic Bezier curves, each spanning at most a
+    /// quarter turn, so that the arc is transformed correctly by non-uniform scales.
+    fn arc(&mut self,
+           center: &Point2D<f32>,
+           radius: f32,
+           start_angle: f32,
+           end_angle: f32,
+           anticlockwise: bool) {
+        let two_pi: f32 = Float::two_pi();
+        let sweep = if !anticlockwise && end_angle - start_angle >= two_pi {
+            two_pi
+        } else if anticlockwise && start_angle - end_angle >= two_pi {
+            -two_pi
+        } else if !anticlockwise {
+            let sweep = (end_angle - start_angle) % two_pi;
+            if sweep < 0.0 { sweep + two_pi } else { sweep }
+        } else {
+            let sweep = (start_angle - end_angle) % two_pi;
+            -(if sweep < 0.0 { sweep + two_pi } else { sweep })
+        };
+
+        let point_at = |angle: f32| {
+            Point2D(center.x + radius * angle.cos(), center.y + radius * angle.sin())
+        };
+
+        let start = point_at(start_angle);
+        if self.current_point.is_none() {
+            self.move_to(&start)
+        } else {
+            self.line_to(&start)
+        }
+
+        let segment_count = (sweep.abs() / Float::frac_pi_2()).ceil() as uint;
+        if segment_count == 0 {
+            return
+        }
+        let segment_sweep = sweep / (segment_count as f32);
+        let control_distance = radius * 4.0 / 3.0 * (segment_sweep / 4.0).tan();
+        let tangent_at = |angle: f32| {
+            Point2D(-angle.sin() * control_distance, angle.cos() * control_distance)
+        };
+
+        let mut angle = start_angle;
+        for _ in range(0, segment_count) {
+            let next_angle = angle + segment_sweep;
+            let cp1 = point_at(angle) + tangent_at(angle);
+            let end = point_at(next_angle);
+            let cp2 = end - tangent_at(next_angle);
+            self.bezier_curve_to(&cp1, &cp2, &end);
+            angle = next_angle;
+        }
+    }
+
+    fn rect(&mut self, rect: &Rect<f32>) {
+        self.move_to(&rect.origin);
+        self.line_to(&Point2D(rect.max_x(), rect.origin.y));
+        self.line_to(&Point2D(rect.max_x(), rect.max_y()));
+        self.line_to(&Point2D(rect.origin.x, rect.max_y()));
+        self.close_path();
+    }
+
+    /// Builds an Azure path for the current path in the user space of the current transform, so
+    /// that strokes are as wide as the line width in user space. Returns `None` if the transform
+    /// is singular, in which case nothing can be drawn.
+    fn build_path(&self, draw_target: &DrawTarget) -> Option<Path> {
+        let inverse = match invert_transform(&self.state.transform) {
+            None => return None,
+            Some(inverse) => inverse,
+        };
+
+        let path_builder = draw_target.create_path_builder();
+        for segment in self.path.iter() {
+            match *segment {
+                MoveToSegment(ref point) => path_builder.move_to(transform_point(&inverse, point)),
+                LineToSegment(ref point) => path_builder.line_to(transform_point(&inverse, point)),
+                BezierCurveToSegment(ref cp1, ref cp2, ref point) => {
+                    path_builder.bezier_curve_to(&transform_point(&inverse, cp1),
+                                                 &transform_point(&inverse, cp2),
+                                                 &transform_point(&inverse, point))
+                }
+                ClosePathSegment => path_builder.close(),
+            }
+        }
+        Some(path_builder.finish())
+    }
+
+    fn fill(&self) {
+        self.draw_with_style(&self.state.fill_style, |draw_target, pattern, draw_options| {
+            match self.build_path(draw_target) {
+                None => {}
+                Some(path) => draw_target.fill(&path, pattern, draw_options),
+            }
+        })
+    }
+
+    fn stroke(&self) {
+        let stroke_options = StrokeOptions::new(self.state.line_width, 1.0);
+        self.draw_with_style(&self.state.stroke_style, |draw_target, pattern, draw_options| {
+            match self.build_path(draw_target) {
+                None => {}
+                Some(path) => draw_target.stroke(&path, pattern, &stroke_options, draw_options),
+            }
+        })
+    }
+
+    fn clip(&mut self) {
+        match self.build_path(&self.drawtarget) {
+            None => {
+                // A singular transform leaves nothing visible.
+                self.drawtarget.push_clip(&self.drawtarget.create_path_builder().finish())
+            }
+            Some(path) => self.drawtarget.push_clip(&path),
+        }
+        self.state.clip_depth += 1;
+    }
+
+    fn save_context(&mut self) {
+        self.saved_states.push(self.state.clone());
+    }
+
+    fn restore_context(&mut self) {
+        match self.saved_states.pop() {
+            None => {}
+            Some(state) => {
+                for _ in range(state.clip_depth, self.state.clip_depth) {
+                    self.drawtarget.pop_clip();
+                }
+                self.state = state;
+                self.drawtarget.set_transform(&self.state.transform);
+            }
+        }
+    }
+
+    fn set_transform(&mut self, transform: &Matrix2D<f32>) {
+        self.state.transform = *transform;
+        self.drawtarget.set_transform(transform);
+    }
+
+    /// Draws a shape with the given fill or stroke style. `draw_shape` is given the draw target,
+    /// color and options to draw the shape with.
+    ///
+    /// Azure can only fill and stroke paths with solid colors, so for gradients and patterns the
+    /// shape is drawn into a mask, the style is painted over the whole canvas and masked with it,
+    /// and the result is composited onto the canvas.
+    fn draw_with_style(&self,
+                       style: &FillOrStrokeStyle,
+                       draw_shape: |&DrawTarget, &ColorPattern, &DrawOptions|) {
+        let draw_options = DrawOptions::new(self.state.global_alpha, 0);
+        match *style {
+            ColorStyle(ref color) => {
+                return draw_shape(&self.drawtarget,
+                                  &ColorPattern::new(to_azure_color(color)),
+                                  &draw_options)
+            }
+            _ => {}
+        }
+
+        let mask = self.drawtarget.create_similar_draw_target(&self.size, B8G8R8A8);
+        mask.set_transform(&self.state.transform);
+        draw_shape(&mask,
+                   &ColorPattern::new(Color::new(0., 0., 0., 1.)),
+                   &DrawOptions::new(1.0, 0));
+
+        let painted = self.drawtarget.create_similar_draw_target(&self.size, B8G8R8A8);
+        painted.set_transform(&self.state.transform);
+        let canvas_rect = Rect(Point2D(0., 0.),
+                               Size2D(self.size.width as AzFloat, self.size.height as AzFloat));
+        let user_space_rect = match invert_transform(&self.state.transform) {
+            None => return,
+            Some(inverse) => transform_rect(&inverse, &canvas_rect),
+        };
+        match *style {
+            ColorStyle(_) => unreachable!(),
+            LinearGradientStyleValue(ref gradient) => {
+                if gradient.stops.is_empty() {
+                    return
+                }
+                let stops = painted.create_gradient_stops(to_gradient_stops(&gradient.stops)
+                                                              .as_slice(),
+                                                          ExtendClamp);
+                let pattern = LinearGradientPattern::new(&Point2D(gradient.x0 as AzFloat,
+                                                                  gradient.y0 as AzFloat),
+                                                         &Point2D(gradient.x1 as AzFloat,
+                                                                  gradient.y1 as AzFloat),
+                                                         stops,
+                                                         &Matrix2D::identity());
+                painted.fill_rect(&user_space_rect, LinearGradientPatternRef(&pattern), None);
+            }
+            RadialGradientStyleValue(ref gradient) => {
+                if gradient.stops.is_empty() {
+                    return
+                }
+                let stops = painted.create_gradient_stops(to_gradient_stops(&gradient.stops)
+                                                              .as_slice(),
+                                                          ExtendClamp);
+                let pattern = RadialGradientPattern::new(&Point2D(gradient.x0 as AzFloat,
+                                                                  gradient.y0 as AzFloat),
+                                                         &Point2D(gradient.x1 as AzFloat,
+                                                                  gradient.y1 as AzFloat),
+                                                         gradient.r0 as AzFloat,
+                                                         gradient.r1 as AzFloat,
+                                                         stops,
+                                                         &Matrix2D::identity());
+                painted.fill_rect(&user_space_rect, RadialGradientPatternRef(&pattern), None);
+            }
+            SurfaceStyleValue(ref surface) => tile_surface(&painted, surface, &user_space_rect),
+        }
+
+        let mut mask_options = DrawOptions::new(1.0, 0);
+        mask_options.set_composition_op(DestInOp);
+        painted.set_transform(&Matrix2D::identity());
+        painted.draw_surface(mask.snapshot(),
+                             canvas_rect,
+                             canvas_rect,
+                             DrawSurfaceOptions::new(Linear, true),
+                             mask_options);
+
+        self.drawtarget.set_transform(&Matrix2D::identity());
+        self.drawtarget.draw_surface(painted.snapshot(),
+                                     canvas_rect,
+                                     canvas_rect,
+                                     DrawSurfaceOptions::new(Linear, true),
+                                     draw_options);
+        self.drawtarget.set_transform(&self.state.transform);
+    }
+
+    fn draw_image(&self,
+                  image_data: Vec<u8>,
+                  image_size: Size2D<i32>,
+                  dest_rect: &Rect<f32>,
+                  source_rect: &Rect<f32>) {
+        let source_surface = self.drawtarget.create_source_surface_from_data(
+            image_data.as_slice(),
+            image_size,
+            image_size.width * 4,
+            B8G8R8A8);
+        self.drawtarget.draw_surface(source_surface,
+                                     *dest_rect,
+                                     *source_rect,
+                                     DrawSurfaceOptions::new(Linear, true),
+                                     DrawOptions::new(self.state.global_alpha, 0));
+    }
+
+    /// Sends back the frame made after the given number of messages, making it if needed.
+    fn send_frame(&self, last_frame: &LastFrame, message_count: uint, chan: Sender<CanvasFrame>) {
+        let mut last_frame = last_frame.lock();
+        match *last_frame {
+            Some((ref frame, frame_message_count)) if frame_message_count == message_count => {
+                chan.send(frame.clone());
+                return
+            }
+            _ => {}
+        }
+        let frame = new_frame(image_from_bgra(self.size.width as uint,
+                                              self.size.height as uint,
+                                              self.snapshot_pixels()));
+        *last_frame = Some((frame.clone(), message_count));
+        chan.send(frame);
+    }
+
+    /// Returns the premultiplied BGRA pixels of the canvas, with the padding that the surface
+    /// may add at the end of each row removed.
+    fn snapshot_pixels(&self) -> Vec<u8> {
+        let row_length = self.size.width as uint * 4;
+        let mut pixels = Vec::with_capacity(row_length * self.size.height as uint);
+        let data_surface = self.drawtarget.snapshot().get_data_surface();
+        let stride = data_surface.stride() as uint;
+        data_surface.with_data(|data| {
+            for y in range(0, self.size.height as uint) {
+                pixels.push_all(data.slice(y * stride, y * stride + row_length));
+            }
+        });
+        pixels
+    }
+
+    /// Reads the unpremultiplied RGBA pixels of the given rectangle. Script has checked that its
+    /// edges and its length in bytes fit in an `i32`; pixels outside the canvas are transparent
+    /// black.
+    fn get_image_data(&self, rect: &Rect<i32>, chan: Sender<Vec<u8>>) {
+        let width = rect.size.width as uint;
+        let mut image_data = Vec::from_elem(width * rect.size.height as uint * 4, 0u8);
+        let canvas_rect = Rect(Point2D(0i32, 0i32), self.size);
+        match rect.intersection(&canvas_rect) {
+            None => {}
+            Some(source_rect) => {
+                let canvas_data = self.snapshot_pixels();
+                let canvas_width = self.size.width as uint;
+                let image_data = image_data.as_mut_slice();
+                for canvas_y in range(source_rect.origin.y, source_rect.max_y()) {
+                    let y = (canvas_y - rect.origin.y) as uint;
+                    for canvas_x in range(source_rect.origin.x, source_rect.max_x()) {
+                        let x = (canvas_x - rect.origin.x) as uint;
+                        let source = (canvas_y as uint * canvas_width + canvas_x as uint) * 4;
+                        let dest = (y * width + x) * 4;
+                        let alpha = canvas_data[source + 3];
+                        image_data[dest + 0] = unpremultiply(canvas_data[source + 2], alpha);
+                        image_data[dest + 1] = unpremultiply(canvas_data[source + 1], alpha);
+                        image_data[dest + 2] = unpremultiply(canvas_data[source + 0], alpha);
+                        image_data[dest + 3] = alpha;
+                    }
+                }
+            }
+        }
+        chan.send(image_data);
+    }
+
+    /// FIXME: This should ignore the clipping region.
+    fn put_image_data(&self, mut image_data: Vec<u8>, rect: &Rect<i32>) {
+        for pixel in image_data.as_mut_slice().chunks_mut(4) {
+            let (red, green, blue, alpha) = (pixel[0], pixel[1], pixel[2], pixel[3]);
+            pixel[0] = premultiply(blue, alpha);
+            pixel[1] = premultiply(green, alpha);
+            pixel[2] = premultiply(red, alpha);
+        }
+
+        let source_surface = self.drawtarget.create_source_surface_from_data(
+            image_data.as_slice(),
+            rect.size,
+            rect.size.width * 4,
+            B8G8R8A8);
+        let source_rect = Rect(Point2D(0., 0.),
+                               Size2D(rect.size.width as AzFloat, rect.size.height as AzFloat));
+        let dest_rect = Rect(Point2D(rect.origin.x as AzFloat, rect.origin.y as AzFloat),
+                             source_rect.size);
+        let mut draw_options = DrawOptions::new(1.0, 0);
+        draw_options.set_composition_op(SourceOp);
+
+        // Image data is not affected by the current transform.
+        self.drawtarget.set_transform(&Matrix2D::identity());
+        self.drawtarget.draw_surface(source_surface,
+                                     dest_rect,
+                                     source_rect,
+                                     DrawSurfaceOptions::new(Linear, true),
+                                     draw_options);
+        self.drawtarget.set_transform(&self.state.transform);
     }
 
     fn create(size: Size2D<i32>) -> DrawTarget {
@@ -72,6 +648,74 @@ impl CanvasRenderTask {
     }
 
     fn recreate(&mut self, size: Size2D<i32>) {
-        self.drawtarget = CanvasRenderTask::create(size);
+        // Setting the size of a canvas resets its whole context.
+        *self = CanvasRenderTask::new(size);
+    }
+}
+
+/// Tiles the given surface over the given rectangle, starting at the origin.
+fn tile_surface(draw_target: &DrawTarget, surface: &SurfaceStyle, rect: &Rect<AzFloat>) {
+    let (width, height) = (surface.surface_size.width as AzFloat,
+                           surface.surface_size.height as AzFloat);
+    if width == 0.0 || height == 0.0 {
+        return
+    }
+
+    let tile_origins = |start: AzFloat, end: AzFloat, size: AzFloat, repeat: bool| {
+        if !repeat {
+            return vec!(0.0)
+        }
+        let mut origins = vec!();
+        let mut origin = (start / size).floor() * size;
+        while origin < end {
+            origins.push(origin);
+            origin = origin + size;
+        }
+        origins
+    };
+
+    let source_rect = Rect(Point2D(0., 0.), Size2D(width, height));
+    for y in tile_origins(rect.origin.y, rect.max_y(), height, surface.repeat_y).iter() {
+        for x in tile_origins(rect.origin.x, rect.max_x(), width, surface.repeat_x).iter() {
+            // Drawing a surface consumes it, so each tile needs its own.
+            let source_surface = draw_target.create_source_surface_from_data(
+                surface.surface_data.as_slice(),
+                surface.surface_size,
+                surface.surface_size.width * 4,
+                B8G8R8A8);
+            draw_target.draw_surface(source_surface,
+                                     Rect(Point2D(*x, *y), source_rect.size),
+                                     source_rect,
+                                     DrawSurfaceOptions::new(Linear, true),
+                                     DrawOptions::new(1.0, 0));
+        }
+    }
+}
+
+fn to_azure_color(color: &RGBA) -> Color {
+    Color::new(color.red as AzFloat,
+               color.green as AzFloat,
+               color.blue as AzFloat,
+               color.alpha as AzFloat)
+}
+
+fn to_gradient_stops(stops: &Vec<CanvasGradientStop>) -> Vec<GradientStop> {
+    stops.iter().map(|stop| {
+        GradientStop {
+            offset: stop.offset as AzFloat,
+            color: to_azure_color(&stop.color),
+        }
+    }).collect()
+}
+
+fn premultiply(value: u8, alpha: u8) -> u8 {
+    ((value as u32 * alpha as u32 + 127) / 255) as u8
+}
+
+fn unpremultiply(value: u8, alpha: u8) -> u8 {
+    if alpha == 0 {
+        0
+    } else {
+        cmp::min((value as u32 * 255 + alpha as u32 / 2) / alpha as u32, 255) as u8
     }
 }
@@ -6,7 +6,11 @@
 #![deny(unused_variables)]
 
 extern crate azure;
+extern crate cssparser;
 extern crate geom;
+extern crate "net" as servo_net;
+extern crate sync;
 extern crate "util" as servo_util;
 
+pub mod canvas_chan;
 pub mod canvas_render_task;
@@ -16,6 +16,7 @@ use layout_traits::{LayoutControlChan, LayoutTaskFactory, ExitNowMsg};
 use libc;
 use script_traits;
 use script_traits::{ResizeMsg, ResizeInactiveMsg, ExitPipelineMsg, SendEventMsg};
+use script_traits::DispatchStorageEventMsg;
 use script_traits::{ScriptControlChan, ScriptTaskFactory};
 use servo_msg::compositor_msg::LayerId;
 use servo_msg::constellation_msg::{ConstellationChan, ExitMsg, FailureMsg, Failure, FrameRectMsg};
@@ -24,11 +25,12 @@ use servo_msg::constellation_msg::{LoadCompleteMsg, LoadUrlMsg, LoadData, Msg, N
 use servo_msg::constellation_msg::{NavigationType, PipelineId, RendererReadyMsg, ResizedWindowMsg};
 use servo_msg::constellation_msg::{ScriptLoadedURLInIFrameMsg, SubpageId, WindowSizeData};
 use servo_msg::constellation_msg::{KeyEvent, Key, KeyState, KeyModifiers};
+use servo_msg::constellation_msg::{BroadcastStorageEventMsg, FrameId};
 use servo_msg::constellation_msg;
 use servo_net::image_cache_task::{ImageCacheTask, ImageCacheTaskClient};
 use servo_net::resource_task::ResourceTask;
 use servo_net::resource_task;
-use servo_net::storage_task::StorageTask;
+use servo_net::storage_task::{StorageTask, StorageType};
 use servo_net::storage_task;
 use servo_util::geometry::{PagePx, ViewportPx};
 use servo_util::opts;
@@ -39,8 +41,13 @@ use std::collections::{HashMap, HashSet};
 use std::io;
 use std::mem::replace;
 use std::rc::Rc;
+use std::sync::atomic::{AtomicUint, INIT_ATOMIC_UINT, SeqCst};
 use url::Url;
 
+/// The source of the IDs given to the top-level browsing contexts of all constellations, which
+/// share the storage task.
+static mut next_frame_id: AtomicUint = INIT_ATOMIC_UINT;
+
 /// Maintains the pipelines and navigation context and grants permission to composite.
 pub struct Constellation<LTF, STF> {
     /// A channel through which messages can be sent to this object.
@@ -76,6 +83,10 @@ pub struct Constellation<LTF, STF> {
     /// The next free ID to assign to a pipeline.
     next_pipeline_id: PipelineId,
 
+    /// The ID of the top-level browsing context this constellation manages, which every
+    /// pipeline it creates belongs to.
+    frame_id: FrameId,
+
     pending_frames: Vec<FrameChange>,
 
     pending_sizes: HashMap<(PipelineId, SubpageId), TypedRect<PagePx, f32>>,
@@ -334,6 +345,7 @@ impl<LTF: LayoutTaskFactory, STF: ScriptTaskFactory> Constellation<LTF, STF> {
                 pipelines: HashMap::new(),
                 navigation_context: NavigationContext::new(),
                 next_pipeline_id: PipelineId(0),
+                frame_id: FrameId(unsafe { next_frame_id.fetch_add(1, SeqCst) }),
                 pending_frames: vec!(),
                 pending_sizes: HashMap::new(),
                 time_profiler_chan: time_profiler_chan,
@@ -366,6 +378,7 @@ impl<LTF: LayoutTaskFactory, STF: ScriptTaskFactory> Constellation<LTF, STF> {
                     -> Rc<Pipeline> {
             let pipe = Pipeline::create::<LTF, STF>(id,
                                                     subpage_id,
+                                                    self.frame_id,
                                                     self.chan.clone(),
                                                     self.compositor_proxy.clone_compositor_proxy(),
                                                     self.devtools_chan.clone(),
@@ -465,6 +478,11 @@ impl<LTF: LayoutTaskFactory, STF: ScriptTaskFactory> Constellation<LTF, STF> {
                 debug!("constellation got key event message");
                 self.handle_key_msg(key, state, modifiers);
             }
+            BroadcastStorageEventMsg(source_id, storage_type, url, key, old_value, new_value) => {
+                debug!("constellation got broadcast storage event message");
+                self.handle_broadcast_storage_event(source_id, storage_type, url, key, old_value,
+                                                    new_value);
+            }
         }
         true
     }
@@ -787,6 +805,24 @@ impl<LTF: LayoutTaskFactory, STF: ScriptTaskFactory> Constellation<LTF, STF> {
         });
     }
 
+    /// Send a storage event to every document being displayed other than the one that made the
+    /// change. Script checks that the documents are of the same origin before firing it.
+    fn handle_broadcast_storage_event(&self, source_id: PipelineId, storage_type: StorageType,
+                                      url: Url, key: Option<String>, old_value: Option<String>,
+                                      new_value: Option<String>) {
+        self.current_frame().as_ref().map(|current_frame| {
+            for frame in current_frame.iter() {
+                if frame.pipeline.id == source_id {
+                    continue;
+                }
+                let ScriptControlChan(ref chan) = frame.pipeline.script_chan;
+                chan.send(DispatchStorageEventMsg(frame.pipeline.id, storage_type, url.clone(),
+                                                  key.clone(), old_value.clone(),
+                                                  new_value.clone()));
+            }
+        });
+    }
+
     fn handle_renderer_ready_msg(&mut self, pipeline_id: PipelineId) {
         debug!("Renderer {} ready to send paint msg", pipeline_id);
         // This message could originate from a pipeline in the navigation context or
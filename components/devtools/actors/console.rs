@@ -10,12 +10,13 @@ use actor::{Actor, ActorRegistry};
 use protocol::JsonPacketStream;
 
 use devtools_traits::{EvaluateJS, NullValue, VoidValue, NumberValue, StringValue, BooleanValue};
-use devtools_traits::{ActorValue, DevtoolScriptControlMsg};
+use devtools_traits::{ActorValue, DevtoolScriptControlMsg, PageError};
 use servo_msg::constellation_msg::PipelineId;
 
-use collections::TreeMap;
+use collections::{RingBuf, TreeMap};
 use serialize::json;
 use serialize::json::ToJson;
+use std::cell::RefCell;
 use std::io::TcpStream;
 
 #[deriving(Encodable)]
@@ -38,7 +39,6 @@ struct ConsoleAPIMessage {
 }
 
 #[deriving(Encodable)]
-#[allow(dead_code)]
 struct PageErrorMessage {
     _type: String, //FIXME: should this be __type__ instead?
     errorMessage: String,
@@ -71,6 +71,14 @@ enum ConsoleMessageType {
     LogMessageType(LogMessage),
 }
 
+/// Sent to the clients listening for page errors when one is reported.
+#[deriving(Encodable)]
+struct PageErrorReply {
+    from: String,
+    __type__: String,
+    pageError: PageErrorMessage,
+}
+
 #[deriving(Encodable)]
 struct GetCachedMessagesReply {
     from: String,
@@ -101,10 +109,80 @@ struct EvaluateJSReply {
     helperResult: json::Json,
 }
 
+/// How many page errors a console keeps for clients that connect later. Older ones are dropped.
+pub const MAX_PAGE_ERRORS: uint = 1000;
+
 pub struct ConsoleActor {
     pub name: String,
     pub pipeline: PipelineId,
     pub script_chan: Sender<DevtoolScriptControlMsg>,
+    /// The last errors reported, which clients get when they connect.
+    pub page_errors: RingBuf<PageError>,
+    /// The connections of the clients that have started listening for page errors.
+    pub page_error_listeners: RefCell<Vec<TcpStream>>,
+}
+
+impl ConsoleActor {
+    fn page_error_message(page_error: &PageError) -> PageErrorMessage {
+        PageErrorMessage {
+            _type: "PageError".to_string(),
+            errorMessage: page_error.errorMessage.clone(),
+            sourceName: page_error.sourceName.clone(),
+            lineText: page_error.lineText.clone(),
+            lineNumber: page_error.lineNumber,
+            columnNumber: page_error.columnNumber,
+            category: page_error.category.clone(),
+            timeStamp: 0,
+            warning: page_error.warning,
+            error: !page_error.warning,
+            exception: false,
+            strict: false,
+            private: false,
+        }
+    }
+
+    /// Keeps the error for clients that connect later, and sends it to the clients listening for
+    /// page errors. The registry has to be locked, as client tasks only write to their
+    /// connections while holding that lock, so that packets never interleave.
+    pub fn add_page_error(&mut self, page_error: PageError) {
+        let reply = PageErrorReply {
+            from: self.name(),
+            __type__: "pageError".to_string(),
+            pageError: ConsoleActor::page_error_message(&page_error),
+        };
+        if self.page_errors.len() == MAX_PAGE_ERRORS {
+            self.page_errors.pop_front();
+        }
+        self.page_errors.push_back(page_error);
+        // Forget the clients that have gone away.
+        self.page_error_listeners.borrow_mut().retain(|stream| {
+            let mut stream = stream.clone();
+            stream.try_write_json_packet(&reply).is_ok()
+        });
+    }
+
+    fn start_listening_for_page_errors(&self, stream: &TcpStream) {
+        self.stop_listening_for_page_errors(stream);
+        self.page_error_listeners.borrow_mut().push(stream.clone());
+    }
+
+    fn stop_listening_for_page_errors(&self, stream: &TcpStream) {
+        let peer = stream.clone().peer_name().ok();
+        self.page_error_listeners.borrow_mut().retain(|listener| {
+            listener.clone().peer_name().ok() != peer
+        });
+    }
+}
+
+/// The names of the listeners that a `startListeners` or `stopListeners` request is about.
+fn requested_listeners(msg: &json::JsonObject) -> Vec<String> {
+    match msg.get(&"listeners".to_string()).and_then(|listeners| listeners.as_list()) {
+        Some(listeners) => {
+            listeners.iter().filter_map(|listener| listener.as_string())
+                     .map(|listener| listener.to_string()).collect()
+        }
+        None => vec!(),
+    }
 }
 
 impl Actor for ConsoleActor {
@@ -120,7 +198,7 @@ impl Actor for ConsoleActor {
         match msg_type.as_slice() {
             "getCachedMessages" => {
                 let types = msg.get(&"messageTypes".to_string()).unwrap().as_list().unwrap();
-                let /*mut*/ messages = vec!();
+                let mut messages = vec!();
                 for msg_type in types.iter() {
                     let msg_type = msg_type.as_string().unwrap();
                     match msg_type.as_slice() {
@@ -129,25 +207,12 @@ impl Actor for ConsoleActor {
                         }
 
                         "PageError" => {
-                            //TODO: make script error reporter pass all reported errors
-                            //      to devtools and cache them for returning here.
-
-                            /*let message = PageErrorMessage {
-                                _type: msg_type.to_string(),
-                                sourceName: "".to_string(),
-                                lineText: "".to_string(),
-                                lineNumber: 0,
-                                columnNumber: 0,
-                                category: "".to_string(),
-                                warning: false,
-                                error: true,
-                                exception: false,
-                                strict: false,
-                                private: false,
-                                timeStamp: 0,
-                                errorMessage: "page error test".to_string(),
-                            };
-                            messages.push(json::from_str(json::encode(&message).as_slice()).unwrap().as_object().unwrap().clone());*/
+                            //TODO: make the script error reporter pass JS errors here too.
+                            for page_error in self.page_errors.iter() {
+                                let message = ConsoleActor::page_error_message(page_error);
+                                let message = json::from_str(json::encode(&message).as_slice());
+                                messages.push(message.unwrap().as_object().unwrap().clone());
+                            }
                         }
 
                         "LogMessage" => {
@@ -173,7 +238,10 @@ impl Actor for ConsoleActor {
             }
 
             "startListeners" => {
-                //TODO: actually implement listener filters that support starting/stopping
+                //TODO: implement the listeners other than PageError
+                if requested_listeners(msg).iter().any(|listener| "PageError" == listener.as_slice()) {
+                    self.start_listening_for_page_errors(stream);
+                }
                 let msg = StartedListenersReply {
                     from: self.name(),
                     nativeConsoleAPI: true,
@@ -188,16 +256,13 @@ impl Actor for ConsoleActor {
             }
 
             "stopListeners" => {
-                //TODO: actually implement listener filters that support starting/stopping
+                let listeners = requested_listeners(msg);
+                if listeners.iter().any(|listener| "PageError" == listener.as_slice()) {
+                    self.stop_listening_for_page_errors(stream);
+                }
                 let msg = StopListenersReply {
                     from: self.name(),
-                    stoppedListeners: msg.get(&"listeners".to_string())
-                                         .unwrap()
-                                         .as_list()
-                                         .unwrap_or(&vec!())
-                                         .iter()
-                                         .map(|listener| listener.as_string().unwrap().to_string())
-                                         .collect(),
+                    stoppedListeners: listeners,
                 };
                 stream.write_json_packet(&msg);
                 true
@@ -286,3 +351,111 @@ impl Actor for ConsoleActor {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{ConsoleActor, MAX_PAGE_ERRORS};
+    use actor::{Actor, ActorRegistry};
+    use protocol::JsonPacketStream;
+
+    use devtools_traits::PageError;
+    use servo_msg::constellation_msg::PipelineId;
+
+    use collections::RingBuf;
+    use serialize::json;
+    use std::cell::RefCell;
+    use std::comm::channel;
+    use std::io::{Acceptor, Listener, TcpListener, TcpStream};
+
+    /// Returns the server and client ends of a local connection.
+    fn connect() -> (TcpStream, TcpStream) {
+        let mut listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.socket_name().unwrap();
+        let mut acceptor = listener.listen().unwrap();
+        let client = TcpStream::connect(address.to_string().as_slice()).unwrap();
+        (acceptor.accept().unwrap(), client)
+    }
+
+    fn request(actor: &ConsoleActor, msg_type: &str, listeners: &str, stream: &mut TcpStream) {
+        let msg = json::from_str(format!("{{\"listeners\": [\"{:s}\"]}}", listeners).as_slice());
+        assert!(actor.handle_message(&ActorRegistry::new(), &msg_type.to_string(),
+                                     msg.unwrap().as_object().unwrap(), stream));
+    }
+
+    fn packet_type(stream: &mut TcpStream) -> String {
+        let packet = stream.read_json_packet().unwrap();
+        let object = packet.as_object().unwrap();
+        match object.get(&"type".to_string()) {
+            Some(packet_type) => packet_type.as_string().unwrap().to_string(),
+            None => "".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_page_errors_reach_listening_clients_only() {
+        let (script_chan, _) = channel();
+        let mut actor = ConsoleActor {
+            name: "console0".to_string(),
+            pipeline: PipelineId(0),
+            script_chan: script_chan,
+            page_errors: RingBuf::new(),
+            page_error_listeners: RefCell::new(vec!()),
+        };
+        let page_error = PageError {
+            category: "CSS Parser".to_string(),
+            errorMessage: "Unsupported property".to_string(),
+            sourceName: "about:blank".to_string(),
+            lineText: "".to_string(),
+            lineNumber: 1,
+            columnNumber: 1,
+            warning: true,
+        };
+
+        let (mut listening_server, mut listening_client) = connect();
+        let (mut other_server, mut other_client) = connect();
+        request(&actor, "startListeners", "PageError", &mut listening_server);
+        assert_eq!(packet_type(&mut listening_client), "".to_string());
+        request(&actor, "startListeners", "ConsoleAPI", &mut other_server);
+        assert_eq!(packet_type(&mut other_client), "".to_string());
+
+        actor.add_page_error(page_error.clone());
+        assert_eq!(packet_type(&mut listening_client), "pageError".to_string());
+
+        // The other client gets the reply to its next request, and no page error before it.
+        request(&actor, "stopListeners", "PageError", &mut listening_server);
+        request(&actor, "stopListeners", "PageError", &mut other_server);
+        assert_eq!(packet_type(&mut listening_client), "".to_string());
+        assert_eq!(packet_type(&mut other_client), "".to_string());
+
+        actor.add_page_error(page_error);
+        assert!(actor.page_error_listeners.borrow().is_empty());
+        assert_eq!(actor.page_errors.len(), 2);
+    }
+
+    #[test]
+    fn test_page_errors_are_capped() {
+        let (script_chan, _) = channel();
+        let mut actor = ConsoleActor {
+            name: "console0".to_string(),
+            pipeline: PipelineId(0),
+            script_chan: script_chan,
+            page_errors: RingBuf::new(),
+            page_error_listeners: RefCell::new(vec!()),
+        };
+        for line in range(0, MAX_PAGE_ERRORS + 10) {
+            actor.add_page_error(PageError {
+                category: "CSS Parser".to_string(),
+                errorMessage: "Unsupported property".to_string(),
+                sourceName: "about:blank".to_string(),
+                lineText: "".to_string(),
+                lineNumber: line,
+                columnNumber: 1,
+                warning: true,
+            });
+        }
+        // The oldest errors were dropped.
+        assert_eq!(actor.page_errors.len(), MAX_PAGE_ERRORS);
+        assert_eq!(actor.page_errors.front().unwrap().lineNumber, 10);
+        assert_eq!(actor.page_errors.back().unwrap().lineNumber, MAX_PAGE_ERRORS + 9);
+    }
+}
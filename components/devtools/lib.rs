@@ -36,10 +36,12 @@ use actors::tab::TabActor;
 use protocol::JsonPacketStream;
 
 use devtools_traits::{ServerExitMsg, DevtoolsControlMsg, NewGlobal, DevtoolScriptControlMsg};
+use devtools_traits::{ReportPageError, PageError};
 use servo_msg::constellation_msg::PipelineId;
 use servo_util::task::spawn_named;
 
 use std::cell::RefCell;
+use std::collections::{HashMap, RingBuf};
 use std::comm;
 use std::comm::{Disconnected, Empty};
 use std::io::{TcpListener, TcpStream};
@@ -87,6 +89,9 @@ fn run_server(receiver: Receiver<DevtoolsControlMsg>, port: u16) {
 
     let mut accepted_connections: Vec<TcpStream> = Vec::new();
 
+    // The name of the console actor of each page.
+    let mut consoles: HashMap<PipelineId, String> = HashMap::new();
+
     /// Process the input from a single devtools client until EOF.
     fn handle_client(actors: Arc<Mutex<ActorRegistry>>, mut stream: TcpStream) {
         println!("connection established to {}", stream.peer_name().unwrap());
@@ -113,6 +118,7 @@ fn run_server(receiver: Receiver<DevtoolsControlMsg>, port: u16) {
     // clients can theoretically connect to multiple globals simultaneously.
     // TODO: move this into the root or tab modules?
     fn handle_new_global(actors: Arc<Mutex<ActorRegistry>>,
+                         consoles: &mut HashMap<PipelineId, String>,
                          pipeline: PipelineId,
                          sender: Sender<DevtoolScriptControlMsg>) {
         let mut actors = actors.lock();
@@ -123,7 +129,10 @@ fn run_server(receiver: Receiver<DevtoolsControlMsg>, port: u16) {
                 name: actors.new_name("console"),
                 script_chan: sender.clone(),
                 pipeline: pipeline,
+                page_errors: RingBuf::new(),
+                page_error_listeners: RefCell::new(vec!()),
             };
+            consoles.insert(pipeline, console.name());
             let inspector = InspectorActor {
                 name: actors.new_name("inspector"),
                 walker: RefCell::new(None),
@@ -151,19 +160,45 @@ fn run_server(receiver: Receiver<DevtoolsControlMsg>, port: u16) {
         actors.register(box inspector);
     }
 
+    /// Hands the error to the console actor of its page, which tells the clients listening for
+    /// page errors. The registry stays locked meanwhile, so the packet cannot interleave with the
+    /// ones the client tasks write.
+    fn handle_page_error(actors: Arc<Mutex<ActorRegistry>>,
+                         console: &str,
+                         page_error: PageError) {
+        let mut actors = actors.lock();
+        actors.find_mut::<ConsoleActor>(console).add_page_error(page_error);
+    }
+
     //TODO: figure out some system that allows us to watch for new connections,
     //      shut down existing ones at arbitrary times, and also watch for messages
     //      from multiple script tasks simultaneously. Polling for new connections
     //      for 300ms and then checking the receiver is not a good compromise
     //      (and makes Servo hang on exit if there's an open connection, no less).
     // accept connections and process them, spawning a new tasks for each one
-    loop {
+    'accept: loop {
         match acceptor.accept() {
             Err(ref e) if e.kind == TimedOut => {
-                match receiver.try_recv() {
-                    Ok(ServerExitMsg) | Err(Disconnected) => break,
-                    Ok(NewGlobal(id, sender)) => handle_new_global(actors.clone(), id, sender),
-                    Err(Empty) => acceptor.set_timeout(Some(POLL_TIMEOUT)),
+                // Handle all the pending messages, since errors can come in bursts.
+                loop {
+                    match receiver.try_recv() {
+                        Ok(ServerExitMsg) | Err(Disconnected) => break 'accept,
+                        Ok(NewGlobal(id, sender)) => {
+                            handle_new_global(actors.clone(), &mut consoles, id, sender)
+                        }
+                        Ok(ReportPageError(id, page_error)) => {
+                            match consoles.get(&id) {
+                                Some(console) => {
+                                    handle_page_error(actors.clone(), console.as_slice(), page_error)
+                                }
+                                None => {}
+                            }
+                        }
+                        Err(Empty) => {
+                            acceptor.set_timeout(Some(POLL_TIMEOUT));
+                            break
+                        }
+                    }
                 }
             }
             Err(_e) => { /* connection failed */ }
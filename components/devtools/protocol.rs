@@ -10,16 +10,24 @@ use std::num;
 
 pub trait JsonPacketStream {
     fn write_json_packet<'a, T: Encodable<json::Encoder<'a>,IoError>>(&mut self, obj: &T);
+    /// Like `write_json_packet`, but lets the caller handle a closed connection.
+    fn try_write_json_packet<'a, T: Encodable<json::Encoder<'a>,IoError>>(&mut self, obj: &T)
+                                                                          -> IoResult<()>;
     fn read_json_packet(&mut self) -> IoResult<json::Json>;
 }
 
 impl JsonPacketStream for TcpStream {
     fn write_json_packet<'a, T: Encodable<json::Encoder<'a>,IoError>>(&mut self, obj: &T) {
+        self.try_write_json_packet(obj).unwrap();
+    }
+
+    fn try_write_json_packet<'a, T: Encodable<json::Encoder<'a>,IoError>>(&mut self, obj: &T)
+                                                                          -> IoResult<()> {
         let s = json::encode(obj).replace("__type__", "type");
         println!("<- {:s}", s);
-        self.write_str(s.len().to_string().as_slice()).unwrap();
-        self.write_u8(':' as u8).unwrap();
-        self.write_str(s.as_slice()).unwrap();
+        try!(self.write_str(s.len().to_string().as_slice()));
+        try!(self.write_u8(':' as u8));
+        self.write_str(s.as_slice())
     }
 
     fn read_json_packet<'a>(&mut self) -> IoResult<json::Json> {
@@ -11,6 +11,7 @@
 #![allow(non_snake_case)]
 
 extern crate "msg" as servo_msg;
+extern crate serialize;
 
 /// This module contains shared types and messages for use by devtools/script.
 /// The traits are here instead of in script so that the devtools crate can be
@@ -25,9 +26,24 @@ pub type DevtoolsControlPort = Receiver<DevtoolScriptControlMsg>;
 /// according to changes in the browser.
 pub enum DevtoolsControlMsg {
     NewGlobal(PipelineId, Sender<DevtoolScriptControlMsg>),
+    /// Show an error in the web console of the given page.
+    ReportPageError(PipelineId, PageError),
     ServerExitMsg
 }
 
+/// An error in a page, such as a CSS parse error. The fields are named as in Firefox's
+/// `nsIScriptError`, which devtools clients expect.
+#[deriving(Clone, Encodable)]
+pub struct PageError {
+    pub category: String,
+    pub errorMessage: String,
+    pub sourceName: String,
+    pub lineText: String,
+    pub lineNumber: uint,
+    pub columnNumber: uint,
+    pub warning: bool,
+}
+
 /// Serialized JS return values
 /// TODO: generalize this beyond the EvaluateJS message?
 pub enum EvaluateJSReply {
@@ -50,7 +50,7 @@ pub mod optimizer;
 /// Because the script task's GC does not trace layout, node data cannot be safely stored in layout
 /// data structures. Also, layout code tends to be faster when the DOM is not being accessed, for
 /// locality reasons. Using `OpaqueNode` enforces this invariant.
-#[deriving(Clone, PartialEq)]
+#[deriving(Clone, PartialEq, Eq, Hash)]
 pub struct OpaqueNode(pub uintptr_t);
 
 impl OpaqueNode {
@@ -148,6 +148,9 @@ pub struct StackingContext {
     pub z_index: i32,
     /// The opacity of this stacking context.
     pub opacity: AzFloat,
+    /// The transform applied to the contents of this stacking context, in its own coordinate
+    /// system (that is, relative to the origin of `bounds`).
+    pub transform: Matrix2D<AzFloat>,
 }
 
 impl StackingContext {
@@ -160,16 +163,46 @@ impl StackingContext {
                bounds: Rect<Au>,
                z_index: i32,
                opacity: AzFloat,
+               transform: Matrix2D<AzFloat>,
                layer: Option<Arc<RenderLayer>>)
                -> StackingContext {
-        StackingContext {
+        let mut stacking_context = StackingContext {
             display_list: display_list,
             layer: layer,
             bounds: bounds,
             clip_rect: bounds,
             z_index: z_index,
             opacity: opacity,
+            transform: transform,
+        };
+        stacking_context.clip_rect = stacking_context.transformed_bounds();
+        stacking_context
+    }
+
+    /// Returns true if this stacking context has a transform other than the identity.
+    #[inline]
+    pub fn is_transformed(&self) -> bool {
+        self.transform != Matrix2D::identity()
+    }
+
+    /// Returns the bounds of this stacking context once its transform has been applied, in the
+    /// coordinate system of the parent stacking context. This is what the parent sees of it, so
+    /// it is what culling must use.
+    pub fn transformed_bounds(&self) -> Rect<Au> {
+        if !self.is_transformed() {
+            return self.bounds
         }
+        let local_bounds = Rect(Point2D(Au(0), Au(0)), self.bounds.size).to_azure_rect();
+        let rect = geometry::transform_rect(&self.transform, &local_bounds);
+        geometry::f32_rect_to_au_rect(rect).translate(&self.bounds.origin)
+    }
+
+    /// Returns the transform that maps this stacking context's coordinate system into that of
+    /// its parent, given the parent's `current_transform`.
+    fn transform_for_child(&self, current_transform: &Matrix2D<AzFloat>) -> Matrix2D<AzFloat> {
+        current_transform.translate(self.bounds.origin.x.to_nearest_px() as AzFloat,
+                                    self.bounds.origin.y.to_nearest_px() as AzFloat)
+                         .mul(&self.transform)
     }
 
     /// Draws the stacking context in the proper order according to the steps in CSS 2.1 § E.2.
@@ -214,11 +247,7 @@ impl StackingContext {
                     break
                 }
                 if positioned_kid.layer.is_none() {
-                    let new_transform =
-                        current_transform.translate(positioned_kid.bounds.origin.x.to_nearest_px()
-                                                        as AzFloat,
-                                                    positioned_kid.bounds.origin.y.to_nearest_px()
-                                                        as AzFloat);
+                    let new_transform = positioned_kid.transform_for_child(current_transform);
                     let new_tile_rect =
                         self.compute_tile_rect_for_child_stacking_context(tile_bounds,
                                                                           &**positioned_kid);
@@ -259,11 +288,7 @@ impl StackingContext {
                 }
 
                 if positioned_kid.layer.is_none() {
-                    let new_transform =
-                        current_transform.translate(positioned_kid.bounds.origin.x.to_nearest_px()
-                                                        as AzFloat,
-                                                    positioned_kid.bounds.origin.y.to_nearest_px()
-                                                        as AzFloat);
+                    let new_transform = positioned_kid.transform_for_child(current_transform);
                     let new_tile_rect =
                         self.compute_tile_rect_for_child_stacking_context(tile_bounds,
                                                                           &**positioned_kid);
@@ -298,12 +323,38 @@ impl StackingContext {
         };
 
         let child_stacking_context_bounds = child_stacking_context.bounds.to_azure_rect();
+        if child_stacking_context.is_transformed() {
+            // Map the tile into the child's coordinate system and take the bounding box of the
+            // result.
+            let tile_bounds = Rect(tile_bounds.origin - child_stacking_context_bounds.origin,
+                                   tile_bounds.size);
+            return match geometry::invert_transform(&child_stacking_context.transform) {
+                Some(inverse) => geometry::transform_rect(&inverse, &tile_bounds),
+                None => ZERO_AZURE_RECT,
+            }
+        }
+
         let tile_subrect = tile_bounds.intersection(&child_stacking_context_bounds)
                                       .unwrap_or(ZERO_AZURE_RECT);
         let offset = tile_subrect.origin - child_stacking_context_bounds.origin;
         Rect(offset, tile_subrect.size)
     }
 
+    /// Maps the given point, in the coordinate system of this stacking context's parent, into
+    /// the coordinate system of this stacking context. Returns `None` if the transform is not
+    /// invertible, in which case nothing in this stacking context can be hit.
+    fn point_in_own_coordinate_system(&self, point: Point2D<Au>) -> Option<Point2D<Au>> {
+        let point = point - self.bounds.origin;
+        if !self.is_transformed() {
+            return Some(point)
+        }
+        geometry::invert_transform(&self.transform).map(|inverse| {
+            let point = Point2D(point.x.to_subpx() as AzFloat, point.y.to_subpx() as AzFloat);
+            let point = geometry::transform_point(&inverse, &point);
+            Point2D(Au::from_frac_px(point.x as f64), Au::from_frac_px(point.y as f64))
+        })
+    }
+
     /// Places all nodes containing the point of interest into `result`, topmost first. If
     /// `topmost_only` is true, stops after placing one node into the list. `result` must be empty
     /// upon entry to this function.
@@ -337,7 +388,10 @@ impl StackingContext {
             if kid.z_index < 0 {
                 continue
             }
-            kid.hit_test(point, result, topmost_only);
+            match kid.point_in_own_coordinate_system(point) {
+                Some(kid_point) => kid.hit_test(kid_point, result, topmost_only),
+                None => {}
+            }
             if topmost_only && !result.is_empty() {
                 return
             }
@@ -362,7 +416,10 @@ impl StackingContext {
             if kid.z_index >= 0 {
                 continue
             }
-            kid.hit_test(point, result, topmost_only);
+            match kid.point_in_own_coordinate_system(point) {
+                Some(kid_point) => kid.hit_test(kid_point, result, topmost_only),
+                None => {}
+            }
             if topmost_only && !result.is_empty() {
                 return
             }
@@ -565,6 +622,9 @@ impl DisplayItem {
                          render_context: &mut RenderContext,
                          current_transform: &Matrix2D<AzFloat>,
                          current_clip_stack: &mut Vec<Rect<Au>>) {
+        // The clip rect is in the same coordinate system as the item, so set the transform first.
+        render_context.draw_target.set_transform(current_transform);
+
         // TODO(pcwalton): This will need some tweaking to deal with more complex clipping regions.
         let clip_rect = &self.base().clip_rect;
         if current_clip_stack.len() == 0 || current_clip_stack.last().unwrap() != clip_rect {
@@ -576,8 +636,6 @@ impl DisplayItem {
             current_clip_stack.push(*clip_rect);
         }
 
-        render_context.draw_target.set_transform(current_transform);
-
         match *self {
             SolidColorDisplayItemClass(ref solid_color) => {
                 render_context.draw_solid_color(&solid_color.base.bounds, solid_color.color)
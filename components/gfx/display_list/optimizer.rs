@@ -58,7 +58,7 @@ impl DisplayListOptimizer {
                                              mut stacking_contexts: I)
                                              where I: Iterator<&'a Arc<StackingContext>> {
         for stacking_context in stacking_contexts {
-            if self.visible_rect.intersects(&stacking_context.bounds) &&
+            if self.visible_rect.intersects(&stacking_context.transformed_bounds()) &&
                     self.visible_rect.intersects(&stacking_context.clip_rect) {
                 result_list.push_back((*stacking_context).clone())
             }
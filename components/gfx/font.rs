@@ -4,19 +4,22 @@
 
 use geom::{Point2D, Rect, Size2D};
 use std::mem;
+use std::slice;
 use std::string;
 use std::rc::Rc;
 use std::cell::RefCell;
 use servo_util::cache::{Cache, HashCache};
 use servo_util::smallvec::{SmallVec, SmallVec8};
-use style::computed_values::{font_variant, font_weight};
+use style::computed_values::{font_kerning, font_variant_caps, font_variant_numeric, font_weight};
 use style::style_structs::Font as FontStyle;
+use style::CodepointRange;
 use sync::Arc;
 
 use servo_util::geometry::Au;
 use platform::font_context::FontContextHandle;
 use platform::font::{FontHandle, FontTable};
 use text::glyph::{GlyphStore, GlyphId};
+use text::script::{Common, Script};
 use text::shaping::ShaperMethods;
 use text::{Shaper, TextRun};
 use font_template::FontTemplateDescriptor;
@@ -48,6 +51,11 @@ pub type FractionalPixel = f64;
 
 pub type FontTableTag = u32;
 
+/// Makes an OpenType tag out of its four characters.
+pub fn font_table_tag(tag: &str) -> FontTableTag {
+    tag.bytes().fold(0, |result, byte| (result << 8) | byte as FontTableTag)
+}
+
 pub trait FontTableTagConversions {
     fn tag_to_str(&self) -> String;
 }
@@ -90,28 +98,100 @@ pub type UsedFontStyle = FontStyle;
 pub struct Font {
     pub handle: FontHandle,
     pub metrics: FontMetrics,
-    pub variant: font_variant::T,
+    pub variant: font_variant_caps::T,
+    /// True if the font has no small capitals of its own, so that they are made by shrinking
+    /// capital letters instead.
+    pub synthesizes_small_caps: bool,
+    /// The OpenType features that select the capitals that `variant` asks for.
+    pub caps_features: Vec<FontFeature>,
+    /// The characters the font is used for, from the `unicode-range` of its `@font-face` rule.
+    /// `None` if it is used for every character.
+    pub unicode_range: Option<Vec<CodepointRange>>,
+    /// True if the font stands in for a web font that is loading and in its block period, so
+    /// that the text laid out with it must not be shown.
+    pub invisible: bool,
     pub descriptor: FontTemplateDescriptor,
     pub requested_pt_size: Au,
     pub actual_pt_size: Au,
     pub shaper: Option<Shaper>,
-    pub shape_cache: HashCache<String, Arc<GlyphStore>>,
+    pub shape_cache: HashCache<ShapeCacheEntry, Arc<GlyphStore>>,
     pub glyph_advance_cache: HashCache<u32, FractionalPixel>,
 }
 
+bitflags! {
+    flags ShapingFlags: u8 {
+        #[doc="Set if the text is entirely whitespace."]
+        const IS_WHITESPACE_SHAPING_FLAG = 0x01,
+        #[doc="Set if the text is to be shaped right-to-left."]
+        const RTL_FLAG = 0x02,
+    }
+}
+
+/// An OpenType feature to shape text with. A value of 0 turns the feature off, and other values
+/// turn it on or pick one of its alternate glyphs.
+#[deriving(Clone, PartialEq, Eq, Hash, Show)]
+pub struct FontFeature {
+    pub tag: FontTableTag,
+    pub value: u32,
+}
+
+impl FontFeature {
+    pub fn new(tag: &str, value: u32) -> FontFeature {
+        FontFeature {
+            tag: font_table_tag(tag),
+            value: value,
+        }
+    }
+}
+
+/// Various options that control text shaping.
+#[deriving(Clone, PartialEq, Eq, Hash)]
+pub struct ShapingOptions {
+    pub flags: ShapingFlags,
+    /// The script of the text, or `Common` if the shaper should guess it.
+    pub script: Script,
+    /// The OpenType features to turn on or off, in order. Later features override earlier ones.
+    pub features: Vec<FontFeature>,
+    /// The BCP 47 language tag of the text, or `None` to use the language of the locale.
+    pub language: Option<String>,
+}
+
+impl ShapingOptions {
+    pub fn new() -> ShapingOptions {
+        ShapingOptions {
+            flags: ShapingFlags::empty(),
+            script: Common,
+            features: vec!(),
+            language: None,
+        }
+    }
+}
+
+/// An entry in the shape cache.
+#[deriving(Clone, PartialEq, Eq, Hash)]
+pub struct ShapeCacheEntry {
+    text: String,
+    options: ShapingOptions,
+}
+
 impl Font {
-    pub fn shape_text(&mut self, text: &str, is_whitespace: bool) -> Arc<GlyphStore> {
+    pub fn shape_text(&mut self, text: &str, options: &ShapingOptions) -> Arc<GlyphStore> {
         self.make_shaper();
         let shaper = &self.shaper;
-        match self.shape_cache.find_equiv(text) {
+        let lookup_key = ShapeCacheEntry {
+            text: text.to_string(),
+            options: options.clone(),
+        };
+        match self.shape_cache.find(&lookup_key) {
             None => {}
-            Some(glyphs) => return (*glyphs).clone(),
+            Some(glyphs) => return glyphs,
         }
 
-        let mut glyphs = GlyphStore::new(text.char_len() as int, is_whitespace);
-        shaper.as_ref().unwrap().shape_text(text, &mut glyphs);
+        let mut glyphs = GlyphStore::new(text.char_len() as int,
+                                         options.flags.contains(IS_WHITESPACE_SHAPING_FLAG));
+        shaper.as_ref().unwrap().shape_text(text, options, &mut glyphs);
         let glyphs = Arc::new(glyphs);
-        self.shape_cache.insert(text.to_string(), glyphs.clone());
+        self.shape_cache.insert(lookup_key, glyphs.clone());
         glyphs
     }
 
@@ -141,14 +221,80 @@ impl Font {
         return result;
     }
 
+    /// Returns true if the font is used for the given character, whether it has a glyph for it
+    /// or not.
+    pub fn covers(&self, codepoint: char) -> bool {
+        match self.unicode_range {
+            Some(ref ranges) => {
+                let codepoint = codepoint as u32;
+                ranges.iter().any(|range| range.start <= codepoint && codepoint <= range.end)
+            }
+            None => true,
+        }
+    }
+
     pub fn glyph_index(&self, codepoint: char) -> Option<GlyphId> {
-        let codepoint = match self.variant {
-            font_variant::small_caps => codepoint.to_uppercase(),
-            font_variant::normal => codepoint,
+        let codepoint = if self.synthesizes_small_caps {
+            codepoint.to_uppercase()
+        } else {
+            codepoint
         };
         self.handle.glyph_index(codepoint)
     }
 
+    /// Returns the OpenType features to shape text in the given style with in this font.
+    pub fn shaping_features(&self, style: &UsedFontStyle) -> Vec<FontFeature> {
+        let mut features = vec!();
+        match style.font_kerning {
+            font_kerning::auto => {}
+            font_kerning::normal => features.push(FontFeature::new("kern", 1)),
+            font_kerning::none => features.push(FontFeature::new("kern", 0)),
+        }
+
+        let ligatures = &style.font_variant_ligatures;
+        push_switched_features(&mut features, &["liga", "clig"], ligatures.common_ligatures);
+        push_switched_features(&mut features, &["dlig"], ligatures.discretionary_ligatures);
+        push_switched_features(&mut features, &["hlig"], ligatures.historical_ligatures);
+        push_switched_features(&mut features, &["calt"], ligatures.contextual_alternates);
+
+        let numeric = &style.font_variant_numeric;
+        match numeric.figure_style {
+            Some(font_variant_numeric::LiningNums) => features.push(FontFeature::new("lnum", 1)),
+            Some(font_variant_numeric::OldstyleNums) => features.push(FontFeature::new("onum", 1)),
+            None => {}
+        }
+        match numeric.figure_spacing {
+            Some(font_variant_numeric::ProportionalNums) => {
+                features.push(FontFeature::new("pnum", 1))
+            }
+            Some(font_variant_numeric::TabularNums) => features.push(FontFeature::new("tnum", 1)),
+            None => {}
+        }
+        match numeric.fractions {
+            Some(font_variant_numeric::DiagonalFractions) => {
+                features.push(FontFeature::new("frac", 1))
+            }
+            Some(font_variant_numeric::StackedFractions) => {
+                features.push(FontFeature::new("afrc", 1))
+            }
+            None => {}
+        }
+        if numeric.ordinal {
+            features.push(FontFeature::new("ordn", 1))
+        }
+        if numeric.slashed_zero {
+            features.push(FontFeature::new("zero", 1))
+        }
+
+        features.push_all(self.caps_features.as_slice());
+
+        // `font-feature-settings` comes last so that it overrides everything else.
+        for &(ref tag, value) in style.font_feature_settings.iter() {
+            features.push(FontFeature::new(tag.as_slice(), value))
+        }
+        features
+    }
+
     pub fn glyph_h_kerning(&mut self, first_glyph: GlyphId, second_glyph: GlyphId) -> FractionalPixel {
         self.handle.glyph_h_kerning(first_glyph, second_glyph)
     }
@@ -164,6 +310,86 @@ impl Font {
     }
 }
 
+fn push_switched_features(features: &mut Vec<FontFeature>, tags: &[&str], setting: Option<bool>) {
+    match setting {
+        Some(on) => {
+            for tag in tags.iter() {
+                features.push(FontFeature::new(*tag, if on { 1 } else { 0 }))
+            }
+        }
+        None => {}
+    }
+}
+
+/// Returns the OpenType features that select the capitals that the given `font-variant-caps`
+/// value asks for in the font, or `None` if the font has no small capitals and they must be
+/// synthesized.
+pub fn caps_features_for_font(handle: &FontHandle, variant: font_variant_caps::T)
+                              -> Option<Vec<FontFeature>> {
+    let tags = match variant {
+        font_variant_caps::normal => return Some(vec!()),
+        // The font uses these if it has them, and nothing is synthesized if it doesn't.
+        font_variant_caps::unicase => return Some(vec!(FontFeature::new("unic", 1))),
+        font_variant_caps::titling_caps => return Some(vec!(FontFeature::new("titl", 1))),
+        font_variant_caps::small_caps => vec!("smcp"),
+        font_variant_caps::all_small_caps => vec!("smcp", "c2sc"),
+        font_variant_caps::petite_caps => vec!("pcap"),
+        font_variant_caps::all_petite_caps => vec!("pcap", "c2pc"),
+    };
+
+    let available_tags = glyph_substitution_features(handle);
+    let has_feature = |tag: &str| available_tags.contains(&font_table_tag(tag));
+    let tags = if has_feature(tags[0]) {
+        tags
+    } else if tags[0] == "pcap" && has_feature("smcp") {
+        // Petite capitals fall back to small capitals.
+        tags.iter().map(|&tag| if tag == "pcap" { "smcp" } else { "c2sc" }).collect()
+    } else {
+        return None
+    };
+    Some(tags.iter().map(|&tag| FontFeature::new(tag, 1)).collect())
+}
+
+/// Returns the tags of the features in the font's glyph substitution (`GSUB`) table.
+fn glyph_substitution_features(handle: &FontHandle) -> Vec<FontTableTag> {
+    let table = match handle.get_table_for_tag(font_table_tag("GSUB")) {
+        Some(table) => table,
+        None => return vec!(),
+    };
+
+    let mut tags = vec!();
+    table.with_buffer(|data, length| {
+        unsafe {
+            slice::raw::buf_as_slice(data, length, |data| {
+                let read_u16 = |offset: uint| {
+                    (data[offset] as uint << 8) | data[offset + 1] as uint
+                };
+
+                // The header has the offset of the feature list, which starts with the number of
+                // feature records. Each record is a tag and a 16-bit offset.
+                if data.len() < 10 {
+                    return
+                }
+                let feature_list = read_u16(6);
+                if data.len() < feature_list + 2 {
+                    return
+                }
+                let feature_count = read_u16(feature_list);
+                for i in range(0, feature_count) {
+                    let record = feature_list + 2 + i * 6;
+                    if data.len() < record + 6 {
+                        return
+                    }
+                    tags.push(data.slice(record, record + 4).iter().fold(0, |tag, &byte| {
+                        (tag << 8) | byte as FontTableTag
+                    }))
+                }
+            })
+        }
+    });
+    tags
+}
+
 pub struct FontGroup {
     pub fonts: SmallVec8<Rc<RefCell<Font>>>,
 }
@@ -175,11 +401,12 @@ impl FontGroup {
         }
     }
 
-    pub fn create_textrun(&self, text: String) -> TextRun {
+    pub fn create_textrun(&self, text: String, options: &ShapingOptions, bidi_level: u8)
+                          -> TextRun {
         assert!(self.fonts.len() > 0);
 
         // TODO(Issue #177): Actually fall back through the FontGroup when a font is unsuitable.
-        TextRun::new(&mut *self.fonts.get(0).borrow_mut(), text.clone())
+        TextRun::new(&mut *self.fonts.get(0).borrow_mut(), text.clone(), options, bidi_level)
     }
 }
 
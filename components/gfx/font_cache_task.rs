@@ -3,20 +3,31 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 use platform::font_list::get_available_families;
+use platform::font_list::get_fallback_font_families;
 use platform::font_list::get_system_default_family;
 use platform::font_list::get_variations_for_family;
 use platform::font_list::get_last_resort_font_families;
 use platform::font_context::FontContextHandle;
+use platform::font::FontHandle;
 
 use collections::str::Str;
+use font::FontHandleMethods;
+use std::ascii::AsciiExt;
 use std::collections::HashMap;
+use std::io::timer;
+use std::time::duration::Duration;
 use sync::Arc;
 use font_template::{FontTemplate, FontTemplateDescriptor};
 use platform::font_template::FontTemplateData;
+use servo_net::mime_classifier::classify_font;
 use servo_net::resource_task::{ResourceTask, load_whole_resource};
 use servo_util::task::spawn_named;
 use servo_util::str::LowercaseString;
-use style::{Source, LocalSource, UrlSource_};
+use style::{CodepointRange, FontFaceRule, Source, LocalSource, UrlSource_};
+use style::{FontDisplay, AutoFontDisplay, BlockFontDisplay, SwapFontDisplay};
+use style::{FallbackFontDisplay, OptionalFontDisplay};
+use time::precise_time_ns;
+use woff::sfnt_data_for_web_font;
 
 /// A list of font templates that make up a given font family.
 struct FontFamily {
@@ -71,11 +82,170 @@ impl FontFamily {
     }
 }
 
+/// The state of the font of an `@font-face` rule.
+#[deriving(PartialEq)]
+enum WebFontState {
+    LoadingWebFont,
+    LoadedWebFont,
+    FailedWebFont,
+}
+
+/// The font of an `@font-face` rule.
+struct WebFontFace {
+    rule: FontFaceRule,
+    state: WebFontState,
+    /// When the font started loading, in nanoseconds.
+    load_start: u64,
+    /// The templates of the font once it has loaded: one for a downloaded font, and one per
+    /// variation of the family for a `local()` one.
+    templates: Vec<FontTemplate>,
+    /// The layout tasks waiting for the font to load.
+    responders: Vec<Box<WebFontResponder + Send>>,
+}
+
+impl WebFontFace {
+    fn elapsed_ms(&self) -> u64 {
+        (precise_time_ns() - self.load_start) / 1000000
+    }
+
+    /// Returns true if text in this font is to be laid out in a fallback font but not shown, as
+    /// the font is still loading and in its block period.
+    fn is_blocked(&self) -> bool {
+        let (block_period, _) = font_display_periods(self.rule.display);
+        self.state == LoadingWebFont && self.elapsed_ms() < block_period
+    }
+
+    /// Returns true if the font is still to be used if it finishes loading now.
+    fn can_swap(&self) -> bool {
+        match font_display_periods(self.rule.display) {
+            (_, None) => true,
+            (block_period, Some(swap_period)) => self.elapsed_ms() < block_period + swap_period,
+        }
+    }
+
+    fn notify(&self, event: WebFontEvent) {
+        for responder in self.responders.iter() {
+            responder.respond(event);
+        }
+    }
+}
+
+/// Returns the block and swap periods of a `font-display` value, in milliseconds: how long text
+/// is invisible while its font loads, and then how long it is shown in a fallback font before
+/// the font is given up on, if ever.
+fn font_display_periods(display: FontDisplay) -> (u64, Option<u64>) {
+    match display {
+        AutoFontDisplay | BlockFontDisplay => (3000, None),
+        SwapFontDisplay => (0, None),
+        FallbackFontDisplay => (100, Some(3000)),
+        OptionalFontDisplay => (100, Some(0)),
+    }
+}
+
+/// Returns true if fonts with the given `format()` hint can be used. WOFF2 fonts can only be
+/// decoded when the `woff2` feature is enabled.
+fn is_supported_format(hint: &str) -> bool {
+    match hint.to_ascii_lower().as_slice() {
+        "truetype" | "opentype" | "woff" => true,
+        "woff2" => cfg!(feature = "woff2"),
+        _ => false,
+    }
+}
+
+/// Loads the font of an `@font-face` rule from the first of its sources that works. Returns the
+/// identifiers and data of its templates, or nothing if no source works.
+fn load_web_font(resource_task: &ResourceTask, sources: &[Source])
+                 -> Vec<(String, Option<Vec<u8>>)> {
+    let font_context = FontContextHandle::new();
+    for source in sources.iter() {
+        match *source {
+            UrlSource_(ref url_source) => {
+                let url = &url_source.url;
+                if !url_source.format_hints.is_empty() &&
+                        !url_source.format_hints.iter().any(|hint| {
+                            is_supported_format(hint.as_slice())
+                        }) {
+                    debug!("Skipping web font in unsupported format: url={}", url);
+                    continue
+                }
+                let bytes = match load_whole_resource(resource_task, url.clone()) {
+                    Ok((_, bytes)) => bytes,
+                    Err(_) => {
+                        debug!("Failed to load web font: url={}", url);
+                        continue
+                    }
+                };
+                // Whatever the server says the resource is, only data that looks like a font is
+                // handed to the font decoders.
+                if classify_font(bytes.as_slice()).is_none() {
+                    debug!("Web font does not match any font type pattern: url={}", url);
+                    continue
+                }
+                let bytes = match sfnt_data_for_web_font(bytes) {
+                    Ok(bytes) => bytes,
+                    Err(()) => {
+                        debug!("Failed to decode web font: url={}", url);
+                        continue
+                    }
+                };
+                let identifier = url.to_string();
+                let template = Arc::new(FontTemplateData::new(identifier.as_slice(),
+                                                              Some(bytes.clone())));
+                let handle: Result<FontHandle, ()> =
+                    FontHandleMethods::new_from_template(&font_context, template, None);
+                match handle {
+                    Ok(_) => return vec!((identifier, Some(bytes))),
+                    Err(()) => debug!("Web font is not a valid font: url={}", url),
+                }
+            }
+            LocalSource(ref family_name) => {
+                let mut templates = vec!();
+                get_variations_for_family(family_name.as_slice(), |path| {
+                    templates.push((path, None));
+                });
+                if !templates.is_empty() {
+                    return templates
+                }
+            }
+        }
+    }
+    vec!()
+}
+
+/// What happened to a web font that a layout task is waiting for.
+pub enum WebFontEvent {
+    /// The font finished loading, or failed to.
+    WebFontLoadFinishedEvent,
+    /// The block period of the font ended while it was still loading, so text in it is now to be
+    /// shown in a fallback font.
+    WebFontBlockPeriodEndedEvent,
+}
+
+/// Receives the changes in the state of the web fonts that a layout task added, each of which
+/// changes the fonts that text is to be laid out with.
+pub trait WebFontResponder {
+    fn respond(&self, event: WebFontEvent);
+}
+
+/// A font of a family that matches a style.
+#[deriving(Clone)]
+pub struct FamilyFontTemplate {
+    pub template: Arc<FontTemplateData>,
+    /// The characters the font is used for, from the `unicode-range` of its `@font-face` rule,
+    /// or `None` if it is used for every character.
+    pub unicode_range: Option<Vec<CodepointRange>>,
+    /// True if the font stands in for a web font that is loading and in its block period.
+    pub invisible: bool,
+}
+
 /// Commands that the FontContext sends to the font cache task.
 pub enum Command {
-    GetFontTemplate(String, FontTemplateDescriptor, Sender<Reply>),
+    GetFamilyFontTemplates(String, FontTemplateDescriptor, Sender<Vec<FamilyFontTemplate>>),
     GetLastResortFontTemplate(FontTemplateDescriptor, Sender<Reply>),
-    AddWebFont(String, Source, Sender<()>),
+    GetFallbackFontTemplate(char, FontTemplateDescriptor, Sender<Reply>),
+    AddWebFont(FontFaceRule, Box<WebFontResponder + Send>),
+    WebFontLoaded(uint, Vec<(String, Option<Vec<u8>>)>),
+    WebFontBlockPeriodEnded(uint),
     Exit(Sender<()>),
 }
 
@@ -88,9 +258,13 @@ pub enum Reply {
 /// font templates that are currently in use.
 struct FontCache {
     port: Receiver<Command>,
+    /// A channel to ourself, for the tasks loading web fonts to report back on.
+    chan: Sender<Command>,
     generic_fonts: HashMap<LowercaseString, LowercaseString>,
     local_families: HashMap<LowercaseString, FontFamily>,
-    web_families: HashMap<LowercaseString, FontFamily>,
+    /// The faces of each web font family, as indices into `web_faces`.
+    web_families: HashMap<LowercaseString, Vec<uint>>,
+    web_faces: Vec<WebFontFace>,
     font_context: FontContextHandle,
     resource_task: ResourceTask,
 }
@@ -111,45 +285,24 @@ impl FontCache {
             let msg = self.port.recv();
 
             match msg {
-                GetFontTemplate(family, descriptor, result) => {
+                GetFamilyFontTemplates(family, descriptor, result) => {
                     let family = LowercaseString::new(family.as_slice());
-                    let maybe_font_template = self.get_font_template(&family, &descriptor);
-                    result.send(GetFontTemplateReply(maybe_font_template));
+                    result.send(self.get_family_font_templates(&family, &descriptor));
                 }
                 GetLastResortFontTemplate(descriptor, result) => {
                     let font_template = self.get_last_resort_font_template(&descriptor);
                     result.send(GetFontTemplateReply(Some(font_template)));
                 }
-                AddWebFont(family_name, src, result) => {
-                    let family_name = LowercaseString::new(family_name.as_slice());
-                    if !self.web_families.contains_key(&family_name) {
-                        let family = FontFamily::new();
-                        self.web_families.insert(family_name.clone(), family);
-                    }
-
-                    match src {
-                        UrlSource_(ref url_source) => {
-                            let url = &url_source.url;
-                            let maybe_resource = load_whole_resource(&self.resource_task, url.clone());
-                            match maybe_resource {
-                                Ok((_, bytes)) => {
-                                    let family = &mut self.web_families[family_name];
-                                    family.add_template(url.to_string().as_slice(), Some(bytes));
-                                },
-                                Err(_) => {
-                                    debug!("Failed to load web font: family={} url={}", family_name, url);
-                                }
-                            }
-                        }
-                        LocalSource(ref local_family_name) => {
-                            let family = &mut self.web_families[family_name];
-                            get_variations_for_family(local_family_name.as_slice(), |path| {
-                                family.add_template(path.as_slice(), None);
-                            });
-                        }
-                    }
-                    result.send(());
+                GetFallbackFontTemplate(codepoint, descriptor, result) => {
+                    let maybe_font_template = self.get_fallback_font_template(codepoint,
+                                                                              &descriptor);
+                    result.send(GetFontTemplateReply(maybe_font_template));
+                }
+                AddWebFont(rule, responder) => self.add_web_font(rule, responder),
+                WebFontLoaded(face_id, templates) => {
+                    self.finish_loading_web_font(face_id, templates)
                 }
+                WebFontBlockPeriodEnded(face_id) => self.end_block_period(face_id),
                 Exit(result) => {
                     result.send(());
                     break;
@@ -190,8 +343,6 @@ impl FontCache {
                 });
             }
 
-            // TODO(Issue #192: handle generic font families, like 'serif' and 'sans-serif'.
-            // if such family exists, try to match style to a font
             let result = s.find_font_for_style(desc, &self.font_context);
             if result.is_some() {
                 return result;
@@ -204,25 +355,161 @@ impl FontCache {
         }
     }
 
-    fn find_font_in_web_family<'a>(&'a mut self, family_name: &LowercaseString, desc: &FontTemplateDescriptor)
-                                -> Option<Arc<FontTemplateData>> {
-        if self.web_families.contains_key(family_name) {
-            let family = &mut self.web_families[*family_name];
-            let maybe_font = family.find_font_for_style(desc, &self.font_context);
-            maybe_font
-        } else {
-            None
+    /// Returns the usable fonts of a web family that match a style, the most recently defined
+    /// first, so that they take precedence for the characters they are used for.
+    fn find_fonts_in_web_family(&mut self, family_name: &LowercaseString,
+                                desc: &FontTemplateDescriptor) -> Vec<FamilyFontTemplate> {
+        let face_ids = match self.web_families.get(family_name) {
+            Some(face_ids) => face_ids.clone(),
+            None => return vec!(),
+        };
+
+        let mut fonts = vec!();
+        for &face_id in face_ids.iter().rev() {
+            if self.web_faces[face_id].is_blocked() {
+                let template = self.get_last_resort_font_template(desc);
+                fonts.push(FamilyFontTemplate {
+                    template: template,
+                    unicode_range: Some(self.web_faces[face_id].rule.unicode_range.clone()),
+                    invisible: true,
+                });
+                continue
+            }
+
+            let face = &mut self.web_faces[face_id];
+            for template in face.templates.iter_mut() {
+                match template.get_if_matches(&self.font_context, desc) {
+                    Some(template) => {
+                        fonts.push(FamilyFontTemplate {
+                            template: template,
+                            unicode_range: Some(face.rule.unicode_range.clone()),
+                            invisible: false,
+                        });
+                        break
+                    }
+                    None => {}
+                }
+            }
+        }
+
+        // As for local families, pick the first valid font in the family if none matches the
+        // descriptor.
+        if fonts.is_empty() {
+            for &face_id in face_ids.iter().rev() {
+                let face = &mut self.web_faces[face_id];
+                for template in face.templates.iter_mut() {
+                    match template.get() {
+                        Some(template) => {
+                            return vec!(FamilyFontTemplate {
+                                template: template,
+                                unicode_range: Some(face.rule.unicode_range.clone()),
+                                invisible: false,
+                            })
+                        }
+                        None => {}
+                    }
+                }
+            }
         }
+        fonts
     }
 
-    fn get_font_template(&mut self, family: &LowercaseString, desc: &FontTemplateDescriptor)
-                            -> Option<Arc<FontTemplateData>> {
+    fn get_family_font_templates(&mut self, family: &LowercaseString,
+                                 desc: &FontTemplateDescriptor) -> Vec<FamilyFontTemplate> {
         let transformed_family_name = self.transform_family(family);
-        let mut maybe_template = self.find_font_in_web_family(&transformed_family_name, desc);
-        if maybe_template.is_none() {
-            maybe_template = self.find_font_in_local_family(&transformed_family_name, desc);
+        let fonts = self.find_fonts_in_web_family(&transformed_family_name, desc);
+        if !fonts.is_empty() {
+            return fonts
+        }
+        match self.find_font_in_local_family(&transformed_family_name, desc) {
+            Some(template) => {
+                vec!(FamilyFontTemplate {
+                    template: template,
+                    unicode_range: None,
+                    invisible: false,
+                })
+            }
+            None => vec!(),
+        }
+    }
+
+    /// Starts loading the font of an `@font-face` rule. The responder is told when the font has
+    /// loaded, and when its block period ends if it hasn't by then.
+    fn add_web_font(&mut self, rule: FontFaceRule, responder: Box<WebFontResponder + Send>) {
+        // The same rule is added again whenever the stylesheets of a document change, and by
+        // every document using it.
+        match self.web_faces.iter().position(|face| face.rule == rule) {
+            Some(face_id) => {
+                let face = &mut self.web_faces[face_id];
+                if face.state == LoadingWebFont {
+                    face.responders.push(responder);
+                } else {
+                    responder.respond(WebFontLoadFinishedEvent);
+                }
+                return
+            }
+            None => {}
         }
-        maybe_template
+
+        let face_id = self.web_faces.len();
+        let family_name = LowercaseString::new(rule.family.as_slice());
+        if !self.web_families.contains_key(&family_name) {
+            self.web_families.insert(family_name.clone(), vec!());
+        }
+        self.web_families[family_name].push(face_id);
+
+        let chan = self.chan.clone();
+        let resource_task = self.resource_task.clone();
+        let sources = rule.sources.clone();
+        spawn_named("WebFontLoader", proc() {
+            let templates = load_web_font(&resource_task, sources.as_slice());
+            drop(chan.send_opt(WebFontLoaded(face_id, templates)))
+        });
+
+        let (block_period, _) = font_display_periods(rule.display);
+        if block_period > 0 {
+            let chan = self.chan.clone();
+            spawn_named("WebFontBlockPeriod", proc() {
+                timer::sleep(Duration::milliseconds(block_period as i64));
+                drop(chan.send_opt(WebFontBlockPeriodEnded(face_id)))
+            });
+        }
+
+        self.web_faces.push(WebFontFace {
+            rule: rule,
+            state: LoadingWebFont,
+            load_start: precise_time_ns(),
+            templates: vec!(),
+            responders: vec!(responder),
+        });
+    }
+
+    fn finish_loading_web_font(&mut self, face_id: uint,
+                               templates: Vec<(String, Option<Vec<u8>>)>) {
+        let face = &mut self.web_faces[face_id];
+        if templates.is_empty() {
+            debug!("Failed to load web font: family={}", face.rule.family);
+            face.state = FailedWebFont;
+        } else if !face.can_swap() {
+            debug!("Web font loaded after its swap period: family={}", face.rule.family);
+            face.state = FailedWebFont;
+        } else {
+            for (identifier, maybe_data) in templates.into_iter() {
+                face.templates.push(FontTemplate::new(identifier.as_slice(), maybe_data));
+            }
+            face.state = LoadedWebFont;
+        }
+
+        face.notify(WebFontLoadFinishedEvent);
+        face.responders.clear();
+    }
+
+    fn end_block_period(&mut self, face_id: uint) {
+        let face = &self.web_faces[face_id];
+        if face.state != LoadingWebFont {
+            return
+        }
+        face.notify(WebFontBlockPeriodEndedEvent);
     }
 
     fn get_last_resort_font_template(&mut self, desc: &FontTemplateDescriptor)
@@ -239,6 +526,35 @@ impl FontCache {
 
         panic!("Unable to find any fonts that match (do you have fallback fonts installed?)");
     }
+
+    /// Finds a local font with a glyph for the given character, for characters that none of the
+    /// fonts a page asks for can render.
+    fn get_fallback_font_template(&mut self, codepoint: char, desc: &FontTemplateDescriptor)
+                                  -> Option<Arc<FontTemplateData>> {
+        for family in get_fallback_font_families(codepoint).iter() {
+            let family = LowercaseString::new(family.as_slice());
+            match self.find_font_in_local_family(&family, desc) {
+                Some(template) => {
+                    if self.template_has_glyph(template.clone(), codepoint) {
+                        return Some(template)
+                    }
+                }
+                None => {}
+            }
+        }
+
+        debug!("FontList: Couldn't find a fallback font for {}", codepoint);
+        None
+    }
+
+    fn template_has_glyph(&self, template: Arc<FontTemplateData>, codepoint: char) -> bool {
+        let handle: Result<FontHandle, ()> =
+            FontHandleMethods::new_from_template(&self.font_context, template, None);
+        match handle {
+            Ok(handle) => handle.glyph_index(codepoint).is_some(),
+            Err(()) => false,
+        }
+    }
 }
 
 /// The public interface to the font cache task, used exclusively by
@@ -251,6 +567,7 @@ pub struct FontCacheTask {
 impl FontCacheTask {
     pub fn new(resource_task: ResourceTask) -> FontCacheTask {
         let (chan, port) = channel();
+        let cache_chan = chan.clone();
 
         spawn_named("FontCacheTask", proc() {
             // TODO: Allow users to specify these.
@@ -263,9 +580,11 @@ impl FontCacheTask {
 
             let mut cache = FontCache {
                 port: port,
+                chan: cache_chan,
                 generic_fonts: generic_fonts,
                 local_families: HashMap::new(),
                 web_families: HashMap::new(),
+                web_faces: vec!(),
                 font_context: FontContextHandle::new(),
                 resource_task: resource_task,
             };
@@ -279,40 +598,48 @@ impl FontCacheTask {
         }
     }
 
-    pub fn get_font_template(&self, family: String, desc: FontTemplateDescriptor)
-                                                -> Option<Arc<FontTemplateData>> {
+    /// Returns the fonts of a family that match a style, in the order they are to be tried.
+    pub fn get_family_font_templates(&self, family: String, desc: FontTemplateDescriptor)
+                                     -> Vec<FamilyFontTemplate> {
+        let (response_chan, response_port) = channel();
+        self.chan.send(GetFamilyFontTemplates(family, desc, response_chan));
+        response_port.recv()
+    }
+
+    pub fn get_last_resort_font_template(&self, desc: FontTemplateDescriptor)
+                                                -> Arc<FontTemplateData> {
 
         let (response_chan, response_port) = channel();
-        self.chan.send(GetFontTemplate(family, desc, response_chan));
+        self.chan.send(GetLastResortFontTemplate(desc, response_chan));
 
         let reply = response_port.recv();
 
         match reply {
             GetFontTemplateReply(data) => {
-                data
+                data.unwrap()
             }
         }
     }
 
-    pub fn get_last_resort_font_template(&self, desc: FontTemplateDescriptor)
-                                                -> Arc<FontTemplateData> {
+    pub fn get_fallback_font_template(&self, codepoint: char, desc: FontTemplateDescriptor)
+                                      -> Option<Arc<FontTemplateData>> {
 
         let (response_chan, response_port) = channel();
-        self.chan.send(GetLastResortFontTemplate(desc, response_chan));
+        self.chan.send(GetFallbackFontTemplate(codepoint, desc, response_chan));
 
         let reply = response_port.recv();
 
         match reply {
             GetFontTemplateReply(data) => {
-                data.unwrap()
+                data
             }
         }
     }
 
-    pub fn add_web_font(&self, family: String, src: Source) {
-        let (response_chan, response_port) = channel();
-        self.chan.send(AddWebFont(family, src, response_chan));
-        response_port.recv();
+    /// Starts loading the font of an `@font-face` rule, without waiting for it. Only the given
+    /// responder is told when the font loads or its block period ends.
+    pub fn add_web_font(&self, rule: FontFaceRule, responder: Box<WebFontResponder + Send>) {
+        self.chan.send(AddWebFont(rule, responder));
     }
 
     pub fn exit(&self) {
@@ -2,12 +2,12 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use font::{Font, FontGroup};
+use font::{Font, FontGroup, caps_features_for_font};
 use font::SpecifiedFontStyle;
 use platform::font_context::FontContextHandle;
-use style::computed_values::{font_style, font_variant};
+use style::computed_values::{font_style, font_variant_caps};
 
-use font_cache_task::FontCacheTask;
+use font_cache_task::{FamilyFontTemplate, FontCacheTask};
 use font_template::FontTemplateDescriptor;
 use platform::font_template::FontTemplateData;
 use font::FontHandleMethods;
@@ -17,6 +17,7 @@ use servo_util::smallvec::{SmallVec, SmallVec8};
 use servo_util::geometry::Au;
 use servo_util::arc_ptr_eq;
 
+use std::collections::HashSet;
 use std::rc::Rc;
 use std::cell::RefCell;
 use sync::Arc;
@@ -41,9 +42,13 @@ fn create_scaled_font(template: &Arc<FontTemplateData>, pt_size: Au) -> ScaledFo
 
 static SMALL_CAPS_SCALE_FACTOR: f64 = 0.8;      // Matches FireFox (see gfxFont.h)
 
+/// The fonts of a family for a style, which are empty if the family has no font.
 struct LayoutFontCacheEntry {
     family: String,
-    font: Option<Rc<RefCell<Font>>>,
+    descriptor: FontTemplateDescriptor,
+    pt_size: Au,
+    variant: font_variant_caps::T,
+    fonts: Vec<Rc<RefCell<Font>>>,
 }
 
 struct FallbackFontCacheEntry {
@@ -69,6 +74,8 @@ pub struct FontContext {
     /// TODO: See bug https://github.com/servo/servo/issues/3300.
     layout_font_cache: Vec<LayoutFontCacheEntry>,
     fallback_font_cache: Vec<FallbackFontCacheEntry>,
+    /// Characters that no system font has a glyph for, so that they aren't looked for again.
+    chars_without_fallback_font: HashSet<char>,
 
     /// Strong reference as the render FontContext is (for now) recycled
     /// per frame. TODO: Make this weak when incremental redraw is done.
@@ -76,6 +83,10 @@ pub struct FontContext {
 
     last_style: Option<Arc<SpecifiedFontStyle>>,
     last_fontgroup: Option<Rc<FontGroup>>,
+
+    /// The generation of the web fonts of the document that the cached layout fonts were looked
+    /// up with.
+    web_font_generation: uint,
 }
 
 impl FontContext {
@@ -83,41 +94,75 @@ impl FontContext {
         let handle = FontContextHandle::new();
         FontContext {
             platform_handle: handle,
-            font_cache_task: font_cache_task,
             layout_font_cache: vec!(),
             fallback_font_cache: vec!(),
+            chars_without_fallback_font: HashSet::new(),
             render_font_cache: vec!(),
             last_style: None,
             last_fontgroup: None,
+            web_font_generation: 0,
+            font_cache_task: font_cache_task,
         }
     }
 
     /// Create a font for use in layout calculations.
     fn create_layout_font(&self, template: Arc<FontTemplateData>,
                             descriptor: FontTemplateDescriptor, pt_size: Au,
-                            variant: font_variant::T) -> Font {
-        // TODO: (Bug #3463): Currently we only support fake small-caps
-        // rendering. We should also support true small-caps (where the
-        // font supports it) in the future.
-        let actual_pt_size = match variant {
-            font_variant::small_caps => pt_size.scale_by(SMALL_CAPS_SCALE_FACTOR),
-            font_variant::normal => pt_size,
-        };
-
+                            variant: font_variant_caps::T) -> Font {
         let handle: FontHandle = FontHandleMethods::new_from_template(&self.platform_handle,
+                                    template.clone(), Some(pt_size)).unwrap();
+
+        // Use the small capitals of the font if it has them. Otherwise, fake them by rendering
+        // capital letters at a smaller size.
+        let (handle, actual_pt_size, caps_features, synthesizes_small_caps) =
+            match caps_features_for_font(&handle, variant) {
+                Some(caps_features) => (handle, pt_size, caps_features, false),
+                None => {
+                    let actual_pt_size = pt_size.scale_by(SMALL_CAPS_SCALE_FACTOR);
+                    let handle = FontHandleMethods::new_from_template(&self.platform_handle,
                                     template, Some(actual_pt_size)).unwrap();
+                    (handle, actual_pt_size, vec!(), true)
+                }
+            };
         let metrics = handle.get_metrics();
 
         Font {
             handle: handle,
             shaper: None,
             variant: variant,
+            synthesizes_small_caps: synthesizes_small_caps,
+            caps_features: caps_features,
             descriptor: descriptor,
             requested_pt_size: pt_size,
             actual_pt_size: actual_pt_size,
             metrics: metrics,
             shape_cache: HashCache::new(),
             glyph_advance_cache: HashCache::new(),
+            unicode_range: None,
+            invisible: false,
+        }
+    }
+
+    /// Create a font of a family for use in layout calculations.
+    fn create_layout_font_from_family_template(&self, family_template: FamilyFontTemplate,
+                                               descriptor: FontTemplateDescriptor, pt_size: Au,
+                                               variant: font_variant_caps::T) -> Font {
+        let FamilyFontTemplate { template, unicode_range, invisible } = family_template;
+        let mut font = self.create_layout_font(template, descriptor, pt_size, variant);
+        font.unicode_range = unicode_range;
+        font.invisible = invisible;
+        font
+    }
+
+    /// Drops the cached layout fonts if the web fonts of the document have changed since they were
+    /// looked up, as web fonts that have loaded, or whose block period has ended, change the
+    /// fonts of their families.
+    pub fn set_web_font_generation(&mut self, web_font_generation: uint) {
+        if web_font_generation != self.web_font_generation {
+            self.web_font_generation = web_font_generation;
+            self.layout_font_cache.clear();
+            self.last_style = None;
+            self.last_fontgroup = None;
         }
     }
 
@@ -145,50 +190,40 @@ impl FontContext {
             // GWTODO: Check on real pages if this is faster as Vec() or HashMap().
             let mut cache_hit = false;
             for cached_font_entry in self.layout_font_cache.iter() {
-                if cached_font_entry.family.as_slice() == family.name() {
-                    match cached_font_entry.font {
-                        None => {
-                            cache_hit = true;
-                            break;
-                        }
-                        Some(ref cached_font_ref) => {
-                            let cached_font = cached_font_ref.borrow();
-                            if cached_font.descriptor == desc &&
-                               cached_font.requested_pt_size == style.font_size &&
-                               cached_font.variant == style.font_variant {
-                                fonts.push((*cached_font_ref).clone());
-                                cache_hit = true;
-                                break;
-                            }
-                        }
+                if cached_font_entry.family.as_slice() == family.name() &&
+                   cached_font_entry.descriptor == desc &&
+                   cached_font_entry.pt_size == style.font_size &&
+                   cached_font_entry.variant == style.font_variant_caps {
+                    for cached_font in cached_font_entry.fonts.iter() {
+                        fonts.push(cached_font.clone());
                     }
+                    cache_hit = true;
+                    break;
                 }
             }
 
             if !cache_hit {
-                let font_template = self.font_cache_task.get_font_template(family.name()
-                                                                                 .to_string(),
-                                                                           desc.clone());
-                match font_template {
-                    Some(font_template) => {
-                        let layout_font = self.create_layout_font(font_template,
-                                                                  desc.clone(),
-                                                                  style.font_size,
-                                                                  style.font_variant);
-                        let layout_font = Rc::new(RefCell::new(layout_font));
-                        self.layout_font_cache.push(LayoutFontCacheEntry {
-                            family: family.name().to_string(),
-                            font: Some(layout_font.clone()),
-                        });
-                        fonts.push(layout_font);
-                    }
-                    None => {
-                        self.layout_font_cache.push(LayoutFontCacheEntry {
-                            family: family.name().to_string(),
-                            font: None,
-                        });
-                    }
+                let family_templates =
+                    self.font_cache_task.get_family_font_templates(family.name().to_string(),
+                                                                   desc.clone());
+                let mut family_fonts = vec!();
+                for family_template in family_templates.into_iter() {
+                    let layout_font =
+                        self.create_layout_font_from_family_template(family_template,
+                                                                     desc.clone(),
+                                                                     style.font_size,
+                                                                     style.font_variant_caps);
+                    let layout_font = Rc::new(RefCell::new(layout_font));
+                    fonts.push(layout_font.clone());
+                    family_fonts.push(layout_font);
                 }
+                self.layout_font_cache.push(LayoutFontCacheEntry {
+                    family: family.name().to_string(),
+                    descriptor: desc.clone(),
+                    pt_size: style.font_size,
+                    variant: style.font_variant_caps,
+                    fonts: family_fonts,
+                });
             }
         }
 
@@ -200,7 +235,7 @@ impl FontContext {
                 let cached_font = cached_font_entry.font.borrow();
                 if cached_font.descriptor == desc &&
                             cached_font.requested_pt_size == style.font_size &&
-                            cached_font.variant == style.font_variant {
+                            cached_font.variant == style.font_variant_caps {
                     fonts.push(cached_font_entry.font.clone());
                     cache_hit = true;
                     break;
@@ -212,7 +247,7 @@ impl FontContext {
                 let layout_font = self.create_layout_font(font_template,
                                                           desc.clone(),
                                                           style.font_size,
-                                                          style.font_variant);
+                                                          style.font_variant_caps);
                 let layout_font = Rc::new(RefCell::new(layout_font));
                 self.fallback_font_cache.push(FallbackFontCacheEntry {
                     font: layout_font.clone(),
@@ -227,6 +262,59 @@ impl FontContext {
         font_group
     }
 
+    /// Returns the font to render the given character with in layout: the first font of the
+    /// group that is used for the character and has a glyph for it or, failing that, a system
+    /// font that has a glyph for it. If no font has a glyph for the character, the first font of
+    /// the group is returned.
+    pub fn get_layout_font_for_char(&mut self,
+                                    font_group: &FontGroup,
+                                    style: &SpecifiedFontStyle,
+                                    codepoint: char)
+                                    -> Rc<RefCell<Font>> {
+        for font in font_group.fonts.iter() {
+            let font_ref = font.borrow();
+            if font_ref.covers(codepoint) && font_ref.glyph_index(codepoint).is_some() {
+                return font.clone()
+            }
+        }
+
+        let first_font = font_group.fonts.get(0).clone();
+        if codepoint.is_whitespace() || codepoint.is_control() ||
+                self.chars_without_fallback_font.contains(&codepoint) {
+            return first_font
+        }
+
+        let desc = FontTemplateDescriptor::new(style.font_weight,
+                                               style.font_style == font_style::italic);
+        for cached_font_entry in self.fallback_font_cache.iter() {
+            let cached_font = cached_font_entry.font.borrow();
+            if cached_font.descriptor == desc &&
+               cached_font.requested_pt_size == style.font_size &&
+               cached_font.variant == style.font_variant_caps &&
+               cached_font.glyph_index(codepoint).is_some() {
+                return cached_font_entry.font.clone()
+            }
+        }
+
+        match self.font_cache_task.get_fallback_font_template(codepoint, desc.clone()) {
+            Some(font_template) => {
+                let layout_font = self.create_layout_font(font_template,
+                                                          desc,
+                                                          style.font_size,
+                                                          style.font_variant_caps);
+                let layout_font = Rc::new(RefCell::new(layout_font));
+                self.fallback_font_cache.push(FallbackFontCacheEntry {
+                    font: layout_font.clone(),
+                });
+                layout_font
+            }
+            None => {
+                self.chars_without_fallback_font.insert(codepoint);
+                first_font
+            }
+        }
+    }
+
     /// Create a render font for use with azure. May return a cached
     /// reference if already used by this font context.
     pub fn get_render_font_from_template(&mut self,
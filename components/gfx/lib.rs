@@ -13,6 +13,7 @@ extern crate log;
 
 extern crate azure;
 extern crate collections;
+extern crate flate2;
 extern crate geom;
 extern crate layers;
 extern crate libc;
@@ -66,6 +67,7 @@ pub mod font;
 pub mod font_context;
 pub mod font_cache_task;
 pub mod font_template;
+pub mod woff;
 
 // Misc.
 mod buffer_map;
@@ -16,7 +16,7 @@ use platform::font_template::FontTemplateData;
 
 use freetype::freetype::{FT_Get_Char_Index, FT_Get_Postscript_Name};
 use freetype::freetype::{FT_Load_Glyph, FT_Set_Char_Size};
-use freetype::freetype::{FT_Get_Kerning, FT_Get_Sfnt_Table};
+use freetype::freetype::{FT_Get_Kerning, FT_Get_Sfnt_Table, FT_Load_Sfnt_Table};
 use freetype::freetype::{FT_New_Memory_Face, FT_Done_Face};
 use freetype::freetype::{FTErrorMethods, FT_F26Dot6, FT_Face, FT_FaceRec};
 use freetype::freetype::{FT_GlyphSlot, FT_Library, FT_Long, FT_ULong};
@@ -39,11 +39,13 @@ fn fixed_to_float_ft(f: i32) -> f64 {
     fixed_to_float(6, f)
 }
 
-pub struct FontTable;
+pub struct FontTable {
+    buffer: Vec<u8>,
+}
 
 impl FontTableMethods for FontTable {
-    fn with_buffer(&self, _blk: |*const u8, uint|) {
-        panic!()
+    fn with_buffer(&self, blk: |*const u8, uint|) {
+        blk(self.buffer.as_ptr(), self.buffer.len())
     }
 }
 
@@ -258,8 +260,26 @@ impl FontHandleMethods for FontHandle {
         return metrics;
     }
 
-    fn get_table_for_tag(&self, _: FontTableTag) -> Option<FontTable> {
-        None
+    /// Loads a table of the font. HarfBuzz reads the GSUB and GPOS tables through this, so all
+    /// text is shaped with the substitutions and positioning of the font, including its default
+    /// ligatures and kerning, and not only text that asks for features.
+    fn get_table_for_tag(&self, tag: FontTableTag) -> Option<FontTable> {
+        let tag = tag as FT_ULong;
+        unsafe {
+            // Ask for the length of the table first, then load it.
+            let mut length = 0;
+            if !FT_Load_Sfnt_Table(self.face, tag, 0, ptr::null_mut(), &mut length).succeeded() {
+                return None
+            }
+            let mut buffer = Vec::from_elem(length as uint, 0u8);
+            if !FT_Load_Sfnt_Table(self.face, tag, 0, buffer.as_mut_ptr(), &mut length)
+                    .succeeded() {
+                return None
+            }
+            Some(FontTable {
+                buffer: buffer,
+            })
+        }
     }
 }
 
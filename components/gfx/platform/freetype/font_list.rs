@@ -7,7 +7,7 @@
 extern crate freetype;
 extern crate fontconfig;
 
-use fontconfig::fontconfig::{FcChar8, FcResultMatch, FcSetSystem};
+use fontconfig::fontconfig::{FcChar8, FcChar32, FcCharSet, FcResultMatch, FcSetSystem};
 use fontconfig::fontconfig::{
     FcConfigGetCurrent, FcConfigGetFonts,
     FcConfigSubstitute, FcDefaultSubstitute,
@@ -17,7 +17,9 @@ use fontconfig::fontconfig::{
     FcMatchPattern,
     FcPatternCreate, FcPatternAddString,
     FcFontSetList, FcObjectSetCreate, FcObjectSetDestroy,
-    FcObjectSetAdd, FcPatternGetInteger
+    FcObjectSetAdd, FcPatternGetInteger,
+    FcCharSetCreate, FcCharSetAddChar, FcCharSetDestroy, FcPatternAddCharSet,
+    FcFontSort, FcPatternGetCharSet, FcCharSetHasChar
 };
 
 use libc;
@@ -28,6 +30,7 @@ use std::string;
 static FC_FAMILY: &'static [u8] = b"family\0";
 static FC_FILE: &'static [u8] = b"file\0";
 static FC_INDEX: &'static [u8] = b"index\0";
+static FC_CHARSET: &'static [u8] = b"charset\0";
 
 pub fn get_available_families(callback: |String|) {
     unsafe {
@@ -124,6 +127,60 @@ pub fn get_system_default_family(generic_name: &str) -> Option<String> {
     }
 }
 
+/// Returns the families of the system fonts to try, best first, for a character that the fonts
+/// a page asks for have no glyph for.
+pub fn get_fallback_font_families(codepoint: char) -> Vec<String> {
+    let mut families = vec!();
+
+    unsafe {
+        let pattern = FcPatternCreate();
+        assert!(pattern.is_not_null());
+        let char_set = FcCharSetCreate();
+        assert!(char_set.is_not_null());
+        FcCharSetAddChar(char_set, codepoint as FcChar32);
+        // The pattern keeps its own reference to the character set.
+        let ok = FcPatternAddCharSet(pattern, FC_CHARSET.as_ptr() as *mut i8, char_set);
+        assert!(ok != 0);
+        FcCharSetDestroy(char_set);
+
+        FcConfigSubstitute(ptr::null_mut(), pattern, FcMatchPattern);
+        FcDefaultSubstitute(pattern);
+
+        // Unlike FcFontMatch, which returns a single font whether or not it has a glyph for the
+        // character, FcFontSort returns all the fonts, best first, so that the ones that lack it
+        // can be skipped.
+        let mut result = 0;
+        let font_set = FcFontSort(ptr::null_mut(), pattern, 1, ptr::null_mut(), &mut result);
+        if result == FcResultMatch {
+            for i in range(0, (*font_set).nfont as int) {
+                let font = (*font_set).fonts.offset(i);
+                let mut font_char_set: *mut FcCharSet = ptr::null_mut();
+                if FcPatternGetCharSet(*font, FC_CHARSET.as_ptr() as *mut i8, 0,
+                                       &mut font_char_set) != FcResultMatch ||
+                        FcCharSetHasChar(font_char_set, codepoint as FcChar32) == 0 {
+                    continue
+                }
+                let mut family: *mut FcChar8 = ptr::null_mut();
+                if FcPatternGetString(*font, FC_FAMILY.as_ptr() as *mut i8, 0,
+                                      &mut family) == FcResultMatch {
+                    let family = string::raw::from_buf(family as *const i8 as *const u8);
+                    if !families.contains(&family) {
+                        families.push(family);
+                    }
+                }
+            }
+        }
+        if font_set.is_not_null() {
+            FcFontSetDestroy(font_set);
+        }
+
+        FcPatternDestroy(pattern);
+    }
+
+    families.push_all(get_last_resort_font_families().as_slice());
+    families
+}
+
 #[cfg(target_os="linux")]
 pub fn get_last_resort_font_families() -> Vec<String> {
     vec!(
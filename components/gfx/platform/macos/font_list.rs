@@ -41,6 +41,24 @@ pub fn get_system_default_family(_generic_name: &str) -> Option<String> {
     None
 }
 
+/// Returns the families of the system fonts to try, best first, for a character that the fonts
+/// a page asks for have no glyph for.
+///
+/// FIXME: Ask Core Text for the font it would cascade to with `CTFontCreateForString` once
+/// rust-core-text exposes it.
+pub fn get_fallback_font_families(_codepoint: char) -> Vec<String> {
+    let mut families = vec!(
+        "Hiragino Kaku Gothic ProN".to_string(),
+        "Apple SD Gothic Neo".to_string(),
+        "Devanagari Sangam MN".to_string(),
+        "Thonburi".to_string(),
+        "Apple Color Emoji".to_string(),
+        "Apple Symbols".to_string()
+    );
+    families.push_all(get_last_resort_font_families().as_slice());
+    families
+}
+
 pub fn get_last_resort_font_families() -> Vec<String> {
     vec!("Arial Unicode MS".to_string(), "Arial".to_string())
 }
@@ -787,24 +787,33 @@ impl ScaledFontExtensionMethods for ScaledFont {
             fields: fields,
         };
 
-        let mut origin = baseline_origin.clone();
-        let mut azglyphs = vec!();
-        azglyphs.reserve(range.length().to_uint());
-
+        // Glyphs are stored in logical order, so right-to-left runs are painted backwards.
+        let mut glyphs_to_draw = vec!();
+        glyphs_to_draw.reserve(range.length().to_uint());
         for (glyphs, _offset, slice_range) in run.iter_slices_for_range(range) {
             for (_i, glyph) in glyphs.iter_glyphs_for_char_range(&slice_range) {
-                let glyph_advance = glyph.advance();
-                let glyph_offset = glyph.offset().unwrap_or(Zero::zero());
-                let azglyph = struct__AzGlyph {
-                    mIndex: glyph.id() as uint32_t,
-                    mPosition: struct__AzPoint {
-                        x: (origin.x + glyph_offset.x).to_subpx() as AzFloat,
-                        y: (origin.y + glyph_offset.y).to_subpx() as AzFloat
-                    }
-                };
-                origin = Point2D(origin.x + glyph_advance, origin.y);
-                azglyphs.push(azglyph)
+                glyphs_to_draw.push((glyph.id(),
+                                     glyph.advance(),
+                                     glyph.offset().unwrap_or(Zero::zero())));
+            };
+        }
+        if run.is_rtl() {
+            glyphs_to_draw.reverse();
+        }
+
+        let mut origin = baseline_origin.clone();
+        let mut azglyphs = vec!();
+        azglyphs.reserve(glyphs_to_draw.len());
+        for &(glyph_id, glyph_advance, glyph_offset) in glyphs_to_draw.iter() {
+            let azglyph = struct__AzGlyph {
+                mIndex: glyph_id as uint32_t,
+                mPosition: struct__AzPoint {
+                    x: (origin.x + glyph_offset.x).to_subpx() as AzFloat,
+                    y: (origin.y + glyph_offset.y).to_subpx() as AzFloat
+                }
             };
+            origin = Point2D(origin.x + glyph_advance, origin.y);
+            azglyphs.push(azglyph)
         }
 
         let azglyph_buf_len = azglyphs.len();
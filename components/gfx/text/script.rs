@@ -0,0 +1,251 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Unicode scripts (UAX #24), used to split text into runs that are shaped separately.
+
+use servo_util::bidi::closing_bracket_for;
+
+/// The `Script` property of a character.
+///
+/// Only scripts that shapers treat specially or that fonts commonly lack are distinguished.
+/// Characters of any other script are treated as `Common` and left to the shaper to identify.
+#[deriving(Clone, PartialEq, Eq, Hash, Show)]
+pub enum Script {
+    Common,
+    Inherited,
+    Arabic,
+    Armenian,
+    Bengali,
+    Bopomofo,
+    Cherokee,
+    Cyrillic,
+    Devanagari,
+    Ethiopic,
+    Georgian,
+    Greek,
+    Gujarati,
+    Gurmukhi,
+    Han,
+    Hangul,
+    Hebrew,
+    Hiragana,
+    Kannada,
+    Katakana,
+    Khmer,
+    Lao,
+    Latin,
+    Malayalam,
+    Mongolian,
+    Myanmar,
+    Oriya,
+    Sinhala,
+    Syriac,
+    Tamil,
+    Telugu,
+    Thaana,
+    Thai,
+    Tibetan,
+}
+
+impl Script {
+    /// Returns the ISO 15924 code of this script, or `None` for `Common` and `Inherited`, which
+    /// don't identify a script of their own.
+    pub fn iso15924_code(&self) -> Option<&'static str> {
+        let code = match *self {
+            Common | Inherited => return None,
+            Arabic => "Arab",
+            Armenian => "Armn",
+            Bengali => "Beng",
+            Bopomofo => "Bopo",
+            Cherokee => "Cher",
+            Cyrillic => "Cyrl",
+            Devanagari => "Deva",
+            Ethiopic => "Ethi",
+            Georgian => "Geor",
+            Greek => "Grek",
+            Gujarati => "Gujr",
+            Gurmukhi => "Guru",
+            Han => "Hani",
+            Hangul => "Hang",
+            Hebrew => "Hebr",
+            Hiragana => "Hira",
+            Kannada => "Knda",
+            Katakana => "Kana",
+            Khmer => "Khmr",
+            Lao => "Laoo",
+            Latin => "Latn",
+            Malayalam => "Mlym",
+            Mongolian => "Mong",
+            Myanmar => "Mymr",
+            Oriya => "Orya",
+            Sinhala => "Sinh",
+            Syriac => "Syrc",
+            Tamil => "Taml",
+            Telugu => "Telu",
+            Thaana => "Thaa",
+            Thai => "Thai",
+            Tibetan => "Tibt",
+        };
+        Some(code)
+    }
+
+    /// Returns true if characters of this script take the script of the text around them.
+    pub fn is_common_or_inherited(&self) -> bool {
+        match *self {
+            Common | Inherited => true,
+            _ => false,
+        }
+    }
+}
+
+/// Returns the script of the given character.
+pub fn script_for_char(c: char) -> Script {
+    let mut low = 0u;
+    let mut high = SCRIPT_TABLE.len();
+    while low < high {
+        let mid = (low + high) / 2;
+        let (start, end, script) = SCRIPT_TABLE[mid];
+        if c < start {
+            high = mid
+        } else if c > end {
+            low = mid + 1
+        } else {
+            return script
+        }
+    }
+    Common
+}
+
+/// Resolves the script of each character of the given text for shaping.
+///
+/// As UAX #24 suggests, characters of the `Common` and `Inherited` scripts take the script of the
+/// character before them, or of the first character after them if they begin the text, and a
+/// closing bracket takes the script of its opening bracket. The text is only `Common` throughout
+/// if none of its characters has a script of its own.
+pub fn resolve_scripts(text: &[char]) -> Vec<Script> {
+    let mut scripts = Vec::with_capacity(text.len());
+    let mut current = Common;
+    // The closing brackets we are waiting for, along with the script of their opening brackets.
+    let mut open_brackets: Vec<(char, Script)> = vec!();
+    for &c in text.iter() {
+        let script = script_for_char(c);
+        if !script.is_common_or_inherited() {
+            if current == Common {
+                // Resolve the leading neutral characters to the first real script.
+                for resolved in scripts.iter_mut() {
+                    *resolved = script
+                }
+                for &(_, ref mut bracket_script) in open_brackets.iter_mut() {
+                    *bracket_script = script
+                }
+            }
+            current = script;
+            scripts.push(script);
+            continue
+        }
+
+        match closing_bracket_for(c) {
+            Some(closing) => open_brackets.push((closing, current)),
+            None => {
+                match open_brackets.iter().rposition(|&(closing, _)| closing == c) {
+                    Some(index) => {
+                        let (_, bracket_script) = open_brackets[index];
+                        open_brackets.truncate(index);
+                        current = bracket_script;
+                    }
+                    None => {}
+                }
+            }
+        }
+        scripts.push(current)
+    }
+    scripts
+}
+
+/// The `Script` property of the characters of the scripts in `Script`, as ranges sorted by their
+/// first character. Characters not in the table are `Common`, per Unicode 7.0.
+static SCRIPT_TABLE: &'static [(char, char, Script)] = &[
+    ('A', 'Z', Latin), ('a', 'z', Latin), ('\u00aa', '\u00aa', Latin), ('\u00ba', '\u00ba', Latin),
+    ('\u00c0', '\u00d6', Latin), ('\u00d8', '\u00f6', Latin), ('\u00f8', '\u02b8', Latin),
+    ('\u02e0', '\u02e4', Latin), ('\u0300', '\u036f', Inherited), ('\u0370', '\u0373', Greek),
+    ('\u0375', '\u0377', Greek), ('\u037a', '\u037d', Greek), ('\u037f', '\u037f', Greek),
+    ('\u0384', '\u0384', Greek), ('\u0386', '\u0386', Greek), ('\u0388', '\u03e1', Greek),
+    ('\u03f0', '\u03ff', Greek), ('\u0400', '\u0484', Cyrillic), ('\u0485', '\u0486', Inherited),
+    ('\u0487', '\u052f', Cyrillic), ('\u0531', '\u0556', Armenian), ('\u0559', '\u055f', Armenian),
+    ('\u0561', '\u0587', Armenian), ('\u058a', '\u058a', Armenian), ('\u058d', '\u058f', Armenian),
+    ('\u0591', '\u05c7', Hebrew), ('\u05d0', '\u05ea', Hebrew), ('\u05f0', '\u05f4', Hebrew),
+    ('\u0600', '\u0604', Arabic), ('\u0606', '\u060b', Arabic), ('\u060d', '\u061a', Arabic),
+    ('\u061e', '\u061e', Arabic), ('\u0620', '\u063f', Arabic), ('\u0641', '\u064a', Arabic),
+    ('\u064b', '\u0655', Inherited), ('\u0656', '\u066f', Arabic), ('\u0670', '\u0670', Inherited),
+    ('\u0671', '\u06dc', Arabic), ('\u06de', '\u06ff', Arabic), ('\u0700', '\u074f', Syriac),
+    ('\u0750', '\u077f', Arabic), ('\u0780', '\u07b1', Thaana), ('\u08a0', '\u08ff', Arabic),
+    ('\u0900', '\u0950', Devanagari), ('\u0951', '\u0952', Inherited),
+    ('\u0953', '\u0963', Devanagari), ('\u0966', '\u097f', Devanagari),
+    ('\u0980', '\u09ff', Bengali), ('\u0a00', '\u0a7f', Gurmukhi), ('\u0a80', '\u0aff', Gujarati),
+    ('\u0b00', '\u0b7f', Oriya), ('\u0b80', '\u0bff', Tamil), ('\u0c00', '\u0c7f', Telugu),
+    ('\u0c80', '\u0cff', Kannada), ('\u0d00', '\u0d7f', Malayalam), ('\u0d80', '\u0dff', Sinhala),
+    ('\u0e01', '\u0e3a', Thai), ('\u0e40', '\u0e7f', Thai), ('\u0e80', '\u0eff', Lao),
+    ('\u0f00', '\u0fd4', Tibetan), ('\u0fd9', '\u0fff', Tibetan), ('\u1000', '\u109f', Myanmar),
+    ('\u10a0', '\u10fa', Georgian), ('\u10fc', '\u10ff', Georgian), ('\u1100', '\u11ff', Hangul),
+    ('\u1200', '\u139f', Ethiopic), ('\u13a0', '\u13ff', Cherokee), ('\u1780', '\u17ff', Khmer),
+    ('\u1800', '\u1801', Mongolian), ('\u1804', '\u1804', Mongolian),
+    ('\u1806', '\u18af', Mongolian), ('\u19e0', '\u19ff', Khmer), ('\u1ab0', '\u1aff', Inherited),
+    ('\u1d00', '\u1d25', Latin), ('\u1d26', '\u1d2a', Greek), ('\u1d2b', '\u1d2b', Cyrillic),
+    ('\u1d2c', '\u1d5c', Latin), ('\u1d5d', '\u1d61', Greek), ('\u1d62', '\u1d65', Latin),
+    ('\u1d66', '\u1d6a', Greek), ('\u1d6b', '\u1d77', Latin), ('\u1d78', '\u1d78', Cyrillic),
+    ('\u1d79', '\u1dbe', Latin), ('\u1dbf', '\u1dbf', Greek), ('\u1dc0', '\u1dff', Inherited),
+    ('\u1e00', '\u1eff', Latin), ('\u1f00', '\u1ffe', Greek), ('\u200c', '\u200d', Inherited),
+    ('\u2071', '\u2071', Latin), ('\u207f', '\u207f', Latin), ('\u2090', '\u209c', Latin),
+    ('\u20d0', '\u20f0', Inherited), ('\u2126', '\u2126', Greek), ('\u212a', '\u212b', Latin),
+    ('\u2132', '\u2132', Latin), ('\u214e', '\u214e', Latin), ('\u2160', '\u2188', Latin),
+    ('\u2c60', '\u2c7f', Latin), ('\u2d00', '\u2d2d', Georgian), ('\u2d80', '\u2ddf', Ethiopic),
+    ('\u2de0', '\u2dff', Cyrillic), ('\u2e80', '\u2fd5', Han), ('\u3005', '\u3005', Han),
+    ('\u3007', '\u3007', Han), ('\u3021', '\u3029', Han), ('\u302a', '\u302d', Inherited),
+    ('\u3038', '\u303b', Han), ('\u3041', '\u3096', Hiragana), ('\u3099', '\u309a', Inherited),
+    ('\u309d', '\u309f', Hiragana), ('\u30a1', '\u30fa', Katakana), ('\u30fd', '\u30ff', Katakana),
+    ('\u3105', '\u312d', Bopomofo), ('\u3131', '\u318e', Hangul), ('\u31a0', '\u31ba', Bopomofo),
+    ('\u31f0', '\u31ff', Katakana), ('\u3200', '\u321e', Hangul), ('\u3260', '\u327e', Hangul),
+    ('\u32d0', '\u32fe', Katakana), ('\u3300', '\u3357', Katakana), ('\u3400', '\u4db5', Han),
+    ('\u4e00', '\u9fcc', Han), ('\ua640', '\ua69f', Cyrillic), ('\ua722', '\ua787', Latin),
+    ('\ua78b', '\ua7ff', Latin), ('\ua8e0', '\ua8fb', Devanagari), ('\ua960', '\ua97c', Hangul),
+    ('\ua9e0', '\ua9fe', Myanmar), ('\uaa60', '\uaa7f', Myanmar), ('\uab01', '\uab2e', Ethiopic),
+    ('\uab30', '\uab5a', Latin), ('\uab5c', '\uab64', Latin), ('\uac00', '\ud7a3', Hangul),
+    ('\ud7b0', '\ud7fb', Hangul), ('\uf900', '\ufad9', Han), ('\ufb00', '\ufb06', Latin),
+    ('\ufb13', '\ufb17', Armenian), ('\ufb1d', '\ufb4f', Hebrew), ('\ufb50', '\ufd3d', Arabic),
+    ('\ufd50', '\ufdfd', Arabic), ('\ufe00', '\ufe0f', Inherited), ('\ufe20', '\ufe2d', Inherited),
+    ('\ufe70', '\ufefc', Arabic), ('\uff21', '\uff3a', Latin), ('\uff41', '\uff5a', Latin),
+    ('\uff66', '\uff6f', Katakana), ('\uff71', '\uff9d', Katakana), ('\uffa0', '\uffdc', Hangul),
+    ('\U00020000', '\U0002a6d6', Han), ('\U0002a700', '\U0002b734', Han),
+    ('\U0002b740', '\U0002b81d', Han), ('\U0002f800', '\U0002fa1d', Han),
+    ('\U000e0100', '\U000e01ef', Inherited),
+];
+
+#[cfg(test)]
+fn resolve(text: &str) -> Vec<Script> {
+    let chars: Vec<char> = text.chars().collect();
+    resolve_scripts(chars.as_slice())
+}
+
+#[test]
+fn test_resolve_leading_neutrals() {
+    // Spaces, digits and a combining mark before the first letter take the script of that letter.
+    assert_eq!(resolve(" 1\u0301αβ"), vec!(Greek, Greek, Greek, Greek, Greek));
+    assert_eq!(resolve("\u0301 a"), vec!(Latin, Latin, Latin));
+    // Neutrals after a letter keep its script.
+    assert_eq!(resolve("a 1ж"), vec!(Latin, Latin, Latin, Cyrillic));
+    // Text without letters stays common.
+    assert_eq!(resolve("12 ."), vec!(Common, Common, Common, Common));
+}
+
+#[test]
+fn test_resolve_paired_brackets() {
+    // A closing bracket takes the script of its opening bracket, not of the text inside.
+    assert_eq!(resolve("a(ж)"), vec!(Latin, Latin, Cyrillic, Latin));
+    assert_eq!(resolve("a[б(в)]"),
+               vec!(Latin, Latin, Cyrillic, Cyrillic, Cyrillic, Cyrillic, Latin));
+    // A leading opening bracket takes the first script, and so does its closing bracket.
+    assert_eq!(resolve("(ж) a"), vec!(Cyrillic, Cyrillic, Cyrillic, Cyrillic, Latin));
+    // A closing bracket without an opening one takes the script before it.
+    assert_eq!(resolve("aж)"), vec!(Latin, Cyrillic, Cyrillic));
+}
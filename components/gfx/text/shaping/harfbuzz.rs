@@ -4,22 +4,23 @@
 
 extern crate harfbuzz;
 
-use font::{Font, FontHandleMethods, FontTableMethods, FontTableTag};
+use font::{Font, FontHandleMethods, FontTableMethods, FontTableTag, RTL_FLAG, ShapingOptions};
 use platform::font::FontTable;
 use text::glyph::{CharIndex, GlyphStore, GlyphId, GlyphData};
 use text::shaping::ShaperMethods;
 use text::util::{float_to_fixed, fixed_to_float};
 
 use geom::Point2D;
-use harfbuzz::{HB_MEMORY_MODE_READONLY, HB_DIRECTION_LTR};
+use harfbuzz::{HB_MEMORY_MODE_READONLY, HB_DIRECTION_LTR, HB_DIRECTION_RTL};
 use harfbuzz::{hb_blob_create, hb_face_create_for_tables};
 use harfbuzz::{hb_blob_t};
 use harfbuzz::{hb_bool_t};
 use harfbuzz::{hb_buffer_add_utf8};
 use harfbuzz::{hb_buffer_destroy};
 use harfbuzz::{hb_buffer_get_glyph_positions};
-use harfbuzz::{hb_buffer_set_direction};
-use harfbuzz::{hb_face_destroy};
+use harfbuzz::{hb_buffer_guess_segment_properties, hb_buffer_reverse};
+use harfbuzz::{hb_buffer_set_direction, hb_buffer_set_language, hb_buffer_set_script};
+use harfbuzz::{hb_face_destroy, hb_feature_t};
 use harfbuzz::{hb_face_t, hb_font_t};
 use harfbuzz::{hb_font_create};
 use harfbuzz::{hb_font_destroy, hb_buffer_create};
@@ -35,6 +36,7 @@ use harfbuzz::{hb_font_set_scale};
 use harfbuzz::{hb_glyph_info_t};
 use harfbuzz::{hb_glyph_position_t};
 use harfbuzz::{hb_position_t, hb_tag_t};
+use harfbuzz::{hb_language_from_string, hb_script_from_string};
 use harfbuzz::{hb_shape, hb_buffer_get_glyph_infos};
 use libc::{c_uint, c_int, c_void, c_char};
 use servo_util::geometry::Au;
@@ -43,6 +45,7 @@ use std::mem;
 use std::char;
 use std::cmp;
 use std::ptr;
+use std::u32;
 
 static NO_GLYPH: i32 = -1;
 static CONTINUATION_BYTE: i32 = -2;
@@ -200,18 +203,56 @@ impl Shaper {
 impl ShaperMethods for Shaper {
     /// Calculate the layout metrics associated with the given text when rendered in a specific
     /// font.
-    fn shape_text(&self, text: &str, glyphs: &mut GlyphStore) {
+    fn shape_text(&self, text: &str, options: &ShapingOptions, glyphs: &mut GlyphStore) {
         unsafe {
             let hb_buffer: *mut hb_buffer_t = hb_buffer_create();
-            hb_buffer_set_direction(hb_buffer, HB_DIRECTION_LTR);
+            hb_buffer_set_direction(hb_buffer, if options.flags.contains(RTL_FLAG) {
+                HB_DIRECTION_RTL
+            } else {
+                HB_DIRECTION_LTR
+            });
 
             hb_buffer_add_utf8(hb_buffer,
                                text.as_ptr() as *const c_char,
                                text.len() as c_int,
                                0,
                                text.len() as c_int);
-
-            hb_shape(self.hb_font, hb_buffer, ptr::null_mut(), 0);
+            match options.script.iso15924_code() {
+                Some(code) => {
+                    let script = hb_script_from_string(code.as_ptr() as *const c_char,
+                                                       code.len() as c_int);
+                    hb_buffer_set_script(hb_buffer, script)
+                }
+                None => {}
+            }
+            match options.language {
+                Some(ref language) => {
+                    let language = hb_language_from_string(language.as_ptr() as *const c_char,
+                                                           language.len() as c_int);
+                    hb_buffer_set_language(hb_buffer, language)
+                }
+                None => {}
+            }
+            // This fills in the script if the text is all `Common`, and the language if no
+            // ancestor of the text has a `lang` attribute.
+            hb_buffer_guess_segment_properties(hb_buffer);
+
+            let mut features: Vec<hb_feature_t> = options.features.iter().map(|feature| {
+                hb_feature_t {
+                    tag: feature.tag as hb_tag_t,
+                    value: feature.value,
+                    start: 0,
+                    end: u32::MAX as c_uint,
+                }
+            }).collect();
+            hb_shape(self.hb_font, hb_buffer, features.as_mut_ptr(), features.len() as c_uint);
+
+            // Harfbuzz returns right-to-left runs in visual order. The glyph store is indexed by
+            // character, so put the glyphs back in logical order; they are reversed again when
+            // the run is painted.
+            if options.flags.contains(RTL_FLAG) {
+                hb_buffer_reverse(hb_buffer);
+            }
             self.save_glyph_results(text, glyphs, hb_buffer);
             hb_buffer_destroy(hb_buffer);
         }
@@ -509,19 +550,20 @@ extern fn get_font_table_func(_: *mut hb_face_t, tag: hb_tag_t, user_data: *mut
         let font: *const Font = user_data as *const Font;
         assert!(font.is_not_null());
 
-        // TODO(Issue #197): reuse font table data, which will change the unsound trickery here.
+        // TODO(Issue #197): reuse font table data.
         match (*font).get_table_for_tag(tag as FontTableTag) {
             None => ptr::null_mut(),
-            Some(ref font_table) => {
-                let skinny_font_table_ptr: *const FontTable = font_table;   // private context
+            Some(font_table) => {
+                // The blob owns the table, and HarfBuzz frees it with `destroy_blob_func` when the
+                // blob is no longer needed.
+                let font_table_ptr: *mut FontTable = mem::transmute(box font_table);
 
                 let mut blob: *mut hb_blob_t = ptr::null_mut();
-                (*skinny_font_table_ptr).with_buffer(|buf: *const u8, len: uint| {
-                    // HarfBuzz calls `destroy_blob_func` when the buffer is no longer needed.
+                (*font_table_ptr).with_buffer(|buf: *const u8, len: uint| {
                     blob = hb_blob_create(buf as *const c_char,
                                           len as c_uint,
                                           HB_MEMORY_MODE_READONLY,
-                                          mem::transmute(skinny_font_table_ptr),
+                                          font_table_ptr as *mut c_void,
                                           destroy_blob_func);
                 });
 
@@ -532,10 +574,8 @@ extern fn get_font_table_func(_: *mut hb_face_t, tag: hb_tag_t, user_data: *mut
     }
 }
 
-// TODO(Issue #197): reuse font table data, which will change the unsound trickery here.
-// In particular, we'll need to cast to a boxed, rather than owned, FontTable.
-
-// even better, should cache the harfbuzz blobs directly instead of recreating a lot.
-extern fn destroy_blob_func(_: *mut c_void) {
-    // TODO: Previous code here was broken. Rewrite.
+extern fn destroy_blob_func(font_table_ptr: *mut c_void) {
+    unsafe {
+        let _: Box<FontTable> = mem::transmute(font_table_ptr);
+    }
 }
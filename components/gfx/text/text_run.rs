@@ -2,7 +2,8 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use font::{Font, RunMetrics, FontMetrics};
+use font::{Font, FontMetrics, IS_WHITESPACE_SHAPING_FLAG, RTL_FLAG, RunMetrics};
+use font::ShapingOptions;
 use servo_util::geometry::Au;
 use servo_util::range::Range;
 use servo_util::vec::{Comparator, FullBinarySearchMethods};
@@ -21,6 +22,10 @@ pub struct TextRun {
     pub font_metrics: FontMetrics,
     /// The glyph runs that make up this text run.
     pub glyphs: Arc<Vec<GlyphRun>>,
+    /// The bidirectional embedding level of this text run. Odd levels are right-to-left.
+    pub bidi_level: u8,
+    /// True if the text is laid out but not shown, as its web font is still loading.
+    pub invisible: bool,
 }
 
 /// A single series of glyphs within a text run.
@@ -117,19 +122,27 @@ impl<'a> Iterator<Range<CharIndex>> for LineIterator<'a> {
 }
 
 impl<'a> TextRun {
-    pub fn new(font: &mut Font, text: String) -> TextRun {
-        let glyphs = TextRun::break_and_shape(font, text.as_slice());
+    pub fn new(font: &mut Font, text: String, options: &ShapingOptions, bidi_level: u8)
+               -> TextRun {
+        let mut options = options.clone();
+        if bidi_level % 2 == 1 {
+            options.flags.insert(RTL_FLAG);
+        }
+        let glyphs = TextRun::break_and_shape(font, text.as_slice(), &options);
         let run = TextRun {
             text: Arc::new(text),
             font_metrics: font.metrics.clone(),
             font_template: font.handle.get_template(),
             actual_pt_size: font.actual_pt_size,
             glyphs: Arc::new(glyphs),
+            bidi_level: bidi_level,
+            invisible: font.invisible,
         };
         return run;
     }
 
-    pub fn break_and_shape(font: &mut Font, text: &str) -> Vec<GlyphRun> {
+    pub fn break_and_shape(font: &mut Font, text: &str, options: &ShapingOptions)
+                           -> Vec<GlyphRun> {
         // TODO(Issue #230): do a better job. See Gecko's LineBreaker.
         let mut glyphs = vec!();
         let (mut byte_i, mut char_i) = (0u, CharIndex(0));
@@ -165,8 +178,12 @@ impl<'a> TextRun {
                 let slice = text.slice(byte_last_boundary, byte_i);
                 debug!("creating glyph store for slice {} (ws? {}), {} - {} in run {}",
                         slice, !cur_slice_is_whitespace, byte_last_boundary, byte_i, text);
+                let mut options = options.clone();
+                if !cur_slice_is_whitespace {
+                    options.flags.insert(IS_WHITESPACE_SHAPING_FLAG);
+                }
                 glyphs.push(GlyphRun {
-                    glyph_store: font.shape_text(slice, !cur_slice_is_whitespace),
+                    glyph_store: font.shape_text(slice, &options),
                     range: Range::new(char_last_boundary, char_i - char_last_boundary),
                 });
                 byte_last_boundary = byte_i;
@@ -182,8 +199,12 @@ impl<'a> TextRun {
             let slice = text.slice_from(byte_last_boundary);
             debug!("creating glyph store for final slice {} (ws? {}), {} - {} in run {}",
                 slice, cur_slice_is_whitespace, byte_last_boundary, text.len(), text);
+            let mut options = options.clone();
+            if cur_slice_is_whitespace {
+                options.flags.insert(IS_WHITESPACE_SHAPING_FLAG);
+            }
             glyphs.push(GlyphRun {
-                glyph_store: font.shape_text(slice, cur_slice_is_whitespace),
+                glyph_store: font.shape_text(slice, &options),
                 range: Range::new(char_last_boundary, char_i - char_last_boundary),
             });
         }
@@ -202,6 +223,11 @@ impl<'a> TextRun {
         &*self.glyphs
     }
 
+    /// Returns true if this run is laid out right-to-left.
+    pub fn is_rtl(&self) -> bool {
+        self.bidi_level % 2 == 1
+    }
+
     pub fn range_is_trimmable_whitespace(&self, range: &Range<CharIndex>) -> bool {
         self.iter_slices_for_range(range).all(|(slice_glyphs, _, _)| {
             slice_glyphs.is_whitespace()
@@ -0,0 +1,1008 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Decoding of web fonts in the WOFF and WOFF2 formats into the sfnt (TrueType or OpenType) data
+//! that the platform font libraries understand.
+//!
+//! See http://www.w3.org/TR/WOFF/ and http://www.w3.org/TR/WOFF2/.
+
+use font::{FontTableTag, font_table_tag};
+
+use flate2::reader::ZlibDecoder;
+#[cfg(feature = "woff2")]
+use libc::{c_int, size_t};
+use std::cmp::{max, min};
+use std::io::{BufReader, EndOfFile, Reader};
+
+static WOFF_SIGNATURE: u32 = 0x774f4646;
+static WOFF2_SIGNATURE: u32 = 0x774f4632;
+static COLLECTION_FLAVOR: u32 = 0x74746366;
+
+/// Fonts that would decode to more bytes than this are treated as broken rather than allocated.
+static MAX_SFNT_SIZE: uint = 1 << 28;
+
+/// The tags of the tables that WOFF2 table directory entries can refer to by index.
+static KNOWN_TABLE_TAGS: [&'static str, ..63] = [
+    "cmap", "head", "hhea", "hmtx", "maxp", "name", "OS/2", "post", "cvt ", "fpgm", "glyf",
+    "loca", "prep", "CFF ", "VORG", "EBDT", "EBLC", "gasp", "hdmx", "kern", "LTSH", "PCLT",
+    "VDMX", "vhea", "vmtx", "BASE", "GDEF", "GPOS", "GSUB", "EBSC", "JSTF", "MATH", "CBDT",
+    "CBLC", "COLR", "CPAL", "SVG ", "sbix", "acnt", "avar", "bdat", "bloc", "bsln", "cvar",
+    "fdsc", "feat", "fmtx", "fvar", "gvar", "hsty", "just", "lcar", "mort", "morx", "opbd",
+    "prop", "trak", "Zapf", "Silf", "Glat", "Gloc", "Feat", "Sill",
+];
+
+// Flags of the components of composite glyphs.
+static ARG_1_AND_2_ARE_WORDS: u16 = 0x0001;
+static WE_HAVE_A_SCALE: u16 = 0x0008;
+static MORE_COMPONENTS: u16 = 0x0020;
+static WE_HAVE_AN_X_AND_Y_SCALE: u16 = 0x0040;
+static WE_HAVE_A_TWO_BY_TWO: u16 = 0x0080;
+static WE_HAVE_INSTRUCTIONS: u16 = 0x0100;
+
+// Flags of the points of simple glyphs.
+static ON_CURVE_POINT: u8 = 0x01;
+static OVERLAP_SIMPLE: u8 = 0x40;
+
+#[cfg(feature = "woff2")]
+#[link(name = "brotlidec")]
+extern {
+    fn BrotliDecoderDecompress(encoded_size: size_t,
+                               encoded_buffer: *const u8,
+                               decoded_size: *mut size_t,
+                               decoded_buffer: *mut u8)
+                               -> c_int;
+}
+
+#[cfg(feature = "woff2")]
+static BROTLI_DECODER_RESULT_SUCCESS: c_int = 1;
+
+/// Returns the sfnt data of a downloaded font, decoding it first if it is a WOFF or WOFF2 font.
+/// The format is sniffed from the data rather than taken from the `format()` hints of the
+/// `@font-face` rule, which may be wrong.
+pub fn sfnt_data_for_web_font(bytes: Vec<u8>) -> Result<Vec<u8>, ()> {
+    let signature = try!(ByteReader::new(bytes.as_slice()).read_u32());
+    if signature == WOFF_SIGNATURE {
+        decode_woff(bytes.as_slice())
+    } else if signature == WOFF2_SIGNATURE {
+        decode_woff2(bytes.as_slice())
+    } else {
+        Ok(bytes)
+    }
+}
+
+/// Decodes a WOFF font, whose tables are each optionally compressed with zlib.
+pub fn decode_woff(bytes: &[u8]) -> Result<Vec<u8>, ()> {
+    let mut reader = ByteReader::new(bytes);
+    let _signature = try!(reader.read_u32());
+    let flavor = try!(reader.read_u32());
+    let length = try!(reader.read_u32());
+    let num_tables = try!(reader.read_u16());
+    if length as uint != bytes.len() || num_tables == 0 {
+        return Err(())
+    }
+    // The reserved field, the sfnt size, the version and the metadata and private blocks.
+    try!(reader.skip(2 + 4 + 2 + 2 + 4 * 5));
+
+    let mut tables = Vec::with_capacity(num_tables as uint);
+    let mut sfnt_size = 0;
+    for _ in range(0, num_tables) {
+        let tag = try!(reader.read_u32());
+        let offset = try!(reader.read_u32()) as uint;
+        let compressed_length = try!(reader.read_u32()) as uint;
+        let length = try!(reader.read_u32()) as uint;
+        let _checksum = try!(reader.read_u32());
+
+        sfnt_size += length;
+        if offset > bytes.len() || compressed_length > bytes.len() - offset ||
+                compressed_length > length || sfnt_size > MAX_SFNT_SIZE {
+            return Err(())
+        }
+        let compressed = bytes.slice(offset, offset + compressed_length);
+        let data = if compressed_length == length {
+            compressed.to_vec()
+        } else {
+            try!(zlib_decompress(compressed, length))
+        };
+        tables.push(SfntTable {
+            tag: tag,
+            data: data,
+        });
+    }
+
+    Ok(build_sfnt(flavor, tables))
+}
+
+/// Decodes a WOFF2 font, whose tables are compressed together with Brotli after the `glyf`,
+/// `loca` and `hmtx` tables have optionally been transformed to compress better.
+pub fn decode_woff2(bytes: &[u8]) -> Result<Vec<u8>, ()> {
+    let mut reader = ByteReader::new(bytes);
+    let _signature = try!(reader.read_u32());
+    let flavor = try!(reader.read_u32());
+    let length = try!(reader.read_u32());
+    let num_tables = try!(reader.read_u16());
+    if length as uint != bytes.len() || num_tables == 0 {
+        return Err(())
+    }
+    if flavor == COLLECTION_FLAVOR {
+        debug!("WOFF2 font collections aren't supported");
+        return Err(())
+    }
+    let _reserved = try!(reader.read_u16());
+    let _sfnt_size = try!(reader.read_u32());
+    let compressed_size = try!(reader.read_u32()) as uint;
+    // The version and the metadata and private blocks.
+    try!(reader.skip(2 + 2 + 4 * 5));
+
+    let glyf_tag = font_table_tag("glyf");
+    let loca_tag = font_table_tag("loca");
+    let mut entries = Vec::with_capacity(num_tables as uint);
+    let mut stream_length = 0;
+    for _ in range(0, num_tables) {
+        let flags = try!(reader.read_u8());
+        let tag = match flags & 0x3f {
+            0x3f => try!(reader.read_u32()),
+            index => font_table_tag(KNOWN_TABLE_TAGS[index as uint]),
+        };
+        let length = try!(reader.read_base128()) as uint;
+
+        // Version 0 is the null transform for every table but `glyf` and `loca`, for which it is
+        // version 3.
+        let transform_version = flags >> 6;
+        let transformed = if tag == glyf_tag || tag == loca_tag {
+            transform_version != 3
+        } else {
+            transform_version != 0
+        };
+        let transform_length = if transformed {
+            try!(reader.read_base128()) as uint
+        } else {
+            length
+        };
+
+        entries.push(Woff2TableEntry {
+            tag: tag,
+            length: length,
+            offset: stream_length,
+            transform_length: transform_length,
+            transformed: transformed,
+        });
+        stream_length += transform_length;
+        if stream_length > MAX_SFNT_SIZE {
+            return Err(())
+        }
+    }
+
+    let stream = try!(brotli_decompress(try!(reader.read_bytes(compressed_size)),
+                                        stream_length));
+    let mut tables = vec!();
+    for entry in entries.iter().filter(|entry| !entry.transformed) {
+        tables.push(SfntTable {
+            tag: entry.tag,
+            data: entry.data(stream.as_slice()).to_vec(),
+        });
+    }
+
+    let x_mins = match (find_entry(entries.as_slice(), glyf_tag),
+                        find_entry(entries.as_slice(), loca_tag)) {
+        (Some(glyf), Some(loca)) if glyf.transformed => {
+            // The `loca` table is rebuilt from the `glyf` table, so it has no data of its own.
+            if !loca.transformed || loca.transform_length != 0 {
+                return Err(())
+            }
+            let glyf_table = try!(reconstruct_glyf(glyf.data(stream.as_slice())));
+            if glyf_table.loca.len() != loca.length {
+                return Err(())
+            }
+            set_index_to_loc_format(tables.as_mut_slice(), glyf_table.index_format);
+            tables.push(SfntTable {
+                tag: glyf_tag,
+                data: glyf_table.glyf,
+            });
+            tables.push(SfntTable {
+                tag: loca_tag,
+                data: glyf_table.loca,
+            });
+            Some(glyf_table.x_mins)
+        }
+        (Some(_), Some(loca)) if !loca.transformed => None,
+        (None, None) => None,
+        _ => return Err(()),
+    };
+
+    let hmtx_tag = font_table_tag("hmtx");
+    for entry in entries.iter().filter(|entry| entry.transformed) {
+        if entry.tag == glyf_tag || entry.tag == loca_tag {
+            continue
+        }
+        // The only other transform is the one of `hmtx`, which needs the bounding boxes of the
+        // glyphs.
+        if entry.tag != hmtx_tag || x_mins.is_none() {
+            return Err(())
+        }
+        let num_h_metrics = try!(number_of_h_metrics(tables.as_slice()));
+        tables.push(SfntTable {
+            tag: hmtx_tag,
+            data: try!(reconstruct_hmtx(entry.data(stream.as_slice()),
+                                        num_h_metrics,
+                                        x_mins.as_ref().unwrap().as_slice())),
+        });
+    }
+
+    Ok(build_sfnt(flavor, tables))
+}
+
+struct SfntTable {
+    tag: FontTableTag,
+    data: Vec<u8>,
+}
+
+struct Woff2TableEntry {
+    tag: FontTableTag,
+    /// The length of the table once it is decoded.
+    length: uint,
+    /// The offset of the table in the decompressed stream.
+    offset: uint,
+    /// The length of the table in the decompressed stream.
+    transform_length: uint,
+    transformed: bool,
+}
+
+impl Woff2TableEntry {
+    fn data<'a>(&self, stream: &'a [u8]) -> &'a [u8] {
+        stream.slice(self.offset, self.offset + self.transform_length)
+    }
+}
+
+fn find_entry<'a>(entries: &'a [Woff2TableEntry], tag: FontTableTag)
+                  -> Option<&'a Woff2TableEntry> {
+    entries.iter().find(|entry| entry.tag == tag)
+}
+
+/// The tables rebuilt from a transformed WOFF2 `glyf` table.
+struct GlyfTable {
+    glyf: Vec<u8>,
+    loca: Vec<u8>,
+    /// The format of the `loca` table, as stored in the `head` table: 0 for 16-bit offsets and 1
+    /// for 32-bit offsets.
+    index_format: u16,
+    /// The minimum x coordinate of each glyph, which the `hmtx` transform may leave out.
+    x_mins: Vec<i16>,
+}
+
+/// Rebuilds the `glyf` and `loca` tables from the separate streams of a transformed `glyf` table.
+fn reconstruct_glyf(data: &[u8]) -> Result<GlyfTable, ()> {
+    let mut header = ByteReader::new(data);
+    let _reserved = try!(header.read_u16());
+    let option_flags = try!(header.read_u16());
+    let num_glyphs = try!(header.read_u16()) as uint;
+    let index_format = try!(header.read_u16());
+    let mut stream_lengths = vec!();
+    for _ in range(0u, 7) {
+        stream_lengths.push(try!(header.read_u32()) as uint);
+    }
+    let mut streams = vec!();
+    for &length in stream_lengths.iter() {
+        streams.push(ByteReader::new(try!(header.read_bytes(length))));
+    }
+    let mut instruction_stream = streams.pop().unwrap();
+    let mut bbox_stream = streams.pop().unwrap();
+    let mut composite_stream = streams.pop().unwrap();
+    let mut glyph_stream = streams.pop().unwrap();
+    let mut flag_stream = streams.pop().unwrap();
+    let mut n_points_stream = streams.pop().unwrap();
+    let mut n_contour_stream = streams.pop().unwrap();
+
+    let bitmap_length = 4 * ((num_glyphs + 31) / 32);
+    let bbox_bitmap = try!(bbox_stream.read_bytes(bitmap_length));
+    let overlap_bitmap = if option_flags & 1 != 0 {
+        Some(try!(header.read_bytes((num_glyphs + 7) / 8)))
+    } else {
+        None
+    };
+
+    let mut glyf = vec!();
+    let mut offsets = Vec::with_capacity(num_glyphs + 1);
+    let mut x_mins = Vec::with_capacity(num_glyphs);
+    for glyph_id in range(0, num_glyphs) {
+        offsets.push(glyf.len());
+
+        let explicit_bbox = if has_bit(bbox_bitmap, glyph_id) {
+            Some([
+                try!(bbox_stream.read_i16()),
+                try!(bbox_stream.read_i16()),
+                try!(bbox_stream.read_i16()),
+                try!(bbox_stream.read_i16()),
+            ])
+        } else {
+            None
+        };
+
+        let n_contours = try!(n_contour_stream.read_i16());
+        if n_contours == 0 {
+            // Empty glyphs have no data in the `glyf` table, and thus no bounding box.
+            if explicit_bbox.is_some() {
+                return Err(())
+            }
+            x_mins.push(0);
+            continue
+        }
+
+        if n_contours < 0 {
+            // The components of composite glyphs are stored as they are in the `glyf` table, but
+            // their bounding box has to be explicit.
+            let bbox = match explicit_bbox {
+                Some(bbox) => bbox,
+                None => return Err(()),
+            };
+            let start = composite_stream.position;
+            let mut has_instructions = false;
+            loop {
+                let flags = try!(composite_stream.read_u16());
+                let _glyph_index = try!(composite_stream.read_u16());
+                let mut arguments_length = if flags & ARG_1_AND_2_ARE_WORDS != 0 { 4 } else { 2 };
+                if flags & WE_HAVE_A_SCALE != 0 {
+                    arguments_length += 2
+                } else if flags & WE_HAVE_AN_X_AND_Y_SCALE != 0 {
+                    arguments_length += 4
+                } else if flags & WE_HAVE_A_TWO_BY_TWO != 0 {
+                    arguments_length += 8
+                }
+                try!(composite_stream.skip(arguments_length));
+                has_instructions = has_instructions || flags & WE_HAVE_INSTRUCTIONS != 0;
+                if flags & MORE_COMPONENTS == 0 {
+                    break
+                }
+            }
+            let components = composite_stream.data.slice(start, composite_stream.position);
+
+            push_i16(&mut glyf, n_contours);
+            for &value in bbox.iter() {
+                push_i16(&mut glyf, value);
+            }
+            glyf.push_all(components);
+            if has_instructions {
+                let instructions_length = try!(glyph_stream.read_255_u16());
+                push_u16(&mut glyf, instructions_length);
+                glyf.push_all(try!(instruction_stream.read_bytes(instructions_length as uint)));
+            }
+            x_mins.push(bbox[0]);
+        } else {
+            let mut end_points = Vec::with_capacity(n_contours as uint);
+            let mut num_points = 0u;
+            for _ in range(0, n_contours) {
+                num_points += try!(n_points_stream.read_255_u16()) as uint;
+                if num_points == 0 || num_points > 0x10000 {
+                    return Err(())
+                }
+                end_points.push((num_points - 1) as u16);
+            }
+
+            // The coordinates of the points are stored as deltas from the previous point, which
+            // is how the `glyf` table stores them as well.
+            let mut flags = Vec::with_capacity(num_points);
+            let mut deltas = Vec::with_capacity(num_points);
+            let (mut x, mut y) = (0i32, 0i32);
+            let (mut x_min, mut y_min, mut x_max, mut y_max) = (0i32, 0i32, 0i32, 0i32);
+            for point in range(0, num_points) {
+                let flag = try!(flag_stream.read_u8());
+                let (dx, dy) = try!(decode_triplet(flag, &mut glyph_stream));
+                let mut point_flags = if flag & 0x80 == 0 { ON_CURVE_POINT } else { 0 };
+                match overlap_bitmap {
+                    Some(bitmap) if point == 0 && has_bit(bitmap, glyph_id) => {
+                        point_flags |= OVERLAP_SIMPLE
+                    }
+                    _ => {}
+                }
+                flags.push(point_flags);
+                deltas.push((dx, dy));
+
+                x += dx;
+                y += dy;
+                if point == 0 {
+                    x_min = x;
+                    y_min = y;
+                    x_max = x;
+                    y_max = y;
+                } else {
+                    x_min = min(x_min, x);
+                    y_min = min(y_min, y);
+                    x_max = max(x_max, x);
+                    y_max = max(y_max, y);
+                }
+            }
+
+            let bbox = match explicit_bbox {
+                Some(bbox) => bbox,
+                None => {
+                    if !fits_in_i16(x_min) || !fits_in_i16(y_min) || !fits_in_i16(x_max) ||
+                            !fits_in_i16(y_max) {
+                        return Err(())
+                    }
+                    [x_min as i16, y_min as i16, x_max as i16, y_max as i16]
+                }
+            };
+
+            let instructions_length = try!(glyph_stream.read_255_u16());
+            let instructions = try!(instruction_stream.read_bytes(instructions_length as uint));
+
+            push_i16(&mut glyf, n_contours);
+            for &value in bbox.iter() {
+                push_i16(&mut glyf, value);
+            }
+            for &end_point in end_points.iter() {
+                push_u16(&mut glyf, end_point);
+            }
+            push_u16(&mut glyf, instructions_length);
+            glyf.push_all(instructions);
+            glyf.push_all(flags.as_slice());
+            // Without the short vector flags, every delta is stored as a 16-bit value.
+            for &(dx, _) in deltas.iter() {
+                if !fits_in_i16(dx) {
+                    return Err(())
+                }
+                push_i16(&mut glyf, dx as i16);
+            }
+            for &(_, dy) in deltas.iter() {
+                if !fits_in_i16(dy) {
+                    return Err(())
+                }
+                push_i16(&mut glyf, dy as i16);
+            }
+            x_mins.push(bbox[0]);
+        }
+
+        while glyf.len() % 4 != 0 {
+            glyf.push(0);
+        }
+    }
+    offsets.push(glyf.len());
+
+    let mut loca = vec!();
+    for &offset in offsets.iter() {
+        match index_format {
+            0 => {
+                if offset / 2 > 0xffff {
+                    return Err(())
+                }
+                push_u16(&mut loca, (offset / 2) as u16)
+            }
+            1 => push_u32(&mut loca, offset as u32),
+            _ => return Err(()),
+        }
+    }
+
+    Ok(GlyfTable {
+        glyf: glyf,
+        loca: loca,
+        index_format: index_format,
+        x_mins: x_mins,
+    })
+}
+
+/// Decodes the coordinates of a point of a transformed `glyf` table, whose flag tells how many
+/// bytes of the glyph stream they take up and how they are packed in them.
+fn decode_triplet(flag: u8, glyph_stream: &mut ByteReader) -> Result<(i32, i32), ()> {
+    fn with_sign(flag: u8, value: i32) -> i32 {
+        if flag & 1 != 0 {
+            value
+        } else {
+            -value
+        }
+    }
+
+    let flag = flag & 0x7f;
+    if flag < 10 {
+        let b0 = try!(glyph_stream.read_u8()) as i32;
+        Ok((0, with_sign(flag, (((flag & 14) as i32) << 7) + b0)))
+    } else if flag < 20 {
+        let b0 = try!(glyph_stream.read_u8()) as i32;
+        Ok((with_sign(flag, ((((flag - 10) & 14) as i32) << 7) + b0), 0))
+    } else if flag < 84 {
+        let index = (flag - 20) as i32;
+        let b0 = try!(glyph_stream.read_u8()) as i32;
+        Ok((with_sign(flag, 1 + (index & 0x30) + (b0 >> 4)),
+            with_sign(flag >> 1, 1 + ((index & 0x0c) << 2) + (b0 & 0x0f))))
+    } else if flag < 120 {
+        let index = (flag - 84) as i32;
+        let b0 = try!(glyph_stream.read_u8()) as i32;
+        let b1 = try!(glyph_stream.read_u8()) as i32;
+        Ok((with_sign(flag, 1 + ((index / 12) << 8) + b0),
+            with_sign(flag >> 1, 1 + (((index % 12) >> 2) << 8) + b1)))
+    } else if flag < 124 {
+        let b0 = try!(glyph_stream.read_u8()) as i32;
+        let b1 = try!(glyph_stream.read_u8()) as i32;
+        let b2 = try!(glyph_stream.read_u8()) as i32;
+        Ok((with_sign(flag, (b0 << 4) + (b1 >> 4)),
+            with_sign(flag >> 1, ((b1 & 0x0f) << 8) + b2)))
+    } else {
+        let b0 = try!(glyph_stream.read_u8()) as i32;
+        let b1 = try!(glyph_stream.read_u8()) as i32;
+        let b2 = try!(glyph_stream.read_u8()) as i32;
+        let b3 = try!(glyph_stream.read_u8()) as i32;
+        Ok((with_sign(flag, (b0 << 8) + b1), with_sign(flag >> 1, (b2 << 8) + b3)))
+    }
+}
+
+/// Rebuilds the `hmtx` table from a transformed one, whose left side bearings may be left out
+/// when they are the minimum x coordinates of the glyphs.
+fn reconstruct_hmtx(data: &[u8], num_h_metrics: uint, x_mins: &[i16]) -> Result<Vec<u8>, ()> {
+    let num_glyphs = x_mins.len();
+    if num_h_metrics == 0 || num_h_metrics > num_glyphs {
+        return Err(())
+    }
+
+    let mut reader = ByteReader::new(data);
+    let flags = try!(reader.read_u8());
+    let mut advances = Vec::with_capacity(num_h_metrics);
+    for _ in range(0, num_h_metrics) {
+        advances.push(try!(reader.read_u16()));
+    }
+    let mut left_side_bearings = Vec::with_capacity(num_glyphs);
+    for glyph_id in range(0, num_glyphs) {
+        // Bit 0 tells whether the bearings of the glyphs with an advance are left out, and bit 1
+        // whether those of the remaining glyphs, which share the last advance, are.
+        let absent = if glyph_id < num_h_metrics { flags & 1 != 0 } else { flags & 2 != 0 };
+        left_side_bearings.push(if absent { x_mins[glyph_id] } else { try!(reader.read_i16()) });
+    }
+
+    let mut hmtx = vec!();
+    for glyph_id in range(0, num_glyphs) {
+        if glyph_id < num_h_metrics {
+            push_u16(&mut hmtx, advances[glyph_id]);
+        }
+        push_i16(&mut hmtx, left_side_bearings[glyph_id]);
+    }
+    Ok(hmtx)
+}
+
+/// Returns the number of glyphs with an advance in the `hmtx` table, as stored in `hhea`.
+fn number_of_h_metrics(tables: &[SfntTable]) -> Result<uint, ()> {
+    let hhea_tag = font_table_tag("hhea");
+    match tables.iter().find(|table| table.tag == hhea_tag) {
+        Some(hhea) => {
+            let mut reader = ByteReader::new(hhea.data.as_slice());
+            try!(reader.skip(34));
+            Ok(try!(reader.read_u16()) as uint)
+        }
+        None => Err(()),
+    }
+}
+
+/// Makes the `head` table agree with the format of a rebuilt `loca` table.
+fn set_index_to_loc_format(tables: &mut [SfntTable], index_format: u16) {
+    let head_tag = font_table_tag("head");
+    for table in tables.iter_mut() {
+        if table.tag == head_tag && table.data.len() >= 54 {
+            let data = table.data.as_mut_slice();
+            data[50] = (index_format >> 8) as u8;
+            data[51] = index_format as u8;
+        }
+    }
+}
+
+/// Writes an sfnt font from its tables, with the table directory sorted by tag.
+fn build_sfnt(flavor: u32, mut tables: Vec<SfntTable>) -> Vec<u8> {
+    tables.sort_by(|a, b| a.tag.cmp(&b.tag));
+
+    let num_tables = tables.len();
+    let mut entry_selector = 0u;
+    while (1u << (entry_selector + 1)) <= num_tables {
+        entry_selector += 1;
+    }
+    let search_range = (1u << entry_selector) * 16;
+
+    let mut sfnt = vec!();
+    push_u32(&mut sfnt, flavor);
+    push_u16(&mut sfnt, num_tables as u16);
+    push_u16(&mut sfnt, search_range as u16);
+    push_u16(&mut sfnt, entry_selector as u16);
+    push_u16(&mut sfnt, (num_tables * 16 - search_range) as u16);
+
+    let mut offset = 12 + 16 * num_tables;
+    for table in tables.iter() {
+        push_u32(&mut sfnt, table.tag);
+        push_u32(&mut sfnt, table_checksum(table.data.as_slice()));
+        push_u32(&mut sfnt, offset as u32);
+        push_u32(&mut sfnt, table.data.len() as u32);
+        offset += (table.data.len() + 3) & !3;
+    }
+    for table in tables.iter() {
+        sfnt.push_all(table.data.as_slice());
+        while sfnt.len() % 4 != 0 {
+            sfnt.push(0);
+        }
+    }
+    sfnt
+}
+
+/// The sum of the big-endian 32-bit words of a table, padded with zeros.
+fn table_checksum(data: &[u8]) -> u32 {
+    data.iter().enumerate().fold(0u32, |sum, (index, &byte)| {
+        sum + ((byte as u32) << (24 - 8 * (index % 4)))
+    })
+}
+
+/// Inflates a table that should decode to `length` bytes. At most one byte more than that is ever
+/// inflated, so that a small stream which would inflate to something huge is rejected cheaply.
+fn zlib_decompress(compressed: &[u8], length: uint) -> Result<Vec<u8>, ()> {
+    let mut decoder = ZlibDecoder::new(BufReader::new(compressed));
+    let mut data = Vec::with_capacity(length + 1);
+    match decoder.push_at_least(length + 1, length + 1, &mut data) {
+        // The stream holds more data than the table directory says it should.
+        Ok(_) => Err(()),
+        Err(ref e) if e.kind == EndOfFile && data.len() == length => Ok(data),
+        Err(_) => Err(()),
+    }
+}
+
+#[cfg(feature = "woff2")]
+fn brotli_decompress(compressed: &[u8], length: uint) -> Result<Vec<u8>, ()> {
+    let mut data = Vec::from_elem(length, 0u8);
+    let mut decoded_length = length as size_t;
+    let result = unsafe {
+        BrotliDecoderDecompress(compressed.len() as size_t,
+                                compressed.as_ptr(),
+                                &mut decoded_length,
+                                data.as_mut_ptr())
+    };
+    if result == BROTLI_DECODER_RESULT_SUCCESS && decoded_length as uint == length {
+        Ok(data)
+    } else {
+        Err(())
+    }
+}
+
+/// Without the `woff2` feature there is no Brotli decoder, so WOFF2 fonts fail to decode and the
+/// next source of their `@font-face` rule is tried instead.
+#[cfg(not(feature = "woff2"))]
+fn brotli_decompress(_: &[u8], _: uint) -> Result<Vec<u8>, ()> {
+    debug!("WOFF2 support was not compiled in; build with the `woff2` feature to enable it");
+    Err(())
+}
+
+/// Returns whether the bit of the given glyph is set in a bitmap of a transformed `glyf` table.
+fn has_bit(bitmap: &[u8], glyph_id: uint) -> bool {
+    bitmap[glyph_id >> 3] & (0x80 >> (glyph_id & 7)) != 0
+}
+
+fn fits_in_i16(value: i32) -> bool {
+    value >= -0x8000 && value <= 0x7fff
+}
+
+fn push_u16(data: &mut Vec<u8>, value: u16) {
+    data.push((value >> 8) as u8);
+    data.push(value as u8);
+}
+
+fn push_i16(data: &mut Vec<u8>, value: i16) {
+    push_u16(data, value as u16)
+}
+
+fn push_u32(data: &mut Vec<u8>, value: u32) {
+    push_u16(data, (value >> 16) as u16);
+    push_u16(data, value as u16);
+}
+
+/// Reads big-endian values from font data, failing rather than reading past its end.
+struct ByteReader<'a> {
+    data: &'a [u8],
+    position: uint,
+}
+
+impl<'a> ByteReader<'a> {
+    fn new(data: &'a [u8]) -> ByteReader<'a> {
+        ByteReader {
+            data: data,
+            position: 0,
+        }
+    }
+
+    fn read_bytes(&mut self, length: uint) -> Result<&'a [u8], ()> {
+        if length > self.data.len() - self.position {
+            return Err(())
+        }
+        let bytes = self.data.slice(self.position, self.position + length);
+        self.position += length;
+        Ok(bytes)
+    }
+
+    fn skip(&mut self, length: uint) -> Result<(), ()> {
+        try!(self.read_bytes(length));
+        Ok(())
+    }
+
+    fn read_u8(&mut self) -> Result<u8, ()> {
+        Ok(try!(self.read_bytes(1))[0])
+    }
+
+    fn read_u16(&mut self) -> Result<u16, ()> {
+        let bytes = try!(self.read_bytes(2));
+        Ok(((bytes[0] as u16) << 8) | (bytes[1] as u16))
+    }
+
+    fn read_i16(&mut self) -> Result<i16, ()> {
+        Ok(try!(self.read_u16()) as i16)
+    }
+
+    fn read_u32(&mut self) -> Result<u32, ()> {
+        let high = try!(self.read_u16()) as u32;
+        let low = try!(self.read_u16()) as u32;
+        Ok((high << 16) | low)
+    }
+
+    /// Reads a WOFF2 `UIntBase128`: a 32-bit value in up to five bytes of seven bits each, the
+    /// most significant first.
+    fn read_base128(&mut self) -> Result<u32, ()> {
+        let mut value = 0u32;
+        for index in range(0u, 5) {
+            let byte = try!(self.read_u8());
+            // Neither leading zeros nor values that overflow are allowed.
+            if (index == 0 && byte == 0x80) || value & 0xfe000000 != 0 {
+                return Err(())
+            }
+            value = (value << 7) | (byte & 0x7f) as u32;
+            if byte & 0x80 == 0 {
+                return Ok(value)
+            }
+        }
+        Err(())
+    }
+
+    /// Reads a WOFF2 `255UInt16`: a 16-bit value in one to three bytes, depending on how large
+    /// it is.
+    fn read_255_u16(&mut self) -> Result<u16, ()> {
+        match try!(self.read_u8()) {
+            253 => self.read_u16(),
+            254 => Ok(try!(self.read_u8()) as u16 + 253 * 2),
+            255 => Ok(try!(self.read_u8()) as u16 + 253),
+            code => Ok(code as u16),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ByteReader, decode_woff, decode_woff2, reconstruct_glyf, reconstruct_hmtx};
+    use super::{sfnt_data_for_web_font, push_u16, push_i16, push_u32};
+    use font::font_table_tag;
+
+    static AHEM_TTF: &'static [u8] = include_bin!("../../tests/ref/fonts/ahem/ahem.ttf");
+    static AHEM_WOFF: &'static [u8] = include_bin!("../../tests/ref/fonts/ahem/ahem.woff");
+    static AHEM_WOFF2: &'static [u8] = include_bin!("../../tests/ref/fonts/ahem/ahem.woff2");
+
+    /// 4096 zeros, compressed with zlib.
+    static ZLIB_ZEROS: [u8, ..26] = [
+        0x78, 0xda, 0xed, 0xc1, 0x01, 0x0d, 0x00, 0x00, 0x00, 0xc2, 0xa0, 0xf7, 0x4f, 0x6d, 0x0f,
+        0x07, 0x14, 0x00, 0x00, 0x00, 0xf0, 0x6e, 0x10, 0x00, 0x00, 0x01,
+    ];
+
+    /// Returns the tag and data of each table of an sfnt font, in the order of its directory.
+    fn sfnt_tables(sfnt: &[u8]) -> Vec<(u32, Vec<u8>)> {
+        let mut reader = ByteReader::new(sfnt);
+        let _flavor = reader.read_u32().unwrap();
+        let num_tables = reader.read_u16().unwrap();
+        reader.skip(6).unwrap();
+        range(0, num_tables).map(|_| {
+            let tag = reader.read_u32().unwrap();
+            let _checksum = reader.read_u32().unwrap();
+            let offset = reader.read_u32().unwrap() as uint;
+            let length = reader.read_u32().unwrap() as uint;
+            (tag, sfnt.slice(offset, offset + length).to_vec())
+        }).collect()
+    }
+
+    /// Builds a WOFF font with a single table.
+    fn single_table_woff(compressed: &[u8], length: uint) -> Vec<u8> {
+        let mut woff = vec!();
+        push_u32(&mut woff, 0x774f4646);
+        push_u32(&mut woff, 0x00010000);
+        push_u32(&mut woff, (44 + 20 + compressed.len()) as u32);
+        push_u16(&mut woff, 1);
+        woff.grow(30, 0);
+        push_u32(&mut woff, font_table_tag("name"));
+        push_u32(&mut woff, 64);
+        push_u32(&mut woff, compressed.len() as u32);
+        push_u32(&mut woff, length as u32);
+        push_u32(&mut woff, 0);
+        woff.push_all(compressed);
+        woff
+    }
+
+    /// Sets the length field of the header of a WOFF or WOFF2 font.
+    fn set_length(font: &mut Vec<u8>, length: uint) {
+        let mut field = vec!();
+        push_u32(&mut field, length as u32);
+        for (index, &byte) in field.iter().enumerate() {
+            font.as_mut_slice()[8 + index] = byte;
+        }
+    }
+
+    #[test]
+    fn test_sfnt_passes_through() {
+        assert_eq!(sfnt_data_for_web_font(AHEM_TTF.to_vec()), Ok(AHEM_TTF.to_vec()));
+        assert!(sfnt_data_for_web_font(vec!(0, 1)).is_err());
+    }
+
+    #[test]
+    fn test_decode_woff() {
+        let sfnt = decode_woff(AHEM_WOFF).unwrap();
+        assert_eq!(sfnt_tables(sfnt.as_slice()), sfnt_tables(AHEM_TTF));
+        assert_eq!(sfnt_data_for_web_font(AHEM_WOFF.to_vec()), Ok(sfnt));
+    }
+
+    #[test]
+    fn test_decode_truncated_woff() {
+        for &length in [0, 4, 13, 44, 100, AHEM_WOFF.len() / 2, AHEM_WOFF.len() - 4].iter() {
+            let mut woff = AHEM_WOFF.slice_to(length).to_vec();
+            assert!(decode_woff(woff.as_slice()).is_err());
+            if length >= 12 {
+                // Even when the header agrees with the truncated length.
+                set_length(&mut woff, length);
+                assert!(decode_woff(woff.as_slice()).is_err());
+            }
+        }
+    }
+
+    #[test]
+    fn test_decode_woff_with_bad_table_lengths() {
+        assert!(decode_woff(single_table_woff(ZLIB_ZEROS.as_slice(), 4096).as_slice()).is_ok());
+        // The table inflates to more data than it claims to hold.
+        assert!(decode_woff(single_table_woff(ZLIB_ZEROS.as_slice(), 100).as_slice()).is_err());
+        // Or to less.
+        assert!(decode_woff(single_table_woff(ZLIB_ZEROS.as_slice(), 5000).as_slice()).is_err());
+        // Compressed tables can't be larger than the table itself.
+        assert!(decode_woff(single_table_woff(ZLIB_ZEROS.as_slice(), 20).as_slice()).is_err());
+    }
+
+    #[test]
+    fn test_decode_woff_with_corrupt_table() {
+        let mut compressed = ZLIB_ZEROS.to_vec();
+        compressed.as_mut_slice()[3] = 0xff;
+        assert!(decode_woff(single_table_woff(compressed.as_slice(), 4096).as_slice()).is_err());
+    }
+
+    #[cfg(feature = "woff2")]
+    #[test]
+    fn test_decode_woff2() {
+        let sfnt = decode_woff2(AHEM_WOFF2).unwrap();
+        assert_eq!(sfnt_tables(sfnt.as_slice()), sfnt_tables(AHEM_TTF));
+    }
+
+    #[cfg(not(feature = "woff2"))]
+    #[test]
+    fn test_decode_woff2_without_brotli() {
+        assert!(decode_woff2(AHEM_WOFF2).is_err());
+    }
+
+    #[test]
+    fn test_decode_truncated_woff2() {
+        for &length in [0, 4, 13, 48, 60, AHEM_WOFF2.len() / 2, AHEM_WOFF2.len() - 1].iter() {
+            let mut woff2 = AHEM_WOFF2.slice_to(length).to_vec();
+            assert!(decode_woff2(woff2.as_slice()).is_err());
+            if length >= 12 {
+                set_length(&mut woff2, length);
+                assert!(decode_woff2(woff2.as_slice()).is_err());
+            }
+        }
+    }
+
+    #[test]
+    fn test_read_base128() {
+        assert_eq!(ByteReader::new([0x3f]).read_base128(), Ok(0x3f));
+        assert_eq!(ByteReader::new([0x81, 0x00]).read_base128(), Ok(0x80));
+        // Leading zeros.
+        assert!(ByteReader::new([0x80, 0x01]).read_base128().is_err());
+        // More than 32 bits.
+        assert!(ByteReader::new([0x90, 0x80, 0x80, 0x80, 0x00]).read_base128().is_err());
+        // More than five bytes.
+        assert!(ByteReader::new([0x81, 0x80, 0x80, 0x80, 0x80, 0x00]).read_base128().is_err());
+        // Truncated.
+        assert!(ByteReader::new([0x81]).read_base128().is_err());
+    }
+
+    #[test]
+    fn test_read_255_u16() {
+        assert_eq!(ByteReader::new([252]).read_255_u16(), Ok(252));
+        assert_eq!(ByteReader::new([255, 0]).read_255_u16(), Ok(253));
+        assert_eq!(ByteReader::new([254, 0]).read_255_u16(), Ok(506));
+        assert_eq!(ByteReader::new([253, 0x12, 0x34]).read_255_u16(), Ok(0x1234));
+        assert!(ByteReader::new([253, 0x12]).read_255_u16().is_err());
+    }
+
+    /// A transformed `glyf` table of an empty glyph and a 100 by 100 square.
+    fn transformed_glyf() -> Vec<u8> {
+        let n_contour_stream = [0, 0, 0, 1];
+        let n_points_stream = [4];
+        let flag_stream = [1, 1, 11, 0];
+        // The coordinates of the points, then the length of the instructions of the square.
+        let glyph_stream = [0, 100, 100, 100, 0];
+        let bbox_stream = [0, 0, 0, 0];
+
+        let mut data = vec!();
+        push_u16(&mut data, 0);
+        push_u16(&mut data, 0);
+        push_u16(&mut data, 2);
+        push_u16(&mut data, 0);
+        for &length in [4u, 1, 4, 5, 0, 4, 0].iter() {
+            push_u32(&mut data, length as u32);
+        }
+        data.push_all(n_contour_stream);
+        data.push_all(n_points_stream);
+        data.push_all(flag_stream);
+        data.push_all(glyph_stream);
+        data.push_all(bbox_stream);
+        data
+    }
+
+    #[test]
+    fn test_reconstruct_glyf() {
+        let table = reconstruct_glyf(transformed_glyf().as_slice()).unwrap();
+
+        let mut glyf = vec!();
+        push_i16(&mut glyf, 1);
+        for &value in [0i16, 0, 100, 100].iter() {
+            push_i16(&mut glyf, value);
+        }
+        push_u16(&mut glyf, 3);
+        push_u16(&mut glyf, 0);
+        glyf.push_all([1, 1, 1, 1]);
+        for &value in [0i16, 0, 100, 0, 0, 100, 0, -100].iter() {
+            push_i16(&mut glyf, value);
+        }
+        glyf.push_all([0, 0]);
+        assert_eq!(table.glyf, glyf);
+
+        let mut loca = vec!();
+        for &offset in [0u16, 0, 18].iter() {
+            push_u16(&mut loca, offset);
+        }
+        assert_eq!(table.loca, loca);
+        assert_eq!(table.index_format, 0);
+        assert_eq!(table.x_mins, vec!(0, 0));
+    }
+
+    #[test]
+    fn test_reconstruct_malformed_glyf() {
+        let data = transformed_glyf();
+        for length in range(0, data.len()) {
+            assert!(reconstruct_glyf(data.slice_to(length)).is_err());
+        }
+
+        // Contours need at least one point.
+        let mut data = transformed_glyf();
+        data.as_mut_slice()[40] = 0;
+        assert!(reconstruct_glyf(data.as_slice()).is_err());
+
+        // Nor can `loca` have an unknown format.
+        let mut data = transformed_glyf();
+        data.as_mut_slice()[7] = 2;
+        assert!(reconstruct_glyf(data.as_slice()).is_err());
+    }
+
+    #[test]
+    fn test_reconstruct_hmtx() {
+        let mut hmtx = vec!();
+        push_u16(&mut hmtx, 500);
+        push_i16(&mut hmtx, 0);
+        push_i16(&mut hmtx, 5);
+
+        // Both bearings left out.
+        let data = [3, 0x01, 0xf4];
+        assert_eq!(reconstruct_hmtx(data, 1, [0, 5]), Ok(hmtx.clone()));
+
+        // Both bearings given.
+        let data = [0, 0x01, 0xf4, 0, 0, 0, 5];
+        assert_eq!(reconstruct_hmtx(data, 1, [7, 7]), Ok(hmtx));
+    }
+
+    #[test]
+    fn test_reconstruct_malformed_hmtx() {
+        let data = [0, 0x01, 0xf4, 0, 0, 0, 5];
+        for length in range(0, data.len()) {
+            assert!(reconstruct_hmtx(data.slice_to(length), 1, [0, 5]).is_err());
+        }
+        assert!(reconstruct_hmtx(data, 0, [0, 5]).is_err());
+        assert!(reconstruct_hmtx(data, 3, [0, 5]).is_err());
+    }
+}
@@ -0,0 +1,502 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! CSS transitions and animations.
+//!
+//! The animation manager remembers the transitions and animations running on each node. Every
+//! time a node is styled, the manager updates them from the new style and applies them on top
+//! of it, at the animation time that script sent with the reflow. Layout dirties the nodes with
+//! running transitions and animations at each reflow, so that they are styled again.
+
+use context::SharedLayoutContext;
+use util::OpaqueNodeMethods;
+
+use gfx::display_list::OpaqueNode;
+use script_traits::{AnimationEndEvent, AnimationEnded, TransitionEnded};
+use std::collections::{HashMap, HashSet};
+use std::f64;
+use std::mem;
+use style::{AnimatableLonghand, ComputedValues, DeclarationBlock, Keyframe, KeyframesRule};
+use style::{Stylist, cascade};
+use style::computed::TimingFunction;
+use style::computed_values::{animation_direction, animation_fill_mode, animation_iteration_count};
+use style::computed_values::{animation_play_state, transition_property};
+use sync::Arc;
+
+/// A transition of one property of a node.
+struct Transition {
+    property: AnimatableLonghand,
+    /// The style the property transitions from.
+    from: Arc<ComputedValues>,
+    /// The style the property transitions to.
+    to: Arc<ComputedValues>,
+    /// When the transition starts, after its delay, in seconds.
+    start_time: f64,
+    duration: f64,
+    timing_function: TimingFunction,
+}
+
+/// A keyframe animation of a node.
+struct Animation {
+    /// The name of the `@keyframes` rule.
+    name: String,
+    /// Where the animation is in `animation-name`, to find its values in the lists of the other
+    /// `animation-*` properties.
+    index: uint,
+    /// When the animation started, before its delay, in seconds. It moves forward while the
+    /// animation is paused.
+    start_time: f64,
+    /// When the animation was paused, if it is.
+    paused_time: Option<f64>,
+    /// Whether the animation reached its end, and `animationend` was fired.
+    finished: bool,
+}
+
+/// The transitions and animations of one node.
+struct NodeAnimations {
+    /// The style of the node before the transitions and animations were applied, to tell the
+    /// changes that start transitions from those that the animations make.
+    base_style: Arc<ComputedValues>,
+    transitions: Vec<Transition>,
+    animations: Vec<Animation>,
+}
+
+impl NodeAnimations {
+    fn is_running(&self) -> bool {
+        !self.transitions.is_empty() || self.animations.iter().any(|animation| {
+            !animation.finished && animation.paused_time.is_none()
+        })
+    }
+}
+
+pub struct AnimationManager {
+    nodes: HashMap<OpaqueNode, NodeAnimations>,
+    /// The transitions and animations that finished since the last reflow.
+    events: Vec<AnimationEndEvent>,
+    /// The earliest time at which an animated image shows its next frame, in seconds.
+    next_image_frame_time: Option<f64>,
+}
+
+impl AnimationManager {
+    pub fn new() -> AnimationManager {
+        AnimationManager {
+            nodes: HashMap::new(),
+            events: vec!(),
+            next_image_frame_time: None,
+        }
+    }
+
+    /// Whether some node needs to be styled again as time passes.
+    pub fn is_running(&self) -> bool {
+        self.nodes.values().any(|node_animations| node_animations.is_running())
+    }
+
+    /// Whether the node needs to be styled again as time passes.
+    pub fn is_node_running(&self, node: &OpaqueNode) -> bool {
+        self.nodes.get(node).map_or(false, |node_animations| node_animations.is_running())
+    }
+
+    /// Forgets the nodes that are no longer in the document.
+    pub fn retain_nodes(&mut self, live_nodes: &HashSet<OpaqueNode>) {
+        let dead_nodes: Vec<OpaqueNode> = self.nodes.keys().filter(|node| {
+            !live_nodes.contains(*node)
+        }).map(|node| node.clone()).collect();
+        for node in dead_nodes.iter() {
+            self.nodes.remove(node);
+        }
+    }
+
+    /// Returns the `transitionend` and `animationend` events to fire.
+    pub fn take_events(&mut self) -> Vec<AnimationEndEvent> {
+        mem::replace(&mut self.events, vec!())
+    }
+
+    /// Asks for a reflow at `time`, in seconds, when an animated image shows its next frame.
+    pub fn request_image_frame(&mut self, time: f64) {
+        self.next_image_frame_time = match self.next_image_frame_time {
+            Some(next_time) if next_time <= time => Some(next_time),
+            _ => Some(time),
+        }
+    }
+
+    /// Returns the earliest time at which an animated image asked for a reflow since the last
+    /// reflow, in seconds.
+    pub fn take_next_image_frame_time(&mut self) -> Option<f64> {
+        self.next_image_frame_time.take()
+    }
+
+    /// Updates the transitions and animations of the node now that its style changed from
+    /// `old_style` to `base_style`, and returns the style with them applied, or `None` if
+    /// nothing is animated.
+    fn update_node(&mut self,
+                   node: OpaqueNode,
+                   old_style: Option<&Arc<ComputedValues>>,
+                   base_style: &Arc<ComputedValues>,
+                   parent_style: Option<&ComputedValues>,
+                   applicable_declarations: &[DeclarationBlock],
+                   stylist: &Stylist,
+                   now: f64)
+                   -> Option<ComputedValues> {
+        let mut node_animations = match self.nodes.pop(&node) {
+            Some(node_animations) => node_animations,
+            None => {
+                NodeAnimations {
+                    base_style: match old_style {
+                        Some(old_style) => old_style.clone(),
+                        None => base_style.clone(),
+                    },
+                    transitions: vec!(),
+                    animations: vec!(),
+                }
+            }
+        };
+        if !style_is_animated(&**base_style) {
+            return None
+        }
+
+        update_animations(&mut node_animations.animations, &**base_style, now);
+        update_transitions(&mut node_animations,
+                           old_style.unwrap_or(base_style),
+                           base_style,
+                           now);
+        node_animations.base_style = base_style.clone();
+
+        let mut style = (**base_style).clone();
+        let mut animated = false;
+        let animation = base_style.get_animation();
+        for running in node_animations.animations.iter_mut() {
+            let i = running.index;
+            let keyframes = match stylist.get_keyframes(running.name.as_slice()) {
+                Some(keyframes) => keyframes,
+                None => continue,
+            };
+            let time = running.paused_time.unwrap_or(now) - running.start_time;
+            let duration = nth(&animation.animation_duration, i).seconds();
+            let delay = nth(&animation.animation_delay, i).seconds();
+            let iterations = match *nth(&animation.animation_iteration_count, i) {
+                animation_iteration_count::Infinite => f64::INFINITY,
+                animation_iteration_count::Count(count) => count,
+            };
+            let fill_mode = *nth(&animation.animation_fill_mode, i);
+            let active_duration = if duration > 0.0 { duration * iterations } else { 0.0 };
+
+            let active_time = time - delay;
+            let (iteration, progress) = if active_time < 0.0 {
+                match fill_mode {
+                    animation_fill_mode::backwards | animation_fill_mode::both => (0.0, 0.0),
+                    _ => continue,
+                }
+            } else if active_time >= active_duration {
+                if !running.finished {
+                    running.finished = true;
+                    self.events.push(AnimationEnded(node.to_untrusted_node_address(),
+                                                    running.name.clone(),
+                                                    active_duration));
+                }
+                match fill_mode {
+                    animation_fill_mode::forwards | animation_fill_mode::both => {
+                        if iterations == 0.0 {
+                            (0.0, 0.0)
+                        } else if !iterations.is_finite() {
+                            // Only animations without a duration end after infinite iterations.
+                            (0.0, 1.0)
+                        } else if iterations.fract() == 0.0 {
+                            (iterations - 1.0, 1.0)
+                        } else {
+                            (iterations.floor(), iterations.fract())
+                        }
+                    }
+                    _ => continue,
+                }
+            } else {
+                let overall_progress = active_time / duration;
+                (overall_progress.floor(), overall_progress.fract())
+            };
+
+            let reversed = match *nth(&animation.animation_direction, i) {
+                animation_direction::normal => false,
+                animation_direction::reverse => true,
+                animation_direction::alternate => iteration % 2.0 == 1.0,
+                animation_direction::alternate_reverse => iteration % 2.0 == 0.0,
+            };
+            let progress = if reversed { 1.0 - progress } else { progress };
+
+            let timing_function = nth(&animation.animation_timing_function, i);
+            animated = apply_keyframes(&mut style,
+                                       &**base_style,
+                                       keyframes,
+                                       progress,
+                                       timing_function,
+                                       parent_style,
+                                       applicable_declarations) || animated;
+        }
+
+        // Transitions apply over animations.
+        let mut finished_transitions = vec!();
+        for (i, transition) in node_animations.transitions.iter().enumerate() {
+            let progress = if transition.duration > 0.0 {
+                ((now - transition.start_time) / transition.duration).max(0.0)
+            } else {
+                1.0
+            };
+            if progress >= 1.0 {
+                finished_transitions.push(i);
+                self.events.push(TransitionEnded(node.to_untrusted_node_address(),
+                                                 transition.property.name().to_string(),
+                                                 transition.duration));
+                continue
+            }
+            let progress = transition.timing_function.solve(progress);
+            animated = transition.property.interpolate(&mut style,
+                                                       &*transition.from,
+                                                       &*transition.to,
+                                                       progress) || animated;
+        }
+        for &i in finished_transitions.iter().rev() {
+            node_animations.transitions.remove(i);
+        }
+
+        self.nodes.insert(node, node_animations);
+        if animated {
+            Some(style)
+        } else {
+            None
+        }
+    }
+}
+
+/// Whether the style may start transitions or run animations.
+pub fn style_is_animated(style: &ComputedValues) -> bool {
+    let animation = style.get_animation();
+    animation.animation_name.iter().any(|name| name.is_some()) ||
+        (!animation.transition_property.is_empty() &&
+         animation.transition_duration.iter().any(|duration| duration.seconds() > 0.0))
+}
+
+/// Updates the transitions and animations of the node now that its style changed from
+/// `old_style` to `base_style`, and returns the style with them applied, or `None` if nothing
+/// is animated.
+pub fn animate_style(context: &SharedLayoutContext,
+                     node: OpaqueNode,
+                     old_style: Option<&Arc<ComputedValues>>,
+                     base_style: &Arc<ComputedValues>,
+                     parent_style: Option<&ComputedValues>,
+                     applicable_declarations: &[DeclarationBlock])
+                     -> Option<Arc<ComputedValues>> {
+    // Transitions and animations only run on nodes whose style says so, which is quick to check
+    // without locking the manager.
+    let was_animated = old_style.map_or(false, |old_style| style_is_animated(&**old_style));
+    if !was_animated && !style_is_animated(&**base_style) {
+        return None
+    }
+
+    let stylist = unsafe { &*context.stylist };
+    let mut manager = context.animation_manager.lock();
+    manager.update_node(node,
+                        old_style,
+                        base_style,
+                        parent_style,
+                        applicable_declarations,
+                        stylist,
+                        context.animation_time).map(|style| Arc::new(style))
+}
+
+/// The value at `index` of one of the `animation-*` or `transition-*` lists, which repeat to
+/// match the length of `animation-name` or `transition-property`.
+fn nth<T>(values: &Vec<T>, index: uint) -> &T {
+    &values[index % values.len()]
+}
+
+/// Starts the animations that were added to `animation-name`, drops the ones that were removed
+/// from it, and pauses or resumes them.
+fn update_animations(animations: &mut Vec<Animation>, style: &ComputedValues, now: f64) {
+    let animation = style.get_animation();
+    let mut old_animations = mem::replace(animations, vec!());
+    for (i, name) in animation.animation_name.iter().enumerate() {
+        let name = match *name {
+            Some(ref name) => name,
+            None => continue,
+        };
+        let mut running = match old_animations.iter().position(|old| old.name == *name) {
+            Some(position) => old_animations.remove(position).unwrap(),
+            None => {
+                Animation {
+                    name: name.clone(),
+                    index: i,
+                    start_time: now,
+                    paused_time: None,
+                    finished: false,
+                }
+            }
+        };
+        running.index = i;
+        match (*nth(&animation.animation_play_state, i), running.paused_time) {
+            (animation_play_state::paused, None) => running.paused_time = Some(now),
+            (animation_play_state::running, Some(paused_time)) => {
+                running.start_time += now - paused_time;
+                running.paused_time = None;
+            }
+            _ => {}
+        }
+        animations.push(running)
+    }
+}
+
+/// Starts transitions for the properties whose value changed, and cancels those of the
+/// properties that no longer transition.
+fn update_transitions(node_animations: &mut NodeAnimations,
+                      old_style: &Arc<ComputedValues>,
+                      base_style: &Arc<ComputedValues>,
+                      now: f64) {
+    let animation = base_style.get_animation();
+    for property in AnimatableLonghand::all().into_iter() {
+        // The last item of `transition-property` that names the property wins.
+        let index = animation.transition_property.iter().rposition(|item| {
+            match *item {
+                transition_property::AllProperties => true,
+                transition_property::NamedProperty(ref name) => {
+                    AnimatableLonghand::from_name(name.as_slice()).contains(&property)
+                }
+            }
+        });
+        let running = node_animations.transitions.iter().position(|transition| {
+            transition.property == property
+        });
+        let index = match index {
+            Some(index) if nth(&animation.transition_duration, index).seconds() > 0.0 => index,
+            _ => {
+                match running {
+                    Some(running) => {
+                        node_animations.transitions.remove(running);
+                    }
+                    None => {}
+                }
+                continue
+            }
+        };
+
+        match running {
+            // Keep the transition going if it still goes to the right value.
+            Some(running) if !property.differs(&*node_animations.transitions[running].to,
+                                               &**base_style) => continue,
+            Some(running) => {
+                node_animations.transitions.remove(running);
+            }
+            None if !property.differs(&*node_animations.base_style, &**base_style) => continue,
+            None => {}
+        }
+
+        // Start from the value the property has on screen, which may be halfway through the
+        // transition that was just cancelled.
+        let mut scratch = (**base_style).clone();
+        if !property.interpolate(&mut scratch, &**old_style, &**base_style, 0.0) {
+            continue
+        }
+        node_animations.transitions.push(Transition {
+            property: property,
+            from: old_style.clone(),
+            to: base_style.clone(),
+            start_time: now + nth(&animation.transition_delay, index).seconds(),
+            duration: nth(&animation.transition_duration, index).seconds(),
+            timing_function: nth(&animation.transition_timing_function, index).clone(),
+        })
+    }
+}
+
+/// Applies the keyframes of an animation at `progress` through the current iteration.
+/// Returns false if no property was animated.
+fn apply_keyframes(style: &mut ComputedValues,
+                   base_style: &ComputedValues,
+                   keyframes: &KeyframesRule,
+                   progress: f64,
+                   timing_function: &TimingFunction,
+                   parent_style: Option<&ComputedValues>,
+                   applicable_declarations: &[DeclarationBlock])
+                   -> bool {
+    let groups = keyframe_groups(keyframes);
+    let next = match groups.iter().position(|group| group.offset > progress) {
+        Some(next) => next,
+        None => groups.len() - 1,
+    };
+    let previous = if next == 0 { 0 } else { next - 1 };
+    let (from, to) = (&groups[previous], &groups[next]);
+
+    let from_style = from.style(base_style, parent_style, applicable_declarations);
+    let to_style = to.style(base_style, parent_style, applicable_declarations);
+    let progress = if to.offset > from.offset {
+        (progress - from.offset) / (to.offset - from.offset)
+    } else {
+        1.0
+    };
+    let timing_function = from.keyframes.iter().rev().filter_map(|keyframe| {
+        keyframe.timing_function.as_ref()
+    }).next().unwrap_or(timing_function);
+    let progress = timing_function.solve(progress);
+
+    let mut animated = false;
+    for property in AnimatableLonghand::all().into_iter() {
+        if property.differs(&from_style, &to_style) || property.differs(&from_style, base_style) {
+            animated = property.interpolate(style, &from_style, &to_style, progress) || animated;
+        }
+    }
+    animated
+}
+
+/// The keyframes at one offset. The declarations of several keyframes with the same offset
+/// cascade in source order.
+struct KeyframeGroup<'a> {
+    offset: f64,
+    keyframes: Vec<&'a Keyframe>,
+}
+
+impl<'a> KeyframeGroup<'a> {
+    /// The style of the node at this keyframe: the properties it doesn't declare keep their
+    /// value from the base style.
+    fn style(&self,
+             base_style: &ComputedValues,
+             parent_style: Option<&ComputedValues>,
+             applicable_declarations: &[DeclarationBlock])
+             -> ComputedValues {
+        if self.keyframes.is_empty() {
+            return base_style.clone()
+        }
+        // FIXME: `!important` declarations should win over animations.
+        let mut declarations = applicable_declarations.to_vec();
+        for keyframe in self.keyframes.iter() {
+            declarations.push(DeclarationBlock::from_declarations(
+                keyframe.declarations.normal.clone()));
+        }
+        let (style, _) = cascade(declarations.as_slice(), false, parent_style, None);
+        style
+    }
+}
+
+/// Groups the keyframes by offset, in order, with empty groups for `from` and `to` if the rule
+/// doesn't have them, as these take the base style.
+fn keyframe_groups<'a>(keyframes: &'a KeyframesRule) -> Vec<KeyframeGroup<'a>> {
+    let mut groups: Vec<KeyframeGroup> = vec!();
+    for keyframe in keyframes.keyframes.iter() {
+        for &offset in keyframe.offsets.iter() {
+            match groups.iter_mut().find(|group| group.offset == offset) {
+                Some(group) => {
+                    group.keyframes.push(keyframe);
+                    continue
+                }
+                None => {}
+            }
+            groups.push(KeyframeGroup {
+                offset: offset,
+                keyframes: vec![keyframe],
+            })
+        }
+    }
+    groups.sort_by(|a, b| a.offset.partial_cmp(&b.offset).unwrap());
+    if groups.first().map_or(true, |group| group.offset > 0.0) {
+        groups.insert(0, KeyframeGroup { offset: 0.0, keyframes: vec!() })
+    }
+    if groups.last().map_or(true, |group| group.offset < 1.0) {
+        groups.push(KeyframeGroup { offset: 1.0, keyframes: vec!() })
+    }
+    groups
+}
@@ -39,9 +39,10 @@ use flow::{HAS_LEFT_FLOATED_DESCENDANTS, HAS_RIGHT_FLOATED_DESCENDANTS};
 use flow::{IMPACTED_BY_LEFT_FLOATS, IMPACTED_BY_RIGHT_FLOATS};
 use flow::{LAYERS_NEEDED_FOR_DESCENDANTS, NEEDS_LAYER};
 use flow::{IS_ABSOLUTELY_POSITIONED};
-use flow::{CLEARS_LEFT, CLEARS_RIGHT};
+use flow::{CLEARS_LEFT, CLEARS_RIGHT, PARENT_IS_RTL};
 use flow;
-use fragment::{Fragment, ImageFragment, InlineBlockFragment, FragmentBoundsIterator};
+use fragment::{CanvasFragment, Fragment, ImageFragment, InlineBlockFragment};
+use fragment::FragmentBoundsIterator;
 use fragment::ScannedTextFragment;
 use incremental::{REFLOW, REFLOW_OUT_OF_FLOW};
 use layout_debug;
@@ -51,6 +52,7 @@ use table::ColumnInlineSize;
 use wrapper::ThreadSafeLayoutNode;
 
 use geom::Size2D;
+use gfx::display_list::DisplayList;
 use serialize::{Encoder, Encodable};
 use servo_msg::compositor_msg::LayerId;
 use servo_util::geometry::{Au, MAX_AU, MAX_RECT, ZERO_POINT};
@@ -61,7 +63,7 @@ use std::fmt;
 use style::ComputedValues;
 use style::computed_values::{LPA_Auto, LPA_Length, LPA_Percentage, LPN_Length, LPN_None};
 use style::computed_values::{LPN_Percentage, LP_Length, LP_Percentage, box_sizing, display, float};
-use style::computed_values::{overflow, position};
+use style::computed_values::{direction, overflow, position, text_align};
 use sync::Arc;
 
 /// Information specific to floated blocks.
@@ -549,6 +551,10 @@ pub struct BlockFlow {
     /// Additional floating flow members.
     pub float: Option<Box<FloatedBlockInfo>>,
 
+    /// The content inline-size that the flex container of this flow assigned to it, if this is a
+    /// flex item. This overrides the computed `inline-size`.
+    pub flex_item_inline_size: Option<Au>,
+
     /// Various flags.
     pub flags: BlockFlowFlags,
 }
@@ -577,6 +583,7 @@ impl BlockFlow {
             inline_size_of_preceding_right_floats: Au(0),
             hypothetical_position: LogicalPoint::new(writing_mode, Au(0), Au(0)),
             float: None,
+            flex_item_inline_size: None,
             flags: BlockFlowFlags::empty(),
         }
     }
@@ -591,6 +598,7 @@ impl BlockFlow {
             inline_size_of_preceding_right_floats: Au(0),
             hypothetical_position: LogicalPoint::new(writing_mode, Au(0), Au(0)),
             float: None,
+            flex_item_inline_size: None,
             flags: BlockFlowFlags::empty(),
         }
     }
@@ -608,6 +616,7 @@ impl BlockFlow {
             inline_size_of_preceding_right_floats: Au(0),
             hypothetical_position: LogicalPoint::new(writing_mode, Au(0), Au(0)),
             float: Some(box FloatedBlockInfo::new(float_kind)),
+            flex_item_inline_size: None,
             flags: BlockFlowFlags::empty(),
         }
     }
@@ -625,6 +634,7 @@ impl BlockFlow {
             inline_size_of_preceding_right_floats: Au(0),
             hypothetical_position: LogicalPoint::new(writing_mode, Au(0), Au(0)),
             float: Some(box FloatedBlockInfo::new(float_kind)),
+            flex_item_inline_size: None,
             flags: BlockFlowFlags::empty(),
         }
     }
@@ -762,11 +772,11 @@ impl BlockFlow {
 
     /// Return true if this has a replaced fragment.
     ///
-    /// The only two types of replaced fragments currently are text fragments
-    /// and image fragments.
+    /// The replaced fragments are currently text, image, canvas, and inline-block fragments.
     fn is_replaced_content(&self) -> bool {
         match self.fragment.specific {
-            ScannedTextFragment(_) | ImageFragment(_) | InlineBlockFragment(_) => true,
+            ScannedTextFragment(_) | ImageFragment(_) | CanvasFragment(_) |
+            InlineBlockFragment(_) => true,
             _ => false,
         }
     }
@@ -781,6 +791,15 @@ impl BlockFlow {
             max(content_intrinsic_inline_sizes.minimum_inline_size, available_inline_size))
     }
 
+    /// Returns the distance from the inline-end edge of the absolute containing block to the
+    /// inline-end content edge of the parent flow, which is the static position of an absolutely
+    /// positioned flow whose parent is right-to-left. See CSS 2.1 § 10.3.7.
+    fn static_position_inline_end(&self, available_inline_size: Au, static_i_offset: Au) -> Au {
+        let containing_block_inline_size = available_inline_size +
+            self.fragment.border_padding.inline_start_end();
+        containing_block_inline_size - (static_i_offset + self.base.block_container_inline_size)
+    }
+
     /// If this is the root flow, shifts all kids down and adjusts our size to account for
     /// root flow margins, which should never be collapsed according to CSS § 8.3.1.
     ///
@@ -1007,26 +1026,9 @@ impl BlockFlow {
                 return
             }
 
-            // Compute any explicitly-specified block size.
-            // Can't use `for` because we assign to `candidate_block_size_iterator.candidate_value`.
-            let mut candidate_block_size_iterator = CandidateBSizeIterator::new(
-                &self.fragment,
-                self.base.block_container_explicit_block_size);
-            loop {
-                match candidate_block_size_iterator.next() {
-                    Some(candidate_block_size) => {
-                        candidate_block_size_iterator.candidate_value =
-                            match candidate_block_size {
-                                Auto => block_size,
-                                Specified(value) => value
-                            }
-                    }
-                    None => break,
-                }
-            }
-
-            // Adjust `cur_b` as necessary to account for the explicitly-specified block-size.
-            block_size = candidate_block_size_iterator.candidate_value;
+            // Compute any explicitly-specified block size, and adjust `cur_b` as necessary to
+            // account for it.
+            block_size = self.used_content_block_size(block_size);
             let delta = block_size - (cur_b - block_start_offset);
             translate_including_floats(&mut cur_b, delta, &mut floats);
 
@@ -1078,6 +1080,30 @@ impl BlockFlow {
         }
     }
 
+    /// Computes the used block-size of this block's content box, given the block-size of its
+    /// contents, by taking `block-size`, `min-block-size` and `max-block-size` into account.
+    ///
+    /// See CSS 2.1 § 10.7.
+    pub fn used_content_block_size(&self, content_block_size: Au) -> Au {
+        // Can't use `for` because we assign to `candidate_block_size_iterator.candidate_value`.
+        let mut candidate_block_size_iterator = CandidateBSizeIterator::new(
+            &self.fragment,
+            self.base.block_container_explicit_block_size);
+        loop {
+            match candidate_block_size_iterator.next() {
+                Some(candidate_block_size) => {
+                    candidate_block_size_iterator.candidate_value =
+                        match candidate_block_size {
+                            Auto => content_block_size,
+                            Specified(value) => value
+                        }
+                }
+                None => break,
+            }
+        }
+        candidate_block_size_iterator.candidate_value
+    }
+
     /// Add placement information about current float flow for use by the parent.
     ///
     /// Also, use information given by parent about other floats to find out our relative position.
@@ -1283,6 +1309,7 @@ impl BlockFlow {
 
         let fixed_static_i_offset = self.base.fixed_static_i_offset + inline_start_content_edge;
         let flags = self.base.flags.clone();
+        let is_rtl = self.fragment.style().get_inheritedbox().direction == direction::rtl;
 
         // This value is used only for table cells.
         let mut inline_start_margin_edge = inline_start_content_edge;
@@ -1316,6 +1343,7 @@ impl BlockFlow {
                 kid_base.block_container_explicit_block_size = explicit_content_size;
                 kid_base.absolute_static_i_offset = absolute_static_i_offset;
                 kid_base.fixed_static_i_offset = fixed_static_i_offset;
+                kid_base.flags.set(PARENT_IS_RTL, is_rtl);
             }
 
             match flow::base(kid).flags.float_kind() {
@@ -1399,9 +1427,11 @@ impl BlockFlow {
             return OtherFormattingContext
         }
         match style.get_box().display {
-            display::table_cell | display::table_caption | display::inline_block => {
+            display::table_cell | display::table_caption | display::inline_block |
+            display::inline_flex => {
                 OtherFormattingContext
             }
+            display::flex => BlockFormattingContext,
             _ if style.get_box().overflow != overflow::visible => BlockFormattingContext,
             _ => NonformattingContext,
         }
@@ -1842,11 +1872,12 @@ impl Flow for BlockFlow {
         if self.base.flags.is_float() {
             // TODO(#2009, pcwalton): This is a pseudo-stacking context. We need to merge `z-index:
             // auto` kids into the parent stacking context, when that is supported.
-            self.build_display_list_for_floating_block(layout_context)
+            self.build_display_list_for_floating_block(box DisplayList::new(), layout_context)
         } else if self.base.flags.contains(IS_ABSOLUTELY_POSITIONED) {
-            self.build_display_list_for_absolutely_positioned_block(layout_context)
+            self.build_display_list_for_absolutely_positioned_block(box DisplayList::new(),
+                                                                    layout_context)
         } else {
-            self.build_display_list_for_block(layout_context, BlockLevel)
+            self.build_display_list_for_block(box DisplayList::new(), layout_context, BlockLevel)
         }
 
         if opts::get().validate_display_list_geometry {
@@ -1860,8 +1891,13 @@ impl Flow for BlockFlow {
 
     fn iterate_through_fragment_bounds(&self, iterator: &mut FragmentBoundsIterator) {
         if iterator.should_process(&self.fragment) {
-            let fragment_origin =
-                self.base.stacking_relative_position_of_child_fragment(&self.fragment);
+            // As when building the display list, a fragment that establishes a stacking context
+            // sits at the origin of it.
+            let fragment_origin = if self.fragment.establishes_stacking_context() {
+                ZERO_POINT
+            } else {
+                self.base.stacking_relative_position_of_child_fragment(&self.fragment)
+            };
             iterator.process(&self.fragment,
                              self.fragment.stacking_relative_bounds(&fragment_origin));
         }
@@ -1977,8 +2013,25 @@ pub trait ISizeAndMarginsComputer {
             (Auto, box_sizing::border_box) | (_, box_sizing::content_box) => {}
         }
 
-        // The text alignment of a block flow is the text alignment of its box's style.
-        block.base.flags.set_text_align(style.get_inheritedtext().text_align);
+        // The flex container has already resolved the inline-size of its items (CSS-FLEXBOX § 9).
+        match block.flex_item_inline_size {
+            Some(inline_size) => computed_inline_size = Specified(inline_size),
+            None => {}
+        }
+
+        // The text alignment of a block flow is the text alignment of its box's style, with
+        // `start` and `end` resolved against its direction.
+        let text_align_in_effect = match (style.get_inheritedtext().text_align,
+                                          style.get_inheritedbox().direction) {
+            (text_align::start, direction::ltr) | (text_align::end, direction::rtl) => {
+                text_align::left
+            }
+            (text_align::start, direction::rtl) | (text_align::end, direction::ltr) => {
+                text_align::right
+            }
+            (text_align, _) => text_align,
+        };
+        block.base.flags.set_text_align(text_align_in_effect);
 
         let margin = style.logical_margin();
         let position = style.logical_position();
@@ -2113,7 +2166,7 @@ pub trait ISizeAndMarginsComputer {
     /// available_inline-size
     /// where available_inline-size = CB inline-size - (horizontal border + padding)
     fn solve_block_inline_size_constraints(&self,
-                                           _: &mut BlockFlow,
+                                           block: &mut BlockFlow,
                                            input: &ISizeConstraintInput)
                                            -> ISizeConstraintSolution {
         let (computed_inline_size, inline_start_margin, inline_end_margin, available_inline_size) =
@@ -2143,10 +2196,18 @@ pub trait ISizeAndMarginsComputer {
         let (inline_start_margin, inline_size, inline_end_margin) =
             match (inline_start_margin, computed_inline_size, inline_end_margin) {
                 // If all have a computed value other than 'auto', the system is
-                // over-constrained so we discard the end margin.
-                (Specified(margin_start), Specified(inline_size), Specified(_margin_end)) =>
-                    (margin_start, inline_size, available_inline_size -
-                     (margin_start + inline_size)),
+                // over-constrained so we discard the end margin, or the start margin if the
+                // containing block is right-to-left.
+                (Specified(margin_start), Specified(inline_size), Specified(margin_end)) => {
+                    if block.base.flags.contains(PARENT_IS_RTL) {
+                        (available_inline_size - (margin_end + inline_size),
+                         inline_size,
+                         margin_end)
+                    } else {
+                        (margin_start, inline_size, available_inline_size -
+                         (margin_start + inline_size))
+                    }
+                }
 
                 // If exactly one value is 'auto', solve for it
                 (Auto, Specified(inline_size), Specified(margin_end)) =>
@@ -2213,16 +2274,34 @@ impl ISizeAndMarginsComputer for AbsoluteNonReplaced {
             ..
         } = input;
 
-        // TODO: Check for direction of parent flow (NOT Containing Block)
-        // when right-to-left is implemented.
-        // Assume direction is 'ltr' for now
+        // The direction of the parent flow (NOT the Containing Block) determines where the
+        // static position is.
+        let parent_is_rtl = block.base.flags.contains(PARENT_IS_RTL);
 
         // Distance from the inline-start edge of the Absolute Containing Block to the
         // inline-start margin edge of a hypothetical box that would have been the
         // first box of the element.
         let static_position_inline_start = static_i_offset;
 
+        // Distance from the inline-end edge of the Absolute Containing Block to the
+        // inline-end margin edge of that hypothetical box, used if the parent is right-to-left.
+        let static_position_inline_end = block.static_position_inline_end(available_inline_size,
+                                                                          static_i_offset);
+
         let (inline_start, inline_end, inline_size, margin_inline_start, margin_inline_end) = match (inline_start, inline_end, computed_inline_size) {
+            (Auto, Auto, Auto) if parent_is_rtl => {
+                let margin_start = inline_start_margin.specified_or_zero();
+                let margin_end = inline_end_margin.specified_or_zero();
+                let inline_end = static_position_inline_end;
+                // Now it is the same situation as inline-end Specified and inline-start
+                // and inline-size Auto.
+
+                // Set inline-start to zero to calculate inline-size
+                let inline_size = block.get_shrink_to_fit_inline_size(
+                    available_inline_size - (inline_end + margin_start + margin_end));
+                let sum = inline_end + inline_size + margin_start + margin_end;
+                (available_inline_size - sum, inline_end, inline_size, margin_start, margin_end)
+            }
             (Auto, Auto, Auto) => {
                 let margin_start = inline_start_margin.specified_or_zero();
                 let margin_end = inline_end_margin.specified_or_zero();
@@ -2240,9 +2319,11 @@ impl ISizeAndMarginsComputer for AbsoluteNonReplaced {
                 match (inline_start_margin, inline_end_margin) {
                     (Auto, Auto) => {
                         let total_margin_val = available_inline_size - inline_start - inline_end - inline_size;
-                        if total_margin_val < Au(0) {
+                        if total_margin_val < Au(0) && parent_is_rtl {
+                            // margin-inline-end becomes 0 because direction is 'rtl'.
+                            (inline_start, inline_end, inline_size, total_margin_val, Au(0))
+                        } else if total_margin_val < Au(0) {
                             // margin-inline-start becomes 0 because direction is 'ltr'.
-                            // TODO: Handle 'rtl' when it is implemented.
                             (inline_start, inline_end, inline_size, Au(0), total_margin_val)
                         } else {
                             // Equal margins
@@ -2259,10 +2340,15 @@ impl ISizeAndMarginsComputer for AbsoluteNonReplaced {
                         let sum = inline_start + inline_end + inline_size + margin_end;
                         (inline_start, inline_end, inline_size, available_inline_size - sum, margin_end)
                     }
+                    (Specified(margin_start), Specified(margin_end)) if parent_is_rtl => {
+                        // Values are over-constrained.
+                        // Ignore value for 'inline-start' cos direction is 'rtl'.
+                        let sum = inline_end + inline_size + margin_start + margin_end;
+                        (available_inline_size - sum, inline_end, inline_size, margin_start, margin_end)
+                    }
                     (Specified(margin_start), Specified(margin_end)) => {
                         // Values are over-constrained.
                         // Ignore value for 'inline-end' cos direction is 'ltr'.
-                        // TODO: Handle 'rtl' when it is implemented.
                         let sum = inline_start + inline_size + margin_start + margin_end;
                         (inline_start, available_inline_size - sum, inline_size, margin_start, margin_end)
                     }
@@ -2311,11 +2397,18 @@ impl ISizeAndMarginsComputer for AbsoluteNonReplaced {
                 (available_inline_size - sum, inline_end, inline_size, margin_start, margin_end)
             }
 
+            (Auto, Auto, Specified(inline_size)) if parent_is_rtl => {
+                let margin_start = inline_start_margin.specified_or_zero();
+                let margin_end = inline_end_margin.specified_or_zero();
+                // Setting 'inline-end' to static position because direction is 'rtl'.
+                let inline_end = static_position_inline_end;
+                let sum = inline_end + inline_size + margin_start + margin_end;
+                (available_inline_size - sum, inline_end, inline_size, margin_start, margin_end)
+            }
             (Auto, Auto, Specified(inline_size)) => {
                 let margin_start = inline_start_margin.specified_or_zero();
                 let margin_end = inline_end_margin.specified_or_zero();
                 // Setting 'inline-start' to static position because direction is 'ltr'.
-                // TODO: Handle 'rtl' when it is implemented.
                 let inline_start = static_position_inline_start;
                 let sum = inline_start + inline_size + margin_start + margin_end;
                 (inline_start, available_inline_size - sum, inline_size, margin_start, margin_end)
@@ -2349,7 +2442,7 @@ impl ISizeAndMarginsComputer for AbsoluteReplaced {
     /// [aka available_inline-size]
     ///
     /// Return the solution for the equation.
-    fn solve_inline_size_constraints(&self, _: &mut BlockFlow, input: &ISizeConstraintInput)
+    fn solve_inline_size_constraints(&self, block: &mut BlockFlow, input: &ISizeConstraintInput)
                                -> ISizeConstraintSolution {
         let &ISizeConstraintInput {
             computed_inline_size,
@@ -2361,11 +2454,9 @@ impl ISizeAndMarginsComputer for AbsoluteReplaced {
             static_i_offset,
             ..
         } = input;
-        // TODO: Check for direction of static-position Containing Block (aka
-        // parent flow, _not_ the actual Containing Block) when right-to-left
-        // is implemented
-        // Assume direction is 'ltr' for now
-        // TODO: Handle all the cases for 'rtl' direction.
+        // The direction of the static-position Containing Block (aka parent flow, _not_ the
+        // actual Containing Block) determines where the static position is.
+        let parent_is_rtl = block.base.flags.contains(PARENT_IS_RTL);
 
         let inline_size = match computed_inline_size {
             Specified(w) => w,
@@ -2379,7 +2470,19 @@ impl ISizeAndMarginsComputer for AbsoluteReplaced {
         // first box of the element.
         let static_position_inline_start = static_i_offset;
 
+        // Distance from the inline-end edge of the Absolute Containing Block to the
+        // inline-end margin edge of that hypothetical box, used if the parent is right-to-left.
+        let static_position_inline_end = block.static_position_inline_end(available_inline_size,
+                                                                          static_i_offset);
+
         let (inline_start, inline_end, inline_size, margin_inline_start, margin_inline_end) = match (inline_start, inline_end) {
+            (Auto, Auto) if parent_is_rtl => {
+                let inline_end = static_position_inline_end;
+                let margin_start = inline_start_margin.specified_or_zero();
+                let margin_end = inline_end_margin.specified_or_zero();
+                let sum = inline_end + inline_size + margin_start + margin_end;
+                (available_inline_size - sum, inline_end, inline_size, margin_start, margin_end)
+            }
             (Auto, Auto) => {
                 let inline_start = static_position_inline_start;
                 let margin_start = inline_start_margin.specified_or_zero();
@@ -2404,7 +2507,10 @@ impl ISizeAndMarginsComputer for AbsoluteReplaced {
                 match (inline_start_margin, inline_end_margin) {
                     (Auto, Auto) => {
                         let total_margin_val = available_inline_size - inline_start - inline_end - inline_size;
-                        if total_margin_val < Au(0) {
+                        if total_margin_val < Au(0) && parent_is_rtl {
+                            // margin-inline-end becomes 0 because direction is 'rtl'.
+                            (inline_start, inline_end, inline_size, total_margin_val, Au(0))
+                        } else if total_margin_val < Au(0) {
                             // margin-inline-start becomes 0 because direction is 'ltr'.
                             (inline_start, inline_end, inline_size, Au(0), total_margin_val)
                         } else {
@@ -2422,6 +2528,12 @@ impl ISizeAndMarginsComputer for AbsoluteReplaced {
                         let sum = inline_start + inline_end + inline_size + margin_end;
                         (inline_start, inline_end, inline_size, available_inline_size - sum, margin_end)
                     }
+                    (Specified(margin_start), Specified(margin_end)) if parent_is_rtl => {
+                        // Values are over-constrained.
+                        // Ignore value for 'inline-start' cos direction is 'rtl'.
+                        let sum = inline_end + inline_size + margin_start + margin_end;
+                        (available_inline_size - sum, inline_end, inline_size, margin_start, margin_end)
+                    }
                     (Specified(margin_start), Specified(margin_end)) => {
                         // Values are over-constrained.
                         // Ignore value for 'inline-end' cos direction is 'ltr'.
@@ -16,20 +16,24 @@
 use css::node_style::StyledNode;
 use block::BlockFlow;
 use context::LayoutContext;
+use flex::FlexFlow;
 use floats::FloatKind;
 use flow::{Flow, ImmutableFlowUtils, MutableOwnedFlowUtils};
 use flow::{Descendants, AbsDescendants};
 use flow::{IS_ABSOLUTELY_POSITIONED};
 use flow;
 use flow_ref::FlowRef;
-use fragment::{Fragment, GenericFragment, IframeFragment, IframeFragmentInfo, ImageFragment};
+use fragment::{Fragment, GeneratedContentFragment, GenericFragment, IframeFragment};
+use fragment::{CanvasFragment, CanvasFragmentInfo, IframeFragmentInfo, ImageFragment};
+use fragment::{ContentItems, ListItemMarker};
 use fragment::{ImageFragmentInfo, InlineAbsoluteHypotheticalFragment};
 use fragment::{InlineAbsoluteHypotheticalFragmentInfo, InlineBlockFragment};
 use fragment::{InlineBlockFragmentInfo, SpecificFragmentInfo, TableCellFragment};
 use fragment::{TableColumnFragment, TableColumnFragmentInfo, TableFragment, TableRowFragment};
 use fragment::{TableWrapperFragment, UnscannedTextFragment, UnscannedTextFragmentInfo};
 use incremental::{RECONSTRUCT_FLOW, RestyleDamage};
-use inline::InlineFlow;
+use inline::{FirstLineStyle, InlineFlow};
+use list_item::ListItemFlow;
 use parallel;
 use table_wrapper::TableWrapperFlow;
 use table::TableFlow;
@@ -44,7 +48,8 @@ use wrapper::{PostorderNodeMutTraversal, TLayoutNode, ThreadSafeLayoutNode};
 use wrapper::{Before, After, Normal};
 
 use gfx::display_list::OpaqueNode;
-use script::dom::element::{HTMLIFrameElementTypeId, HTMLImageElementTypeId};
+use script::dom::element::{HTMLCanvasElementTypeId, HTMLIFrameElementTypeId};
+use script::dom::element::HTMLImageElementTypeId;
 use script::dom::element::{HTMLObjectElementTypeId, HTMLInputElementTypeId};
 use script::dom::element::{HTMLTableColElementTypeId, HTMLTableDataCellElementTypeId};
 use script::dom::element::{HTMLTableElementTypeId, HTMLTableHeaderCellElementTypeId};
@@ -53,12 +58,14 @@ use script::dom::node::{CommentNodeTypeId, DoctypeNodeTypeId, DocumentFragmentNo
 use script::dom::node::{DocumentNodeTypeId, ElementNodeTypeId, ProcessingInstructionNodeTypeId};
 use script::dom::node::{TextNodeTypeId};
 use script::dom::htmlobjectelement::is_image_data;
+use servo_util::arc_ptr_eq;
 use servo_util::opts;
 use std::collections::DList;
 use std::mem;
 use std::sync::atomic::Relaxed;
-use style::ComputedValues;
-use style::computed_values::{display, position, float};
+use style::{ComputedValues, DeclarationBlock, cascade};
+use style::computed_values::{content, display, float, list_style_position, list_style_type};
+use style::computed_values::position;
 use sync::Arc;
 use url::Url;
 
@@ -249,6 +256,41 @@ impl<'a> FlowConstructor<'a> {
         }
     }
 
+    /// Builds the text fragment for a pseudo-element or a form control. Pseudo-element content
+    /// that uses counters can't be turned into text until the whole flow tree has been built, so
+    /// it becomes a `GeneratedContentFragment` for the `generated_content` traversal to resolve.
+    fn build_fragment_for_text_content(&self, node: &ThreadSafeLayoutNode) -> Fragment {
+        let specific = match node.style().get_box().content {
+            content::Content(ref items) if node.get_pseudo_element_type() != Normal &&
+                    items.iter().any(|item| {
+                        match *item {
+                            content::StringContent(_) => false,
+                            content::CounterContent(..) | content::CountersContent(..) => true,
+                        }
+                    }) => {
+                GeneratedContentFragment(box ContentItems(items.clone()))
+            }
+            _ => UnscannedTextFragment(UnscannedTextFragmentInfo::new(node)),
+        };
+        Fragment::new_from_specific_info(node, specific)
+    }
+
+    /// Builds the marker fragment for a list item, if it has one: either its `list-style-image`
+    /// or a `GeneratedContentFragment` that becomes the text for its `list-style-type` once
+    /// counters have been resolved.
+    fn build_marker_for_list_item(&mut self, node: &ThreadSafeLayoutNode) -> Option<Fragment> {
+        let specific = match node.style().get_list().list_style_image {
+            Some(ref url) => self.build_fragment_info_for_image(node, Some(url.clone())),
+            None => {
+                match node.style().get_list().list_style_type {
+                    list_style_type::none => return None,
+                    _ => GeneratedContentFragment(box ListItemMarker),
+                }
+            }
+        };
+        Some(Fragment::new_anonymous_fragment(node, specific))
+    }
+
     /// Builds specific `Fragment` info for the given node.
     ///
     /// This does *not* construct the text for generated content (but, for generated content with
@@ -268,6 +310,9 @@ impl<'a> FlowConstructor<'a> {
                 let data = node.get_object_data();
                 self.build_fragment_info_for_image(node, data)
             }
+            Some(ElementNodeTypeId(HTMLCanvasElementTypeId)) => {
+                CanvasFragment(box CanvasFragmentInfo::new(node))
+            }
             Some(ElementNodeTypeId(HTMLTableElementTypeId)) => TableWrapperFragment,
             Some(ElementNodeTypeId(HTMLTableColElementTypeId)) => {
                 TableColumnFragment(TableColumnFragmentInfo::new(node))
@@ -318,6 +363,27 @@ impl<'a> FlowConstructor<'a> {
             }
         }
 
+        // The `first-line` and `first-letter` pseudo-elements apply to the first formatted line of
+        // a block container, which is in this inline flow if no other flow precedes it.
+        let is_first_line = node.get_pseudo_element_type() == Normal &&
+            flow::base(&**flow).children.is_empty() && flow_list.is_empty();
+        let first_line_style = if is_first_line {
+            node.get_first_line_style()
+        } else {
+            None
+        };
+        if is_first_line {
+            match node.get_first_letter_style() {
+                Some(ref first_letter_style) => {
+                    split_first_letter(&mut fragments,
+                                       node.style(),
+                                       first_letter_style,
+                                       node.get_first_letter_declarations().as_slice())
+                }
+                None => {}
+            }
+        }
+
         // Build a list of all the inline-block fragments before fragments is moved.
         let mut inline_block_flows = vec!();
         for f in fragments.iter() {
@@ -334,7 +400,8 @@ impl<'a> FlowConstructor<'a> {
         // for runs might collapse so much whitespace away that only hypothetical fragments
         // remain. In that case the inline flow will compute its ascent and descent to be zero.
         let fragments = TextRunScanner::new().scan_for_runs(self.layout_context.font_context(),
-                                                            fragments);
+                                                            fragments,
+                                                            &**node.style());
         let mut inline_flow_ref =
             FlowRef::new(box InlineFlow::from_fragments(fragments, node.style().writing_mode));
 
@@ -346,6 +413,9 @@ impl<'a> FlowConstructor<'a> {
         {
             let inline_flow = inline_flow_ref.as_inline();
 
+            inline_flow.first_line_style = first_line_style.map(|style| {
+                FirstLineStyle::new(style, node.style().clone())
+            });
 
             let (ascent, descent) =
                 inline_flow.compute_minimum_ascent_and_descent(self.layout_context.font_context(),
@@ -478,19 +548,36 @@ impl<'a> FlowConstructor<'a> {
     /// this block flow.
     /// Also, deal with the absolute and fixed descendants bubbled up by
     /// children nodes.
-    fn build_flow_for_block(&mut self, mut flow: FlowRef, node: &ThreadSafeLayoutNode)
+    fn build_flow_for_block(&mut self, flow: FlowRef, node: &ThreadSafeLayoutNode)
                             -> ConstructionResult {
+        self.build_flow_for_block_starting_with_fragment(flow, node, None)
+    }
+
+    /// Like `build_flow_for_block`, but the first line of the block starts with the given
+    /// fragment, if any. This is used for list item markers that are inside the list item.
+    fn build_flow_for_block_starting_with_fragment(&mut self,
+                                                   mut flow: FlowRef,
+                                                   node: &ThreadSafeLayoutNode,
+                                                   initial_fragment: Option<Fragment>)
+                                                   -> ConstructionResult {
         // Gather up fragments for the inline flows we might need to create.
         let mut inline_fragment_accumulator = InlineFragmentsAccumulator::new();
         let mut consecutive_siblings = vec!();
         let mut first_fragment = true;
 
+        match initial_fragment {
+            None => {}
+            Some(initial_fragment) => {
+                inline_fragment_accumulator.fragments.push_back(initial_fragment);
+                first_fragment = false;
+            }
+        }
+
         // Special case: If this is generated content, then we need to initialize the accumulator
         // with the fragment corresponding to that content.
         if node.get_pseudo_element_type() != Normal ||
            node.type_id() == Some(ElementNodeTypeId(HTMLInputElementTypeId)) {
-            let fragment_info = UnscannedTextFragment(UnscannedTextFragmentInfo::new(node));
-            let fragment = Fragment::new_from_specific_info(node, fragment_info);
+            let fragment = self.build_fragment_for_text_content(node);
             inline_fragment_accumulator.fragments.push_back(fragment);
             first_fragment = false;
         }
@@ -560,6 +647,47 @@ impl<'a> FlowConstructor<'a> {
         self.build_flow_for_block(FlowRef::new(flow), node)
     }
 
+    /// Builds the flow for a node with `display: flex` or `display: inline-flex`. This yields a
+    /// `FlexFlow` with the flex items underneath it.
+    fn build_flow_for_flex(&mut self, node: &ThreadSafeLayoutNode, float_value: float::T)
+                           -> ConstructionResult {
+        let flow = match float_value {
+            float::none => box FlexFlow::from_node(self, node),
+            _ => {
+                let float_kind = FloatKind::from_property(float_value);
+                box FlexFlow::float_from_node(self, node, float_kind)
+            }
+        };
+        self.build_flow_for_block(FlowRef::new(flow as Box<Flow>), node)
+    }
+
+    /// Builds the flow for a node with `display: list-item`. This yields a `ListItemFlow` that
+    /// places the marker outside of its border box or, if `list-style-position` is `inside`, a
+    /// `BlockFlow` whose first line starts with the marker.
+    fn build_flow_for_list_item(&mut self, node: &ThreadSafeLayoutNode, float_value: float::T)
+                                -> ConstructionResult {
+        let marker = self.build_marker_for_list_item(node);
+        let float_kind = match float_value {
+            float::none => None,
+            _ => Some(FloatKind::from_property(float_value)),
+        };
+        match node.style().get_list().list_style_position {
+            list_style_position::outside => {
+                let flow = box ListItemFlow::from_node_and_marker(self, node, marker, float_kind);
+                self.build_flow_for_block(FlowRef::new(flow as Box<Flow>), node)
+            }
+            list_style_position::inside => {
+                let flow = match float_kind {
+                    None => box BlockFlow::from_node(self, node),
+                    Some(float_kind) => box BlockFlow::float_from_node(self, node, float_kind),
+                };
+                self.build_flow_for_block_starting_with_fragment(FlowRef::new(flow as Box<Flow>),
+                                                                 node,
+                                                                 marker)
+            }
+        }
+    }
+
     /// Concatenates the fragments of kids, adding in our own borders/padding/margins if necessary.
     /// Returns the `InlineFragmentsConstructionResult`, if any. There will be no
     /// `InlineFragmentsConstructionResult` if this node consisted entirely of ignorable
@@ -678,8 +806,7 @@ impl<'a> FlowConstructor<'a> {
         // fragment corresponding to that content. Otherwise, just initialize with the ordinary
         // fragment that needs to be generated for this inline node.
         let fragment = if node.get_pseudo_element_type() != Normal {
-            let fragment_info = UnscannedTextFragment(UnscannedTextFragmentInfo::new(node));
-            Fragment::new_from_specific_info(node, fragment_info)
+            self.build_fragment_for_text_content(node)
         } else {
             Fragment::new(self, node)
         };
@@ -697,7 +824,10 @@ impl<'a> FlowConstructor<'a> {
 
     fn build_fragment_for_inline_block(&mut self, node: &ThreadSafeLayoutNode)
                                        -> ConstructionResult {
-        let block_flow_result = self.build_flow_for_nonfloated_block(node);
+        let block_flow_result = match node.style().get_box().display {
+            display::inline_flex => self.build_flow_for_flex(node, float::none),
+            _ => self.build_flow_for_nonfloated_block(node),
+        };
         let (block_flow, abs_descendants) = match block_flow_result {
             FlowConstructionResult(block_flow, abs_descendants) => (block_flow, abs_descendants),
             _ => unreachable!()
@@ -1039,6 +1169,18 @@ impl<'a> PostorderNodeMutTraversal for FlowConstructor<'a> {
                 node.set_flow_construction_result(construction_result)
             }
 
+            // Flex containers contribute flex flow construction results.
+            (display::flex, float_value, _) => {
+                let construction_result = self.build_flow_for_flex(node, float_value);
+                node.set_flow_construction_result(construction_result)
+            }
+
+            // List items contribute list item flow construction results.
+            (display::list_item, float_value, _) => {
+                let construction_result = self.build_flow_for_list_item(node, float_value);
+                node.set_flow_construction_result(construction_result)
+            }
+
             // Absolutely positioned elements will have computed value of
             // `float` as 'none' and `display` as per the table.
             // Only match here for block items. If an item is absolutely
@@ -1065,8 +1207,8 @@ impl<'a> PostorderNodeMutTraversal for FlowConstructor<'a> {
                 node.set_flow_construction_result(construction_result)
             }
 
-            // Inline-block items contribute inline fragment construction results.
-            (display::inline_block, float::none, _) => {
+            // Inline-block and inline flex items contribute inline fragment construction results.
+            (display::inline_block, float::none, _) | (display::inline_flex, float::none, _) => {
                 let construction_result = self.build_fragment_for_inline_block(node);
                 node.set_flow_construction_result(construction_result)
             }
@@ -1155,7 +1297,8 @@ impl<'ln> NodeUtils for ThreadSafeLayoutNode<'ln> {
             Some(DocumentFragmentNodeTypeId) |
             Some(DocumentNodeTypeId) |
             None |
-            Some(ElementNodeTypeId(HTMLImageElementTypeId)) => true,
+            Some(ElementNodeTypeId(HTMLImageElementTypeId)) |
+            Some(ElementNodeTypeId(HTMLCanvasElementTypeId)) => true,
             Some(ElementNodeTypeId(HTMLObjectElementTypeId)) => self.has_object_data(),
             Some(ElementNodeTypeId(_)) => false,
         }
@@ -1278,6 +1421,95 @@ pub fn strip_ignorable_whitespace_from_start(this: &mut DList<Fragment>) {
     }
 }
 
+/// Splits the first letter off the first text in a list of fragments, and gives it the style of
+/// the `first-letter` pseudo-element. See CSS Selectors Level 3 § 7.2.
+///
+/// A letter directly inside the block container takes `first_letter_style`. A letter inside a
+/// descendant inline element takes the `first-letter` declarations over the style of that element
+/// instead, so that it keeps what it inherits from there.
+pub fn split_first_letter(this: &mut DList<Fragment>,
+                          block_style: &Arc<ComputedValues>,
+                          first_letter_style: &Arc<ComputedValues>,
+                          first_letter_declarations: &[DeclarationBlock]) {
+    let mut preceding = DList::new();
+    loop {
+        let fragment = match this.pop_front() {
+            Some(fragment) => fragment,
+            None => break,
+        };
+        let text = match fragment.specific {
+            UnscannedTextFragment(ref info) => Some((*info.text).clone()),
+            _ => None,
+        };
+        let text = match text {
+            Some(text) => text,
+            None => {
+                // Replaced content and inline blocks come before any first letter.
+                this.push_front(fragment);
+                break
+            }
+        };
+        let (start, end) = match first_letter_range(text.as_slice()) {
+            Some(range) => range,
+            None => {
+                preceding.push_back(fragment);
+                continue
+            }
+        };
+
+        debug!("splitting off first letter {}", text.as_slice().slice(start, end));
+        let letter_style = if arc_ptr_eq(&fragment.style, block_style) {
+            first_letter_style.clone()
+        } else {
+            let (style, _) = cascade(first_letter_declarations,
+                                     false,
+                                     Some(&*fragment.style),
+                                     None);
+            Arc::new(style)
+        };
+        if end < text.len() {
+            this.push_front(fragment.with_unscanned_text_and_style(
+                    text.as_slice().slice_from(end).to_string(),
+                    fragment.style.clone()))
+        }
+        this.push_front(fragment.with_unscanned_text_and_style(
+                text.as_slice().slice(start, end).to_string(),
+                letter_style));
+        if start > 0 {
+            this.push_front(fragment.with_unscanned_text_and_style(
+                    text.as_slice().slice_to(start).to_string(),
+                    fragment.style.clone()))
+        }
+        break
+    }
+
+    loop {
+        match preceding.pop_back() {
+            Some(fragment) => this.push_front(fragment),
+            None => break,
+        }
+    }
+}
+
+/// Returns the byte range of the first letter of the given text, including any punctuation
+/// around it, or `None` if the text is only whitespace.
+fn first_letter_range(text: &str) -> Option<(uint, uint)> {
+    let start = match text.char_indices().find(|&(_, c)| !c.is_whitespace()) {
+        Some((start, _)) => start,
+        None => return None,
+    };
+    let mut end = text.len();
+    let mut seen_letter = false;
+    for (i, c) in text.slice_from(start).char_indices() {
+        if c.is_whitespace() || (seen_letter && c.is_alphanumeric()) {
+            end = start + i;
+            break
+        }
+        seen_letter = seen_letter || c.is_alphanumeric();
+    }
+    Some((start, end))
+}
+
 /// Strips ignorable whitespace from the end of a list of fragments.
 pub fn strip_ignorable_whitespace_from_end(this: &mut DList<Fragment>) {
     if this.is_empty() {
@@ -4,6 +4,7 @@
 
 //! Data needed by the layout task.
 
+use animation::AnimationManager;
 use css::matching::{ApplicableDeclarationsCache, StyleSharingCandidateCache};
 
 use geom::{Rect, Size2D};
@@ -80,6 +81,16 @@ pub struct SharedLayoutContext {
     /// Starts at zero, and increased by one every time a layout completes.
     /// This can be used to easily check for invalid stale data.
     pub generation: uint,
+
+    /// The running transitions and animations.
+    pub animation_manager: Arc<Mutex<AnimationManager>>,
+
+    /// The time at which transitions and animations are computed, in seconds.
+    pub animation_time: f64,
+
+    /// The generation of the web fonts of the document, which changes whenever one of them loads
+    /// or its block period ends.
+    pub web_font_generation: uint,
 }
 
 pub struct LayoutContext<'a> {
@@ -91,6 +102,10 @@ impl<'a> LayoutContext<'a> {
     pub fn new(shared_layout_context: &'a SharedLayoutContext) -> LayoutContext<'a> {
 
         let local_context = create_or_get_local_context(shared_layout_context);
+        unsafe {
+            (*local_context).font_context
+                            .set_web_font_generation(shared_layout_context.web_font_generation);
+        }
 
         LayoutContext {
             shared: shared_layout_context,
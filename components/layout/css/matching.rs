@@ -4,9 +4,11 @@
 
 //! High-level interface to CSS selector matching.
 
+use animation;
+use context::SharedLayoutContext;
 use css::node_style::StyledNode;
-use incremental::{mod, RestyleDamage};
-use util::{LayoutDataAccess, LayoutDataWrapper};
+use incremental::{mod, RestyleDamage, RECONSTRUCT_FLOW};
+use util::{LayoutDataAccess, LayoutDataWrapper, OpaqueNodeMethods};
 use wrapper::{LayoutElement, LayoutNode, TLayoutNode};
 
 use script::dom::node::{TextNodeTypeId};
@@ -19,6 +21,7 @@ use std::hash::{Hash, sip};
 use std::slice::Items;
 use string_cache::{Atom, Namespace};
 use style::{mod, After, Before, ComputedValues, DeclarationBlock, Stylist, TElement, TNode};
+use style::{FirstLetter, FirstLine};
 use style::{AttrIsEqualMode, AttrIsPresentMode, CommonStyleAffectingAttributes, cascade};
 use sync::Arc;
 
@@ -26,6 +29,8 @@ pub struct ApplicableDeclarations {
     pub normal: SmallVec16<DeclarationBlock>,
     pub before: Vec<DeclarationBlock>,
     pub after: Vec<DeclarationBlock>,
+    pub first_line: Vec<DeclarationBlock>,
+    pub first_letter: Vec<DeclarationBlock>,
 
     /// Whether the `normal` declarations are shareable with other nodes.
     pub normal_shareable: bool,
@@ -37,6 +42,8 @@ impl ApplicableDeclarations {
             normal: SmallVec16::new(),
             before: Vec::new(),
             after: Vec::new(),
+            first_line: Vec::new(),
+            first_letter: Vec::new(),
             normal_shareable: false,
         }
     }
@@ -45,6 +52,8 @@ impl ApplicableDeclarations {
         self.normal = SmallVec16::new();
         self.before = Vec::new();
         self.after = Vec::new();
+        self.first_line = Vec::new();
+        self.first_letter = Vec::new();
         self.normal_shareable = false;
     }
 }
@@ -232,7 +241,7 @@ impl StyleSharingCandidate {
         };
 
         let element = node.as_element();
-        if element.style_attribute().is_some() {
+        if element.style_attribute().is_some() || animation::style_is_animated(&*style) {
             return None
         }
 
@@ -371,6 +380,7 @@ pub trait MatchMethods {
                                       -> StyleSharingResult;
 
     unsafe fn cascade_node(&self,
+                           layout_context: &SharedLayoutContext,
                            parent: Option<LayoutNode>,
                            applicable_declarations: &ApplicableDeclarations,
                            applicable_declarations_cache: &mut ApplicableDeclarationsCache);
@@ -499,10 +509,22 @@ impl<'ln> MatchMethods for LayoutNode<'ln> {
                                              None,
                                              Some(After),
                                              &mut applicable_declarations.after);
+        stylist.push_applicable_declarations(self,
+                                             parent_bf,
+                                             None,
+                                             Some(FirstLine),
+                                             &mut applicable_declarations.first_line);
+        stylist.push_applicable_declarations(self,
+                                             parent_bf,
+                                             None,
+                                             Some(FirstLetter),
+                                             &mut applicable_declarations.first_letter);
 
         *shareable = applicable_declarations.normal_shareable &&
             applicable_declarations.before.len() == 0 &&
-            applicable_declarations.after.len() == 0
+            applicable_declarations.after.len() == 0 &&
+            applicable_declarations.first_line.len() == 0 &&
+            applicable_declarations.first_letter.len() == 0
     }
 
     unsafe fn share_style_if_possible(&self,
@@ -522,6 +544,19 @@ impl<'ln> MatchMethods for LayoutNode<'ln> {
             return CannotShare(false)
         }
 
+        // The transitions and animations of the node must be updated as it is styled.
+        match *self.borrow_layout_data_unchecked() {
+            Some(ref layout_data) => {
+                match layout_data.shared_data.style {
+                    Some(ref style) if animation::style_is_animated(&**style) => {
+                        return CannotShare(false)
+                    }
+                    _ => {}
+                }
+            }
+            None => {}
+        }
+
         for (i, &(ref candidate, ())) in style_sharing_candidate_cache.iter().enumerate() {
             match self.share_style_with_candidate_if_possible(parent.clone(), candidate) {
                 Some(shared_style) => {
@@ -582,6 +617,7 @@ impl<'ln> MatchMethods for LayoutNode<'ln> {
     }
 
     unsafe fn cascade_node(&self,
+                           layout_context: &SharedLayoutContext,
                            parent: Option<LayoutNode>,
                            applicable_declarations: &ApplicableDeclarations,
                            applicable_declarations_cache: &mut ApplicableDeclarationsCache) {
@@ -614,12 +650,30 @@ impl<'ln> MatchMethods for LayoutNode<'ln> {
                         layout_data.shared_data.style = Some(cloned_parent_style);
                     }
                     _ => {
+                        let old_style = layout_data.shared_data.style.clone();
                         let mut damage = self.cascade_node_pseudo_element(
                             parent_style,
                             applicable_declarations.normal.as_slice(),
                             &mut layout_data.shared_data.style,
                             applicable_declarations_cache,
                             applicable_declarations.normal_shareable);
+
+                        // Run the transitions and animations on top of the cascaded style.
+                        let animated_style = animation::animate_style(
+                            layout_context,
+                            OpaqueNodeMethods::from_layout_node(self),
+                            old_style.as_ref(),
+                            layout_data.shared_data.style.as_ref().unwrap(),
+                            parent_style.map(|parent_style| &**parent_style),
+                            applicable_declarations.normal.as_slice());
+                        match animated_style {
+                            Some(animated_style) => {
+                                damage = incremental::compute_damage(&old_style,
+                                                                     &*animated_style);
+                                layout_data.shared_data.style = Some(animated_style);
+                            }
+                            None => {}
+                        }
                         if applicable_declarations.before.len() > 0 {
                            damage = damage | self.cascade_node_pseudo_element(
                                Some(layout_data.shared_data.style.as_ref().unwrap()),
@@ -636,6 +690,45 @@ impl<'ln> MatchMethods for LayoutNode<'ln> {
                                applicable_declarations_cache,
                                false);
                         }
+                        // The `first-line` style is baked into fragments during flow construction,
+                        // so any change to it requires the flow to be rebuilt.
+                        if applicable_declarations.first_line.len() > 0 {
+                           let pseudo_damage = self.cascade_node_pseudo_element(
+                               Some(layout_data.shared_data.style.as_ref().unwrap()),
+                               applicable_declarations.first_line.as_slice(),
+                               &mut layout_data.data.first_line_style,
+                               applicable_declarations_cache,
+                               false);
+                           if !pseudo_damage.is_empty() {
+                               damage = damage | pseudo_damage | RECONSTRUCT_FLOW;
+                           }
+                        } else if layout_data.data.first_line_style.take().is_some() {
+                            damage = damage | RECONSTRUCT_FLOW;
+                        }
+                        // The `first-letter` style is baked into fragments during flow construction,
+                        // so any change to it requires the flow to be rebuilt. It inherits from
+                        // the `first-line` style, since the first letter is on the first line. The
+                        // declarations are kept to style a first letter in a descendant inline.
+                        if applicable_declarations.first_letter.len() > 0 {
+                           let parent_style = match layout_data.data.first_line_style {
+                               Some(ref first_line_style) => first_line_style.clone(),
+                               None => layout_data.shared_data.style.as_ref().unwrap().clone(),
+                           };
+                           let pseudo_damage = self.cascade_node_pseudo_element(
+                               Some(&parent_style),
+                               applicable_declarations.first_letter.as_slice(),
+                               &mut layout_data.data.first_letter_style,
+                               applicable_declarations_cache,
+                               false);
+                           layout_data.data.first_letter_declarations =
+                               applicable_declarations.first_letter.clone();
+                           if !pseudo_damage.is_empty() {
+                               damage = damage | pseudo_damage | RECONSTRUCT_FLOW;
+                           }
+                        } else if layout_data.data.first_letter_style.take().is_some() {
+                            layout_data.data.first_letter_declarations = vec!();
+                            damage = damage | RECONSTRUCT_FLOW;
+                        }
                         layout_data.data.restyle_damage = damage;
                     }
                 }
@@ -12,12 +12,14 @@
 
 use block::BlockFlow;
 use context::LayoutContext;
-use flow::{mod, Flow, NEEDS_LAYER};
-use fragment::{Fragment, GenericFragment, IframeFragment, IframeFragmentInfo, ImageFragment};
+use flow::{mod, Flow, IS_ABSOLUTELY_POSITIONED, NEEDS_LAYER};
+use fragment::{CanvasFragment, Fragment, GenericFragment, IframeFragment, IframeFragmentInfo};
+use fragment::ImageFragment;
 use fragment::{ImageFragmentInfo, InlineAbsoluteHypotheticalFragment, InlineBlockFragment};
 use fragment::{ScannedTextFragment, ScannedTextFragmentInfo, TableFragment};
 use fragment::{TableCellFragment, TableColumnFragment, TableRowFragment, TableWrapperFragment};
-use fragment::{UnscannedTextFragment};
+use fragment::{GeneratedContentFragment, UnscannedTextFragment};
+use list_item::ListItemFlow;
 use model;
 use util::{OpaqueNodeMethods, ToGfxColor};
 
@@ -620,6 +622,9 @@ impl FragmentDisplayListBuilding for Fragment {
         match self.specific {
             UnscannedTextFragment(_) => panic!("Shouldn't see unscanned fragments here."),
             TableColumnFragment(_) => panic!("Shouldn't see table column fragments here."),
+            GeneratedContentFragment(_) => {
+                panic!("Shouldn't see unresolved generated content fragments here.")
+            }
             ScannedTextFragment(ref text_fragment) => {
                 // Create the text display item.
                 let orientation = if self.style.writing_mode.is_vertical() {
@@ -640,17 +645,21 @@ impl FragmentDisplayListBuilding for Fragment {
                         + flow_origin
                 };
 
-                display_list.content.push_back(TextDisplayItemClass(box TextDisplayItem {
-                    base: BaseDisplayItem::new(absolute_content_box, self.node, *clip_rect),
-                    text_run: text_fragment.run.clone(),
-                    range: text_fragment.range,
-                    text_color: self.style().get_color().color.to_gfx_color(),
-                    orientation: orientation,
-                    baseline_origin: baseline_origin,
-                }));
+                // Text laid out in a stand-in for a web font that is still in its block period
+                // takes up space but isn't shown, and neither are its decorations.
+                if !text_fragment.run.invisible {
+                    display_list.content.push_back(TextDisplayItemClass(box TextDisplayItem {
+                        base: BaseDisplayItem::new(absolute_content_box, self.node, *clip_rect),
+                        text_run: text_fragment.run.clone(),
+                        range: text_fragment.range,
+                        text_color: self.style().get_color().color.to_gfx_color(),
+                        orientation: orientation,
+                        baseline_origin: baseline_origin,
+                    }));
+                }
 
                 // Create display items for text decoration
-                {
+                if !text_fragment.run.invisible {
                     let line = |maybe_color: Option<RGBA>, rect: || -> LogicalRect<Au>| {
                         match maybe_color {
                             None => {}
@@ -706,10 +715,22 @@ impl FragmentDisplayListBuilding for Fragment {
             }
             ImageFragment(ref mut image_fragment) => {
                 let image_ref = &mut image_fragment.image;
-                match image_ref.get_image(self.node.to_untrusted_node_address()) {
-                    Some(image) => {
+                let time = layout_context.shared.animation_time;
+                match image_ref.get_frame(self.node.to_untrusted_node_address(), time) {
+                    Some((image, next_frame_delay)) => {
                         debug!("(building display list) building image fragment");
 
+                        // Reflow again when an animated image shows its next frame.
+                        match next_frame_delay {
+                            Some(delay) => {
+                                let mut animation_manager =
+                                    layout_context.shared.animation_manager.lock();
+                                animation_manager.request_image_frame(time +
+                                                                      delay as f64 / 1000.0);
+                            }
+                            None => {}
+                        }
+
                         // Place the image into the display list.
                         display_list.content.push_back(ImageDisplayItemClass(box ImageDisplayItem {
                             base: BaseDisplayItem::new(absolute_content_box,
@@ -727,6 +748,23 @@ impl FragmentDisplayListBuilding for Fragment {
                     }
                 }
             }
+            CanvasFragment(ref canvas_fragment_info) => {
+                let size = canvas_fragment_info.canvas_size;
+                // A canvas without a rendering context is transparent black, so there is nothing
+                // to paint.
+                match canvas_fragment_info.renderer {
+                    Some(ref renderer) if size.width > 0 && size.height > 0 => {
+                        display_list.content.push_back(ImageDisplayItemClass(box ImageDisplayItem {
+                            base: BaseDisplayItem::new(absolute_content_box,
+                                                       self.node,
+                                                       *clip_rect),
+                            image: renderer.lock().frame(),
+                            stretch_size: absolute_content_box.size,
+                        }));
+                    }
+                    _ => {}
+                }
+            }
         }
 
         if opts::get().show_debug_fragment_borders {
@@ -805,11 +843,15 @@ pub trait BlockFlowDisplayListBuilding {
                                          layout_context: &LayoutContext,
                                          background_border_level: BackgroundAndBorderLevel);
     fn build_display_list_for_block(&mut self,
+                                    display_list: Box<DisplayList>,
                                     layout_context: &LayoutContext,
                                     background_border_level: BackgroundAndBorderLevel);
     fn build_display_list_for_absolutely_positioned_block(&mut self,
+                                                          display_list: Box<DisplayList>,
                                                           layout_context: &LayoutContext);
-    fn build_display_list_for_floating_block(&mut self, layout_context: &LayoutContext);
+    fn build_display_list_for_floating_block(&mut self,
+                                             display_list: Box<DisplayList>,
+                                             layout_context: &LayoutContext);
     fn create_stacking_context(&self,
                                display_list: Box<DisplayList>,
                                layer: Option<Arc<RenderLayer>>)
@@ -836,9 +878,9 @@ impl BlockFlowDisplayListBuilding for BlockFlow {
     }
 
     fn build_display_list_for_block(&mut self,
+                                    mut display_list: Box<DisplayList>,
                                     layout_context: &LayoutContext,
                                     background_border_level: BackgroundAndBorderLevel) {
-        let mut display_list = box DisplayList::new();
         self.build_display_list_for_block_base(&mut *display_list,
                                                layout_context,
                                                background_border_level);
@@ -851,14 +893,19 @@ impl BlockFlowDisplayListBuilding for BlockFlow {
     }
 
     fn build_display_list_for_absolutely_positioned_block(&mut self,
+                                                          mut display_list: Box<DisplayList>,
                                                           layout_context: &LayoutContext) {
-        let mut display_list = box DisplayList::new();
         self.build_display_list_for_block_base(&mut *display_list,
                                                layout_context,
                                                RootOfStackingContextLevel);
 
-        if !self.base.absolute_position_info.layers_needed_for_positioned_flows &&
-                !self.base.flags.contains(NEEDS_LAYER) {
+        // The compositor can't transform layers, so transformed blocks are painted into the
+        // layer of their parent instead.
+        //
+        // TODO(#1244): Give them layers once it can, so that animating them is cheap.
+        if (!self.base.absolute_position_info.layers_needed_for_positioned_flows &&
+                !self.base.flags.contains(NEEDS_LAYER)) ||
+                !self.fragment.style().get_effects().transform.is_empty() {
             // We didn't need a layer.
             self.base.display_list_building_result =
                 StackingContextResult(self.create_stacking_context(display_list, None));
@@ -881,8 +928,9 @@ impl BlockFlowDisplayListBuilding for BlockFlow {
         self.base.display_list_building_result = StackingContextResult(stacking_context)
     }
 
-    fn build_display_list_for_floating_block(&mut self, layout_context: &LayoutContext) {
-        let mut display_list = box DisplayList::new();
+    fn build_display_list_for_floating_block(&mut self,
+                                             mut display_list: Box<DisplayList>,
+                                             layout_context: &LayoutContext) {
         self.build_display_list_for_block_base(&mut *display_list,
                                                layout_context,
                                                RootOfStackingContextLevel);
@@ -903,7 +951,46 @@ impl BlockFlowDisplayListBuilding for BlockFlow {
                           self.base.overflow.size.to_physical(self.base.writing_mode));
         let z_index = self.fragment.style().get_box().z_index.number_or_zero();
         let opacity = self.fragment.style().get_effects().opacity as f32;
-        Arc::new(StackingContext::new(display_list, bounds, z_index, opacity, layer))
+        let border_box = self.fragment.stacking_relative_bounds(&ZERO_POINT);
+        let transform = self.fragment.transform_matrix(&border_box);
+        Arc::new(StackingContext::new(display_list, bounds, z_index, opacity, transform, layer))
+    }
+}
+
+pub trait ListItemFlowDisplayListBuilding {
+    fn build_display_list_for_list_item(&mut self, layout_context: &LayoutContext);
+}
+
+impl ListItemFlowDisplayListBuilding for ListItemFlow {
+    fn build_display_list_for_list_item(&mut self, layout_context: &LayoutContext) {
+        // Draw the marker, if applicable. It moves with the border box of the list item.
+        let mut display_list = box DisplayList::new();
+        match self.marker {
+            None => {}
+            Some(ref mut marker) => {
+                let block = &self.block_flow;
+                let flow_origin = if block.fragment.establishes_stacking_context() {
+                    ZERO_POINT
+                } else {
+                    block.base.stacking_relative_position_of_child_fragment(&block.fragment)
+                };
+                marker.build_display_list(&mut *display_list,
+                                          layout_context,
+                                          flow_origin,
+                                          ContentLevel,
+                                          &block.base.clip_rect);
+            }
+        }
+
+        // Draw the rest of the block.
+        if self.block_flow.base.flags.is_float() {
+            self.block_flow.build_display_list_for_floating_block(display_list, layout_context)
+        } else if self.block_flow.base.flags.contains(IS_ABSOLUTELY_POSITIONED) {
+            self.block_flow.build_display_list_for_absolutely_positioned_block(display_list,
+                                                                               layout_context)
+        } else {
+            self.block_flow.build_display_list_for_block(display_list, layout_context, BlockLevel)
+        }
     }
 }
 
@@ -0,0 +1,770 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! CSS flexible box layout.
+//!
+//! This follows the CSS Flexible Box Layout Module Level 1 specification here:
+//!
+//!   http://dev.w3.org/csswg/css-flexbox/
+//!
+//! Hereafter this document is referred to as CSS-FLEXBOX.
+//!
+//! A flex container is laid out as a block formatting context whose in-flow kids are its flex
+//! items. The kids are first laid out as if they were stacked blocks; the flex container then
+//! overrides their inline sizes and positions during the assign-inline-sizes traversal, and their
+//! block sizes and positions during the assign-block-sizes traversal. Since both of these only
+//! touch the immediate kids of the flex container, they are compatible with the parallel
+//! traversals.
+
+#![deny(unsafe_blocks)]
+
+use block::{BlockFlow, MarginsMayNotCollapse};
+use construct::FlowConstructor;
+use context::LayoutContext;
+use floats::FloatKind;
+use flow::{FlexFlowClass, FlowClass, Flow};
+use flow::{IMPACTED_BY_LEFT_FLOATS, IMPACTED_BY_RIGHT_FLOATS, IS_ABSOLUTELY_POSITIONED};
+use flow;
+use fragment::FragmentBoundsIterator;
+use incremental::{REFLOW, REFLOW_OUT_OF_FLOW};
+use model::{Auto, MaybeAuto, Specified, specified, specified_or_none};
+use wrapper::ThreadSafeLayoutNode;
+
+use servo_util::geometry::Au;
+use servo_util::logical_geometry::LogicalSize;
+use std::cmp::max;
+use std::fmt;
+use style::{ComputedValues, CSSFloat};
+use style::computed_values::{LPA_Auto, LPA_Length, LPA_Percentage, LPN_Length, LPN_None};
+use style::computed_values::{LPN_Percentage, LP_Length, LP_Percentage, align_content};
+use style::computed_values::{align_items, align_self, box_sizing, flex_direction, flex_wrap};
+use style::computed_values::justify_content;
+use sync::Arc;
+
+/// A line of flex items. See CSS-FLEXBOX § 6.
+#[deriving(Encodable)]
+struct FlexLine {
+    /// The index of the first item of this line among the flex items of the container, in
+    /// `order`-modified document order.
+    start: uint,
+    /// The index one past the last item of this line.
+    end: uint,
+}
+
+/// A flex container.
+#[deriving(Encodable)]
+pub struct FlexFlow {
+    /// Data common to all block flows.
+    pub block_flow: BlockFlow,
+
+    /// The flex lines of a row flex container, as computed during inline-size assignment.
+    lines: Vec<FlexLine>,
+}
+
+impl FlexFlow {
+    pub fn from_node(constructor: &mut FlowConstructor, node: &ThreadSafeLayoutNode) -> FlexFlow {
+        FlexFlow {
+            block_flow: BlockFlow::from_node(constructor, node),
+            lines: Vec::new(),
+        }
+    }
+
+    pub fn float_from_node(constructor: &mut FlowConstructor,
+                           node: &ThreadSafeLayoutNode,
+                           float_kind: FloatKind)
+                           -> FlexFlow {
+        FlexFlow {
+            block_flow: BlockFlow::float_from_node(constructor, node, float_kind),
+            lines: Vec::new(),
+        }
+    }
+
+    /// Returns true if the main axis of this flex container is the inline axis.
+    fn is_row(&self) -> bool {
+        match self.block_flow.fragment.style().get_flex().flex_direction {
+            flex_direction::row | flex_direction::row_reverse => true,
+            flex_direction::column | flex_direction::column_reverse => false,
+        }
+    }
+
+    /// Resolves the flexible lengths of the items of a row flex container, breaks them into lines,
+    /// and positions them along the inline axis. See CSS-FLEXBOX § 9.
+    fn assign_inline_sizes_for_row(&mut self,
+                                   inline_start_content_edge: Au,
+                                   available_inline_size: Au) {
+        let (wraps, reverse, justify_content) = {
+            let style = self.block_flow.fragment.style();
+            (style.get_flex().flex_wrap != flex_wrap::nowrap,
+             style.get_flex().flex_direction == flex_direction::row_reverse,
+             style.get_flex().justify_content)
+        };
+
+        let mut items = flex_items(&mut self.block_flow);
+        let sizes: Vec<FlexItemSizes> = items.iter_mut().map(|kid| {
+            FlexItemSizes::for_inline_axis(&mut **kid, available_inline_size)
+        }).collect();
+
+        // Collect the items into lines (CSS-FLEXBOX § 9.3).
+        let mut lines = Vec::new();
+        let mut line_start = 0;
+        let mut line_size = Au(0);
+        for (index, item) in sizes.iter().enumerate() {
+            let outer_size = item.hypothetical_size + item.surrounding_size;
+            if wraps && index > line_start && line_size + outer_size > available_inline_size {
+                lines.push(FlexLine {
+                    start: line_start,
+                    end: index,
+                });
+                line_start = index;
+                line_size = Au(0);
+            }
+            line_size = line_size + outer_size;
+        }
+        lines.push(FlexLine {
+            start: line_start,
+            end: sizes.len(),
+        });
+
+        for line in lines.iter() {
+            let line_sizes = sizes.slice(line.start, line.end);
+            let target_sizes = resolve_flexible_lengths(line_sizes, available_inline_size);
+
+            let used_size = line_sizes.iter().zip(target_sizes.iter()).fold(Au(0),
+                |sum, (item, &size)| sum + size + item.surrounding_size);
+            let (mut position, spacing) = justify(justify_content,
+                                                  available_inline_size - used_size,
+                                                  line_sizes.len());
+
+            for index in range(line.start, line.end) {
+                let content_size = target_sizes[index - line.start];
+                let outer_size = content_size + sizes[index].surrounding_size;
+                let kid = &mut *items[index];
+
+                let offset = if reverse {
+                    available_inline_size - position - outer_size
+                } else {
+                    position
+                };
+                position = position + outer_size + spacing;
+
+                // The item's own inline-size assignment will pick up the size we give it here.
+                // Make sure that it runs if the size changed.
+                let kid_block = kid.as_block();
+                if kid_block.flex_item_inline_size != Some(content_size) {
+                    kid_block.flex_item_inline_size = Some(content_size);
+                    kid_block.base.restyle_damage.insert(REFLOW);
+                }
+
+                // FIXME: Percentage margins, borders and padding of the item will resolve against
+                // its outer size instead of the inline-size of the flex container.
+                kid_block.base.block_container_inline_size = outer_size;
+                kid_block.base.position.start.i = inline_start_content_edge + offset;
+            }
+        }
+
+        self.lines = lines
+    }
+
+    /// Sizes and positions the items of a column flex container along the inline axis, which is
+    /// its cross axis. See CSS-FLEXBOX § 9.4.
+    ///
+    /// FIXME: Column flex containers never wrap.
+    fn assign_inline_sizes_for_column(&mut self,
+                                      inline_start_content_edge: Au,
+                                      available_inline_size: Au) {
+        let align_items = self.block_flow.fragment.style().get_flex().align_items;
+
+        let mut items = flex_items(&mut self.block_flow);
+        for kid in items.iter_mut() {
+            let kid = &mut **kid;
+            let preferred_inline_size = flow::base(kid).intrinsic_inline_sizes
+                                                       .preferred_inline_size;
+            let (align, inline_size) = {
+                let style = kid.as_block().fragment.style();
+                (used_align_self(style.get_flex().align_self, align_items),
+                 style.content_inline_size())
+            };
+
+            // Stretched items with an automatic inline-size fill the container like blocks do.
+            if align == align_items::stretch && inline_size == LPA_Auto {
+                let kid_block = kid.as_block();
+                if kid_block.flex_item_inline_size.is_some() {
+                    kid_block.flex_item_inline_size = None;
+                    kid_block.base.restyle_damage.insert(REFLOW);
+                }
+                continue
+            }
+
+            // Otherwise, the item is sized as if it were a float (CSS 2.1 § 10.3.5).
+            let item = FlexItemSizes::for_inline_axis(kid, available_inline_size);
+            let content_size = match MaybeAuto::from_style(inline_size, available_inline_size) {
+                Specified(_) => item.hypothetical_size,
+                Auto => {
+                    let preferred_size = max(preferred_inline_size - item.surrounding_size,
+                                             Au(0));
+                    let available_size = max(available_inline_size - item.surrounding_size,
+                                             Au(0));
+                    item.clamp(if preferred_size < available_size {
+                        preferred_size
+                    } else {
+                        available_size
+                    })
+                }
+            };
+            let outer_size = content_size + item.surrounding_size;
+            let free_space = available_inline_size - outer_size;
+            let offset = match align {
+                align_items::flex_end => free_space,
+                align_items::center => free_space / 2,
+                // FIXME: Baseline alignment along the inline axis needs vertical writing modes.
+                align_items::stretch | align_items::flex_start | align_items::baseline => Au(0),
+            };
+
+            let kid_block = kid.as_block();
+            if kid_block.flex_item_inline_size != Some(content_size) {
+                kid_block.flex_item_inline_size = Some(content_size);
+                kid_block.base.restyle_damage.insert(REFLOW);
+            }
+            kid_block.base.block_container_inline_size = outer_size;
+            kid_block.base.position.start.i = inline_start_content_edge + offset;
+        }
+    }
+
+    /// Sizes the flex lines of a row flex container and aligns its items within them. Returns
+    /// the content block-size of the flex container. See CSS-FLEXBOX §§ 9.4 and 9.6.
+    fn assign_block_sizes_for_row(&mut self) -> Au {
+        let (wrap_reverse, align_items, align_content) = {
+            let style = self.block_flow.fragment.style();
+            (style.get_flex().flex_wrap == flex_wrap::wrap_reverse,
+             style.get_flex().align_items,
+             style.get_flex().align_content)
+        };
+        let block_start_content_edge = self.block_flow.fragment.border_padding.block_start;
+
+        // Each line is as tall as its tallest item, including margins.
+        let mut line_sizes: Vec<Au> = {
+            let mut items = flex_items(&mut self.block_flow);
+            self.lines.iter().map(|line| {
+                range(line.start, line.end).fold(Au(0), |size, index| {
+                    let kid_block = items[index].as_block();
+                    max(size, kid_block.base.position.size.block +
+                              kid_block.fragment.margin.block_start_end())
+                })
+            }).collect()
+        };
+        let lines_size = line_sizes.iter().fold(Au(0), |sum, &size| sum + size);
+        let content_block_size = self.block_flow.used_content_block_size(lines_size);
+
+        // A single-line flex container sizes its line to its own content box; a multi-line one
+        // distributes the leftover space according to `align-content`.
+        let free_space = content_block_size - lines_size;
+        let (mut position, spacing) = if line_sizes.len() == 1 ||
+                align_content == align_content::stretch {
+            if free_space > Au(0) {
+                let line_count = line_sizes.len() as i32;
+                for line_size in line_sizes.iter_mut() {
+                    *line_size = *line_size + free_space / line_count
+                }
+            }
+            (Au(0), Au(0))
+        } else {
+            let justify_content = match align_content {
+                align_content::flex_end => justify_content::flex_end,
+                align_content::center => justify_content::center,
+                align_content::space_between => justify_content::space_between,
+                align_content::space_around => justify_content::space_around,
+                align_content::flex_start | align_content::stretch => justify_content::flex_start,
+            };
+            justify(justify_content, free_space, line_sizes.len())
+        };
+
+        let mut items = flex_items(&mut self.block_flow);
+        for (line, &line_size) in self.lines.iter().zip(line_sizes.iter()) {
+            let line_offset = if wrap_reverse {
+                content_block_size - position - line_size
+            } else {
+                position
+            };
+            position = position + line_size + spacing;
+
+            for index in range(line.start, line.end) {
+                let kid_block = items[index].as_block();
+                let align = used_align_self(kid_block.fragment.style().get_flex().align_self,
+                                            align_items);
+                let margin = kid_block.fragment.margin;
+                let border_padding = kid_block.fragment.border_padding.block_start_end();
+
+                // Stretched items with an automatic block-size fill their line (CSS-FLEXBOX
+                // § 9.4, step 11).
+                if align == align_items::stretch &&
+                        kid_block.fragment.style().content_block_size() == LPA_Auto {
+                    let content_size = line_size - margin.block_start_end() - border_padding;
+                    let block_size =
+                        kid_block.used_content_block_size(max(content_size, Au(0))) +
+                        border_padding;
+                    kid_block.fragment.border_box.size.block = block_size;
+                    kid_block.base.position.size.block = block_size;
+                }
+
+                let free_space = line_size - kid_block.base.position.size.block -
+                    margin.block_start_end();
+                let offset = match (align, wrap_reverse) {
+                    (align_items::center, _) => free_space / 2,
+                    (align_items::flex_end, false) | (align_items::flex_start, true) => {
+                        free_space
+                    }
+                    // FIXME: Baseline alignment is not supported yet; such items are aligned to
+                    // the cross-start edge instead.
+                    (align_items::baseline, true) => free_space,
+                    _ => Au(0),
+                };
+                kid_block.base.position.start.b = block_start_content_edge + line_offset +
+                    offset + margin.block_start;
+            }
+        }
+
+        content_block_size
+    }
+
+    /// Resolves the flexible lengths of the items of a column flex container and positions them
+    /// along the block axis. Returns the content block-size of the flex container. See
+    /// CSS-FLEXBOX § 9.7.
+    fn assign_block_sizes_for_column(&mut self) -> Au {
+        let (reverse, justify_content) = {
+            let style = self.block_flow.fragment.style();
+            (style.get_flex().flex_direction == flex_direction::column_reverse,
+             style.get_flex().justify_content)
+        };
+        let block_start_content_edge = self.block_flow.fragment.border_padding.block_start;
+        let definite_block_size = match self.block_flow.fragment.style().content_block_size() {
+            LPA_Length(length) => Some(length),
+            LPA_Percentage(percent) => {
+                self.block_flow.base.block_container_explicit_block_size.map(|size| {
+                    size.scale_by(percent)
+                })
+            }
+            LPA_Auto => None,
+        };
+
+        let sizes: Vec<FlexItemSizes> = flex_items(&mut self.block_flow).into_iter().map(|kid| {
+            FlexItemSizes::for_block_axis(kid.as_block(), definite_block_size)
+        }).collect();
+        let hypothetical_size = sizes.iter().fold(Au(0), |sum, item| {
+            sum + item.hypothetical_size + item.surrounding_size
+        });
+        let content_block_size = self.block_flow.used_content_block_size(hypothetical_size);
+
+        let target_sizes = resolve_flexible_lengths(sizes.as_slice(), content_block_size);
+        let used_size = sizes.iter().zip(target_sizes.iter()).fold(Au(0), |sum, (item, &size)| {
+            sum + size + item.surrounding_size
+        });
+        let (mut position, spacing) = justify(justify_content,
+                                              content_block_size - used_size,
+                                              sizes.len());
+
+        let mut items = flex_items(&mut self.block_flow);
+        for (index, kid) in items.iter_mut().enumerate() {
+            let kid_block = kid.as_block();
+            let border_padding = kid_block.fragment.border_padding.block_start_end();
+            let block_size = target_sizes[index] + border_padding;
+            kid_block.fragment.border_box.size.block = block_size;
+            kid_block.base.position.size.block = block_size;
+
+            let outer_size = target_sizes[index] + sizes[index].surrounding_size;
+            let offset = if reverse {
+                content_block_size - position - outer_size
+            } else {
+                position
+            };
+            position = position + outer_size + spacing;
+            kid_block.base.position.start.b = block_start_content_edge + offset +
+                kid_block.fragment.margin.block_start;
+        }
+
+        content_block_size
+    }
+}
+
+/// Returns the flex items of the given flex container in `order`-modified document order
+/// (CSS-FLEXBOX § 5.4).
+///
+/// FIXME: Floated kids should be flex items too, since `float` does not apply to flex items.
+fn flex_items<'a>(block_flow: &'a mut BlockFlow) -> Vec<&'a mut Flow + 'a> {
+    let mut items: Vec<(i32, &'a mut Flow + 'a)> = block_flow.base.child_iter().filter(|kid| {
+        let kid_base = flow::base(&***kid);
+        !kid_base.flags.contains(IS_ABSOLUTELY_POSITIONED) && !kid_base.flags.is_float()
+    }).map(|kid| {
+        let order = kid.as_block().fragment.style().get_flex().order;
+        (order, kid)
+    }).collect();
+
+    // This is a stable sort, so items with the same `order` stay in document order.
+    items.sort_by(|&(a, _), &(b, _)| a.cmp(&b));
+    items.into_iter().map(|(_, kid)| kid).collect()
+}
+
+/// Returns the value of `align-self` that applies to an item, resolving `auto` against the
+/// `align-items` of its flex container.
+fn used_align_self(align_self: align_self::T, align_items: align_items::T) -> align_items::T {
+    match align_self {
+        align_self::auto => align_items,
+        align_self::stretch => align_items::stretch,
+        align_self::flex_start => align_items::flex_start,
+        align_self::flex_end => align_items::flex_end,
+        align_self::center => align_items::center,
+        align_self::baseline => align_items::baseline,
+    }
+}
+
+/// Returns the offset of the first of `count` boxes from the start of a line, and the extra space
+/// between adjacent boxes, that distribute `free_space` as the given `justify-content` value
+/// specifies. See CSS-FLEXBOX § 8.2.
+fn justify(justify_content: justify_content::T, free_space: Au, count: uint) -> (Au, Au) {
+    match justify_content {
+        justify_content::flex_start => (Au(0), Au(0)),
+        justify_content::flex_end => (free_space, Au(0)),
+        justify_content::center => (free_space / 2, Au(0)),
+        justify_content::space_between => {
+            if free_space > Au(0) && count > 1 {
+                (Au(0), free_space / (count - 1) as i32)
+            } else {
+                (Au(0), Au(0))
+            }
+        }
+        justify_content::space_around => {
+            if free_space > Au(0) && count > 0 {
+                let spacing = free_space / count as i32;
+                (spacing / 2, spacing)
+            } else {
+                (free_space / 2, Au(0))
+            }
+        }
+    }
+}
+
+/// The sizes of a flex item along the main axis of its flex container. All sizes are sizes of
+/// the content box.
+struct FlexItemSizes {
+    /// The flex base size (CSS-FLEXBOX § 9.2, step 3).
+    base_size: Au,
+    /// The flex base size, clamped by the min and max main sizes.
+    hypothetical_size: Au,
+    /// The min main size.
+    min_size: Au,
+    /// The max main size, if any.
+    max_size: Option<Au>,
+    /// The sum of the borders, padding and margins of the item along the main axis.
+    surrounding_size: Au,
+    /// The value of `flex-grow`.
+    grow: CSSFloat,
+    /// The value of `flex-shrink`.
+    shrink: CSSFloat,
+}
+
+impl FlexItemSizes {
+    /// Computes the sizes of an item of a row flex container whose content box is
+    /// `available_inline_size` wide.
+    ///
+    /// FIXME: The automatic minimum size of flex items (CSS-FLEXBOX § 4.5) is not implemented.
+    fn for_inline_axis(kid: &mut Flow, available_inline_size: Au) -> FlexItemSizes {
+        let preferred_inline_size = flow::base(kid).intrinsic_inline_sizes.preferred_inline_size;
+        let kid_block = kid.as_block();
+        kid_block.fragment.compute_border_and_padding(available_inline_size);
+        kid_block.fragment.compute_inline_direction_margins(available_inline_size);
+        let border_padding = kid_block.fragment.border_padding.inline_start_end();
+        let surrounding_size = border_padding + kid_block.fragment.margin.inline_start_end();
+
+        let style = kid_block.fragment.style();
+        let box_sizing_adjustment = match style.get_box().box_sizing {
+            box_sizing::border_box => border_padding,
+            box_sizing::content_box => Au(0),
+        };
+        let specified_size = match style.get_flex().flex_basis {
+            LPA_Auto => style.content_inline_size(),
+            flex_basis => flex_basis,
+        };
+        let base_size = match MaybeAuto::from_style(specified_size, available_inline_size) {
+            Specified(size) => max(size - box_sizing_adjustment, Au(0)),
+            Auto => max(preferred_inline_size - surrounding_size, Au(0)),
+        };
+
+        FlexItemSizes::new(style,
+                           base_size,
+                           specified(style.min_inline_size(), available_inline_size),
+                           specified_or_none(style.max_inline_size(), available_inline_size),
+                           surrounding_size)
+    }
+
+    /// Computes the sizes of an item of a column flex container, whose content box has the given
+    /// block-size if it is definite. The item must already have been laid out as a block.
+    fn for_block_axis(kid_block: &BlockFlow, definite_block_size: Option<Au>) -> FlexItemSizes {
+        let border_padding = kid_block.fragment.border_padding.block_start_end();
+        let surrounding_size = border_padding + kid_block.fragment.margin.block_start_end();
+        let content_size = kid_block.base.position.size.block - border_padding;
+
+        let style = kid_block.fragment.style();
+        let box_sizing_adjustment = match style.get_box().box_sizing {
+            box_sizing::border_box => border_padding,
+            box_sizing::content_box => Au(0),
+        };
+        let base_size = match (style.get_flex().flex_basis, definite_block_size) {
+            (LPA_Length(length), _) => max(length - box_sizing_adjustment, Au(0)),
+            (LPA_Percentage(percent), Some(definite_block_size)) => {
+                max(definite_block_size.scale_by(percent) - box_sizing_adjustment, Au(0))
+            }
+            (LPA_Percentage(_), None) | (LPA_Auto, _) => content_size,
+        };
+        let min_size = match (style.min_block_size(), definite_block_size) {
+            (LP_Length(length), _) => length,
+            (LP_Percentage(percent), Some(definite_block_size)) => {
+                definite_block_size.scale_by(percent)
+            }
+            (LP_Percentage(_), None) => Au(0),
+        };
+        let max_size = match (style.max_block_size(), definite_block_size) {
+            (LPN_Length(length), _) => Some(length),
+            (LPN_Percentage(percent), Some(definite_block_size)) => {
+                Some(definite_block_size.scale_by(percent))
+            }
+            (LPN_Percentage(_), None) | (LPN_None, _) => None,
+        };
+
+        FlexItemSizes::new(style, base_size, min_size, max_size, surrounding_size)
+    }
+
+    fn new(style: &ComputedValues,
+           base_size: Au,
+           min_size: Au,
+           max_size: Option<Au>,
+           surrounding_size: Au)
+           -> FlexItemSizes {
+        let mut sizes = FlexItemSizes {
+            base_size: base_size,
+            hypothetical_size: base_size,
+            min_size: min_size,
+            max_size: max_size,
+            surrounding_size: surrounding_size,
+            grow: style.get_flex().flex_grow,
+            shrink: style.get_flex().flex_shrink,
+        };
+        sizes.hypothetical_size = sizes.clamp(base_size);
+        sizes
+    }
+
+    /// Clamps the given main size by the min and max main sizes of this item.
+    fn clamp(&self, size: Au) -> Au {
+        let size = match self.max_size {
+            Some(max_size) if size > max_size => max_size,
+            _ => size,
+        };
+        max(size, self.min_size)
+    }
+}
+
+/// Resolves the flexible lengths of a line of items, given the space available to the line along
+/// the main axis, and returns the target main sizes of their content boxes. See CSS-FLEXBOX § 9.7.
+///
+/// FIXME: This distributes the free space in a single pass instead of repeatedly freezing the
+/// items that violate their min or max main sizes and redistributing the remainder.
+fn resolve_flexible_lengths(items: &[FlexItemSizes], available_size: Au) -> Vec<Au> {
+    let hypothetical_size = items.iter().fold(Au(0), |sum, item| {
+        sum + item.hypothetical_size + item.surrounding_size
+    });
+    let free_space = available_size - hypothetical_size;
+
+    if free_space > Au(0) {
+        let total_grow = items.iter().fold(0.0, |sum, item| sum + item.grow);
+        if total_grow > 0.0 {
+            return items.iter().map(|item| {
+                item.clamp(item.hypothetical_size + free_space.scale_by(item.grow / total_grow))
+            }).collect()
+        }
+    } else if free_space < Au(0) {
+        // Items shrink in proportion to their flex shrink factor multiplied by their flex base
+        // size.
+        let total_shrink = items.iter().fold(0.0, |sum, item| {
+            sum + item.shrink * item.base_size.to_subpx()
+        });
+        if total_shrink > 0.0 {
+            return items.iter().map(|item| {
+                let ratio = item.shrink * item.base_size.to_subpx() / total_shrink;
+                item.clamp(max(item.hypothetical_size + free_space.scale_by(ratio), Au(0)))
+            }).collect()
+        }
+    }
+
+    items.iter().map(|item| item.hypothetical_size).collect()
+}
+
+impl Flow for FlexFlow {
+    fn class(&self) -> FlowClass {
+        FlexFlowClass
+    }
+
+    fn as_block<'a>(&'a mut self) -> &'a mut BlockFlow {
+        &mut self.block_flow
+    }
+
+    fn as_immutable_block<'a>(&'a self) -> &'a BlockFlow {
+        &self.block_flow
+    }
+
+    fn as_immutable_flex<'a>(&'a self) -> &'a FlexFlow {
+        self
+    }
+
+    fn bubble_inline_sizes(&mut self) {
+        // A column flex container contributes the same intrinsic inline-sizes as a block, since
+        // its items are stacked.
+        self.block_flow.bubble_inline_sizes();
+        if !self.is_row() {
+            return
+        }
+
+        // If this flex container has a fixed width, the block computation has already used it.
+        match self.block_flow.fragment.style().get_box().width {
+            LPA_Length(_) => return,
+            LPA_Percentage(_) | LPA_Auto => {}
+        }
+
+        // The items of a row flex container sit side by side. Unless they can wrap, the
+        // container can't be narrower than all of their minimum inline-sizes together.
+        let wraps = self.block_flow.fragment.style().get_flex().flex_wrap != flex_wrap::nowrap;
+        let mut computation = self.block_flow.fragment.compute_intrinsic_inline_sizes();
+        for kid in flex_items(&mut self.block_flow).into_iter() {
+            let kid_base = flow::base(kid);
+            computation.content_intrinsic_sizes.minimum_inline_size = if wraps {
+                max(computation.content_intrinsic_sizes.minimum_inline_size,
+                    kid_base.intrinsic_inline_sizes.minimum_inline_size)
+            } else {
+                computation.content_intrinsic_sizes.minimum_inline_size +
+                    kid_base.intrinsic_inline_sizes.minimum_inline_size
+            };
+            computation.content_intrinsic_sizes.preferred_inline_size =
+                computation.content_intrinsic_sizes.preferred_inline_size +
+                kid_base.intrinsic_inline_sizes.preferred_inline_size;
+        }
+        self.block_flow.base.intrinsic_inline_sizes = computation.finish();
+    }
+
+    fn assign_inline_sizes(&mut self, layout_context: &LayoutContext) {
+        debug!("assign_inline_sizes({}): assigning inline_size for flow",
+               if self.block_flow.base.flags.is_float() {
+                   "floated flex"
+               } else {
+                   "flex"
+               });
+
+        if !self.block_flow.base.restyle_damage.intersects(REFLOW_OUT_OF_FLOW | REFLOW) {
+            return
+        }
+
+        // Lay ourselves out as a block first. This computes our own inline-size and hands our
+        // content box to all of our kids, including the absolutely-positioned and floated ones.
+        self.block_flow.assign_inline_sizes(layout_context);
+
+        // Flex items are laid out side by side or stacked according to the flex algorithm, so
+        // they are never impacted by floats.
+        for kid in flex_items(&mut self.block_flow).into_iter() {
+            let kid_base = flow::mut_base(kid);
+            kid_base.flags.remove(IMPACTED_BY_LEFT_FLOATS);
+            kid_base.flags.remove(IMPACTED_BY_RIGHT_FLOATS);
+        }
+
+        let inline_start_content_edge = self.block_flow.fragment.border_box.start.i +
+            self.block_flow.fragment.border_padding.inline_start;
+        let available_inline_size = self.block_flow.fragment.border_box.size.inline -
+            self.block_flow.fragment.border_padding.inline_start_end();
+        if self.is_row() {
+            self.assign_inline_sizes_for_row(inline_start_content_edge, available_inline_size)
+        } else {
+            self.assign_inline_sizes_for_column(inline_start_content_edge, available_inline_size)
+        }
+    }
+
+    fn assign_block_size<'a>(&mut self, layout_context: &'a LayoutContext<'a>) {
+        debug!("assign_block_size: assigning block_size for flex");
+
+        // Stack the items as blocks first. This lays out floats and absolutely-positioned kids
+        // and gives us the block-size of each item.
+        //
+        // FIXME: The block-sizes of absolutely-positioned descendants for which this flow is the
+        // containing block are computed against the stacked block-size, not the final one.
+        let needs_reflow = self.block_flow.base.restyle_damage.contains(REFLOW);
+        self.block_flow.assign_block_size_block_base(layout_context, MarginsMayNotCollapse);
+        if !needs_reflow {
+            return
+        }
+
+        let mut content_block_size = if self.is_row() {
+            self.assign_block_sizes_for_row()
+        } else {
+            self.assign_block_sizes_for_column()
+        };
+
+        if self.block_flow.base.flags.contains(IS_ABSOLUTELY_POSITIONED) {
+            // Store the content block-size for use in calculating the absolute flow's dimensions
+            // later, as `BlockFlow::assign_block_size_block_base` does.
+            self.block_flow.fragment.border_box.size.block = content_block_size;
+            return
+        }
+
+        if self.block_flow.is_root() {
+            let screen_size = LogicalSize::from_physical(self.block_flow.base.writing_mode,
+                                                         layout_context.shared.screen_size);
+            content_block_size = max(content_block_size, screen_size.block)
+        }
+
+        let block_size = content_block_size +
+            self.block_flow.fragment.border_padding.block_start_end();
+        self.block_flow.fragment.border_box.size.block = block_size;
+        self.block_flow.base.position.size.block = block_size;
+    }
+
+    fn compute_absolute_position(&mut self) {
+        self.block_flow.compute_absolute_position()
+    }
+
+    fn place_float_if_applicable<'a>(&mut self, layout_context: &'a LayoutContext<'a>) {
+        self.block_flow.place_float_if_applicable(layout_context)
+    }
+
+    fn assign_block_size_for_inorder_child_if_necessary<'a>(&mut self,
+                                                            layout_context: &'a LayoutContext<'a>)
+                                                            -> bool {
+        // Flex containers are formatting contexts, so they are never impacted by floats and
+        // never have to be laid out again here.
+        self.block_flow.assign_block_size_for_inorder_child_if_necessary(layout_context)
+    }
+
+    fn update_late_computed_inline_position_if_necessary(&mut self, inline_position: Au) {
+        self.block_flow.update_late_computed_inline_position_if_necessary(inline_position)
+    }
+
+    fn update_late_computed_block_position_if_necessary(&mut self, block_position: Au) {
+        self.block_flow.update_late_computed_block_position_if_necessary(block_position)
+    }
+
+    fn build_display_list(&mut self, layout_context: &LayoutContext) {
+        self.block_flow.build_display_list(layout_context)
+    }
+
+    fn repair_style(&mut self, new_style: &Arc<ComputedValues>) {
+        self.block_flow.repair_style(new_style)
+    }
+
+    fn iterate_through_fragment_bounds(&self, iterator: &mut FragmentBoundsIterator) {
+        self.block_flow.iterate_through_fragment_bounds(iterator);
+    }
+}
+
+impl fmt::Show for FlexFlow {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.block_flow.base.flags.is_float() {
+            write!(f, "FlexFlow(Float): {}", self.block_flow.fragment)
+        } else {
+            write!(f, "FlexFlow: {}", self.block_flow.fragment)
+        }
+    }
+}
@@ -30,12 +30,15 @@ use block::BlockFlow;
 use context::LayoutContext;
 use display_list_builder::{DisplayListBuildingResult, DisplayListResult};
 use display_list_builder::{NoDisplayListBuildingResult, StackingContextResult};
+use flex::FlexFlow;
 use floats::Floats;
 use flow_list::{FlowList, FlowListIterator, MutFlowListIterator};
 use flow_ref::FlowRef;
-use fragment::{Fragment, FragmentBoundsIterator, TableRowFragment, TableCellFragment};
+use fragment::{Fragment, FragmentBoundsIterator, GenericFragment, TableRowFragment};
+use fragment::TableCellFragment;
 use incremental::{RECONSTRUCT_FLOW, REFLOW, REFLOW_OUT_OF_FLOW, RestyleDamage};
 use inline::InlineFlow;
+use list_item::ListItemFlow;
 use model::{CollapsibleMargins, IntrinsicISizes, MarginCollapseInfo};
 use parallel::FlowParallelInfo;
 use table::{ColumnInlineSize, TableFlow};
@@ -163,6 +166,22 @@ pub trait Flow: fmt::Show + ToString + Sync {
         panic!("called as_table_cell() on a non-tablecell flow")
     }
 
+    /// If this is a flex flow, returns the underlying object, borrowed immutably. Fails otherwise.
+    fn as_immutable_flex<'a>(&'a self) -> &'a FlexFlow {
+        panic!("called as_immutable_flex() on a non-flex flow")
+    }
+
+    /// If this is a list item flow, returns the underlying object. Fails otherwise.
+    fn as_list_item<'a>(&'a mut self) -> &'a mut ListItemFlow {
+        panic!("called as_list_item() on a non-list-item flow")
+    }
+
+    /// If this is a list item flow, returns the underlying object, borrowed immutably. Fails
+    /// otherwise.
+    fn as_immutable_list_item<'a>(&'a self) -> &'a ListItemFlow {
+        panic!("called as_immutable_list_item() on a non-list-item flow")
+    }
+
     /// If this is a table row or table rowgroup or table flow, returns column inline-sizes.
     /// Fails otherwise.
     fn column_inline_sizes<'a>(&'a mut self) -> &'a mut Vec<ColumnInlineSize> {
@@ -428,6 +447,8 @@ pub enum FlowClass {
     TableRowFlowClass,
     TableCaptionFlowClass,
     TableCellFlowClass,
+    FlexFlowClass,
+    ListItemFlowClass,
 }
 
 /// A top-down traversal.
@@ -501,6 +522,10 @@ bitflags! {
 
                  NB: If you update this, update `TEXT_ALIGN_SHIFT` below."]
         const TEXT_ALIGN = 0b0111_1000_0000_0000,
+        #[doc = "Whether the containing block of this flow is right-to-left. This determines which"]
+        #[doc = "margin is ignored when inline sizes are over-constrained (CSS 2.1 § 10.3.3) and"]
+        #[doc = "where absolutely positioned flows go by default (CSS 2.1 § 10.3.7)."]
+        const PARENT_IS_RTL = 0b1000_0000_0000_0000,
     }
 }
 
@@ -537,7 +562,7 @@ impl FlowFlags {
 
     #[inline]
     pub fn set_text_align_override(&mut self, parent: FlowFlags) {
-        self.insert(parent & TEXT_ALIGN);
+        *self = (*self & !TEXT_ALIGN) | (parent & TEXT_ALIGN);
     }
 
     #[inline]
@@ -815,6 +840,8 @@ impl<E, S: Encoder<E>> Encodable<S, E> for BaseFlow {
                                         TableRowGroupFlowClass => c.as_immutable_table_rowgroup().encode(e),
                                         TableRowFlowClass => c.as_immutable_table_row().encode(e),
                                         TableCellFlowClass => c.as_immutable_table_cell().encode(e),
+                                        FlexFlowClass => c.as_immutable_flex().encode(e),
+                                        ListItemFlowClass => c.as_immutable_list_item().encode(e),
                                         _ => { Ok(()) }     // TODO: Support captions
                                     }
                                 })
@@ -974,10 +1001,10 @@ impl BaseFlow {
 }
 
 impl<'a> ImmutableFlowUtils for &'a Flow + 'a {
-    /// Returns true if this flow is a block flow.
+    /// Returns true if this flow is a block flow or a flex or list item flow, which wrap one.
     fn is_block_like(self) -> bool {
         match self.class() {
-            BlockFlowClass => true,
+            BlockFlowClass | FlexFlowClass | ListItemFlowClass => true,
             _ => false,
         }
     }
@@ -1058,6 +1085,8 @@ impl<'a> ImmutableFlowUtils for &'a Flow + 'a {
             TableFlowClass => !child.is_proper_table_child(),
             TableRowGroupFlowClass => !child.is_table_row(),
             TableRowFlowClass => !child.is_table_cell(),
+            // CSS-FLEXBOX § 4: each contiguous run of text becomes an anonymous flex item.
+            FlexFlowClass => child.is_inline_flow(),
             _ => false
         }
     }
@@ -1066,13 +1095,17 @@ impl<'a> ImmutableFlowUtils for &'a Flow + 'a {
     fn generate_missing_child_flow(self, node: &ThreadSafeLayoutNode) -> FlowRef {
         let flow = match self.class() {
             TableFlowClass | TableRowGroupFlowClass => {
-                let fragment = Fragment::new_anonymous_table_fragment(node, TableRowFragment);
+                let fragment = Fragment::new_anonymous_fragment(node, TableRowFragment);
                 box TableRowFlow::from_node_and_fragment(node, fragment) as Box<Flow>
             },
             TableRowFlowClass => {
-                let fragment = Fragment::new_anonymous_table_fragment(node, TableCellFragment);
+                let fragment = Fragment::new_anonymous_fragment(node, TableCellFragment);
                 box TableCellFlow::from_node_and_fragment(node, fragment) as Box<Flow>
             },
+            FlexFlowClass => {
+                let fragment = Fragment::new_anonymous_fragment(node, GenericFragment);
+                box BlockFlow::from_node_and_fragment(node, fragment) as Box<Flow>
+            },
             _ => {
                 panic!("no need to generate a missing child")
             }
@@ -1099,7 +1132,8 @@ impl<'a> ImmutableFlowUtils for &'a Flow + 'a {
     fn is_block_container(self) -> bool {
         match self.class() {
             // TODO: Change this when inline-blocks are supported.
-            BlockFlowClass | TableCaptionFlowClass | TableCellFlowClass => {
+            BlockFlowClass | TableCaptionFlowClass | TableCellFlowClass | FlexFlowClass |
+            ListItemFlowClass => {
                 // FIXME: Actually check the type of the node
                 self.child_count() != 0
             }
@@ -22,7 +22,8 @@ use text;
 use util::OpaqueNodeMethods;
 use wrapper::{TLayoutNode, ThreadSafeLayoutNode};
 
-use geom::{Point2D, Rect, Size2D};
+use canvas::canvas_chan::CanvasChan;
+use geom::{Matrix2D, Point2D, Rect, Size2D};
 use gfx::display_list::OpaqueNode;
 use gfx::text::glyph::CharIndex;
 use gfx::text::text_run::TextRun;
@@ -43,8 +44,8 @@ use std::from_str::FromStr;
 use string_cache::Atom;
 use style::{ComputedValues, TElement, TNode, cascade_anonymous};
 use style::computed_values::{LengthOrPercentage, LengthOrPercentageOrAuto};
-use style::computed_values::{LengthOrPercentageOrNone};
-use style::computed_values::{LPA_Auto, clear, position, text_align, text_decoration};
+use style::computed_values::{LengthOrPercentageOrNone, content};
+use style::computed_values::{LPA_Auto, clear, position, text_align, text_decoration, transform};
 use style::computed_values::{vertical_align, white_space};
 use sync::{Arc, Mutex};
 use url::Url;
@@ -101,6 +102,14 @@ pub struct Fragment {
     /// that are part of an inline formatting context.
     pub inline_context: Option<InlineFragmentContext>,
 
+    /// The resolved bidirectional embedding level of this fragment, as determined by the Unicode
+    /// Bidirectional Algorithm. Even levels are left-to-right and odd levels are right-to-left.
+    pub bidi_level: u8,
+
+    /// The generated content that this text fragment was resolved from, if any, so that it can
+    /// be resolved again when the counters it uses change.
+    pub generated_content: Option<Box<ResolvedGeneratedContent>>,
+
     /// A debug ID that is consistent for the life of
     /// this fragment (via transform etc).
     pub debug_id: u16,
@@ -125,6 +134,8 @@ impl<E, S: Encoder<E>> Encodable<S, E> for Fragment {
 #[deriving(Clone)]
 pub enum SpecificFragmentInfo {
     GenericFragment,
+    CanvasFragment(Box<CanvasFragmentInfo>),
+    GeneratedContentFragment(Box<GeneratedContentInfo>),
     IframeFragment(Box<IframeFragmentInfo>),
     ImageFragment(Box<ImageFragmentInfo>),
 
@@ -146,7 +157,9 @@ impl SpecificFragmentInfo {
     fn restyle_damage(&self) -> RestyleDamage {
         let flow =
             match *self {
-                IframeFragment(_)
+                CanvasFragment(_)
+                | GeneratedContentFragment(_)
+                | IframeFragment(_)
                 | ImageFragment(_)
                 | ScannedTextFragment(_)
                 | TableFragment
@@ -166,6 +179,8 @@ impl SpecificFragmentInfo {
     pub fn get_type(&self) -> &'static str {
         match *self {
             GenericFragment => "GenericFragment",
+            CanvasFragment(_) => "CanvasFragment",
+            GeneratedContentFragment(_) => "GeneratedContentFragment",
             IframeFragment(_) => "IframeFragment",
             ImageFragment(_) => "ImageFragment",
             InlineAbsoluteHypotheticalFragment(_) => "InlineAbsoluteHypotheticalFragment",
@@ -181,6 +196,24 @@ impl SpecificFragmentInfo {
     }
 }
 
+/// Generated content whose text depends on counter values, which are not known until the whole
+/// flow tree has been constructed. The `generated_content` traversal replaces these with text
+/// fragments before layout proper begins.
+#[deriving(Clone)]
+pub enum GeneratedContentInfo {
+    /// The marker of a list item, per its `list-style-type`.
+    ListItemMarker,
+    /// The `content` of a pseudo-element that uses `counter()` or `counters()`.
+    ContentItems(Vec<content::ContentItem>),
+}
+
+/// Generated content that has been turned into text, and that text.
+#[deriving(Clone)]
+pub struct ResolvedGeneratedContent {
+    pub info: GeneratedContentInfo,
+    pub text: String,
+}
+
 /// A hypothetical box (see CSS 2.1 § 10.3.7) for an absolutely-positioned block that was declared
 /// with `display: inline;`.
 ///
@@ -216,17 +249,54 @@ impl InlineBlockFragmentInfo {
     }
 }
 
+/// A fragment that represents a replaced `<canvas>` element.
+#[deriving(Clone)]
+pub struct CanvasFragmentInfo {
+    pub replaced_image_fragment_info: ReplacedImageFragmentInfo,
+    /// The channel to the canvas task, or `None` if script has not created a rendering context.
+    pub renderer: Option<Arc<Mutex<CanvasChan>>>,
+    /// The size of the canvas bitmap, in pixels.
+    pub canvas_size: Size2D<int>,
+}
+
+impl CanvasFragmentInfo {
+    /// Creates the information specific to a canvas fragment.
+    pub fn new(node: &ThreadSafeLayoutNode) -> CanvasFragmentInfo {
+        CanvasFragmentInfo {
+            replaced_image_fragment_info: ReplacedImageFragmentInfo::new(node, None, None),
+            renderer: node.get_renderer().map(|renderer| Arc::new(Mutex::new(renderer))),
+            canvas_size: Size2D(node.get_canvas_width() as int, node.get_canvas_height() as int),
+        }
+    }
+
+    /// Returns the original inline-size of the canvas.
+    pub fn canvas_inline_size(&self) -> Au {
+        let size = self.canvas_size;
+        Au::from_px(if self.replaced_image_fragment_info.writing_mode_is_vertical {
+            size.height
+        } else {
+            size.width
+        })
+    }
+
+    /// Returns the original block-size of the canvas.
+    pub fn canvas_block_size(&self) -> Au {
+        let size = self.canvas_size;
+        Au::from_px(if self.replaced_image_fragment_info.writing_mode_is_vertical {
+            size.width
+        } else {
+            size.height
+        })
+    }
+}
+
 /// A fragment that represents a replaced content image and its accompanying borders, shadows, etc.
 #[deriving(Clone)]
 pub struct ImageFragmentInfo {
+    pub replaced_image_fragment_info: ReplacedImageFragmentInfo,
     /// The image held within this fragment.
     pub image: ImageHolder<UntrustedNodeAddress>,
     pub for_node: UntrustedNodeAddress,
-    pub computed_inline_size: Option<Au>,
-    pub computed_block_size: Option<Au>,
-    pub dom_inline_size: Option<Au>,
-    pub dom_block_size: Option<Au>,
-    pub writing_mode_is_vertical: bool,
 }
 
 impl ImageFragmentInfo {
@@ -246,7 +316,6 @@ impl ImageFragmentInfo {
             }).and_then(|pixels| Some(Au::from_px(pixels)))
         }
 
-        let is_vertical = node.style().writing_mode.is_vertical();
         let dom_width = convert_length(node, &atom!("width"));
         let dom_height = convert_length(node, &atom!("height"));
 
@@ -254,8 +323,65 @@ impl ImageFragmentInfo {
         let untrusted_node: UntrustedNodeAddress = opaque_node.to_untrusted_node_address();
 
         ImageFragmentInfo {
+            replaced_image_fragment_info: ReplacedImageFragmentInfo::new(node,
+                                                                         dom_width,
+                                                                         dom_height),
             image: ImageHolder::new(image_url, local_image_cache),
             for_node: untrusted_node,
+        }
+    }
+
+    /// Returns the original inline-size of the image.
+    pub fn image_inline_size(&mut self) -> Au {
+        let size = self.image.get_size(self.for_node).unwrap_or(Size2D::zero());
+        Au::from_px(if self.replaced_image_fragment_info.writing_mode_is_vertical {
+            size.height
+        } else {
+            size.width
+        })
+    }
+
+    /// Returns the original block-size of the image.
+    pub fn image_block_size(&mut self) -> Au {
+        let size = self.image.get_size(self.for_node).unwrap_or(Size2D::zero());
+        Au::from_px(if self.replaced_image_fragment_info.writing_mode_is_vertical {
+            size.width
+        } else {
+            size.height
+        })
+    }
+
+    /// Tile an image
+    pub fn tile_image(position: &mut Au, size: &mut Au,
+                        virtual_position: Au, image_size: u32) {
+        let image_size = image_size as int;
+        let delta_pixels = geometry::to_px(virtual_position - *position);
+        let tile_count = (delta_pixels + image_size - 1) / image_size;
+        let offset = Au::from_px(image_size * tile_count);
+        let new_position = virtual_position - offset;
+        *size = *position - new_position + *size;
+        *position = new_position;
+    }
+}
+
+/// The sizing information common to replaced content with an intrinsic size: images and
+/// canvases.
+#[deriving(Clone)]
+pub struct ReplacedImageFragmentInfo {
+    pub computed_inline_size: Option<Au>,
+    pub computed_block_size: Option<Au>,
+    pub dom_inline_size: Option<Au>,
+    pub dom_block_size: Option<Au>,
+    pub writing_mode_is_vertical: bool,
+}
+
+impl ReplacedImageFragmentInfo {
+    /// Creates the sizing information for a replaced element whose `width` and `height`
+    /// attributes, if any, specify its size.
+    pub fn new(node: &ThreadSafeLayoutNode, dom_width: Option<Au>, dom_height: Option<Au>)
+               -> ReplacedImageFragmentInfo {
+        let is_vertical = node.style().writing_mode.is_vertical();
+        ReplacedImageFragmentInfo {
             computed_inline_size: None,
             computed_block_size: None,
             dom_inline_size: if is_vertical { dom_height } else { dom_width },
@@ -274,18 +400,6 @@ impl ImageFragmentInfo {
         self.computed_block_size.expect("image block_size is not computed yet!")
     }
 
-    /// Returns the original inline-size of the image.
-    pub fn image_inline_size(&mut self) -> Au {
-        let size = self.image.get_size(self.for_node).unwrap_or(Size2D::zero());
-        Au::from_px(if self.writing_mode_is_vertical { size.height } else { size.width })
-    }
-
-    /// Returns the original block-size of the image.
-    pub fn image_block_size(&mut self) -> Au {
-        let size = self.image.get_size(self.for_node).unwrap_or(Size2D::zero());
-        Au::from_px(if self.writing_mode_is_vertical { size.width } else { size.height })
-    }
-
     // Return used value for inline-size or block-size.
     //
     // `dom_length`: inline-size or block-size as specified in the `img` tag.
@@ -318,16 +432,90 @@ impl ImageFragmentInfo {
         })
     }
 
-    /// Tile an image
-    pub fn tile_image(position: &mut Au, size: &mut Au,
-                        virtual_position: Au, image_size: u32) {
-        let image_size = image_size as int;
-        let delta_pixels = geometry::to_px(virtual_position - *position);
-        let tile_count = (delta_pixels + image_size - 1) / image_size;
-        let offset = Au::from_px(image_size * tile_count);
-        let new_position = virtual_position - offset;
-        *size = *position - new_position + *size;
-        *position = new_position;
+    /// Computes the used inline-size of the content from the style and the intrinsic size, and
+    /// returns the inline-size of the border box.
+    pub fn calculate_replaced_inline_size(&mut self,
+                                          style: &ComputedValues,
+                                          noncontent_inline_size: Au,
+                                          container_inline_size: Au,
+                                          intrinsic_inline_size: Au,
+                                          intrinsic_block_size: Au)
+                                          -> Au {
+        // TODO(ksh8281): compute border,margin
+        let inline_size = ReplacedImageFragmentInfo::style_length(
+            style.content_inline_size(),
+            self.dom_inline_size,
+            container_inline_size);
+
+        let inline_size = match inline_size {
+            Auto => {
+                if intrinsic_block_size == Au(0) {
+                    intrinsic_inline_size
+                } else {
+                    let ratio = intrinsic_inline_size.to_f32().unwrap() /
+                                intrinsic_block_size.to_f32().unwrap();
+
+                    let specified_height = ReplacedImageFragmentInfo::style_length(
+                        style.content_block_size(),
+                        self.dom_block_size,
+                        Au(0));
+                    let specified_height = match specified_height {
+                        Auto => intrinsic_block_size,
+                        Specified(h) => h,
+                    };
+                    let specified_height = ReplacedImageFragmentInfo::clamp_size(
+                        specified_height,
+                        style.min_block_size(),
+                        style.max_block_size(),
+                        Au(0));
+                    Au((specified_height.to_f32().unwrap() * ratio) as i32)
+                }
+            },
+            Specified(w) => w,
+        };
+
+        let inline_size = ReplacedImageFragmentInfo::clamp_size(inline_size,
+                                                                style.min_inline_size(),
+                                                                style.max_inline_size(),
+                                                                container_inline_size);
+
+        self.computed_inline_size = Some(inline_size);
+        inline_size + noncontent_inline_size
+    }
+
+    /// Computes the used block-size of the content from the style, the intrinsic size, and the
+    /// used inline-size, and returns the block-size of the border box.
+    pub fn calculate_replaced_block_size(&mut self,
+                                         style: &ComputedValues,
+                                         noncontent_block_size: Au,
+                                         containing_block_block_size: Au,
+                                         intrinsic_inline_size: Au,
+                                         intrinsic_block_size: Au)
+                                         -> Au {
+        // TODO(ksh8281): compute border,margin,padding
+        let inline_size = self.computed_inline_size();
+        let block_size = ReplacedImageFragmentInfo::style_length(
+            style.content_block_size(),
+            self.dom_block_size,
+            containing_block_block_size);
+
+        let block_size = match block_size {
+            Auto => {
+                let scale = intrinsic_inline_size.to_f32().unwrap() / inline_size.to_f32().unwrap();
+                Au((intrinsic_block_size.to_f32().unwrap() / scale) as i32)
+            },
+            Specified(h) => {
+                h
+            }
+        };
+
+        let block_size = ReplacedImageFragmentInfo::clamp_size(block_size,
+                                                               style.min_block_size(),
+                                                               style.max_block_size(),
+                                                               Au(0));
+
+        self.computed_block_size = Some(block_size);
+        block_size + noncontent_block_size
     }
 }
 
@@ -486,6 +674,8 @@ impl Fragment {
             margin: LogicalMargin::zero(writing_mode),
             specific: constructor.build_specific_fragment_info_for_node(node),
             inline_context: None,
+            bidi_level: 0,
+            generated_content: None,
             debug_id: layout_debug::generate_unique_debug_id(),
         }
     }
@@ -504,14 +694,15 @@ impl Fragment {
             margin: LogicalMargin::zero(writing_mode),
             specific: specific,
             inline_context: None,
+            bidi_level: 0,
+            generated_content: None,
             debug_id: layout_debug::generate_unique_debug_id(),
         }
     }
 
-    /// Constructs a new `Fragment` instance for an anonymous table object.
-    pub fn new_anonymous_table_fragment(node: &ThreadSafeLayoutNode,
-                                        specific: SpecificFragmentInfo)
-                                        -> Fragment {
+    /// Constructs a new `Fragment` instance for an anonymous table object or flex item.
+    pub fn new_anonymous_fragment(node: &ThreadSafeLayoutNode, specific: SpecificFragmentInfo)
+                                  -> Fragment {
         // CSS 2.1 § 17.2.1 This is for non-inherited properties on anonymous table fragments
         // example:
         //
@@ -520,7 +711,8 @@ impl Fragment {
         //     </div>
         //
         // Anonymous table fragments, TableRowFragment and TableCellFragment, are generated around
-        // `Foo`, but they shouldn't inherit the border.
+        // `Foo`, but they shouldn't inherit the border. The same goes for the anonymous flex items
+        // that wrap runs of text in a flex container.
 
         let node_style = cascade_anonymous(&**node.style());
         let writing_mode = node_style.writing_mode;
@@ -533,6 +725,8 @@ impl Fragment {
             margin: LogicalMargin::zero(writing_mode),
             specific: specific,
             inline_context: None,
+            bidi_level: 0,
+            generated_content: None,
             debug_id: layout_debug::generate_unique_debug_id(),
         }
     }
@@ -553,6 +747,8 @@ impl Fragment {
             margin: LogicalMargin::zero(writing_mode),
             specific: specific,
             inline_context: None,
+            bidi_level: 0,
+            generated_content: None,
             debug_id: layout_debug::generate_unique_debug_id(),
         }
     }
@@ -613,10 +809,25 @@ impl Fragment {
             margin: self.margin,
             specific: ScannedTextFragment(info),
             inline_context: self.inline_context.clone(),
+            bidi_level: self.bidi_level,
+            generated_content: self.generated_content.clone(),
             debug_id: self.debug_id,
         }
     }
 
+    /// Creates a fragment for the same node that holds the given unscanned text with the given
+    /// style. This is used to split the `first-letter` pseudo-element off a text fragment.
+    pub fn with_unscanned_text_and_style(&self, text: String, style: Arc<ComputedValues>)
+                                         -> Fragment {
+        let mut fragment = Fragment::from_opaque_node_and_style(
+            self.node,
+            style,
+            self.restyle_damage,
+            UnscannedTextFragment(UnscannedTextFragmentInfo::from_text(text)));
+        fragment.inline_context = self.inline_context.clone();
+        fragment
+    }
+
     pub fn restyle_damage(&self) -> RestyleDamage {
         self.restyle_damage | self.specific.restyle_damage()
     }
@@ -635,7 +846,8 @@ impl Fragment {
     fn quantities_included_in_intrinsic_inline_size(&self)
                                                     -> QuantitiesIncludedInIntrinsicInlineSizes {
         match self.specific {
-            GenericFragment | IframeFragment(_) | ImageFragment(_) | InlineBlockFragment(_) => {
+            GenericFragment | CanvasFragment(_) | IframeFragment(_) | ImageFragment(_) |
+            InlineBlockFragment(_) => {
                 QuantitiesIncludedInIntrinsicInlineSizes::all()
             }
             TableFragment | TableCellFragment => {
@@ -653,7 +865,7 @@ impl Fragment {
                     INTRINSIC_INLINE_SIZE_INCLUDES_SPECIFIED
             }
             ScannedTextFragment(_) | TableColumnFragment(_) | UnscannedTextFragment(_) |
-            InlineAbsoluteHypotheticalFragment(_) => {
+            InlineAbsoluteHypotheticalFragment(_) | GeneratedContentFragment(_) => {
                 QuantitiesIncludedInIntrinsicInlineSizes::empty()
             }
         }
@@ -967,6 +1179,13 @@ impl Fragment {
                     preferred_inline_size: image_inline_size,
                 })
             }
+            CanvasFragment(ref canvas_fragment_info) => {
+                let canvas_inline_size = canvas_fragment_info.canvas_inline_size();
+                result.union_block(&IntrinsicISizes {
+                    minimum_inline_size: canvas_inline_size,
+                    preferred_inline_size: canvas_inline_size,
+                })
+            }
             ScannedTextFragment(ref text_fragment_info) => {
                 let range = &text_fragment_info.range;
                 let min_line_inline_size = text_fragment_info.run.min_width_for_range(range);
@@ -985,6 +1204,10 @@ impl Fragment {
             UnscannedTextFragment(..) => {
                 panic!("Unscanned text fragments should have been scanned by now!")
             }
+            GeneratedContentFragment(..) => {
+                // Flow construction bubbles inline-sizes before counters can be resolved. The
+                // generated content traversal bubbles them again once this has become text.
+            }
         };
 
         // Take borders and padding for parent inline fragments into account, if necessary.
@@ -1014,7 +1237,10 @@ impl Fragment {
             TableRowFragment | TableWrapperFragment | InlineBlockFragment(_) |
             InlineAbsoluteHypotheticalFragment(_) => Au(0),
             ImageFragment(ref image_fragment_info) => {
-                image_fragment_info.computed_inline_size()
+                image_fragment_info.replaced_image_fragment_info.computed_inline_size()
+            }
+            CanvasFragment(ref canvas_fragment_info) => {
+                canvas_fragment_info.replaced_image_fragment_info.computed_inline_size()
             }
             ScannedTextFragment(ref text_fragment_info) => {
                 let (range, run) = (&text_fragment_info.range, &text_fragment_info.run);
@@ -1023,6 +1249,9 @@ impl Fragment {
             }
             TableColumnFragment(_) => panic!("Table column fragments do not have inline_size"),
             UnscannedTextFragment(_) => panic!("Unscanned text fragments should have been scanned by now!"),
+            GeneratedContentFragment(_) => {
+                panic!("Generated content fragments should have been resolved by now!")
+            }
         }
     }
 
@@ -1033,7 +1262,10 @@ impl Fragment {
             TableRowFragment | TableWrapperFragment | InlineBlockFragment(_) |
             InlineAbsoluteHypotheticalFragment(_) => Au(0),
             ImageFragment(ref image_fragment_info) => {
-                image_fragment_info.computed_block_size()
+                image_fragment_info.replaced_image_fragment_info.computed_block_size()
+            }
+            CanvasFragment(ref canvas_fragment_info) => {
+                canvas_fragment_info.replaced_image_fragment_info.computed_block_size()
             }
             ScannedTextFragment(_) => {
                 // Compute the block-size based on the line-block-size and font size.
@@ -1041,6 +1273,9 @@ impl Fragment {
             }
             TableColumnFragment(_) => panic!("Table column fragments do not have block_size"),
             UnscannedTextFragment(_) => panic!("Unscanned text fragments should have been scanned by now!"),
+            GeneratedContentFragment(_) => {
+                panic!("Generated content fragments should have been resolved by now!")
+            }
         }
     }
 
@@ -1064,10 +1299,13 @@ impl Fragment {
     pub fn find_split_info_by_new_line(&self)
             -> Option<(SplitInfo, Option<SplitInfo>, Arc<Box<TextRun>> /* TODO(bjz): remove */)> {
         match self.specific {
-            GenericFragment | IframeFragment(_) | ImageFragment(_) | TableFragment | TableCellFragment |
-            TableRowFragment | TableWrapperFragment => None,
+            GenericFragment | CanvasFragment(_) | IframeFragment(_) | ImageFragment(_) |
+            TableFragment | TableCellFragment | TableRowFragment | TableWrapperFragment => None,
             TableColumnFragment(_) => panic!("Table column fragments do not need to split"),
             UnscannedTextFragment(_) => panic!("Unscanned text fragments should have been scanned by now!"),
+            GeneratedContentFragment(_) => {
+                panic!("Generated content fragments should have been resolved by now!")
+            }
             InlineBlockFragment(_) | InlineAbsoluteHypotheticalFragment(_) => {
                 panic!("Inline blocks or inline absolute hypothetical fragments do not get split")
             }
@@ -1112,13 +1350,16 @@ impl Fragment {
                                                       Option<SplitInfo>,
                                                       Arc<Box<TextRun>>)> {
         match self.specific {
-            GenericFragment | IframeFragment(_) | ImageFragment(_) | TableFragment |
-            TableCellFragment | TableRowFragment | TableWrapperFragment | InlineBlockFragment(_) |
-            InlineAbsoluteHypotheticalFragment(_) => None,
+            GenericFragment | CanvasFragment(_) | IframeFragment(_) | ImageFragment(_) |
+            TableFragment | TableCellFragment | TableRowFragment | TableWrapperFragment |
+            InlineBlockFragment(_) | InlineAbsoluteHypotheticalFragment(_) => None,
             TableColumnFragment(_) => panic!("Table column fragments do not have inline_size"),
             UnscannedTextFragment(_) => {
                 panic!("Unscanned text fragments should have been scanned by now!")
             }
+            GeneratedContentFragment(_) => {
+                panic!("Generated content fragments should have been resolved by now!")
+            }
             ScannedTextFragment(ref text_fragment_info) => {
                 let mut pieces_processed_count: uint = 0;
                 let mut remaining_inline_size: Au = max_inline_size;
@@ -1222,16 +1463,14 @@ impl Fragment {
             UnscannedTextFragment(_) => {
                 panic!("Unscanned text fragments should have been scanned by now!")
             }
-            ImageFragment(_) | ScannedTextFragment(_) | InlineBlockFragment(_) |
-            InlineAbsoluteHypotheticalFragment(_) => {}
+            GeneratedContentFragment(_) => {
+                panic!("Generated content fragments should have been resolved by now!")
+            }
+            CanvasFragment(_) | ImageFragment(_) | ScannedTextFragment(_) |
+            InlineBlockFragment(_) | InlineAbsoluteHypotheticalFragment(_) => {}
         };
 
-        let style_inline_size = self.style().content_inline_size();
-        let style_block_size = self.style().content_block_size();
-        let style_min_inline_size = self.style().min_inline_size();
-        let style_max_inline_size = self.style().max_inline_size();
-        let style_min_block_size = self.style().min_block_size();
-        let style_max_block_size = self.style().max_block_size();
+        let style = self.style.clone();
         let noncontent_inline_size = self.border_padding.inline_start_end();
 
         match self.specific {
@@ -1255,49 +1494,26 @@ impl Fragment {
                 self.border_box.size.inline = info.content_size.inline + noncontent_inline_size
             }
             ImageFragment(ref mut image_fragment_info) => {
-                // TODO(ksh8281): compute border,margin
-                let inline_size = ImageFragmentInfo::style_length(
-                    style_inline_size,
-                    image_fragment_info.dom_inline_size,
-                    container_inline_size);
-
-                let inline_size = match inline_size {
-                    Auto => {
-                        let intrinsic_width = image_fragment_info.image_inline_size();
-                        let intrinsic_height = image_fragment_info.image_block_size();
-
-                        if intrinsic_height == Au(0) {
-                            intrinsic_width
-                        } else {
-                            let ratio = intrinsic_width.to_f32().unwrap() /
-                                        intrinsic_height.to_f32().unwrap();
-
-                            let specified_height = ImageFragmentInfo::style_length(
-                                style_block_size,
-                                image_fragment_info.dom_block_size,
-                                Au(0));
-                            let specified_height = match specified_height {
-                                Auto => intrinsic_height,
-                                Specified(h) => h,
-                            };
-                            let specified_height = ImageFragmentInfo::clamp_size(
-                                specified_height,
-                                style_min_block_size,
-                                style_max_block_size,
-                                Au(0));
-                            Au((specified_height.to_f32().unwrap() * ratio) as i32)
-                        }
-                    },
-                    Specified(w) => w,
-                };
-
-                let inline_size = ImageFragmentInfo::clamp_size(inline_size,
-                                                                style_min_inline_size,
-                                                                style_max_inline_size,
-                                                                container_inline_size);
-
-                self.border_box.size.inline = inline_size + noncontent_inline_size;
-                image_fragment_info.computed_inline_size = Some(inline_size);
+                let intrinsic_inline_size = image_fragment_info.image_inline_size();
+                let intrinsic_block_size = image_fragment_info.image_block_size();
+                self.border_box.size.inline =
+                    image_fragment_info.replaced_image_fragment_info
+                                       .calculate_replaced_inline_size(&*style,
+                                                                       noncontent_inline_size,
+                                                                       container_inline_size,
+                                                                       intrinsic_inline_size,
+                                                                       intrinsic_block_size);
+            }
+            CanvasFragment(ref mut canvas_fragment_info) => {
+                let intrinsic_inline_size = canvas_fragment_info.canvas_inline_size();
+                let intrinsic_block_size = canvas_fragment_info.canvas_block_size();
+                self.border_box.size.inline =
+                    canvas_fragment_info.replaced_image_fragment_info
+                                        .calculate_replaced_inline_size(&*style,
+                                                                        noncontent_inline_size,
+                                                                        container_inline_size,
+                                                                        intrinsic_inline_size,
+                                                                        intrinsic_block_size);
             }
             _ => panic!("this case should have been handled above"),
         }
@@ -1315,42 +1531,38 @@ impl Fragment {
             UnscannedTextFragment(_) => {
                 panic!("Unscanned text fragments should have been scanned by now!")
             }
-            ImageFragment(_) | ScannedTextFragment(_) | InlineBlockFragment(_) |
-            InlineAbsoluteHypotheticalFragment(_) => {}
+            GeneratedContentFragment(_) => {
+                panic!("Generated content fragments should have been resolved by now!")
+            }
+            CanvasFragment(_) | ImageFragment(_) | ScannedTextFragment(_) |
+            InlineBlockFragment(_) | InlineAbsoluteHypotheticalFragment(_) => {}
         }
 
-        let style_block_size = self.style().content_block_size();
-        let style_min_block_size = self.style().min_block_size();
-        let style_max_block_size = self.style().max_block_size();
+        let style = self.style.clone();
         let noncontent_block_size = self.border_padding.block_start_end();
 
         match self.specific {
             ImageFragment(ref mut image_fragment_info) => {
-                // TODO(ksh8281): compute border,margin,padding
-                let inline_size = image_fragment_info.computed_inline_size();
-                let block_size = ImageFragmentInfo::style_length(
-                    style_block_size,
-                    image_fragment_info.dom_block_size,
-                    containing_block_block_size);
-
-                let block_size = match block_size {
-                    Auto => {
-                        let scale = image_fragment_info.image_inline_size().to_f32().unwrap()
-                            / inline_size.to_f32().unwrap();
-                        Au((image_fragment_info.image_block_size().to_f32().unwrap() / scale)
-                           as i32)
-                    },
-                    Specified(h) => {
-                        h
-                    }
-                };
-
-                let block_size = ImageFragmentInfo::clamp_size(block_size, style_min_block_size,
-                                                               style_max_block_size,
-                                                               Au(0));
-
-                image_fragment_info.computed_block_size = Some(block_size);
-                self.border_box.size.block = block_size + noncontent_block_size
+                let intrinsic_inline_size = image_fragment_info.image_inline_size();
+                let intrinsic_block_size = image_fragment_info.image_block_size();
+                self.border_box.size.block =
+                    image_fragment_info.replaced_image_fragment_info
+                                       .calculate_replaced_block_size(&*style,
+                                                                      noncontent_block_size,
+                                                                      containing_block_block_size,
+                                                                      intrinsic_inline_size,
+                                                                      intrinsic_block_size);
+            }
+            CanvasFragment(ref mut canvas_fragment_info) => {
+                let intrinsic_inline_size = canvas_fragment_info.canvas_inline_size();
+                let intrinsic_block_size = canvas_fragment_info.canvas_block_size();
+                self.border_box.size.block =
+                    canvas_fragment_info.replaced_image_fragment_info
+                                        .calculate_replaced_block_size(&*style,
+                                                                       noncontent_block_size,
+                                                                       containing_block_block_size,
+                                                                       intrinsic_inline_size,
+                                                                       intrinsic_block_size);
             }
             ScannedTextFragment(ref info) => {
                 // Scanned text fragments' content block-sizes are calculated by the text run
@@ -1377,13 +1589,24 @@ impl Fragment {
     pub fn inline_metrics(&self, layout_context: &LayoutContext) -> InlineMetrics {
         match self.specific {
             ImageFragment(ref image_fragment_info) => {
-                let computed_block_size = image_fragment_info.computed_block_size();
+                let computed_block_size =
+                    image_fragment_info.replaced_image_fragment_info.computed_block_size();
                 InlineMetrics {
                     block_size_above_baseline: computed_block_size + self.border_padding.block_start_end(),
                     depth_below_baseline: Au(0),
                     ascent: computed_block_size + self.border_padding.block_end,
                 }
             }
+            CanvasFragment(ref canvas_fragment_info) => {
+                let computed_block_size =
+                    canvas_fragment_info.replaced_image_fragment_info.computed_block_size();
+                InlineMetrics {
+                    block_size_above_baseline: computed_block_size +
+                        self.border_padding.block_start_end(),
+                    depth_below_baseline: Au(0),
+                    ascent: computed_block_size + self.border_padding.block_end,
+                }
+            }
             ScannedTextFragment(ref text_fragment) => {
                 // See CSS 2.1 § 10.8.1.
                 let line_height = self.calculate_line_height(layout_context);
@@ -1451,9 +1674,9 @@ impl Fragment {
         match self.specific {
             InlineBlockFragment(_) | InlineAbsoluteHypotheticalFragment(_) |
             TableWrapperFragment => false,
-            GenericFragment | IframeFragment(_) | ImageFragment(_) | ScannedTextFragment(_) |
-            TableFragment | TableCellFragment | TableColumnFragment(_) | TableRowFragment |
-            UnscannedTextFragment(_) => true,
+            GenericFragment | CanvasFragment(_) | IframeFragment(_) | ImageFragment(_) |
+            ScannedTextFragment(_) | TableFragment | TableCellFragment | TableColumnFragment(_) |
+            TableRowFragment | UnscannedTextFragment(_) | GeneratedContentFragment(_) => true,
         }
     }
 
@@ -1497,6 +1720,9 @@ impl Fragment {
         if self.style().get_effects().opacity != 1.0 {
             return true
         }
+        if !self.style().get_effects().transform.is_empty() {
+            return true
+        }
         match self.style().get_box().position {
             position::absolute | position::fixed => {
                 // FIXME(pcwalton): This should only establish a new stacking context when
@@ -1510,6 +1736,51 @@ impl Fragment {
             }
         }
     }
+
+    /// Returns the matrix for the `transform` and `transform-origin` properties of this fragment,
+    /// in the coordinate system of the stacking context that it establishes. `border_box` is the
+    /// border box of this fragment in that coordinate system.
+    pub fn transform_matrix(&self, border_box: &Rect<Au>) -> Matrix2D<f32> {
+        let effects = self.style().get_effects();
+        if effects.transform.is_empty() {
+            return Matrix2D::identity()
+        }
+
+        let origin_x = (border_box.origin.x +
+                        specified(effects.transform_origin.horizontal,
+                                  border_box.size.width)).to_subpx() as f32;
+        let origin_y = (border_box.origin.y +
+                        specified(effects.transform_origin.vertical,
+                                  border_box.size.height)).to_subpx() as f32;
+
+        let mut matrix = Matrix2D::identity().translate(origin_x, origin_y);
+        for operation in effects.transform.iter() {
+            let operation_matrix = match *operation {
+                transform::Matrix(a, b, c, d, e, f) => {
+                    Matrix2D::new(a as f32, b as f32, c as f32, d as f32, e as f32, f as f32)
+                }
+                transform::Translate(tx, ty) => {
+                    let tx = specified(tx, border_box.size.width).to_subpx() as f32;
+                    let ty = specified(ty, border_box.size.height).to_subpx() as f32;
+                    Matrix2D::new(1.0, 0.0, 0.0, 1.0, tx, ty)
+                }
+                transform::Scale(sx, sy) => {
+                    Matrix2D::new(sx as f32, 0.0, 0.0, sy as f32, 0.0, 0.0)
+                }
+                transform::Rotate(angle) => {
+                    let (sin, cos) = (angle.radians() as f32).sin_cos();
+                    Matrix2D::new(cos, sin, -sin, cos, 0.0, 0.0)
+                }
+                transform::Skew(angle_x, angle_y) => {
+                    Matrix2D::new(1.0, (angle_y.radians() as f32).tan(),
+                                  (angle_x.radians() as f32).tan(), 1.0,
+                                  0.0, 0.0)
+                }
+            };
+            matrix = matrix.mul(&operation_matrix)
+        }
+        matrix.translate(-origin_x, -origin_y)
+    }
 }
 
 impl fmt::Show for Fragment {
@@ -0,0 +1,472 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! The generated content traversal, which computes the values of counters (CSS 2.1 § 12.4) and
+//! turns list item markers and pseudo-element content that uses counters into text.
+//!
+//! The value of a counter at an element depends on every element before it in document order, so
+//! this can't happen during flow construction, which is bottom-up and may be parallel. Instead,
+//! flow construction leaves `GeneratedContentFragment`s in the flow tree, and this traversal
+//! visits the whole flow tree sequentially, in document order, once it has been built.
+//!
+//! Counter scopes follow the nesting of elements. Each flow and fragment is visited at the depth
+//! of its element in the document, which anonymous flows share with the element they were created
+//! for, and an instance of a counter created by an element goes out of scope when the traversal
+//! leaves the element's parent.
+//!
+//! Generated content that has been turned into text keeps what it was resolved from, so that it
+//! is resolved again on every layout and stays up to date as elements are inserted or removed.
+
+#![deny(unsafe_blocks)]
+
+use context::LayoutContext;
+use flow::{Flow, InlineFlowClass, ListItemFlowClass, TableColGroupFlowClass};
+use flow;
+use fragment::{ContentItems, Fragment, GeneratedContentFragment, GeneratedContentInfo};
+use fragment::{ImageFragment, InlineAbsoluteHypotheticalFragment, InlineBlockFragment};
+use fragment::{ListItemMarker, ResolvedGeneratedContent};
+use incremental::{BUBBLE_ISIZES, REFLOW, REFLOW_OUT_OF_FLOW};
+use inline::InlineFlow;
+use list_item::ListItemFlow;
+use text::TextRunScanner;
+
+use gfx::display_list::OpaqueNode;
+use std::ascii::AsciiExt;
+use std::collections::{DList, HashMap, HashSet};
+use std::collections::hash_map::{Occupied, Vacant};
+use std::mem;
+use style::ComputedValues;
+use style::computed_values::{content, display, list_style_type};
+
+/// The name of the counter that list items increment implicitly.
+static LIST_ITEM_COUNTER: &'static str = "list-item";
+
+static LOWER_GREEK: &'static str = "αβγδεζηθικλμνξοπρστυφχψω";
+static LOWER_LATIN: &'static str = "abcdefghijklmnopqrstuvwxyz";
+static UPPER_LATIN: &'static str = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+/// An instance of a counter (CSS 2.1 § 12.4.1).
+struct CounterInstance {
+    /// The depth in the document of the element that created this instance.
+    level: uint,
+    /// The current value of this instance.
+    value: i32,
+}
+
+/// The instances of a counter that are in scope, outermost first.
+struct Counter {
+    instances: Vec<CounterInstance>,
+}
+
+impl Counter {
+    fn new() -> Counter {
+        Counter {
+            instances: Vec::new(),
+        }
+    }
+
+    /// Drops the instances that were created deeper in the document than the given level.
+    fn truncate_to_level(&mut self, level: uint) {
+        while self.instances.last().map_or(false, |instance| instance.level > level) {
+            self.instances.pop();
+        }
+    }
+
+    /// Resets this counter at the given level. A reset replaces the instance created by a
+    /// preceding sibling, if any, and nests a new instance otherwise.
+    fn reset(&mut self, level: uint, value: i32) {
+        if self.instances.last().map_or(false, |instance| instance.level == level) {
+            self.instances.last_mut().unwrap().value = value;
+            return
+        }
+        self.instances.push(CounterInstance {
+            level: level,
+            value: value,
+        })
+    }
+
+    /// Increments the innermost instance of this counter, creating one at the given level if
+    /// there is none in scope.
+    fn increment(&mut self, level: uint, amount: i32) {
+        if self.instances.is_empty() {
+            self.reset(level, 0)
+        }
+        let instance = self.instances.last_mut().unwrap();
+        instance.value = instance.value + amount
+    }
+
+    /// The value of the innermost instance of this counter, or zero if there is none in scope.
+    fn value(&self) -> i32 {
+        self.instances.last().map_or(0, |instance| instance.value)
+    }
+}
+
+/// The state of the generated content traversal.
+pub struct ResolveGeneratedContent<'a> {
+    layout_context: &'a LayoutContext<'a>,
+
+    /// The counters that have been used so far, by name.
+    counters: HashMap<String, Counter>,
+
+    /// The elements and pseudo-elements whose counter properties have been applied, by node and
+    /// style. An element may have several fragments, such as a table and its wrapper or the
+    /// pieces of a line-broken run of text, but must only affect counters once.
+    applied: HashSet<(OpaqueNode, uint)>,
+}
+
+impl<'a> ResolveGeneratedContent<'a> {
+    pub fn new(layout_context: &'a LayoutContext<'a>) -> ResolveGeneratedContent<'a> {
+        ResolveGeneratedContent {
+            layout_context: layout_context,
+            counters: HashMap::new(),
+            applied: HashSet::new(),
+        }
+    }
+
+    /// Resolves the generated content of the given flow, whose element is at the given depth of
+    /// the document, and of its descendants. Returns true if any of them changed, in which case
+    /// their intrinsic inline-sizes have been recomputed and they have been marked as needing
+    /// reflow.
+    pub fn traverse(&mut self, flow: &mut Flow, level: uint) -> bool {
+        for (_, counter) in self.counters.iter_mut() {
+            counter.truncate_to_level(level)
+        }
+
+        let mut changed = match flow.class() {
+            InlineFlowClass => self.resolve_inline_flow(flow.as_inline(), level),
+            ListItemFlowClass => {
+                let list_item = flow.as_list_item();
+                self.apply_counter_properties(&list_item.block_flow.fragment, level);
+                self.resolve_outside_marker(list_item)
+            }
+            TableColGroupFlowClass => false,
+            _ => {
+                self.apply_counter_properties(&flow.as_block().fragment, level);
+                false
+            }
+        };
+
+        let node = flow_node(flow);
+        for kid in flow::child_iter(flow) {
+            // Anonymous flows and flows for inline content belong to the same element as their
+            // parent.
+            let kid_level = match flow_node(kid) {
+                Some(kid_node) if Some(kid_node) != node => level + 1,
+                _ => level,
+            };
+            if self.traverse(kid, kid_level) {
+                changed = true
+            }
+        }
+
+        if changed {
+            flow.bubble_inline_sizes();
+            flow::mut_base(flow).restyle_damage.insert(BUBBLE_ISIZES | REFLOW_OUT_OF_FLOW | REFLOW);
+        }
+        changed
+    }
+
+    fn counter(&mut self, name: &String) -> &mut Counter {
+        match self.counters.entry(name.clone()) {
+            Occupied(entry) => entry.into_mut(),
+            Vacant(entry) => entry.set(Counter::new()),
+        }
+    }
+
+    /// Applies `counter-reset` and then `counter-increment` for the element or pseudo-element
+    /// that the given fragment belongs to, at the given level.
+    fn apply_counter_properties(&mut self, fragment: &Fragment, level: uint) {
+        let style = fragment.style();
+        let box_style = style.get_box();
+        let is_list_item = box_style.display == display::list_item;
+        if box_style.counter_reset.is_empty() && box_style.counter_increment.is_empty() &&
+                !is_list_item {
+            return
+        }
+        let style_address = &**style as *const ComputedValues as uint;
+        if !self.applied.insert((fragment.node, style_address)) {
+            return
+        }
+
+        for &(ref name, value) in box_style.counter_reset.iter() {
+            self.counter(name).reset(level, value)
+        }
+
+        // List items increment the `list-item` counter unless they say otherwise.
+        let mut increments_list_item = is_list_item;
+        for &(ref name, amount) in box_style.counter_increment.iter() {
+            if name.as_slice() == LIST_ITEM_COUNTER {
+                increments_list_item = false
+            }
+            self.counter(name).increment(level, amount)
+        }
+        if increments_list_item {
+            self.counter(&LIST_ITEM_COUNTER.to_string()).increment(level, 1)
+        }
+    }
+
+    /// Applies counter properties for, and resolves generated content in, the fragments of the
+    /// given inline flow, whose block is at the given depth. The fragments are nested inside the
+    /// inline elements of their inline context.
+    fn resolve_inline_flow(&mut self, inline: &mut InlineFlow, level: uint) -> bool {
+        let mut changed = false;
+        let mut old_fragments = mem::replace(&mut inline.fragments.fragments, Vec::new())
+                                    .into_iter()
+                                    .peekable();
+        let mut new_fragments = Vec::new();
+        let mut previous_ancestors = Vec::new();
+        loop {
+            let fragment = match old_fragments.next() {
+                Some(fragment) => fragment,
+                None => break,
+            };
+
+            // Leaving an inline element ends the scope of the counters its children created.
+            let ancestors = inline_ancestors(&fragment);
+            let common_ancestors = ancestors.iter()
+                                            .zip(previous_ancestors.iter())
+                                            .take_while(|&(a, b)| a == b)
+                                            .count();
+            for (_, counter) in self.counters.iter_mut() {
+                counter.truncate_to_level(level + 1 + common_ancestors)
+            }
+            match fragment.specific {
+                // The flows of these are visited on their own.
+                InlineBlockFragment(_) | InlineAbsoluteHypotheticalFragment(_) => {}
+                _ => self.apply_counter_properties(&fragment, level + 1 + ancestors.len()),
+            }
+            previous_ancestors = ancestors;
+
+            let info = match fragment.specific {
+                GeneratedContentFragment(ref info) => Some((**info).clone()),
+                _ => fragment.generated_content.as_ref().map(|resolved| resolved.info.clone()),
+            };
+            let info = match info {
+                None => {
+                    new_fragments.push(fragment);
+                    continue
+                }
+                Some(info) => info,
+            };
+
+            // Line breaking may have split text that was resolved before into several fragments.
+            let mut pieces = vec!(fragment);
+            while old_fragments.peek().map_or(false, |next| {
+                is_same_generated_content(&pieces[0], next)
+            }) {
+                pieces.push(old_fragments.next().unwrap())
+            }
+
+            let text = self.text_for(&info, &pieces[0]);
+            let unchanged = match pieces[0].generated_content {
+                Some(ref resolved) => resolved.text == text,
+                None => false,
+            };
+            if unchanged {
+                new_fragments.extend(pieces.into_iter());
+            } else {
+                new_fragments.extend(self.build_text_fragments(&pieces[0], info, text).into_iter());
+                changed = true
+            }
+        }
+        inline.fragments.fragments = new_fragments;
+        changed
+    }
+
+    /// Updates the text of the marker of the given list item if the value of its counter has
+    /// changed since the last layout.
+    fn resolve_outside_marker(&mut self, list_item: &mut ListItemFlow) -> bool {
+        let text = match list_item.marker {
+            None => return false,
+            Some(ref marker) => {
+                match marker.specific {
+                    ImageFragment(_) => return false,
+                    _ => self.text_for(&ListItemMarker, marker),
+                }
+            }
+        };
+        if list_item.marker_text.as_ref() == Some(&text) {
+            return false
+        }
+
+        let marker = list_item.marker.take().unwrap();
+        list_item.marker = self.build_text_fragments(&marker, ListItemMarker, text.clone())
+                               .into_iter()
+                               .next();
+        list_item.marker_text = Some(text);
+        true
+    }
+
+    /// Returns the text for the given generated content of the given fragment.
+    fn text_for(&self, info: &GeneratedContentInfo, fragment: &Fragment) -> String {
+        match *info {
+            ListItemMarker => {
+                // The marker inherits `list-style-type` from its list item.
+                let list_style_type = fragment.style().get_list().list_style_type;
+                let value = self.counter_value(&LIST_ITEM_COUNTER.to_string());
+                let suffix = match list_style_type {
+                    list_style_type::none => return String::new(),
+                    list_style_type::disc | list_style_type::circle |
+                    list_style_type::square => " ",
+                    _ => ". ",
+                };
+                format!("{}{}", format_counter(value, list_style_type), suffix)
+            }
+            ContentItems(ref items) => {
+                let mut text = String::new();
+                for item in items.iter() {
+                    match *item {
+                        content::StringContent(ref string) => text.push_str(string.as_slice()),
+                        content::CounterContent(ref name, list_style_type) => {
+                            let value = self.counter_value(name);
+                            text.push_str(format_counter(value, list_style_type).as_slice())
+                        }
+                        content::CountersContent(ref name, ref separator, list_style_type) => {
+                            let values: Vec<i32> = match self.counters.get(name) {
+                                Some(counter) if !counter.instances.is_empty() => {
+                                    counter.instances
+                                           .iter()
+                                           .map(|instance| instance.value)
+                                           .collect()
+                                }
+                                _ => vec!(0),
+                            };
+                            for (i, &value) in values.iter().enumerate() {
+                                if i != 0 {
+                                    text.push_str(separator.as_slice())
+                                }
+                                text.push_str(format_counter(value, list_style_type).as_slice())
+                            }
+                        }
+                    }
+                }
+                text
+            }
+        }
+    }
+
+    fn counter_value(&self, name: &String) -> i32 {
+        self.counters.get(name).map_or(0, |counter| counter.value())
+    }
+
+    /// Builds the scanned text fragments that replace the given generated content fragment, or
+    /// the text that it was resolved to before.
+    fn build_text_fragments(&self, fragment: &Fragment, info: GeneratedContentInfo, text: String)
+                            -> Vec<Fragment> {
+        let mut text_fragment = fragment.with_unscanned_text_and_style(text.clone(),
+                                                                       fragment.style.clone());
+        text_fragment.generated_content = Some(box ResolvedGeneratedContent {
+            info: info,
+            text: text,
+        });
+        let mut fragments = DList::new();
+        fragments.push_back(text_fragment);
+        TextRunScanner::new().scan_for_runs(self.layout_context.font_context(),
+                                            fragments,
+                                            &*fragment.style)
+                             .fragments
+    }
+}
+
+/// The node of the element that the given flow was created for, or `None` if the flow only holds
+/// inline content.
+fn flow_node(flow: &mut Flow) -> Option<OpaqueNode> {
+    match flow.class() {
+        InlineFlowClass | TableColGroupFlowClass => None,
+        _ => Some(flow.as_block().fragment.node),
+    }
+}
+
+/// The styles of the inline elements that the given fragment is nested in, outermost first, by
+/// address.
+fn inline_ancestors(fragment: &Fragment) -> Vec<uint> {
+    match fragment.inline_context {
+        Some(ref context) => {
+            context.styles
+                   .iter()
+                   .rev()
+                   .map(|style| &**style as *const ComputedValues as uint)
+                   .collect()
+        }
+        None => Vec::new(),
+    }
+}
+
+/// Returns true if `next` is another piece of the text that `fragment` was resolved to.
+fn is_same_generated_content(fragment: &Fragment, next: &Fragment) -> bool {
+    fragment.generated_content.is_some() && next.generated_content.is_some() &&
+        fragment.node == next.node &&
+        &*fragment.style as *const ComputedValues == &*next.style as *const ComputedValues
+}
+
+/// Formats the given counter value in the given style, without a suffix. Values that the style
+/// can't represent are formatted as decimal numbers instead.
+pub fn format_counter(value: i32, list_style_type: list_style_type::T) -> String {
+    match list_style_type {
+        list_style_type::none => String::new(),
+        list_style_type::disc => "•".to_string(),
+        list_style_type::circle => "◦".to_string(),
+        list_style_type::square => "▪".to_string(),
+        list_style_type::decimal => value.to_string(),
+        list_style_type::decimal_leading_zero => {
+            if value >= 0 && value < 10 {
+                format!("0{}", value)
+            } else if value < 0 && value > -10 {
+                format!("-0{}", -value)
+            } else {
+                value.to_string()
+            }
+        }
+        list_style_type::lower_roman => format_roman(value, false),
+        list_style_type::upper_roman => format_roman(value, true),
+        list_style_type::lower_greek => format_alphabetic(value, LOWER_GREEK),
+        list_style_type::lower_latin | list_style_type::lower_alpha => {
+            format_alphabetic(value, LOWER_LATIN)
+        }
+        list_style_type::upper_latin | list_style_type::upper_alpha => {
+            format_alphabetic(value, UPPER_LATIN)
+        }
+    }
+}
+
+/// Formats a value from 1 to 3999 as a roman numeral.
+fn format_roman(value: i32, uppercase: bool) -> String {
+    static NUMERALS: [(i32, &'static str), ..13] = [
+        (1000, "m"), (900, "cm"), (500, "d"), (400, "cd"), (100, "c"), (90, "xc"), (50, "l"),
+        (40, "xl"), (10, "x"), (9, "ix"), (5, "v"), (4, "iv"), (1, "i"),
+    ];
+    if value < 1 || value > 3999 {
+        return value.to_string()
+    }
+    let mut remaining = value;
+    let mut result = String::new();
+    for &(numeral_value, numeral) in NUMERALS.iter() {
+        while remaining >= numeral_value {
+            result.push_str(numeral);
+            remaining = remaining - numeral_value;
+        }
+    }
+    if uppercase {
+        result.as_slice().to_ascii_upper()
+    } else {
+        result
+    }
+}
+
+/// Formats a positive value in the alphabetic numbering system with the given digits, in which
+/// the value after "z" is "aa".
+fn format_alphabetic(value: i32, alphabet: &str) -> String {
+    if value < 1 {
+        return value.to_string()
+    }
+    let alphabet: Vec<char> = alphabet.chars().collect();
+    let mut remaining = value as uint;
+    let mut digits = Vec::new();
+    while remaining > 0 {
+        remaining = remaining - 1;
+        digits.push(alphabet[remaining % alphabet.len()]);
+        remaining = remaining / alphabet.len();
+    }
+    digits.into_iter().rev().collect()
+}
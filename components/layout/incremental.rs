@@ -151,7 +151,8 @@ pub fn compute_damage(old: &Option<Arc<ComputedValues>>, new: &ComputedValues) -
     add_if_not_equal!(old, new, damage,
                       [ REPAINT, REFLOW_OUT_OF_FLOW ], [
         get_positionoffsets.top, get_positionoffsets.left,
-        get_positionoffsets.right, get_positionoffsets.bottom
+        get_positionoffsets.right, get_positionoffsets.bottom,
+        get_effects.transform, get_effects.transform_origin
     ]);
 
     add_if_not_equal!(old, new, damage,
@@ -164,12 +165,18 @@ pub fn compute_damage(old: &Option<Arc<ComputedValues>>, new: &ComputedValues) -
         get_padding.padding_bottom, get_padding.padding_left,
         get_box.width, get_box.height,
         get_font.font_family, get_font.font_size, get_font.font_style, get_font.font_weight,
-        get_inheritedtext.text_align, get_text.text_decoration, get_inheritedbox.line_height
+        get_inheritedtext.text_align, get_text.text_decoration, get_inheritedbox.line_height,
+        get_flex.flex_direction, get_flex.flex_wrap, get_flex.flex_grow, get_flex.flex_shrink,
+        get_flex.flex_basis, get_flex.justify_content, get_flex.align_items,
+        get_flex.align_self, get_flex.align_content, get_flex.order
     ]);
 
     add_if_not_equal!(old, new, damage,
                       [ REPAINT, BUBBLE_ISIZES, REFLOW_OUT_OF_FLOW, REFLOW, RECONSTRUCT_FLOW ],
-                      [ get_box.float, get_box.display, get_box.position ]);
+                      [ get_box.float, get_box.display, get_box.position, get_box.content,
+                        get_box.counter_reset, get_box.counter_increment,
+                        get_list.list_style_type, get_list.list_style_position,
+                        get_list.list_style_image ]);
 
     // FIXME: test somehow that we checked every CSS property
 
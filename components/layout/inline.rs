@@ -9,7 +9,7 @@ use context::LayoutContext;
 use display_list_builder::{ContentLevel, DisplayListResult, FragmentDisplayListBuilding};
 use floats::{FloatLeft, Floats, PlacementInfo};
 use flow::{BaseFlow, FlowClass, Flow, ForceNonfloated, InlineFlowClass, MutableFlowUtils};
-use flow::{IS_ABSOLUTELY_POSITIONED};
+use flow::{IS_ABSOLUTELY_POSITIONED, PARENT_IS_RTL};
 use flow;
 use fragment::{Fragment, InlineAbsoluteHypotheticalFragment, InlineBlockFragment};
 use fragment::{FragmentBoundsIterator, ScannedTextFragment, ScannedTextFragmentInfo};
@@ -25,11 +25,14 @@ use gfx::display_list::DisplayList;
 use gfx::font::FontMetrics;
 use gfx::font_context::FontContext;
 use gfx::text::glyph::CharIndex;
+use gfx::text::text_run::TextRun;
+use servo_util::bidi;
 use servo_util::geometry::Au;
 use servo_util::logical_geometry::{LogicalRect, LogicalSize, WritingMode};
 use servo_util::opts;
 use servo_util::range::{IntRangeIndex, Range, RangeIndex};
 use servo_util::arc_ptr_eq;
+use serialize::{Encodable, Encoder};
 use std::cmp::max;
 use std::fmt;
 use std::mem;
@@ -242,6 +245,22 @@ impl LineBreaker {
                     fragment
                 };
 
+                // Fragments directly inside the block container are measured in the
+                // `first-line` style while the first line is filled, and in their own style after
+                // that.
+                let mut cur_fragment = cur_fragment;
+                match flow.first_line_style {
+                    Some(ref mut first_line_style) => {
+                        if self.lines.is_empty() {
+                            first_line_style.apply(&mut cur_fragment,
+                                                   layout_context.font_context())
+                        } else {
+                            first_line_style.restore(&mut cur_fragment)
+                        }
+                    }
+                    None => {}
+                }
+
                 let fragment_was_appended = match cur_fragment.white_space() {
                     white_space::normal => {
                         self.try_append_to_line(cur_fragment, flow, layout_context, WrapNormally)
@@ -701,6 +720,99 @@ pub struct InlineFlow {
     /// The minimum depth below the baseline for each line, as specified by the line block-size and
     /// font style.
     pub minimum_depth_below_baseline: Au,
+
+    /// The style of the `first-line` pseudo-element of the block container, if this flow holds
+    /// its first formatted line.
+    pub first_line_style: Option<FirstLineStyle>,
+}
+
+/// The styles needed to apply the `first-line` pseudo-element to the first line of a flow.
+///
+/// Only the fragments of text directly inside the block container take the `first-line` style;
+/// fragments of descendant inline elements keep their own. The style is applied while the first
+/// line is filled, and the text is shaped again in it, so that properties like `font-size` affect
+/// where the line breaks.
+pub struct FirstLineStyle {
+    /// The computed style of the `first-line` pseudo-element.
+    pub style: Arc<ComputedValues>,
+
+    /// The style of the block container, shared by the fragments directly inside it.
+    pub block_style: Arc<ComputedValues>,
+
+    /// The text runs shaped in the `first-line` style during the last layout, each with the run
+    /// its text came from and the index in that run at which the text starts.
+    pub reshaped_runs: Vec<(Arc<Box<TextRun>>, Arc<Box<TextRun>>, CharIndex)>,
+}
+
+impl FirstLineStyle {
+    pub fn new(style: Arc<ComputedValues>, block_style: Arc<ComputedValues>) -> FirstLineStyle {
+        FirstLineStyle {
+            style: style,
+            block_style: block_style,
+            reshaped_runs: vec!(),
+        }
+    }
+
+    /// Gives a fragment directly inside the block container the `first-line` style. Text is
+    /// shaped again in that style.
+    fn apply(&mut self, fragment: &mut Fragment, font_context: &mut FontContext) {
+        if !arc_ptr_eq(&fragment.style, &self.block_style) {
+            return
+        }
+        let source = match fragment.specific {
+            ScannedTextFragment(ref info) => Some((info.run.clone(), info.range.begin())),
+            _ => None,
+        };
+        match source {
+            Some((run, offset)) => {
+                let reshaped = text::reshape_text_fragment(font_context,
+                                                           &*fragment,
+                                                           self.style.clone());
+                *fragment = reshaped;
+                match fragment.specific {
+                    ScannedTextFragment(ref info) => {
+                        self.reshaped_runs.push((info.run.clone(), run, offset))
+                    }
+                    _ => {}
+                }
+            }
+            None => fragment.style = self.style.clone(),
+        }
+    }
+
+    /// Gives a fragment that `apply` changed the style of the block container again, and the text
+    /// run its text came from.
+    fn restore(&self, fragment: &mut Fragment) {
+        if !arc_ptr_eq(&fragment.style, &self.style) {
+            return
+        }
+        fragment.style = self.block_style.clone();
+        let source = match fragment.specific {
+            ScannedTextFragment(ref info) => {
+                self.reshaped_runs.iter().find(|&&(ref reshaped_run, _, _)| {
+                    arc_ptr_eq(reshaped_run, &info.run)
+                }).map(|&(_, ref run, offset)| {
+                    (run.clone(), Range::new(info.range.begin() + offset, info.range.length()))
+                })
+            }
+            _ => None,
+        };
+        match source {
+            Some((run, range)) => text::set_text_run(fragment, run, range),
+            None => {}
+        }
+    }
+}
+
+impl<E, S: Encoder<E>> Encodable<S, E> for FirstLineStyle {
+    fn encode(&self, e: &mut S) -> Result<(), E> {
+        e.emit_struct("first_line_style", 0, |e| {
+            try!(e.emit_struct_field("font_size",
+                                     0,
+                                     |e| self.style.get_font().font_size.encode(e)))
+            e.emit_struct_field("reshaped_runs", 1, |e| self.reshaped_runs.len().encode(e))
+        })
+    }
 }
 
 impl InlineFlow {
@@ -711,6 +823,7 @@ impl InlineFlow {
             lines: Vec::new(),
             minimum_block_size_above_baseline: Au(0),
             minimum_depth_below_baseline: Au(0),
+            first_line_style: None,
         }
     }
 
@@ -790,7 +903,8 @@ impl InlineFlow {
     /// Sets fragment positions in the inline direction based on alignment for one line.
     fn set_inline_fragment_positions(fragments: &mut InlineFragments,
                                      line: &Line,
-                                     line_align: text_align::T) {
+                                     line_align: text_align::T,
+                                     writing_mode: WritingMode) {
         // Figure out how much inline-size we have.
         let slack_inline_size = max(Au(0), line.green_zone.inline - line.bounds.size.inline);
 
@@ -801,13 +915,27 @@ impl InlineFlow {
             // coordinates.
             //
             // TODO(burg, issue #213): Implement `text-align: justify`.
-            text_align::left | text_align::justify => Au(0),
+            //
+            // The containing block resolves `start` and `end` against its direction, so they
+            // are only seen here if it hasn't set the alignment; treat them as left-to-right.
+            text_align::left | text_align::start | text_align::justify => Au(0),
             text_align::center => slack_inline_size.scale_by(0.5),
-            text_align::right => slack_inline_size,
+            text_align::right | text_align::end => slack_inline_size,
         };
 
-        for fragment_index in range(line.range.begin(), line.range.end()) {
-            let fragment = fragments.get_mut(fragment_index.to_uint());
+        // Lay the fragments out in visual order, per UAX #9 rule L2. That order runs from left to
+        // right, so reverse it where the inline axis of the containing block runs the other way.
+        let line_start = line.range.begin().to_uint();
+        let levels: Vec<u8> = range(line.range.begin(), line.range.end()).map(|fragment_index| {
+            fragments.get(fragment_index.to_uint()).bidi_level
+        }).collect();
+        let mut visual_order = bidi::visual_order(levels.as_slice());
+        if !writing_mode.is_bidi_ltr() {
+            visual_order.reverse()
+        }
+
+        for &index in visual_order.iter() {
+            let fragment = fragments.get_mut(line_start + index);
             let size = fragment.border_box.size;
             fragment.border_box = LogicalRect::new(fragment.style.writing_mode,
                                                    offset,
@@ -960,11 +1088,14 @@ impl Flow for InlineFlow {
             }
         }
 
-        // If there are any inline-block kids, propagate explicit block sizes down to them.
+        // If there are any inline-block kids, propagate explicit block sizes and the direction
+        // of the containing block down to them.
         let block_container_explicit_block_size = self.base.block_container_explicit_block_size;
+        let parent_is_rtl = self.base.flags.contains(PARENT_IS_RTL);
         for kid in self.base.child_iter() {
-            flow::mut_base(kid).block_container_explicit_block_size =
-                block_container_explicit_block_size;
+            let kid_base = flow::mut_base(kid);
+            kid_base.block_container_explicit_block_size = block_container_explicit_block_size;
+            kid_base.flags.set(PARENT_IS_RTL, parent_is_rtl);
         }
     }
 
@@ -997,6 +1128,17 @@ impl Flow for InlineFlow {
 
         debug!("lines: {}", self.lines);
 
+        // Undo the `first-line` style from the last layout, since the first line may have changed.
+        match self.first_line_style {
+            Some(ref mut first_line_style) => {
+                for fragment in self.fragments.fragments.iter_mut() {
+                    first_line_style.restore(fragment)
+                }
+                first_line_style.reshaped_runs.clear()
+            }
+            None => {}
+        }
+
         self.fragments.merge_broken_lines();
 
         self.lines = Vec::new();
@@ -1012,7 +1154,10 @@ impl Flow for InlineFlow {
         let mut line_distance_from_flow_block_start = Au(0);
         for line in self.lines.iter_mut() {
             // Lay out fragments in the inline direction.
-            InlineFlow::set_inline_fragment_positions(&mut self.fragments, line, text_align);
+            InlineFlow::set_inline_fragment_positions(&mut self.fragments,
+                                                      line,
+                                                      text_align,
+                                                      self.base.writing_mode);
 
             // Set the block-start position of the current line.
             // `line_height_offset` is updated at the end of the previous loop.
@@ -5,6 +5,7 @@
 //! The layout task. Performs layout on the DOM, builds display lists and sends them to be
 //! rendered.
 
+use animation::AnimationManager;
 use css::node_style::StyledNode;
 use construct::FlowConstructionResult;
 use context::SharedLayoutContext;
@@ -19,15 +20,15 @@ use sequential;
 use util::{LayoutDataAccess, LayoutDataWrapper, OpaqueNodeMethods, ToGfxColor};
 use wrapper::{LayoutNode, TLayoutNode, ThreadSafeLayoutNode};
 
-use encoding::EncodingRef;
-use encoding::all::UTF_8;
+use geom::matrix2d::Matrix2D;
 use geom::point::Point2D;
 use geom::rect::Rect;
 use geom::size::Size2D;
 use geom::scale_factor::ScaleFactor;
 use gfx::color;
 use gfx::display_list::{DisplayList, OpaqueNode, StackingContext};
-use gfx::font_cache_task::FontCacheTask;
+use gfx::font_cache_task::{FontCacheTask, WebFontEvent, WebFontLoadFinishedEvent};
+use gfx::font_cache_task::WebFontResponder;
 use gfx::render_task::{mod, RenderInitMsg, RenderChan, RenderLayer};
 use layout_traits;
 use layout_traits::{LayoutControlMsg, LayoutTaskFactory};
@@ -35,37 +36,46 @@ use log;
 use script::dom::bindings::js::JS;
 use script::dom::node::{ElementNodeTypeId, LayoutDataRef, Node};
 use script::dom::element::{HTMLBodyElementTypeId, HTMLHtmlElementTypeId};
-use script::layout_interface::{AddStylesheetMsg, ContentBoxResponse, ContentBoxesResponse};
+use script::layout_interface::{ContentBoxResponse, ContentBoxesResponse};
 use script::layout_interface::{ContentBoxesQuery, ContentBoxQuery, ExitNowMsg, GetRPCMsg};
-use script::layout_interface::{HitTestResponse, LayoutChan, LayoutRPC, LoadStylesheetMsg};
+use script::layout_interface::{HitTestResponse, LayoutChan, LayoutRPC, SetStylesheetsMsg};
 use script::layout_interface::{MouseOverResponse, Msg, NoQuery, PrepareToExitMsg};
 use script::layout_interface::{ReapLayoutDataMsg, Reflow, ReflowForDisplay, ReflowMsg};
-use script::layout_interface::{ScriptLayoutChan, TrustedNodeAddress};
+use script::layout_interface::{ScriptLayoutChan, TrustedNodeAddress, device_for_window_size};
 use script_traits::{SendEventMsg, ReflowEvent, ReflowCompleteMsg, OpaqueScriptLayoutChannel};
+use script_traits::DispatchAnimationEventsMsg;
 use script_traits::{ScriptControlChan, UntrustedNodeAddress};
 use servo_msg::compositor_msg::Scrollable;
 use servo_msg::constellation_msg::{ConstellationChan, PipelineId, Failure, FailureMsg};
 use servo_net::image_cache_task::{ImageCacheTask, ImageResponseMsg};
 use servo_net::local_image_cache::{ImageResponder, LocalImageCache};
-use servo_net::resource_task::{ResourceTask, load_bytes_iter};
+use servo_net::resource_task::ResourceTask;
 use servo_util::geometry::Au;
 use servo_util::logical_geometry::LogicalPoint;
 use servo_util::opts;
 use servo_util::smallvec::{SmallVec, SmallVec1, VecLike};
-use servo_util::task::spawn_named_with_send_on_failure;
+use servo_util::task::{spawn_named, spawn_named_with_send_on_failure};
 use servo_util::task_state;
 use servo_util::time::{TimeProfilerChan, profile};
 use servo_util::time;
 use servo_util::workqueue::WorkQueue;
 use std::cell::Cell;
+use std::collections::HashSet;
 use std::comm::{channel, Sender, Receiver, Select};
+use std::io::timer;
 use std::mem;
 use std::ptr;
-use style::{AuthorOrigin, Stylesheet, Stylist, TNode, iter_font_face_rules};
+use std::sync::atomic::{AtomicBool, AtomicUint, SeqCst};
+use std::time::duration::Duration;
+use style::{FontFaceRule, Stylesheet, Stylist, TNode, iter_font_face_rules};
 use style::{Device, Screen};
 use sync::{Arc, Mutex, MutexGuard};
 use url::Url;
 
+/// How long to wait between two frames of the running transitions and animations, in
+/// milliseconds.
+static ANIMATION_TICK_INTERVAL: i64 = 16;
+
 /// Mutable data belonging to the LayoutTask.
 ///
 /// This needs to be protected by a mutex so we can do fast RPCs.
@@ -96,6 +106,26 @@ pub struct LayoutTaskData {
 
     /// A queued response for the content boxes of a node.
     pub content_boxes_response: Vec<Rect<Au>>,
+
+    /// The running transitions and animations.
+    pub animation_manager: Arc<Mutex<AnimationManager>>,
+
+    /// The time at which transitions and animations are computed, in seconds.
+    pub animation_time: f64,
+
+    /// The number of web fonts of the document that are loading.
+    pub pending_web_fonts: Arc<AtomicUint>,
+
+    /// Increased by the web font responders of the document whenever one of its web fonts loads
+    /// or its block period ends.
+    pub web_font_generation: Arc<AtomicUint>,
+
+    /// The generation of the web fonts of the document as of the last reflow.
+    pub last_web_font_generation: uint,
+
+    /// The @font-face rules that the font cache has been told about, so that the rules of a
+    /// stylesheet that script has copied to change it are not added again.
+    pub web_font_rules: Vec<FontFaceRule>,
 }
 
 /// Information needed by the layout task.
@@ -136,6 +166,13 @@ pub struct LayoutTask {
     /// Is this the first reflow in this LayoutTask?
     pub first_reflow: Cell<bool>,
 
+    /// Whether script was asked to reflow again to move the transitions and animations forward.
+    pub animation_tick_pending: Arc<AtomicBool>,
+
+    /// The channel on which the animation timer is asked to have script reflow again after a
+    /// number of milliseconds.
+    pub animation_timer: Sender<i64>,
+
     /// A mutex to allow for fast, read-only RPC of layout's internal data
     /// structures, while still letting the LayoutTask modify them.
     ///
@@ -164,6 +201,29 @@ impl ImageResponder<UntrustedNodeAddress> for LayoutImageResponder {
     }
 }
 
+/// Asks script for a reflow whenever a web font of the document loads or its block period ends,
+/// as either changes the fonts that text is laid out with.
+struct LayoutWebFontResponder {
+    id: PipelineId,
+    script_chan: ScriptControlChan,
+    pending_web_fonts: Arc<AtomicUint>,
+    web_font_generation: Arc<AtomicUint>,
+}
+
+impl WebFontResponder for LayoutWebFontResponder {
+    fn respond(&self, event: WebFontEvent) {
+        self.web_font_generation.fetch_add(1, SeqCst);
+        match event {
+            WebFontLoadFinishedEvent => {
+                self.pending_web_fonts.fetch_sub(1, SeqCst);
+            }
+            _ => {}
+        }
+        let ScriptControlChan(ref chan) = self.script_chan;
+        drop(chan.send_opt(SendEventMsg(self.id.clone(), ReflowEvent(SmallVec1::new()))))
+    }
+}
+
 impl LayoutTaskFactory for LayoutTask {
     /// Spawns a new layout task.
     fn create(_phantom: Option<&mut LayoutTask>,
@@ -249,14 +309,20 @@ impl LayoutTask {
         let local_image_cache =
             Arc::new(Mutex::new(LocalImageCache::new(image_cache_task.clone())));
         let screen_size = Size2D(Au(0), Au(0));
-        let device = Device::new(Screen, opts::get().initial_window_size.as_f32() * ScaleFactor(1.0));
+        let initial_viewport = opts::get().initial_window_size.as_f32() * ScaleFactor(1.0);
+        let device_pixel_ratio = opts::get().device_pixels_per_px.map_or(1.0, |ratio| ratio.get());
+        let device = Device::new(Screen, initial_viewport, initial_viewport,
+                                 ScaleFactor(device_pixel_ratio));
         let parallel_traversal = if opts::get().layout_threads != 1 {
             Some(WorkQueue::new("LayoutWorker", task_state::LAYOUT,
                                 opts::get().layout_threads, ptr::null()))
         } else {
             None
         };
-
+        let animation_tick_pending = Arc::new(AtomicBool::new(false));
+        let animation_timer = LayoutTask::start_animation_timer(id,
+                                                                script_chan.clone(),
+                                                                animation_tick_pending.clone());
         LayoutTask {
             id: id,
             port: port,
@@ -270,6 +336,8 @@ impl LayoutTask {
             image_cache_task: image_cache_task.clone(),
             font_cache_task: font_cache_task,
             first_reflow: Cell::new(true),
+            animation_tick_pending: animation_tick_pending,
+            animation_timer: animation_timer,
             rw_data: Arc::new(Mutex::new(
                 LayoutTaskData {
                     local_image_cache: local_image_cache,
@@ -281,6 +349,12 @@ impl LayoutTask {
                     generation: 0,
                     content_box_response: Rect::zero(),
                     content_boxes_response: Vec::new(),
+                    animation_manager: Arc::new(Mutex::new(AnimationManager::new())),
+                    animation_time: 0.0,
+                    pending_web_fonts: Arc::new(AtomicUint::new(0)),
+                    web_font_generation: Arc::new(AtomicUint::new(0)),
+                    last_web_font_generation: 0,
+                    web_font_rules: vec!(),
               })),
         }
     }
@@ -310,6 +384,9 @@ impl LayoutTask {
             reflow_root: OpaqueNodeMethods::from_layout_node(reflow_root),
             dirty: Rect::zero(),
             generation: rw_data.generation,
+            animation_manager: rw_data.animation_manager.clone(),
+            animation_time: rw_data.animation_time,
+            web_font_generation: rw_data.last_web_font_generation,
         }
     }
 
@@ -388,8 +465,7 @@ impl LayoutTask {
                                                                                  LayoutTaskData>>)
                                  -> bool {
         match request {
-            AddStylesheetMsg(sheet) => self.handle_add_stylesheet(sheet, possibly_locked_rw_data),
-            LoadStylesheetMsg(url) => self.handle_load_stylesheet(url, possibly_locked_rw_data),
+            SetStylesheetsMsg(sheets) => self.handle_set_stylesheets(sheets, possibly_locked_rw_data),
             GetRPCMsg(response_chan) => {
                 response_chan.send(box LayoutRPCImpl(self.rw_data.clone()) as
                                    Box<LayoutRPC + Send>);
@@ -465,36 +541,34 @@ impl LayoutTask {
         response_port.recv()
     }
 
-    fn handle_load_stylesheet<'a>(&'a self,
-                                  url: Url,
+    fn handle_set_stylesheets<'a>(&'a self,
+                                  sheets: Vec<Arc<Stylesheet>>,
                                   possibly_locked_rw_data:
                                     &mut Option<MutexGuard<'a, LayoutTaskData>>) {
-        // TODO: Get the actual value. http://dev.w3.org/csswg/css-syntax/#environment-encoding
-        let environment_encoding = UTF_8 as EncodingRef;
-
-        let (metadata, iter) = load_bytes_iter(&self.resource_task, url);
-        let protocol_encoding_label = metadata.charset.as_ref().map(|s| s.as_slice());
-        let final_url = metadata.final_url;
-
-        let sheet = Stylesheet::from_bytes_iter(iter,
-                                                final_url,
-                                                protocol_encoding_label,
-                                                Some(environment_encoding),
-                                                AuthorOrigin);
-        self.handle_add_stylesheet(sheet, possibly_locked_rw_data);
-    }
-
-    fn handle_add_stylesheet<'a>(&'a self,
-                                 sheet: Stylesheet,
-                                 possibly_locked_rw_data:
-                                    &mut Option<MutexGuard<'a, LayoutTaskData>>) {
-        // Find all font-face rules and notify the font cache of them.
+        // Find the font-face rules that are new, and notify the font cache of them. Script
+        // copies a stylesheet to change it, so the stylesheets themselves can't tell.
         // GWTODO: Need to handle unloading web fonts (when we handle unloading stylesheets!)
         let mut rw_data = self.lock_rw_data(possibly_locked_rw_data);
-        iter_font_face_rules(&sheet, &rw_data.stylist.device, |family, src| {
-            self.font_cache_task.add_web_font(family.to_string(), (*src).clone());
-        });
-        rw_data.stylist.add_stylesheet(sheet);
+        let mut new_rules = vec!();
+        for sheet in sheets.iter() {
+            iter_font_face_rules(&**sheet, &rw_data.stylist.device, |rule| {
+                if !rw_data.web_font_rules.contains(rule) && !new_rules.contains(rule) {
+                    new_rules.push(rule.clone());
+                }
+            });
+        }
+        for rule in new_rules.into_iter() {
+            rw_data.pending_web_fonts.fetch_add(1, SeqCst);
+            let responder = box LayoutWebFontResponder {
+                id: self.id.clone(),
+                script_chan: self.script_chan.clone(),
+                pending_web_fonts: rw_data.pending_web_fonts.clone(),
+                web_font_generation: rw_data.web_font_generation.clone(),
+            };
+            self.font_cache_task.add_web_font(rule.clone(), responder);
+            rw_data.web_font_rules.push(rule);
+        }
+        rw_data.stylist.set_author_stylesheets(sheets);
         LayoutTask::return_rw_data(possibly_locked_rw_data, rw_data);
     }
 
@@ -680,6 +754,7 @@ impl LayoutTask {
                                                                  origin,
                                                                  0,
                                                                  1.0,
+                                                                 Matrix2D::identity(),
                                                                  Some(render_layer)));
 
             rw_data.stacking_context = Some(stacking_context.clone());
@@ -718,15 +793,23 @@ impl LayoutTask {
             local_image_cache.next_round(self.make_on_image_available_cb());
         }
 
-        // TODO: Calculate the "actual viewport":
-        // http://www.w3.org/TR/css-device-adapt/#actual-viewport
-        let viewport_size = data.window_size.initial_viewport;
+        let device = device_for_window_size(&data.window_size);
+        let viewport_size = device.viewport_size;
 
         let old_screen_size = rw_data.screen_size;
         let current_screen_size = Size2D(Au::from_frac32_px(viewport_size.width.get()),
                                          Au::from_frac32_px(viewport_size.height.get()));
         rw_data.screen_size = current_screen_size;
 
+        // Style the animated nodes again if time moved on.
+        if data.animation_time != rw_data.animation_time {
+            rw_data.animation_time = data.animation_time;
+            let mut animation_manager = rw_data.animation_manager.lock();
+            unsafe {
+                LayoutTask::dirty_animated_nodes(node, &mut *animation_manager);
+            }
+        }
+
         // Create a layout context for use throughout the following passes.
         let mut shared_layout_ctx = self.build_shared_layout_context(rw_data.deref(),
                                                                      node,
@@ -735,12 +818,21 @@ impl LayoutTask {
         // Handle conditions where the entire flow tree is invalid.
         let screen_size_changed = current_screen_size != old_screen_size;
 
-        if screen_size_changed {
-            let device = Device::new(Screen, data.window_size.initial_viewport);
+        let device_pixel_ratio_changed =
+            device.device_pixel_ratio.get() != rw_data.stylist.device.device_pixel_ratio.get();
+
+        if screen_size_changed || device_pixel_ratio_changed {
             rw_data.stylist.set_device(device);
         }
 
-        let needs_dirtying = rw_data.stylist.update();
+        // Text has to be laid out again with the fonts that web fonts that have loaded, or whose
+        // block period has ended, change to.
+        let web_font_generation = rw_data.web_font_generation.load(SeqCst);
+        let web_fonts_changed = web_font_generation != rw_data.last_web_font_generation;
+        rw_data.last_web_font_generation = web_font_generation;
+        shared_layout_ctx.web_font_generation = web_font_generation;
+
+        let needs_dirtying = rw_data.stylist.update() || web_fonts_changed;
 
         // If the entire flow tree is invalid, then it will be reflowed anyhow.
         let needs_reflow = screen_size_changed && !needs_dirtying;
@@ -773,7 +865,10 @@ impl LayoutTask {
                 }
             }
 
-            self.get_layout_root((*node).clone())
+            // Counters depend on the whole flow tree, so they can only be resolved now.
+            let mut layout_root = self.get_layout_root((*node).clone());
+            sequential::resolve_generated_content(&mut layout_root, &shared_layout_ctx);
+            layout_root
         });
 
         profile(time::LayoutRestyleDamagePropagation,
@@ -846,6 +941,13 @@ impl LayoutTask {
 
         rw_data.generation += 1;
 
+        let (animation_events, animations_running, next_image_frame_time) = {
+            let mut animation_manager = rw_data.animation_manager.lock();
+            (animation_manager.take_events(),
+             animation_manager.is_running(),
+             animation_manager.take_next_image_frame_time())
+        };
+
         // Tell script that we're done.
         //
         // FIXME(pcwalton): This should probably be *one* channel, but we can't fix this without
@@ -853,6 +955,85 @@ impl LayoutTask {
         data.script_join_chan.send(());
         let ScriptControlChan(ref chan) = data.script_chan;
         chan.send(ReflowCompleteMsg(self.id, data.id));
+        if !animation_events.is_empty() {
+            chan.send(DispatchAnimationEventsMsg(self.id, animation_events));
+        }
+
+        // With the virtual animation clock, script decides when time moves on.
+        if !opts::get().virtual_animation_clock {
+            if animations_running {
+                self.schedule_animation_tick(ANIMATION_TICK_INTERVAL);
+            } else {
+                match next_image_frame_time {
+                    Some(time) => {
+                        let delay = (time - rw_data.animation_time) * 1000.0;
+                        self.schedule_animation_tick(delay.max(0.0) as i64);
+                    }
+                    None => {}
+                }
+            }
+        }
+    }
+
+    /// Starts the task that, for each delay in milliseconds it is sent, asks script to reflow
+    /// again once the delay has passed. It stops when the layout task goes away.
+    fn start_animation_timer(id: PipelineId,
+                             script_chan: ScriptControlChan,
+                             animation_tick_pending: Arc<AtomicBool>)
+                             -> Sender<i64> {
+        let (timer_chan, timer_port) = channel();
+        let ScriptControlChan(chan) = script_chan;
+        spawn_named("LayoutAnimationTimer", proc() {
+            for delay in timer_port.iter() {
+                timer::sleep(Duration::milliseconds(delay));
+                animation_tick_pending.store(false, SeqCst);
+                if chan.send_opt(SendEventMsg(id, ReflowEvent(SmallVec1::new()))).is_err() {
+                    break
+                }
+            }
+        });
+        timer_chan
+    }
+
+    /// Asks script to reflow again in `delay` milliseconds, unless it was already asked, to move
+    /// the running transitions, animations and animated images forward.
+    fn schedule_animation_tick(&self, delay: i64) {
+        if self.animation_tick_pending.swap(true, SeqCst) {
+            return
+        }
+        self.animation_timer.send(delay);
+    }
+
+    /// Dirties the nodes with running transitions and animations, and their descendants which
+    /// inherit from them, so that they are styled again, and marks their ancestors so that the
+    /// style traversal reaches them. Forgets the nodes that left the document.
+    unsafe fn dirty_animated_nodes(node: &mut LayoutNode,
+                                   animation_manager: &mut AnimationManager) {
+        if !animation_manager.is_running() {
+            return
+        }
+        let mut live_nodes = HashSet::new();
+        for node in node.traverse_preorder() {
+            let opaque_node: OpaqueNode = OpaqueNodeMethods::from_layout_node(&node);
+            if animation_manager.is_node_running(&opaque_node) && !node.is_dirty() {
+                for descendant in node.traverse_preorder() {
+                    descendant.set_dirty(true);
+                    descendant.set_dirty_descendants(true);
+                }
+                let mut ancestor = node.parent_node();
+                loop {
+                    match ancestor {
+                        Some(parent) => {
+                            parent.set_dirty_descendants(true);
+                            ancestor = parent.parent_node();
+                        }
+                        None => break,
+                    }
+                }
+            }
+            live_nodes.insert(opaque_node);
+        }
+        animation_manager.retain_nodes(&live_nodes);
     }
 
     unsafe fn dirty_all_nodes(node: &mut LayoutNode) {
@@ -964,6 +1145,12 @@ impl LayoutRPC for LayoutRPCImpl {
             Ok(MouseOverResponse(mouse_over_list))
         }
     }
+
+    fn web_fonts_loading(&self) -> bool {
+        let &LayoutRPCImpl(ref rw_data) = self;
+        let rw_data = rw_data.lock();
+        rw_data.pending_web_fonts.load(SeqCst) != 0
+    }
 }
 
 struct UnioningFragmentBoundsIterator {
@@ -14,6 +14,7 @@
 #[phase(plugin, link)]
 extern crate log;
 
+extern crate canvas;
 extern crate geom;
 extern crate gfx;
 extern crate layout_traits;
@@ -41,17 +42,21 @@ extern crate url;
 // Listed first because of macro definitions
 pub mod layout_debug;
 
+pub mod animation;
 pub mod block;
 pub mod construct;
 pub mod context;
 pub mod display_list_builder;
+pub mod flex;
 pub mod floats;
 pub mod flow;
 pub mod flow_list;
 pub mod flow_ref;
 pub mod fragment;
+pub mod generated_content;
 pub mod layout_task;
 pub mod inline;
+pub mod list_item;
 pub mod model;
 pub mod parallel;
 pub mod sequential;
@@ -0,0 +1,197 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Layout for elements with `display: list-item` and an outside marker.
+//!
+//! A list item is laid out as a block. Its marker, if it has one, is a single fragment that sits
+//! just before the inline-start edge of the list item's border box, aligned with the top of its
+//! content box. Markers that are inside the list item are ordinary inline fragments instead and
+//! do not need a flow of their own; see `FlowConstructor::build_flow_for_list_item`.
+
+#![deny(unsafe_blocks)]
+
+use block::BlockFlow;
+use construct::FlowConstructor;
+use context::LayoutContext;
+use display_list_builder::ListItemFlowDisplayListBuilding;
+use floats::FloatKind;
+use flow::{Flow, FlowClass, ListItemFlowClass};
+use fragment::{Fragment, FragmentBoundsIterator};
+use wrapper::ThreadSafeLayoutNode;
+
+use servo_msg::compositor_msg::LayerId;
+use servo_util::geometry::Au;
+use servo_util::logical_geometry::LogicalRect;
+use servo_util::opts;
+use std::fmt;
+use style::ComputedValues;
+use style::computed_values::position;
+use sync::Arc;
+
+/// A block with `display: list-item` whose marker is outside of it.
+#[deriving(Encodable)]
+pub struct ListItemFlow {
+    /// Data common to all block flows.
+    pub block_flow: BlockFlow,
+
+    /// The marker, if this list item has one.
+    pub marker: Option<Fragment>,
+
+    /// The text of the marker the last time that the generated content traversal resolved it.
+    pub marker_text: Option<String>,
+}
+
+impl ListItemFlow {
+    pub fn from_node_and_marker(constructor: &mut FlowConstructor,
+                                node: &ThreadSafeLayoutNode,
+                                marker: Option<Fragment>,
+                                float_kind: Option<FloatKind>)
+                                -> ListItemFlow {
+        ListItemFlow {
+            block_flow: match float_kind {
+                None => BlockFlow::from_node(constructor, node),
+                Some(float_kind) => BlockFlow::float_from_node(constructor, node, float_kind),
+            },
+            marker: marker,
+            marker_text: None,
+        }
+    }
+}
+
+impl Flow for ListItemFlow {
+    fn class(&self) -> FlowClass {
+        ListItemFlowClass
+    }
+
+    fn as_list_item<'a>(&'a mut self) -> &'a mut ListItemFlow {
+        self
+    }
+
+    fn as_immutable_list_item<'a>(&'a self) -> &'a ListItemFlow {
+        self
+    }
+
+    fn as_block<'a>(&'a mut self) -> &'a mut BlockFlow {
+        &mut self.block_flow
+    }
+
+    fn as_immutable_block<'a>(&'a self) -> &'a BlockFlow {
+        &self.block_flow
+    }
+
+    fn bubble_inline_sizes(&mut self) {
+        // The marker sits outside of the border box, so it doesn't contribute to the intrinsic
+        // inline-sizes of the list item.
+        self.block_flow.bubble_inline_sizes();
+    }
+
+    fn assign_inline_sizes(&mut self, layout_context: &LayoutContext) {
+        debug!("assign_inline_sizes: assigning inline_size for list_item");
+        self.block_flow.assign_inline_sizes(layout_context);
+
+        match self.marker {
+            None => {}
+            Some(ref mut marker) => {
+                // The marker is sized like an inline replaced element or a run of text; its
+                // text has already been scanned.
+                let container_inline_size = self.block_flow.base.block_container_inline_size;
+                marker.assign_replaced_inline_size_if_necessary(container_inline_size);
+                marker.border_box.start.i = self.block_flow.fragment.border_box.start.i -
+                    marker.border_box.size.inline;
+            }
+        }
+    }
+
+    fn assign_block_size<'a>(&mut self, layout_context: &'a LayoutContext<'a>) {
+        debug!("assign_block_size: assigning block_size for list_item");
+        self.block_flow.assign_block_size(layout_context);
+
+        match self.marker {
+            None => {}
+            Some(ref mut marker) => {
+                let containing_block_block_size =
+                    self.block_flow.base.block_container_explicit_block_size.unwrap_or(Au(0));
+                marker.assign_replaced_block_size_if_necessary(containing_block_block_size);
+                marker.border_box.start.b = self.block_flow.fragment.border_box.start.b +
+                    self.block_flow.fragment.border_padding.block_start;
+            }
+        }
+    }
+
+    fn compute_absolute_position(&mut self) {
+        self.block_flow.compute_absolute_position()
+    }
+
+    fn place_float_if_applicable<'a>(&mut self, layout_context: &'a LayoutContext<'a>) {
+        self.block_flow.place_float_if_applicable(layout_context)
+    }
+
+    fn assign_block_size_for_inorder_child_if_necessary<'a>(&mut self,
+                                                            layout_context: &'a LayoutContext<'a>)
+                                                            -> bool {
+        self.block_flow.assign_block_size_for_inorder_child_if_necessary(layout_context)
+    }
+
+    fn mark_as_root(&mut self) {
+        self.block_flow.mark_as_root()
+    }
+
+    fn is_store_overflow_delayed(&mut self) -> bool {
+        self.block_flow.is_store_overflow_delayed()
+    }
+
+    fn is_root(&self) -> bool {
+        self.block_flow.is_root()
+    }
+
+    fn positioning(&self) -> position::T {
+        self.block_flow.positioning()
+    }
+
+    fn is_root_of_absolute_flow_tree(&self) -> bool {
+        self.block_flow.is_root_of_absolute_flow_tree()
+    }
+
+    fn generated_containing_block_rect(&self) -> LogicalRect<Au> {
+        self.block_flow.generated_containing_block_rect()
+    }
+
+    fn layer_id(&self, fragment_index: uint) -> LayerId {
+        self.block_flow.layer_id(fragment_index)
+    }
+
+    fn is_absolute_containing_block(&self) -> bool {
+        self.block_flow.is_absolute_containing_block()
+    }
+
+    fn update_late_computed_inline_position_if_necessary(&mut self, inline_position: Au) {
+        self.block_flow.update_late_computed_inline_position_if_necessary(inline_position)
+    }
+
+    fn update_late_computed_block_position_if_necessary(&mut self, block_position: Au) {
+        self.block_flow.update_late_computed_block_position_if_necessary(block_position)
+    }
+
+    fn build_display_list(&mut self, layout_context: &LayoutContext) {
+        self.build_display_list_for_list_item(layout_context);
+
+        if opts::get().validate_display_list_geometry {
+            self.block_flow.base.validate_display_list_geometry();
+        }
+    }
+
+    fn repair_style(&mut self, new_style: &Arc<ComputedValues>) {
+        self.block_flow.repair_style(new_style)
+    }
+
+    fn iterate_through_fragment_bounds(&self, iterator: &mut FragmentBoundsIterator) {
+        self.block_flow.iterate_through_fragment_bounds(iterator);
+    }
+}
+
+impl fmt::Show for ListItemFlow {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "ListItemFlow: {}", self.block_flow)
+    }
+}
@@ -5,10 +5,14 @@
 //! Implements sequential traversals over the DOM and flow trees.
 
 use context::{LayoutContext, SharedLayoutContext};
-use flow::{Flow, MutableFlowUtils, PreorderFlowTraversal, PostorderFlowTraversal};
+use flow::{Flow, ImmutableFlowUtils, MutableFlowUtils, PreorderFlowTraversal};
+use flow::{PostorderFlowTraversal};
 use flow;
 use flow_ref::FlowRef;
-use fragment::FragmentBoundsIterator;
+use fragment::{Fragment, FragmentBoundsIterator};
+use generated_content::ResolveGeneratedContent;
+use geom::{Matrix2D, Point2D, Rect, Size2D};
+use servo_util::geometry::{mod, Au, ZERO_POINT};
 use servo_util::opts;
 use traversal::{BubbleISizes, RecalcStyleForNode, ConstructFlows};
 use traversal::{AssignBSizesAndStoreOverflow, AssignISizes};
@@ -36,6 +40,13 @@ pub fn traverse_dom_preorder(root: LayoutNode,
     doit(root, recalc_style, construct_flows);
 }
 
+/// Resolves counters and the generated content that uses them. This must be done sequentially, in
+/// document order, after the whole flow tree has been constructed.
+pub fn resolve_generated_content(root: &mut FlowRef, shared_layout_context: &SharedLayoutContext) {
+    let layout_context = LayoutContext::new(shared_layout_context);
+    ResolveGeneratedContent::new(&layout_context).traverse(root.deref_mut(), 0);
+}
+
 pub fn traverse_flow_tree_preorder(root: &mut FlowRef,
                                    shared_layout_context: &SharedLayoutContext) {
     fn doit(flow: &mut Flow,
@@ -96,13 +107,52 @@ pub fn build_display_list_for_subtree(root: &mut FlowRef,
 
 pub fn iterate_through_flow_tree_fragment_bounds(root: &mut FlowRef,
                                                  iterator: &mut FragmentBoundsIterator) {
-    fn doit(flow: &mut Flow, iterator: &mut FragmentBoundsIterator) {
-        flow.iterate_through_fragment_bounds(iterator);
+    fn doit(flow: &mut Flow, transform: &Matrix2D<f32>, iterator: &mut FragmentBoundsIterator) {
+        // A flow that establishes a stacking context reports its bounds, and positions its
+        // descendants, relative to that stacking context, to which its transform applies.
+        let mut transform_for_children = *transform;
+        if flow.is_block_like() {
+            let block = flow.as_immutable_block();
+            if block.fragment.establishes_stacking_context() {
+                let origin = block.base.stacking_relative_position;
+                let border_box = block.fragment.stacking_relative_bounds(&ZERO_POINT);
+                transform_for_children =
+                    transform.translate(origin.x.to_subpx() as f32, origin.y.to_subpx() as f32)
+                             .mul(&block.fragment.transform_matrix(&border_box));
+            }
+        }
+
+        flow.iterate_through_fragment_bounds(&mut TransformingFragmentBoundsIterator {
+            transform: &transform_for_children,
+            iterator: iterator,
+        });
 
         for kid in flow::mut_base(flow).child_iter() {
-            doit(kid, iterator);
+            doit(kid, &transform_for_children, iterator);
         }
     }
 
-    doit(root.deref_mut(), iterator);
+    doit(root.deref_mut(), &Matrix2D::identity(), iterator);
+}
+
+/// Maps the bounds reported by a flow, which are relative to its nearest ancestor stacking
+/// context, into the coordinate system of the root before handing them to `iterator`.
+struct TransformingFragmentBoundsIterator<'a> {
+    transform: &'a Matrix2D<f32>,
+    iterator: &'a mut FragmentBoundsIterator + 'a,
+}
+
+impl<'a> FragmentBoundsIterator for TransformingFragmentBoundsIterator<'a> {
+    fn process(&mut self, fragment: &Fragment, bounds: Rect<Au>) {
+        let bounds = Rect(Point2D(bounds.origin.x.to_subpx() as f32,
+                                  bounds.origin.y.to_subpx() as f32),
+                          Size2D(bounds.size.width.to_subpx() as f32,
+                                 bounds.size.height.to_subpx() as f32));
+        let bounds = geometry::transform_rect(self.transform, &bounds);
+        self.iterator.process(fragment, geometry::f32_rect_to_au_rect(bounds))
+    }
+
+    fn should_process(&mut self, fragment: &Fragment) -> bool {
+        self.iterator.should_process(fragment)
+    }
 }
@@ -6,23 +6,28 @@
 
 #![deny(unsafe_blocks)]
 
-use fragment::{Fragment, ScannedTextFragmentInfo, UnscannedTextFragment};
+use fragment::{Fragment, ScannedTextFragment, ScannedTextFragmentInfo, UnscannedTextFragment};
 use inline::InlineFragments;
 
-use gfx::font::{FontMetrics,RunMetrics};
+use gfx::font::{Font, FontMetrics, RunMetrics, ShapingOptions};
 use gfx::font_context::FontContext;
 use gfx::text::glyph::CharIndex;
+use gfx::text::script::{Common, Inherited, Script, resolve_scripts, script_for_char};
 use gfx::text::text_run::TextRun;
 use gfx::text::util::{mod, CompressWhitespaceNewline, CompressNone};
+use servo_util::arc_ptr_eq;
+use servo_util::bidi::BidiParagraph;
 use servo_util::dlist;
 use servo_util::geometry::Au;
 use servo_util::logical_geometry::{LogicalSize, WritingMode};
 use servo_util::range::Range;
-use servo_util::smallvec::{SmallVec, SmallVec1};
+use servo_util::smallvec::SmallVec;
+use std::cell::RefCell;
 use std::collections::DList;
 use std::mem;
+use std::rc::Rc;
 use style::ComputedValues;
-use style::computed_values::{line_height, text_orientation, white_space};
+use style::computed_values::{direction, line_height, text_orientation, unicode_bidi, white_space};
 use style::style_structs::Font as FontStyle;
 use sync::Arc;
 
@@ -38,13 +43,19 @@ impl TextRunScanner {
         }
     }
 
-    pub fn scan_for_runs(&mut self, font_context: &mut FontContext, mut fragments: DList<Fragment>)
+    /// Scans the given fragments, which make up a single paragraph of the block whose style is
+    /// `paragraph_style`, for text runs.
+    pub fn scan_for_runs(&mut self,
+                         font_context: &mut FontContext,
+                         mut fragments: DList<Fragment>,
+                         paragraph_style: &ComputedValues)
                          -> InlineFragments {
         debug!("TextRunScanner: scanning {:u} fragments for text runs...", fragments.len());
 
         // FIXME(pcwalton): We want to be sure not to allocate multiple times, since this is a
         // performance-critical spot, but this may overestimate and allocate too much memory.
         let mut new_fragments = Vec::with_capacity(fragments.len());
+        let mut clumps = Vec::new();
         let mut last_whitespace = true;
         while !fragments.is_empty() {
             // Create a clump.
@@ -57,10 +68,17 @@ impl TextRunScanner {
                 self.clump.append(dlist::split(&mut fragments));
             }
 
-            // Flush that clump to the list of fragments we're building up.
-            last_whitespace = self.flush_clump_to_list(font_context,
-                                                       &mut new_fragments,
-                                                       last_whitespace);
+            // Compress the text of that clump.
+            clumps.push(self.take_clump(&mut last_whitespace));
+        }
+
+        // Text in different directions is shaped and reordered separately, so resolve the
+        // embedding levels of the whole paragraph before any text runs are created.
+        let levels = resolve_bidi_levels(clumps.as_mut_slice(), paragraph_style);
+
+        // Flush the clumps to the list of fragments we're building up.
+        for clump in clumps.into_iter() {
+            flush_clump_to_list(font_context, clump, levels.as_slice(), &mut new_fragments)
         }
 
         debug!("TextRunScanner: complete.");
@@ -72,110 +90,374 @@ impl TextRunScanner {
     /// A "clump" is a range of inline flow leaves that can be merged together into a single
     /// fragment. Adjacent text with the same style can be merged, and nothing else can.
     ///
-    /// The flow keeps track of the fragments contained by all non-leaf DOM nodes. This is necessary
-    /// for correct painting order. Since we compress several leaf fragments here, the mapping must
-    /// be adjusted.
-    fn flush_clump_to_list(&mut self,
-                           font_context: &mut FontContext,
-                           out_fragments: &mut Vec<Fragment>,
-                           mut last_whitespace: bool)
-                           -> bool {
-        debug!("TextRunScanner: flushing {} fragments in range", self.clump.len());
-
-        debug_assert!(!self.clump.is_empty());
-        match self.clump.front().unwrap().specific {
-            UnscannedTextFragment(_) => {}
+    /// This takes the fragments of the current clump and, if they are text, concatenates all of
+    /// their transformed strings together, saving the new character indices.
+    fn take_clump(&mut self, last_whitespace: &mut bool) -> Clump {
+        let fragments: Vec<Fragment> =
+            mem::replace(&mut self.clump, DList::new()).into_iter().collect();
+        debug!("TextRunScanner: taking {} fragments in range", fragments.len());
+
+        debug_assert!(!fragments.is_empty());
+        let compression = match fragments[0].specific {
+            UnscannedTextFragment(_) => {
+                match fragments[0].white_space() {
+                    white_space::normal | white_space::nowrap => CompressWhitespaceNewline,
+                    white_space::pre => CompressNone,
+                }
+            }
             _ => {
-                debug_assert!(self.clump.len() == 1,
-                              "WAT: can't coalesce non-text nodes in flush_clump_to_list()!");
-                out_fragments.push(self.clump.pop_front().unwrap());
-                return last_whitespace
+                debug_assert!(fragments.len() == 1,
+                              "WAT: can't coalesce non-text nodes in take_clump()!");
+                return Clump {
+                    fragments: fragments,
+                    text: None,
+                    ranges: Vec::new(),
+                    new_line_positions: Vec::new(),
+                    paragraph_offsets: Vec::new(),
+                }
             }
-        }
+        };
 
-        // TODO(#177): Text run creation must account for the renderability of text by font group
-        // fonts. This is probably achieved by creating the font group above and then letting
-        // `FontGroup` decide which `Font` to stick into the text run.
-        //
-        // Concatenate all of the transformed strings together, saving the new character indices.
-        let mut new_ranges: SmallVec1<Range<CharIndex>> = SmallVec1::new();
-        let mut new_line_positions: SmallVec1<NewLinePositions> = SmallVec1::new();
+        let mut text = String::new();
+        let mut ranges = Vec::with_capacity(fragments.len());
+        let mut new_line_positions = Vec::with_capacity(fragments.len());
         let mut char_total = CharIndex(0);
-        let run = {
-            let fontgroup;
-            let compression;
-            {
-                let in_fragment = self.clump.front().unwrap();
-                let font_style = in_fragment.style().get_font_arc();
-                fontgroup = font_context.get_layout_font_group_for_style(font_style);
-                compression = match in_fragment.white_space() {
-                    white_space::normal | white_space::nowrap => CompressWhitespaceNewline,
-                    white_space::pre => CompressNone,
+        for fragment in fragments.iter() {
+            let fragment_text = match fragment.specific {
+                UnscannedTextFragment(ref text_fragment_info) => &text_fragment_info.text,
+                _ => panic!("Expected an unscanned text fragment!"),
+            };
+
+            let mut new_line_pos = Vec::new();
+            let old_length = CharIndex(text.as_slice().char_len() as int);
+            *last_whitespace = util::transform_text(fragment_text.as_slice(),
+                                                    compression,
+                                                    *last_whitespace,
+                                                    &mut text,
+                                                    &mut new_line_pos);
+            new_line_positions.push(NewLinePositions(new_line_pos));
+
+            let added_chars = CharIndex(text.as_slice().char_len() as int) - old_length;
+            ranges.push(Range::new(char_total, added_chars));
+            char_total = char_total + added_chars;
+        }
+
+        Clump {
+            fragments: fragments,
+            text: Some(text),
+            ranges: ranges,
+            new_line_positions: new_line_positions,
+            paragraph_offsets: Vec::new(),
+        }
+    }
+}
+
+/// The fragments of a clump, along with their transformed text.
+struct Clump {
+    fragments: Vec<Fragment>,
+    /// The transformed text of all of the fragments, or `None` if this clump is a single
+    /// fragment that isn't text.
+    text: Option<String>,
+    /// The range of the transformed text that belongs to each fragment.
+    ranges: Vec<Range<CharIndex>>,
+    /// The positions of the new lines in each fragment, relative to the start of its range.
+    new_line_positions: Vec<NewLinePositions>,
+    /// The index in the paragraph of the first character of each fragment.
+    paragraph_offsets: Vec<uint>,
+}
+
+/// The character that stands in for fragments that aren't text in the paragraph.
+static OBJECT_REPLACEMENT_CHARACTER: char = '\ufffc';
+
+/// Builds the text of the paragraph made up of the given clumps, with explicit directional
+/// formatting characters for inline elements whose `unicode-bidi` isn't `normal`, and resolves
+/// its embedding levels. This records where each fragment starts in the paragraph.
+fn resolve_bidi_levels(clumps: &mut [Clump], paragraph_style: &ComputedValues) -> Vec<u8> {
+    let mut paragraph = Vec::new();
+    let mut open_styles = Vec::new();
+    for clump in clumps.iter_mut() {
+        let mut text_chars = clump.text.as_ref().map(|text| text.as_slice().chars());
+        for (i, fragment) in clump.fragments.iter().enumerate() {
+            push_bidi_control_characters(&mut paragraph, &mut open_styles, fragment);
+            clump.paragraph_offsets.push(paragraph.len());
+            match text_chars {
+                Some(ref mut text_chars) => {
+                    for _ in range(0, clump.ranges[i].length().to_uint()) {
+                        paragraph.push(text_chars.next().unwrap())
+                    }
                 }
+                None => paragraph.push(OBJECT_REPLACEMENT_CHARACTER),
             }
+        }
+    }
 
-            // First, transform/compress text of all the nodes.
-            let mut run_text = String::new();
-            for in_fragment in self.clump.iter() {
-                let in_fragment = match in_fragment.specific {
-                    UnscannedTextFragment(ref text_fragment_info) => &text_fragment_info.text,
-                    _ => panic!("Expected an unscanned text fragment!"),
-                };
-
-                let mut new_line_pos = Vec::new();
-                let old_length = CharIndex(run_text.as_slice().char_len() as int);
-                last_whitespace = util::transform_text(in_fragment.as_slice(),
-                                                       compression,
-                                                       last_whitespace,
-                                                       &mut run_text,
-                                                       &mut new_line_pos);
-                new_line_positions.push(NewLinePositions(new_line_pos));
-
-                let added_chars = CharIndex(run_text.as_slice().char_len() as int) - old_length;
-                new_ranges.push(Range::new(char_total, added_chars));
-                char_total = char_total + added_chars;
+    let paragraph_level = match paragraph_style.get_text().unicode_bidi {
+        unicode_bidi::plaintext => None,
+        _ => {
+            match paragraph_style.get_inheritedbox().direction {
+                direction::ltr => Some(0),
+                direction::rtl => Some(1),
             }
+        }
+    };
+    let directional_override = match paragraph_style.get_text().unicode_bidi {
+        unicode_bidi::bidi_override | unicode_bidi::isolate_override => true,
+        _ => false,
+    };
+    BidiParagraph::new(paragraph.as_slice(), paragraph_level, directional_override).levels
+}
+
+/// Closes the embeddings and isolates of the inline elements that `fragment` is not inside and
+/// opens those of the ones that it is inside, given the styles of the inline elements whose
+/// embeddings and isolates are open, from the outermost inward.
+///
+/// FIXME: Sibling elements that share a style are treated as a single element.
+fn push_bidi_control_characters(paragraph: &mut Vec<char>,
+                                open_styles: &mut Vec<Arc<ComputedValues>>,
+                                fragment: &Fragment) {
+    let styles: Vec<&Arc<ComputedValues>> = match fragment.inline_context {
+        None => Vec::new(),
+        Some(ref inline_context) => {
+            inline_context.styles.iter().rev().filter(|style| {
+                style.get_text().unicode_bidi != unicode_bidi::normal
+            }).collect()
+        }
+    };
 
-            // Now create the run.
-            //
-            // TextRuns contain a cycle which is usually resolved by the teardown sequence.
-            // If no clump takes ownership, however, it will leak.
-            if run_text.len() == 0 {
-                self.clump = DList::new();
-                return last_whitespace
+    let common_length = open_styles.iter()
+                                   .zip(styles.iter())
+                                   .take_while(|&(open_style, style)| {
+                                       arc_ptr_eq(open_style, *style)
+                                   })
+                                   .count();
+    while open_styles.len() > common_length {
+        let (_, closing) = bidi_control_characters(&*open_styles.pop().unwrap());
+        paragraph.extend(closing.chars());
+    }
+    for style in styles.slice_from(common_length).iter() {
+        let (opening, _) = bidi_control_characters(&***style);
+        paragraph.extend(opening.chars());
+        open_styles.push((*style).clone());
+    }
+}
+
+/// Returns the directional formatting characters that begin and end an inline element with the
+/// given style, per CSS Writing Modes Level 3 § 2.4.2.
+fn bidi_control_characters(style: &ComputedValues) -> (&'static str, &'static str) {
+    let is_rtl = style.get_inheritedbox().direction == direction::rtl;
+    match (style.get_text().unicode_bidi, is_rtl) {
+        (unicode_bidi::normal, _) => ("", ""),
+        (unicode_bidi::embed, false) => ("\u202a", "\u202c"),
+        (unicode_bidi::embed, true) => ("\u202b", "\u202c"),
+        (unicode_bidi::isolate, false) => ("\u2066", "\u2069"),
+        (unicode_bidi::isolate, true) => ("\u2067", "\u2069"),
+        (unicode_bidi::bidi_override, false) => ("\u202d", "\u202c"),
+        (unicode_bidi::bidi_override, true) => ("\u202e", "\u202c"),
+        (unicode_bidi::isolate_override, false) => ("\u2066\u202d", "\u202c\u2069"),
+        (unicode_bidi::isolate_override, true) => ("\u2067\u202e", "\u202c\u2069"),
+        (unicode_bidi::plaintext, _) => ("\u2068", "\u2069"),
+    }
+}
+
+/// Creates text runs for the given clump, one for each stretch of text with a single embedding
+/// level, script and font, and makes new fragments with the runs and adjusted text indices.
+///
+/// The flow keeps track of the fragments contained by all non-leaf DOM nodes. This is necessary
+/// for correct painting order. Since we compress several leaf fragments here, the mapping must
+/// be adjusted.
+fn flush_clump_to_list(font_context: &mut FontContext,
+                       clump: Clump,
+                       levels: &[u8],
+                       out_fragments: &mut Vec<Fragment>) {
+    let Clump {
+        fragments,
+        text,
+        ranges,
+        new_line_positions,
+        paragraph_offsets
+    } = clump;
+    debug!("TextRunScanner: flushing {} fragments in range", fragments.len());
+
+    let text = match text {
+        Some(text) => text,
+        None => {
+            let mut fragment = fragments.into_iter().next().unwrap();
+            fragment.bidi_level = levels[paragraph_offsets[0]];
+            out_fragments.push(fragment);
+            return
+        }
+    };
+
+    // TextRuns contain a cycle which is usually resolved by the teardown sequence.
+    // If no clump takes ownership, however, it will leak.
+    if text.len() == 0 {
+        return
+    }
+
+    let font_style = fragments[0].style().get_font_arc();
+    let fontgroup = font_context.get_layout_font_group_for_style(font_style.clone());
+
+    let chars: Vec<char> = text.as_slice().chars().collect();
+    let mut char_levels = Vec::with_capacity(chars.len());
+    for (range, &paragraph_offset) in ranges.iter().zip(paragraph_offsets.iter()) {
+        let length = range.length().to_uint();
+        char_levels.push_all(levels.slice(paragraph_offset, paragraph_offset + length));
+    }
+    let scripts = resolve_scripts(chars.as_slice());
+
+    // Pick a font for each character. Combining marks, and punctuation that the font of the
+    // text before it can render, stay in that font so that they are shaped along with it.
+    let mut fonts: Vec<Rc<RefCell<Font>>> = Vec::with_capacity(chars.len());
+    for &ch in chars.iter() {
+        let keeps_last_font = match fonts.last() {
+            None => false,
+            Some(last_font) => {
+                match script_for_char(ch) {
+                    Inherited => true,
+                    Common => last_font.borrow().glyph_index(ch).is_some(),
+                    _ => false,
+                }
             }
-            Arc::new(box TextRun::new(&mut *fontgroup.fonts.get(0).borrow_mut(), run_text))
         };
+        let font = if keeps_last_font {
+            fonts.last().unwrap().clone()
+        } else {
+            font_context.get_layout_font_for_char(&*fontgroup, &*font_style, ch)
+        };
+        fonts.push(font)
+    }
 
-        // Make new fragments with the run and adjusted text indices.
-        debug!("TextRunScanner: pushing {} fragment(s)", self.clump.len());
-        for (logical_offset, old_fragment) in
-                mem::replace(&mut self.clump, DList::new()).into_iter().enumerate() {
-            let range = *new_ranges.get(logical_offset);
-            if range.is_empty() {
-                debug!("Elided an `UnscannedTextFragment` because it was zero-length after \
-                        compression; {}",
-                       old_fragment);
+    // Now create the runs.
+    let mut runs = Vec::new();
+    let mut run_text = String::new();
+    let mut run_start = 0;
+    for (i, &ch) in chars.iter().enumerate() {
+        if i > run_start && (char_levels[i] != char_levels[run_start] ||
+                             scripts[i] != scripts[run_start] ||
+                             !is_same_font(&fonts[i], &fonts[run_start])) {
+            let run_range = Range::new(CharIndex(run_start as int),
+                                       CharIndex((i - run_start) as int));
+            let run = create_text_run(&fonts[run_start],
+                                      &*font_style,
+                                      mem::replace(&mut run_text, String::new()),
+                                      scripts[run_start],
+                                      char_levels[run_start]);
+            runs.push((run_range, run));
+            run_start = i;
+        }
+        run_text.push(ch);
+    }
+    let run_range = Range::new(CharIndex(run_start as int),
+                               CharIndex((chars.len() - run_start) as int));
+    let run = create_text_run(&fonts[run_start],
+                              &*font_style,
+                              run_text,
+                              scripts[run_start],
+                              char_levels[run_start]);
+    runs.push((run_range, run));
+
+    // Make new fragments with the runs and adjusted text indices. A fragment is split wherever
+    // a new run begins.
+    debug!("TextRunScanner: pushing {} fragment(s)", fragments.len());
+    for ((old_fragment, range), NewLinePositions(new_line_positions)) in
+            fragments.into_iter().zip(ranges.into_iter()).zip(new_line_positions.into_iter()) {
+        if range.is_empty() {
+            debug!("Elided an `UnscannedTextFragment` because it was zero-length after \
+                    compression; {}",
+                   old_fragment);
+            continue
+        }
+
+        for &(ref run_range, ref run) in runs.iter() {
+            let piece = range.intersect(run_range);
+            if piece.is_empty() {
                 continue
             }
 
+            let offset = piece.begin() - range.begin();
+            let new_line_positions = new_line_positions.iter().filter(|&&position| {
+                position >= offset && position < offset + piece.length()
+            }).map(|&position| position - offset).collect();
+
             let text_size = old_fragment.border_box.size;
-            let &NewLinePositions(ref mut new_line_positions) =
-                new_line_positions.get_mut(logical_offset);
-            let new_text_fragment_info =
-                box ScannedTextFragmentInfo::new(run.clone(),
-                                                 range,
-                                                 mem::replace(new_line_positions, Vec::new()),
-                                                 text_size);
-            let new_metrics = new_text_fragment_info.run.metrics_for_range(&range);
+            let piece = Range::new(piece.begin() - run_range.begin(), piece.length());
+            let new_text_fragment_info = box ScannedTextFragmentInfo::new(run.clone(),
+                                                                         piece,
+                                                                         new_line_positions,
+                                                                         text_size);
+            let new_metrics = new_text_fragment_info.run.metrics_for_range(&piece);
             let bounding_box_size = bounding_box_for_run_metrics(&new_metrics,
                                                                  old_fragment.style.writing_mode);
-            let new_fragment = old_fragment.transform(bounding_box_size, new_text_fragment_info);
+            let mut new_fragment = old_fragment.transform(bounding_box_size,
+                                                          new_text_fragment_info);
+            new_fragment.bidi_level = run.bidi_level;
             out_fragments.push(new_fragment)
         }
+    }
+}
+
+/// Shapes the text of a scanned text fragment again in another style, as the `first-line`
+/// pseudo-element does to the text on the first line. The new fragment has a text run of its own
+/// that holds exactly its text, so its character indices are those of the old fragment less the
+/// start of its range.
+pub fn reshape_text_fragment(font_context: &mut FontContext,
+                             fragment: &Fragment,
+                             style: Arc<ComputedValues>)
+                             -> Fragment {
+    let info = match fragment.specific {
+        ScannedTextFragment(ref info) => info,
+        _ => panic!("only scanned text fragments can be shaped again"),
+    };
+    let text = info.run.text.as_slice().slice_chars(info.range.begin().to_uint(),
+                                                    info.range.end().to_uint());
+    let chars: Vec<char> = text.chars().collect();
 
-        last_whitespace
+    // The fragment lies within one text run, so all of its text has one script and level.
+    let font_style = style.get_font_arc();
+    let fontgroup = font_context.get_layout_font_group_for_style(font_style.clone());
+    let first_char = chars.as_slice().get(0).map_or(' ', |&ch| ch);
+    let font = font_context.get_layout_font_for_char(&*fontgroup, &*font_style, first_char);
+    let scripts = resolve_scripts(chars.as_slice());
+    let script = scripts.as_slice().get(0).map_or(Common, |&script| script);
+    let run = create_text_run(&font, &*font_style, text.to_string(), script, info.run.bidi_level);
+
+    let mut new_fragment = fragment.clone();
+    new_fragment.style = style;
+    set_text_run(&mut new_fragment, run, Range::new(CharIndex(0), info.range.length()));
+    new_fragment
+}
+
+/// Makes a scanned text fragment show the given range of the given text run, and sizes it to fit.
+pub fn set_text_run(fragment: &mut Fragment, run: Arc<Box<TextRun>>, range: Range<CharIndex>) {
+    let size = bounding_box_for_run_metrics(&run.metrics_for_range(&range),
+                                            fragment.style.writing_mode);
+    match fragment.specific {
+        ScannedTextFragment(ref mut info) => {
+            info.run = run;
+            info.range = range;
+            info.content_size = size;
+        }
+        _ => panic!("only scanned text fragments have text runs"),
     }
+    fragment.border_box.size = size
+}
+
+/// Shapes the text of a run, all of which has the given font, script and embedding level.
+fn create_text_run(font: &Rc<RefCell<Font>>,
+                   font_style: &FontStyle,
+                   text: String,
+                   script: Script,
+                   bidi_level: u8)
+                   -> Arc<Box<TextRun>> {
+    let mut options = ShapingOptions::new();
+    options.script = script;
+    options.features = font.borrow().shaping_features(font_style);
+    options.language = font_style._servo_lang.clone();
+    Arc::new(box TextRun::new(&mut *font.borrow_mut(), text, &options, bidi_level))
+}
+
+fn is_same_font(a: &Rc<RefCell<Font>>, b: &Rc<RefCell<Font>>) -> bool {
+    &**a as *const RefCell<Font> == &**b as *const RefCell<Font>
 }
 
 struct NewLinePositions(Vec<CharIndex>);
@@ -17,7 +17,7 @@ use script::layout_interface::{LayoutChan, TrustedNodeAddress};
 use script_traits::UntrustedNodeAddress;
 use std::mem;
 use std::cell::{Ref, RefMut};
-use style::ComputedValues;
+use style::{ComputedValues, DeclarationBlock};
 use style;
 use sync::Arc;
 
@@ -29,6 +29,16 @@ pub struct PrivateLayoutData {
     /// The results of CSS styling for this node's `after` pseudo-element, if any.
     pub after_style: Option<Arc<ComputedValues>>,
 
+    /// The results of CSS styling for this node's `first-line` pseudo-element, if any.
+    pub first_line_style: Option<Arc<ComputedValues>>,
+
+    /// The results of CSS styling for this node's `first-letter` pseudo-element, if any.
+    pub first_letter_style: Option<Arc<ComputedValues>>,
+
+    /// The declarations that apply to this node's `first-letter` pseudo-element, for a first
+    /// letter inside a descendant inline element, which they apply over.
+    pub first_letter_declarations: Vec<DeclarationBlock>,
+
     /// Description of how to account for recent style changes.
     pub restyle_damage: RestyleDamage,
 
@@ -53,6 +63,9 @@ impl PrivateLayoutData {
         PrivateLayoutData {
             before_style: None,
             after_style: None,
+            first_line_style: None,
+            first_letter_style: None,
+            first_letter_declarations: vec!(),
             restyle_damage: RestyleDamage::empty(),
             flow_construction_result: NoConstructionResult,
             before_flow_construction_result: NoConstructionResult,
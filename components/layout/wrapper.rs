@@ -36,13 +36,16 @@ use incremental::RestyleDamage;
 use util::{LayoutDataAccess, LayoutDataFlags, LayoutDataWrapper, OpaqueNodeMethods};
 use util::{PrivateLayoutData};
 
+use canvas::canvas_chan::CanvasChan;
 use gfx::display_list::OpaqueNode;
-use script::dom::bindings::codegen::InheritTypes::{ElementCast, HTMLIFrameElementCast};
-use script::dom::bindings::codegen::InheritTypes::{HTMLImageElementCast, HTMLInputElementCast};
+use script::dom::bindings::codegen::InheritTypes::{ElementCast, HTMLCanvasElementCast};
+use script::dom::bindings::codegen::InheritTypes::{HTMLIFrameElementCast, HTMLImageElementCast};
+use script::dom::bindings::codegen::InheritTypes::HTMLInputElementCast;
 use script::dom::bindings::codegen::InheritTypes::{NodeCast, TextCast};
 use script::dom::bindings::js::JS;
 use script::dom::element::{Element, HTMLAreaElementTypeId, HTMLAnchorElementTypeId};
 use script::dom::element::{HTMLLinkElementTypeId, LayoutElementHelpers, RawLayoutElementHelpers};
+use script::dom::htmlcanvaselement::LayoutHTMLCanvasElementHelpers;
 use script::dom::htmliframeelement::HTMLIFrameElement;
 use script::dom::htmlimageelement::LayoutHTMLImageElementHelpers;
 use script::dom::htmlinputelement::LayoutHTMLInputElementHelpers;
@@ -57,7 +60,10 @@ use std::kinds::marker::ContravariantLifetime;
 use std::mem;
 use style::computed_values::{content, display, white_space};
 use style::{AnyNamespace, AttrSelector, IntegerAttribute, LengthAttribute};
-use style::{PropertyDeclarationBlock, SpecificNamespace, TElement, TElementAttributes, TNode};
+use style::{ComputedValues, DeclarationBlock, PropertyDeclarationBlock, SpecificNamespace};
+use style::TElement;
+use style::{TElementAttributes, TNode};
+use sync::Arc;
 use url::Url;
 use string_cache::{Atom, Namespace};
 
@@ -108,6 +114,39 @@ pub trait TLayoutNode {
         }
     }
 
+    /// If this is a canvas element, returns the channel to its canvas task, or `None` if script
+    /// has not created a rendering context for it. If this is not a canvas element, fails.
+    fn get_renderer(&self) -> Option<CanvasChan> {
+        unsafe {
+            match HTMLCanvasElementCast::to_js(self.get_jsmanaged()) {
+                Some(elem) => elem.get_renderer(),
+                None => panic!("not a canvas element!")
+            }
+        }
+    }
+
+    /// If this is a canvas element, returns the width of its bitmap. If this is not a canvas
+    /// element, fails.
+    fn get_canvas_width(&self) -> u32 {
+        unsafe {
+            match HTMLCanvasElementCast::to_js(self.get_jsmanaged()) {
+                Some(elem) => elem.get_canvas_width(),
+                None => panic!("not a canvas element!")
+            }
+        }
+    }
+
+    /// If this is a canvas element, returns the height of its bitmap. If this is not a canvas
+    /// element, fails.
+    fn get_canvas_height(&self) -> u32 {
+        unsafe {
+            match HTMLCanvasElementCast::to_js(self.get_jsmanaged()) {
+                Some(elem) => elem.get_canvas_height(),
+                None => panic!("not a canvas element!")
+            }
+        }
+    }
+
     /// If this node is an iframe element, returns its pipeline and subpage IDs. If this node is
     /// not an iframe element, fails.
     fn iframe_pipeline_and_subpage_ids(&self) -> (PipelineId, SubpageId) {
@@ -390,6 +429,17 @@ impl<'ln> TNode<'ln, LayoutElement<'ln>> for LayoutNode<'ln> {
         }
     }
 
+    fn is_empty(self) -> bool {
+        self.children().all(|kid| {
+            unsafe {
+                match TextCast::to_js(&kid.node) {
+                    Some(text) => (*text.unsafe_get()).characterdata().data_for_layout().is_empty(),
+                    None => !kid.node_is_element(),
+                }
+            }
+        })
+    }
+
     fn has_changed(self) -> bool {
         unsafe { self.node.get_flag(HAS_CHANGED) }
     }
@@ -550,6 +600,41 @@ impl<'le> TElement<'le> for LayoutElement<'le> {
         }
     }
 
+    #[inline]
+    fn get_checked_state(self) -> bool {
+        unsafe {
+            NodeCast::from_actual(self.element).get_checked_state_for_layout()
+        }
+    }
+
+    #[inline]
+    fn get_indeterminate_state(self) -> bool {
+        unsafe {
+            NodeCast::from_actual(self.element).get_indeterminate_state_for_layout()
+        }
+    }
+
+    #[inline]
+    fn get_active_state(self) -> bool {
+        unsafe {
+            NodeCast::from_actual(self.element).get_active_state_for_layout()
+        }
+    }
+
+    #[inline]
+    fn get_focus_state(self) -> bool {
+        unsafe {
+            NodeCast::from_actual(self.element).get_focus_state_for_layout()
+        }
+    }
+
+    #[inline]
+    fn get_target_state(self) -> bool {
+        unsafe {
+            NodeCast::from_actual(self.element).get_target_state_for_layout()
+        }
+    }
+
     #[inline]
     fn has_class(self, name: &Atom) -> bool {
         unsafe {
@@ -586,17 +671,22 @@ impl<'le> TElementAttributes for LayoutElement<'le> {
     }
 }
 
+/// Returns the text of the given `content`. Content that uses counters is resolved later, by the
+/// generated content traversal, instead.
 fn get_content(content_list: &content::T) -> String {
+    let mut text = String::new();
     match *content_list {
-        content::Content(ref value) => {
-            let iter = &mut value.clone().into_iter().peekable();
-            match iter.next() {
-                Some(content::StringContent(content)) => content,
-                _ => "".to_string(),
+        content::Content(ref items) => {
+            for item in items.iter() {
+                match *item {
+                    content::StringContent(ref string) => text.push_str(string.as_slice()),
+                    content::CounterContent(..) | content::CountersContent(..) => {}
+                }
             }
         }
-        _ => "".to_string(),
+        _ => {}
     }
+    text
 }
 
 #[deriving(PartialEq, Clone)]
@@ -806,6 +896,30 @@ impl<'ln> ThreadSafeLayoutNode<'ln> {
         layout_data_wrapper_ref.data.after_style.is_some()
     }
 
+    /// Returns the style of this node's `first-line` pseudo-element, if any.
+    #[inline]
+    pub fn get_first_line_style(&self) -> Option<Arc<ComputedValues>> {
+        let layout_data_wrapper = self.borrow_layout_data();
+        let layout_data_wrapper_ref = layout_data_wrapper.as_ref().unwrap();
+        layout_data_wrapper_ref.data.first_line_style.clone()
+    }
+
+    /// Returns the style of this node's `first-letter` pseudo-element, if any.
+    #[inline]
+    pub fn get_first_letter_style(&self) -> Option<Arc<ComputedValues>> {
+        let layout_data_wrapper = self.borrow_layout_data();
+        let layout_data_wrapper_ref = layout_data_wrapper.as_ref().unwrap();
+        layout_data_wrapper_ref.data.first_letter_style.clone()
+    }
+
+    /// Returns the declarations that apply to this node's `first-letter` pseudo-element.
+    #[inline]
+    pub fn get_first_letter_declarations(&self) -> Vec<DeclarationBlock> {
+        let layout_data_wrapper = self.borrow_layout_data();
+        let layout_data_wrapper_ref = layout_data_wrapper.as_ref().unwrap();
+        layout_data_wrapper_ref.data.first_letter_declarations.clone()
+    }
+
     /// Borrows the layout data without checking. Fails on a conflicting borrow.
     #[inline(always)]
     fn borrow_layout_data_unchecked<'a>(&'a self) -> *const Option<LayoutDataWrapper> {
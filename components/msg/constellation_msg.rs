@@ -11,6 +11,7 @@ use geom::scale_factor::ScaleFactor;
 use http::headers::request::HeaderCollection as RequestHeaderCollection;
 use http::method::{Method, Get};
 use layers::geometry::DevicePixel;
+use servo_net::storage_task::StorageType;
 use servo_util::geometry::{PagePx, ViewportPx};
 use std::comm::{channel, Sender, Receiver};
 use url::Url;
@@ -205,6 +206,9 @@ pub enum Msg {
     RendererReadyMsg(PipelineId),
     ResizedWindowMsg(WindowSizeData),
     KeyEvent(Key, KeyState, KeyModifiers),
+    /// A storage area of the given pipeline's document changed; the other documents of the same
+    /// origin get a `storage` event. Carries the document URL, key, old value and new value.
+    BroadcastStorageEventMsg(PipelineId, StorageType, Url, Option<String>, Option<String>, Option<String>),
 }
 
 /// Similar to net::resource_task::LoadData
@@ -247,3 +251,8 @@ pub struct PipelineId(pub uint);
 
 #[deriving(Clone, PartialEq, Eq, Hash, Show)]
 pub struct SubpageId(pub uint);
+
+/// Identifies a top-level browsing context, which outlives the pipelines of the documents it
+/// navigates through and is shared by the browsing contexts nested in it.
+#[deriving(Clone, PartialEq, Eq, Hash, Show)]
+pub struct FrameId(pub uint);
@@ -10,6 +10,7 @@ extern crate geom;
 extern crate http;
 extern crate layers;
 extern crate serialize;
+extern crate "net" as servo_net;
 extern crate "util" as servo_util;
 extern crate url;
 
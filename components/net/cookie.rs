@@ -0,0 +1,399 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Implementation of cookie creation and matching as specified by
+//! http://tools.ietf.org/html/rfc6265
+
+use http::headers::HeaderEnum;
+use http::headers::response::HeaderCollection as ResponseHeaderCollection;
+use std::ascii::AsciiExt;
+use std::i64;
+use time::{Timespec, Tm, get_time, strptime};
+use url::Url;
+
+/// Where a cookie is being read or written from. Cookies flagged `HttpOnly` are invisible to
+/// non-HTTP APIs such as `document.cookie`.
+#[deriving(Clone, PartialEq, Show)]
+pub enum CookieSource {
+    /// An HTTP API
+    HTTP,
+    /// A non-HTTP API
+    NonHTTP,
+}
+
+/// A stored cookie, as described by http://tools.ietf.org/html/rfc6265#section-5.3
+#[deriving(Clone, Show)]
+pub struct Cookie {
+    pub name: String,
+    pub value: String,
+    pub domain: String,
+    pub path: String,
+    /// The time after which this cookie should be discarded; `None` for session cookies.
+    pub expiry_time: Option<Timespec>,
+    pub creation_time: Timespec,
+    pub last_access: Timespec,
+    pub host_only: bool,
+    pub secure_only: bool,
+    pub http_only: bool,
+}
+
+impl Cookie {
+    /// Parse a `Set-Cookie` header value received for `request`, returning `None` if the
+    /// cookie must be ignored. http://tools.ietf.org/html/rfc6265#section-5.2 and
+    /// http://tools.ietf.org/html/rfc6265#section-5.3
+    pub fn new_wrapped(header: &str, request: &Url, source: CookieSource) -> Option<Cookie> {
+        let mut parts = header.split(';');
+
+        // Step 1-5 of 5.2: the name-value-pair.
+        let name_value_pair = parts.next().unwrap_or("");
+        let (name, value) = match name_value_pair.find('=') {
+            Some(index) => (name_value_pair.slice_to(index).trim(),
+                            name_value_pair.slice_from(index + 1).trim()),
+            None => return None,
+        };
+        if name.is_empty() {
+            return None;
+        }
+
+        let mut expires = None;
+        let mut max_age = None;
+        let mut domain = None;
+        let mut path = None;
+        let mut secure = false;
+        let mut http_only = false;
+
+        // Step 6 of 5.2: the unparsed attributes.
+        for attribute in parts {
+            let (attr_name, attr_value) = match attribute.find('=') {
+                Some(index) => (attribute.slice_to(index).trim(),
+                                attribute.slice_from(index + 1).trim()),
+                None => (attribute.trim(), ""),
+            };
+            match attr_name.to_ascii_lower().as_slice() {
+                // http://tools.ietf.org/html/rfc6265#section-5.2.1
+                "expires" => {
+                    match parse_cookie_date(attr_value) {
+                        Some(time) => expires = Some(time),
+                        None => {}
+                    }
+                }
+                // http://tools.ietf.org/html/rfc6265#section-5.2.2
+                "max-age" => {
+                    let first = attr_value.chars().next();
+                    if first.map_or(false, |c| is_ascii_digit(c) || c == '-') {
+                        match from_str::<i64>(attr_value) {
+                            Some(delta) => max_age = Some(delta),
+                            None => {}
+                        }
+                    }
+                }
+                // http://tools.ietf.org/html/rfc6265#section-5.2.3
+                "domain" => {
+                    if !attr_value.is_empty() {
+                        let value = if attr_value.starts_with(".") {
+                            attr_value.slice_from(1)
+                        } else {
+                            attr_value
+                        };
+                        domain = Some(value.to_ascii_lower());
+                    }
+                }
+                // http://tools.ietf.org/html/rfc6265#section-5.2.4
+                "path" => {
+                    if attr_value.starts_with("/") {
+                        path = Some(attr_value.to_string());
+                    }
+                }
+                // http://tools.ietf.org/html/rfc6265#section-5.2.5
+                "secure" => secure = true,
+                // http://tools.ietf.org/html/rfc6265#section-5.2.6
+                "httponly" => http_only = true,
+                _ => {}
+            }
+        }
+
+        let now = get_time();
+
+        // Step 3 of 5.3: Max-Age takes precedence over Expires.
+        let expiry_time = match (max_age, expires) {
+            (Some(delta), _) if delta <= 0 => Some(Timespec::new(i64::MIN, 0)),
+            (Some(delta), _) => Some(Timespec::new(now.sec.checked_add(&delta).unwrap_or(i64::MAX), 0)),
+            (None, Some(time)) => Some(time),
+            (None, None) => None,
+        };
+
+        // Step 4-6 of 5.3: the domain attribute.
+        let request_host = match request.domain() {
+            Some(host) => host.to_ascii_lower(),
+            None => return None,
+        };
+        let (domain, host_only) = match domain {
+            Some(ref domain) if *domain != request_host => {
+                if !Cookie::domain_match(request_host.as_slice(), domain.as_slice()) {
+                    return None;
+                }
+                (domain.clone(), false)
+            }
+            Some(domain) => (domain, false),
+            None => (request_host, true),
+        };
+
+        // Step 7 of 5.3: the path attribute.
+        let path = path.unwrap_or_else(|| Cookie::default_path(request));
+
+        // Step 10 of 5.3: non-HTTP APIs can't create HttpOnly cookies.
+        if http_only && source == NonHTTP {
+            return None;
+        }
+
+        Some(Cookie {
+            name: name.to_string(),
+            value: value.to_string(),
+            domain: domain,
+            path: path,
+            expiry_time: expiry_time,
+            creation_time: now,
+            last_access: now,
+            host_only: host_only,
+            secure_only: secure,
+            http_only: http_only,
+        })
+    }
+
+    /// Whether this cookie's expiry time has passed.
+    pub fn is_expired(&self) -> bool {
+        self.expiry_time.map_or(false, |time| time <= get_time())
+    }
+
+    pub fn touch(&mut self) {
+        self.last_access = get_time();
+    }
+
+    /// http://tools.ietf.org/html/rfc6265#section-5.1.4
+    pub fn default_path(request: &Url) -> String {
+        let path = request.serialize_path().unwrap_or_else(|| "/".to_string());
+        if !path.as_slice().starts_with("/") {
+            return "/".to_string();
+        }
+        match path.as_slice().rfind('/') {
+            Some(0) | None => "/".to_string(),
+            Some(index) => path.as_slice().slice_to(index).to_string(),
+        }
+    }
+
+    /// http://tools.ietf.org/html/rfc6265#section-5.1.4
+    pub fn path_match(request_path: &str, cookie_path: &str) -> bool {
+        request_path == cookie_path ||
+            (request_path.starts_with(cookie_path) &&
+             (cookie_path.ends_with("/") ||
+              request_path.char_at(cookie_path.len()) == '/'))
+    }
+
+    /// http://tools.ietf.org/html/rfc6265#section-5.1.3
+    pub fn domain_match(string: &str, domain_string: &str) -> bool {
+        if string == domain_string {
+            return true;
+        }
+        string.ends_with(domain_string) &&
+            string.char_at(string.len() - domain_string.len() - 1) == '.' &&
+            !is_ip_address(string)
+    }
+
+    /// Whether this cookie should be sent along with a request for `url`.
+    /// http://tools.ietf.org/html/rfc6265#section-5.4 step 1
+    pub fn appropriate_for_url(&self, url: &Url, source: CookieSource) -> bool {
+        let domain = match url.domain() {
+            Some(domain) => domain.to_ascii_lower(),
+            None => return false,
+        };
+        if self.host_only {
+            if self.domain != domain {
+                return false;
+            }
+        } else if !Cookie::domain_match(domain.as_slice(), self.domain.as_slice()) {
+            return false;
+        }
+
+        let path = url.serialize_path().unwrap_or_else(|| "/".to_string());
+        if !Cookie::path_match(path.as_slice(), self.path.as_slice()) {
+            return false;
+        }
+
+        if self.secure_only && url.scheme.as_slice() != "https" {
+            return false;
+        }
+        if self.http_only && source == NonHTTP {
+            return false;
+        }
+        true
+    }
+}
+
+fn is_ascii_digit(c: char) -> bool {
+    match c {
+        '0'...'9' => true,
+        _ => false,
+    }
+}
+
+fn is_ip_address(host: &str) -> bool {
+    host.contains_char(':') ||
+        (!host.is_empty() && host.chars().all(|c| is_ascii_digit(c) || c == '.'))
+}
+
+/// Parse the value of an `Expires` attribute. Servers in the wild use several date formats, so
+/// try the common ones in turn.
+fn parse_cookie_date(value: &str) -> Option<Timespec> {
+    static FORMATS: [&'static str, ..4] = [
+        "%a, %d %b %Y %H:%M:%S %Z",  // RFC 1123
+        "%a, %d-%b-%Y %H:%M:%S %Z",  // Netscape
+        "%A, %d-%b-%y %H:%M:%S %Z",  // RFC 850
+        "%a %b %d %H:%M:%S %Y",      // asctime
+    ];
+    for format in FORMATS.iter() {
+        match strptime(value, *format) {
+            Ok(tm) => return Some(fix_two_digit_year(tm).to_timespec()),
+            Err(_) => {}
+        }
+    }
+    None
+}
+
+/// http://tools.ietf.org/html/rfc6265#section-5.1.1 step 3 and 4
+fn fix_two_digit_year(mut tm: Tm) -> Tm {
+    // `tm_year` counts from 1900, so a two-digit year shows up as a negative offset.
+    let year = tm.tm_year + 1900;
+    if year >= 0 && year < 70 {
+        tm.tm_year = year + 100;
+    } else if year >= 70 && year < 100 {
+        tm.tm_year = year;
+    }
+    tm
+}
+
+/// Extract the raw `Set-Cookie` header values from a set of response headers.
+pub fn set_cookie_headers(headers: &ResponseHeaderCollection) -> Vec<String> {
+    headers.iter().filter(|header| {
+        header.header_name().as_slice().eq_ignore_ascii_case("set-cookie")
+    }).map(|header| header.header_value()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Cookie, HTTP, NonHTTP, set_cookie_headers};
+    use http::headers::response::HeaderCollection as ResponseHeaderCollection;
+    use url::Url;
+
+    fn url(s: &str) -> Url {
+        Url::parse(s).unwrap()
+    }
+
+    #[test]
+    fn test_path_match() {
+        assert!(Cookie::path_match("/", "/"));
+        assert!(Cookie::path_match("/index.html", "/"));
+        assert!(Cookie::path_match("/w/index.html", "/"));
+        assert!(Cookie::path_match("/w/index.html", "/w/index.html"));
+        assert!(Cookie::path_match("/w/index.html", "/w/"));
+        assert!(Cookie::path_match("/w/index.html", "/w"));
+
+        assert!(!Cookie::path_match("/", "/w/"));
+        assert!(!Cookie::path_match("/a", "/w/"));
+        assert!(!Cookie::path_match("/", "/w"));
+        assert!(!Cookie::path_match("/w/index.html", "/w/index"));
+        assert!(!Cookie::path_match("/windex.html", "/w/"));
+        assert!(!Cookie::path_match("/windex.html", "/w"));
+    }
+
+    #[test]
+    fn test_domain_match() {
+        assert!(Cookie::domain_match("foo.com", "foo.com"));
+        assert!(Cookie::domain_match("bar.foo.com", "foo.com"));
+        assert!(Cookie::domain_match("baz.bar.foo.com", "foo.com"));
+
+        assert!(!Cookie::domain_match("bar.foo.com", "bar.com"));
+        assert!(!Cookie::domain_match("bar.com", "baz.bar.com"));
+        assert!(!Cookie::domain_match("foo.com", "bar.com"));
+        assert!(!Cookie::domain_match("barfoo.com", "foo.com"));
+        assert!(!Cookie::domain_match("1.2.3.4", "2.3.4"));
+    }
+
+    #[test]
+    fn test_default_path() {
+        assert_eq!(Cookie::default_path(&url("http://example.com/")).as_slice(), "/");
+        assert_eq!(Cookie::default_path(&url("http://example.com/foo")).as_slice(), "/");
+        assert_eq!(Cookie::default_path(&url("http://example.com/foo/")).as_slice(), "/foo");
+        assert_eq!(Cookie::default_path(&url("http://example.com/foo/bar")).as_slice(), "/foo");
+    }
+
+    #[test]
+    fn test_parse() {
+        let u = &url("http://example.com/foo/bar");
+
+        assert!(Cookie::new_wrapped("", u, HTTP).is_none());
+        assert!(Cookie::new_wrapped("=bar", u, HTTP).is_none());
+        assert!(Cookie::new_wrapped("foo", u, HTTP).is_none());
+        assert!(Cookie::new_wrapped("foo=bar; Domain=other.com", u, HTTP).is_none());
+        assert!(Cookie::new_wrapped("foo=bar; HttpOnly", u, NonHTTP).is_none());
+
+        let cookie = Cookie::new_wrapped(" foo = bar ; Secure; HttpOnly", u, HTTP).unwrap();
+        assert_eq!(cookie.name.as_slice(), "foo");
+        assert_eq!(cookie.value.as_slice(), "bar");
+        assert_eq!(cookie.domain.as_slice(), "example.com");
+        assert_eq!(cookie.path.as_slice(), "/foo");
+        assert!(cookie.host_only);
+        assert!(cookie.secure_only);
+        assert!(cookie.http_only);
+        assert!(cookie.expiry_time.is_none());
+
+        let cookie = Cookie::new_wrapped("foo=bar; Domain=.Example.com; Path=/", u, HTTP).unwrap();
+        assert_eq!(cookie.domain.as_slice(), "example.com");
+        assert_eq!(cookie.path.as_slice(), "/");
+        assert!(!cookie.host_only);
+    }
+
+    #[test]
+    fn test_expiry() {
+        let u = &url("http://example.com/");
+
+        let cookie = Cookie::new_wrapped("foo=bar; Max-Age=0", u, HTTP).unwrap();
+        assert!(cookie.is_expired());
+
+        let cookie = Cookie::new_wrapped("foo=bar; Expires=Wed, 09 Jun 2021 10:18:14 GMT", u, HTTP);
+        assert!(cookie.unwrap().expiry_time.is_some());
+
+        let cookie = Cookie::new_wrapped("foo=bar; Expires=Thu, 01 Jan 1970 00:00:01 GMT", u, HTTP);
+        assert!(cookie.unwrap().is_expired());
+
+        // Max-Age wins over Expires.
+        let cookie = Cookie::new_wrapped("foo=bar; Expires=Thu, 01 Jan 1970 00:00:01 GMT; Max-Age=3600",
+                                         u, HTTP).unwrap();
+        assert!(!cookie.is_expired());
+    }
+
+    #[test]
+    fn test_appropriate_for_url() {
+        let cookie = Cookie::new_wrapped("foo=bar; Path=/a; Secure; HttpOnly",
+                                         &url("https://example.com/a/b"), HTTP).unwrap();
+        assert!(cookie.appropriate_for_url(&url("https://example.com/a/c"), HTTP));
+        assert!(!cookie.appropriate_for_url(&url("http://example.com/a/c"), HTTP));
+        assert!(!cookie.appropriate_for_url(&url("https://example.com/b"), HTTP));
+        assert!(!cookie.appropriate_for_url(&url("https://sub.example.com/a"), HTTP));
+        assert!(!cookie.appropriate_for_url(&url("https://example.com/a"), NonHTTP));
+
+        let cookie = Cookie::new_wrapped("foo=bar; Domain=example.com",
+                                         &url("http://www.example.com/"), HTTP).unwrap();
+        assert!(cookie.appropriate_for_url(&url("http://sub.example.com/"), HTTP));
+        assert!(cookie.appropriate_for_url(&url("http://example.com/"), HTTP));
+        assert!(!cookie.appropriate_for_url(&url("http://example.org/"), HTTP));
+    }
+
+    #[test]
+    fn test_set_cookie_headers() {
+        let mut headers = ResponseHeaderCollection::new();
+        let _ = headers.insert_raw("Set-Cookie".to_string(), b"foo=bar; Path=/");
+        let _ = headers.insert_raw("X-Other".to_string(), b"ignored");
+        assert_eq!(set_cookie_headers(&headers), vec!("foo=bar; Path=/".to_string()));
+    }
+}
@@ -0,0 +1,165 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Implementation of cookie storage as specified by
+//! http://tools.ietf.org/html/rfc6265#section-5.3
+
+use cookie::{Cookie, CookieSource, NonHTTP};
+use url::Url;
+
+pub struct CookieStorage {
+    cookies: Vec<Cookie>,
+}
+
+impl CookieStorage {
+    pub fn new() -> CookieStorage {
+        CookieStorage {
+            cookies: Vec::new(),
+        }
+    }
+
+    /// Remove the cookie with the same name, domain and path as `cookie`, returning it if
+    /// one was found. Returns `Err(())` if the existing cookie may not be touched by `source`.
+    pub fn remove(&mut self, cookie: &Cookie, source: CookieSource) -> Result<Option<Cookie>, ()> {
+        let position = self.cookies.iter().position(|c| {
+            c.domain == cookie.domain && c.path == cookie.path && c.name == cookie.name
+        });
+
+        match position {
+            Some(index) => {
+                // Step 11.2 of http://tools.ietf.org/html/rfc6265#section-5.3
+                if source == NonHTTP && self.cookies[index].http_only {
+                    return Err(());
+                }
+                Ok(self.cookies.remove(index))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// http://tools.ietf.org/html/rfc6265#section-5.3 steps 11 and 12
+    pub fn push(&mut self, mut cookie: Cookie, source: CookieSource) {
+        let old_cookie = match self.remove(&cookie, source) {
+            Ok(old_cookie) => old_cookie,
+            Err(()) => return,
+        };
+
+        // Step 11.3: keep the creation time of the cookie being replaced.
+        match old_cookie {
+            Some(old_cookie) => cookie.creation_time = old_cookie.creation_time,
+            None => {}
+        }
+
+        // An expired cookie only serves to evict an existing one.
+        if cookie.is_expired() {
+            return;
+        }
+        self.cookies.push(cookie);
+    }
+
+    /// Parse a `Set-Cookie` header value received for `url` and store the resulting cookie.
+    pub fn set_cookie_for_url(&mut self, url: &Url, header: &str, source: CookieSource) {
+        match Cookie::new_wrapped(header, url, source) {
+            Some(cookie) => self.push(cookie, source),
+            None => debug!("cookie_storage: ignoring cookie {:s}", header),
+        }
+    }
+
+    /// Build the value of the `Cookie` header for a request to `url`, or `None` if there are no
+    /// cookies to send. http://tools.ietf.org/html/rfc6265#section-5.4
+    pub fn cookies_for_url(&mut self, url: &Url, source: CookieSource) -> Option<String> {
+        self.cookies.retain(|c| !c.is_expired());
+
+        let mut matching: Vec<&mut Cookie> = self.cookies.iter_mut().filter(|c| {
+            c.appropriate_for_url(url, source)
+        }).collect();
+
+        // Step 2: longer paths first, then earlier creation times.
+        matching.sort_by(|a, b| {
+            match b.path.len().cmp(&a.path.len()) {
+                Equal => a.creation_time.cmp(&b.creation_time),
+                ordering => ordering,
+            }
+        });
+
+        let mut result = String::new();
+        for cookie in matching.into_iter() {
+            // Step 3: update the last-access time of each cookie we send.
+            cookie.touch();
+            if !result.is_empty() {
+                result.push_str("; ");
+            }
+            result.push_str(cookie.name.as_slice());
+            result.push_str("=");
+            result.push_str(cookie.value.as_slice());
+        }
+
+        if result.is_empty() {
+            None
+        } else {
+            Some(result)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CookieStorage;
+    use cookie::{HTTP, NonHTTP, set_cookie_headers};
+    use http::headers::response::HeaderCollection as ResponseHeaderCollection;
+    use url::Url;
+
+    #[test]
+    fn test_store_and_retrieve() {
+        let url = Url::parse("http://example.com/a/b").unwrap();
+        let mut storage = CookieStorage::new();
+        assert_eq!(storage.cookies_for_url(&url, HTTP), None);
+
+        storage.set_cookie_for_url(&url, "a=1; Path=/", HTTP);
+        storage.set_cookie_for_url(&url, "b=2; Path=/a", HTTP);
+        storage.set_cookie_for_url(&url, "c=3; HttpOnly", HTTP);
+        assert_eq!(storage.cookies_for_url(&url, HTTP), Some("b=2; c=3; a=1".to_string()));
+        assert_eq!(storage.cookies_for_url(&url, NonHTTP), Some("b=2; a=1".to_string()));
+
+        let other = Url::parse("http://example.com/").unwrap();
+        assert_eq!(storage.cookies_for_url(&other, HTTP), Some("a=1".to_string()));
+    }
+
+    #[test]
+    fn test_replace_and_expire() {
+        let url = Url::parse("http://example.com/").unwrap();
+        let mut storage = CookieStorage::new();
+
+        storage.set_cookie_for_url(&url, "a=1", HTTP);
+        storage.set_cookie_for_url(&url, "a=2", HTTP);
+        assert_eq!(storage.cookies_for_url(&url, HTTP), Some("a=2".to_string()));
+
+        storage.set_cookie_for_url(&url, "a=3; Max-Age=0", HTTP);
+        assert_eq!(storage.cookies_for_url(&url, HTTP), None);
+    }
+
+    #[test]
+    fn test_non_http_cannot_overwrite_http_only() {
+        let url = Url::parse("http://example.com/").unwrap();
+        let mut storage = CookieStorage::new();
+
+        storage.set_cookie_for_url(&url, "a=1; HttpOnly", HTTP);
+        storage.set_cookie_for_url(&url, "a=2", NonHTTP);
+        assert_eq!(storage.cookies_for_url(&url, HTTP), Some("a=1".to_string()));
+    }
+
+    #[test]
+    fn test_cookies_from_response_headers() {
+        let url = Url::parse("http://example.com/").unwrap();
+        let mut headers = ResponseHeaderCollection::new();
+        let _ = headers.insert_raw("Set-Cookie".to_string(), b"session=abc; Path=/; HttpOnly");
+
+        let mut storage = CookieStorage::new();
+        for header in set_cookie_headers(&headers).iter() {
+            storage.set_cookie_for_url(&url, header.as_slice(), HTTP);
+        }
+        assert_eq!(storage.cookies_for_url(&url, HTTP), Some("session=abc".to_string()));
+        assert_eq!(storage.cookies_for_url(&url, NonHTTP), None);
+    }
+}
@@ -136,7 +136,7 @@ impl CORSCache for BasicCORSCache {
     #[allow(dead_code)]
     fn clear (&mut self, request: CacheRequestDetails) {
         let BasicCORSCache(buf) = self.clone();
-        let new_buf: Vec<CORSCacheEntry> = buf.into_iter().filter(|e| e.origin == request.origin && request.destination == e.url).collect();
+        let new_buf: Vec<CORSCacheEntry> = buf.into_iter().filter(|e| !(e.origin == request.origin && request.destination == e.url)).collect();
         *self = BasicCORSCache(new_buf);
     }
 
@@ -144,7 +144,7 @@ impl CORSCache for BasicCORSCache {
     fn cleanup(&mut self) {
         let BasicCORSCache(buf) = self.clone();
         let now = time::now().to_timespec();
-        let new_buf: Vec<CORSCacheEntry> = buf.into_iter().filter(|e| now.sec > e.created.sec + e.max_age as i64).collect();
+        let new_buf: Vec<CORSCacheEntry> = buf.into_iter().filter(|e| now.sec < e.created.sec + e.max_age as i64).collect();
         *self = BasicCORSCache(new_buf);
     }
 
@@ -314,3 +314,45 @@ impl CORSCacheTask {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{BasicCORSCache, CORSCache, CacheRequestDetails};
+    use http::method::{Put, Delete};
+    use url::Url;
+
+    fn details() -> CacheRequestDetails {
+        CacheRequestDetails {
+            origin: Url::parse("http://example.com/").unwrap(),
+            destination: Url::parse("http://example.org/api").unwrap(),
+            credentials: false
+        }
+    }
+
+    #[test]
+    fn test_match_and_update_inserts_entries() {
+        let mut cache = BasicCORSCache(vec![]);
+        assert!(!cache.match_method(details(), Put));
+        assert!(!cache.match_method_and_update(details(), Put, 60));
+        assert!(cache.match_method(details(), Put));
+        assert!(!cache.match_method(details(), Delete));
+
+        assert!(!cache.match_header_and_update(details(), "X-Custom", 60));
+        assert!(cache.match_header(details(), "x-custom"));
+    }
+
+    #[test]
+    fn test_expired_entries_are_removed() {
+        let mut cache = BasicCORSCache(vec![]);
+        cache.match_method_and_update(details(), Put, 0);
+        assert!(!cache.match_method(details(), Put));
+    }
+
+    #[test]
+    fn test_clear() {
+        let mut cache = BasicCORSCache(vec![]);
+        cache.match_header_and_update(details(), "X-Custom", 60);
+        cache.clear(details());
+        assert!(!cache.match_header(details(), "X-Custom"));
+    }
+}
@@ -2,13 +2,36 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
+use about_loader;
+use cookie::{HTTP, set_cookie_headers};
+use data_loader;
+use file_loader;
+use http_cache::HttpCacheTask;
+use http_loader;
+use resource_task::{LoadData, TargetedLoadResponse, ResourceTask, GetCookiesForUrl, SetCookiesForUrl};
+use resource_task::Done as LoadDone;
 use url::Url;
-use http::method::{Get, Method};
+use http::headers::HeaderEnum;
+use http::headers::content_type::MediaType;
+use http::headers::request::{Header, Accept, AcceptLanguage, ContentLanguage, ContentType};
+use http::method::{Get, Head, Post, Options, Method};
 use http::headers::request::HeaderCollection;
-use fetch::cors_cache::CORSCache;
-use fetch::response::Response;
+use http::headers::response::HeaderCollection as ResponseHeaderCollection;
+use std::ascii::AsciiExt;
+use std::comm::{channel, Receiver};
+use std::from_str::FromStr;
+use fetch::cors_cache::{CORSCache, CacheRequestDetails};
+use fetch::response::{Response, CORS};
+use fetch::response::Basic as BasicResponse;
+use fetch::response::Opaque as OpaqueResponse;
+use fetch::response::Done as BodyDone;
+use fetch::response::Receiving as BodyReceiving;
+
+/// The most redirects a single fetch will follow. http://fetch.spec.whatwg.org/#http-fetch
+static MAX_REDIRECTS: uint = 20;
 
 /// A [request context](http://fetch.spec.whatwg.org/#concept-request-context)
+#[deriving(Clone, PartialEq)]
 pub enum Context {
     Audio, Beacon, CSPreport, Download, Embed, Eventsource,
     Favicon, Fetch, Font, Form, Frame, Hyperlink, IFrame, Image,
@@ -18,6 +41,7 @@ pub enum Context {
 }
 
 /// A [request context frame type](http://fetch.spec.whatwg.org/#concept-request-context-frame-type)
+#[deriving(Clone, PartialEq)]
 pub enum ContextFrameType {
     Auxiliary,
     TopLevel,
@@ -26,6 +50,7 @@ pub enum ContextFrameType {
 }
 
 /// A [referer](http://fetch.spec.whatwg.org/#concept-request-referrer)
+#[deriving(Clone, PartialEq)]
 pub enum Referer {
     RefererNone,
     Client,
@@ -33,6 +58,7 @@ pub enum Referer {
 }
 
 /// A [request mode](http://fetch.spec.whatwg.org/#concept-request-mode)
+#[deriving(Clone, PartialEq)]
 pub enum RequestMode {
     SameOrigin,
     NoCORS,
@@ -41,6 +67,7 @@ pub enum RequestMode {
 }
 
 /// Request [credentials mode](http://fetch.spec.whatwg.org/#concept-request-credentials-mode)
+#[deriving(Clone, PartialEq)]
 pub enum CredentialsMode {
     Omit,
     CredentialsSameOrigin,
@@ -48,12 +75,22 @@ pub enum CredentialsMode {
 }
 
 /// [Response tainting](http://fetch.spec.whatwg.org/#concept-request-response-tainting)
+#[deriving(Clone, PartialEq)]
 pub enum ResponseTainting {
     Basic,
     CORSTainting,
     Opaque
 }
 
+/// The parts of the resource task that a fetch needs in order to reach the network
+#[deriving(Clone)]
+pub struct FetchContext {
+    /// Used to read and store cookies
+    pub resource_task: ResourceTask,
+    pub http_cache: Option<HttpCacheTask>,
+    pub user_agent: Option<String>,
+}
+
 /// A [Request](http://fetch.spec.whatwg.org/#requests) as defined by the Fetch spec
 pub struct Request {
     pub method: Method,
@@ -79,7 +116,9 @@ pub struct Request {
     pub manual_redirect: bool,
     pub redirect_count: uint,
     pub response_tainting: ResponseTainting,
-    pub cache: Option<Box<CORSCache+'static>>
+    pub cache: Option<Box<CORSCache+Send>>,
+    /// Without a context, fetches of HTTP(S) URLs result in a network error
+    pub fetch_context: Option<FetchContext>
 }
 
 impl Request {
@@ -106,7 +145,50 @@ impl Request {
             manual_redirect: false,
             redirect_count: 0,
             response_tainting: Basic,
-            cache: None
+            cache: None,
+            fetch_context: None
+        }
+    }
+
+    /// [Main fetch](http://fetch.spec.whatwg.org/#concept-fetch)
+    pub fn fetch(&mut self, cors_flag: bool) -> Response {
+        // Step 1: Mixed content checks (unimplemented)
+        // Step 2
+        let response = if (self.is_same_origin() && !cors_flag) ||
+                          (self.url.scheme.as_slice() == "data" && self.same_origin_data) ||
+                          self.url.scheme.as_slice() == "about" {
+            self.basic_fetch()
+        } else if self.mode == SameOrigin {
+            Response::network_error()
+        } else if self.mode == NoCORS {
+            self.response_tainting = Opaque;
+            self.basic_fetch()
+        } else if !is_http_scheme(&self.url) {
+            Response::network_error()
+        } else if self.mode == ForcedPreflightMode ||
+                  (self.unsafe_request && (!is_simple_method(&self.method) ||
+                                           self.headers.iter().any(|h| !is_simple_header(&h)))) {
+            self.response_tainting = CORSTainting;
+            self.http_fetch(true, true, false)
+        } else {
+            self.response_tainting = CORSTainting;
+            self.http_fetch(true, false, false)
+        };
+
+        // Step 3
+        let mut response = response;
+        if response.url.is_none() {
+            response.url = Some(self.url.clone());
+        }
+
+        // Step 4: responses for redirects have already been filtered by the nested fetch
+        if response.is_network_error() || response.internal_response.is_some() {
+            return response;
+        }
+        match self.response_tainting {
+            Basic => response.to_filtered(BasicResponse),
+            CORSTainting => response.to_filtered(CORS),
+            Opaque => response.to_filtered(OpaqueResponse)
         }
     }
 
@@ -117,33 +199,478 @@ impl Request {
                 Some(s) if s.as_slice() == "blank" => {
                     let mut response = Response::new();
                     let _ = response.headers.insert_raw("Content-Type".to_string(), b"text/html;charset=utf-8");
+                    response.body = BodyDone(vec!());
                     response
                 },
-                _ => Response::network_error()
+                // Servo's own pages, such as about:failure
+                Some(_) => self.load_with(about_loader::factory),
+                None => Response::network_error()
             },
             "http" | "https" => {
                 self.http_fetch(false, false, false)
             },
-            "blob" | "data" | "file" | "ftp" => {
-                // XXXManishearth handle these
-                panic!("Unimplemented scheme for Fetch")
-            },
-
-            _ => Response::network_error()
+            "data" if self.method == Get => self.load_with(data_loader::factory),
+            "file" if self.method == Get => self.load_with(file_loader::factory),
+            // There is no blob URL store yet, and FTP is not supported
+            _ => Response::network_error_with_reason(
+                format!("{:s} request, but we don't support that scheme", self.url.scheme))
         }
     }
 
-    // [HTTP fetch](http://fetch.spec.whatwg.org#http-fetch)
-    pub fn http_fetch(&mut self, _cors_flag: bool, cors_preflight_flag: bool, _authentication_fetch_flag: bool) -> Response {
-        let response = Response::new();
-        // TODO: Service worker fetch
-        // Step 3
+    /// [HTTP fetch](http://fetch.spec.whatwg.org#http-fetch)
+    pub fn http_fetch(&mut self, cors_flag: bool, cors_preflight_flag: bool, authentication_fetch_flag: bool) -> Response {
+        // Step 1: Service worker fetch (unimplemented)
+        // Step 2
         // Substep 1
         self.skip_service_worker = true;
         // Substep 2
-        if cors_preflight_flag {
-            // XXXManishearth stuff goes here
+        if cors_preflight_flag && self.needs_preflight() {
+            let preflight_result = self.preflight_fetch();
+            if preflight_result.is_network_error() {
+                return preflight_result;
+            }
         }
+        // Substep 3
+        let credentials_flag = match self.credentials_mode {
+            Include => true,
+            CredentialsSameOrigin => !cors_flag,
+            Omit => false
+        };
+        // Substep 4
+        let response = self.http_network_or_cache_fetch(credentials_flag, authentication_fetch_flag);
+        // Substep 5
+        if cors_flag && !self.cors_check(&response, credentials_flag) {
+            return Response::network_error();
+        }
+
+        // Step 3
+        match response.status.code() {
+            301 | 302 | 303 | 307 | 308 => {
+                // Substep 1
+                if self.manual_redirect {
+                    return response;
+                }
+                // Substep 2
+                let location = match response.headers.location {
+                    Some(ref location) => location.clone(),
+                    None => return response
+                };
+                // Substep 3
+                if self.redirect_count == MAX_REDIRECTS {
+                    debug!("fetch: too many redirects for {:s}", self.url.serialize());
+                    return Response::network_error();
+                }
+                // Substep 4
+                self.redirect_count += 1;
+                // Substep 5
+                self.same_origin_data = true;
+                // Substep 6: a preflight only covers the URL it was made for
+                if cors_flag && cors_preflight_flag {
+                    debug!("fetch: preflight fetch inconsistent with main fetch");
+                    return Response::network_error();
+                }
+                // Substep 7
+                let code = response.status.code();
+                if ((code == 301 || code == 302) && self.method == Post) || code == 303 {
+                    self.method = Get;
+                    self.body = None;
+                }
+                // Substep 8
+                info!("redirecting to {:s}", location.serialize());
+                self.url = location;
+                return self.fetch(cors_flag);
+            },
+            // HTTP authentication (unimplemented)
+            _ => {}
+        }
+
+        // Step 4
         response
     }
+
+    /// [HTTP network or cache fetch](http://fetch.spec.whatwg.org#http-network-or-cache-fetch)
+    pub fn http_network_or_cache_fetch(&mut self, credentials_flag: bool, _authentication_fetch_flag: bool) -> Response {
+        let context = match self.fetch_context {
+            Some(ref context) => context.clone(),
+            None => return Response::network_error()
+        };
+
+        // Step 1
+        let mut headers = self.headers.clone();
+        // Step 2
+        match self.referer {
+            RefererUrl(ref url) => {
+                headers.referer = Some(url.serialize());
+            },
+            RefererNone | Client => {}
+        }
+        // Step 3
+        if self.force_origin_header || self.response_tainting == CORSTainting {
+            match self.origin {
+                Some(ref origin) => {
+                    let _ = headers.insert_raw("Origin".to_string(), serialize_origin(origin).as_bytes());
+                },
+                None => {}
+            }
+        }
+        // Step 4
+        if headers.user_agent.is_none() {
+            headers.user_agent = context.user_agent.clone();
+        }
+        // Step 5
+        if credentials_flag {
+            let (cookies_chan, cookies_port) = channel();
+            context.resource_task.send(GetCookiesForUrl(self.url.clone(), cookies_chan, HTTP));
+            match cookies_port.recv() {
+                Some(cookies) => {
+                    let _ = headers.insert_raw("Cookie".to_string(), cookies.as_bytes());
+                },
+                None => {}
+            }
+        }
+
+        // Step 6: the HTTP cache is consulted by the network fetch
+        let response = self.http_network_fetch(&context, headers);
+
+        // Step 7
+        if credentials_flag && !response.is_network_error() {
+            for cookie in set_cookie_headers(&response.headers).into_iter() {
+                context.resource_task.send(SetCookiesForUrl(self.url.clone(), cookie, HTTP));
+            }
+        }
+        response
+    }
+
+    /// [HTTP network fetch](http://fetch.spec.whatwg.org#http-network-fetch)
+    fn http_network_fetch(&self, context: &FetchContext, headers: HeaderCollection) -> Response {
+        let mut load_data = self.load_data();
+        load_data.headers = headers;
+        let (start_chan, start_port) = channel();
+        http_loader::factory(context.http_cache.clone())(load_data, start_chan);
+        response_from_load(start_port)
+    }
+
+    /// [CORS preflight fetch](http://fetch.spec.whatwg.org#cors-preflight-fetch)
+    fn preflight_fetch(&mut self) -> Response {
+        // Step 1
+        let mut preflight = Request::new(self.url.clone(), self.context.clone());
+        preflight.method = Options;
+        preflight.origin = self.origin.clone();
+        preflight.referer = self.referer.clone();
+        preflight.mode = CORSMode;
+        preflight.response_tainting = CORSTainting;
+        preflight.fetch_context = self.fetch_context.clone();
+        // Step 2
+        let _ = preflight.headers.insert_raw("Access-Control-Request-Method".to_string(),
+                                             self.method.http_value().as_bytes());
+        // Steps 3-5
+        let header_names = self.author_header_names();
+        if !header_names.is_empty() {
+            let _ = preflight.headers.insert_raw("Access-Control-Request-Headers".to_string(),
+                                                 header_names.connect(", ").as_bytes());
+        }
+
+        // Step 6
+        let response = preflight.http_network_or_cache_fetch(false, false);
+
+        // Step 7
+        let status = response.status.code();
+        if status < 200 || status > 299 || !self.cors_check(&response, false) {
+            return Response::network_error();
+        }
+        // Substeps 1-3
+        let mut methods: Vec<Method> = header_list(&response.headers, "Access-Control-Allow-Methods")
+            .iter().filter_map(|m| FromStr::from_str(m.as_slice())).collect();
+        let allowed_headers = header_list(&response.headers, "Access-Control-Allow-Headers");
+        // Substep 4
+        if methods.is_empty() && self.mode == ForcedPreflightMode {
+            methods = vec!(self.method.clone());
+        }
+        // Substep 5
+        if !is_simple_method(&self.method) && !methods.contains(&self.method) {
+            return Response::network_error();
+        }
+        // Substep 6
+        if header_names.iter().any(|name| {
+            !allowed_headers.iter().any(|allowed| allowed.as_slice().eq_ignore_ascii_case(name.as_slice()))
+        }) {
+            return Response::network_error();
+        }
+        // Substeps 7-8
+        let max_age: uint = find_header(&response.headers, "Access-Control-Max-Age")
+            .and_then(|age| FromStr::from_str(age.as_slice().trim())).unwrap_or(0);
+        // Substep 9: no limit is imposed on max-age
+        // Substeps 10-11
+        let origin = self.origin.clone();
+        let url = self.url.clone();
+        let credentials = self.credentials_mode == Include;
+        match (self.cache.as_mut(), origin) {
+            (Some(cache), Some(origin)) => {
+                for method in methods.into_iter() {
+                    cache.match_method_and_update(cache_details(&origin, &url, credentials), method, max_age);
+                }
+                for header in allowed_headers.iter() {
+                    cache.match_header_and_update(cache_details(&origin, &url, credentials),
+                                                  header.as_slice(), max_age);
+                }
+            },
+            _ => {}
+        }
+        // Substep 12
+        response
+    }
+
+    /// [CORS check](http://fetch.spec.whatwg.org#concept-cors-check)
+    fn cors_check(&self, response: &Response, credentials_flag: bool) -> bool {
+        // Step 1
+        let allowed_origin = match find_header(&response.headers, "Access-Control-Allow-Origin") {
+            Some(origin) => origin,
+            None => return false
+        };
+        let allowed_origin = allowed_origin.as_slice().trim();
+        // Step 2
+        if !credentials_flag && allowed_origin == "*" {
+            return true;
+        }
+        // Step 3
+        match self.origin {
+            Some(ref origin) if serialize_origin(origin).as_slice() == allowed_origin => {},
+            _ => return false
+        }
+        // Step 4
+        if !credentials_flag {
+            return true;
+        }
+        // Step 5
+        match find_header(&response.headers, "Access-Control-Allow-Credentials") {
+            Some(ref value) if value.as_slice().trim() == "true" => true,
+            _ => false
+        }
+    }
+
+    /// Whether the CORS preflight cache lacks a match for the method or any of the author
+    /// headers of this request. http://fetch.spec.whatwg.org#http-fetch
+    fn needs_preflight(&mut self) -> bool {
+        let header_names = self.author_header_names();
+        let forced = self.mode == ForcedPreflightMode;
+        let method = self.method.clone();
+        let url = self.url.clone();
+        let credentials = self.credentials_mode == Include;
+        let origin = match self.origin {
+            Some(ref origin) => origin.clone(),
+            None => return true
+        };
+        let cache = match self.cache {
+            Some(ref mut cache) => cache,
+            None => return true
+        };
+        ((forced || !is_simple_method(&method)) &&
+         !cache.match_method(cache_details(&origin, &url, credentials), method)) ||
+        header_names.iter().any(|name| {
+            !cache.match_header(cache_details(&origin, &url, credentials), name.as_slice())
+        })
+    }
+
+    /// The sorted, lowercased names of the headers that are not simple headers.
+    fn author_header_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.headers.iter().filter(|h| !is_simple_header(h))
+                                         .map(|h| h.header_name().as_slice().to_ascii_lower())
+                                         .collect();
+        names.sort();
+        names.dedup();
+        names
+    }
+
+    /// Whether this request's URL is [same origin](http://tools.ietf.org/html/rfc6454#section-5)
+    /// with its origin. Requests without an origin are made on behalf of the user agent.
+    fn is_same_origin(&self) -> bool {
+        match self.origin {
+            Some(ref origin) => origin.scheme == self.url.scheme &&
+                                origin.host() == self.url.host() &&
+                                origin.port() == self.url.port(),
+            None => true
+        }
+    }
+
+    /// Build the `LoadData` used to hand this request over to one of the scheme loaders.
+    fn load_data(&self) -> LoadData {
+        // Nothing listens to the eventual consumer; the loader's response comes back to us.
+        let (consumer, _) = channel();
+        let mut load_data = LoadData::new(self.url.clone(), consumer);
+        load_data.method = self.method.clone();
+        load_data.headers = self.headers.clone();
+        load_data.data = self.body.clone();
+        load_data
+    }
+
+    fn load_with(&self, factory: fn(LoadData, Sender<TargetedLoadResponse>)) -> Response {
+        let (start_chan, start_port) = channel();
+        factory(self.load_data(), start_chan);
+        response_from_load(start_port)
+    }
+}
+
+/// Turn what a loader sends back into a `Response`. The body is left on the loader's progress
+/// port so that it can be forwarded as it arrives. Loaders that fail before producing a response
+/// become network errors carrying the loader's error.
+fn response_from_load(start_port: Receiver<TargetedLoadResponse>) -> Response {
+    let load_response = match start_port.recv_opt() {
+        Ok(targeted) => targeted.load_response,
+        Err(()) => return Response::network_error_with_reason("loader went away".to_string())
+    };
+    let metadata = load_response.metadata;
+
+    // Loaders report failures to reach the network with a response that has no status.
+    if metadata.status.is_none() {
+        let reason = match load_response.progress_port.recv_opt() {
+            Ok(LoadDone(Err(e))) => e,
+            _ => "network error".to_string()
+        };
+        debug!("fetch: network error: {:s}", reason);
+        return Response::network_error_with_reason(reason);
+    }
+
+    let mut response = Response::new();
+    response.url = Some(metadata.final_url);
+    match metadata.status {
+        Some(status) => response.status = status,
+        None => {}
+    }
+    match metadata.headers {
+        Some(headers) => response.headers = headers,
+        None => {}
+    }
+    if response.headers.content_type.is_none() {
+        response.headers.content_type = metadata.content_type.map(|(type_, subtype)| {
+            MediaType {
+                type_: type_,
+                subtype: subtype,
+                parameters: metadata.charset.iter().map(|charset| {
+                    ("charset".to_string(), charset.clone())
+                }).collect()
+            }
+        });
+    }
+
+    response.body = BodyReceiving(vec!());
+    response.body_port = Some(load_response.progress_port);
+    response
+}
+
+fn cache_details(origin: &Url, url: &Url, credentials: bool) -> CacheRequestDetails {
+    CacheRequestDetails {
+        origin: origin.clone(),
+        destination: url.clone(),
+        credentials: credentials
+    }
+}
+
+/// The [ASCII serialization](http://tools.ietf.org/html/rfc6454#section-6.2) of the origin of `url`
+pub fn serialize_origin(url: &Url) -> String {
+    let mut origin = url.scheme.clone();
+    origin.push_str("://");
+    url.serialize_host().map(|host| origin.push_str(host.as_slice()));
+    url.port().map(|port| {
+        origin.push_str(":");
+        origin.push_str(port.to_string().as_slice());
+    });
+    origin
+}
+
+fn is_http_scheme(url: &Url) -> bool {
+    match url.scheme.as_slice() {
+        "http" | "https" => true,
+        _ => false
+    }
+}
+
+/// http://fetch.spec.whatwg.org/#simple-method
+fn is_simple_method(m: &Method) -> bool {
+    match *m {
+        Get | Head | Post => true,
+        _ => false
+    }
+}
+
+/// http://fetch.spec.whatwg.org/#simple-header
+fn is_simple_header(h: &Header) -> bool {
+    match *h {
+        Accept(_) | AcceptLanguage(_) | ContentLanguage(_) => true,
+        ContentType(MediaType {type_: ref t, subtype: ref s, ..}) => match (t.as_slice(), s.as_slice()) {
+            ("text", "plain") | ("application", "x-www-form-urlencoded") | ("multipart", "form-data") => true,
+            _ => false
+        },
+        _ => false
+    }
+}
+
+fn find_header(headers: &ResponseHeaderCollection, name: &str) -> Option<String> {
+    headers.iter().find(|h| h.header_name().as_slice().eq_ignore_ascii_case(name))
+                  .map(|h| h.header_value())
+}
+
+/// Split a comma-separated header value into its non-empty, trimmed items.
+fn header_list(headers: &ResponseHeaderCollection, name: &str) -> Vec<String> {
+    match find_header(headers, name) {
+        Some(value) => value.as_slice().split(',').map(|item| item.trim())
+                            .filter(|item| !item.is_empty())
+                            .map(|item| item.to_string()).collect(),
+        None => vec!()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Request, Subresource, XMLHttpRequest, CORSMode, serialize_origin};
+    use fetch::response::{Basic, Done};
+    use http::method::Post;
+    use url::Url;
+
+    #[test]
+    fn test_serialize_origin() {
+        let url = Url::parse("http://example.com:8000/a/b?c").unwrap();
+        assert_eq!(serialize_origin(&url), "http://example.com:8000".to_string());
+        let url = Url::parse("https://example.com/").unwrap();
+        assert_eq!(serialize_origin(&url), "https://example.com".to_string());
+    }
+
+    #[test]
+    fn test_fetch_about_blank() {
+        let mut request = Request::new(Url::parse("about:blank").unwrap(), Subresource);
+        let response = request.fetch(false);
+        assert!(response.response_type == Basic);
+        assert_eq!(response.headers.content_type.unwrap().subtype, "html".to_string());
+    }
+
+    #[test]
+    fn test_fetch_data_url() {
+        let mut request = Request::new(Url::parse("data:text/plain,hello").unwrap(), Subresource);
+        let mut response = request.fetch(false);
+        assert!(!response.is_network_error());
+        assert_eq!(response.wait_until_done(), Ok(()));
+        match response.body {
+            Done(ref body) => assert_eq!(body.as_slice(), b"hello"),
+            _ => panic!("expected a complete body")
+        }
+    }
+
+    #[test]
+    fn test_unsupported_schemes_are_network_errors() {
+        for url in ["ftp://example.com/", "blob:abc", "bogus://whatever"].iter() {
+            let mut request = Request::new(Url::parse(*url).unwrap(), Subresource);
+            assert!(request.fetch(false).is_network_error());
+        }
+
+        let mut request = Request::new(Url::parse("data:,hello").unwrap(), Subresource);
+        request.method = Post;
+        assert!(request.fetch(false).is_network_error());
+    }
+
+    #[test]
+    fn test_cross_origin_cors_fetch_of_data_url() {
+        let mut request = Request::new(Url::parse("data:,hello").unwrap(), XMLHttpRequest);
+        request.origin = Some(Url::parse("http://example.com/").unwrap());
+        request.mode = CORSMode;
+        assert!(request.fetch(false).is_network_error());
+    }
 }
@@ -2,12 +2,14 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
+use resource_task::{ProgressMsg, Payload};
+use resource_task::Done as LoadDone;
 use url::Url;
 use http::status::{Status, UnregisteredStatus};
 use http::status::Ok as StatusOk;
 use http::headers::HeaderEnum;
 use http::headers::response::HeaderCollection;
-use std::ascii::OwnedAsciiExt;
+use std::ascii::{AsciiExt, OwnedAsciiExt};
 use std::comm::Receiver;
 
 /// [Response type](http://fetch.spec.whatwg.org/#concept-response-type)
@@ -52,7 +54,6 @@ pub struct ResponseLoader {
 }
 
 /// A [Response](http://fetch.spec.whatwg.org/#concept-response) as defined by the Fetch spec
-#[deriving(Clone)]
 pub struct Response {
     pub response_type: ResponseType,
     pub termination_reason: Option<TerminationReason>,
@@ -60,10 +61,31 @@ pub struct Response {
     pub status: Status,
     pub headers: HeaderCollection,
     pub body: ResponseBody,
+    /// The rest of the body while it is still arriving from the loader. Only the unfiltered
+    /// response holds it; clones and filtered responses never do.
+    pub body_port: Option<Receiver<ProgressMsg>>,
+    /// The loader's description of what went wrong, if this is a network error
+    pub error_reason: Option<String>,
     /// [Internal response](http://fetch.spec.whatwg.org/#concept-internal-response), only used if the Response is a filtered response
     pub internal_response: Option<Box<Response>>,
 }
 
+impl Clone for Response {
+    fn clone(&self) -> Response {
+        Response {
+            response_type: self.response_type.clone(),
+            termination_reason: self.termination_reason.clone(),
+            url: self.url.clone(),
+            status: self.status.clone(),
+            headers: self.headers.clone(),
+            body: self.body.clone(),
+            body_port: None,
+            error_reason: self.error_reason.clone(),
+            internal_response: self.internal_response.clone(),
+        }
+    }
+}
+
 impl Response {
     pub fn new() -> Response {
         Response {
@@ -73,6 +95,8 @@ impl Response {
             status: StatusOk,
             headers: HeaderCollection::new(),
             body: Empty,
+            body_port: None,
+            error_reason: None,
             internal_response: None
         }
     }
@@ -85,10 +109,19 @@ impl Response {
             status: UnregisteredStatus(0, "".to_string()),
             headers: HeaderCollection::new(),
             body: Empty,
+            body_port: None,
+            error_reason: None,
             internal_response: None
         }
     }
 
+    /// A network error that remembers why the underlying load failed.
+    pub fn network_error_with_reason(reason: String) -> Response {
+        let mut response = Response::network_error();
+        response.error_reason = Some(reason);
+        response
+    }
+
     pub fn is_network_error(&self) -> bool {
         match self.response_type {
             Error => true,
@@ -121,13 +154,26 @@ impl Response {
                 response.response_type = filter_type;
             },
             CORS => {
+                // http://fetch.spec.whatwg.org/#concept-filtered-response-cors
+                let mut exposed = vec!();
+                for h in old_headers.iter() {
+                    if h.header_name().as_slice().eq_ignore_ascii_case("access-control-expose-headers") {
+                        for name in h.header_value().as_slice().split(',') {
+                            let name = name.trim();
+                            if !name.is_empty() {
+                                exposed.push(name.to_ascii_lower());
+                            }
+                        }
+                    }
+                }
                 let mut headers = HeaderCollection::new();
                 for h in old_headers.iter() {
-                    match h.header_name().into_ascii_lower().as_slice() {
+                    let name = h.header_name().into_ascii_lower();
+                    match name.as_slice() {
                         "cache-control" | "content-language" |
-                        "content-type" | "expires" | "last-modified" | "Pragma" => {},
-                        // XXXManishearth handle Access-Control-Expose-Headers
-                        _ => headers.insert(h)
+                        "content-type" | "expires" | "last-modified" | "pragma" => headers.insert(h),
+                        _ if exposed.contains(&name) => headers.insert(h),
+                        _ => {}
                     }
                 }
                 response.headers = headers;
@@ -137,8 +183,102 @@ impl Response {
                 response.headers = HeaderCollection::new();
                 response.status = UnregisteredStatus(0, "".to_string());
                 response.body = Empty;
+                response.response_type = filter_type;
             }
         }
         response
     }
+
+    /// Take the port on which the rest of the body arrives, from whichever response in the chain
+    /// of filtered responses holds it.
+    pub fn take_body_port(&mut self) -> Option<Receiver<ProgressMsg>> {
+        match self.body_port.take() {
+            Some(port) => Some(port),
+            None => match self.internal_response {
+                Some(ref mut internal) => internal.take_body_port(),
+                None => None
+            }
+        }
+    }
+
+    /// Block until the whole body has arrived, collecting it into `body`.
+    pub fn wait_until_done(&mut self) -> Result<(), String> {
+        let port = match self.take_body_port() {
+            Some(port) => port,
+            None => return Ok(())
+        };
+        let mut buf = match self.body {
+            Receiving(ref body) | Done(ref body) => body.clone(),
+            Empty => vec!()
+        };
+        loop {
+            match port.recv_opt() {
+                Ok(Payload(data)) => buf.push_all(data.as_slice()),
+                Ok(LoadDone(Ok(()))) => break,
+                Ok(LoadDone(Err(e))) => return Err(e),
+                Err(()) => return Err("loader went away".to_string())
+            }
+        }
+        self.body = Done(buf);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Response, Basic, CORS, Opaque, Done, Empty};
+    use http::headers::HeaderEnum;
+
+    fn response_with_headers(headers: &[(&str, &str)]) -> Response {
+        let mut response = Response::new();
+        for &(name, value) in headers.iter() {
+            let _ = response.headers.insert_raw(name.to_string(), value.as_bytes());
+        }
+        response.body = Done(b"body".to_vec());
+        response
+    }
+
+    fn header_names(response: &Response) -> Vec<String> {
+        let mut names: Vec<String> = response.headers.iter().map(|h| h.header_name()).collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_basic_filter_drops_set_cookie() {
+        let response = response_with_headers(&[("Set-Cookie", "a=b"), ("X-Custom", "1")]);
+        let filtered = response.to_filtered(Basic);
+        assert!(filtered.response_type == Basic);
+        assert_eq!(header_names(&filtered), vec!("X-Custom".to_string()));
+        assert!(filtered.internal_response.is_some());
+    }
+
+    #[test]
+    fn test_cors_filter_keeps_simple_and_exposed_headers() {
+        let response = response_with_headers(&[("Content-Language", "en"),
+                                               ("X-Custom", "1"),
+                                               ("X-Secret", "2"),
+                                               ("Access-Control-Expose-Headers", "x-custom")]);
+        let filtered = response.to_filtered(CORS);
+        assert!(filtered.response_type == CORS);
+        assert_eq!(header_names(&filtered), vec!("Content-Language".to_string(),
+                                                 "X-Custom".to_string()));
+    }
+
+    #[test]
+    fn test_opaque_filter_hides_everything() {
+        let response = response_with_headers(&[("X-Custom", "1")]);
+        let filtered = response.to_filtered(Opaque);
+        assert!(filtered.response_type == Opaque);
+        assert_eq!(filtered.status.code(), 0);
+        assert!(filtered.headers.iter().next().is_none());
+        match filtered.body {
+            Empty => {},
+            _ => panic!("opaque responses have no body")
+        }
+        match filtered.internal_response {
+            Some(ref internal) => assert_eq!(header_names(&**internal), vec!("X-Custom".to_string())),
+            None => panic!("expected an internal response")
+        }
+    }
 }
@@ -0,0 +1,647 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! A private, on-disk HTTP cache, as described by http://tools.ietf.org/html/rfc7234.
+//!
+//! Responses are stored as a pair of files in the cache directory: the decoded body, and a JSON
+//! description of the response headers. The cache runs in its own task; `http_loader` asks it for
+//! stored responses before going to the network and hands it cacheable responses afterwards.
+
+use http::headers::HeaderEnum;
+use http::headers::response::HeaderCollection as ResponseHeaderCollection;
+use serialize::json;
+use std::ascii::AsciiExt;
+use std::collections::HashMap;
+use std::comm::{channel, Receiver, Sender};
+use std::hash::hash;
+use std::io::fs;
+use std::io::fs::PathExtensions;
+use std::io::File;
+use std::cmp;
+use time::{get_time, strptime};
+use url::Url;
+
+use servo_util::task::spawn_named;
+
+/// A list of response headers, as `(name, value)` pairs.
+pub type Headers = Vec<(String, String)>;
+
+/// A response retrieved from the cache.
+#[deriving(Clone, PartialEq, Show)]
+pub struct CachedResponse {
+    pub headers: Headers,
+    pub body: Vec<u8>,
+}
+
+/// The result of looking up a URL in the cache.
+pub enum CacheLookup {
+    /// The stored response can be used without contacting the server.
+    Fresh(CachedResponse),
+    /// The stored response must be revalidated with the server before it can be used.
+    Stale(CachedResponse),
+    /// Nothing usable is stored for this URL.
+    Miss,
+}
+
+/// Messages understood by the HTTP cache task.
+pub enum HttpCacheMsg {
+    /// Look up the response stored for a URL
+    Lookup(Url, Sender<CacheLookup>),
+    /// Store a complete response, with its decoded body
+    Store(Url, Headers, Vec<u8>),
+    /// A stale response was confirmed by a `304 Not Modified` carrying these headers; sends back
+    /// the updated response if it is still stored
+    Revalidated(Url, Headers, Sender<Option<CachedResponse>>),
+    /// Forget any response stored for a URL
+    Invalidate(Url),
+    Exit,
+}
+
+/// Handle to an HTTP cache task
+pub type HttpCacheTask = Sender<HttpCacheMsg>;
+
+/// Create an HTTP cache task storing up to `max_size` bytes of response bodies in `directory`.
+pub fn new_http_cache_task(directory: Path, max_size: uint) -> HttpCacheTask {
+    let (chan, port) = channel();
+    spawn_named("HttpCache", proc() {
+        HttpCacheManager::new(port, HttpCache::new(directory, max_size)).start();
+    });
+    chan
+}
+
+struct HttpCacheManager {
+    port: Receiver<HttpCacheMsg>,
+    cache: HttpCache,
+}
+
+impl HttpCacheManager {
+    fn new(port: Receiver<HttpCacheMsg>, cache: HttpCache) -> HttpCacheManager {
+        HttpCacheManager {
+            port: port,
+            cache: cache,
+        }
+    }
+
+    fn start(&mut self) {
+        loop {
+            match self.port.recv() {
+                Lookup(url, consumer) => {
+                    consumer.send(self.cache.lookup(&url, get_time().sec))
+                }
+                Store(url, headers, body) => {
+                    self.cache.store(&url, headers, body, get_time().sec)
+                }
+                Revalidated(url, headers, consumer) => {
+                    consumer.send(self.cache.revalidated(&url, headers, get_time().sec))
+                }
+                Invalidate(url) => {
+                    self.cache.remove(&url)
+                }
+                Exit => {
+                    break
+                }
+            }
+        }
+    }
+}
+
+/// What is remembered about a stored response, serialized next to its body.
+#[deriving(Clone, Encodable, Decodable)]
+struct CacheEntry {
+    url: String,
+    headers: Headers,
+    /// When the response was received, in seconds since the epoch.
+    response_time: i64,
+    /// When the response was last used, for eviction.
+    last_used: i64,
+    /// The size of the stored body in bytes.
+    size: uint,
+}
+
+pub struct HttpCache {
+    directory: Path,
+    max_size: uint,
+    entries: HashMap<String, CacheEntry>,
+    total_size: uint,
+}
+
+impl HttpCache {
+    /// Open the cache stored in `directory`, creating the directory if needed.
+    pub fn new(directory: Path, max_size: uint) -> HttpCache {
+        let mut cache = HttpCache {
+            directory: directory,
+            max_size: max_size,
+            entries: HashMap::new(),
+            total_size: 0,
+        };
+        cache.load_index();
+        cache
+    }
+
+    fn load_index(&mut self) {
+        if !self.directory.exists() {
+            match fs::mkdir_recursive(&self.directory, ::std::io::USER_RWX) {
+                Ok(()) => {}
+                Err(e) => warn!("http_cache: couldn't create {}: {}", self.directory.display(), e),
+            }
+            return;
+        }
+
+        let paths = match fs::readdir(&self.directory) {
+            Ok(paths) => paths,
+            Err(e) => {
+                warn!("http_cache: couldn't read {}: {}", self.directory.display(), e);
+                return;
+            }
+        };
+        for path in paths.iter().filter(|path| path.extension_str() == Some("json")) {
+            let entry = File::open(path).read_to_string().ok().and_then(|contents| {
+                json::decode::<CacheEntry>(contents.as_slice()).ok()
+            });
+            match entry {
+                Some(entry) => {
+                    self.total_size += entry.size;
+                    self.entries.insert(entry.url.clone(), entry);
+                }
+                None => {
+                    debug!("http_cache: discarding unreadable entry {}", path.display());
+                    let _ = fs::unlink(path);
+                    let _ = fs::unlink(&path.with_extension(""));
+                }
+            }
+        }
+        self.evict();
+    }
+
+    fn entry_path(&self, key: &String) -> Path {
+        self.directory.join(format!("{:016x}", hash(key)))
+    }
+
+    fn read_body(&self, key: &String) -> Option<Vec<u8>> {
+        File::open(&self.entry_path(key)).read_to_end().ok()
+    }
+
+    /// Look up the response stored for `url`, as of `now` (in seconds since the epoch).
+    pub fn lookup(&mut self, url: &Url, now: i64) -> CacheLookup {
+        let key = url.serialize();
+        let (headers, response_time) = match self.entries.get(&key) {
+            Some(entry) => (entry.headers.clone(), entry.response_time),
+            None => return Miss,
+        };
+
+        let body = match self.read_body(&key) {
+            Some(body) => body,
+            None => {
+                self.remove(url);
+                return Miss;
+            }
+        };
+        self.entries.get_mut(&key).map(|entry| entry.last_used = now);
+
+        let response = CachedResponse {
+            headers: headers,
+            body: body,
+        };
+        if is_fresh(response.headers.as_slice(), response_time, now) {
+            Fresh(response)
+        } else {
+            Stale(response)
+        }
+    }
+
+    /// Store a response for `url` if its headers allow it.
+    pub fn store(&mut self, url: &Url, headers: Headers, body: Vec<u8>, now: i64) {
+        self.remove(url);
+        if !is_storable(headers.as_slice()) || body.len() > self.max_size {
+            return;
+        }
+
+        let key = url.serialize();
+        let entry = CacheEntry {
+            url: key.clone(),
+            headers: headers.into_iter().filter(|&(ref name, _)| {
+                !is_unstored_header(name.as_slice())
+            }).collect(),
+            response_time: now,
+            last_used: now,
+            size: body.len(),
+        };
+        if !self.write_entry(&entry) {
+            return;
+        }
+        match File::create(&self.entry_path(&key)).write(body.as_slice()) {
+            Ok(()) => {}
+            Err(e) => {
+                warn!("http_cache: couldn't store {:s}: {}", key, e);
+                self.remove(url);
+                return;
+            }
+        }
+
+        self.total_size += entry.size;
+        self.entries.insert(key, entry);
+        self.evict();
+    }
+
+    fn write_entry(&self, entry: &CacheEntry) -> bool {
+        let path = self.entry_path(&entry.url).with_extension("json");
+        match File::create(&path).write_str(json::encode(entry).as_slice()) {
+            Ok(()) => true,
+            Err(e) => {
+                warn!("http_cache: couldn't store {:s}: {}", entry.url, e);
+                false
+            }
+        }
+    }
+
+    /// Update the stored response for `url` with the headers of a `304 Not Modified` response.
+    /// http://tools.ietf.org/html/rfc7234#section-4.3.4
+    pub fn revalidated(&mut self, url: &Url, new_headers: Headers, now: i64)
+                       -> Option<CachedResponse> {
+        let key = url.serialize();
+        let body = match self.read_body(&key) {
+            Some(body) => body,
+            None => {
+                self.remove(url);
+                return None;
+            }
+        };
+
+        let entry = match self.entries.get_mut(&key) {
+            Some(entry) => {
+                for &(ref name, ref value) in new_headers.iter() {
+                    set_header(&mut entry.headers, name.as_slice(), value.clone());
+                }
+                entry.response_time = now;
+                entry.last_used = now;
+                entry.clone()
+            }
+            None => return None,
+        };
+        self.write_entry(&entry);
+
+        Some(CachedResponse {
+            headers: entry.headers,
+            body: body,
+        })
+    }
+
+    /// Forget the response stored for `url`.
+    pub fn remove(&mut self, url: &Url) {
+        let key = url.serialize();
+        self.remove_key(&key);
+    }
+
+    fn remove_key(&mut self, key: &String) {
+        match self.entries.remove(key) {
+            Some(entry) => {
+                self.total_size -= entry.size;
+                let path = self.entry_path(key);
+                let _ = fs::unlink(&path);
+                let _ = fs::unlink(&path.with_extension("json"));
+            }
+            None => {}
+        }
+    }
+
+    /// Evict the least recently used responses until the cache fits in its size limit.
+    fn evict(&mut self) {
+        while self.total_size > self.max_size {
+            let oldest = self.entries.values().min_by(|entry| entry.last_used).map(|entry| {
+                entry.url.clone()
+            });
+            match oldest {
+                Some(key) => {
+                    debug!("http_cache: evicting {:s}", key);
+                    self.remove_key(&key);
+                }
+                None => break,
+            }
+        }
+    }
+}
+
+/// Flatten a header collection into `(name, value)` pairs.
+pub fn headers_from_collection(collection: &ResponseHeaderCollection) -> Headers {
+    collection.iter().map(|header| (header.header_name(), header.header_value())).collect()
+}
+
+/// Rebuild a header collection from `(name, value)` pairs.
+pub fn collection_from_headers(headers: &[(String, String)]) -> ResponseHeaderCollection {
+    let mut collection = ResponseHeaderCollection::new();
+    for &(ref name, ref value) in headers.iter() {
+        let _ = collection.insert_raw(name.clone(), value.as_bytes());
+    }
+    collection
+}
+
+fn get_header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    headers.iter().find(|&&(ref header_name, _)| {
+        header_name.as_slice().eq_ignore_ascii_case(name)
+    }).map(|&(_, ref value)| value.as_slice())
+}
+
+/// Whether a header shouldn't be kept with a stored response. The stored body is already
+/// decoded, so the original framing headers don't apply to it, and cookies are only ever set by
+/// the response that carried them.
+fn is_unstored_header(name: &str) -> bool {
+    match name.to_ascii_lower().as_slice() {
+        "content-length" | "content-encoding" | "transfer-encoding" | "set-cookie" => true,
+        _ => false,
+    }
+}
+
+fn set_header(headers: &mut Headers, name: &str, value: String) {
+    if is_unstored_header(name) {
+        return;
+    }
+    match headers.iter_mut().find(|&&(ref header_name, _)| {
+        header_name.as_slice().eq_ignore_ascii_case(name)
+    }) {
+        Some(&(_, ref mut old_value)) => {
+            *old_value = value;
+            return;
+        }
+        None => {}
+    }
+    headers.push((name.to_string(), value));
+}
+
+/// The directives of a `Cache-Control` header that we care about.
+/// http://tools.ietf.org/html/rfc7234#section-5.2
+struct CacheControl {
+    no_store: bool,
+    no_cache: bool,
+    max_age: Option<i64>,
+}
+
+fn parse_cache_control(headers: &[(String, String)]) -> CacheControl {
+    let mut cache_control = CacheControl {
+        no_store: false,
+        no_cache: false,
+        max_age: None,
+    };
+    let value = match get_header(headers, "cache-control") {
+        Some(value) => value,
+        None => return cache_control,
+    };
+    for directive in value.split(',') {
+        let (name, argument) = match directive.find('=') {
+            Some(index) => (directive.slice_to(index).trim(),
+                            Some(directive.slice_from(index + 1).trim().trim_chars('"'))),
+            None => (directive.trim(), None),
+        };
+        match name.to_ascii_lower().as_slice() {
+            "no-store" => cache_control.no_store = true,
+            "no-cache" | "must-revalidate" => cache_control.no_cache = true,
+            "max-age" => cache_control.max_age = argument.and_then(|arg| from_str(arg)),
+            _ => {}
+        }
+    }
+    cache_control
+}
+
+/// Parse an HTTP-date in any of the formats allowed by
+/// http://tools.ietf.org/html/rfc7231#section-7.1.1.1, as seconds since the epoch.
+pub fn parse_http_date(value: &str) -> Option<i64> {
+    static FORMATS: [&'static str, ..3] = [
+        "%a, %d %b %Y %H:%M:%S GMT",  // IMF-fixdate
+        "%A, %d-%b-%y %H:%M:%S GMT",  // RFC 850
+        "%a %b %d %H:%M:%S %Y",       // asctime
+    ];
+    FORMATS.iter().filter_map(|format| strptime(value.trim(), *format).ok()).next().map(|tm| {
+        tm.to_timespec().sec
+    })
+}
+
+/// Whether a response with these headers may be stored at all.
+/// http://tools.ietf.org/html/rfc7234#section-3
+fn is_storable(headers: &[(String, String)]) -> bool {
+    if parse_cache_control(headers).no_store {
+        return false;
+    }
+    // We don't keep track of request headers, so responses that vary on them can't be reused.
+    match get_header(headers, "vary") {
+        Some(value) if !value.trim().is_empty() => return false,
+        _ => {}
+    }
+    true
+}
+
+/// How long a response stays fresh after it was generated, in seconds.
+/// http://tools.ietf.org/html/rfc7234#section-4.2.1
+fn freshness_lifetime(headers: &[(String, String)]) -> i64 {
+    let cache_control = parse_cache_control(headers);
+    if cache_control.no_cache {
+        return 0;
+    }
+    match cache_control.max_age {
+        Some(max_age) => return max_age,
+        None => {}
+    }
+
+    let date = get_header(headers, "date").and_then(parse_http_date);
+    match get_header(headers, "expires") {
+        // An invalid Expires value means the response is already expired.
+        Some(expires) => {
+            return match (parse_http_date(expires), date) {
+                (Some(expires), Some(date)) => expires - date,
+                _ => 0,
+            }
+        }
+        None => {}
+    }
+
+    // Heuristic freshness: 10% of the time since the resource was last modified.
+    // http://tools.ietf.org/html/rfc7234#section-4.2.2
+    match (get_header(headers, "last-modified").and_then(parse_http_date), date) {
+        (Some(last_modified), Some(date)) if date > last_modified => (date - last_modified) / 10,
+        _ => 0,
+    }
+}
+
+/// The age of a stored response at time `now`.
+/// http://tools.ietf.org/html/rfc7234#section-4.2.3
+fn current_age(headers: &[(String, String)], response_time: i64, now: i64) -> i64 {
+    let age_value = get_header(headers, "age").and_then(|age| from_str::<i64>(age.trim())).unwrap_or(0);
+    let apparent_age = match get_header(headers, "date").and_then(parse_http_date) {
+        Some(date) => cmp::max(0, response_time - date),
+        None => 0,
+    };
+    cmp::max(apparent_age, age_value) + (now - response_time)
+}
+
+/// Whether a response received at `response_time` can still be used without revalidation at
+/// time `now`. http://tools.ietf.org/html/rfc7234#section-4.2
+fn is_fresh(headers: &[(String, String)], response_time: i64, now: i64) -> bool {
+    freshness_lifetime(headers) > current_age(headers, response_time, now)
+}
+
+/// The conditional request headers needed to revalidate a stored response.
+/// http://tools.ietf.org/html/rfc7232#section-3
+pub fn validators(headers: &[(String, String)]) -> Headers {
+    let mut validators = vec!();
+    match get_header(headers, "etag") {
+        Some(etag) => validators.push(("If-None-Match".to_string(), etag.to_string())),
+        None => {}
+    }
+    match get_header(headers, "last-modified") {
+        Some(last_modified) => {
+            validators.push(("If-Modified-Since".to_string(), last_modified.to_string()))
+        }
+        None => {}
+    }
+    validators
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HttpCache, CachedResponse, Fresh, Stale, Miss, Headers};
+    use super::{is_fresh, is_storable, freshness_lifetime, parse_http_date, validators};
+    use std::io::TempDir;
+    use url::Url;
+
+    // Sun, 06 Nov 1994 08:49:37 GMT
+    static DATE: i64 = 784111777;
+
+    fn headers(pairs: &[(&str, &str)]) -> Headers {
+        pairs.iter().map(|&(name, value)| (name.to_string(), value.to_string())).collect()
+    }
+
+    #[test]
+    fn test_parse_http_date() {
+        assert_eq!(parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT"), Some(DATE));
+        assert_eq!(parse_http_date(" Sun, 06 Nov 1994 08:49:37 GMT "), Some(DATE));
+        assert_eq!(parse_http_date("yesterday"), None);
+    }
+
+    #[test]
+    fn test_freshness_lifetime() {
+        let cases = [
+            (headers(&[]), 0),
+            (headers(&[("Cache-Control", "max-age=60")]), 60),
+            (headers(&[("Cache-Control", "public, max-age=\"60\"")]), 60),
+            (headers(&[("Cache-Control", "max-age=60, no-cache")]), 0),
+            (headers(&[("Cache-Control", "must-revalidate, max-age=60")]), 0),
+            (headers(&[("Date", "Sun, 06 Nov 1994 08:49:37 GMT"),
+                      ("Expires", "Sun, 06 Nov 1994 09:49:37 GMT")]), 3600),
+            (headers(&[("Date", "Sun, 06 Nov 1994 08:49:37 GMT"),
+                      ("Expires", "0")]), 0),
+            (headers(&[("Cache-Control", "max-age=10"),
+                      ("Date", "Sun, 06 Nov 1994 08:49:37 GMT"),
+                      ("Expires", "Sun, 06 Nov 1994 09:49:37 GMT")]), 10),
+            (headers(&[("Date", "Sun, 06 Nov 1994 08:49:37 GMT"),
+                      ("Last-Modified", "Sun, 06 Nov 1994 06:49:37 GMT")]), 720),
+        ];
+        for &(ref headers, lifetime) in cases.iter() {
+            assert_eq!(freshness_lifetime(headers.as_slice()), lifetime);
+        }
+    }
+
+    #[test]
+    fn test_is_fresh() {
+        let h = headers(&[("Cache-Control", "max-age=60")]);
+        assert!(is_fresh(h.as_slice(), DATE, DATE));
+        assert!(is_fresh(h.as_slice(), DATE, DATE + 59));
+        assert!(!is_fresh(h.as_slice(), DATE, DATE + 60));
+
+        // The Age header counts against the lifetime.
+        let h = headers(&[("Cache-Control", "max-age=60"), ("Age", "30")]);
+        assert!(!is_fresh(h.as_slice(), DATE, DATE + 30));
+    }
+
+    #[test]
+    fn test_is_storable() {
+        assert!(is_storable(headers(&[("Cache-Control", "max-age=60")]).as_slice()));
+        assert!(!is_storable(headers(&[("Cache-Control", "no-store")]).as_slice()));
+        assert!(!is_storable(headers(&[("Vary", "Accept-Encoding")]).as_slice()));
+    }
+
+    #[test]
+    fn test_validators() {
+        let h = headers(&[("ETag", "\"abc\""), ("Last-Modified", "Sun, 06 Nov 1994 08:49:37 GMT")]);
+        assert_eq!(validators(h.as_slice()),
+                   headers(&[("If-None-Match", "\"abc\""),
+                            ("If-Modified-Since", "Sun, 06 Nov 1994 08:49:37 GMT")]));
+    }
+
+    #[test]
+    fn test_store_lookup_revalidate() {
+        let dir = TempDir::new("http_cache").unwrap();
+        let url = Url::parse("http://example.com/style.css").unwrap();
+        let mut cache = HttpCache::new(dir.path().clone(), 1024);
+
+        match cache.lookup(&url, DATE) {
+            Miss => {}
+            _ => panic!("empty cache should miss"),
+        }
+
+        let h = headers(&[("Cache-Control", "max-age=60"), ("ETag", "\"v1\"")]);
+        let mut received = h.clone();
+        received.push(("Content-Encoding".to_string(), "gzip".to_string()));
+        received.push(("Set-Cookie".to_string(), "a=b".to_string()));
+        cache.store(&url, received, b"body".to_vec(), DATE);
+        match cache.lookup(&url, DATE + 10) {
+            Fresh(response) => assert_eq!(response, CachedResponse { headers: h.clone(), body: b"body".to_vec() }),
+            _ => panic!("expected a fresh response"),
+        }
+        match cache.lookup(&url, DATE + 100) {
+            Stale(_) => {}
+            _ => panic!("expected a stale response"),
+        }
+
+        let response = cache.revalidated(&url, headers(&[("Cache-Control", "max-age=120")]), DATE + 100);
+        assert_eq!(response.unwrap().body, b"body".to_vec());
+        match cache.lookup(&url, DATE + 200) {
+            Fresh(response) => assert_eq!(response.headers,
+                                          headers(&[("Cache-Control", "max-age=120"), ("ETag", "\"v1\"")])),
+            _ => panic!("expected a fresh response after revalidation"),
+        }
+
+        // The index survives reopening the cache.
+        let mut cache = HttpCache::new(dir.path().clone(), 1024);
+        match cache.lookup(&url, DATE + 200) {
+            Fresh(_) => {}
+            _ => panic!("expected the response to be persisted"),
+        }
+
+        cache.remove(&url);
+        match cache.lookup(&url, DATE + 200) {
+            Miss => {}
+            _ => panic!("expected the response to be removed"),
+        }
+    }
+
+    #[test]
+    fn test_eviction() {
+        let dir = TempDir::new("http_cache").unwrap();
+        let mut cache = HttpCache::new(dir.path().clone(), 10);
+        let h = headers(&[("Cache-Control", "max-age=60")]);
+        let a = Url::parse("http://example.com/a").unwrap();
+        let b = Url::parse("http://example.com/b").unwrap();
+        let c = Url::parse("http://example.com/c").unwrap();
+
+        cache.store(&a, h.clone(), b"aaaa".to_vec(), DATE);
+        cache.store(&b, h.clone(), b"bbbb".to_vec(), DATE + 1);
+        // Using `a` makes `b` the least recently used entry.
+        let _ = cache.lookup(&a, DATE + 2);
+        cache.store(&c, h.clone(), b"cccc".to_vec(), DATE + 3);
+
+        match cache.lookup(&b, DATE + 4) {
+            Miss => {}
+            _ => panic!("expected b to be evicted"),
+        }
+        match (cache.lookup(&a, DATE + 4), cache.lookup(&c, DATE + 4)) {
+            (Fresh(_), Fresh(_)) => {}
+            _ => panic!("expected a and c to be kept"),
+        }
+
+        // Bodies larger than the whole cache are never stored.
+        cache.store(&a, h.clone(), b"0123456789abcdef".to_vec(), DATE + 5);
+        match cache.lookup(&a, DATE + 6) {
+            Miss => {}
+            _ => panic!("expected an oversized body not to be stored"),
+        }
+    }
+}
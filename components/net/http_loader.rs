@@ -2,18 +2,29 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use resource_task::{Metadata, Payload, Done, TargetedLoadResponse, LoadData, start_sending_opt, ResponseSenders};
+use http_cache::{HttpCacheTask, CachedResponse, Lookup, Store, Revalidated, Invalidate};
+use http_cache::{Fresh, Stale, Miss, headers_from_collection, collection_from_headers, validators};
+use resource_task::{Metadata, Payload, Done, ProgressMsg, TargetedLoadResponse, LoadData, start_sending_opt, ResponseSenders};
 
+use flate2::reader::{DeflateDecoder, GzDecoder, ZlibDecoder};
 use log;
-use std::collections::HashSet;
+use std::ascii::AsciiExt;
+use std::cell::Cell;
+use std::cmp::min;
+use std::comm::channel;
+use std::slice::bytes::copy_memory;
 use http::client::{RequestWriter, NetworkStream};
 use http::headers::HeaderEnum;
-use std::io::Reader;
+use http::method::Get;
+use std::io::{IoResult, Reader, EndOfFile};
 use servo_util::task::spawn_named;
 use url::Url;
 
-pub fn factory(load_data: LoadData, start_chan: Sender<TargetedLoadResponse>) {
-    spawn_named("http_loader", proc() load(load_data, start_chan))
+pub fn factory(http_cache: Option<HttpCacheTask>)
+               -> proc(LoadData, Sender<TargetedLoadResponse>): Send {
+    proc(load_data: LoadData, start_chan: Sender<TargetedLoadResponse>) {
+        spawn_named("http_loader", proc() load(load_data, start_chan, http_cache))
+    }
 }
 
 fn send_error(url: Url, err: String, senders: ResponseSenders) {
@@ -26,149 +37,556 @@ fn send_error(url: Url, err: String, senders: ResponseSenders) {
     };
 }
 
-fn load(load_data: LoadData, start_chan: Sender<TargetedLoadResponse>) {
-    // FIXME: At the time of writing this FIXME, servo didn't have any central
-    //        location for configuration. If you're reading this and such a
-    //        repository DOES exist, please update this constant to use it.
-    let max_redirects = 50u;
-    let mut iters = 0u;
-    let mut url = load_data.url.clone();
-    let mut redirected_to = HashSet::new();
+/// Answer a load from a response stored in the HTTP cache.
+fn send_cached_response(url: Url, response: CachedResponse, senders: ResponseSenders) {
+    info!("using cached response for {:s}", url.serialize());
+    let headers = collection_from_headers(response.headers.as_slice());
+    let mut metadata = Metadata::default(url);
+    metadata.set_content_type(&headers.content_type);
+    metadata.headers = Some(headers);
+
+    match start_sending_opt(senders, metadata) {
+        Ok(progress_chan) => {
+            let _ = progress_chan.send_opt(Payload(response.body));
+            let _ = progress_chan.send_opt(Done(Ok(())));
+        }
+        Err(_) => {}
+    }
+}
+
+/// The `Content-Encoding` of a response body. http://tools.ietf.org/html/rfc2616#section-3.5
+#[deriving(PartialEq, Show)]
+enum ContentEncoding {
+    Identity,
+    Gzip,
+    Deflate,
+}
+
+impl ContentEncoding {
+    fn from_header_value(value: &str) -> Result<ContentEncoding, String> {
+        match value.trim().to_ascii_lower().as_slice() {
+            "" | "identity" => Ok(Identity),
+            "gzip" | "x-gzip" => Ok(Gzip),
+            "deflate" => Ok(Deflate),
+            other => Err(format!("unsupported content encoding: {:s}", other)),
+        }
+    }
+
+    /// Parses a `Content-Encoding` header value into the codings that were applied to the body,
+    /// in the order in which they were applied, leaving out `identity`.
+    fn list_from_header_value(value: &str) -> Result<Vec<ContentEncoding>, String> {
+        let mut encodings = vec!();
+        for coding in value.split(',') {
+            match try!(ContentEncoding::from_header_value(coding)) {
+                Identity => {}
+                encoding => encodings.push(encoding),
+            }
+        }
+        Ok(encodings)
+    }
+}
+
+/// A reader that keeps track of how many bytes have been read from the network, so that truncated
+/// bodies can be detected even when they are being decompressed.
+struct CountingReader<'a, R: 'a> {
+    inner: &'a mut R,
+    count: &'a Cell<uint>,
+}
+
+impl<'a, R: Reader> Reader for CountingReader<'a, R> {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<uint> {
+        let len = try!(self.inner.read(buf));
+        self.count.set(self.count.get() + len);
+        Ok(len)
+    }
+}
+
+/// A reader that can look at the start of a stream before deciding how to decode it.
+struct PeekReader<R> {
+    inner: R,
+    peeked: Vec<u8>,
+    position: uint,
+}
+
+impl<R: Reader> PeekReader<R> {
+    fn new(inner: R) -> PeekReader<R> {
+        PeekReader {
+            inner: inner,
+            peeked: vec!(),
+            position: 0,
+        }
+    }
+
+    /// Returns the first `length` bytes of the stream without consuming them, or fewer if the
+    /// stream is shorter than that.
+    fn peek(&mut self, length: uint) -> Result<&[u8], String> {
+        while self.peeked.len() < length {
+            match self.inner.push(length - self.peeked.len(), &mut self.peeked) {
+                Ok(_) => {}
+                Err(ref e) if e.kind == EndOfFile => break,
+                Err(e) => return Err(e.desc.to_string()),
+            }
+        }
+        Ok(self.peeked.as_slice())
+    }
+}
+
+impl<R: Reader> Reader for PeekReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<uint> {
+        if self.position < self.peeked.len() {
+            let len = min(buf.len(), self.peeked.len() - self.position);
+            copy_memory(buf, self.peeked.slice(self.position, self.position + len));
+            self.position += len;
+            return Ok(len);
+        }
+        self.inner.read(buf)
+    }
+}
+
+/// Whether a stream starts with a valid zlib header. http://tools.ietf.org/html/rfc1950
+fn is_zlib_header(header: &[u8]) -> bool {
+    header.len() == 2 && header[0] & 0x0f == 8 &&
+        ((header[0] as uint) << 8 | header[1] as uint) % 31 == 0
+}
+
+/// Wraps `reader` in a decoder that undoes `encoding`.
+fn decoder_for<'a>(encoding: ContentEncoding, reader: Box<Reader + 'a>)
+                   -> Result<Box<Reader + 'a>, String> {
+    match encoding {
+        Identity => Ok(reader),
+        Gzip => match GzDecoder::new(reader) {
+            Ok(decoder) => Ok(box decoder as Box<Reader + 'a>),
+            Err(e) => Err(format!("invalid gzip stream: {:s}", e.desc)),
+        },
+        Deflate => {
+            // `deflate` is meant to be zlib-wrapped, but many servers send raw deflate data.
+            let mut reader = PeekReader::new(reader);
+            let is_zlib = is_zlib_header(try!(reader.peek(2)));
+            if is_zlib {
+                Ok(box ZlibDecoder::new(reader) as Box<Reader + 'a>)
+            } else {
+                Ok(box DeflateDecoder::new(reader) as Box<Reader + 'a>)
+            }
+        }
+    }
+}
+
+/// Decode a response body that had `encodings` applied to it, in that order, sending it on to
+/// the consumer as `Payload` chunks as it arrives. Corrupt or truncated compressed data results
+/// in an error. If `body` is `Some`, the decoded data is also collected into it.
+fn decode_and_send<'a, R: Reader + 'a>(encodings: &[ContentEncoding], reader: R,
+                                       progress_chan: &Sender<ProgressMsg>,
+                                       body: &mut Option<Vec<u8>>) -> Result<(), String> {
+    // Responses without a body, such as those to HEAD requests or with a 204 or 304 status, may
+    // still have a `Content-Encoding`.
+    let mut reader = PeekReader::new(reader);
+    if try!(reader.peek(1)).is_empty() {
+        return Ok(());
+    }
+
+    let mut decoded = box reader as Box<Reader + 'a>;
+    for &encoding in encodings.iter().rev() {
+        decoded = try!(decoder_for(encoding, decoded));
+    }
+    send_data(decoded, progress_chan, body)
+}
+
+fn send_data<R: Reader>(mut reader: R, progress_chan: &Sender<ProgressMsg>,
+                        body: &mut Option<Vec<u8>>) -> Result<(), String> {
+    loop {
+        let mut buf = Vec::with_capacity(1024);
+
+        unsafe { buf.set_len(1024); }
+        match reader.read(buf.as_mut_slice()) {
+            Ok(len) => {
+                unsafe { buf.set_len(len); }
+                match *body {
+                    Some(ref mut body) => body.push_all(buf.as_slice()),
+                    None => {}
+                }
+                if progress_chan.send_opt(Payload(buf)).is_err() {
+                    // The send errors when the receiver is out of scope,
+                    // which will happen if the fetch has timed out (or has been aborted)
+                    // so we don't need to continue with the loading of the file here.
+                    return Ok(());
+                }
+            }
+            Err(ref e) if e.kind == EndOfFile => return Ok(()),
+            Err(e) => return Err(e.desc.to_string()),
+        }
+    }
+}
+
+/// Make a single HTTP request for `load_data`, answering it from the HTTP cache where possible.
+/// Redirects, cookies and CORS are handled by the fetch code that drives this loader.
+fn load(load_data: LoadData, start_chan: Sender<TargetedLoadResponse>,
+        http_cache: Option<HttpCacheTask>) {
+    let url = load_data.url.clone();
 
     let senders = ResponseSenders {
         immediate_consumer: start_chan,
         eventual_consumer: load_data.consumer
     };
 
-    // Loop to handle redirects.
-    loop {
-        iters = iters + 1;
-
-        if iters > max_redirects {
-            send_error(url, "too many redirects".to_string(), senders);
+    match url.scheme.as_slice() {
+        "http" | "https" => {}
+        _ => {
+            let s = format!("{:s} request, but we don't support that scheme", url.scheme);
+            send_error(url, s, senders);
             return;
         }
+    }
 
-        if redirected_to.contains(&url) {
-            send_error(url, "redirect loop".to_string(), senders);
+    // http://tools.ietf.org/html/rfc7234#section-4
+    let cached = match (&http_cache, &load_data.method) {
+        (&Some(ref http_cache), &Get) => {
+            let (cache_chan, cache_port) = channel();
+            http_cache.send(Lookup(url.clone(), cache_chan));
+            cache_port.recv()
+        }
+        (&Some(ref http_cache), _) => {
+            // Unsafe methods invalidate the stored response.
+            // http://tools.ietf.org/html/rfc7234#section-4.4
+            http_cache.send(Invalidate(url.clone()));
+            Miss
+        }
+        (&None, _) => Miss,
+    };
+    let stale = match cached {
+        Fresh(response) => {
+            send_cached_response(url, response, senders);
             return;
         }
+        Stale(response) => Some(response),
+        Miss => None,
+    };
 
-        redirected_to.insert(url.clone());
+    info!("requesting {:s}", url.serialize());
 
-        match url.scheme.as_slice() {
-            "http" | "https" => {}
-            _ => {
-                let s = format!("{:s} request, but we don't support that scheme", url.scheme);
-                send_error(url, s, senders);
-                return;
-            }
+    let request = RequestWriter::<NetworkStream>::new(load_data.method.clone(), url.clone());
+    let mut writer = match request {
+        Ok(w) => box w,
+        Err(e) => {
+            send_error(url, e.desc.to_string(), senders);
+            return;
         }
+    };
 
-        info!("requesting {:s}", url.serialize());
+    // Preserve the `host` header set automatically by RequestWriter.
+    let host = writer.headers.host.clone();
+    writer.headers = load_data.headers.clone();
+    writer.headers.host = host;
 
-        let request = RequestWriter::<NetworkStream>::new(load_data.method.clone(), url.clone());
-        let mut writer = match request {
-            Ok(w) => box w,
-            Err(e) => {
-                send_error(url, e.desc.to_string(), senders);
-                return;
+    // Ask the server whether our stored response is still good.
+    match stale {
+        Some(ref response) => {
+            for (name, value) in validators(response.headers.as_slice()).into_iter() {
+                let _ = writer.headers.insert_raw(name, value.as_bytes());
             }
-        };
-
-        // Preserve the `host` header set automatically by RequestWriter.
-        let host = writer.headers.host.clone();
-        writer.headers = load_data.headers.clone();
-        writer.headers.host = host;
-        if writer.headers.accept_encoding.is_none() {
-            // We currently don't support HTTP Compression (FIXME #2587)
-            writer.headers.accept_encoding = Some(String::from_str("identity".as_slice()))
-        }
-        match load_data.data {
-            Some(ref data) => {
-                writer.headers.content_length = Some(data.len());
-                match writer.write(data.as_slice()) {
-                    Err(e) => {
-                        send_error(url, e.desc.to_string(), senders);
-                        return;
-                    }
-                    _ => {}
+        }
+        None => {}
+    }
+
+    if writer.headers.accept_encoding.is_none() {
+        writer.headers.accept_encoding = Some(String::from_str("gzip, deflate"))
+    }
+    match load_data.data {
+        Some(ref data) => {
+            writer.headers.content_length = Some(data.len());
+            match writer.write(data.as_slice()) {
+                Err(e) => {
+                    send_error(url, e.desc.to_string(), senders);
+                    return;
                 }
-            },
-            _ => {}
-        }
-        let mut response = match writer.read_response() {
-            Ok(r) => r,
-            Err((_, e)) => {
-                send_error(url, e.desc.to_string(), senders);
-                return;
+                _ => {}
             }
-        };
+        },
+        _ => {}
+    }
+    let mut response = match writer.read_response() {
+        Ok(r) => r,
+        Err((_, e)) => {
+            send_error(url, e.desc.to_string(), senders);
+            return;
+        }
+    };
 
-        // Dump headers, but only do the iteration if info!() is enabled.
-        info!("got HTTP response {:s}, headers:", response.status.to_string());
-        if log_enabled!(log::INFO) {
-            for header in response.headers.iter() {
-                info!(" - {:s}: {:s}", header.header_name(), header.header_value());
-            }
+    // Dump headers, but only do the iteration if info!() is enabled.
+    info!("got HTTP response {:s}, headers:", response.status.to_string());
+    if log_enabled!(log::INFO) {
+        for header in response.headers.iter() {
+            info!(" - {:s}: {:s}", header.header_name(), header.header_value());
         }
+    }
 
-        if 3 == (response.status.code() / 100) {
-            match response.headers.location {
-                Some(new_url) => {
-                    // CORS (http://fetch.spec.whatwg.org/#http-fetch, status section, point 9, 10)
-                    match load_data.cors {
-                        Some(ref c) => {
-                            if c.preflight {
-                                // The preflight lied
-                                send_error(url, "Preflight fetch inconsistent with main fetch".to_string(), senders);
-                                return;
-                            } else {
-                                // XXXManishearth There are some CORS-related steps here,
-                                // but they don't seem necessary until credentials are implemented
-                            }
-                        }
-                        _ => {}
-                    }
-                    info!("redirecting to {:s}", new_url.serialize());
-                    url = new_url;
-                    continue;
-                }
-                None => ()
-            }
+    if response.status.code() == 304 && stale.is_some() {
+        let http_cache = http_cache.as_ref().unwrap();
+        let (cache_chan, cache_port) = channel();
+        http_cache.send(Revalidated(url.clone(), headers_from_collection(&response.headers),
+                                    cache_chan));
+        match cache_port.recv() {
+            Some(response) => send_cached_response(url, response, senders),
+            None => send_error(url, "cached response went away during revalidation".to_string(),
+                               senders),
         }
+        return;
+    }
+
+    let mut metadata = Metadata::default(url.clone());
+    metadata.set_content_type(&response.headers.content_type);
+    metadata.headers = Some(response.headers.clone());
+    metadata.status = Some(response.status.clone());
 
-        let mut metadata = Metadata::default(url);
-        metadata.set_content_type(&response.headers.content_type);
-        metadata.headers = Some(response.headers.clone());
-        metadata.status = Some(response.status.clone());
+    let encodings = match response.headers.iter().find(|header| {
+        header.header_name().as_slice().eq_ignore_ascii_case("content-encoding")
+    }) {
+        Some(header) => {
+            ContentEncoding::list_from_header_value(header.header_value().as_slice())
+        }
+        None => Ok(vec!()),
+    };
+    let content_length = response.headers.content_length;
+
+    // Only complete responses to GET requests are stored.
+    let cacheable = http_cache.is_some() && load_data.method == Get &&
+                    response.status.code() == 200;
+    let mut body = if cacheable { Some(vec!()) } else { None };
+    let stored_headers = headers_from_collection(&response.headers);
+
+    let progress_chan = match start_sending_opt(senders, metadata) {
+        Ok(p) => p,
+        _ => return
+    };
+
+    let encodings = match encodings {
+        Ok(encodings) => encodings,
+        Err(e) => {
+            let _ = progress_chan.send_opt(Done(Err(e)));
+            return;
+        }
+    };
 
-        let progress_chan = match start_sending_opt(senders, metadata) {
-            Ok(p) => p,
-            _ => return
-        };
+    let received = Cell::new(0u);
+    let result = {
+        let reader = CountingReader { inner: &mut response, count: &received };
+        decode_and_send(encodings.as_slice(), reader, &progress_chan, &mut body)
+    };
+    let result = match (result, content_length) {
+        (Ok(()), Some(length)) if received.get() < length => {
+            Err(format!("truncated response: expected {} bytes, got {}", length, received.get()))
+        }
+        (result, _) => result,
+    };
+    match (&result, body, &http_cache) {
+        (&Ok(()), Some(body), &Some(ref http_cache)) => {
+            http_cache.send(Store(url.clone(), stored_headers, body));
+        }
+        _ => {}
+    }
+    let _ = progress_chan.send_opt(Done(result));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ContentEncoding, Identity, Gzip, Deflate, decode_and_send};
+    use super::{is_zlib_header, load};
+    use http_cache::{HttpCacheTask, Exit, new_http_cache_task};
+    use resource_task::{LoadData, Payload, Done};
+    use std::comm::{channel, Receiver};
+    use std::io::{Acceptor, Listener, MemReader, TcpListener, TempDir};
+    use url::Url;
+
+    static HELLO_GZIP: [u8, ..31] = [
+        31, 139, 8, 0, 0, 0, 0, 0, 2, 3, 203, 72, 205, 201, 201, 87, 40, 207, 47, 202, 73, 1, 0,
+        133, 17, 74, 13, 11, 0, 0, 0,
+    ];
+
+    static HELLO_DEFLATE: [u8, ..19] = [
+        120, 156, 203, 72, 205, 201, 201, 87, 40, 207, 47, 202, 73, 1, 0, 26, 11, 4, 93,
+    ];
+
+    /// `HELLO_DEFLATE` without its zlib header and checksum.
+    static HELLO_RAW_DEFLATE: [u8, ..13] = [
+        203, 72, 205, 201, 201, 87, 40, 207, 47, 202, 73, 1, 0,
+    ];
+
+    fn decode(encodings: &[ContentEncoding], data: &[u8]) -> (Vec<u8>, Result<(), String>) {
+        let (progress_chan, progress_port) = channel();
+        let result = decode_and_send(encodings, MemReader::new(data.to_vec()), &progress_chan,
+                                     &mut None);
+        progress_chan.send(Done(Ok(())));
+
+        let mut buf = vec!();
         loop {
-            let mut buf = Vec::with_capacity(1024);
-
-            unsafe { buf.set_len(1024); }
-            match response.read(buf.as_mut_slice()) {
-                Ok(len) => {
-                    unsafe { buf.set_len(len); }
-                    if progress_chan.send_opt(Payload(buf)).is_err() {
-                        // The send errors when the receiver is out of scope,
-                        // which will happen if the fetch has timed out (or has been aborted)
-                        // so we don't need to continue with the loading of the file here.
-                        return;
-                    }
+            match progress_port.recv() {
+                Payload(data) => buf.push_all(data.as_slice()),
+                Done(_) => return (buf, result),
+            }
+        }
+    }
+
+    /// Serves each of `responses` to a connection of its own on a local port, sending on each
+    /// request that it reads. Returns the URL of the server and the receiver of the requests.
+    fn serve(responses: Vec<&'static str>) -> (String, Receiver<String>) {
+        let mut listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.socket_name().unwrap();
+        let mut acceptor = listener.listen().unwrap();
+        let (request_chan, request_port) = channel();
+        spawn(proc() {
+            for response in responses.into_iter() {
+                let mut stream = acceptor.accept().unwrap();
+                let mut request = vec!();
+                while !request.as_slice().ends_with(b"\r\n\r\n") {
+                    request.push(stream.read_byte().unwrap());
                 }
-                Err(_) => {
-                    let _ = progress_chan.send_opt(Done(Ok(())));
-                    break;
+                request_chan.send(String::from_utf8(request).unwrap());
+                stream.write(response.as_bytes()).unwrap();
+            }
+        });
+        (format!("http://{}/", address), request_port)
+    }
+
+    /// Loads `url` through the HTTP cache, and returns the body of the response.
+    fn load_body(http_cache: &HttpCacheTask, url: &Url) -> Vec<u8> {
+        let (start_chan, start_port) = channel();
+        let (consumer, _) = channel();
+        load(LoadData::new(url.clone(), consumer), start_chan, Some(http_cache.clone()));
+        let progress_port = start_port.recv().load_response.progress_port;
+        let mut body = vec!();
+        loop {
+            match progress_port.recv() {
+                Payload(data) => body.push_all(data.as_slice()),
+                Done(result) => {
+                    assert_eq!(result, Ok(()));
+                    return body
                 }
             }
         }
+    }
+
+    #[test]
+    fn test_load_revalidates_stale_responses() {
+        let (server, requests) = serve(vec!(
+            "HTTP/1.1 200 OK\r\nCache-Control: max-age=0\r\nETag: \"v1\"\r\n\
+             Content-Length: 5\r\nConnection: close\r\n\r\nhello",
+            "HTTP/1.1 304 Not Modified\r\nCache-Control: max-age=3600\r\n\
+             Connection: close\r\n\r\n",
+        ));
+        let dir = TempDir::new("http_loader").unwrap();
+        let http_cache = new_http_cache_task(dir.path().clone(), 1024);
+        let url = Url::parse(format!("{}style.css", server).as_slice()).unwrap();
+
+        // The response is stored, but it is stale right away.
+        assert_eq!(load_body(&http_cache, &url), b"hello".to_vec());
+        assert!(!requests.recv().as_slice().contains("If-None-Match"));
+
+        // So the next load asks the server whether it is still good, and gets the stored body.
+        assert_eq!(load_body(&http_cache, &url), b"hello".to_vec());
+        assert!(requests.recv().as_slice().contains("If-None-Match: \"v1\""));
+
+        // The 304 response made it fresh, so the server isn't asked again.
+        assert_eq!(load_body(&http_cache, &url), b"hello".to_vec());
+        assert!(requests.try_recv().is_err());
+
+        http_cache.send(Exit);
+    }
+
+    #[test]
+    fn test_content_encoding_from_header_value() {
+        assert_eq!(ContentEncoding::from_header_value("identity"), Ok(Identity));
+        assert_eq!(ContentEncoding::from_header_value(" GZIP "), Ok(Gzip));
+        assert_eq!(ContentEncoding::from_header_value("x-gzip"), Ok(Gzip));
+        assert_eq!(ContentEncoding::from_header_value("deflate"), Ok(Deflate));
+        assert!(ContentEncoding::from_header_value("br").is_err());
+    }
+
+    #[test]
+    fn test_content_encoding_list_from_header_value() {
+        assert_eq!(ContentEncoding::list_from_header_value(""), Ok(vec!()));
+        assert_eq!(ContentEncoding::list_from_header_value("gzip, identity"), Ok(vec!(Gzip)));
+        assert_eq!(ContentEncoding::list_from_header_value("deflate,gzip"),
+                   Ok(vec!(Deflate, Gzip)));
+        assert!(ContentEncoding::list_from_header_value("gzip, br").is_err());
+    }
+
+    #[test]
+    fn test_is_zlib_header() {
+        assert!(is_zlib_header(HELLO_DEFLATE.slice_to(2)));
+        assert!(!is_zlib_header(HELLO_RAW_DEFLATE.slice_to(2)));
+        assert!(!is_zlib_header([120]));
+    }
+
+    #[test]
+    fn test_decode_gzip() {
+        let (data, result) = decode([Gzip], HELLO_GZIP.as_slice());
+        assert_eq!(result, Ok(()));
+        assert_eq!(data.as_slice(), b"hello world");
+    }
+
+    #[test]
+    fn test_decode_deflate() {
+        let (data, result) = decode([Deflate], HELLO_DEFLATE.as_slice());
+        assert_eq!(result, Ok(()));
+        assert_eq!(data.as_slice(), b"hello world");
+    }
+
+    #[test]
+    fn test_decode_raw_deflate() {
+        let (data, result) = decode([Deflate], HELLO_RAW_DEFLATE.as_slice());
+        assert_eq!(result, Ok(()));
+        assert_eq!(data.as_slice(), b"hello world");
+    }
+
+    #[test]
+    fn test_decode_empty_body() {
+        for encodings in [[Gzip], [Deflate]].iter() {
+            let (data, result) = decode(encodings.as_slice(), []);
+            assert_eq!(result, Ok(()));
+            assert!(data.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_decode_identity() {
+        let (data, result) = decode([], b"hello world");
+        assert_eq!(result, Ok(()));
+        assert_eq!(data.as_slice(), b"hello world");
+    }
+
+    #[test]
+    fn test_decode_corrupt_gzip() {
+        let (_, result) = decode([Gzip], b"not gzip at all");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_decode_corrupt_deflate() {
+        let mut corrupt = HELLO_DEFLATE.to_vec();
+        corrupt[2] = 0xff;
+        let (_, result) = decode([Deflate], corrupt.as_slice());
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_decode_truncated_gzip() {
+        // Cut off in the middle of the compressed data, and before the checksum.
+        for &length in [15u, 25].iter() {
+            let (_, result) = decode([Gzip], HELLO_GZIP.slice_to(length));
+            assert!(result.is_err());
+        }
+    }
+
+    #[test]
+    fn test_decode_gzip_with_bad_checksum() {
+        let mut corrupt = HELLO_GZIP.to_vec();
+        corrupt[24] ^= 0xff;
+        let (_, result) = decode([Gzip], corrupt.as_slice());
+        assert!(result.is_err());
+    }
 
-        // We didn't get redirected.
-        break;
+    #[test]
+    fn test_decode_truncated_deflate() {
+        let (_, result) = decode([Deflate], HELLO_DEFLATE.slice_to(10));
+        assert!(result.is_err());
     }
 }
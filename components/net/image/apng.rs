@@ -0,0 +1,269 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Animated PNG support. Each frame's image data is rewrapped as a standalone PNG and handed to
+//! libpng, then composited onto the canvas according to the frame's dispose and blend ops.
+
+use image::base::{DecodedImage, ImageFrame, MAX_IMAGE_PIXELS, blend_over};
+
+use png;
+
+static SIGNATURE: [u8, ..8] = [137, 80, 78, 71, 13, 10, 26, 10];
+
+fn read_u32(data: &[u8]) -> u32 {
+    ((data[0] as u32) << 24) | ((data[1] as u32) << 16) | ((data[2] as u32) << 8) | data[3] as u32
+}
+
+fn read_u16(data: &[u8]) -> u16 {
+    ((data[0] as u16) << 8) | data[1] as u16
+}
+
+fn write_u32(out: &mut Vec<u8>, value: u32) {
+    out.push((value >> 24) as u8);
+    out.push((value >> 16) as u8);
+    out.push((value >> 8) as u8);
+    out.push(value as u8);
+}
+
+fn crc32(data: &[u8], crc: u32) -> u32 {
+    let mut crc = crc;
+    for &byte in data.iter() {
+        crc ^= byte as u32;
+        for _ in range(0u, 8) {
+            crc = if crc & 1 != 0 { 0xEDB88320 ^ (crc >> 1) } else { crc >> 1 };
+        }
+    }
+    crc
+}
+
+fn write_chunk(out: &mut Vec<u8>, kind: &[u8], data: &[u8]) {
+    write_u32(out, data.len() as u32);
+    out.push_all(kind);
+    out.push_all(data);
+    write_u32(out, !crc32(data, crc32(kind, 0xFFFFFFFF)));
+}
+
+/// The contents of an fcTL chunk.
+struct FrameControl {
+    width: uint,
+    height: uint,
+    x: uint,
+    y: uint,
+    delay: u32,
+    dispose_op: u8,
+    blend_op: u8,
+}
+
+/// Decodes a standalone PNG into non-premultiplied RGBA.
+fn decode_png_rgba(data: &[u8]) -> Option<(uint, uint, Vec<u8>)> {
+    let image = match png::load_png_from_memory(data) {
+        Ok(image) => image,
+        Err(_) => return None,
+    };
+    let (width, height) = (image.width as uint, image.height as uint);
+    let mut rgba = Vec::with_capacity(width * height * 4);
+    match image.pixels {
+        png::RGBA8(ref pixels) => rgba.push_all(pixels.as_slice()),
+        png::RGB8(ref pixels) => {
+            for pixel in pixels.as_slice().chunks(3) {
+                rgba.push_all(pixel);
+                rgba.push(255);
+            }
+        }
+        png::K8(ref pixels) => {
+            for &value in pixels.iter() {
+                rgba.push_all(&[value, value, value, 255]);
+            }
+        }
+        png::KA8(ref pixels) => {
+            for pixel in pixels.as_slice().chunks(2) {
+                rgba.push_all(&[pixel[0], pixel[0], pixel[0], pixel[1]]);
+            }
+        }
+    }
+    if rgba.len() != width * height * 4 {
+        return None;
+    }
+    Some((width, height, rgba))
+}
+
+fn clear_rect(canvas: &mut [u8], canvas_width: uint, control: &FrameControl) {
+    for y in range(control.y, control.y + control.height) {
+        for x in range(control.x, control.x + control.width) {
+            let offset = (y * canvas_width + x) * 4;
+            for i in range(0u, 4) {
+                canvas[offset + i] = 0;
+            }
+        }
+    }
+}
+
+/// Decodes every complete frame of an animated PNG. Returns `None` for PNGs that aren't animated,
+/// which should be decoded as still images instead.
+pub fn decode(data: &[u8]) -> Option<DecodedImage> {
+    if data.len() < 8 || data.slice_to(8) != SIGNATURE.as_slice() {
+        return None;
+    }
+
+    let mut pos = 8;
+    let mut header: Option<&[u8]> = None;
+    let mut shared_chunks: Vec<(&[u8], &[u8])> = vec!();
+    let mut loop_count = None;
+    let mut animated = false;
+    let mut seen_image_data = false;
+    let mut control: Option<FrameControl> = None;
+    let mut frame_data = vec!();
+    let mut frames = vec!();
+    let mut canvas = vec!();
+    let (mut width, mut height) = (0u, 0u);
+
+    // Incomplete chunks at the end are ignored, so truncated data yields the complete frames.
+    while pos + 12 <= data.len() {
+        let length = read_u32(data.slice_from(pos)) as uint;
+        if length > data.len() - pos - 12 {
+            break;
+        }
+        let kind = data.slice(pos + 4, pos + 8);
+        let body = data.slice(pos + 8, pos + 8 + length);
+        pos += 12 + length;
+
+        // Anything other than more image data ends the frame being gathered.
+        let is_image_data = kind == b"IDAT" || kind == b"fdAT";
+        if !is_image_data && !frame_data.is_empty() {
+            let (frame_control, header) = match (control.take(), header) {
+                (Some(frame_control), Some(header)) => (frame_control, header),
+                _ => return None,
+            };
+
+            let mut frame_png = SIGNATURE.to_vec();
+            let mut frame_header = header.to_vec();
+            for i in range(0u, 4) {
+                frame_header[i] = (frame_control.width >> (24 - 8 * i)) as u8;
+                frame_header[4 + i] = (frame_control.height >> (24 - 8 * i)) as u8;
+            }
+            write_chunk(&mut frame_png, b"IHDR", frame_header.as_slice());
+            for &(chunk_kind, chunk_body) in shared_chunks.iter() {
+                write_chunk(&mut frame_png, chunk_kind, chunk_body);
+            }
+            write_chunk(&mut frame_png, b"IDAT", frame_data.as_slice());
+            write_chunk(&mut frame_png, b"IEND", &[]);
+            frame_data.clear();
+
+            let (frame_width, frame_height, pixels) =
+                match decode_png_rgba(frame_png.as_slice()) {
+                    Some(decoded) => decoded,
+                    None => return None,
+                };
+            if frame_width != frame_control.width || frame_height != frame_control.height {
+                return None;
+            }
+
+            let previous = if frame_control.dispose_op == 2 && !frames.is_empty() {
+                Some(canvas.clone())
+            } else {
+                None
+            };
+            for y in range(0, frame_height) {
+                for x in range(0, frame_width) {
+                    let source = (y * frame_width + x) * 4;
+                    let dest = ((frame_control.y + y) * width + frame_control.x + x) * 4;
+                    let source = pixels.slice(source, source + 4);
+                    if frame_control.blend_op == 0 {
+                        for i in range(0u, 4) {
+                            canvas[dest + i] = source[i];
+                        }
+                    } else {
+                        blend_over(canvas.slice_mut(dest, dest + 4), source);
+                    }
+                }
+            }
+            frames.push(ImageFrame::from_rgba(width, height, canvas.clone(),
+                                              frame_control.delay));
+
+            // Disposing to the previous frame on the first frame means clearing.
+            match (frame_control.dispose_op, previous) {
+                (2, Some(previous)) => canvas = previous,
+                (1, _) | (2, None) => clear_rect(canvas.as_mut_slice(), width, &frame_control),
+                _ => {}
+            }
+        }
+
+        if kind == b"IHDR" {
+            if length != 13 {
+                return None;
+            }
+            width = read_u32(body) as uint;
+            height = read_u32(body.slice_from(4)) as uint;
+            if width as u64 * height as u64 > MAX_IMAGE_PIXELS as u64 {
+                return None;
+            }
+            canvas = Vec::from_elem(width * height * 4, 0u8);
+            header = Some(body);
+        } else if kind == b"acTL" {
+            if length != 8 {
+                return None;
+            }
+            animated = true;
+            loop_count = match read_u32(body.slice_from(4)) {
+                0 => None,
+                plays => Some(plays),
+            };
+        } else if kind == b"fcTL" {
+            if length != 26 {
+                return None;
+            }
+            let delay_numerator = read_u16(body.slice_from(20)) as u32;
+            let delay_denominator = match read_u16(body.slice_from(22)) {
+                0 => 100,
+                denominator => denominator as u32,
+            };
+            let frame_control = FrameControl {
+                width: read_u32(body.slice_from(4)) as uint,
+                height: read_u32(body.slice_from(8)) as uint,
+                x: read_u32(body.slice_from(12)) as uint,
+                y: read_u32(body.slice_from(16)) as uint,
+                delay: delay_numerator * 1000 / delay_denominator,
+                dispose_op: body[24],
+                blend_op: body[25],
+            };
+            if frame_control.x as u64 + frame_control.width as u64 > width as u64 ||
+               frame_control.y as u64 + frame_control.height as u64 > height as u64 {
+                return None;
+            }
+            control = Some(frame_control);
+        } else if kind == b"IDAT" {
+            seen_image_data = true;
+            if !animated {
+                return None;
+            }
+            // The default image is only part of the animation if an fcTL precedes it.
+            if control.is_some() {
+                frame_data.push_all(body);
+            }
+        } else if kind == b"fdAT" {
+            // Skip the sequence number.
+            if length < 4 {
+                return None;
+            }
+            if control.is_some() {
+                frame_data.push_all(body.slice_from(4));
+            }
+        } else if kind == b"IEND" {
+            break;
+        } else if !seen_image_data {
+            // Palettes, gamma and the like apply to every frame.
+            shared_chunks.push((kind, body));
+        }
+    }
+
+    if !animated || frames.is_empty() {
+        return None;
+    }
+    Some(DecodedImage {
+        width: width,
+        height: height,
+        frames: frames,
+        loop_count: loop_count,
+    })
+}
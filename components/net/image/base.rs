@@ -5,11 +5,112 @@
 use std::iter::range_step;
 use stb_image::image as stb_image;
 use png;
+use sync::Arc;
 
 // FIXME: Images must not be copied every frame. Instead we should atomically
 // reference count them.
 pub type Image = png::Image;
 
+/// Images with more pixels than this are treated as broken rather than allocated.
+pub static MAX_IMAGE_PIXELS: uint = 1 << 26;
+
+/// Frames with a delay of this many milliseconds or less are shown for
+/// `DEFAULT_FRAME_DELAY` instead, as other browsers do.
+static MIN_FRAME_DELAY: u32 = 10;
+static DEFAULT_FRAME_DELAY: u32 = 100;
+
+/// A single frame of a possibly animated image. Frames are always the full size of the image.
+#[deriving(Clone)]
+pub struct ImageFrame {
+    pub image: Arc<Box<Image>>,
+    /// How long the frame is shown for, in milliseconds.
+    pub delay: u32,
+}
+
+impl ImageFrame {
+    /// Creates a frame from non-premultiplied RGBA pixels.
+    pub fn from_rgba(width: uint, height: uint, rgba: Vec<u8>, delay: u32) -> ImageFrame {
+        ImageFrame {
+            image: Arc::new(box image_from_rgba(width, height, rgba)),
+            delay: delay,
+        }
+    }
+}
+
+/// The frames of an image, or as many of them as could be decoded so far.
+#[deriving(Clone)]
+pub struct DecodedImage {
+    pub width: uint,
+    pub height: uint,
+    pub frames: Vec<ImageFrame>,
+    /// The number of times the animation plays, or `None` if it repeats forever.
+    pub loop_count: Option<u32>,
+}
+
+impl DecodedImage {
+    pub fn from_image(image: Image) -> DecodedImage {
+        DecodedImage {
+            width: image.width as uint,
+            height: image.height as uint,
+            frames: vec!(ImageFrame {
+                image: Arc::new(box image),
+                delay: 0,
+            }),
+            loop_count: Some(1),
+        }
+    }
+
+    /// The image to show before any animation starts.
+    pub fn first_frame(&self) -> Arc<Box<Image>> {
+        self.frames[0].image.clone()
+    }
+
+    pub fn is_animated(&self) -> bool {
+        self.frames.len() > 1
+    }
+
+    /// Returns the frame to show `elapsed` milliseconds after the animation started, and how
+    /// many milliseconds later the next frame is due, or `None` if the animation is over.
+    pub fn frame_at(&self, elapsed: u64) -> (&ImageFrame, Option<u64>) {
+        if !self.is_animated() {
+            return (&self.frames[0], None)
+        }
+
+        let delays: Vec<u64> = self.frames.iter().map(|frame| {
+            if frame.delay <= MIN_FRAME_DELAY {
+                DEFAULT_FRAME_DELAY as u64
+            } else {
+                frame.delay as u64
+            }
+        }).collect();
+        let duration = delays.iter().fold(0, |total, &delay| total + delay);
+
+        let mut time = match self.loop_count {
+            Some(loop_count) if elapsed >= duration * (loop_count as u64) => {
+                return (self.frames.last().unwrap(), None)
+            }
+            _ => elapsed % duration,
+        };
+        for (frame, &delay) in self.frames.iter().zip(delays.iter()) {
+            if time < delay {
+                return (frame, Some(delay - time))
+            }
+            time -= delay;
+        }
+        unreachable!()
+    }
+
+    /// The number of bytes taken up by the pixels of every frame.
+    pub fn size_in_bytes(&self) -> uint {
+        self.frames.iter().map(|frame| {
+            match frame.image.pixels {
+                png::K8(ref pixels) | png::KA8(ref pixels) | png::RGB8(ref pixels) |
+                png::RGBA8(ref pixels) => pixels.len(),
+            }
+        }).fold(0, |total, size| total + size)
+    }
+}
+
 
 static TEST_IMAGE: &'static [u8] = include_bin!("test.jpeg");
 
@@ -41,6 +142,59 @@ fn byte_swap_and_premultiply(data: &mut [u8]) {
     }
 }
 
+/// Converts non-premultiplied RGBA pixels into an `Image` in the format gfx expects.
+pub fn image_from_rgba(width: uint, height: uint, mut rgba: Vec<u8>) -> Image {
+    byte_swap_and_premultiply(rgba.as_mut_slice());
+    png::Image {
+        width: width as u32,
+        height: height as u32,
+        pixels: png::RGBA8(rgba),
+    }
+}
+
+/// Wraps premultiplied BGRA pixels, such as the contents of a canvas, in an `Image`.
+pub fn image_from_bgra(width: uint, height: uint, bgra: Vec<u8>) -> Image {
+    png::Image {
+        width: width as u32,
+        height: height as u32,
+        pixels: png::RGBA8(bgra),
+    }
+}
+
+/// Returns the pixels of an image as premultiplied BGRA, or `None` if its color type is not
+/// supported.
+pub fn image_to_bgra(image: &Image) -> Option<Vec<u8>> {
+    match image.pixels {
+        png::RGBA8(ref pixels) => Some(pixels.clone()),
+        png::K8(ref pixels) => {
+            Some(pixels.iter().flat_map(|&value| vec!(value, value, value, 255).into_iter())
+                              .collect())
+        }
+        png::RGB8(_) | png::KA8(_) => None,
+    }
+}
+
+/// Alpha-blends a non-premultiplied RGBA `source` pixel over `dest`.
+pub fn blend_over(dest: &mut [u8], source: &[u8]) {
+    let source_alpha = source[3] as u32;
+    if source_alpha == 255 {
+        for i in range(0u, 4) {
+            dest[i] = source[i];
+        }
+        return;
+    }
+    if source_alpha == 0 {
+        return;
+    }
+    let dest_alpha = (dest[3] as u32 * (255 - source_alpha) + 127) / 255;
+    let alpha = source_alpha + dest_alpha;
+    for i in range(0u, 3) {
+        let value = source[i] as u32 * source_alpha + dest[i] as u32 * dest_alpha;
+        dest[i] = ((value + alpha / 2) / alpha) as u8;
+    }
+    dest[3] = alpha as u8;
+}
+
 pub fn load_from_memory(buffer: &[u8]) -> Option<Image> {
     if buffer.len() == 0 {
         return None;
@@ -86,3 +240,58 @@ pub fn load_from_memory(buffer: &[u8]) -> Option<Image> {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{DecodedImage, ImageFrame};
+
+    fn animation(delays: &[u32], loop_count: Option<u32>) -> DecodedImage {
+        DecodedImage {
+            width: 1,
+            height: 1,
+            frames: delays.iter().map(|&delay| {
+                ImageFrame::from_rgba(1, 1, vec!(0, 0, 0, 255), delay)
+            }).collect(),
+            loop_count: loop_count,
+        }
+    }
+
+    fn frame_index(image: &DecodedImage, elapsed: u64) -> (uint, Option<u64>) {
+        let (frame, next) = image.frame_at(elapsed);
+        let index = image.frames.iter().position(|other| {
+            &*other.image as *const _ == &*frame.image as *const _
+        }).unwrap();
+        (index, next)
+    }
+
+    #[test]
+    fn test_frame_at_follows_the_frame_delays() {
+        let image = animation(&[100, 200], None);
+        assert_eq!(frame_index(&image, 0), (0, Some(100)));
+        assert_eq!(frame_index(&image, 99), (0, Some(1)));
+        assert_eq!(frame_index(&image, 100), (1, Some(200)));
+        assert_eq!(frame_index(&image, 250), (1, Some(50)));
+        assert_eq!(frame_index(&image, 300), (0, Some(100)));
+    }
+
+    #[test]
+    fn test_frame_at_stops_on_the_last_frame_after_the_last_loop() {
+        let image = animation(&[100, 200], Some(2));
+        assert_eq!(frame_index(&image, 400), (1, Some(200)));
+        assert_eq!(frame_index(&image, 600), (1, None));
+        assert_eq!(frame_index(&image, 10000), (1, None));
+    }
+
+    #[test]
+    fn test_frame_at_shows_short_frames_for_the_default_delay() {
+        let image = animation(&[0, 10], None);
+        assert_eq!(frame_index(&image, 0), (0, Some(100)));
+        assert_eq!(frame_index(&image, 150), (1, Some(50)));
+    }
+
+    #[test]
+    fn test_frame_at_does_not_animate_single_frames() {
+        let image = animation(&[100], None);
+        assert_eq!(frame_index(&image, 1000), (0, None));
+    }
+}
@@ -0,0 +1,218 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Picks a decoder for image data based on its signature, and decides when data that is still
+//! arriving is worth decoding again.
+
+use image::base::{DecodedImage, load_from_memory};
+use image::{apng, gif, webp};
+
+use std::cmp::max;
+
+#[deriving(PartialEq, Show)]
+pub enum ImageFormat {
+    GifFormat,
+    PngFormat,
+    WebpFormat,
+    /// Formats that only the still image decoders handle, such as JPEG.
+    OtherFormat,
+}
+
+pub fn sniff_format(data: &[u8]) -> ImageFormat {
+    if data.len() >= 6 && (data.slice_to(6) == b"GIF87a" || data.slice_to(6) == b"GIF89a") {
+        GifFormat
+    } else if data.len() >= 8 && data.slice_to(8) == b"\x89PNG\r\n\x1a\n" {
+        PngFormat
+    } else if data.len() >= 12 && data.slice_to(4) == b"RIFF" && data.slice(8, 12) == b"WEBP" {
+        WebpFormat
+    } else {
+        OtherFormat
+    }
+}
+
+/// Decodes a complete image, with every frame if it is animated.
+pub fn decode_image(data: &[u8]) -> Option<DecodedImage> {
+    match sniff_format(data) {
+        GifFormat => gif::decode(data),
+        PngFormat => {
+            // Still PNGs go to libpng directly.
+            apng::decode(data).or_else(|| decode_still_image(data))
+        }
+        WebpFormat => webp::decode(data),
+        OtherFormat => decode_still_image(data),
+    }
+}
+
+fn decode_still_image(data: &[u8]) -> Option<DecodedImage> {
+    load_from_memory(data).map(|image| DecodedImage::from_image(image))
+}
+
+/// Decodes as much as possible of an image whose data hasn't all arrived. Only the formats whose
+/// decoders cope with truncated data give anything.
+pub fn decode_partial_image(data: &[u8]) -> Option<DecodedImage> {
+    match sniff_format(data) {
+        GifFormat => gif::decode(data),
+        PngFormat => apng::decode(data),
+        WebpFormat => webp::decode(data),
+        OtherFormat => None,
+    }
+}
+
+/// Decoding starts over from the beginning each time, so wait for this much more data, or for
+/// the data to grow by half, whichever is more, before decoding again. This keeps the total
+/// decoding work proportional to the size of the image.
+static MIN_PARTIAL_DECODE_STEP: uint = 16 * 1024;
+
+/// Tracks how much of an image's data was last decoded, while the data is arriving.
+pub struct ProgressiveDecoder {
+    decoded_length: uint,
+}
+
+impl ProgressiveDecoder {
+    pub fn new() -> ProgressiveDecoder {
+        ProgressiveDecoder {
+            decoded_length: 0,
+        }
+    }
+
+    /// Decodes the data received so far, if enough has arrived since the last decode.
+    pub fn decode(&mut self, data: &[u8]) -> Option<DecodedImage> {
+        let threshold = max(self.decoded_length + MIN_PARTIAL_DECODE_STEP,
+                            self.decoded_length * 3 / 2);
+        if data.len() < threshold {
+            return None;
+        }
+        self.decoded_length = data.len();
+        decode_partial_image(data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{GifFormat, OtherFormat, PngFormat, WebpFormat};
+    use super::{ProgressiveDecoder, decode_image, decode_partial_image, sniff_format};
+    use image::base::{DecodedImage, test_image_bin};
+    use png;
+
+    static ANIMATED_GIF: &'static [u8] = include_bin!("test_animated.gif");
+    static ANIMATED_PNG: &'static [u8] = include_bin!("test_animated.png");
+    static ANIMATED_WEBP: &'static [u8] = include_bin!("test_animated.webp");
+    static LOSSLESS_WEBP: &'static [u8] = include_bin!("test_lossless.webp");
+    static LOSSY_WEBP: &'static [u8] = include_bin!("test_lossy.webp");
+
+    /// Returns a pixel of a frame, which is premultiplied BGRA.
+    fn pixel(image: &DecodedImage, frame: uint, x: uint, y: uint) -> Vec<u8> {
+        let frame = &image.frames[frame].image;
+        let offset = (y * frame.width as uint + x) * 4;
+        match frame.pixels {
+            png::RGBA8(ref pixels) => pixels.slice(offset, offset + 4).to_vec(),
+            _ => panic!("frames should be RGBA"),
+        }
+    }
+
+    fn delays(image: &DecodedImage) -> Vec<u32> {
+        image.frames.iter().map(|frame| frame.delay).collect()
+    }
+
+    #[test]
+    fn test_sniff_format() {
+        assert_eq!(sniff_format(ANIMATED_GIF), GifFormat);
+        assert_eq!(sniff_format(ANIMATED_PNG), PngFormat);
+        assert_eq!(sniff_format(LOSSY_WEBP), WebpFormat);
+        assert_eq!(sniff_format(test_image_bin().as_slice()), OtherFormat);
+        assert_eq!(sniff_format(b"RIFF"), OtherFormat);
+    }
+
+    #[test]
+    fn test_decode_animated_gif() {
+        let image = decode_image(ANIMATED_GIF).unwrap();
+        assert_eq!((image.width, image.height), (4, 4));
+        assert_eq!(delays(&image), vec!(100, 200));
+        assert_eq!(image.loop_count, None);
+        assert_eq!(pixel(&image, 0, 1, 1), vec!(0, 0, 255, 255));
+        assert_eq!(pixel(&image, 1, 1, 1), vec!(0, 255, 0, 255));
+        assert_eq!(pixel(&image, 1, 0, 0), vec!(0, 0, 255, 255));
+    }
+
+    #[test]
+    fn test_decode_truncated_gif() {
+        // Cut off before the second frame.
+        let image = decode_partial_image(ANIMATED_GIF.slice_to(69)).unwrap();
+        assert_eq!(image.frames.len(), 1);
+        assert_eq!(pixel(&image, 0, 1, 1), vec!(0, 0, 255, 255));
+        assert!(decode_partial_image(ANIMATED_GIF.slice_to(13)).is_none());
+    }
+
+    #[test]
+    fn test_decode_animated_png() {
+        let image = decode_image(ANIMATED_PNG).unwrap();
+        assert_eq!((image.width, image.height), (4, 4));
+        assert_eq!(delays(&image), vec!(100, 200));
+        assert_eq!(image.loop_count, None);
+        assert_eq!(pixel(&image, 1, 0, 0), vec!(255, 0, 0, 255));
+        // Half transparent red blended over blue.
+        assert_eq!(pixel(&image, 1, 3, 3), vec!(127, 0, 128, 255));
+    }
+
+    #[test]
+    fn test_decode_lossless_webp() {
+        let image = decode_image(LOSSLESS_WEBP).unwrap();
+        assert_eq!((image.width, image.height), (4, 4));
+        assert!(!image.is_animated());
+        assert_eq!(pixel(&image, 0, 0, 0), vec!(0, 0, 255, 255));
+        assert_eq!(pixel(&image, 0, 3, 3), vec!(128, 0, 0, 128));
+    }
+
+    #[test]
+    fn test_decode_lossy_webp() {
+        let image = decode_image(LOSSY_WEBP).unwrap();
+        assert_eq!((image.width, image.height), (32, 48));
+        assert_eq!(pixel(&image, 0, 0, 0), vec!(0, 0, 2, 255));
+        assert_eq!(pixel(&image, 0, 31, 47), vec!(162, 232, 253, 255));
+    }
+
+    #[test]
+    fn test_decode_truncated_lossy_webp() {
+        // The rows that haven't arrived are transparent.
+        let image = decode_partial_image(LOSSY_WEBP.slice_to(298)).unwrap();
+        assert_eq!((image.width, image.height), (32, 48));
+        assert_eq!(pixel(&image, 0, 0, 15)[3], 255);
+        assert_eq!(pixel(&image, 0, 0, 47), vec!(0, 0, 0, 0));
+    }
+
+    #[test]
+    fn test_decode_animated_webp() {
+        let image = decode_image(ANIMATED_WEBP).unwrap();
+        assert_eq!((image.width, image.height), (8, 8));
+        assert_eq!(delays(&image), vec!(100, 200));
+        assert_eq!(image.loop_count, None);
+        assert_eq!(pixel(&image, 0, 4, 2), vec!(0, 0, 255, 255));
+        assert_eq!(pixel(&image, 1, 4, 2), vec!(0, 255, 0, 255));
+        assert_eq!(pixel(&image, 1, 0, 0), vec!(0, 0, 255, 255));
+
+        let image = decode_partial_image(ANIMATED_WEBP.slice_to(100)).unwrap();
+        assert_eq!(image.frames.len(), 1);
+    }
+
+    #[test]
+    fn test_decode_still_image() {
+        let data = test_image_bin();
+        let image = decode_image(data.as_slice()).unwrap();
+        assert_eq!(image.frames.len(), 1);
+        assert_eq!(image.loop_count, Some(1));
+        // JPEGs are only decoded once all of the data has arrived.
+        assert!(decode_partial_image(data.as_slice()).is_none());
+    }
+
+    #[test]
+    fn test_progressive_decoder_waits_for_data() {
+        let mut decoder = ProgressiveDecoder::new();
+        assert!(decoder.decode(ANIMATED_GIF).is_none());
+
+        let mut data = ANIMATED_GIF.to_vec();
+        data.grow(16 * 1024, 0);
+        assert_eq!(decoder.decode(data.as_slice()).unwrap().frames.len(), 2);
+        assert!(decoder.decode(data.as_slice()).is_none());
+    }
+}
@@ -0,0 +1,384 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! A GIF decoder that produces every frame of an animation. Truncated data decodes to as much of
+//! the image as has arrived, so this is also used for progressive display.
+
+use image::base::{DecodedImage, ImageFrame, MAX_IMAGE_PIXELS};
+
+use std::cmp::min;
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: uint,
+}
+
+impl<'a> Reader<'a> {
+    fn read_u8(&mut self) -> Result<u8, ()> {
+        if self.pos >= self.data.len() {
+            return Err(());
+        }
+        self.pos += 1;
+        Ok(self.data[self.pos - 1])
+    }
+
+    fn read_u16(&mut self) -> Result<u16, ()> {
+        let low = try!(self.read_u8()) as u16;
+        let high = try!(self.read_u8()) as u16;
+        Ok(low | (high << 8))
+    }
+
+    fn read_bytes(&mut self, length: uint) -> Result<&'a [u8], ()> {
+        if self.pos + length > self.data.len() {
+            return Err(());
+        }
+        self.pos += length;
+        Ok(self.data.slice(self.pos - length, self.pos))
+    }
+
+    fn skip_sub_blocks(&mut self) -> Result<(), ()> {
+        loop {
+            let length = try!(self.read_u8()) as uint;
+            if length == 0 {
+                return Ok(());
+            }
+            try!(self.read_bytes(length));
+        }
+    }
+}
+
+static MAX_CODE_SIZE: uint = 12;
+
+/// Decompresses LZW data into `out`, returning how many indices were written. Decoding stops at
+/// the end code, at the end of the data, or at the first invalid code.
+fn lzw_decode(min_code_size: uint, data: &[u8], out: &mut [u8]) -> uint {
+    if min_code_size > 11 {
+        return 0;
+    }
+    let clear_code = 1u << min_code_size;
+    let end_code = clear_code + 1;
+    let mut prefix = [0u16, ..1 << MAX_CODE_SIZE];
+    let mut suffix = [0u8, ..1 << MAX_CODE_SIZE];
+    let mut first = [0u8, ..1 << MAX_CODE_SIZE];
+    let mut stack = [0u8, ..1 << MAX_CODE_SIZE];
+    for i in range(0, clear_code) {
+        suffix[i] = i as u8;
+        first[i] = i as u8;
+    }
+
+    let mut code_size = min_code_size + 1;
+    let mut next_code = end_code + 1;
+    let mut previous: Option<uint> = None;
+    let mut bits = 0u32;
+    let mut bit_count = 0u;
+    let mut written = 0u;
+    let mut pos = 0u;
+    loop {
+        while bit_count < code_size {
+            if pos >= data.len() {
+                return written;
+            }
+            bits |= (data[pos] as u32) << bit_count;
+            pos += 1;
+            bit_count += 8;
+        }
+        let code = (bits & ((1u32 << code_size) - 1)) as uint;
+        bits >>= code_size;
+        bit_count -= code_size;
+
+        if code == clear_code {
+            code_size = min_code_size + 1;
+            next_code = end_code + 1;
+            previous = None;
+            continue;
+        }
+        if code == end_code {
+            return written;
+        }
+
+        let previous_code = match previous {
+            Some(previous_code) => previous_code,
+            None => {
+                // The first code after a clear must be a literal.
+                if code >= clear_code || written >= out.len() {
+                    return written;
+                }
+                out[written] = code as u8;
+                written += 1;
+                previous = Some(code);
+                continue;
+            }
+        };
+
+        let mut stack_size = 0u;
+        let mut current = if code < next_code {
+            code
+        } else if code == next_code {
+            stack[0] = first[previous_code];
+            stack_size = 1;
+            previous_code
+        } else {
+            return written;
+        };
+        while current >= clear_code {
+            stack[stack_size] = suffix[current];
+            stack_size += 1;
+            current = prefix[current] as uint;
+        }
+        stack[stack_size] = current as u8;
+        stack_size += 1;
+
+        if next_code < (1 << MAX_CODE_SIZE) {
+            prefix[next_code] = previous_code as u16;
+            suffix[next_code] = current as u8;
+            first[next_code] = first[previous_code];
+            next_code += 1;
+            if next_code == (1 << code_size) && code_size < MAX_CODE_SIZE {
+                code_size += 1;
+            }
+        }
+        previous = Some(code);
+
+        while stack_size > 0 {
+            if written >= out.len() {
+                return written;
+            }
+            stack_size -= 1;
+            out[written] = stack[stack_size];
+            written += 1;
+        }
+    }
+}
+
+/// Maps the `n`th row in the order an interlaced image is stored to its position in the image.
+fn deinterlace_row(n: uint, height: uint) -> uint {
+    let pass1 = (height + 7) / 8;
+    if n < pass1 {
+        return n * 8;
+    }
+    let n = n - pass1;
+    let pass2 = (height + 3) / 8;
+    if n < pass2 {
+        return n * 8 + 4;
+    }
+    let n = n - pass2;
+    let pass3 = (height + 1) / 4;
+    if n < pass3 {
+        return n * 4 + 2;
+    }
+    (n - pass3) * 2 + 1
+}
+
+/// The settings from a graphic control extension, which apply to the next frame.
+struct GraphicControl {
+    disposal: u8,
+    delay: u32,
+    transparent: Option<u8>,
+}
+
+impl GraphicControl {
+    fn new() -> GraphicControl {
+        GraphicControl {
+            disposal: 0,
+            delay: 0,
+            transparent: None,
+        }
+    }
+}
+
+fn read_graphic_control(reader: &mut Reader) -> Result<GraphicControl, ()> {
+    let length = try!(reader.read_u8()) as uint;
+    if length < 4 {
+        return Err(());
+    }
+    let flags = try!(reader.read_u8());
+    let delay = try!(reader.read_u16());
+    let transparent = try!(reader.read_u8());
+    try!(reader.read_bytes(length - 4));
+    try!(reader.skip_sub_blocks());
+    Ok(GraphicControl {
+        disposal: (flags >> 2) & 7,
+        delay: delay as u32 * 10,
+        transparent: if flags & 1 != 0 { Some(transparent) } else { None },
+    })
+}
+
+/// Reads an application extension, updating `loop_count` if it is a looping extension.
+fn read_application_extension(reader: &mut Reader, loop_count: &mut Option<u32>)
+                              -> Result<(), ()> {
+    let length = try!(reader.read_u8()) as uint;
+    let application = try!(reader.read_bytes(length));
+    if application != b"NETSCAPE2.0" && application != b"ANIMEXTS1.0" {
+        return reader.skip_sub_blocks();
+    }
+    loop {
+        let length = try!(reader.read_u8()) as uint;
+        if length == 0 {
+            return Ok(());
+        }
+        let block = try!(reader.read_bytes(length));
+        if length >= 3 && block[0] == 1 {
+            // The extension stores the number of repeats, where zero means forever.
+            *loop_count = match block[1] as u32 | ((block[2] as u32) << 8) {
+                0 => None,
+                repeats => Some(repeats + 1),
+            };
+        }
+    }
+}
+
+pub fn decode(data: &[u8]) -> Option<DecodedImage> {
+    let mut reader = Reader {
+        data: data,
+        pos: 0,
+    };
+    match reader.read_bytes(6) {
+        Ok(signature) if signature == b"GIF87a" || signature == b"GIF89a" => {}
+        _ => return None,
+    }
+    let (width, height, flags) = match (reader.read_u16(), reader.read_u16(), reader.read_u8()) {
+        (Ok(width), Ok(height), Ok(flags)) => (width as uint, height as uint, flags),
+        _ => return None,
+    };
+    // Skip the background color and aspect ratio.
+    if reader.read_bytes(2).is_err() {
+        return None;
+    }
+    let global_palette = if flags & 0x80 != 0 {
+        match reader.read_bytes(3 * (2u << (flags & 7) as uint)) {
+            Ok(palette) => Some(palette),
+            Err(()) => return None,
+        }
+    } else {
+        None
+    };
+    if width == 0 || height == 0 || width * height > MAX_IMAGE_PIXELS {
+        return None;
+    }
+
+    let mut canvas = Vec::from_elem(width * height * 4, 0u8);
+    let mut frames = vec!();
+    let mut loop_count = Some(1);
+    let mut control = GraphicControl::new();
+
+    loop {
+        match reader.read_u8() {
+            Ok(0x21) => {
+                let result = match reader.read_u8() {
+                    Ok(0xF9) => {
+                        read_graphic_control(&mut reader).map(|new_control| control = new_control)
+                    }
+                    Ok(0xFF) => read_application_extension(&mut reader, &mut loop_count),
+                    Ok(_) => reader.skip_sub_blocks(),
+                    Err(()) => Err(()),
+                };
+                if result.is_err() {
+                    break;
+                }
+            }
+            Ok(0x2C) => {
+                let descriptor = (reader.read_u16(), reader.read_u16(), reader.read_u16(),
+                                  reader.read_u16(), reader.read_u8());
+                let (left, top, frame_width, frame_height, frame_flags) = match descriptor {
+                    (Ok(left), Ok(top), Ok(frame_width), Ok(frame_height), Ok(frame_flags)) => {
+                        (left as uint, top as uint, frame_width as uint, frame_height as uint,
+                         frame_flags)
+                    }
+                    _ => break,
+                };
+                let local_palette = if frame_flags & 0x80 != 0 {
+                    match reader.read_bytes(3 * (2u << (frame_flags & 7) as uint)) {
+                        Ok(palette) => Some(palette),
+                        Err(()) => break,
+                    }
+                } else {
+                    None
+                };
+                let palette = match local_palette.or(global_palette) {
+                    Some(palette) => palette,
+                    None => break,
+                };
+                let min_code_size = match reader.read_u8() {
+                    Ok(min_code_size) => min_code_size as uint,
+                    Err(()) => break,
+                };
+
+                // Gather the data sub-blocks, noting whether the terminator arrived.
+                let mut compressed = vec!();
+                let mut finished = false;
+                loop {
+                    let length = match reader.read_u8() {
+                        Ok(length) => length as uint,
+                        Err(()) => break,
+                    };
+                    if length == 0 {
+                        finished = true;
+                        break;
+                    }
+                    let available = min(length, data.len() - reader.pos);
+                    compressed.push_all(data.slice(reader.pos, reader.pos + available));
+                    reader.pos += available;
+                    if available < length {
+                        break;
+                    }
+                }
+                let mut indices = Vec::from_elem(frame_width * frame_height, 0u8);
+                let written = lzw_decode(min_code_size, compressed.as_slice(),
+                                         indices.as_mut_slice());
+
+                let previous = if control.disposal == 3 { Some(canvas.clone()) } else { None };
+                let interlaced = frame_flags & 0x40 != 0;
+                for i in range(0, written) {
+                    let (x, y) = (i % frame_width, i / frame_width);
+                    let y = if interlaced { deinterlace_row(y, frame_height) } else { y };
+                    let (canvas_x, canvas_y) = (left + x, top + y);
+                    let index = indices[i];
+                    if canvas_x >= width || canvas_y >= height ||
+                       Some(index) == control.transparent {
+                        continue;
+                    }
+                    let color = index as uint * 3;
+                    let offset = (canvas_y * width + canvas_x) * 4;
+                    if color + 2 < palette.len() {
+                        canvas[offset] = palette[color];
+                        canvas[offset + 1] = palette[color + 1];
+                        canvas[offset + 2] = palette[color + 2];
+                        canvas[offset + 3] = 255;
+                    }
+                }
+                frames.push(ImageFrame::from_rgba(width, height, canvas.clone(), control.delay));
+                if !finished {
+                    break;
+                }
+
+                match (control.disposal, previous) {
+                    (2, _) => {
+                        for y in range(top, min(top + frame_height, height)) {
+                            for x in range(left, min(left + frame_width, width)) {
+                                let offset = (y * width + x) * 4;
+                                for i in range(0u, 4) {
+                                    canvas[offset + i] = 0;
+                                }
+                            }
+                        }
+                    }
+                    (3, Some(previous)) => canvas = previous,
+                    _ => {}
+                }
+                control = GraphicControl::new();
+            }
+            _ => break,
+        }
+    }
+
+    if frames.is_empty() {
+        return None;
+    }
+    Some(DecodedImage {
+        width: width,
+        height: height,
+        frames: frames,
+        loop_count: loop_count,
+    })
+}
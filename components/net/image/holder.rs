@@ -2,8 +2,8 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use image::base::Image;
-use image_cache_task::{ImageReady, ImageNotReady, ImageFailed};
+use image::base::{DecodedImage, Image};
+use image_cache_task::{ImageReady, ImagePartial, ImageNotReady, ImageFailed};
 use local_image_cache::LocalImageCache;
 
 use geom::size::Size2D;
@@ -16,11 +16,17 @@ use url::Url;
 // injection.
 
 /// A struct to store image data. The image will be loaded once the first time it is requested,
-/// and an Arc will be stored.  Clones of this Arc are given out on demand.
+/// and an Arc will be stored.  Clones of this Arc are given out on demand. While the image is
+/// still loading, what has been decoded of it so far is stored instead, and replaced as more
+/// arrives.
 #[deriving(Clone)]
 pub struct ImageHolder<NodeAddress> {
     url: Url,
     image: Option<Arc<Box<Image>>>,
+    /// Whether `image` is only part of the image.
+    partial: bool,
+    /// Every frame of the image, with their timing, once it is known to be animated.
+    frames: Option<Arc<DecodedImage>>,
     cached_size: Size2D<int>,
     local_image_cache: Arc<Mutex<LocalImageCache<NodeAddress>>>,
 }
@@ -32,6 +38,8 @@ impl<NodeAddress: Send> ImageHolder<NodeAddress> {
         let holder = ImageHolder {
             url: url,
             image: None,
+            partial: false,
+            frames: None,
             cached_size: Size2D(0,0),
             local_image_cache: local_image_cache.clone(),
         };
@@ -79,8 +87,9 @@ impl<NodeAddress: Send> ImageHolder<NodeAddress> {
         debug!("get_image() {}", self.url.serialize());
 
         // If this is the first time we've called this function, load
-        // the image and store it for the future
-        if self.image.is_none() {
+        // the image and store it for the future. Partial images are
+        // loaded again in case more of them is available.
+        if self.image.is_none() || self.partial {
             let port = {
                 let val = self.local_image_cache.lock();
                 let mut local_image_cache = val;
@@ -89,6 +98,12 @@ impl<NodeAddress: Send> ImageHolder<NodeAddress> {
             match port.recv() {
                 ImageReady(image) => {
                     self.image = Some(image);
+                    self.partial = false;
+                }
+                ImagePartial(image) => {
+                    debug!("image partially loaded for {:s}", self.url.serialize());
+                    self.image = Some(image);
+                    self.partial = true;
                 }
                 ImageNotReady => {
                     debug!("image not ready for {:s}", self.url.serialize());
@@ -107,6 +122,33 @@ impl<NodeAddress: Send> ImageHolder<NodeAddress> {
         return result;
     }
 
+    /// Returns the frame of the image to show at `time`, in seconds, and how many milliseconds
+    /// later the next frame is due, or `None` if the image is not animated or its animation is
+    /// over.
+    pub fn get_frame(&mut self, node_address: NodeAddress, time: f64)
+                     -> Option<(Arc<Box<Image>>, Option<u64>)> {
+        let image = match self.get_image(node_address) {
+            Some(image) => image,
+            None => return None,
+        };
+
+        // Frames of a partial image are asked for again in case more of them are available.
+        let mut local_image_cache = self.local_image_cache.lock();
+        if self.frames.is_none() || self.partial {
+            self.frames = local_image_cache.get_frames(&self.url);
+        }
+
+        match self.frames {
+            Some(ref frames) if frames.is_animated() => {
+                let start = local_image_cache.animation_start(&self.url, time);
+                let elapsed = ((time - start) * 1000.0).max(0.0) as u64;
+                let (frame, next_frame_delay) = frames.frame_at(elapsed);
+                Some((frame.image.clone(), next_frame_delay))
+            }
+            _ => Some((image, None)),
+        }
+    }
+
     pub fn url(&self) -> &Url {
         &self.url
     }
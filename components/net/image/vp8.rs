@@ -0,0 +1,1661 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! A decoder for lossy WebP images, which are VP8 key frames (RFC 6386). The output matches
+//! libwebp's, including its fancy chroma upsampling.
+
+use image::base::MAX_IMAGE_PIXELS;
+
+use std::cmp::{max, min};
+
+static COEFF_BANDS: [uint, ..17] = [0, 1, 2, 3, 6, 4, 5, 6, 6, 6, 6, 6, 6, 6, 6, 7, 0];
+static ZIGZAG: [uint, ..16] = [0, 1, 4, 8, 5, 2, 3, 6, 9, 12, 13, 10, 7, 11, 14, 15];
+
+/// The probabilities of the extra bits of each category of large coefficients.
+static DCT_CAT_PROBS: [&'static [u8], ..6] = [
+    &[159],
+    &[165, 145],
+    &[173, 148, 140],
+    &[176, 155, 140, 135],
+    &[180, 157, 141, 134, 130],
+    &[254, 254, 243, 230, 196, 177, 153, 140, 133, 130, 129],
+];
+static DCT_CAT_BASE: [i32, ..6] = [5, 7, 11, 19, 35, 67];
+
+// Luma and chroma prediction modes for whole macroblocks.
+const DC_PRED: u8 = 0;
+const V_PRED: u8 = 1;
+const H_PRED: u8 = 2;
+const TM_PRED: u8 = 3;
+const B_PRED: u8 = 4;
+
+// Prediction modes for 4x4 luma subblocks.
+const B_DC_PRED: u8 = 0;
+const B_TM_PRED: u8 = 1;
+const B_VE_PRED: u8 = 2;
+const B_HE_PRED: u8 = 3;
+const B_LD_PRED: u8 = 4;
+const B_RD_PRED: u8 = 5;
+const B_VR_PRED: u8 = 6;
+const B_VL_PRED: u8 = 7;
+const B_HD_PRED: u8 = 8;
+const B_HU_PRED: u8 = 9;
+
+// Trees are stored as pairs of branches; leaves are negated values.
+static YMODE_TREE: [i8, ..8] = [
+    -(B_PRED as i8), 2, 4, 6, -(DC_PRED as i8), -(V_PRED as i8), -(H_PRED as i8), -(TM_PRED as i8),
+];
+static YMODE_PROBS: [u8, ..4] = [145, 156, 163, 128];
+static UV_MODE_TREE: [i8, ..6] = [
+    -(DC_PRED as i8), 2, -(V_PRED as i8), 4, -(H_PRED as i8), -(TM_PRED as i8),
+];
+static UV_MODE_PROBS: [u8, ..3] = [142, 114, 183];
+static BMODE_TREE: [i8, ..18] = [
+    -(B_DC_PRED as i8), 2,
+    -(B_TM_PRED as i8), 4,
+    -(B_VE_PRED as i8), 6,
+    8, 12,
+    -(B_HE_PRED as i8), 10,
+    -(B_RD_PRED as i8), -(B_VR_PRED as i8),
+    -(B_LD_PRED as i8), 14,
+    -(B_VL_PRED as i8), 16,
+    -(B_HD_PRED as i8), -(B_HU_PRED as i8),
+];
+static SEGMENT_TREE: [i8, ..6] = [2, 4, 0, -1, -2, -3];
+
+/// The boolean entropy decoder that all of a VP8 frame is coded with.
+struct BoolDecoder<'a> {
+    data: &'a [u8],
+    pos: uint,
+    value: u32,
+    range: u32,
+    bit_count: u32,
+}
+
+impl<'a> BoolDecoder<'a> {
+    fn new(data: &'a [u8]) -> BoolDecoder<'a> {
+        let mut decoder = BoolDecoder {
+            data: data,
+            pos: 0,
+            value: 0,
+            range: 255,
+            bit_count: 0,
+        };
+        let high = decoder.next_byte();
+        let low = decoder.next_byte();
+        decoder.value = (high << 8) | low;
+        decoder
+    }
+
+    /// Past the end of the data, the stream reads as zeros.
+    fn next_byte(&mut self) -> u32 {
+        self.pos += 1;
+        if self.pos <= self.data.len() {
+            self.data[self.pos - 1] as u32
+        } else {
+            0
+        }
+    }
+
+    fn read_bool(&mut self, probability: u8) -> bool {
+        let split = 1 + (((self.range - 1) * probability as u32) >> 8);
+        let big_split = split << 8;
+        let result = if self.value >= big_split {
+            self.range -= split;
+            self.value -= big_split;
+            true
+        } else {
+            self.range = split;
+            false
+        };
+        while self.range < 128 {
+            self.value <<= 1;
+            self.range <<= 1;
+            self.bit_count += 1;
+            if self.bit_count == 8 {
+                self.bit_count = 0;
+                self.value |= self.next_byte();
+            }
+        }
+        result
+    }
+
+    fn read_literal(&mut self, bits: uint) -> u32 {
+        let mut value = 0;
+        for _ in range(0, bits) {
+            value = (value << 1) | self.read_bool(128) as u32;
+        }
+        value
+    }
+
+    fn read_flag(&mut self) -> bool {
+        self.read_bool(128)
+    }
+
+    fn read_optional_signed(&mut self, bits: uint) -> i32 {
+        if !self.read_flag() {
+            return 0;
+        }
+        let value = self.read_literal(bits) as i32;
+        if self.read_flag() { -value } else { value }
+    }
+
+    fn read_tree(&mut self, tree: &[i8], probabilities: &[u8]) -> u8 {
+        let mut i = 0i8;
+        loop {
+            let branch = self.read_bool(probabilities[i as uint >> 1]) as uint;
+            i = tree[i as uint + branch];
+            if i <= 0 {
+                return -i as u8;
+            }
+        }
+    }
+
+    /// Whether reading has gone further past the end of the data than the two bytes the decoder
+    /// reads ahead, in which case the data must have been truncated.
+    fn exhausted(&self) -> bool {
+        self.pos > self.data.len() + 2
+    }
+}
+
+/// The dequantization factors for a segment.
+struct Quant {
+    y_dc: i32,
+    y_ac: i32,
+    y2_dc: i32,
+    y2_ac: i32,
+    uv_dc: i32,
+    uv_ac: i32,
+}
+
+/// The loop filter settings for a macroblock. A zero limit means it isn't filtered.
+struct FilterParams {
+    limit: i32,
+    interior_limit: i32,
+    hev_threshold: i32,
+    /// Whether the edges between subblocks are filtered too.
+    inner: bool,
+}
+
+/// A decoded frame, in YUV 4:2:0 with planes padded to whole macroblocks.
+pub struct Frame {
+    pub width: uint,
+    pub height: uint,
+    /// The number of rows that were decoded, which is less than `height` for truncated data.
+    pub rows: uint,
+    y_stride: uint,
+    uv_stride: uint,
+    y: Vec<u8>,
+    u: Vec<u8>,
+    v: Vec<u8>,
+}
+
+struct MacroBlockInfo {
+    ymode: u8,
+    uvmode: u8,
+    bmodes: [u8, ..16],
+}
+
+fn clamp255(value: i32) -> u8 {
+    if value < 0 {
+        0
+    } else if value > 255 {
+        255
+    } else {
+        value as u8
+    }
+}
+
+fn clamp(value: i32, low: i32, high: i32) -> i32 {
+    max(low, min(high, value))
+}
+
+/// Decodes the contents of a VP8 chunk. Truncated data decodes to the macroblock rows that
+/// arrived, as long as there is at least one.
+pub fn decode(data: &[u8]) -> Result<Frame, ()> {
+    if data.len() < 10 {
+        return Err(());
+    }
+    let tag = data[0] as u32 | ((data[1] as u32) << 8) | ((data[2] as u32) << 16);
+    let key_frame = tag & 1 == 0;
+    let first_partition_size = (tag >> 5) as uint;
+    if !key_frame || data[3] != 0x9d || data[4] != 0x01 || data[5] != 0x2a {
+        return Err(());
+    }
+    let width = (data[6] as uint | ((data[7] as uint) << 8)) & 0x3fff;
+    let height = (data[8] as uint | ((data[9] as uint) << 8)) & 0x3fff;
+    if width == 0 || height == 0 || width * height > MAX_IMAGE_PIXELS {
+        return Err(());
+    }
+    let data = data.slice_from(10);
+    if first_partition_size > data.len() {
+        return Err(());
+    }
+    let mut header = BoolDecoder::new(data.slice_to(first_partition_size));
+
+    // Skip the color space and clamping type.
+    header.read_literal(2);
+
+    let mut segmentation = false;
+    let mut update_segment_map = false;
+    let mut absolute_segment_values = false;
+    let mut segment_quant = [0i32, ..4];
+    let mut segment_filter = [0i32, ..4];
+    let mut segment_probabilities = [255u8, ..3];
+    if header.read_flag() {
+        segmentation = true;
+        update_segment_map = header.read_flag();
+        if header.read_flag() {
+            absolute_segment_values = header.read_flag();
+            for i in range(0u, 4) {
+                segment_quant[i] = header.read_optional_signed(7);
+            }
+            for i in range(0u, 4) {
+                segment_filter[i] = header.read_optional_signed(6);
+            }
+        }
+        if update_segment_map {
+            for i in range(0u, 3) {
+                if header.read_flag() {
+                    segment_probabilities[i] = header.read_literal(8) as u8;
+                }
+            }
+        }
+    }
+
+    let simple_filter = header.read_flag();
+    let filter_level = header.read_literal(6) as i32;
+    let sharpness = header.read_literal(3) as i32;
+    let mut reference_delta = 0;
+    let mut mode_delta = 0;
+    let filter_deltas = header.read_flag();
+    if filter_deltas && header.read_flag() {
+        // Key frames only use the deltas for intra prediction and for B_PRED.
+        for i in range(0u, 4) {
+            let delta = header.read_optional_signed(6);
+            if i == 0 {
+                reference_delta = delta;
+            }
+        }
+        for i in range(0u, 4) {
+            let delta = header.read_optional_signed(6);
+            if i == 0 {
+                mode_delta = delta;
+            }
+        }
+    }
+
+    let num_partitions = 1u << header.read_literal(2) as uint;
+
+    let base_quant = header.read_literal(7) as i32;
+    let y_dc_delta = header.read_optional_signed(4);
+    let y2_dc_delta = header.read_optional_signed(4);
+    let y2_ac_delta = header.read_optional_signed(4);
+    let uv_dc_delta = header.read_optional_signed(4);
+    let uv_ac_delta = header.read_optional_signed(4);
+
+    let mut quants = vec!();
+    for segment in range(0u, 4) {
+        let quant = if !segmentation {
+            base_quant
+        } else if absolute_segment_values {
+            segment_quant[segment]
+        } else {
+            base_quant + segment_quant[segment]
+        };
+        let dc = |index: i32| DC_QUANT[clamp(index, 0, 127) as uint];
+        let ac = |index: i32| AC_QUANT[clamp(index, 0, 127) as uint];
+        quants.push(Quant {
+            y_dc: dc(quant + y_dc_delta),
+            y_ac: ac(quant),
+            y2_dc: dc(quant + y2_dc_delta) * 2,
+            y2_ac: max(ac(quant + y2_ac_delta) * 155 / 100, 8),
+            uv_dc: min(dc(quant + uv_dc_delta), 132),
+            uv_ac: ac(quant + uv_ac_delta),
+        });
+    }
+
+    // Skip the flag for whether to keep the probabilities, since there's only one frame.
+    header.read_flag();
+
+    let mut coeff_probabilities = COEFF_PROBS;
+    for i in range(0u, 4) {
+        for j in range(0u, 8) {
+            for k in range(0u, 3) {
+                for l in range(0u, 11) {
+                    if header.read_bool(COEFF_UPDATE_PROBS[i][j][k][l]) {
+                        coeff_probabilities[i][j][k][l] = header.read_literal(8) as u8;
+                    }
+                }
+            }
+        }
+    }
+
+    let skip_probability = if header.read_flag() {
+        Some(header.read_literal(8) as u8)
+    } else {
+        None
+    };
+
+    // The coefficient partitions follow the first partition, preceded by all but the last one's
+    // sizes.
+    let rest = data.slice_from(first_partition_size);
+    let sizes_length = 3 * (num_partitions - 1);
+    if rest.len() < sizes_length {
+        return Err(());
+    }
+    let mut partitions = vec!();
+    let mut offset = sizes_length;
+    for partition in range(0, num_partitions) {
+        let size = if partition < num_partitions - 1 {
+            let size = rest.slice_from(3 * partition);
+            size[0] as uint | ((size[1] as uint) << 8) | ((size[2] as uint) << 16)
+        } else if offset < rest.len() {
+            rest.len() - offset
+        } else {
+            0
+        };
+        let start = min(offset, rest.len());
+        let end = min(offset + size, rest.len());
+        partitions.push(BoolDecoder::new(rest.slice(start, end)));
+        offset += size;
+    }
+
+    let mb_width = (width + 15) / 16;
+    let mb_height = (height + 15) / 16;
+    let y_stride = mb_width * 16;
+    let uv_stride = mb_width * 8;
+    let mut frame = Frame {
+        width: width,
+        height: height,
+        rows: height,
+        y_stride: y_stride,
+        uv_stride: uv_stride,
+        y: Vec::from_elem(y_stride * mb_height * 16, 0u8),
+        u: Vec::from_elem(uv_stride * mb_height * 8, 0u8),
+        v: Vec::from_elem(uv_stride * mb_height * 8, 0u8),
+    };
+
+    let mut filters = vec!();
+    // Whether the neighbouring blocks had coefficients, which is the context for reading the
+    // first coefficient of each block: four luma blocks, two blocks of each chroma plane and the
+    // Y2 block, for each column and for the macroblock to the left.
+    let mut above_nonzero = Vec::from_elem(mb_width * 9, false);
+    let mut above_bmodes = Vec::from_elem(mb_width * 4, B_DC_PRED);
+
+    let mut mb_rows = mb_height;
+    for mby in range(0, mb_height) {
+        let mut left_nonzero = [false, ..9];
+        let mut left_bmodes = [B_DC_PRED, ..4];
+        for mbx in range(0, mb_width) {
+            // The macroblock header comes from the first partition.
+            let segment = if update_segment_map {
+                header.read_tree(&SEGMENT_TREE, &segment_probabilities) as uint
+            } else {
+                0
+            };
+            let skip = match skip_probability {
+                Some(probability) => header.read_bool(probability),
+                None => false,
+            };
+            let ymode = header.read_tree(&YMODE_TREE, &YMODE_PROBS);
+            let mut bmodes = [B_DC_PRED, ..16];
+            if ymode == B_PRED {
+                for by in range(0u, 4) {
+                    for bx in range(0u, 4) {
+                        let above = if by == 0 {
+                            above_bmodes[mbx * 4 + bx]
+                        } else {
+                            bmodes[(by - 1) * 4 + bx]
+                        };
+                        let left = if bx == 0 { left_bmodes[by] } else { bmodes[by * 4 + bx - 1] };
+                        let probabilities = &KEYFRAME_BPRED_MODE_PROBS[above as uint][left as uint];
+                        bmodes[by * 4 + bx] = header.read_tree(&BMODE_TREE, probabilities);
+                    }
+                }
+                for i in range(0u, 4) {
+                    above_bmodes[mbx * 4 + i] = bmodes[12 + i];
+                    left_bmodes[i] = bmodes[i * 4 + 3];
+                }
+            } else {
+                // Later subblocks use the modes implied by whole macroblock prediction as
+                // context.
+                let implied = match ymode {
+                    V_PRED => B_VE_PRED,
+                    H_PRED => B_HE_PRED,
+                    TM_PRED => B_TM_PRED,
+                    _ => B_DC_PRED,
+                };
+                for i in range(0u, 4) {
+                    above_bmodes[mbx * 4 + i] = implied;
+                    left_bmodes[i] = implied;
+                }
+            }
+            let info = MacroBlockInfo {
+                ymode: ymode,
+                uvmode: header.read_tree(&UV_MODE_TREE, &UV_MODE_PROBS),
+                bmodes: bmodes,
+            };
+
+            // The coefficients come from the partition for this row. Blocks 0 to 15 are luma,
+            // 16 to 23 chroma and 24 is Y2, which holds the luma DC coefficients when the
+            // macroblock isn't split into subblocks.
+            let mut coeffs = Vec::from_elem(25 * 16, 0i32);
+            let mut has_coeffs = false;
+            let has_y2 = ymode != B_PRED;
+            let quant = &quants[segment];
+            let tokens = &mut partitions[mby % num_partitions];
+            let above = above_nonzero.slice_mut(mbx * 9, mbx * 9 + 9);
+            if !skip {
+                let mut first = 0;
+                let mut plane = 3;
+                if has_y2 {
+                    let context = above[8] as uint + left_nonzero[8] as uint;
+                    let nonzero = read_coefficients(tokens, &coeff_probabilities[1], context, 0,
+                                                    quant.y2_dc, quant.y2_ac,
+                                                    coeffs.slice_mut(384, 400));
+                    above[8] = nonzero > 0;
+                    left_nonzero[8] = nonzero > 0;
+                    let dc = inverse_wht(coeffs.slice(384, 400));
+                    for i in range(0u, 16) {
+                        coeffs[i * 16] = dc[i];
+                        has_coeffs = has_coeffs || dc[i] != 0;
+                    }
+                    first = 1;
+                    plane = 0;
+                }
+                for by in range(0u, 4) {
+                    for bx in range(0u, 4) {
+                        let context = above[bx] as uint + left_nonzero[by] as uint;
+                        let block = by * 4 + bx;
+                        let nonzero = read_coefficients(tokens, &coeff_probabilities[plane],
+                                                        context, first, quant.y_dc, quant.y_ac,
+                                                        coeffs.slice_mut(block * 16,
+                                                                         block * 16 + 16));
+                        above[bx] = nonzero > first;
+                        left_nonzero[by] = nonzero > first;
+                        has_coeffs = has_coeffs || nonzero > first;
+                    }
+                }
+                for plane in range(0u, 2) {
+                    for by in range(0u, 2) {
+                        for bx in range(0u, 2) {
+                            let above_index = 4 + plane * 2 + bx;
+                            let left_index = 4 + plane * 2 + by;
+                            let context = above[above_index] as uint +
+                                          left_nonzero[left_index] as uint;
+                            let block = 16 + plane * 4 + by * 2 + bx;
+                            let nonzero = read_coefficients(tokens, &coeff_probabilities[2],
+                                                            context, 0, quant.uv_dc, quant.uv_ac,
+                                                            coeffs.slice_mut(block * 16,
+                                                                             block * 16 + 16));
+                            above[above_index] = nonzero > 0;
+                            left_nonzero[left_index] = nonzero > 0;
+                            has_coeffs = has_coeffs || nonzero > 0;
+                        }
+                    }
+                }
+            } else {
+                // Skipped macroblocks leave the Y2 context alone unless they have a Y2 block.
+                let contexts = if has_y2 { 9 } else { 8 };
+                for i in range(0, contexts) {
+                    above[i] = false;
+                    left_nonzero[i] = false;
+                }
+            }
+
+            reconstruct(&mut frame, mbx, mby, &info, coeffs.as_slice());
+
+            let mut level = filter_level;
+            if segmentation {
+                level = if absolute_segment_values {
+                    segment_filter[segment]
+                } else {
+                    level + segment_filter[segment]
+                };
+            }
+            if filter_deltas {
+                level += reference_delta;
+                if ymode == B_PRED {
+                    level += mode_delta;
+                }
+            }
+            let level = clamp(level, 0, 63);
+            let mut interior_limit = level;
+            if sharpness > 0 {
+                interior_limit >>= if sharpness > 4 { 2 } else { 1 };
+                interior_limit = min(interior_limit, 9 - sharpness);
+            }
+            let interior_limit = max(interior_limit, 1);
+            filters.push(FilterParams {
+                limit: if level > 0 { 2 * level + interior_limit } else { 0 },
+                interior_limit: interior_limit,
+                hev_threshold: if level >= 40 { 2 } else if level >= 15 { 1 } else { 0 },
+                inner: ymode == B_PRED || has_coeffs,
+            });
+        }
+
+        if partitions[mby % num_partitions].exhausted() || header.exhausted() {
+            // The data was truncated, so this row is garbage.
+            if mby == 0 {
+                return Err(());
+            }
+            mb_rows = mby;
+            frame.rows = mby * 16;
+            break;
+        }
+    }
+
+    for mby in range(0, mb_rows) {
+        for mbx in range(0, mb_width) {
+            let filter = &filters[mby * mb_width + mbx];
+            if filter.limit == 0 {
+                continue;
+            }
+            if simple_filter {
+                simple_filter_macroblock(&mut frame, mbx, mby, filter);
+            } else {
+                normal_filter_macroblock(&mut frame, mbx, mby, filter);
+            }
+        }
+    }
+    Ok(frame)
+}
+
+/// Reads the tokens of a block into dequantized coefficients, starting at `first`. Returns the
+/// index past the last token read, which is `first` if the block is empty.
+fn read_coefficients(decoder: &mut BoolDecoder, probabilities: &[[[u8, ..11], ..3], ..8],
+                     context: uint, first: uint, dc_quant: i32, ac_quant: i32, out: &mut [i32])
+                     -> uint {
+    let mut i = first;
+    let mut p = &probabilities[COEFF_BANDS[i]][context];
+    while i < 16 {
+        if !decoder.read_bool(p[0]) {
+            return i;
+        }
+        while !decoder.read_bool(p[1]) {
+            i += 1;
+            if i == 16 {
+                return 16;
+            }
+            p = &probabilities[COEFF_BANDS[i]][0];
+        }
+        let (value, next_context) = if !decoder.read_bool(p[2]) {
+            (1, 1)
+        } else if !decoder.read_bool(p[3]) {
+            if !decoder.read_bool(p[4]) {
+                (2, 2)
+            } else {
+                (3 + decoder.read_bool(p[5]) as i32, 2)
+            }
+        } else {
+            let category = if !decoder.read_bool(p[6]) {
+                decoder.read_bool(p[7]) as uint
+            } else {
+                let high = decoder.read_bool(p[8]) as uint;
+                let low = decoder.read_bool(p[9 + high]) as uint;
+                2 + 2 * high + low
+            };
+            let mut extra = 0;
+            for &probability in DCT_CAT_PROBS[category].iter() {
+                extra = extra * 2 + decoder.read_bool(probability) as i32;
+            }
+            (DCT_CAT_BASE[category] + extra, 2)
+        };
+        let value = if decoder.read_flag() { -value } else { value };
+        out[ZIGZAG[i]] = value * if i > 0 { ac_quant } else { dc_quant };
+        i += 1;
+        p = &probabilities[COEFF_BANDS[i]][next_context];
+    }
+    16
+}
+
+/// The inverse Walsh-Hadamard transform, which recovers the luma DC coefficients from Y2.
+fn inverse_wht(input: &[i32]) -> [i32, ..16] {
+    let mut temp = [0i32, ..16];
+    for i in range(0u, 4) {
+        let a0 = input[i] + input[12 + i];
+        let a1 = input[4 + i] + input[8 + i];
+        let a2 = input[4 + i] - input[8 + i];
+        let a3 = input[i] - input[12 + i];
+        temp[i] = a0 + a1;
+        temp[8 + i] = a0 - a1;
+        temp[4 + i] = a3 + a2;
+        temp[12 + i] = a3 - a2;
+    }
+    let mut out = [0i32, ..16];
+    for i in range(0u, 4) {
+        let dc = temp[i * 4] + 3;
+        let a0 = dc + temp[i * 4 + 3];
+        let a1 = temp[i * 4 + 1] + temp[i * 4 + 2];
+        let a2 = temp[i * 4 + 1] - temp[i * 4 + 2];
+        let a3 = dc - temp[i * 4 + 3];
+        out[i * 4] = (a0 + a1) >> 3;
+        out[i * 4 + 1] = (a3 + a2) >> 3;
+        out[i * 4 + 2] = (a0 - a1) >> 3;
+        out[i * 4 + 3] = (a3 - a2) >> 3;
+    }
+    out
+}
+
+fn mul1(a: i32) -> i32 {
+    ((a * 20091) >> 16) + a
+}
+
+fn mul2(a: i32) -> i32 {
+    (a * 35468) >> 16
+}
+
+/// Adds the inverse DCT of `coeffs` to the 4x4 block at `offset`.
+fn add_idct(plane: &mut [u8], offset: uint, stride: uint, coeffs: &[i32]) {
+    let mut temp = [0i32, ..16];
+    for i in range(0u, 4) {
+        let a = coeffs[i] + coeffs[8 + i];
+        let b = coeffs[i] - coeffs[8 + i];
+        let c = mul2(coeffs[4 + i]) - mul1(coeffs[12 + i]);
+        let d = mul1(coeffs[4 + i]) + mul2(coeffs[12 + i]);
+        temp[i * 4] = a + d;
+        temp[i * 4 + 1] = b + c;
+        temp[i * 4 + 2] = b - c;
+        temp[i * 4 + 3] = a - d;
+    }
+    for i in range(0u, 4) {
+        let dc = temp[i] + 4;
+        let a = dc + temp[8 + i];
+        let b = dc - temp[8 + i];
+        let c = mul2(temp[4 + i]) - mul1(temp[12 + i]);
+        let d = mul1(temp[4 + i]) + mul2(temp[12 + i]);
+        let row = offset + i * stride;
+        let values = [a + d, b + c, b - c, a - d];
+        for x in range(0u, 4) {
+            plane[row + x] = clamp255(plane[row + x] as i32 + (values[x] >> 3));
+        }
+    }
+}
+
+/// Returns the pixels bordering a block: `above_length` pixels of the row above, preceded by the
+/// top left corner, and the column to the left. Missing edges take the values the spec gives.
+fn edges(plane: &[u8], stride: uint, x: uint, y: uint, size: uint, above_length: uint)
+         -> (Vec<u8>, Vec<u8>) {
+    let mut above = Vec::from_elem(above_length + 1, 127u8);
+    let mut left = Vec::from_elem(size, 129u8);
+    if y > 0 {
+        let row = (y - 1) * stride;
+        above[0] = if x > 0 { plane[row + x - 1] } else { 129 };
+        for i in range(0, above_length) {
+            above[i + 1] = plane[row + min(x + i, stride - 1)];
+        }
+    }
+    if x > 0 {
+        for i in range(0, size) {
+            left[i] = plane[(y + i) * stride + x - 1];
+        }
+    }
+    (above, left)
+}
+
+/// Predicts a whole 16x16 luma or 8x8 chroma block.
+fn predict_block(plane: &mut [u8], stride: uint, x: uint, y: uint, size: uint, mode: u8) {
+    let (above, left) = edges(plane, stride, x, y, size, size);
+    let shift = if size == 16 { 4 } else { 3 };
+    let dc = {
+        let above_sum = above.slice_from(1).iter().fold(0u, |sum, &value| sum + value as uint);
+        let left_sum = left.iter().fold(0u, |sum, &value| sum + value as uint);
+        match (y > 0, x > 0) {
+            (true, true) => (above_sum + left_sum + size) >> (shift + 1),
+            (true, false) => (above_sum + (size >> 1)) >> shift,
+            (false, true) => (left_sum + (size >> 1)) >> shift,
+            (false, false) => 128,
+        }
+    };
+    for j in range(0, size) {
+        for i in range(0, size) {
+            plane[(y + j) * stride + x + i] = match mode {
+                DC_PRED => dc as u8,
+                V_PRED => above[i + 1],
+                H_PRED => left[j],
+                _ => clamp255(left[j] as i32 + above[i + 1] as i32 - above[0] as i32),
+            };
+        }
+    }
+}
+
+fn average2(a: i32, b: i32) -> i32 {
+    (a + b + 1) >> 1
+}
+
+fn average3(a: i32, b: i32, c: i32) -> i32 {
+    (a + 2 * b + c + 2) >> 2
+}
+
+/// Predicts a 4x4 luma subblock. `above_right` replaces the four pixels above and to the right,
+/// for subblocks on the right of a macroblock.
+fn predict_subblock(plane: &mut [u8], stride: uint, x: uint, y: uint, mode: u8,
+                    above_right: Option<[u8, ..4]>) {
+    let (mut above, left) = edges(plane, stride, x, y, 4, 8);
+    match above_right {
+        Some(above_right) => {
+            for i in range(0u, 4) {
+                above[5 + i] = above_right[i];
+            }
+        }
+        None => {}
+    }
+    let p = above[0] as i32;
+    let a: Vec<i32> = above.slice_from(1).iter().map(|&value| value as i32).collect();
+    let l: Vec<i32> = left.iter().map(|&value| value as i32).collect();
+    // The left column from the bottom up, the corner, then the row above.
+    let e = [l[3], l[2], l[1], l[0], p, a[0], a[1], a[2], a[3]];
+    let mut b = [[0i32, ..4], ..4];
+    match mode {
+        B_DC_PRED => {
+            let mut sum = 4;
+            for i in range(0u, 4) {
+                sum += a[i] + l[i];
+            }
+            b = [[sum >> 3, ..4], ..4];
+        }
+        B_TM_PRED => {
+            for r in range(0u, 4) {
+                for c in range(0u, 4) {
+                    b[r][c] = clamp255(l[r] + a[c] - p) as i32;
+                }
+            }
+        }
+        B_VE_PRED => {
+            for c in range(0u, 4) {
+                let value = average3(if c == 0 { p } else { a[c - 1] }, a[c], a[c + 1]);
+                for r in range(0u, 4) {
+                    b[r][c] = value;
+                }
+            }
+        }
+        B_HE_PRED => {
+            let rows = [average3(p, l[0], l[1]), average3(l[0], l[1], l[2]),
+                        average3(l[1], l[2], l[3]), average3(l[2], l[3], l[3])];
+            for r in range(0u, 4) {
+                b[r] = [rows[r], ..4];
+            }
+        }
+        B_LD_PRED => {
+            for r in range(0u, 4) {
+                for c in range(0u, 4) {
+                    let k = r + c;
+                    b[r][c] = if k < 6 {
+                        average3(a[k], a[k + 1], a[k + 2])
+                    } else {
+                        average3(a[6], a[7], a[7])
+                    };
+                }
+            }
+        }
+        B_RD_PRED => {
+            for r in range(0u, 4) {
+                for c in range(0u, 4) {
+                    b[r][c] = average3(e[3 + c - r], e[4 + c - r], e[5 + c - r]);
+                }
+            }
+        }
+        B_VR_PRED => {
+            b[3][0] = average3(e[1], e[2], e[3]);
+            b[2][0] = average3(e[2], e[3], e[4]);
+            b[3][1] = average3(e[3], e[4], e[5]);
+            b[1][0] = b[3][1];
+            b[2][1] = average2(e[4], e[5]);
+            b[0][0] = b[2][1];
+            b[3][2] = average3(e[4], e[5], e[6]);
+            b[1][1] = b[3][2];
+            b[2][2] = average2(e[5], e[6]);
+            b[0][1] = b[2][2];
+            b[3][3] = average3(e[5], e[6], e[7]);
+            b[1][2] = b[3][3];
+            b[2][3] = average2(e[6], e[7]);
+            b[0][2] = b[2][3];
+            b[1][3] = average3(e[6], e[7], e[8]);
+            b[0][3] = average2(e[7], e[8]);
+        }
+        B_VL_PRED => {
+            b[0][0] = average2(a[0], a[1]);
+            b[1][0] = average3(a[0], a[1], a[2]);
+            b[2][0] = average2(a[1], a[2]);
+            b[0][1] = b[2][0];
+            b[1][1] = average3(a[1], a[2], a[3]);
+            b[3][0] = b[1][1];
+            b[2][1] = average2(a[2], a[3]);
+            b[0][2] = b[2][1];
+            b[3][1] = average3(a[2], a[3], a[4]);
+            b[1][2] = b[3][1];
+            b[2][2] = average2(a[3], a[4]);
+            b[0][3] = b[2][2];
+            b[3][2] = average3(a[3], a[4], a[5]);
+            b[1][3] = b[3][2];
+            b[2][3] = average3(a[4], a[5], a[6]);
+            b[3][3] = average3(a[5], a[6], a[7]);
+        }
+        B_HD_PRED => {
+            b[3][0] = average2(e[0], e[1]);
+            b[3][1] = average3(e[0], e[1], e[2]);
+            b[2][0] = average2(e[1], e[2]);
+            b[3][2] = b[2][0];
+            b[2][1] = average3(e[1], e[2], e[3]);
+            b[3][3] = b[2][1];
+            b[2][2] = average2(e[2], e[3]);
+            b[1][0] = b[2][2];
+            b[2][3] = average3(e[2], e[3], e[4]);
+            b[1][1] = b[2][3];
+            b[1][2] = average2(e[3], e[4]);
+            b[0][0] = b[1][2];
+            b[1][3] = average3(e[3], e[4], e[5]);
+            b[0][1] = b[1][3];
+            b[0][2] = average3(e[4], e[5], e[6]);
+            b[0][3] = average3(e[5], e[6], e[7]);
+        }
+        _ => {
+            // B_HU_PRED
+            b = [[l[3], ..4], ..4];
+            b[0][0] = average2(l[0], l[1]);
+            b[0][1] = average3(l[0], l[1], l[2]);
+            b[0][2] = average2(l[1], l[2]);
+            b[1][0] = b[0][2];
+            b[0][3] = average3(l[1], l[2], l[3]);
+            b[1][1] = b[0][3];
+            b[1][2] = average2(l[2], l[3]);
+            b[2][0] = b[1][2];
+            b[1][3] = average3(l[2], l[3], l[3]);
+            b[2][1] = b[1][3];
+        }
+    }
+    for r in range(0u, 4) {
+        for c in range(0u, 4) {
+            plane[(y + r) * stride + x + c] = b[r][c] as u8;
+        }
+    }
+}
+
+/// Predicts a macroblock and adds its residue.
+fn reconstruct(frame: &mut Frame, mbx: uint, mby: uint, info: &MacroBlockInfo, coeffs: &[i32]) {
+    let y_stride = frame.y_stride;
+    let (x0, y0) = (mbx * 16, mby * 16);
+    if info.ymode == B_PRED {
+        // Subblocks on the right of the macroblock use the pixels above and to the right of the
+        // macroblock, since the ones beside them haven't been decoded yet.
+        let mut above_right = [127u8, ..4];
+        if mby > 0 {
+            let row = (y0 - 1) * y_stride;
+            for i in range(0u, 4) {
+                above_right[i] = if x0 + 16 < y_stride {
+                    frame.y[row + x0 + 16 + i]
+                } else {
+                    frame.y[row + x0 + 15]
+                };
+            }
+        }
+        for by in range(0u, 4) {
+            for bx in range(0u, 4) {
+                let block = by * 4 + bx;
+                let (x, y) = (x0 + bx * 4, y0 + by * 4);
+                predict_subblock(frame.y.as_mut_slice(), y_stride, x, y, info.bmodes[block],
+                                 if bx == 3 { Some(above_right) } else { None });
+                add_idct(frame.y.as_mut_slice(), y * y_stride + x, y_stride,
+                         coeffs.slice(block * 16, block * 16 + 16));
+            }
+        }
+    } else {
+        predict_block(frame.y.as_mut_slice(), y_stride, x0, y0, 16, info.ymode);
+        for block in range(0u, 16) {
+            let (x, y) = (x0 + (block % 4) * 4, y0 + (block / 4) * 4);
+            add_idct(frame.y.as_mut_slice(), y * y_stride + x, y_stride,
+                     coeffs.slice(block * 16, block * 16 + 16));
+        }
+    }
+
+    let uv_stride = frame.uv_stride;
+    reconstruct_chroma(frame.u.as_mut_slice(), uv_stride, mbx, mby, info.uvmode,
+                       coeffs.slice(16 * 16, 20 * 16));
+    reconstruct_chroma(frame.v.as_mut_slice(), uv_stride, mbx, mby, info.uvmode,
+                       coeffs.slice(20 * 16, 24 * 16));
+}
+
+fn reconstruct_chroma(plane: &mut [u8], stride: uint, mbx: uint, mby: uint, mode: u8,
+                      coeffs: &[i32]) {
+    let (x0, y0) = (mbx * 8, mby * 8);
+    predict_block(plane, stride, x0, y0, 8, mode);
+    for block in range(0u, 4) {
+        let (x, y) = (x0 + (block % 2) * 4, y0 + (block / 2) * 4);
+        add_idct(plane, y * stride + x, stride, coeffs.slice(block * 16, block * 16 + 16));
+    }
+}
+
+fn difference(a: u8, b: u8) -> i32 {
+    if a > b { (a - b) as i32 } else { (b - a) as i32 }
+}
+
+fn clamp_signed(value: i32) -> i32 {
+    clamp(value, -128, 127)
+}
+
+/// Adjusts the pixel either side of the edge before `p`, taking the outer pixels into account.
+/// `step` is the distance between pixels across the edge.
+fn filter2(buffer: &mut [u8], p: uint, step: uint) {
+    let (p1, p0) = (buffer[p - 2 * step] as i32, buffer[p - step] as i32);
+    let (q0, q1) = (buffer[p] as i32, buffer[p + step] as i32);
+    let a = 3 * (q0 - p0) + clamp_signed(p1 - q1);
+    let a1 = clamp_signed(a + 4) >> 3;
+    let a2 = clamp_signed(a + 3) >> 3;
+    buffer[p - step] = clamp255(p0 + a2);
+    buffer[p] = clamp255(q0 - a1);
+}
+
+/// Adjusts the two pixels either side of an edge between subblocks.
+fn filter4(buffer: &mut [u8], p: uint, step: uint) {
+    let (p1, p0) = (buffer[p - 2 * step] as i32, buffer[p - step] as i32);
+    let (q0, q1) = (buffer[p] as i32, buffer[p + step] as i32);
+    let a = 3 * (q0 - p0);
+    let a1 = clamp_signed(a + 4) >> 3;
+    let a2 = clamp_signed(a + 3) >> 3;
+    let a3 = (a1 + 1) >> 1;
+    buffer[p - 2 * step] = clamp255(p1 + a3);
+    buffer[p - step] = clamp255(p0 + a2);
+    buffer[p] = clamp255(q0 - a1);
+    buffer[p + step] = clamp255(q1 - a3);
+}
+
+/// Adjusts the three pixels either side of an edge between macroblocks.
+fn filter6(buffer: &mut [u8], p: uint, step: uint) {
+    let (p2, p1, p0) = (buffer[p - 3 * step] as i32, buffer[p - 2 * step] as i32,
+                        buffer[p - step] as i32);
+    let (q0, q1, q2) = (buffer[p] as i32, buffer[p + step] as i32, buffer[p + 2 * step] as i32);
+    let a = clamp_signed(3 * (q0 - p0) + clamp_signed(p1 - q1));
+    let a1 = (27 * a + 63) >> 7;
+    let a2 = (18 * a + 63) >> 7;
+    let a3 = (9 * a + 63) >> 7;
+    buffer[p - 3 * step] = clamp255(p2 + a3);
+    buffer[p - 2 * step] = clamp255(p1 + a2);
+    buffer[p - step] = clamp255(p0 + a1);
+    buffer[p] = clamp255(q0 - a1);
+    buffer[p + step] = clamp255(q1 - a2);
+    buffer[p + 2 * step] = clamp255(q2 - a3);
+}
+
+fn high_edge_variance(buffer: &[u8], p: uint, step: uint, threshold: i32) -> bool {
+    difference(buffer[p - 2 * step], buffer[p - step]) > threshold ||
+        difference(buffer[p + step], buffer[p]) > threshold
+}
+
+fn simple_threshold(buffer: &[u8], p: uint, step: uint, limit: i32) -> bool {
+    4 * difference(buffer[p - step], buffer[p]) +
+        difference(buffer[p - 2 * step], buffer[p + step]) <= 2 * limit + 1
+}
+
+fn normal_threshold(buffer: &[u8], p: uint, step: uint, limit: i32, interior_limit: i32) -> bool {
+    simple_threshold(buffer, p, step, limit) &&
+        difference(buffer[p - 4 * step], buffer[p - 3 * step]) <= interior_limit &&
+        difference(buffer[p - 3 * step], buffer[p - 2 * step]) <= interior_limit &&
+        difference(buffer[p - 2 * step], buffer[p - step]) <= interior_limit &&
+        difference(buffer[p + 3 * step], buffer[p + 2 * step]) <= interior_limit &&
+        difference(buffer[p + 2 * step], buffer[p + step]) <= interior_limit &&
+        difference(buffer[p + step], buffer[p]) <= interior_limit
+}
+
+/// Filters `length` pixels along the edge starting at `p`. `step` crosses the edge and `advance`
+/// moves along it.
+fn filter_edge(buffer: &mut [u8], p: uint, step: uint, advance: uint, length: uint,
+               filter: &FilterParams, limit: i32, macroblock_edge: bool) {
+    for i in range(0, length) {
+        let q = p + i * advance;
+        if !normal_threshold(buffer, q, step, limit, filter.interior_limit) {
+            continue;
+        }
+        if high_edge_variance(buffer, q, step, filter.hev_threshold) {
+            filter2(buffer, q, step);
+        } else if macroblock_edge {
+            filter6(buffer, q, step);
+        } else {
+            filter4(buffer, q, step);
+        }
+    }
+}
+
+fn normal_filter_macroblock(frame: &mut Frame, mbx: uint, mby: uint, filter: &FilterParams) {
+    let (y_stride, uv_stride) = (frame.y_stride, frame.uv_stride);
+    let y = mby * 16 * y_stride + mbx * 16;
+    let uv = mby * 8 * uv_stride + mbx * 8;
+    let edge_limit = filter.limit + 4;
+    if mbx > 0 {
+        filter_edge(frame.y.as_mut_slice(), y, 1, y_stride, 16, filter, edge_limit, true);
+        filter_edge(frame.u.as_mut_slice(), uv, 1, uv_stride, 8, filter, edge_limit, true);
+        filter_edge(frame.v.as_mut_slice(), uv, 1, uv_stride, 8, filter, edge_limit, true);
+    }
+    if filter.inner {
+        for i in range(1u, 4) {
+            filter_edge(frame.y.as_mut_slice(), y + i * 4, 1, y_stride, 16, filter, filter.limit,
+                        false);
+        }
+        filter_edge(frame.u.as_mut_slice(), uv + 4, 1, uv_stride, 8, filter, filter.limit, false);
+        filter_edge(frame.v.as_mut_slice(), uv + 4, 1, uv_stride, 8, filter, filter.limit, false);
+    }
+    if mby > 0 {
+        filter_edge(frame.y.as_mut_slice(), y, y_stride, 1, 16, filter, edge_limit, true);
+        filter_edge(frame.u.as_mut_slice(), uv, uv_stride, 1, 8, filter, edge_limit, true);
+        filter_edge(frame.v.as_mut_slice(), uv, uv_stride, 1, 8, filter, edge_limit, true);
+    }
+    if filter.inner {
+        for i in range(1u, 4) {
+            filter_edge(frame.y.as_mut_slice(), y + i * 4 * y_stride, y_stride, 1, 16, filter,
+                        filter.limit, false);
+        }
+        filter_edge(frame.u.as_mut_slice(), uv + 4 * uv_stride, uv_stride, 1, 8, filter,
+                    filter.limit, false);
+        filter_edge(frame.v.as_mut_slice(), uv + 4 * uv_stride, uv_stride, 1, 8, filter,
+                    filter.limit, false);
+    }
+}
+
+fn simple_filter_edge(buffer: &mut [u8], p: uint, step: uint, advance: uint, limit: i32) {
+    for i in range(0u, 16) {
+        let q = p + i * advance;
+        if simple_threshold(buffer, q, step, limit) {
+            filter2(buffer, q, step);
+        }
+    }
+}
+
+/// The simple filter only applies to luma.
+fn simple_filter_macroblock(frame: &mut Frame, mbx: uint, mby: uint, filter: &FilterParams) {
+    let y_stride = frame.y_stride;
+    let y = mby * 16 * y_stride + mbx * 16;
+    if mbx > 0 {
+        simple_filter_edge(frame.y.as_mut_slice(), y, 1, y_stride, filter.limit + 4);
+    }
+    if filter.inner {
+        for i in range(1u, 4) {
+            simple_filter_edge(frame.y.as_mut_slice(), y + i * 4, 1, y_stride, filter.limit);
+        }
+    }
+    if mby > 0 {
+        simple_filter_edge(frame.y.as_mut_slice(), y, y_stride, 1, filter.limit + 4);
+    }
+    if filter.inner {
+        for i in range(1u, 4) {
+            simple_filter_edge(frame.y.as_mut_slice(), y + i * 4 * y_stride, y_stride, 1,
+                               filter.limit);
+        }
+    }
+}
+
+fn multiply_high(value: i32, coefficient: i32) -> i32 {
+    (value * coefficient) >> 8
+}
+
+fn clip_rgb(value: i32) -> u8 {
+    if value & !16383 == 0 {
+        (value >> 6) as u8
+    } else if value < 0 {
+        0
+    } else {
+        255
+    }
+}
+
+/// Writes the opaque RGBA pixel at (`x`, `row`), given its packed chroma samples.
+fn write_pixel(frame: &Frame, out: &mut [u8], row: uint, x: uint, uv: u32) {
+    let y = frame.y[row * frame.y_stride + x] as i32;
+    let (u, v) = ((uv & 0xff) as i32, ((uv >> 16) & 0xff) as i32);
+    let offset = (row * frame.width + x) * 4;
+    out[offset] = clip_rgb(multiply_high(y, 19077) + multiply_high(v, 26149) - 14234);
+    out[offset + 1] = clip_rgb(multiply_high(y, 19077) - multiply_high(u, 6419) -
+                               multiply_high(v, 13320) + 8708);
+    out[offset + 2] = clip_rgb(multiply_high(y, 19077) + multiply_high(u, 33050) - 17685);
+    out[offset + 3] = 255;
+}
+
+/// Loads the chroma samples at (`x`, `row`), packed so that both can be interpolated at once.
+fn load_uv(frame: &Frame, row: uint, x: uint) -> u32 {
+    let index = row * frame.uv_stride + x;
+    frame.u[index] as u32 | ((frame.v[index] as u32) << 16)
+}
+
+/// Converts a luma row, and the one below it if there is one, interpolating the chroma between
+/// the chroma rows either side.
+fn upsample_rows(frame: &Frame, top_row: uint, bottom_row: Option<uint>, top_uv: uint,
+                 current_uv: uint, out: &mut [u8]) {
+    let width = frame.width;
+    let mut top_left = load_uv(frame, top_uv, 0);
+    let mut left = load_uv(frame, current_uv, 0);
+    write_pixel(frame, out, top_row, 0, (3 * top_left + left + 0x00020002) >> 2);
+    match bottom_row {
+        Some(bottom_row) => write_pixel(frame, out, bottom_row, 0,
+                                        (3 * left + top_left + 0x00020002) >> 2),
+        None => {}
+    }
+    for x in range(1, (width - 1) / 2 + 1) {
+        let top = load_uv(frame, top_uv, x);
+        let current = load_uv(frame, current_uv, x);
+        let average = top_left + top + left + current + 0x00080008;
+        let diagonal_12 = (average + 2 * (top + left)) >> 3;
+        let diagonal_03 = (average + 2 * (top_left + current)) >> 3;
+        write_pixel(frame, out, top_row, 2 * x - 1, ((diagonal_12 + top_left) >> 1) & 0x00ff00ff);
+        write_pixel(frame, out, top_row, 2 * x, ((diagonal_03 + top) >> 1) & 0x00ff00ff);
+        match bottom_row {
+            Some(bottom_row) => {
+                write_pixel(frame, out, bottom_row, 2 * x - 1,
+                            ((diagonal_03 + left) >> 1) & 0x00ff00ff);
+                write_pixel(frame, out, bottom_row, 2 * x,
+                            ((diagonal_12 + current) >> 1) & 0x00ff00ff);
+            }
+            None => {}
+        }
+        top_left = top;
+        left = current;
+    }
+    if width & 1 == 0 {
+        write_pixel(frame, out, top_row, width - 1, (3 * top_left + left + 0x00020002) >> 2);
+        match bottom_row {
+            Some(bottom_row) => write_pixel(frame, out, bottom_row, width - 1,
+                                            (3 * left + top_left + 0x00020002) >> 2),
+            None => {}
+        }
+    }
+}
+
+/// Converts the decoded rows of a frame to RGBA. Rows that weren't decoded are transparent.
+pub fn to_rgba(frame: &Frame) -> Vec<u8> {
+    let mut out = Vec::from_elem(frame.width * frame.height * 4, 0u8);
+    let rows = min(frame.rows, frame.height);
+    upsample_rows(frame, 0, None, 0, 0, out.as_mut_slice());
+    let mut k = 1;
+    while 2 * k < rows {
+        upsample_rows(frame, 2 * k - 1, Some(2 * k), k - 1, k, out.as_mut_slice());
+        k += 1;
+    }
+    if rows & 1 == 0 {
+        upsample_rows(frame, rows - 1, None, rows / 2 - 1, rows / 2 - 1, out.as_mut_slice());
+    }
+    out
+}
+
+// The probability and quantizer tables from RFC 6386.
+
+static KEYFRAME_BPRED_MODE_PROBS: [[[u8, ..9], ..10], ..10] = [
+    [
+        [231, 120, 48, 89, 115, 113, 120, 152, 112],
+        [152, 179, 64, 126, 170, 118, 46, 70, 95],
+        [175, 69, 143, 80, 85, 82, 72, 155, 103],
+        [56, 58, 10, 171, 218, 189, 17, 13, 152],
+        [144, 71, 10, 38, 171, 213, 144, 34, 26],
+        [114, 26, 17, 163, 44, 195, 21, 10, 173],
+        [121, 24, 80, 195, 26, 62, 44, 64, 85],
+        [170, 46, 55, 19, 136, 160, 33, 206, 71],
+        [63, 20, 8, 114, 114, 208, 12, 9, 226],
+        [81, 40, 11, 96, 182, 84, 29, 16, 36],
+    ],
+    [
+        [134, 183, 89, 137, 98, 101, 106, 165, 148],
+        [72, 187, 100, 130, 157, 111, 32, 75, 80],
+        [66, 102, 167, 99, 74, 62, 40, 234, 128],
+        [41, 53, 9, 178, 241, 141, 26, 8, 107],
+        [104, 79, 12, 27, 217, 255, 87, 17, 7],
+        [74, 43, 26, 146, 73, 166, 49, 23, 157],
+        [65, 38, 105, 160, 51, 52, 31, 115, 128],
+        [87, 68, 71, 44, 114, 51, 15, 186, 23],
+        [47, 41, 14, 110, 182, 183, 21, 17, 194],
+        [66, 45, 25, 102, 197, 189, 23, 18, 22],
+    ],
+    [
+        [88, 88, 147, 150, 42, 46, 45, 196, 205],
+        [43, 97, 183, 117, 85, 38, 35, 179, 61],
+        [39, 53, 200, 87, 26, 21, 43, 232, 171],
+        [56, 34, 51, 104, 114, 102, 29, 93, 77],
+        [107, 54, 32, 26, 51, 1, 81, 43, 31],
+        [39, 28, 85, 171, 58, 165, 90, 98, 64],
+        [34, 22, 116, 206, 23, 34, 43, 166, 73],
+        [68, 25, 106, 22, 64, 171, 36, 225, 114],
+        [34, 19, 21, 102, 132, 188, 16, 76, 124],
+        [62, 18, 78, 95, 85, 57, 50, 48, 51],
+    ],
+    [
+        [193, 101, 35, 159, 215, 111, 89, 46, 111],
+        [60, 148, 31, 172, 219, 228, 21, 18, 111],
+        [112, 113, 77, 85, 179, 255, 38, 120, 114],
+        [40, 42, 1, 196, 245, 209, 10, 25, 109],
+        [100, 80, 8, 43, 154, 1, 51, 26, 71],
+        [88, 43, 29, 140, 166, 213, 37, 43, 154],
+        [61, 63, 30, 155, 67, 45, 68, 1, 209],
+        [142, 78, 78, 16, 255, 128, 34, 197, 171],
+        [41, 40, 5, 102, 211, 183, 4, 1, 221],
+        [51, 50, 17, 168, 209, 192, 23, 25, 82],
+    ],
+    [
+        [125, 98, 42, 88, 104, 85, 117, 175, 82],
+        [95, 84, 53, 89, 128, 100, 113, 101, 45],
+        [75, 79, 123, 47, 51, 128, 81, 171, 1],
+        [57, 17, 5, 71, 102, 57, 53, 41, 49],
+        [115, 21, 2, 10, 102, 255, 166, 23, 6],
+        [38, 33, 13, 121, 57, 73, 26, 1, 85],
+        [41, 10, 67, 138, 77, 110, 90, 47, 114],
+        [101, 29, 16, 10, 85, 128, 101, 196, 26],
+        [57, 18, 10, 102, 102, 213, 34, 20, 43],
+        [117, 20, 15, 36, 163, 128, 68, 1, 26],
+    ],
+    [
+        [138, 31, 36, 171, 27, 166, 38, 44, 229],
+        [67, 87, 58, 169, 82, 115, 26, 59, 179],
+        [63, 59, 90, 180, 59, 166, 93, 73, 154],
+        [40, 40, 21, 116, 143, 209, 34, 39, 175],
+        [57, 46, 22, 24, 128, 1, 54, 17, 37],
+        [47, 15, 16, 183, 34, 223, 49, 45, 183],
+        [46, 17, 33, 183, 6, 98, 15, 32, 183],
+        [65, 32, 73, 115, 28, 128, 23, 128, 205],
+        [40, 3, 9, 115, 51, 192, 18, 6, 223],
+        [87, 37, 9, 115, 59, 77, 64, 21, 47],
+    ],
+    [
+        [104, 55, 44, 218, 9, 54, 53, 130, 226],
+        [64, 90, 70, 205, 40, 41, 23, 26, 57],
+        [54, 57, 112, 184, 5, 41, 38, 166, 213],
+        [30, 34, 26, 133, 152, 116, 10, 32, 134],
+        [75, 32, 12, 51, 192, 255, 160, 43, 51],
+        [39, 19, 53, 221, 26, 114, 32, 73, 255],
+        [31, 9, 65, 234, 2, 15, 1, 118, 73],
+        [88, 31, 35, 67, 102, 85, 55, 186, 85],
+        [56, 21, 23, 111, 59, 205, 45, 37, 192],
+        [55, 38, 70, 124, 73, 102, 1, 34, 98],
+    ],
+    [
+        [102, 61, 71, 37, 34, 53, 31, 243, 192],
+        [69, 60, 71, 38, 73, 119, 28, 222, 37],
+        [68, 45, 128, 34, 1, 47, 11, 245, 171],
+        [62, 17, 19, 70, 146, 85, 55, 62, 70],
+        [75, 15, 9, 9, 64, 255, 184, 119, 16],
+        [37, 43, 37, 154, 100, 163, 85, 160, 1],
+        [63, 9, 92, 136, 28, 64, 32, 201, 85],
+        [86, 6, 28, 5, 64, 255, 25, 248, 1],
+        [56, 8, 17, 132, 137, 255, 55, 116, 128],
+        [58, 15, 20, 82, 135, 57, 26, 121, 40],
+    ],
+    [
+        [164, 50, 31, 137, 154, 133, 25, 35, 218],
+        [51, 103, 44, 131, 131, 123, 31, 6, 158],
+        [86, 40, 64, 135, 148, 224, 45, 183, 128],
+        [22, 26, 17, 131, 240, 154, 14, 1, 209],
+        [83, 12, 13, 54, 192, 255, 68, 47, 28],
+        [45, 16, 21, 91, 64, 222, 7, 1, 197],
+        [56, 21, 39, 155, 60, 138, 23, 102, 213],
+        [85, 26, 85, 85, 128, 128, 32, 146, 171],
+        [18, 11, 7, 63, 144, 171, 4, 4, 246],
+        [35, 27, 10, 146, 174, 171, 12, 26, 128],
+    ],
+    [
+        [190, 80, 35, 99, 180, 80, 126, 54, 45],
+        [85, 126, 47, 87, 176, 51, 41, 20, 32],
+        [101, 75, 128, 139, 118, 146, 116, 128, 85],
+        [56, 41, 15, 176, 236, 85, 37, 9, 62],
+        [146, 36, 19, 30, 171, 255, 97, 27, 20],
+        [71, 30, 17, 119, 118, 255, 17, 18, 138],
+        [101, 38, 60, 138, 55, 70, 43, 26, 142],
+        [138, 45, 61, 62, 219, 1, 81, 188, 64],
+        [32, 41, 20, 117, 151, 142, 20, 21, 163],
+        [112, 19, 12, 61, 195, 128, 48, 4, 24],
+    ],
+];
+
+static COEFF_UPDATE_PROBS: [[[[u8, ..11], ..3], ..8], ..4] = [
+    [
+        [
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+        ],
+        [
+            [176, 246, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [223, 241, 252, 255, 255, 255, 255, 255, 255, 255, 255],
+            [249, 253, 253, 255, 255, 255, 255, 255, 255, 255, 255],
+        ],
+        [
+            [255, 244, 252, 255, 255, 255, 255, 255, 255, 255, 255],
+            [234, 254, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+            [253, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+        ],
+        [
+            [255, 246, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+            [239, 253, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+            [254, 255, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+        ],
+        [
+            [255, 248, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+            [251, 255, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+        ],
+        [
+            [255, 253, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+            [251, 254, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+            [254, 255, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+        ],
+        [
+            [255, 254, 253, 255, 254, 255, 255, 255, 255, 255, 255],
+            [250, 255, 254, 255, 254, 255, 255, 255, 255, 255, 255],
+            [254, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+        ],
+        [
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+        ],
+    ],
+    [
+        [
+            [217, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [225, 252, 241, 253, 255, 255, 254, 255, 255, 255, 255],
+            [234, 250, 241, 250, 253, 255, 253, 254, 255, 255, 255],
+        ],
+        [
+            [255, 254, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [223, 254, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+            [238, 253, 254, 254, 255, 255, 255, 255, 255, 255, 255],
+        ],
+        [
+            [255, 248, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+            [249, 254, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+        ],
+        [
+            [255, 253, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [247, 254, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+        ],
+        [
+            [255, 253, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+            [252, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+        ],
+        [
+            [255, 254, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+            [253, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+        ],
+        [
+            [255, 254, 253, 255, 255, 255, 255, 255, 255, 255, 255],
+            [250, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [254, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+        ],
+        [
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+        ],
+    ],
+    [
+        [
+            [186, 251, 250, 255, 255, 255, 255, 255, 255, 255, 255],
+            [234, 251, 244, 254, 255, 255, 255, 255, 255, 255, 255],
+            [251, 251, 243, 253, 254, 255, 254, 255, 255, 255, 255],
+        ],
+        [
+            [255, 253, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+            [236, 253, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+            [251, 253, 253, 254, 254, 255, 255, 255, 255, 255, 255],
+        ],
+        [
+            [255, 254, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+            [254, 254, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+        ],
+        [
+            [255, 254, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [254, 254, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [254, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+        ],
+        [
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [254, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+        ],
+        [
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+        ],
+        [
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+        ],
+        [
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+        ],
+    ],
+    [
+        [
+            [248, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [250, 254, 252, 254, 255, 255, 255, 255, 255, 255, 255],
+            [248, 254, 249, 253, 255, 255, 255, 255, 255, 255, 255],
+        ],
+        [
+            [255, 253, 253, 255, 255, 255, 255, 255, 255, 255, 255],
+            [246, 253, 253, 255, 255, 255, 255, 255, 255, 255, 255],
+            [252, 254, 251, 254, 254, 255, 255, 255, 255, 255, 255],
+        ],
+        [
+            [255, 254, 252, 255, 255, 255, 255, 255, 255, 255, 255],
+            [248, 254, 253, 255, 255, 255, 255, 255, 255, 255, 255],
+            [253, 255, 254, 254, 255, 255, 255, 255, 255, 255, 255],
+        ],
+        [
+            [255, 251, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+            [245, 251, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+            [253, 253, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+        ],
+        [
+            [255, 251, 253, 255, 255, 255, 255, 255, 255, 255, 255],
+            [252, 253, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+            [255, 254, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+        ],
+        [
+            [255, 252, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [249, 255, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+            [255, 255, 254, 255, 255, 255, 255, 255, 255, 255, 255],
+        ],
+        [
+            [255, 255, 253, 255, 255, 255, 255, 255, 255, 255, 255],
+            [250, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+        ],
+        [
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [254, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+            [255, 255, 255, 255, 255, 255, 255, 255, 255, 255, 255],
+        ],
+    ],
+];
+
+static COEFF_PROBS: [[[[u8, ..11], ..3], ..8], ..4] = [
+    [
+        [
+            [128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128],
+            [128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128],
+            [128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128],
+        ],
+        [
+            [253, 136, 254, 255, 228, 219, 128, 128, 128, 128, 128],
+            [189, 129, 242, 255, 227, 213, 255, 219, 128, 128, 128],
+            [106, 126, 227, 252, 214, 209, 255, 255, 128, 128, 128],
+        ],
+        [
+            [1, 98, 248, 255, 236, 226, 255, 255, 128, 128, 128],
+            [181, 133, 238, 254, 221, 234, 255, 154, 128, 128, 128],
+            [78, 134, 202, 247, 198, 180, 255, 219, 128, 128, 128],
+        ],
+        [
+            [1, 185, 249, 255, 243, 255, 128, 128, 128, 128, 128],
+            [184, 150, 247, 255, 236, 224, 128, 128, 128, 128, 128],
+            [77, 110, 216, 255, 236, 230, 128, 128, 128, 128, 128],
+        ],
+        [
+            [1, 101, 251, 255, 241, 255, 128, 128, 128, 128, 128],
+            [170, 139, 241, 252, 236, 209, 255, 255, 128, 128, 128],
+            [37, 116, 196, 243, 228, 255, 255, 255, 128, 128, 128],
+        ],
+        [
+            [1, 204, 254, 255, 245, 255, 128, 128, 128, 128, 128],
+            [207, 160, 250, 255, 238, 128, 128, 128, 128, 128, 128],
+            [102, 103, 231, 255, 211, 171, 128, 128, 128, 128, 128],
+        ],
+        [
+            [1, 152, 252, 255, 240, 255, 128, 128, 128, 128, 128],
+            [177, 135, 243, 255, 234, 225, 128, 128, 128, 128, 128],
+            [80, 129, 211, 255, 194, 224, 128, 128, 128, 128, 128],
+        ],
+        [
+            [1, 1, 255, 128, 128, 128, 128, 128, 128, 128, 128],
+            [246, 1, 255, 128, 128, 128, 128, 128, 128, 128, 128],
+            [255, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128],
+        ],
+    ],
+    [
+        [
+            [198, 35, 237, 223, 193, 187, 162, 160, 145, 155, 62],
+            [131, 45, 198, 221, 172, 176, 220, 157, 252, 221, 1],
+            [68, 47, 146, 208, 149, 167, 221, 162, 255, 223, 128],
+        ],
+        [
+            [1, 149, 241, 255, 221, 224, 255, 255, 128, 128, 128],
+            [184, 141, 234, 253, 222, 220, 255, 199, 128, 128, 128],
+            [81, 99, 181, 242, 176, 190, 249, 202, 255, 255, 128],
+        ],
+        [
+            [1, 129, 232, 253, 214, 197, 242, 196, 255, 255, 128],
+            [99, 121, 210, 250, 201, 198, 255, 202, 128, 128, 128],
+            [23, 91, 163, 242, 170, 187, 247, 210, 255, 255, 128],
+        ],
+        [
+            [1, 200, 246, 255, 234, 255, 128, 128, 128, 128, 128],
+            [109, 178, 241, 255, 231, 245, 255, 255, 128, 128, 128],
+            [44, 130, 201, 253, 205, 192, 255, 255, 128, 128, 128],
+        ],
+        [
+            [1, 132, 239, 251, 219, 209, 255, 165, 128, 128, 128],
+            [94, 136, 225, 251, 218, 190, 255, 255, 128, 128, 128],
+            [22, 100, 174, 245, 186, 161, 255, 199, 128, 128, 128],
+        ],
+        [
+            [1, 182, 249, 255, 232, 235, 128, 128, 128, 128, 128],
+            [124, 143, 241, 255, 227, 234, 128, 128, 128, 128, 128],
+            [35, 77, 181, 251, 193, 211, 255, 205, 128, 128, 128],
+        ],
+        [
+            [1, 157, 247, 255, 236, 231, 255, 255, 128, 128, 128],
+            [121, 141, 235, 255, 225, 227, 255, 255, 128, 128, 128],
+            [45, 99, 188, 251, 195, 217, 255, 224, 128, 128, 128],
+        ],
+        [
+            [1, 1, 251, 255, 213, 255, 128, 128, 128, 128, 128],
+            [203, 1, 248, 255, 255, 128, 128, 128, 128, 128, 128],
+            [137, 1, 177, 255, 224, 255, 128, 128, 128, 128, 128],
+        ],
+    ],
+    [
+        [
+            [253, 9, 248, 251, 207, 208, 255, 192, 128, 128, 128],
+            [175, 13, 224, 243, 193, 185, 249, 198, 255, 255, 128],
+            [73, 17, 171, 221, 161, 179, 236, 167, 255, 234, 128],
+        ],
+        [
+            [1, 95, 247, 253, 212, 183, 255, 255, 128, 128, 128],
+            [239, 90, 244, 250, 211, 209, 255, 255, 128, 128, 128],
+            [155, 77, 195, 248, 188, 195, 255, 255, 128, 128, 128],
+        ],
+        [
+            [1, 24, 239, 251, 218, 219, 255, 205, 128, 128, 128],
+            [201, 51, 219, 255, 196, 186, 128, 128, 128, 128, 128],
+            [69, 46, 190, 239, 201, 218, 255, 228, 128, 128, 128],
+        ],
+        [
+            [1, 191, 251, 255, 255, 128, 128, 128, 128, 128, 128],
+            [223, 165, 249, 255, 213, 255, 128, 128, 128, 128, 128],
+            [141, 124, 248, 255, 255, 128, 128, 128, 128, 128, 128],
+        ],
+        [
+            [1, 16, 248, 255, 255, 128, 128, 128, 128, 128, 128],
+            [190, 36, 230, 255, 236, 255, 128, 128, 128, 128, 128],
+            [149, 1, 255, 128, 128, 128, 128, 128, 128, 128, 128],
+        ],
+        [
+            [1, 226, 255, 128, 128, 128, 128, 128, 128, 128, 128],
+            [247, 192, 255, 128, 128, 128, 128, 128, 128, 128, 128],
+            [240, 128, 255, 128, 128, 128, 128, 128, 128, 128, 128],
+        ],
+        [
+            [1, 134, 252, 255, 255, 128, 128, 128, 128, 128, 128],
+            [213, 62, 250, 255, 255, 128, 128, 128, 128, 128, 128],
+            [55, 93, 255, 128, 128, 128, 128, 128, 128, 128, 128],
+        ],
+        [
+            [128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128],
+            [128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128],
+            [128, 128, 128, 128, 128, 128, 128, 128, 128, 128, 128],
+        ],
+    ],
+    [
+        [
+            [202, 24, 213, 235, 186, 191, 220, 160, 240, 175, 255],
+            [126, 38, 182, 232, 169, 184, 228, 174, 255, 187, 128],
+            [61, 46, 138, 219, 151, 178, 240, 170, 255, 216, 128],
+        ],
+        [
+            [1, 112, 230, 250, 199, 191, 247, 159, 255, 255, 128],
+            [166, 109, 228, 252, 211, 215, 255, 174, 128, 128, 128],
+            [39, 77, 162, 232, 172, 180, 245, 178, 255, 255, 128],
+        ],
+        [
+            [1, 52, 220, 246, 198, 199, 249, 220, 255, 255, 128],
+            [124, 74, 191, 243, 183, 193, 250, 221, 255, 255, 128],
+            [24, 71, 130, 219, 154, 170, 243, 182, 255, 255, 128],
+        ],
+        [
+            [1, 182, 225, 249, 219, 240, 255, 224, 128, 128, 128],
+            [149, 150, 226, 252, 216, 205, 255, 171, 128, 128, 128],
+            [28, 108, 170, 242, 183, 194, 254, 223, 255, 255, 128],
+        ],
+        [
+            [1, 81, 230, 252, 204, 203, 255, 192, 128, 128, 128],
+            [123, 102, 209, 247, 188, 196, 255, 233, 128, 128, 128],
+            [20, 95, 153, 243, 164, 173, 255, 203, 128, 128, 128],
+        ],
+        [
+            [1, 222, 248, 255, 216, 213, 128, 128, 128, 128, 128],
+            [168, 175, 246, 252, 235, 205, 255, 255, 128, 128, 128],
+            [47, 116, 215, 255, 211, 212, 255, 255, 128, 128, 128],
+        ],
+        [
+            [1, 121, 236, 253, 212, 214, 255, 255, 128, 128, 128],
+            [141, 84, 213, 252, 201, 202, 255, 219, 128, 128, 128],
+            [42, 80, 160, 240, 162, 185, 255, 205, 128, 128, 128],
+        ],
+        [
+            [1, 1, 255, 128, 128, 128, 128, 128, 128, 128, 128],
+            [244, 1, 255, 128, 128, 128, 128, 128, 128, 128, 128],
+            [238, 1, 255, 128, 128, 128, 128, 128, 128, 128, 128],
+        ],
+    ],
+];
+
+static DC_QUANT: [i32, ..128] = [
+    4, 5, 6, 7, 8, 9, 10, 10, 11, 12, 13, 14, 15, 16, 17, 17, 18, 19, 20, 20, 21, 21, 22, 22, 23,
+    23, 24, 25, 25, 26, 27, 28, 29, 30, 31, 32, 33, 34, 35, 36, 37, 37, 38, 39, 40, 41, 42, 43, 44,
+    45, 46, 46, 47, 48, 49, 50, 51, 52, 53, 54, 55, 56, 57, 58, 59, 60, 61, 62, 63, 64, 65, 66, 67,
+    68, 69, 70, 71, 72, 73, 74, 75, 76, 76, 77, 78, 79, 80, 81, 82, 83, 84, 85, 86, 87, 88, 89, 91,
+    93, 95, 96, 98, 100, 101, 102, 104, 106, 108, 110, 112, 114, 116, 118, 122, 124, 126, 128, 130,
+    132, 134, 136, 138, 140, 143, 145, 148, 151, 154, 157,
+];
+
+static AC_QUANT: [i32, ..128] = [
+    4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25, 26, 27, 28,
+    29, 30, 31, 32, 33, 34, 35, 36, 37, 38, 39, 40, 41, 42, 43, 44, 45, 46, 47, 48, 49, 50, 51, 52,
+    53, 54, 55, 56, 57, 58, 60, 62, 64, 66, 68, 70, 72, 74, 76, 78, 80, 82, 84, 86, 88, 90, 92, 94,
+    96, 98, 100, 102, 104, 106, 108, 110, 112, 114, 116, 119, 122, 125, 128, 131, 134, 137, 140,
+    143, 146, 149, 152, 155, 158, 161, 164, 167, 170, 173, 177, 181, 185, 189, 193, 197, 201, 205,
+    209, 213, 217, 221, 225, 229, 234, 239, 245, 249, 254, 259, 264, 269, 274, 279, 284,
+];
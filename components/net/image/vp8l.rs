@@ -0,0 +1,590 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! A decoder for lossless WebP (VP8L) bitstreams. Lossy WebP images use this too, for their alpha
+//! channel.
+
+use image::base::MAX_IMAGE_PIXELS;
+
+use std::cmp::max;
+
+/// Reads bits least significant first.
+pub struct BitReader<'a> {
+    data: &'a [u8],
+    pos: uint,
+    bit_buffer: u64,
+    bit_count: uint,
+}
+
+impl<'a> BitReader<'a> {
+    pub fn new(data: &'a [u8]) -> BitReader<'a> {
+        BitReader {
+            data: data,
+            pos: 0,
+            bit_buffer: 0,
+            bit_count: 0,
+        }
+    }
+
+    pub fn read_bits(&mut self, count: uint) -> Result<u32, ()> {
+        debug_assert!(count <= 32);
+        while self.bit_count < count {
+            if self.pos >= self.data.len() {
+                return Err(());
+            }
+            self.bit_buffer |= (self.data[self.pos] as u64) << self.bit_count;
+            self.pos += 1;
+            self.bit_count += 8;
+        }
+        let value = (self.bit_buffer & ((1u64 << count) - 1)) as u32;
+        self.bit_buffer >>= count;
+        self.bit_count -= count;
+        Ok(value)
+    }
+}
+
+static NUM_LITERAL_CODES: uint = 256;
+static NUM_LENGTH_CODES: uint = 24;
+static NUM_DISTANCE_CODES: uint = 40;
+static MAX_CODE_LENGTH: uint = 15;
+
+static CODE_LENGTH_CODE_ORDER: [uint, ..19] = [
+    17, 18, 0, 1, 2, 3, 4, 5, 16, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15,
+];
+
+/// The (x, y) offsets of the 120 short distance codes.
+static DISTANCE_MAP: [(i8, i8), ..120] = [
+    (0, 1), (1, 0), (1, 1), (-1, 1), (0, 2), (2, 0), (1, 2), (-1, 2),
+    (2, 1), (-2, 1), (2, 2), (-2, 2), (0, 3), (3, 0), (1, 3), (-1, 3),
+    (3, 1), (-3, 1), (2, 3), (-2, 3), (3, 2), (-3, 2), (0, 4), (4, 0),
+    (1, 4), (-1, 4), (4, 1), (-4, 1), (3, 3), (-3, 3), (2, 4), (-2, 4),
+    (4, 2), (-4, 2), (0, 5), (3, 4), (-3, 4), (4, 3), (-4, 3), (5, 0),
+    (1, 5), (-1, 5), (5, 1), (-5, 1), (2, 5), (-2, 5), (5, 2), (-5, 2),
+    (4, 4), (-4, 4), (3, 5), (-3, 5), (5, 3), (-5, 3), (0, 6), (6, 0),
+    (1, 6), (-1, 6), (6, 1), (-6, 1), (2, 6), (-2, 6), (6, 2), (-6, 2),
+    (4, 5), (-4, 5), (5, 4), (-5, 4), (3, 6), (-3, 6), (6, 3), (-6, 3),
+    (0, 7), (7, 0), (1, 7), (-1, 7), (5, 5), (-5, 5), (7, 1), (-7, 1),
+    (4, 6), (-4, 6), (6, 4), (-6, 4), (2, 7), (-2, 7), (7, 2), (-7, 2),
+    (3, 7), (-3, 7), (7, 3), (-7, 3), (5, 6), (-5, 6), (6, 5), (-6, 5),
+    (8, 0), (4, 7), (-4, 7), (7, 4), (-7, 4), (8, 1), (8, 2), (6, 6),
+    (-6, 6), (8, 3), (5, 7), (-5, 7), (7, 5), (-7, 5), (8, 4), (6, 7),
+    (-6, 7), (7, 6), (-7, 6), (8, 5), (7, 7), (-7, 7), (8, 6), (8, 7),
+];
+
+/// A canonical prefix code, decoded a bit at a time.
+struct PrefixCode {
+    /// The number of codes of each length.
+    counts: [u16, ..16],
+    /// The symbols, in code order.
+    symbols: Vec<u16>,
+    /// The only symbol of a code with one symbol, which takes no bits to read.
+    single: Option<u16>,
+}
+
+impl PrefixCode {
+    fn from_lengths(lengths: &[u8]) -> Result<PrefixCode, ()> {
+        let mut counts = [0u16, ..16];
+        let mut used = 0u;
+        let mut last = 0u;
+        for (symbol, &length) in lengths.iter().enumerate() {
+            if length > 0 {
+                counts[length as uint] += 1;
+                used += 1;
+                last = symbol;
+            }
+        }
+        if used == 0 {
+            return Err(());
+        }
+        if used == 1 {
+            return Ok(PrefixCode {
+                counts: counts,
+                symbols: vec!(),
+                single: Some(last as u16),
+            });
+        }
+
+        // Incomplete and oversubscribed codes are both invalid.
+        let mut left = 1i32;
+        for length in range(1, MAX_CODE_LENGTH + 1) {
+            left = (left << 1) - counts[length] as i32;
+            if left < 0 {
+                return Err(());
+            }
+        }
+        if left != 0 {
+            return Err(());
+        }
+
+        let mut offsets = [0u16, ..17];
+        for length in range(1, MAX_CODE_LENGTH + 1) {
+            offsets[length + 1] = offsets[length] + counts[length];
+        }
+        let mut symbols = Vec::from_elem(used, 0u16);
+        for (symbol, &length) in lengths.iter().enumerate() {
+            if length > 0 {
+                symbols[offsets[length as uint] as uint] = symbol as u16;
+                offsets[length as uint] += 1;
+            }
+        }
+        Ok(PrefixCode {
+            counts: counts,
+            symbols: symbols,
+            single: None,
+        })
+    }
+
+    fn read_symbol(&self, reader: &mut BitReader) -> Result<u16, ()> {
+        match self.single {
+            Some(symbol) => return Ok(symbol),
+            None => {}
+        }
+        let mut code = 0i32;
+        let mut first = 0i32;
+        let mut index = 0i32;
+        for length in range(1, MAX_CODE_LENGTH + 1) {
+            code |= try!(reader.read_bits(1)) as i32;
+            let count = self.counts[length] as i32;
+            if code - count < first {
+                return Ok(self.symbols[(index + code - first) as uint]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(())
+    }
+}
+
+fn read_prefix_code(reader: &mut BitReader, alphabet_size: uint) -> Result<PrefixCode, ()> {
+    let mut lengths = Vec::from_elem(alphabet_size, 0u8);
+
+    // A simple code has one or two symbols.
+    if try!(reader.read_bits(1)) == 1 {
+        let num_symbols = try!(reader.read_bits(1)) + 1;
+        let first_bits = if try!(reader.read_bits(1)) == 1 { 8 } else { 1 };
+        let mut symbols = vec!(try!(reader.read_bits(first_bits)) as uint);
+        if num_symbols == 2 {
+            symbols.push(try!(reader.read_bits(8)) as uint);
+        }
+        for &symbol in symbols.iter() {
+            if symbol >= alphabet_size {
+                return Err(());
+            }
+            lengths[symbol] = 1;
+        }
+        return PrefixCode::from_lengths(lengths.as_slice());
+    }
+
+    let mut code_length_code_lengths = [0u8, ..19];
+    let num_codes = try!(reader.read_bits(4)) as uint + 4;
+    for i in range(0, num_codes) {
+        code_length_code_lengths[CODE_LENGTH_CODE_ORDER[i]] = try!(reader.read_bits(3)) as u8;
+    }
+    let code_length_code = try!(PrefixCode::from_lengths(code_length_code_lengths.as_slice()));
+
+    let mut max_symbol = if try!(reader.read_bits(1)) == 1 {
+        let length_bits = 2 + 2 * try!(reader.read_bits(3)) as uint;
+        let max_symbol = 2 + try!(reader.read_bits(length_bits)) as uint;
+        if max_symbol > alphabet_size {
+            return Err(());
+        }
+        max_symbol
+    } else {
+        alphabet_size
+    };
+
+    let mut previous_length = 8u8;
+    let mut symbol = 0u;
+    while symbol < alphabet_size && max_symbol > 0 {
+        max_symbol -= 1;
+        let code = try!(code_length_code.read_symbol(reader));
+        if code < 16 {
+            lengths[symbol] = code as u8;
+            symbol += 1;
+            if code != 0 {
+                previous_length = code as u8;
+            }
+            continue;
+        }
+        let (extra_bits, offset, value) = match code {
+            16 => (2, 3, previous_length),
+            17 => (3, 3, 0),
+            _ => (7, 11, 0),
+        };
+        let repeat = try!(reader.read_bits(extra_bits)) as uint + offset;
+        if symbol + repeat > alphabet_size {
+            return Err(());
+        }
+        for _ in range(0, repeat) {
+            lengths[symbol] = value;
+            symbol += 1;
+        }
+    }
+    PrefixCode::from_lengths(lengths.as_slice())
+}
+
+/// The five prefix codes used together to read pixels.
+struct CodeGroup {
+    green: PrefixCode,
+    red: PrefixCode,
+    blue: PrefixCode,
+    alpha: PrefixCode,
+    distance: PrefixCode,
+}
+
+/// A transform, along with the width of the image it applies to.
+enum Transform {
+    PredictorTransform(uint, uint, Vec<u32>),
+    CrossColorTransform(uint, uint, Vec<u32>),
+    SubtractGreenTransform,
+    ColorIndexingTransform(uint, uint, Vec<u32>),
+}
+
+fn subsample_size(size: uint, bits: uint) -> uint {
+    (size + (1 << bits) - 1) >> bits
+}
+
+/// Decodes a lossless image stream, which follows the five byte header in a VP8L chunk and makes
+/// up the whole of compressed alpha data, into ARGB pixels.
+pub fn decode_image_stream(reader: &mut BitReader, width: uint, height: uint)
+                           -> Result<Vec<u32>, ()> {
+    let mut transforms = vec!();
+    let mut xsize = width;
+    let mut seen = [false, ..4];
+    while try!(reader.read_bits(1)) == 1 {
+        let kind = try!(reader.read_bits(2)) as uint;
+        if seen[kind] {
+            return Err(());
+        }
+        seen[kind] = true;
+        match kind {
+            0 | 1 => {
+                let bits = try!(reader.read_bits(3)) as uint + 2;
+                let data = try!(decode_entropy_coded_image(reader,
+                                                           subsample_size(xsize, bits),
+                                                           subsample_size(height, bits),
+                                                           false));
+                transforms.push(if kind == 0 {
+                    PredictorTransform(xsize, bits, data)
+                } else {
+                    CrossColorTransform(xsize, bits, data)
+                });
+            }
+            2 => transforms.push(SubtractGreenTransform),
+            _ => {
+                let size = try!(reader.read_bits(8)) as uint + 1;
+                let mut table = try!(decode_entropy_coded_image(reader, size, 1, false));
+                // The palette is delta coded.
+                for i in range(1, size) {
+                    table[i] = add_pixels(table[i], table[i - 1]);
+                }
+                let bits = if size <= 2 {
+                    3
+                } else if size <= 4 {
+                    2
+                } else if size <= 16 {
+                    1
+                } else {
+                    0
+                };
+                transforms.push(ColorIndexingTransform(xsize, bits, table));
+                xsize = subsample_size(xsize, bits);
+            }
+        }
+    }
+
+    let mut pixels = try!(decode_entropy_coded_image(reader, xsize, height, true));
+
+    for transform in transforms.iter().rev() {
+        match *transform {
+            PredictorTransform(width, bits, ref data) => {
+                inverse_predictor(pixels.as_mut_slice(), width, height, bits, data.as_slice())
+            }
+            CrossColorTransform(width, bits, ref data) => {
+                inverse_cross_color(pixels.as_mut_slice(), width, height, bits, data.as_slice())
+            }
+            SubtractGreenTransform => {
+                for pixel in pixels.iter_mut() {
+                    let green = (*pixel >> 8) & 0xff;
+                    let red = (((*pixel >> 16) & 0xff) + green) & 0xff;
+                    let blue = ((*pixel & 0xff) + green) & 0xff;
+                    *pixel = (*pixel & 0xff00ff00) | (red << 16) | blue;
+                }
+            }
+            ColorIndexingTransform(width, bits, ref table) => {
+                pixels = inverse_color_indexing(pixels.as_slice(), width, height, bits,
+                                                table.as_slice());
+            }
+        }
+    }
+    Ok(pixels)
+}
+
+fn decode_entropy_coded_image(reader: &mut BitReader, xsize: uint, ysize: uint, is_main: bool)
+                              -> Result<Vec<u32>, ()> {
+    let cache_bits = if try!(reader.read_bits(1)) == 1 {
+        let bits = try!(reader.read_bits(4)) as uint;
+        if bits < 1 || bits > 11 {
+            return Err(());
+        }
+        bits
+    } else {
+        0
+    };
+    let cache_size = if cache_bits > 0 { 1 << cache_bits } else { 0 };
+
+    // Only the main image can use different codes in different places.
+    let mut meta_bits = 0;
+    let mut meta_image = vec!();
+    let mut num_groups = 1;
+    if is_main && try!(reader.read_bits(1)) == 1 {
+        meta_bits = try!(reader.read_bits(3)) as uint + 2;
+        meta_image = try!(decode_entropy_coded_image(reader,
+                                                     subsample_size(xsize, meta_bits),
+                                                     subsample_size(ysize, meta_bits),
+                                                     false));
+        for pixel in meta_image.iter_mut() {
+            *pixel = (*pixel >> 8) & 0xffff;
+            num_groups = max(num_groups, *pixel as uint + 1);
+        }
+    }
+
+    let mut groups = Vec::with_capacity(num_groups);
+    for _ in range(0, num_groups) {
+        groups.push(CodeGroup {
+            green: try!(read_prefix_code(reader,
+                                         NUM_LITERAL_CODES + NUM_LENGTH_CODES + cache_size)),
+            red: try!(read_prefix_code(reader, NUM_LITERAL_CODES)),
+            blue: try!(read_prefix_code(reader, NUM_LITERAL_CODES)),
+            alpha: try!(read_prefix_code(reader, NUM_LITERAL_CODES)),
+            distance: try!(read_prefix_code(reader, NUM_DISTANCE_CODES)),
+        });
+    }
+
+    let total = xsize * ysize;
+    let mut pixels = Vec::from_elem(total, 0u32);
+    let mut cache = Vec::from_elem(cache_size, 0u32);
+    let meta_width = subsample_size(xsize, meta_bits);
+    let mut pos = 0u;
+    let mut last_cached = 0u;
+    while pos < total {
+        let group = if meta_bits == 0 {
+            &groups[0]
+        } else {
+            let (x, y) = (pos % xsize, pos / xsize);
+            &groups[meta_image[(y >> meta_bits) * meta_width + (x >> meta_bits)] as uint]
+        };
+        let code = try!(group.green.read_symbol(reader)) as uint;
+        if code < NUM_LITERAL_CODES {
+            let red = try!(group.red.read_symbol(reader)) as u32;
+            let blue = try!(group.blue.read_symbol(reader)) as u32;
+            let alpha = try!(group.alpha.read_symbol(reader)) as u32;
+            pixels[pos] = (alpha << 24) | (red << 16) | ((code as u32) << 8) | blue;
+            pos += 1;
+        } else if code < NUM_LITERAL_CODES + NUM_LENGTH_CODES {
+            let length = try!(read_lz77_value(reader, code - NUM_LITERAL_CODES));
+            let distance_symbol = try!(group.distance.read_symbol(reader)) as uint;
+            let distance_code = try!(read_lz77_value(reader, distance_symbol));
+            let distance = plane_code_to_distance(xsize, distance_code);
+            if distance > pos || pos + length > total {
+                return Err(());
+            }
+            for _ in range(0, length) {
+                pixels[pos] = pixels[pos - distance];
+                pos += 1;
+            }
+        } else {
+            // Everything before this pixel has to be in the cache before looking it up.
+            while last_cached < pos {
+                let pixel = pixels[last_cached];
+                cache[color_cache_key(pixel, cache_bits)] = pixel;
+                last_cached += 1;
+            }
+            pixels[pos] = cache[code - NUM_LITERAL_CODES - NUM_LENGTH_CODES];
+            pos += 1;
+        }
+        if cache_bits > 0 {
+            while last_cached < pos {
+                let pixel = pixels[last_cached];
+                cache[color_cache_key(pixel, cache_bits)] = pixel;
+                last_cached += 1;
+            }
+        }
+    }
+    Ok(pixels)
+}
+
+fn color_cache_key(argb: u32, bits: uint) -> uint {
+    ((0x1e35a7bdu32 * argb) >> (32 - bits)) as uint
+}
+
+fn read_lz77_value(reader: &mut BitReader, prefix: uint) -> Result<uint, ()> {
+    if prefix < 4 {
+        return Ok(prefix + 1);
+    }
+    let extra_bits = (prefix - 2) >> 1;
+    let offset = (2 + (prefix & 1)) << extra_bits;
+    Ok(offset + try!(reader.read_bits(extra_bits)) as uint + 1)
+}
+
+fn plane_code_to_distance(xsize: uint, code: uint) -> uint {
+    if code > 120 {
+        return code - 120;
+    }
+    let (dx, dy) = DISTANCE_MAP[code - 1];
+    let distance = dx as int + dy as int * xsize as int;
+    if distance < 1 { 1 } else { distance as uint }
+}
+
+fn add_pixels(a: u32, b: u32) -> u32 {
+    let alpha_green = ((a & 0xff00ff00) + (b & 0xff00ff00)) & 0xff00ff00;
+    let red_blue = ((a & 0x00ff00ff) + (b & 0x00ff00ff)) & 0x00ff00ff;
+    alpha_green | red_blue
+}
+
+fn average2(a: u32, b: u32) -> u32 {
+    (((a ^ b) & 0xfefefefe) >> 1) + (a & b)
+}
+
+fn channel(pixel: u32, shift: uint) -> i32 {
+    ((pixel >> shift) & 0xff) as i32
+}
+
+fn clamp(value: i32) -> u32 {
+    if value < 0 {
+        0
+    } else if value > 255 {
+        255
+    } else {
+        value as u32
+    }
+}
+
+fn distance(a: i32, b: i32) -> i32 {
+    if a > b { a - b } else { b - a }
+}
+
+fn select(left: u32, top: u32, top_left: u32) -> u32 {
+    let mut left_distance = 0;
+    let mut top_distance = 0;
+    for &shift in [0u, 8, 16, 24].iter() {
+        left_distance += distance(channel(top, shift), channel(top_left, shift));
+        top_distance += distance(channel(left, shift), channel(top_left, shift));
+    }
+    if left_distance < top_distance { left } else { top }
+}
+
+fn clamp_add_subtract_full(a: u32, b: u32, c: u32) -> u32 {
+    let mut result = 0;
+    for &shift in [0u, 8, 16, 24].iter() {
+        result |= clamp(channel(a, shift) + channel(b, shift) - channel(c, shift)) << shift;
+    }
+    result
+}
+
+fn clamp_add_subtract_half(a: u32, b: u32) -> u32 {
+    let mut result = 0;
+    for &shift in [0u, 8, 16, 24].iter() {
+        let (a, b) = (channel(a, shift), channel(b, shift));
+        result |= clamp(a + (a - b) / 2) << shift;
+    }
+    result
+}
+
+fn inverse_predictor(pixels: &mut [u32], width: uint, height: uint, bits: uint, data: &[u32]) {
+    let block_width = subsample_size(width, bits);
+    for y in range(0, height) {
+        for x in range(0, width) {
+            let i = y * width + x;
+            let prediction = if y == 0 {
+                if x == 0 { 0xff000000 } else { pixels[i - 1] }
+            } else if x == 0 {
+                pixels[i - width]
+            } else {
+                let mode = (data[(y >> bits) * block_width + (x >> bits)] >> 8) & 0xf;
+                let left = pixels[i - 1];
+                let top = pixels[i - width];
+                let top_left = pixels[i - width - 1];
+                let top_right = pixels[i - width + 1];
+                match mode {
+                    1 => left,
+                    2 => top,
+                    3 => top_right,
+                    4 => top_left,
+                    5 => average2(average2(left, top_right), top),
+                    6 => average2(left, top_left),
+                    7 => average2(left, top),
+                    8 => average2(top_left, top),
+                    9 => average2(top, top_right),
+                    10 => average2(average2(left, top_left), average2(top, top_right)),
+                    11 => select(left, top, top_left),
+                    12 => clamp_add_subtract_full(left, top, top_left),
+                    13 => clamp_add_subtract_half(average2(left, top), top_left),
+                    _ => 0xff000000,
+                }
+            };
+            pixels[i] = add_pixels(pixels[i], prediction);
+        }
+    }
+}
+
+fn color_transform_delta(transform: u32, color: u32) -> i32 {
+    ((transform as u8 as i8 as i32) * (color as u8 as i8 as i32)) >> 5
+}
+
+fn inverse_cross_color(pixels: &mut [u32], width: uint, height: uint, bits: uint, data: &[u32]) {
+    let block_width = subsample_size(width, bits);
+    for y in range(0, height) {
+        for x in range(0, width) {
+            let element = data[(y >> bits) * block_width + (x >> bits)];
+            let green_to_red = element & 0xff;
+            let green_to_blue = (element >> 8) & 0xff;
+            let red_to_blue = (element >> 16) & 0xff;
+            let pixel = pixels[y * width + x];
+            let green = (pixel >> 8) & 0xff;
+            let red = (channel(pixel, 16) + color_transform_delta(green_to_red, green)) & 0xff;
+            let mut blue = (channel(pixel, 0) + color_transform_delta(green_to_blue, green)) & 0xff;
+            blue = (blue + color_transform_delta(red_to_blue, red as u32)) & 0xff;
+            pixels[y * width + x] = (pixel & 0xff00ff00) | ((red as u32) << 16) | blue as u32;
+        }
+    }
+}
+
+fn inverse_color_indexing(pixels: &[u32], width: uint, height: uint, bits: uint, table: &[u32])
+                          -> Vec<u32> {
+    let packed_width = subsample_size(width, bits);
+    let pixels_per_byte = 1u << bits;
+    let bits_per_pixel = 8u >> bits;
+    let mask = (1u32 << bits_per_pixel) - 1;
+    let mut result = Vec::from_elem(width * height, 0u32);
+    for y in range(0, height) {
+        for x in range(0, width) {
+            let packed = (pixels[y * packed_width + x / pixels_per_byte] >> 8) & 0xff;
+            let index = ((packed >> ((x % pixels_per_byte) * bits_per_pixel)) & mask) as uint;
+            result[y * width + x] = if index < table.len() { table[index] } else { 0 };
+        }
+    }
+    result
+}
+
+/// Decodes the contents of a VP8L chunk into its width, height and ARGB pixels.
+pub fn decode(data: &[u8]) -> Result<(uint, uint, Vec<u32>), ()> {
+    let mut reader = BitReader::new(data);
+    if try!(reader.read_bits(8)) != 0x2f {
+        return Err(());
+    }
+    let width = try!(reader.read_bits(14)) as uint + 1;
+    let height = try!(reader.read_bits(14)) as uint + 1;
+    if width * height > MAX_IMAGE_PIXELS {
+        return Err(());
+    }
+    // Skip the alpha hint.
+    try!(reader.read_bits(1));
+    if try!(reader.read_bits(3)) != 0 {
+        return Err(());
+    }
+    let pixels = try!(decode_image_stream(&mut reader, width, height));
+    Ok((width, height, pixels))
+}
@@ -0,0 +1,304 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! The WebP container, which holds a lossy or lossless still image or the frames of an
+//! animation. Truncated lossy images decode to the rows that have arrived, and truncated
+//! animations to the frames that have.
+
+use image::base::{DecodedImage, ImageFrame, MAX_IMAGE_PIXELS, blend_over};
+use image::{vp8, vp8l};
+
+use std::cmp::{max, min};
+
+fn read_u16(data: &[u8]) -> u32 {
+    data[0] as u32 | ((data[1] as u32) << 8)
+}
+
+fn read_u24(data: &[u8]) -> u32 {
+    read_u16(data) | ((data[2] as u32) << 16)
+}
+
+fn read_u32(data: &[u8]) -> u32 {
+    read_u24(data) | ((data[3] as u32) << 24)
+}
+
+struct Chunk<'a> {
+    kind: &'a [u8],
+    data: &'a [u8],
+    /// Whether the chunk was cut short.
+    truncated: bool,
+}
+
+/// Splits `data` into chunks, the last of which may be truncated. `truncated` says whether the
+/// data itself was cut short, which truncates the last chunk too.
+fn chunks<'a>(data: &'a [u8], truncated: bool) -> Vec<Chunk<'a>> {
+    let mut chunks = vec!();
+    let mut pos = 0;
+    while pos + 8 <= data.len() {
+        let length = read_u32(data.slice_from(pos + 4)) as uint;
+        let start = pos + 8;
+        let end = min(start + length, data.len());
+        chunks.push(Chunk {
+            kind: data.slice(pos, pos + 4),
+            data: data.slice(start, end),
+            truncated: end < start + length,
+        });
+        // Chunks are padded to an even length.
+        pos = start + length + (length & 1);
+    }
+    if truncated {
+        match chunks.last_mut() {
+            Some(chunk) => chunk.truncated = true,
+            None => {}
+        }
+    }
+    chunks
+}
+
+/// Decodes an image from its "VP8 " or "VP8L" chunk, and its "ALPH" chunk if it has one, into
+/// non-premultiplied RGBA.
+fn decode_bitstream(alpha: Option<&Chunk>, image: &Chunk) -> Option<(uint, uint, Vec<u8>)> {
+    if image.kind == b"VP8L" {
+        // Lossless images can't be decoded in part.
+        if image.truncated {
+            return None;
+        }
+        let (width, height, argb) = match vp8l::decode(image.data) {
+            Ok(decoded) => decoded,
+            Err(()) => return None,
+        };
+        let mut rgba = Vec::with_capacity(width * height * 4);
+        for &pixel in argb.iter() {
+            rgba.push_all(&[(pixel >> 16) as u8, (pixel >> 8) as u8, pixel as u8,
+                            (pixel >> 24) as u8]);
+        }
+        return Some((width, height, rgba));
+    }
+
+    let frame = match vp8::decode(image.data) {
+        Ok(frame) => frame,
+        Err(()) => return None,
+    };
+    let mut rgba = vp8::to_rgba(&frame);
+    match alpha {
+        Some(alpha) => {
+            let plane = match decode_alpha(alpha.data, frame.width, frame.height) {
+                Some(plane) => plane,
+                None => return None,
+            };
+            for i in range(0, frame.width * min(frame.rows, frame.height)) {
+                rgba[i * 4 + 3] = plane[i];
+            }
+        }
+        None => {}
+    }
+    Some((frame.width, frame.height, rgba))
+}
+
+/// Decodes the alpha plane of a lossy image.
+fn decode_alpha(data: &[u8], width: uint, height: uint) -> Option<Vec<u8>> {
+    if data.is_empty() {
+        return None;
+    }
+    let header = data[0];
+    let mut plane = match header & 3 {
+        0 => {
+            if data.len() < 1 + width * height {
+                return None;
+            }
+            data.slice(1, 1 + width * height).to_vec()
+        }
+        1 => {
+            // The plane is compressed as the green channel of a lossless image.
+            let mut reader = vp8l::BitReader::new(data.slice_from(1));
+            match vp8l::decode_image_stream(&mut reader, width, height) {
+                Ok(argb) => argb.iter().map(|&pixel| (pixel >> 8) as u8).collect(),
+                Err(()) => return None,
+            }
+        }
+        _ => return None,
+    };
+
+    let filter = (header >> 2) & 3;
+    if filter == 0 {
+        return Some(plane);
+    }
+    for y in range(0, height) {
+        for x in range(0, width) {
+            let i = y * width + x;
+            let prediction = if x == 0 && y == 0 {
+                0
+            } else if y == 0 {
+                plane[i - 1]
+            } else if x == 0 {
+                plane[i - width]
+            } else {
+                match filter {
+                    1 => plane[i - 1],
+                    2 => plane[i - width],
+                    _ => {
+                        let gradient = plane[i - 1] as i32 + plane[i - width] as i32 -
+                                       plane[i - width - 1] as i32;
+                        max(0, min(255, gradient)) as u8
+                    }
+                }
+            };
+            plane[i] = plane[i] + prediction;
+        }
+    }
+    Some(plane)
+}
+
+fn still_image(width: uint, height: uint, rgba: Vec<u8>) -> DecodedImage {
+    DecodedImage {
+        width: width,
+        height: height,
+        frames: vec!(ImageFrame::from_rgba(width, height, rgba, 0)),
+        loop_count: Some(1),
+    }
+}
+
+pub fn decode(data: &[u8]) -> Option<DecodedImage> {
+    if data.len() < 12 || data.slice(0, 4) != b"RIFF" || data.slice(8, 12) != b"WEBP" {
+        return None;
+    }
+    let riff_end = max(12, min(data.len(), 8 + read_u32(data.slice_from(4)) as uint));
+    let chunks = chunks(data.slice(12, riff_end), false);
+    let first = match chunks.as_slice().head() {
+        Some(first) => first,
+        None => return None,
+    };
+
+    // Simple files hold nothing but the image.
+    if first.kind != b"VP8X" {
+        return decode_bitstream(None, first).map(|(width, height, rgba)| {
+            still_image(width, height, rgba)
+        });
+    }
+
+    if first.data.len() < 10 {
+        return None;
+    }
+    let animated = first.data[0] & 0x02 != 0;
+    let width = read_u24(first.data.slice_from(4)) as uint + 1;
+    let height = read_u24(first.data.slice_from(7)) as uint + 1;
+    if width * height > MAX_IMAGE_PIXELS {
+        return None;
+    }
+
+    if !animated {
+        let mut alpha = None;
+        for chunk in chunks.slice_from(1).iter() {
+            if chunk.kind == b"ALPH" {
+                if !chunk.truncated {
+                    alpha = Some(chunk);
+                }
+            } else if chunk.kind == b"VP8 " || chunk.kind == b"VP8L" {
+                return match decode_bitstream(alpha, chunk) {
+                    Some((image_width, image_height, rgba)) => {
+                        if image_width != width || image_height != height {
+                            return None;
+                        }
+                        Some(still_image(width, height, rgba))
+                    }
+                    None => None,
+                };
+            }
+        }
+        return None;
+    }
+
+    let mut loop_count = None;
+    let mut canvas = Vec::from_elem(width * height * 4, 0u8);
+    let mut frames = vec!();
+    for chunk in chunks.slice_from(1).iter() {
+        if chunk.kind == b"ANIM" {
+            if chunk.data.len() < 6 {
+                return None;
+            }
+            loop_count = match read_u16(chunk.data.slice_from(4)) {
+                0 => None,
+                plays => Some(plays),
+            };
+        } else if chunk.kind == b"ANMF" {
+            let header = chunk.data;
+            if header.len() < 16 {
+                break;
+            }
+            let frame_x = read_u24(header) as uint * 2;
+            let frame_y = read_u24(header.slice_from(3)) as uint * 2;
+            let frame_width = read_u24(header.slice_from(6)) as uint + 1;
+            let frame_height = read_u24(header.slice_from(9)) as uint + 1;
+            let delay = read_u24(header.slice_from(12));
+            let blend = header[15] & 0x02 == 0;
+            let dispose = header[15] & 0x01 != 0;
+            if frame_x + frame_width > width || frame_y + frame_height > height {
+                return None;
+            }
+
+            let frame_chunks = chunks(header.slice_from(16), chunk.truncated);
+            let mut alpha = None;
+            let mut image = None;
+            for frame_chunk in frame_chunks.iter() {
+                if frame_chunk.kind == b"ALPH" {
+                    if !frame_chunk.truncated {
+                        alpha = Some(frame_chunk);
+                    }
+                } else if frame_chunk.kind == b"VP8 " || frame_chunk.kind == b"VP8L" {
+                    image = Some(frame_chunk);
+                    break;
+                }
+            }
+            let rgba = match image.and_then(|image| decode_bitstream(alpha, image)) {
+                Some((image_width, image_height, rgba)) => {
+                    if image_width != frame_width || image_height != frame_height {
+                        return None;
+                    }
+                    rgba
+                }
+                None => break,
+            };
+
+            for y in range(0, frame_height) {
+                for x in range(0, frame_width) {
+                    let source = (y * frame_width + x) * 4;
+                    let source = rgba.slice(source, source + 4);
+                    let dest = ((frame_y + y) * width + frame_x + x) * 4;
+                    if blend {
+                        blend_over(canvas.slice_mut(dest, dest + 4), source);
+                    } else {
+                        for i in range(0u, 4) {
+                            canvas[dest + i] = source[i];
+                        }
+                    }
+                }
+            }
+            frames.push(ImageFrame::from_rgba(width, height, canvas.clone(), delay));
+            if chunk.truncated {
+                break;
+            }
+
+            if dispose {
+                for y in range(frame_y, frame_y + frame_height) {
+                    for x in range(frame_x, frame_x + frame_width) {
+                        let offset = (y * width + x) * 4;
+                        for i in range(0u, 4) {
+                            canvas[offset + i] = 0;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    if frames.is_empty() {
+        return None;
+    }
+    Some(DecodedImage {
+        width: width,
+        height: height,
+        frames: frames,
+        loop_count: loop_count,
+    })
+}
@@ -2,10 +2,12 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
-use image::base::{Image, load_from_memory};
+use image::base::{DecodedImage, Image};
+use image::decoder::{ProgressiveDecoder, decode_image};
 use resource_task;
 use resource_task::{LoadData, ResourceTask};
 
+use servo_util::memory::{MemoryProfilerChan, ReportMsg};
 use servo_util::task::spawn_named;
 use servo_util::taskpool::TaskPool;
 use std::comm::{channel, Receiver, Sender};
@@ -13,6 +15,7 @@ use std::collections::HashMap;
 use std::collections::hash_map::{Occupied, Vacant};
 use std::mem::replace;
 use std::result;
+use std::sync::atomic::{AtomicBool, SeqCst};
 use sync::{Arc, Mutex};
 use serialize::{Encoder, Encodable};
 use url::Url;
@@ -32,14 +35,24 @@ pub enum Msg {
     /// Wait for an image to become available (or fail to load).
     WaitForImage(Url, Sender<ImageResponseMsg>),
 
+    /// Wait for more of an image to be decoded while its data is arriving. The response is
+    /// ImagePartial, or the same as for WaitForImage once the image is complete.
+    WaitForProgress(Url, Sender<ImageResponseMsg>),
+
+    /// Request every frame of an image decoded so far, with their timing, for animating it.
+    GetFrames(Url, Sender<Option<Arc<DecodedImage>>>),
+
     /// Clients must wait for a response before shutting down the ResourceTask
     Exit(Sender<()>),
 
     /// Used by the prefetch tasks to post back image binaries
     StorePrefetchedImageData(Url, Result<Vec<u8>, ()>),
 
+    /// Used by the prefetch tasks to post images decoded from the data received so far
+    StorePartialImage(Url, Arc<DecodedImage>),
+
     /// Used by the decoder tasks to post decoded images back to the cache
-    StoreImage(Url, Option<Arc<Box<Image>>>),
+    StoreImage(Url, Option<Arc<DecodedImage>>),
 
     /// For testing
     WaitForStore(Sender<()>),
@@ -50,7 +63,10 @@ pub enum Msg {
 
 #[deriving(Clone)]
 pub enum ImageResponseMsg {
+    /// The image, or its first frame if it is animated.
     ImageReady(Arc<Box<Image>>),
+    /// What has been decoded of an image whose data is still arriving.
+    ImagePartial(Arc<Box<Image>>),
     ImageNotReady,
     ImageFailed
 }
@@ -59,10 +75,12 @@ impl PartialEq for ImageResponseMsg {
     fn eq(&self, other: &ImageResponseMsg) -> bool {
         match (self, other) {
             (&ImageReady(..), &ImageReady(..)) => panic!("unimplemented comparison"),
+            (&ImagePartial(..), &ImagePartial(..)) => panic!("unimplemented comparison"),
             (&ImageNotReady, &ImageNotReady) => true,
             (&ImageFailed, &ImageFailed) => true,
 
-            (&ImageReady(..), _) | (&ImageNotReady, _) | (&ImageFailed, _) => false
+            (&ImageReady(..), _) | (&ImagePartial(..), _) | (&ImageNotReady, _) |
+            (&ImageFailed, _) => false
         }
     }
 }
@@ -81,7 +99,10 @@ impl<E, S: Encoder<E>> Encodable<S, E> for ImageCacheTask {
 type DecoderFactory = fn() -> (proc(&[u8]) : 'static -> Option<Image>);
 
 impl ImageCacheTask {
-    pub fn new(resource_task: ResourceTask, task_pool: TaskPool) -> ImageCacheTask {
+    /// Creates an image cache that evicts images once they take up more than `memory_budget`
+    /// bytes, reporting how much they use to the memory profiler.
+    pub fn new(resource_task: ResourceTask, task_pool: TaskPool,
+               memory_profiler_chan: MemoryProfilerChan, memory_budget: uint) -> ImageCacheTask {
         let (chan, port) = channel();
         let chan_clone = chan.clone();
 
@@ -92,6 +113,16 @@ impl ImageCacheTask {
                 chan: chan_clone,
                 state_map: HashMap::new(),
                 wait_map: HashMap::new(),
+                progress_wait_map: HashMap::new(),
+                partial_map: HashMap::new(),
+                decode_requests: HashMap::new(),
+                last_used: HashMap::new(),
+                use_count: 0,
+                memory_budget: memory_budget,
+                decoded_size: 0,
+                encoded_size: 0,
+                reported_sizes: (0, 0),
+                memory_profiler_chan: memory_profiler_chan,
                 need_exit: None,
                 task_pool: task_pool,
             };
@@ -103,11 +134,14 @@ impl ImageCacheTask {
         }
     }
 
-    pub fn new_sync(resource_task: ResourceTask, task_pool: TaskPool) -> ImageCacheTask {
+    pub fn new_sync(resource_task: ResourceTask, task_pool: TaskPool,
+                    memory_profiler_chan: MemoryProfilerChan, memory_budget: uint)
+                    -> ImageCacheTask {
         let (chan, port) = channel();
 
         spawn_named("ImageCacheTask (sync)", proc() {
-            let inner_cache = ImageCacheTask::new(resource_task, task_pool);
+            let inner_cache = ImageCacheTask::new(resource_task, task_pool, memory_profiler_chan,
+                                                  memory_budget);
 
             loop {
                 let msg: Msg = port.recv();
@@ -142,6 +176,26 @@ struct ImageCache {
     state_map: HashMap<Url, ImageState>,
     /// List of clients waiting on a WaitForImage response
     wait_map: HashMap<Url, Arc<Mutex<Vec<Sender<ImageResponseMsg>>>>>,
+    /// List of clients waiting on a WaitForProgress response
+    progress_wait_map: HashMap<Url, Vec<Sender<ImageResponseMsg>>>,
+    /// The images decoded from partial data, for images that are still loading
+    partial_map: HashMap<Url, Arc<DecodedImage>>,
+    /// Flags telling the prefetch tasks that their images are wanted, so that they start
+    /// decoding the data as it arrives
+    decode_requests: HashMap<Url, Arc<AtomicBool>>,
+    /// When each image was last used, counted in uses of any image, for eviction
+    last_used: HashMap<Url, u64>,
+    /// The number of times any image has been used
+    use_count: u64,
+    /// How many bytes of decoded and encoded images to keep before evicting some
+    memory_budget: uint,
+    /// The number of bytes taken up by the pixels of decoded images
+    decoded_size: uint,
+    /// The number of bytes of image data kept for decoding
+    encoded_size: uint,
+    /// The sizes last reported to the memory profiler
+    reported_sizes: (uint, uint),
+    memory_profiler_chan: MemoryProfilerChan,
     need_exit: Option<Sender<()>>,
     task_pool: TaskPool,
 }
@@ -150,9 +204,13 @@ struct ImageCache {
 enum ImageState {
     Init,
     Prefetching(AfterPrefetch),
-    Prefetched(Vec<u8>),
-    Decoding,
-    Decoded(Arc<Box<Image>>),
+    Prefetched(Arc<Vec<u8>>),
+    Decoding(Arc<Vec<u8>>),
+    /// The decoded image, and the data it was decoded from
+    Decoded(Arc<DecodedImage>, Arc<Vec<u8>>),
+    /// The decoded image was evicted to save memory. It is decoded again when it is next asked
+    /// for, from the data if that was kept or else from data fetched again.
+    Evicted(Option<Arc<Vec<u8>>>),
     Failed
 }
 
@@ -181,6 +239,7 @@ impl ImageCache {
                     self.store_prefetched_image_data(url, data);
                 }
                 Decode(url) => self.decode(url),
+                StorePartialImage(url, image) => self.store_partial_image(url, image),
                 StoreImage(url, image) => {
                     store_chan.map(|chan| {
                         chan.send(());
@@ -193,6 +252,8 @@ impl ImageCache {
                 WaitForImage(url, response) => {
                     self.wait_for_image(url, response)
                 }
+                WaitForProgress(url, response) => self.wait_for_progress(url, response),
+                GetFrames(url, response) => self.get_frames(url, response),
                 WaitForStore(chan) => store_chan = Some(chan),
                 WaitForStorePrefetched(chan) => store_prefetched_chan = Some(chan),
                 Exit(response) => {
@@ -201,6 +262,8 @@ impl ImageCache {
                 }
             }
 
+            self.report_memory_usage();
+
             let need_exit = replace(&mut self.need_exit, None);
 
             match need_exit {
@@ -211,9 +274,9 @@ impl ImageCache {
                 for (_, state) in self.state_map.iter() {
                     match *state {
                         Prefetching(..) => can_exit = false,
-                        Decoding => can_exit = false,
+                        Decoding(..) => can_exit = false,
 
-                        Init | Prefetched(..) | Decoded(..) | Failed => ()
+                        Init | Prefetched(..) | Decoded(..) | Evicted(..) | Failed => ()
                     }
                 }
 
@@ -237,21 +300,99 @@ impl ImageCache {
     }
 
     fn set_state(&mut self, url: Url, state: ImageState) {
+        let (old_decoded_size, old_encoded_size) = match self.state_map.get(&url) {
+            Some(old_state) => state_sizes(old_state),
+            None => (0, 0),
+        };
+        let (decoded_size, encoded_size) = state_sizes(&state);
+        self.decoded_size = self.decoded_size - old_decoded_size + decoded_size;
+        self.encoded_size = self.encoded_size - old_encoded_size + encoded_size;
         self.state_map.insert(url, state);
     }
 
+    fn touch(&mut self, url: &Url) {
+        self.use_count += 1;
+        self.last_used.insert(url.clone(), self.use_count);
+    }
+
+    /// Evicts the least recently used images other than `keep` until the cache fits in its
+    /// memory budget. Decoded pixels go first, since they can be recreated from the data, and
+    /// then the data itself.
+    fn evict(&mut self, keep: &Url) {
+        while self.decoded_size + self.encoded_size > self.memory_budget {
+            let victim = match self.least_recently_used(keep, false) {
+                Some(url) => Some(url),
+                None => self.least_recently_used(keep, true),
+            };
+            let url = match victim {
+                Some(url) => url,
+                None => break,
+            };
+            debug!("image_cache_task: evicting {:s}", url.serialize());
+            let state = match self.get_state(&url) {
+                Decoded(_, data) => Evicted(Some(data)),
+                _ => Evicted(None),
+            };
+            self.set_state(url, state);
+        }
+    }
+
+    /// Finds the least recently used image other than `keep` that is decoded, or that only
+    /// holds data if `data_only` is set.
+    fn least_recently_used(&self, keep: &Url, data_only: bool) -> Option<Url> {
+        let mut victim: Option<(&Url, u64)> = None;
+        for (url, state) in self.state_map.iter() {
+            let evictable = match *state {
+                Decoded(..) => !data_only,
+                Prefetched(..) | Evicted(Some(..)) => data_only,
+                Init | Prefetching(..) | Decoding(..) | Evicted(None) | Failed => false,
+            };
+            if !evictable || url == keep {
+                continue;
+            }
+            let last_used = match self.last_used.get(url) {
+                Some(&last_used) => last_used,
+                None => 0,
+            };
+            let older = match victim {
+                Some((_, oldest)) => last_used < oldest,
+                None => true,
+            };
+            if older {
+                victim = Some((url, last_used));
+            }
+        }
+        victim.map(|(url, _)| url.clone())
+    }
+
+    fn report_memory_usage(&mut self) {
+        let sizes = (self.decoded_size, self.encoded_size);
+        if sizes == self.reported_sizes {
+            return;
+        }
+        self.reported_sizes = sizes;
+
+        // The profiler may already have shut down.
+        let MemoryProfilerChan(ref chan) = self.memory_profiler_chan;
+        let _ = chan.send_opt(ReportMsg("images-decoded".to_string(), self.decoded_size as u64));
+        let _ = chan.send_opt(ReportMsg("images-encoded".to_string(), self.encoded_size as u64));
+    }
+
     fn prefetch(&mut self, url: Url) {
         match self.get_state(&url) {
             Init => {
                 let to_cache = self.chan.clone();
                 let resource_task = self.resource_task.clone();
                 let url_clone = url.clone();
+                let decode_requested = Arc::new(AtomicBool::new(false));
+                self.decode_requests.insert(url.clone(), decode_requested.clone());
 
                 spawn_named("ImageCacheTask (prefetch)", proc() {
                     let url = url_clone;
                     debug!("image_cache_task: started fetch for {:s}", url.serialize());
 
-                    let image = load_image_data(url.clone(), resource_task.clone());
+                    let image = load_image_data(url.clone(), resource_task.clone(), &to_cache,
+                                                &*decode_requested);
                     to_cache.send(StorePrefetchedImageData(url.clone(), image));
                     debug!("image_cache_task: ended fetch for {:s}", url.serialize());
                 });
@@ -259,18 +400,23 @@ impl ImageCache {
                 self.set_state(url, Prefetching(DoNotDecode));
             }
 
-            Prefetching(..) | Prefetched(..) | Decoding | Decoded(..) | Failed => {
+            Prefetching(..) | Prefetched(..) | Decoding(..) | Decoded(..) | Evicted(..) |
+            Failed => {
                 // We've already begun working on this image
             }
         }
     }
 
     fn store_prefetched_image_data(&mut self, url: Url, data: Result<Vec<u8>, ()>) {
+        self.decode_requests.remove(&url);
+
         match self.get_state(&url) {
           Prefetching(next_step) => {
             match data {
               Ok(data) => {
-                self.set_state(url.clone(), Prefetched(data));
+                self.set_state(url.clone(), Prefetched(Arc::new(data)));
+                self.touch(&url);
+                self.evict(&url);
                 match next_step {
                   DoDecode => self.decode(url),
                   _ => ()
@@ -278,6 +424,8 @@ impl ImageCache {
               }
               Err(..) => {
                 self.set_state(url.clone(), Failed);
+                self.partial_map.remove(&url);
+                self.purge_progress_waiters(&url, || ImageFailed);
                 self.purge_waiters(url, || ImageFailed);
               }
             }
@@ -285,8 +433,9 @@ impl ImageCache {
 
           Init
           | Prefetched(..)
-          | Decoding
+          | Decoding(..)
           | Decoded(..)
+          | Evicted(..)
           | Failed => {
             panic!("wrong state for storing prefetched image")
           }
@@ -298,7 +447,12 @@ impl ImageCache {
             Init => panic!("decoding image before prefetch"),
 
             Prefetching(DoNotDecode) => {
-                // We don't have the data yet, queue up the decode
+                // We don't have the data yet, queue up the decode, and decode
+                // what we can as it arrives
+                match self.decode_requests.get(&url) {
+                    Some(decode_requested) => decode_requested.store(true, SeqCst),
+                    None => (),
+                }
                 self.set_state(url, Prefetching(DoDecode))
             }
 
@@ -306,39 +460,67 @@ impl ImageCache {
                 // We don't have the data yet, but the decode request is queued up
             }
 
-            Prefetched(data) => {
+            Prefetched(data) | Evicted(Some(data)) => {
                 let to_cache = self.chan.clone();
                 let url_clone = url.clone();
+                let data_clone = data.clone();
 
                 self.task_pool.execute(proc() {
                     let url = url_clone;
+                    let data = data_clone;
                     debug!("image_cache_task: started image decode for {:s}", url.serialize());
-                    let image = load_from_memory(data.as_slice());
-                    let image = image.map(|image| Arc::new(box image));
+                    let image = decode_image(data.as_slice()).map(|image| Arc::new(image));
                     to_cache.send(StoreImage(url.clone(), image));
                     debug!("image_cache_task: ended image decode for {:s}", url.serialize());
                 });
 
-                self.set_state(url, Decoding);
+                self.set_state(url, Decoding(data));
+            }
+
+            Evicted(None) => {
+                // The data was evicted too, so fetch it again
+                self.set_state(url.clone(), Init);
+                self.prefetch(url.clone());
+                self.decode(url);
             }
 
-            Decoding | Decoded(..) | Failed => {
+            Decoding(..) | Decoded(..) | Failed => {
                 // We've already begun decoding
             }
         }
     }
 
-    fn store_image(&mut self, url: Url, image: Option<Arc<Box<Image>>>) {
+    fn store_partial_image(&mut self, url: Url, image: Arc<DecodedImage>) {
+        match self.get_state(&url) {
+            Prefetching(DoDecode) | Decoding(..) => {
+                let first_frame = image.first_frame();
+                self.partial_map.insert(url.clone(), image);
+                self.purge_progress_waiters(&url, || ImagePartial(first_frame.clone()));
+            }
+
+            // The full image may have been decoded already
+            Init | Prefetching(DoNotDecode) | Prefetched(..) | Decoded(..) | Evicted(..) |
+            Failed => ()
+        }
+    }
+
+    fn store_image(&mut self, url: Url, image: Option<Arc<DecodedImage>>) {
+        self.partial_map.remove(&url);
 
         match self.get_state(&url) {
-          Decoding => {
+          Decoding(data) => {
             match image {
               Some(image) => {
-                self.set_state(url.clone(), Decoded(image.clone()));
-                self.purge_waiters(url, || ImageReady(image.clone()) );
+                let first_frame = image.first_frame();
+                self.set_state(url.clone(), Decoded(image, data));
+                self.touch(&url);
+                self.evict(&url);
+                self.purge_progress_waiters(&url, || ImageReady(first_frame.clone()));
+                self.purge_waiters(url, || ImageReady(first_frame.clone()));
               }
               None => {
                 self.set_state(url.clone(), Failed);
+                self.purge_progress_waiters(&url, || ImageFailed);
                 self.purge_waiters(url, || ImageFailed );
               }
             }
@@ -348,6 +530,7 @@ impl ImageCache {
           | Prefetching(..)
           | Prefetched(..)
           | Decoded(..)
+          | Evicted(..)
           | Failed => {
             panic!("incorrect state in store_image")
           }
@@ -367,24 +550,65 @@ impl ImageCache {
         }
     }
 
-    fn get_image(&self, url: Url, response: Sender<ImageResponseMsg>) {
+    fn purge_progress_waiters(&mut self, url: &Url, f: || -> ImageResponseMsg) {
+        match self.progress_wait_map.remove(url) {
+            Some(waiters) => {
+                for response in waiters.iter() {
+                    response.send(f());
+                }
+            }
+            None => ()
+        }
+    }
+
+    /// The response for an image that is still loading.
+    fn not_ready_response(&self, url: &Url) -> ImageResponseMsg {
+        match self.partial_map.get(url) {
+            Some(image) => ImagePartial(image.first_frame()),
+            None => ImageNotReady,
+        }
+    }
+
+    /// Marks an image as used, and starts decoding it again if it was evicted.
+    fn use_image(&mut self, url: &Url) {
+        self.touch(url);
+        match self.get_state(url) {
+            Evicted(..) => self.decode(url.clone()),
+            _ => (),
+        }
+    }
+
+    fn get_image(&mut self, url: Url, response: Sender<ImageResponseMsg>) {
+        self.use_image(&url);
+
         match self.get_state(&url) {
             Init => panic!("request for image before prefetch"),
-            Prefetching(DoDecode) => response.send(ImageNotReady),
+            Prefetching(DoDecode) => response.send(self.not_ready_response(&url)),
             Prefetching(DoNotDecode) | Prefetched(..) => panic!("request for image before decode"),
-            Decoding => response.send(ImageNotReady),
-            Decoded(image) => response.send(ImageReady(image)),
+            Decoding(..) | Evicted(..) => response.send(self.not_ready_response(&url)),
+            Decoded(image, _) => response.send(ImageReady(image.first_frame())),
             Failed => response.send(ImageFailed),
         }
     }
 
+    fn get_frames(&mut self, url: Url, response: Sender<Option<Arc<DecodedImage>>>) {
+        self.use_image(&url);
+
+        match self.get_state(&url) {
+            Decoded(image, _) => response.send(Some(image)),
+            _ => response.send(self.partial_map.get(&url).map(|image| image.clone())),
+        }
+    }
+
     fn wait_for_image(&mut self, url: Url, response: Sender<ImageResponseMsg>) {
+        self.use_image(&url);
+
         match self.get_state(&url) {
             Init => panic!("request for image before prefetch"),
 
             Prefetching(DoNotDecode) | Prefetched(..) => panic!("request for image before decode"),
 
-            Prefetching(DoDecode) | Decoding => {
+            Prefetching(DoDecode) | Decoding(..) | Evicted(..) => {
                 // We don't have this image yet
                 match self.wait_map.entry(url) {
                     Occupied(mut entry) => {
@@ -396,8 +620,8 @@ impl ImageCache {
                 }
             }
 
-            Decoded(image) => {
-                response.send(ImageReady(image));
+            Decoded(image, _) => {
+                response.send(ImageReady(image.first_frame()));
             }
 
             Failed => {
@@ -406,9 +630,38 @@ impl ImageCache {
         }
     }
 
+    fn wait_for_progress(&mut self, url: Url, response: Sender<ImageResponseMsg>) {
+        self.use_image(&url);
+
+        match self.get_state(&url) {
+            Prefetching(DoDecode) | Decoding(..) => {
+                match self.progress_wait_map.entry(url) {
+                    Occupied(mut entry) => {
+                        entry.get_mut().push(response);
+                    }
+                    Vacant(entry) => {
+                        entry.set(vec!(response));
+                    }
+                }
+            }
+
+            // Once the image has loaded this is just like waiting for it
+            _ => self.wait_for_image(url, response),
+        }
+    }
+
 }
 
 
+/// The number of bytes of decoded pixels and of image data held in a state.
+fn state_sizes(state: &ImageState) -> (uint, uint) {
+    match *state {
+        Prefetched(ref data) | Decoding(ref data) | Evicted(Some(ref data)) => (0, data.len()),
+        Decoded(ref image, ref data) => (image.size_in_bytes(), data.len()),
+        Init | Prefetching(..) | Evicted(None) | Failed => (0, 0),
+    }
+}
+
 pub trait ImageCacheTaskClient {
     fn exit(&self);
 }
@@ -441,17 +694,29 @@ impl ImageCacheTask {
     }
 }
 
-fn load_image_data(url: Url, resource_task: ResourceTask) -> Result<Vec<u8>, ()> {
+/// Loads the data for an image. Once `decode_requested` is set, the data received so far is
+/// decoded from time to time and sent back to the cache, so that it can be shown before it has
+/// all arrived.
+fn load_image_data(url: Url, resource_task: ResourceTask, to_cache: &Sender<Msg>,
+                   decode_requested: &AtomicBool) -> Result<Vec<u8>, ()> {
     let (response_chan, response_port) = channel();
-    resource_task.send(resource_task::Load(LoadData::new(url, response_chan)));
+    resource_task.send(resource_task::Load(LoadData::new(url.clone(), response_chan)));
 
     let mut image_data = vec!();
+    let mut decoder = ProgressiveDecoder::new();
 
     let progress_port = response_port.recv().progress_port;
     loop {
         match progress_port.recv() {
             resource_task::Payload(data) => {
                 image_data.push_all(data.as_slice());
+                if decode_requested.load(SeqCst) {
+                    match decoder.decode(image_data.as_slice()) {
+                        Some(image) => to_cache.send(StorePartialImage(url.clone(),
+                                                                       Arc::new(image))),
+                        None => (),
+                    }
+                }
             }
             resource_task::Done(result::Ok(..)) => {
                 return Ok(image_data);
@@ -484,6 +749,7 @@ mod tests {
     use resource_task::{ResourceTask, Metadata, start_sending, ResponseSenders};
     use sniffer_task;
     use image::base::test_image_bin;
+    use servo_util::memory::MemoryProfiler;
     use servo_util::taskpool::TaskPool;
     use std::comm;
     use url::Url;
@@ -512,6 +778,17 @@ mod tests {
         }
     }
 
+    struct SendTestImageAndNotify {
+        url_requested_chan: Sender<()>,
+    }
+    impl Closure for SendTestImageAndNotify {
+        fn invoke(&self, response: Sender<resource_task::ProgressMsg>) {
+            self.url_requested_chan.send(());
+            response.send(resource_task::Payload(test_image_bin()));
+            response.send(resource_task::Done(Ok(())));
+        }
+    }
+
     struct SendBogusImage;
     impl Closure for SendBogusImage {
         fn invoke(&self, response: Sender<resource_task::ProgressMsg>) {
@@ -554,6 +831,23 @@ mod tests {
         }
     }
 
+    struct WaitSendPartialImage {
+        wait_port: Receiver<()>,
+    }
+    impl Closure for WaitSendPartialImage {
+        fn invoke(&self, response: Sender<resource_task::ProgressMsg>) {
+            // Send the first frame of an animation, padded so that it is
+            // worth decoding, then finish loading once the client has seen it
+            static ANIMATED_GIF: &'static [u8] = include_bin!("image/test_animated.gif");
+            let mut data = ANIMATED_GIF.slice_to(69).to_vec();
+            data.grow(16 * 1024, 0);
+            self.wait_port.recv();
+            response.send(resource_task::Payload(data));
+            self.wait_port.recv();
+            response.send(resource_task::Done(Ok(())));
+        }
+    }
+
     fn mock_resource_task<T: Closure+Send>(on_load: Box<T>) -> ResourceTask {
         spawn_listener(proc(port: Receiver<resource_task::ControlMsg>) {
             loop {
@@ -574,11 +868,24 @@ mod tests {
         })
     }
 
+    /// Creates an image cache with a memory budget large enough that nothing is evicted.
+    fn new_image_cache_task(resource_task: ResourceTask) -> ImageCacheTask {
+        ImageCacheTask::new(resource_task, TaskPool::new(4), MemoryProfiler::create(None), 1 << 30)
+    }
+
+    fn load_and_wait_for_image(image_cache_task: &ImageCacheTask, url: &Url) -> ImageResponseMsg {
+        image_cache_task.send(Prefetch(url.clone()));
+        image_cache_task.send(Decode(url.clone()));
+        let (response_chan, response_port) = comm::channel();
+        image_cache_task.send(WaitForImage(url.clone(), response_chan));
+        response_port.recv()
+    }
+
     #[test]
     fn should_exit_on_request() {
         let mock_resource_task = mock_resource_task(box DoesNothing);
 
-        let image_cache_task = ImageCacheTask::new(mock_resource_task.clone(), TaskPool::new(4));
+        let image_cache_task = new_image_cache_task(mock_resource_task.clone());
 
         image_cache_task.exit();
         mock_resource_task.send(resource_task::Exit);
@@ -589,7 +896,7 @@ mod tests {
     fn should_fail_if_unprefetched_image_is_requested() {
         let mock_resource_task = mock_resource_task(box DoesNothing);
 
-        let image_cache_task = ImageCacheTask::new(mock_resource_task.clone(), TaskPool::new(4));
+        let image_cache_task = new_image_cache_task(mock_resource_task.clone());
         let url = Url::parse("file:///").unwrap();
 
         let (chan, port) = channel();
@@ -603,7 +910,7 @@ mod tests {
 
         let mock_resource_task = mock_resource_task(box JustSendOK { url_requested_chan: url_requested_chan});
 
-        let image_cache_task = ImageCacheTask::new(mock_resource_task.clone(), TaskPool::new(4));
+        let image_cache_task = new_image_cache_task(mock_resource_task.clone());
         let url = Url::parse("file:///").unwrap();
 
         image_cache_task.send(Prefetch(url));
@@ -618,7 +925,7 @@ mod tests {
 
         let mock_resource_task = mock_resource_task(box JustSendOK { url_requested_chan: url_requested_chan});
 
-        let image_cache_task = ImageCacheTask::new(mock_resource_task.clone(), TaskPool::new(4));
+        let image_cache_task = new_image_cache_task(mock_resource_task.clone());
         let url = Url::parse("file:///").unwrap();
 
         image_cache_task.send(Prefetch(url.clone()));
@@ -638,7 +945,7 @@ mod tests {
 
         let mock_resource_task = mock_resource_task(box WaitSendTestImage{wait_port: wait_port});
 
-        let image_cache_task = ImageCacheTask::new(mock_resource_task.clone(), TaskPool::new(4));
+        let image_cache_task = new_image_cache_task(mock_resource_task.clone());
         let url = Url::parse("file:///").unwrap();
 
         image_cache_task.send(Prefetch(url.clone()));
@@ -655,7 +962,7 @@ mod tests {
     fn should_return_decoded_image_data_if_data_has_arrived() {
         let mock_resource_task = mock_resource_task(box SendTestImage);
 
-        let image_cache_task = ImageCacheTask::new(mock_resource_task.clone(), TaskPool::new(4));
+        let image_cache_task = new_image_cache_task(mock_resource_task.clone());
         let url = Url::parse("file:///").unwrap();
 
         let join_port = image_cache_task.wait_for_store();
@@ -681,7 +988,7 @@ mod tests {
     fn should_return_decoded_image_data_for_multiple_requests() {
         let mock_resource_task = mock_resource_task(box SendTestImage);
 
-        let image_cache_task = ImageCacheTask::new(mock_resource_task.clone(), TaskPool::new(4));
+        let image_cache_task = new_image_cache_task(mock_resource_task.clone());
         let url = Url::parse("file:///").unwrap();
 
         let join_port = image_cache_task.wait_for_store();
@@ -734,7 +1041,7 @@ mod tests {
             }
         });
 
-        let image_cache_task = ImageCacheTask::new(mock_resource_task.clone(), TaskPool::new(4));
+        let image_cache_task = new_image_cache_task(mock_resource_task.clone());
         let url = Url::parse("file:///").unwrap();
 
         image_cache_task.send(Prefetch(url.clone()));
@@ -786,7 +1093,7 @@ mod tests {
             }
         });
 
-        let image_cache_task = ImageCacheTask::new(mock_resource_task.clone(), TaskPool::new(4));
+        let image_cache_task = new_image_cache_task(mock_resource_task.clone());
         let url = Url::parse("file:///").unwrap();
 
         image_cache_task.send(Prefetch(url.clone()));
@@ -815,7 +1122,7 @@ mod tests {
     fn should_return_failed_if_image_bin_cannot_be_fetched() {
         let mock_resource_task = mock_resource_task(box SendTestImageErr);
 
-        let image_cache_task = ImageCacheTask::new(mock_resource_task.clone(), TaskPool::new(4));
+        let image_cache_task = new_image_cache_task(mock_resource_task.clone());
         let url = Url::parse("file:///").unwrap();
 
         let join_port = image_cache_task.wait_for_store_prefetched();
@@ -841,7 +1148,7 @@ mod tests {
     fn should_return_failed_for_multiple_get_image_requests_if_image_bin_cannot_be_fetched() {
         let mock_resource_task = mock_resource_task(box SendTestImageErr);
 
-        let image_cache_task = ImageCacheTask::new(mock_resource_task.clone(), TaskPool::new(4));
+        let image_cache_task = new_image_cache_task(mock_resource_task.clone());
         let url = Url::parse("file:///").unwrap();
 
         let join_port = image_cache_task.wait_for_store_prefetched();
@@ -875,7 +1182,7 @@ mod tests {
     fn should_return_failed_if_image_decode_fails() {
         let mock_resource_task = mock_resource_task(box SendBogusImage);
 
-        let image_cache_task = ImageCacheTask::new(mock_resource_task.clone(), TaskPool::new(4));
+        let image_cache_task = new_image_cache_task(mock_resource_task.clone());
         let url = Url::parse("file:///").unwrap();
 
         let join_port = image_cache_task.wait_for_store();
@@ -903,7 +1210,7 @@ mod tests {
     fn should_return_image_on_wait_if_image_is_already_loaded() {
         let mock_resource_task = mock_resource_task(box SendTestImage);
 
-        let image_cache_task = ImageCacheTask::new(mock_resource_task.clone(), TaskPool::new(4));
+        let image_cache_task = new_image_cache_task(mock_resource_task.clone());
         let url = Url::parse("file:///").unwrap();
 
         let join_port = image_cache_task.wait_for_store();
@@ -931,7 +1238,7 @@ mod tests {
 
         let mock_resource_task = mock_resource_task(box WaitSendTestImage {wait_port: wait_port});
 
-        let image_cache_task = ImageCacheTask::new(mock_resource_task.clone(), TaskPool::new(4));
+        let image_cache_task = new_image_cache_task(mock_resource_task.clone());
         let url = Url::parse("file:///").unwrap();
 
         image_cache_task.send(Prefetch(url.clone()));
@@ -957,7 +1264,7 @@ mod tests {
 
         let mock_resource_task = mock_resource_task(box WaitSendTestImageErr{wait_port: wait_port});
 
-        let image_cache_task = ImageCacheTask::new(mock_resource_task.clone(), TaskPool::new(4));
+        let image_cache_task = new_image_cache_task(mock_resource_task.clone());
         let url = Url::parse("file:///").unwrap();
 
         image_cache_task.send(Prefetch(url.clone()));
@@ -977,11 +1284,141 @@ mod tests {
         mock_resource_task.send(resource_task::Exit);
     }
 
+    #[test]
+    fn should_return_partial_image_while_data_is_arriving() {
+        let (wait_chan, wait_port) = comm::channel();
+
+        let mock_resource_task = mock_resource_task(box WaitSendPartialImage {wait_port: wait_port});
+
+        let image_cache_task = new_image_cache_task(mock_resource_task.clone());
+        let url = Url::parse("file:///").unwrap();
+
+        image_cache_task.send(Prefetch(url.clone()));
+        image_cache_task.send(Decode(url.clone()));
+
+        let (response_chan, response_port) = comm::channel();
+        image_cache_task.send(WaitForProgress(url.clone(), response_chan));
+
+        wait_chan.send(());
+
+        match response_port.recv() {
+          ImagePartial(..) => (),
+          _ => panic!("bleh")
+        }
+
+        let (response_chan, response_port) = comm::channel();
+        image_cache_task.send(GetImage(url.clone(), response_chan));
+        match response_port.recv() {
+          ImagePartial(..) => (),
+          _ => panic!("bleh")
+        }
+
+        let (frames_chan, frames_port) = comm::channel();
+        image_cache_task.send(GetFrames(url.clone(), frames_chan));
+        assert_eq!(frames_port.recv().unwrap().frames.len(), 1);
+
+        let (response_chan, response_port) = comm::channel();
+        image_cache_task.send(WaitForImage(url, response_chan));
+
+        wait_chan.send(());
+
+        match response_port.recv() {
+          ImageReady(..) => (),
+          _ => panic!("bleh")
+        }
+
+        image_cache_task.exit();
+        mock_resource_task.send(resource_task::Exit);
+    }
+
+    #[test]
+    fn should_decode_evicted_images_again() {
+        let (url_requested_chan, url_requested_port) = comm::channel();
+        let mock_resource_task = mock_resource_task(box SendTestImageAndNotify {
+            url_requested_chan: url_requested_chan,
+        });
+
+        // Room for one decoded test image, but not for two
+        let image_cache_task = ImageCacheTask::new(mock_resource_task.clone(), TaskPool::new(4),
+                                                   MemoryProfiler::create(None), 700 * 1000);
+        let url1 = Url::parse("file:///1").unwrap();
+        let url2 = Url::parse("file:///2").unwrap();
+
+        match load_and_wait_for_image(&image_cache_task, &url1) {
+          ImageReady(..) => (),
+          _ => panic!("bleh")
+        }
+        match load_and_wait_for_image(&image_cache_task, &url2) {
+          ImageReady(..) => (),
+          _ => panic!("bleh")
+        }
+        url_requested_port.recv();
+        url_requested_port.recv();
+
+        // Decoding the second image evicted the first
+        let (response_chan, response_port) = comm::channel();
+        image_cache_task.send(GetImage(url1.clone(), response_chan));
+        assert!(response_port.recv() == ImageNotReady);
+
+        let (response_chan, response_port) = comm::channel();
+        image_cache_task.send(WaitForImage(url1, response_chan));
+        match response_port.recv() {
+          ImageReady(..) => (),
+          _ => panic!("bleh")
+        }
+
+        // The image was decoded again from the data the cache kept
+        assert!(url_requested_port.try_recv().is_err());
+
+        image_cache_task.exit();
+        mock_resource_task.send(resource_task::Exit);
+    }
+
+    #[test]
+    fn should_fetch_evicted_images_again() {
+        let (url_requested_chan, url_requested_port) = comm::channel();
+        let mock_resource_task = mock_resource_task(box SendTestImageAndNotify {
+            url_requested_chan: url_requested_chan,
+        });
+
+        // No room for anything but the most recently used image
+        let image_cache_task = ImageCacheTask::new(mock_resource_task.clone(), TaskPool::new(4),
+                                                   MemoryProfiler::create(None), 0);
+        let url1 = Url::parse("file:///1").unwrap();
+        let url2 = Url::parse("file:///2").unwrap();
+
+        match load_and_wait_for_image(&image_cache_task, &url1) {
+          ImageReady(..) => (),
+          _ => panic!("bleh")
+        }
+        match load_and_wait_for_image(&image_cache_task, &url2) {
+          ImageReady(..) => (),
+          _ => panic!("bleh")
+        }
+        url_requested_port.recv();
+        url_requested_port.recv();
+
+        let (response_chan, response_port) = comm::channel();
+        image_cache_task.send(WaitForImage(url1, response_chan));
+        match response_port.recv() {
+          ImageReady(..) => (),
+          _ => panic!("bleh")
+        }
+
+        // The data was evicted along with the pixels, so it was fetched again
+        url_requested_port.recv();
+
+        image_cache_task.exit();
+        mock_resource_task.send(resource_task::Exit);
+    }
+
     #[test]
     fn sync_cache_should_wait_for_images() {
         let mock_resource_task = mock_resource_task(box SendTestImage);
 
-        let image_cache_task = ImageCacheTask::new_sync(mock_resource_task.clone(), TaskPool::new(4));
+        let image_cache_task = ImageCacheTask::new_sync(mock_resource_task.clone(),
+                                                        TaskPool::new(4),
+                                                        MemoryProfiler::create(None), 1 << 30);
         let url = Url::parse("file:///").unwrap();
 
         image_cache_task.send(Prefetch(url.clone()));
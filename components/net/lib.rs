@@ -8,6 +8,7 @@
 #![deny(unused_variables)]
 
 extern crate collections;
+extern crate flate2;
 extern crate geom;
 extern crate http;
 extern crate png;
@@ -26,16 +27,26 @@ extern crate url;
 /// However, image handling is generally very integrated with the network stack (especially where
 /// caching is involved) and as a result it must live in here.
 pub mod image {
+    pub mod apng;
     pub mod base;
+    pub mod decoder;
+    pub mod gif;
     pub mod holder;
+    pub mod vp8;
+    pub mod vp8l;
+    pub mod webp;
 }
 
 pub mod about_loader;
+pub mod cookie;
+pub mod cookie_storage;
 pub mod file_loader;
+pub mod http_cache;
 pub mod http_loader;
 pub mod data_loader;
 pub mod image_cache_task;
 pub mod local_image_cache;
+pub mod mime_classifier;
 pub mod resource_task;
 pub mod storage_task;
 mod sniffer_task;
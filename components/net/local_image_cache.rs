@@ -8,13 +8,15 @@ extra message traffic, it also avoids waiting on the same image
 multiple times and thus triggering reflows multiple times.
 */
 
-use image_cache_task::{Decode, GetImage, ImageCacheTask, ImageFailed, ImageNotReady, ImageReady};
-use image_cache_task::{ImageResponseMsg, Prefetch, WaitForImage};
+use image::base::DecodedImage;
+use image_cache_task::{Decode, GetFrames, GetImage, ImageCacheTask, ImageFailed, ImageNotReady};
+use image_cache_task::{ImagePartial, ImageReady, ImageResponseMsg, Prefetch, WaitForProgress};
 
 use std::comm::{Receiver, channel};
 use std::collections::HashMap;
 use std::collections::hash_map::{Occupied, Vacant};
 use servo_util::task::spawn_named;
+use sync::Arc;
 use url::Url;
 
 pub trait ImageResponder<NodeAddress: Send> {
@@ -44,7 +46,9 @@ struct ImageState {
     prefetched: bool,
     decoded: bool,
     last_request_round: uint,
-    last_response: ImageResponseMsg
+    last_response: ImageResponseMsg,
+    /// When the image first asked for its animation to start, in seconds.
+    animation_start: Option<f64>,
 }
 
 impl<NodeAddress: Send> LocalImageCache<NodeAddress> {
@@ -93,14 +97,17 @@ impl<NodeAddress: Send> LocalImageCache<NodeAddress> {
 
             match state.last_response {
                 ImageReady(ref image) => {
+                    // The image is kept alive here even if the remote
+                    // cache evicts its copy, so there is no need to
+                    // ask for it again
                     let (chan, port) = channel();
                     chan.send(ImageReady(image.clone()));
                     return port;
                 }
-                ImageNotReady => {
+                ImageNotReady | ImagePartial(..) => {
                     if last_round == round_number {
                         let (chan, port) = channel();
-                        chan.send(ImageNotReady);
+                        chan.send(state.last_response.clone());
                         return port;
                     } else {
                         // We haven't requested the image from the
@@ -120,8 +127,9 @@ impl<NodeAddress: Send> LocalImageCache<NodeAddress> {
 
         let response = response_port.recv();
         match response {
-            ImageNotReady => {
-                // Need to reflow when the image is available
+            ImageNotReady | ImagePartial(..) => {
+                // Need to reflow when more of the image is available, which
+                // may be before all of it is
                 // FIXME: Instead we should be just passing a Future
                 // to the caller, then to the display list. Finally,
                 // the compositor should be resonsible for waiting
@@ -133,7 +141,7 @@ impl<NodeAddress: Send> LocalImageCache<NodeAddress> {
                 let url = (*url).clone();
                 spawn_named("LocalImageCache", proc() {
                     let (response_chan, response_port) = channel();
-                    image_cache_task.send(WaitForImage(url, response_chan));
+                    image_cache_task.send(WaitForProgress(url, response_chan));
                     on_image_available(response_port.recv(), node_address);
                 });
             }
@@ -143,6 +151,7 @@ impl<NodeAddress: Send> LocalImageCache<NodeAddress> {
         // Put a copy of the response in the cache
         let response_copy = match response {
             ImageReady(ref image) => ImageReady(image.clone()),
+            ImagePartial(ref image) => ImagePartial(image.clone()),
             ImageNotReady => ImageNotReady,
             ImageFailed => ImageFailed
         };
@@ -153,6 +162,27 @@ impl<NodeAddress: Send> LocalImageCache<NodeAddress> {
         return port;
     }
 
+    /// Requests every frame of the image decoded so far, for animating it.
+    pub fn get_frames(&mut self, url: &Url) -> Option<Arc<DecodedImage>> {
+        let (response_chan, response_port) = channel();
+        self.image_cache_task.send(GetFrames((*url).clone(), response_chan));
+        response_port.recv()
+    }
+
+    /// Returns the time at which the animation of the image started, in seconds, starting it at
+    /// `time` if this is the first time it is asked for. Every use of the same image shows the
+    /// same frame.
+    pub fn animation_start(&mut self, url: &Url, time: f64) -> f64 {
+        let state = self.get_state(url);
+        match state.animation_start {
+            Some(start) => start,
+            None => {
+                state.animation_start = Some(time);
+                time
+            }
+        }
+    }
+
     fn get_state<'a>(&'a mut self, url: &Url) -> &'a mut ImageState {
         match self.state_map.entry((*url).clone()) {
             Occupied(entry) => entry.into_mut(),
@@ -162,6 +192,7 @@ impl<NodeAddress: Send> LocalImageCache<NodeAddress> {
                     decoded: false,
                     last_request_round: 0,
                     last_response: ImageNotReady,
+                    animation_start: None,
                 })
         }
     }
@@ -0,0 +1,568 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! An implementation of the [MIME Sniffing standard](http://mimesniff.spec.whatwg.org/).
+
+use std::ascii::AsciiExt;
+use std::cmp;
+use std::iter::range_step;
+
+/// The number of bytes of the resource header that the sniffing algorithms look at.
+/// http://mimesniff.spec.whatwg.org/#reading-the-resource-header
+pub const RESOURCE_HEADER_LENGTH: uint = 1445;
+
+/// A MIME type as a `(type, subtype)` pair, as stored in `Metadata::content_type`.
+pub type MIMEType = (String, String);
+
+/// A byte pattern as described by http://mimesniff.spec.whatwg.org/#matching-a-mime-type-pattern
+struct ByteMatcher {
+    pattern: &'static [u8],
+    mask: &'static [u8],
+    /// Bytes that may precede the pattern and are skipped over.
+    leading_ignore: &'static [u8],
+    content_type: (&'static str, &'static str),
+}
+
+impl ByteMatcher {
+    fn matches(&self, data: &[u8]) -> bool {
+        let start = data.iter().position(|b| !self.leading_ignore.contains(b)).unwrap_or(data.len());
+        let data = data.slice_from(start);
+        if data.len() < self.pattern.len() {
+            return false;
+        }
+        self.pattern.iter().zip(self.mask.iter()).zip(data.iter()).all(|((&p, &m), &d)| {
+            d & m == p
+        })
+    }
+
+    fn classify(&self, data: &[u8]) -> Option<MIMEType> {
+        if self.matches(data) {
+            let (type_, subtype) = self.content_type;
+            Some((type_.to_string(), subtype.to_string()))
+        } else {
+            None
+        }
+    }
+}
+
+const WHITESPACE: &'static [u8] = b"\t\n\x0C\r ";
+
+/// The HTML tags that identify a resource as `text/html`, when followed by a tag-terminating byte.
+/// http://mimesniff.spec.whatwg.org/#identifying-a-resource-with-an-unknown-mime-type
+static HTML_TAGS: [&'static [u8], ..16] = [
+    b"<!DOCTYPE HTML", b"<HTML", b"<HEAD", b"<SCRIPT", b"<IFRAME", b"<H1", b"<DIV", b"<FONT",
+    b"<TABLE", b"<A", b"<STYLE", b"<TITLE", b"<B", b"<BODY", b"<BR", b"<P",
+];
+
+/// Scriptable types other than HTML.
+static SCRIPTABLE_PATTERNS: [ByteMatcher, ..3] = [
+    ByteMatcher {
+        pattern: b"<!--",
+        mask: b"\xFF\xFF\xFF\xFF",
+        leading_ignore: WHITESPACE,
+        content_type: ("text", "html"),
+    },
+    ByteMatcher {
+        pattern: b"<?xml",
+        mask: b"\xFF\xFF\xFF\xFF\xFF",
+        leading_ignore: WHITESPACE,
+        content_type: ("text", "xml"),
+    },
+    ByteMatcher {
+        pattern: b"%PDF-",
+        mask: b"\xFF\xFF\xFF\xFF\xFF",
+        leading_ignore: b"",
+        content_type: ("application", "pdf"),
+    },
+];
+
+/// Non-scriptable types that can be identified from a resource with an unknown MIME type.
+static NON_SCRIPTABLE_PATTERNS: [ByteMatcher, ..4] = [
+    ByteMatcher {
+        pattern: b"%!PS-Adobe-",
+        mask: b"\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF",
+        leading_ignore: b"",
+        content_type: ("application", "postscript"),
+    },
+    // UTF-16BE, UTF-16LE and UTF-8 byte order marks.
+    ByteMatcher {
+        pattern: b"\xFE\xFF\x00\x00",
+        mask: b"\xFF\xFF\x00\x00",
+        leading_ignore: b"",
+        content_type: ("text", "plain"),
+    },
+    ByteMatcher {
+        pattern: b"\xFF\xFE\x00\x00",
+        mask: b"\xFF\xFF\x00\x00",
+        leading_ignore: b"",
+        content_type: ("text", "plain"),
+    },
+    ByteMatcher {
+        pattern: b"\xEF\xBB\xBF\x00",
+        mask: b"\xFF\xFF\xFF\x00",
+        leading_ignore: b"",
+        content_type: ("text", "plain"),
+    },
+];
+
+/// http://mimesniff.spec.whatwg.org/#matching-an-image-type-pattern
+static IMAGE_PATTERNS: [ByteMatcher, ..8] = [
+    ByteMatcher {
+        pattern: b"\x00\x00\x01\x00",
+        mask: b"\xFF\xFF\xFF\xFF",
+        leading_ignore: b"",
+        content_type: ("image", "x-icon"),
+    },
+    ByteMatcher {
+        pattern: b"\x00\x00\x02\x00",
+        mask: b"\xFF\xFF\xFF\xFF",
+        leading_ignore: b"",
+        content_type: ("image", "x-icon"),
+    },
+    ByteMatcher {
+        pattern: b"BM",
+        mask: b"\xFF\xFF",
+        leading_ignore: b"",
+        content_type: ("image", "bmp"),
+    },
+    ByteMatcher {
+        pattern: b"GIF87a",
+        mask: b"\xFF\xFF\xFF\xFF\xFF\xFF",
+        leading_ignore: b"",
+        content_type: ("image", "gif"),
+    },
+    ByteMatcher {
+        pattern: b"GIF89a",
+        mask: b"\xFF\xFF\xFF\xFF\xFF\xFF",
+        leading_ignore: b"",
+        content_type: ("image", "gif"),
+    },
+    ByteMatcher {
+        pattern: b"RIFF\x00\x00\x00\x00WEBPVP",
+        mask: b"\xFF\xFF\xFF\xFF\x00\x00\x00\x00\xFF\xFF\xFF\xFF\xFF\xFF",
+        leading_ignore: b"",
+        content_type: ("image", "webp"),
+    },
+    ByteMatcher {
+        pattern: b"\x89PNG\r\n\x1A\n",
+        mask: b"\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF",
+        leading_ignore: b"",
+        content_type: ("image", "png"),
+    },
+    ByteMatcher {
+        pattern: b"\xFF\xD8\xFF",
+        mask: b"\xFF\xFF\xFF",
+        leading_ignore: b"",
+        content_type: ("image", "jpeg"),
+    },
+];
+
+/// http://mimesniff.spec.whatwg.org/#matching-an-audio-or-video-type-pattern
+static AUDIO_VIDEO_PATTERNS: [ByteMatcher, ..7] = [
+    ByteMatcher {
+        pattern: b"\x1A\x45\xDF\xA3",
+        mask: b"\xFF\xFF\xFF\xFF",
+        leading_ignore: b"",
+        content_type: ("video", "webm"),
+    },
+    ByteMatcher {
+        pattern: b".snd",
+        mask: b"\xFF\xFF\xFF\xFF",
+        leading_ignore: b"",
+        content_type: ("audio", "basic"),
+    },
+    ByteMatcher {
+        pattern: b"FORM\x00\x00\x00\x00AIFF",
+        mask: b"\xFF\xFF\xFF\xFF\x00\x00\x00\x00\xFF\xFF\xFF\xFF",
+        leading_ignore: b"",
+        content_type: ("audio", "aiff"),
+    },
+    ByteMatcher {
+        pattern: b"ID3",
+        mask: b"\xFF\xFF\xFF",
+        leading_ignore: b"",
+        content_type: ("audio", "mpeg"),
+    },
+    ByteMatcher {
+        pattern: b"OggS\x00",
+        mask: b"\xFF\xFF\xFF\xFF\xFF",
+        leading_ignore: b"",
+        content_type: ("application", "ogg"),
+    },
+    ByteMatcher {
+        pattern: b"MThd\x00\x00\x00\x06",
+        mask: b"\xFF\xFF\xFF\xFF\xFF\xFF\xFF\xFF",
+        leading_ignore: b"",
+        content_type: ("audio", "midi"),
+    },
+    ByteMatcher {
+        pattern: b"RIFF\x00\x00\x00\x00WAVE",
+        mask: b"\xFF\xFF\xFF\xFF\x00\x00\x00\x00\xFF\xFF\xFF\xFF",
+        leading_ignore: b"",
+        content_type: ("audio", "wave"),
+    },
+];
+
+/// http://mimesniff.spec.whatwg.org/#matching-a-font-type-pattern
+static FONT_PATTERNS: [ByteMatcher, ..5] = [
+    ByteMatcher {
+        pattern: b"\x00\x01\x00\x00",
+        mask: b"\xFF\xFF\xFF\xFF",
+        leading_ignore: b"",
+        content_type: ("application", "font-sfnt"),
+    },
+    ByteMatcher {
+        pattern: b"OTTO",
+        mask: b"\xFF\xFF\xFF\xFF",
+        leading_ignore: b"",
+        content_type: ("application", "font-sfnt"),
+    },
+    ByteMatcher {
+        pattern: b"ttcf",
+        mask: b"\xFF\xFF\xFF\xFF",
+        leading_ignore: b"",
+        content_type: ("application", "font-sfnt"),
+    },
+    ByteMatcher {
+        pattern: b"wOFF",
+        mask: b"\xFF\xFF\xFF\xFF",
+        leading_ignore: b"",
+        content_type: ("application", "font-woff"),
+    },
+    ByteMatcher {
+        pattern: b"wOF2",
+        mask: b"\xFF\xFF\xFF\xFF",
+        leading_ignore: b"",
+        content_type: ("application", "font-woff2"),
+    },
+];
+
+/// http://mimesniff.spec.whatwg.org/#matching-an-archive-type-pattern
+static ARCHIVE_PATTERNS: [ByteMatcher, ..3] = [
+    ByteMatcher {
+        pattern: b"\x1F\x8B\x08",
+        mask: b"\xFF\xFF\xFF",
+        leading_ignore: b"",
+        content_type: ("application", "x-gzip"),
+    },
+    ByteMatcher {
+        pattern: b"PK\x03\x04",
+        mask: b"\xFF\xFF\xFF\xFF",
+        leading_ignore: b"",
+        content_type: ("application", "zip"),
+    },
+    ByteMatcher {
+        pattern: b"Rar \x1A\x07\x00",
+        mask: b"\xFF\xFF\xFF\xFF\xFF\xFF\xFF",
+        leading_ignore: b"",
+        content_type: ("application", "x-rar-compressed"),
+    },
+];
+
+fn classify_with(patterns: &[ByteMatcher], data: &[u8]) -> Option<MIMEType> {
+    patterns.iter().filter_map(|matcher| matcher.classify(data)).next()
+}
+
+fn is_tag_terminating_byte(byte: u8) -> bool {
+    byte == b' ' || byte == b'>'
+}
+
+/// Match the HTML patterns from step 1 of
+/// http://mimesniff.spec.whatwg.org/#identifying-a-resource-with-an-unknown-mime-type
+fn is_html(data: &[u8]) -> bool {
+    let start = data.iter().position(|b| !WHITESPACE.contains(b)).unwrap_or(data.len());
+    let data = data.slice_from(start);
+    HTML_TAGS.iter().any(|tag| {
+        data.len() > tag.len() &&
+            data.slice_to(tag.len()).eq_ignore_ascii_case(*tag) &&
+            is_tag_terminating_byte(data[tag.len()])
+    })
+}
+
+/// http://mimesniff.spec.whatwg.org/#signature-for-mp4
+fn is_mp4(data: &[u8]) -> bool {
+    if data.len() < 12 {
+        return false;
+    }
+    let box_size = (data[0] as uint << 24) | (data[1] as uint << 16) |
+                   (data[2] as uint << 8) | data[3] as uint;
+    if data.len() < box_size || box_size % 4 != 0 || box_size < 12 {
+        return false;
+    }
+    if data.slice(4, 8) != b"ftyp" {
+        return false;
+    }
+    if data.slice(8, 11) == b"mp4" {
+        return true;
+    }
+    range_step(16, box_size, 4).any(|offset| {
+        offset + 3 <= box_size && data.slice(offset, offset + 3) == b"mp4"
+    })
+}
+
+/// http://mimesniff.spec.whatwg.org/#binary-data-byte
+fn is_binary_data_byte(byte: u8) -> bool {
+    match byte {
+        0x00...0x08 | 0x0B | 0x0E...0x1A | 0x1C...0x1F => true,
+        _ => false,
+    }
+}
+
+/// http://mimesniff.spec.whatwg.org/#rules-for-text-or-binary
+fn classify_text_or_binary(data: &[u8]) -> MIMEType {
+    let is_utf16_bom = data.len() >= 2 &&
+        (data.slice_to(2) == b"\xFE\xFF" || data.slice_to(2) == b"\xFF\xFE");
+    let is_utf8_bom = data.len() >= 3 && data.slice_to(3) == b"\xEF\xBB\xBF";
+    if is_utf16_bom || is_utf8_bom || !data.iter().any(|&b| is_binary_data_byte(b)) {
+        ("text".to_string(), "plain".to_string())
+    } else {
+        ("application".to_string(), "octet-stream".to_string())
+    }
+}
+
+/// http://mimesniff.spec.whatwg.org/#rules-for-identifying-an-unknown-mime-type
+fn classify_unknown(data: &[u8], sniff_scriptable: bool) -> MIMEType {
+    if sniff_scriptable {
+        if is_html(data) {
+            return ("text".to_string(), "html".to_string());
+        }
+        match classify_with(SCRIPTABLE_PATTERNS.as_slice(), data) {
+            Some(mime_type) => return mime_type,
+            None => {}
+        }
+    }
+
+    match classify_with(NON_SCRIPTABLE_PATTERNS.as_slice(), data)
+            .or_else(|| classify_with(IMAGE_PATTERNS.as_slice(), data))
+            .or_else(|| classify_audio_video(data))
+            .or_else(|| classify_with(ARCHIVE_PATTERNS.as_slice(), data)) {
+        Some(mime_type) => mime_type,
+        None => classify_text_or_binary(data),
+    }
+}
+
+fn classify_audio_video(data: &[u8]) -> Option<MIMEType> {
+    if is_mp4(data) {
+        return Some(("video".to_string(), "mp4".to_string()));
+    }
+    classify_with(AUDIO_VIDEO_PATTERNS.as_slice(), data)
+}
+
+/// Whether `data` matches any of the font type patterns.
+pub fn classify_font(data: &[u8]) -> Option<MIMEType> {
+    classify_with(FONT_PATTERNS.as_slice(), data)
+}
+
+fn is_unknown_type(mime_type: &Option<MIMEType>) -> bool {
+    match *mime_type {
+        None => true,
+        Some((ref type_, ref subtype)) => {
+            match (type_.as_slice(), subtype.as_slice()) {
+                ("unknown", "unknown") | ("application", "unknown") | ("*", "*") => true,
+                _ => false,
+            }
+        }
+    }
+}
+
+fn is_xml_type(type_: &str, subtype: &str) -> bool {
+    subtype.ends_with("+xml") ||
+        (subtype == "xml" && (type_ == "text" || type_ == "application"))
+}
+
+/// Whether the computed MIME type of a resource may differ from `supplied_type`, so that its
+/// resource header has to be read. The arguments are as for `classify`.
+pub fn may_sniff(supplied_type: &Option<MIMEType>, no_sniff: bool,
+                 check_for_apache_bug: bool) -> bool {
+    if is_unknown_type(supplied_type) {
+        return true
+    }
+    if no_sniff {
+        return false
+    }
+    if check_for_apache_bug {
+        return true
+    }
+    let (ref type_, ref subtype) = *supplied_type.as_ref().unwrap();
+    match type_.as_slice() {
+        "image" | "audio" | "video" => !is_xml_type(type_.as_slice(), subtype.as_slice()),
+        _ => false,
+    }
+}
+
+/// Determine the computed MIME type of a resource.
+/// http://mimesniff.spec.whatwg.org/#determining-the-computed-mime-type-of-a-resource
+///
+/// `check_for_apache_bug` should be set when the supplied `Content-Type` was one of the values
+/// that old Apache versions send by default for every resource.
+pub fn classify(data: &[u8], supplied_type: &Option<MIMEType>, no_sniff: bool,
+                check_for_apache_bug: bool) -> Option<MIMEType> {
+    let data = data.slice_to(cmp::min(data.len(), RESOURCE_HEADER_LENGTH));
+
+    if is_unknown_type(supplied_type) {
+        return Some(classify_unknown(data, !no_sniff));
+    }
+
+    let (type_, subtype) = supplied_type.clone().unwrap();
+
+    if no_sniff {
+        return Some((type_, subtype));
+    }
+
+    if check_for_apache_bug {
+        return Some(classify_text_or_binary(data));
+    }
+
+    if is_xml_type(type_.as_slice(), subtype.as_slice()) ||
+            (type_.as_slice() == "text" && subtype.as_slice() == "html") {
+        return Some((type_, subtype));
+    }
+
+    let sniffed = match type_.as_slice() {
+        "image" => classify_with(IMAGE_PATTERNS.as_slice(), data),
+        "audio" | "video" => classify_audio_video(data),
+        _ => None,
+    };
+    Some(sniffed.unwrap_or((type_, subtype)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{classify, classify_font, may_sniff};
+
+    fn mime(type_: &str, subtype: &str) -> Option<(String, String)> {
+        Some((type_.to_string(), subtype.to_string()))
+    }
+
+    fn test_unknown(data: &[u8], expected: (&str, &str)) {
+        let (type_, subtype) = expected;
+        assert_eq!(classify(data, &None, false, false), mime(type_, subtype));
+    }
+
+    #[test]
+    fn test_sniff_unknown_table() {
+        let cases: Vec<(&[u8], (&str, &str))> = vec!(
+            (b"<!DOCTYPE html>", ("text", "html")),
+            (b"  \n<HTML>", ("text", "html")),
+            (b"<html ", ("text", "html")),
+            (b"<head>", ("text", "html")),
+            (b"<script>", ("text", "html")),
+            (b"<iframe>", ("text", "html")),
+            (b"<h1>", ("text", "html")),
+            (b"<div>", ("text", "html")),
+            (b"<font>", ("text", "html")),
+            (b"<table>", ("text", "html")),
+            (b"<a>", ("text", "html")),
+            (b"<style>", ("text", "html")),
+            (b"<title>", ("text", "html")),
+            (b"<b>", ("text", "html")),
+            (b"<body>", ("text", "html")),
+            (b"<br>", ("text", "html")),
+            (b"<p>", ("text", "html")),
+            (b"<!-- comment -->", ("text", "html")),
+            (b"<?xml version=\"1.0\"?>", ("text", "xml")),
+            (b"%PDF-1.4", ("application", "pdf")),
+            (b"%!PS-Adobe-3.0", ("application", "postscript")),
+            (b"\xFE\xFF\x00h", ("text", "plain")),
+            (b"\xFF\xFEh\x00", ("text", "plain")),
+            (b"\xEF\xBB\xBFhello", ("text", "plain")),
+            (b"\x00\x00\x01\x00\x01\x00", ("image", "x-icon")),
+            (b"\x00\x00\x02\x00\x01\x00", ("image", "x-icon")),
+            (b"BM\x00\x00\x00\x00", ("image", "bmp")),
+            (b"GIF87a\x01\x00", ("image", "gif")),
+            (b"GIF89a\x01\x00", ("image", "gif")),
+            (b"RIFF\x24\x00\x00\x00WEBPVP8 ", ("image", "webp")),
+            (b"\x89PNG\r\n\x1A\n\x00\x00", ("image", "png")),
+            (b"\xFF\xD8\xFF\xE0", ("image", "jpeg")),
+            (b"\x1A\x45\xDF\xA3\x01\x00", ("video", "webm")),
+            (b".snd\x00\x00", ("audio", "basic")),
+            (b"FORM\x00\x00\x00\x00AIFF", ("audio", "aiff")),
+            (b"ID3\x03\x00", ("audio", "mpeg")),
+            (b"OggS\x00\x02", ("application", "ogg")),
+            (b"MThd\x00\x00\x00\x06\x00", ("audio", "midi")),
+            (b"RIFF\x24\x00\x00\x00WAVEfmt ", ("audio", "wave")),
+            (b"\x00\x00\x00\x18ftypmp42\x00\x00\x00\x00mp41isom", ("video", "mp4")),
+            (b"\x1F\x8B\x08\x00", ("application", "x-gzip")),
+            (b"PK\x03\x04\x14\x00", ("application", "zip")),
+            (b"Rar \x1A\x07\x00\x01", ("application", "x-rar-compressed")),
+            (b"just some text", ("text", "plain")),
+            (b"", ("text", "plain")),
+            (b"\x00\x01\x02binary", ("application", "octet-stream")),
+        );
+        for &(data, expected) in cases.iter() {
+            test_unknown(data, expected);
+        }
+    }
+
+    #[test]
+    fn test_sniff_html_needs_tag_terminator() {
+        test_unknown(b"<htmlfoo", ("text", "plain"));
+        test_unknown(b"<bodyx", ("text", "plain"));
+    }
+
+    #[test]
+    fn test_unknown_supplied_types() {
+        let html = b"<html>";
+        assert_eq!(classify(html, &mime("unknown", "unknown"), false, false), mime("text", "html"));
+        assert_eq!(classify(html, &mime("application", "unknown"), false, false), mime("text", "html"));
+        assert_eq!(classify(html, &mime("*", "*"), false, false), mime("text", "html"));
+    }
+
+    #[test]
+    fn test_no_sniff() {
+        // Scriptable types are never sniffed from an unknown type when nosniff is set.
+        assert_eq!(classify(b"<html>", &None, true, false), mime("text", "plain"));
+        assert_eq!(classify(b"GIF89a", &None, true, false), mime("image", "gif"));
+        assert_eq!(classify(b"GIF89a", &mime("image", "png"), true, false), mime("image", "png"));
+    }
+
+    #[test]
+    fn test_apache_bug() {
+        assert_eq!(classify(b"\x00\x01binary", &mime("text", "plain"), false, true),
+                   mime("application", "octet-stream"));
+        assert_eq!(classify(b"plain text", &mime("text", "plain"), false, true),
+                   mime("text", "plain"));
+    }
+
+    #[test]
+    fn test_supplied_types() {
+        // Images and media are corrected to what their signature says.
+        assert_eq!(classify(b"GIF89a", &mime("image", "png"), false, false), mime("image", "gif"));
+        assert_eq!(classify(b"not an image", &mime("image", "png"), false, false), mime("image", "png"));
+        assert_eq!(classify(b"OggS\x00", &mime("audio", "mpeg"), false, false),
+                   mime("application", "ogg"));
+
+        // Everything else is left alone.
+        assert_eq!(classify(b"GIF89a", &mime("text", "html"), false, false), mime("text", "html"));
+        assert_eq!(classify(b"<html>", &mime("application", "xhtml+xml"), false, false),
+                   mime("application", "xhtml+xml"));
+        assert_eq!(classify(b"<html>", &mime("text", "css"), false, false), mime("text", "css"));
+    }
+
+    #[test]
+    fn test_may_sniff() {
+        assert!(may_sniff(&None, false, false));
+        assert!(may_sniff(&mime("image", "png"), false, false));
+        assert!(may_sniff(&mime("text", "plain"), false, true));
+        assert!(!may_sniff(&mime("image", "png"), true, false));
+        assert!(!may_sniff(&mime("image", "svg+xml"), false, false));
+        assert!(!may_sniff(&mime("text", "html"), false, false));
+        assert!(!may_sniff(&mime("text", "css"), false, false));
+    }
+
+    #[test]
+    fn test_font_table() {
+        let cases: Vec<(&[u8], Option<(String, String)>)> = vec!(
+            (b"\x00\x01\x00\x00\x00\x10", mime("application", "font-sfnt")),
+            (b"OTTO\x00\x10", mime("application", "font-sfnt")),
+            (b"ttcf\x00\x01", mime("application", "font-sfnt")),
+            (b"wOFF\x00\x01", mime("application", "font-woff")),
+            (b"wOF2\x00\x01", mime("application", "font-woff2")),
+            (b"<html>", None),
+        );
+        for &(data, ref expected) in cases.iter() {
+            assert_eq!(&classify_font(data), expected);
+        }
+    }
+}
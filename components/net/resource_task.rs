@@ -4,10 +4,16 @@
 
 //! A task that takes a URL and streams back the binary data.
 
-use about_loader;
-use data_loader;
-use file_loader;
-use http_loader;
+use cookie;
+use cookie_storage::CookieStorage;
+use fetch::cors_cache::{CORSCache, CORSCacheTask, CORSCacheSender, ExitMsg};
+use fetch::request::{Request, FetchContext, Subresource, XMLHttpRequest, RefererNone};
+use fetch::request::{CORSMode, ForcedPreflightMode, Include, CredentialsSameOrigin};
+use fetch::response::{Response, Opaque};
+use fetch::response::Done as BodyDone;
+use fetch::response::Receiving as BodyReceiving;
+use http_cache;
+use http_cache::HttpCacheTask;
 use sniffer_task;
 use sniffer_task::SnifferTask;
 
@@ -26,6 +32,10 @@ use servo_util::task::spawn_named;
 pub enum ControlMsg {
     /// Request the data associated with a particular URL
     Load(LoadData),
+    /// Store a cookie from a `Set-Cookie` header value (or `document.cookie` assignment)
+    SetCookiesForUrl(Url, String, cookie::CookieSource),
+    /// Retrieve the stored cookies for a given URL, formatted as a `Cookie` header value
+    GetCookiesForUrl(Url, Sender<Option<String>>, cookie::CookieSource),
     Exit
 }
 
@@ -54,10 +64,12 @@ impl LoadData {
 
 #[deriving(Clone)]
 pub struct ResourceCORSData {
-    /// CORS Preflight flag
+    /// Whether a CORS preflight is required even for simple requests
     pub preflight: bool,
     /// Origin of CORS Request
-    pub origin: Url
+    pub origin: Url,
+    /// Whether cookies should be sent with the cross-origin request
+    pub credentials: bool
 }
 
 /// Metadata about a loaded resource, such as is obtained from HTTP headers.
@@ -181,12 +193,17 @@ pub fn load_whole_resource(resource_task: &ResourceTask, url: Url)
 /// Handle to a resource task
 pub type ResourceTask = Sender<ControlMsg>;
 
-/// Create a ResourceTask
-pub fn new_resource_task(user_agent: Option<String>) -> ResourceTask {
+/// Create a ResourceTask, optionally backed by an HTTP cache
+pub fn new_resource_task(user_agent: Option<String>, http_cache: Option<HttpCacheTask>) -> ResourceTask {
     let (setup_chan, setup_port) = channel();
     let sniffer_task = sniffer_task::new_sniffer_task();
+    let resource_task = setup_chan.clone();
+    let mut cors_cache_task = CORSCacheTask::new();
+    let cors_cache = cors_cache_task.get_sender();
+    spawn_named("CORSCacheTask", proc() cors_cache_task.run());
     spawn_named("ResourceManager", proc() {
-        ResourceManager::new(setup_port, user_agent, sniffer_task).start();
+        ResourceManager::new(setup_port, user_agent, sniffer_task, resource_task, http_cache,
+                             cors_cache).start();
     });
     setup_chan
 }
@@ -195,27 +212,50 @@ struct ResourceManager {
     from_client: Receiver<ControlMsg>,
     user_agent: Option<String>,
     sniffer_task: SnifferTask,
+    cookie_storage: CookieStorage,
+    /// A handle to this resource task, given to loaders that need to store cookies.
+    resource_task: ResourceTask,
+    http_cache: Option<HttpCacheTask>,
+    /// The CORS preflight cache shared by all fetches.
+    cors_cache: CORSCacheSender,
 }
 
 impl ResourceManager {
-    fn new(from_client: Receiver<ControlMsg>, user_agent: Option<String>, sniffer_task: SnifferTask) -> ResourceManager {
+    fn new(from_client: Receiver<ControlMsg>, user_agent: Option<String>, sniffer_task: SnifferTask,
+           resource_task: ResourceTask, http_cache: Option<HttpCacheTask>,
+           cors_cache: CORSCacheSender) -> ResourceManager {
         ResourceManager {
             from_client: from_client,
             user_agent: user_agent,
             sniffer_task: sniffer_task,
+            cookie_storage: CookieStorage::new(),
+            resource_task: resource_task,
+            http_cache: http_cache,
+            cors_cache: cors_cache,
         }
     }
 }
 
 
 impl ResourceManager {
-    fn start(&self) {
+    fn start(&mut self) {
         loop {
             match self.from_client.recv() {
               Load(load_data) => {
                 self.load(load_data)
               }
+              SetCookiesForUrl(request, cookie, source) => {
+                self.cookie_storage.set_cookie_for_url(&request, cookie.as_slice(), source)
+              }
+              GetCookiesForUrl(url, consumer, source) => {
+                consumer.send(self.cookie_storage.cookies_for_url(&url, source))
+              }
               Exit => {
+                match self.http_cache {
+                    Some(ref http_cache) => http_cache.send(http_cache::Exit),
+                    None => {}
+                }
+                self.cors_cache.send(ExitMsg);
                 break
               }
             }
@@ -223,28 +263,113 @@ impl ResourceManager {
     }
 
     fn load(&self, load_data: LoadData) {
-        let mut load_data = load_data;
-        load_data.headers.user_agent = self.user_agent.clone();
         let senders = ResponseSenders {
             immediate_consumer: self.sniffer_task.clone(),
             eventual_consumer: load_data.consumer.clone(),
         };
 
-        let loader = match load_data.url.scheme.as_slice() {
-            "file" => file_loader::factory,
-            "http" | "https" => http_loader::factory,
-            "data" => data_loader::factory,
-            "about" => about_loader::factory,
-            _ => {
-                debug!("resource_task: no loader for scheme {:s}", load_data.url.scheme);
-                start_sending(senders, Metadata::default(load_data.url))
-                    .send(Done(Err("no loader for scheme".to_string())));
-                return
-            }
+        let context = match load_data.cors {
+            Some(_) => XMLHttpRequest,
+            None => Subresource,
         };
+        let mut request = Request::new(load_data.url.clone(), context);
+        request.method = load_data.method;
+        request.headers = load_data.headers;
+        request.body = load_data.data;
+        request.referer = RefererNone;
+        match load_data.cors {
+            Some(cors) => {
+                request.origin = Some(cors.origin);
+                request.mode = if cors.preflight { ForcedPreflightMode } else { CORSMode };
+                request.unsafe_request = true;
+                request.credentials_mode = if cors.credentials { Include } else { CredentialsSameOrigin };
+            }
+            None => request.credentials_mode = Include,
+        }
+        request.cache = Some(box self.cors_cache.clone() as Box<CORSCache+Send>);
+        request.fetch_context = Some(FetchContext {
+            resource_task: self.resource_task.clone(),
+            http_cache: self.http_cache.clone(),
+            user_agent: self.user_agent.clone(),
+        });
+
         debug!("resource_task: loading url: {:s}", load_data.url.serialize());
+        spawn_named("Fetch", proc() {
+            let mut request = request;
+            let response = request.fetch(false);
+            send_response(response, request.url, senders);
+        });
+    }
+}
 
-        loader(load_data, self.sniffer_task.clone());
+/// Hand the response of a fetch over to the consumer of a `Load`, forwarding the body to it as
+/// it arrives from the loader.
+fn send_response(mut response: Response, url: Url, senders: ResponseSenders) {
+    let body_port = response.take_body_port();
+
+    // The consumers of loads are parts of the user agent itself, such as the image cache, which
+    // may use the content of an opaque response even though script may not inspect it.
+    let response = match response.response_type {
+        Opaque => match response.internal_response {
+            Some(internal) => *internal,
+            None => Response::network_error(),
+        },
+        _ => response,
+    };
+
+    let mut metadata = Metadata::default(response.url.clone().unwrap_or(url));
+    if response.is_network_error() {
+        metadata.status = None;
+        let reason = response.error_reason.unwrap_or_else(|| "network error".to_string());
+        match start_sending_opt(senders, metadata) {
+            Ok(progress_chan) => {
+                let _ = progress_chan.send_opt(Done(Err(reason)));
+            }
+            Err(()) => {}
+        }
+        return;
+    }
+    metadata.set_content_type(&response.headers.content_type);
+    metadata.status = Some(response.status);
+    metadata.headers = Some(response.headers);
+
+    let progress_chan = match start_sending_opt(senders, metadata) {
+        Ok(progress_chan) => progress_chan,
+        Err(()) => return,
+    };
+    match response.body {
+        BodyDone(body) | BodyReceiving(body) => {
+            if !body.is_empty() {
+                let _ = progress_chan.send_opt(Payload(body));
+            }
+        }
+        _ => {}
+    }
+
+    let body_port = match body_port {
+        Some(body_port) => body_port,
+        None => {
+            let _ = progress_chan.send_opt(Done(Ok(())));
+            return;
+        }
+    };
+    loop {
+        match body_port.recv_opt() {
+            Ok(Payload(data)) => {
+                if progress_chan.send_opt(Payload(data)).is_err() {
+                    // The consumer has gone away; dropping the port stops the loader.
+                    return;
+                }
+            }
+            Ok(Done(result)) => {
+                let _ = progress_chan.send_opt(Done(result));
+                return;
+            }
+            Err(()) => {
+                let _ = progress_chan.send_opt(Done(Err("loader went away".to_string())));
+                return;
+            }
+        }
     }
 }
 
@@ -278,13 +403,13 @@ impl Iterator<Vec<u8>> for ProgressMsgPortIterator {
 
 #[test]
 fn test_exit() {
-    let resource_task = new_resource_task(None);
+    let resource_task = new_resource_task(None, None);
     resource_task.send(Exit);
 }
 
 #[test]
 fn test_bad_scheme() {
-    let resource_task = new_resource_task(None);
+    let resource_task = new_resource_task(None, None);
     let (start_chan, start) = channel();
     let url = Url::parse("bogus://whatever").unwrap();
     resource_task.send(Load(LoadData::new(url, start_chan)));
@@ -295,3 +420,43 @@ fn test_bad_scheme() {
     }
     resource_task.send(Exit);
 }
+
+#[test]
+fn test_loader_error_reaches_consumer() {
+    let resource_task = new_resource_task(None, None);
+    let (start_chan, start) = channel();
+    let url = Url::parse("bogus://whatever").unwrap();
+    resource_task.send(Load(LoadData::new(url, start_chan)));
+    let response = start.recv();
+    match response.progress_port.recv() {
+      Done(Err(e)) => assert!(e.as_slice().contains("bogus")),
+      _ => panic!("expected the loader's error")
+    }
+    resource_task.send(Exit);
+}
+
+#[test]
+fn test_send_response_streams_body() {
+    let (body_chan, body_port) = channel();
+    let mut response = Response::new();
+    response.body = BodyReceiving(vec!());
+    response.body_port = Some(body_port);
+
+    let (start_chan, start_port) = channel();
+    let (consumer, _) = channel();
+    let senders = ResponseSenders {
+        immediate_consumer: start_chan,
+        eventual_consumer: consumer,
+    };
+    let url = Url::parse("http://example.com/").unwrap();
+    spawn_named("send_response", proc() send_response(response, url, senders));
+
+    // Each chunk reaches the consumer before the load has finished.
+    let progress_port = start_port.recv().load_response.progress_port;
+    body_chan.send(Payload(b"first".to_vec()));
+    assert_eq!(progress_port.recv(), Payload(b"first".to_vec()));
+    body_chan.send(Payload(b"second".to_vec()));
+    assert_eq!(progress_port.recv(), Payload(b"second".to_vec()));
+    body_chan.send(Done(Err("connection reset".to_string())));
+    assert_eq!(progress_port.recv(), Done(Err("connection reset".to_string())));
+}
@@ -3,9 +3,14 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 //! A task that sniffs data
+use mime_classifier;
+use resource_task::{LoadResponse, Metadata, ProgressMsg, Payload, Done, TargetedLoadResponse};
+
+use http::headers::HeaderEnum;
+use std::ascii::AsciiExt;
 use std::comm::{channel, Receiver, Sender};
 use std::task::TaskBuilder;
-use resource_task::{TargetedLoadResponse};
+use servo_util::task::spawn_named;
 
 pub type SnifferTask = Sender<TargetedLoadResponse>;
 
@@ -35,10 +40,133 @@ impl SnifferManager {
         loop {
             match self.data_receiver.recv_opt() {
                 Ok(snif_data) => {
-                    let _ = snif_data.consumer.send_opt(snif_data.load_response);
+                    if !should_sniff(&snif_data.load_response.metadata) {
+                        let TargetedLoadResponse { load_response, consumer } = snif_data;
+                        drop(consumer.send_opt(load_response));
+                        continue;
+                    }
+
+                    // Sniffing has to wait for the first bytes of the payload to arrive, so do it
+                    // in a separate task rather than holding up every other load.
+                    spawn_named("SnifferTask", proc() {
+                        sniff(snif_data);
+                    });
                 }
                 Err(_) => break,
             }
         }
     }
 }
+
+/// Buffer the resource header of a load, classify it, and pass the load on to its consumer
+/// with the computed MIME type.
+fn sniff(snif_data: TargetedLoadResponse) {
+    let TargetedLoadResponse { load_response, consumer } = snif_data;
+    let LoadResponse { mut metadata, progress_port } = load_response;
+
+    let mut buffered = vec!();
+    let mut header = vec!();
+    loop {
+        match progress_port.recv_opt() {
+            Ok(Payload(data)) => {
+                header.push_all(data.as_slice());
+                buffered.push(Payload(data));
+                if header.len() >= mime_classifier::RESOURCE_HEADER_LENGTH {
+                    break;
+                }
+            }
+            Ok(done @ Done(_)) => {
+                buffered.push(done);
+                break;
+            }
+            // The loader went away without finishing; pass on whatever we have.
+            Err(_) => break,
+        }
+    }
+
+    // There's nothing to sniff in a load that failed outright.
+    let failed = match buffered.as_slice() {
+        [Done(Err(_))] => true,
+        _ => false,
+    };
+    if !failed {
+        let no_sniff = has_nosniff_header(&metadata);
+        let check_for_apache_bug = is_apache_default_type(&metadata);
+        metadata.content_type = mime_classifier::classify(header.as_slice(), &metadata.content_type,
+                                                          no_sniff, check_for_apache_bug);
+    }
+
+    let (progress_chan, new_progress_port) = channel();
+    let result = consumer.send_opt(LoadResponse {
+        metadata: metadata,
+        progress_port: new_progress_port,
+    });
+    if result.is_err() {
+        return;
+    }
+
+    for msg in buffered.into_iter() {
+        if !forward(&progress_chan, msg) {
+            return;
+        }
+    }
+    loop {
+        match progress_port.recv_opt() {
+            Ok(msg) => {
+                if !forward(&progress_chan, msg) {
+                    return;
+                }
+            }
+            Err(_) => return,
+        }
+    }
+}
+
+/// Whether the computed MIME type of a load may differ from the one it was given, so that its
+/// resource header has to be buffered. The type of a data: URL is always the one it names.
+fn should_sniff(metadata: &Metadata) -> bool {
+    metadata.final_url.scheme.as_slice() != "data" &&
+        mime_classifier::may_sniff(&metadata.content_type,
+                                   has_nosniff_header(metadata),
+                                   is_apache_default_type(metadata))
+}
+
+/// Send `msg` to the consumer, returning whether more messages should follow.
+fn forward(progress_chan: &Sender<ProgressMsg>, msg: ProgressMsg) -> bool {
+    let is_done = match msg {
+        Done(_) => true,
+        Payload(_) => false,
+    };
+    progress_chan.send_opt(msg).is_ok() && !is_done
+}
+
+/// http://mimesniff.spec.whatwg.org/#no-sniff-flag
+fn has_nosniff_header(metadata: &Metadata) -> bool {
+    match metadata.headers {
+        Some(ref headers) => headers.iter().any(|header| {
+            header.header_name().as_slice().eq_ignore_ascii_case("x-content-type-options") &&
+                header.header_value().as_slice().trim().eq_ignore_ascii_case("nosniff")
+        }),
+        None => false,
+    }
+}
+
+/// http://mimesniff.spec.whatwg.org/#check-for-apache-bug-flag
+fn is_apache_default_type(metadata: &Metadata) -> bool {
+    // Only HTTP responses are affected.
+    if metadata.headers.is_none() {
+        return false;
+    }
+    match metadata.content_type {
+        Some((ref type_, ref subtype)) if type_.as_slice() == "text" && subtype.as_slice() == "plain" => {
+            match metadata.charset {
+                None => true,
+                Some(ref charset) => {
+                    let charset = charset.as_slice();
+                    charset.eq_ignore_ascii_case("iso-8859-1") || charset.eq_ignore_ascii_case("utf-8")
+                }
+            }
+        }
+        _ => false,
+    }
+}
@@ -1,34 +1,58 @@
 /* This Source Code Form is subject to the terms of the Mozilla Public
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+use fetch::request::serialize_origin;
+use serialize::json;
 use std::comm::{channel, Receiver, Sender};
 use std::collections::HashMap;
 use std::collections::TreeMap;
+use std::io::{File, fs};
+use std::io::fs::PathExtensions;
 use url::Url;
 
 use servo_util::str::DOMString;
 use servo_util::task::spawn_named;
 
+/// The maximum amount of data, in bytes of keys and values, that an origin may keep in
+/// each storage area. http://www.w3.org/TR/webstorage/#disk-space
+const QUOTA_SIZE_LIMIT: uint = 5 * 1024 * 1024;
+
+/// The file in the profile directory that local storage is persisted to.
+const LOCAL_STORAGE_FILE: &'static str = "local_storage.json";
+
+/// The storage area an operation applies to
+#[deriving(Clone, PartialEq, Show)]
+pub enum StorageType {
+    /// `sessionStorage`, which only lives as long as the browser does. Its areas are kept apart
+    /// for each top-level browsing context as well as each origin, so this holds the id of the
+    /// top-level browsing context; documents it navigates to and nested browsing contexts share
+    /// its areas.
+    Session(uint),
+    /// `localStorage`, which is kept in the profile directory if there is one
+    Local
+}
+
 /// Request operations on the storage data associated with a particular url
 pub enum StorageTaskMsg {
     /// gets the number of key/value pairs present in the associated storage data
-    Length(Sender<u32>, Url),
+    Length(Sender<u32>, Url, StorageType),
 
     /// gets the name of the key at the specified index in the associated storage data
-    Key(Sender<Option<DOMString>>, Url, u32),
+    Key(Sender<Option<DOMString>>, Url, StorageType, u32),
 
     /// gets the value associated with the given key in the associated storage data
-    GetItem(Sender<Option<DOMString>>, Url, DOMString),
+    GetItem(Sender<Option<DOMString>>, Url, StorageType, DOMString),
 
-    /// sets the value of the given key in the associated storage data
-    /// TODO throw QuotaExceededError in case of error
-    SetItem(Sender<bool>, Url, DOMString, DOMString),
+    /// sets the value of the given key in the associated storage data, replying with whether
+    /// the data changed and the old value, or `Err` if the origin's quota would be exceeded
+    SetItem(Sender<Result<(bool, Option<DOMString>), ()>>, Url, StorageType, DOMString, DOMString),
 
-    /// removes the key/value pair for the given key in the associated storage data
-    RemoveItem(Sender<bool>, Url, DOMString),
+    /// removes the key/value pair for the given key in the associated storage data,
+    /// replying with the removed value
+    RemoveItem(Sender<Option<DOMString>>, Url, StorageType, DOMString),
 
     /// clears the associated storage data by removing all the key/value pairs
-    Clear(Sender<bool>, Url),
+    Clear(Sender<bool>, Url, StorageType),
 
     /// shut down this task
     Exit
@@ -38,30 +62,40 @@ pub enum StorageTaskMsg {
 pub type StorageTask = Sender<StorageTaskMsg>;
 
 pub trait StorageTaskFactory {
-    fn new() -> StorageTask;
+    fn new(profile_dir: Option<Path>) -> StorageTask;
 }
 
 impl StorageTaskFactory for StorageTask {
-    /// Create a StorageTask
-    fn new() -> StorageTask {
+    /// Create a StorageTask, persisting local storage to `profile_dir` if it is given
+    fn new(profile_dir: Option<Path>) -> StorageTask {
         let (chan, port) = channel();
         spawn_named("StorageManager", proc() {
-            StorageManager::new(port).start();
+            StorageManager::new(port, profile_dir).start();
         });
         chan
     }
 }
 
+type OriginData = TreeMap<DOMString, DOMString>;
+
 struct StorageManager {
     port: Receiver<StorageTaskMsg>,
-    data: HashMap<String, TreeMap<DOMString, DOMString>>,
+    session_data: HashMap<(uint, String), OriginData>,
+    local_data: TreeMap<String, OriginData>,
+    profile_dir: Option<Path>,
 }
 
 impl StorageManager {
-    fn new(port: Receiver<StorageTaskMsg>) -> StorageManager {
+    fn new(port: Receiver<StorageTaskMsg>, profile_dir: Option<Path>) -> StorageManager {
+        let local_data = match profile_dir {
+            Some(ref dir) => load_local_data(dir),
+            None => TreeMap::new(),
+        };
         StorageManager {
             port: port,
-            data: HashMap::new(),
+            session_data: HashMap::new(),
+            local_data: local_data,
+            profile_dir: profile_dir,
         }
     }
 }
@@ -70,23 +104,23 @@ impl StorageManager {
     fn start(&mut self) {
         loop {
             match self.port.recv() {
-                Length(sender, url) => {
-                    self.length(sender, url)
+                Length(sender, url, storage_type) => {
+                    self.length(sender, url, storage_type)
                 }
-                Key(sender, url, index) => {
-                    self.key(sender, url, index)
+                Key(sender, url, storage_type, index) => {
+                    self.key(sender, url, storage_type, index)
                 }
-                SetItem(sender, url, name, value) => {
-                    self.set_item(sender, url, name, value)
+                SetItem(sender, url, storage_type, name, value) => {
+                    self.set_item(sender, url, storage_type, name, value)
                 }
-                GetItem(sender, url, name) => {
-                    self.get_item(sender, url, name)
+                GetItem(sender, url, storage_type, name) => {
+                    self.get_item(sender, url, storage_type, name)
                 }
-                RemoveItem(sender, url, name) => {
-                    self.remove_item(sender, url, name)
+                RemoveItem(sender, url, storage_type, name) => {
+                    self.remove_item(sender, url, storage_type, name)
                 }
-                Clear(sender, url) => {
-                    self.clear(sender, url)
+                Clear(sender, url, storage_type) => {
+                    self.clear(sender, url, storage_type)
                 }
                 Exit => {
                     break
@@ -95,71 +129,235 @@ impl StorageManager {
         }
     }
 
-    fn length(&self, sender: Sender<u32>, url: Url) {
-        let origin = self.get_origin_as_string(url);
-        sender.send(self.data.get(&origin).map_or(0u, |entry| entry.len()) as u32);
+    fn select_data(&self, storage_type: StorageType, origin: &String) -> Option<&OriginData> {
+        match storage_type {
+            Session(id) => self.session_data.get(&(id, origin.clone())),
+            Local => self.local_data.get(origin),
+        }
     }
 
-    fn key(&self, sender: Sender<Option<DOMString>>, url: Url, index: u32) {
-        let origin = self.get_origin_as_string(url);
-        sender.send(self.data.get(&origin)
+    fn select_data_mut(&mut self, storage_type: StorageType, origin: &String) -> Option<&mut OriginData> {
+        match storage_type {
+            Session(id) => self.session_data.get_mut(&(id, origin.clone())),
+            Local => self.local_data.get_mut(origin),
+        }
+    }
+
+    fn length(&self, sender: Sender<u32>, url: Url, storage_type: StorageType) {
+        let origin = serialize_origin(&url);
+        sender.send(self.select_data(storage_type, &origin).map_or(0u, |entry| entry.len()) as u32);
+    }
+
+    fn key(&self, sender: Sender<Option<DOMString>>, url: Url, storage_type: StorageType, index: u32) {
+        let origin = serialize_origin(&url);
+        sender.send(self.select_data(storage_type, &origin)
                     .and_then(|entry| entry.keys().nth(index as uint))
                     .map(|key| key.clone()));
     }
 
-    fn set_item(&mut self, sender: Sender<bool>, url: Url, name: DOMString, value: DOMString) {
-        let origin = self.get_origin_as_string(url);
-        if !self.data.contains_key(&origin) {
-            self.data.insert(origin.clone(), TreeMap::new());
+    fn set_item(&mut self, sender: Sender<Result<(bool, Option<DOMString>), ()>>, url: Url,
+                storage_type: StorageType, name: DOMString, value: DOMString) {
+        let origin = serialize_origin(&url);
+        if self.select_data(storage_type, &origin).is_none() {
+            match storage_type {
+                Session(id) => { self.session_data.insert((id, origin.clone()), TreeMap::new()); }
+                Local => { self.local_data.insert(origin.clone(), TreeMap::new()); }
+            }
         }
 
-        let updated = self.data.get_mut(&origin).map(|entry| {
-            if entry.get(&origin).map_or(true, |item| item.as_slice() != value.as_slice()) {
-                entry.insert(name.clone(), value.clone());
-                true
-            } else {
-                false
+        let result = self.select_data_mut(storage_type, &origin).map(|entry| {
+            let old_value = entry.get(&name).map(|value| value.clone());
+            if old_value.as_ref().map_or(false, |old| old.as_slice() == value.as_slice()) {
+                return Ok((false, old_value));
+            }
+
+            let new_size = data_size(entry) + name.len() + value.len() -
+                           old_value.as_ref().map_or(0, |old| name.len() + old.len());
+            if new_size > QUOTA_SIZE_LIMIT {
+                return Err(());
             }
+            entry.insert(name.clone(), value.clone());
+            Ok((true, old_value))
         }).unwrap();
 
-        sender.send(updated);
+        match result {
+            Ok((true, _)) if storage_type == Local => self.save_local_data(),
+            _ => {}
+        }
+        sender.send(result);
     }
 
-    fn get_item(&self, sender: Sender<Option<DOMString>>, url: Url, name: DOMString) {
-        let origin = self.get_origin_as_string(url);
-        sender.send(self.data.get(&origin)
+    fn get_item(&self, sender: Sender<Option<DOMString>>, url: Url, storage_type: StorageType,
+                name: DOMString) {
+        let origin = serialize_origin(&url);
+        sender.send(self.select_data(storage_type, &origin)
                     .and_then(|entry| entry.get(&name))
                     .map(|value| value.to_string()));
     }
 
-    fn remove_item(&mut self, sender: Sender<bool>, url: Url, name: DOMString) {
-        let origin = self.get_origin_as_string(url);
-        sender.send(self.data.get_mut(&origin)
-                    .map_or(false, |entry| entry.remove(&name).is_some()));
-    }
-
-    fn clear(&mut self, sender: Sender<bool>, url: Url) {
-        let origin = self.get_origin_as_string(url);
-        sender.send(self.data.get_mut(&origin)
-                    .map_or(false, |entry| {
-                        if !entry.is_empty() {
-                            entry.clear();
-                            true
-                        } else {
-                            false
-                        }}));
-    }
-
-    fn get_origin_as_string(&self, url: Url) -> String {
-        let mut origin = "".to_string();
-        origin.push_str(url.scheme.as_slice());
-        origin.push_str("://");
-        url.domain().map(|domain| origin.push_str(domain.as_slice()));
-        url.port().map(|port| {
-            origin.push_str(":");
-            origin.push_str(port.to_string().as_slice());
-        });
-        origin.push_str("/");
-        origin
+    fn remove_item(&mut self, sender: Sender<Option<DOMString>>, url: Url, storage_type: StorageType,
+                   name: DOMString) {
+        let origin = serialize_origin(&url);
+        let old_value = self.select_data_mut(storage_type, &origin)
+                            .and_then(|entry| entry.remove(&name));
+        if old_value.is_some() && storage_type == Local {
+            self.save_local_data();
+        }
+        sender.send(old_value);
+    }
+
+    fn clear(&mut self, sender: Sender<bool>, url: Url, storage_type: StorageType) {
+        let origin = serialize_origin(&url);
+        let cleared = self.select_data_mut(storage_type, &origin)
+                          .map_or(false, |entry| {
+                              if !entry.is_empty() {
+                                  entry.clear();
+                                  true
+                              } else {
+                                  false
+                              }});
+        if cleared && storage_type == Local {
+            self.save_local_data();
+        }
+        sender.send(cleared);
+    }
+
+    /// Write local storage out to the profile directory, if there is one.
+    fn save_local_data(&self) {
+        let dir = match self.profile_dir {
+            Some(ref dir) => dir,
+            None => return,
+        };
+        // Write to a temporary file first so that a crash can't leave a truncated file behind.
+        let temp_path = dir.join(format!("{}.tmp", LOCAL_STORAGE_FILE));
+        let result = File::create(&temp_path)
+            .write_str(json::encode(&self.local_data).as_slice())
+            .and_then(|_| fs::rename(&temp_path, &dir.join(LOCAL_STORAGE_FILE)));
+        match result {
+            Ok(()) => {}
+            Err(e) => error!("storage_task: failed to save local storage: {}", e),
+        }
+    }
+}
+
+/// The number of bytes of keys and values stored for an origin.
+fn data_size(data: &OriginData) -> uint {
+    data.iter().fold(0, |size, (key, value)| size + key.len() + value.len())
+}
+
+/// Read the local storage persisted in `dir`, starting afresh if there is none or it is unreadable.
+fn load_local_data(dir: &Path) -> TreeMap<String, OriginData> {
+    let path = dir.join(LOCAL_STORAGE_FILE);
+    if !path.exists() {
+        return TreeMap::new();
+    }
+    match File::open(&path).read_to_string() {
+        Ok(contents) => match json::decode(contents.as_slice()) {
+            Ok(data) => data,
+            Err(e) => {
+                error!("storage_task: ignoring corrupt local storage file: {}", e);
+                TreeMap::new()
+            }
+        },
+        Err(e) => {
+            error!("storage_task: failed to read local storage: {}", e);
+            TreeMap::new()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{StorageTask, StorageTaskFactory, StorageType, Session, Local, QUOTA_SIZE_LIMIT};
+    use super::{Length, GetItem, SetItem, RemoveItem, Clear, Exit};
+    use std::comm::channel;
+    use std::io::TempDir;
+    use url::Url;
+
+    fn set(task: &StorageTask, url: &str, storage_type: StorageType, name: &str, value: String)
+           -> Result<(bool, Option<String>), ()> {
+        let (sender, receiver) = channel();
+        task.send(SetItem(sender, Url::parse(url).unwrap(), storage_type, name.to_string(), value));
+        receiver.recv()
+    }
+
+    fn get(task: &StorageTask, url: &str, storage_type: StorageType, name: &str) -> Option<String> {
+        let (sender, receiver) = channel();
+        task.send(GetItem(sender, Url::parse(url).unwrap(), storage_type, name.to_string()));
+        receiver.recv()
+    }
+
+    #[test]
+    fn test_origin_keyed() {
+        let task: StorageTask = StorageTaskFactory::new(None);
+        assert_eq!(set(&task, "http://example.com/a", Local, "key", "1".to_string()), Ok((true, None)));
+        assert_eq!(get(&task, "http://example.com/b/c?d", Local, "key"), Some("1".to_string()));
+        assert_eq!(get(&task, "http://example.com:8000/", Local, "key"), None);
+        assert_eq!(get(&task, "https://example.com/", Local, "key"), None);
+        task.send(Exit);
+    }
+
+    #[test]
+    fn test_session_and_local_are_separate() {
+        let task: StorageTask = StorageTaskFactory::new(None);
+        set(&task, "http://example.com/", Session(1), "key", "session".to_string()).unwrap();
+        assert_eq!(get(&task, "http://example.com/", Local, "key"), None);
+        assert_eq!(set(&task, "http://example.com/", Session(1), "key", "session".to_string()),
+                   Ok((false, Some("session".to_string()))));
+
+        let (sender, receiver) = channel();
+        task.send(Length(sender, Url::parse("http://example.com/").unwrap(), Session(1)));
+        assert_eq!(receiver.recv(), 1);
+
+        let (sender, receiver) = channel();
+        task.send(RemoveItem(sender, Url::parse("http://example.com/").unwrap(), Session(1),
+                             "key".to_string()));
+        assert_eq!(receiver.recv(), Some("session".to_string()));
+        task.send(Exit);
+    }
+
+    #[test]
+    fn test_session_areas_are_per_top_level_browsing_context() {
+        let task: StorageTask = StorageTaskFactory::new(None);
+        set(&task, "http://example.com/", Session(1), "key", "1".to_string()).unwrap();
+        set(&task, "http://example.com/", Session(2), "key", "2".to_string()).unwrap();
+        assert_eq!(get(&task, "http://example.com/", Session(1), "key"), Some("1".to_string()));
+        assert_eq!(get(&task, "http://example.com/", Session(2), "key"), Some("2".to_string()));
+        assert_eq!(get(&task, "http://example.com/", Session(3), "key"), None);
+        task.send(Exit);
+    }
+
+    #[test]
+    fn test_quota() {
+        let task: StorageTask = StorageTaskFactory::new(None);
+        let big = String::from_char(QUOTA_SIZE_LIMIT / 2, 'a');
+        assert!(set(&task, "http://example.com/", Local, "a", big.clone()).is_ok());
+        assert!(set(&task, "http://example.com/", Local, "b", big.clone()).is_err());
+        // Other origins and the other storage area have their own quota.
+        assert!(set(&task, "http://example.org/", Local, "b", big.clone()).is_ok());
+        assert!(set(&task, "http://example.com/", Session(1), "b", big.clone()).is_ok());
+        // Replacing a value only counts the difference.
+        assert!(set(&task, "http://example.com/", Local, "a", big.clone() + "b").is_ok());
+
+        let (sender, receiver) = channel();
+        task.send(Clear(sender, Url::parse("http://example.com/").unwrap(), Local));
+        assert!(receiver.recv());
+        assert!(set(&task, "http://example.com/", Local, "b", big).is_ok());
+        task.send(Exit);
+    }
+
+    #[test]
+    fn test_local_storage_persists() {
+        let dir = TempDir::new("storage_task").unwrap();
+
+        let task: StorageTask = StorageTaskFactory::new(Some(dir.path().clone()));
+        set(&task, "http://example.com/", Local, "key", "value".to_string()).unwrap();
+        set(&task, "http://example.com/", Session(1), "key", "session".to_string()).unwrap();
+        task.send(Exit);
+
+        let task: StorageTask = StorageTaskFactory::new(Some(dir.path().clone()));
+        assert_eq!(get(&task, "http://example.com/", Local, "key"), Some("value".to_string()));
+        assert_eq!(get(&task, "http://example.com/", Session(1), "key"), None);
+        task.send(Exit);
     }
 }
@@ -0,0 +1,84 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use dom::bindings::codegen::Bindings::EventBinding::EventMethods;
+use dom::bindings::codegen::Bindings::AnimationEventBinding;
+use dom::bindings::codegen::Bindings::AnimationEventBinding::AnimationEventMethods;
+use dom::bindings::codegen::InheritTypes::{EventCast, AnimationEventDerived};
+use dom::bindings::error::Fallible;
+use dom::bindings::global::GlobalRef;
+use dom::bindings::js::{JSRef, Temporary};
+use dom::bindings::utils::{Reflectable, Reflector, reflect_dom_object};
+use dom::event::{Event, AnimationEventTypeId};
+use servo_util::str::DOMString;
+
+#[dom_struct]
+pub struct AnimationEvent {
+    event: Event,
+    animation_name: DOMString,
+    elapsed_time: f32,
+    pseudo_element: DOMString,
+}
+
+impl AnimationEventDerived for Event {
+    fn is_animationevent(&self) -> bool {
+        *self.type_id() == AnimationEventTypeId
+    }
+}
+
+impl AnimationEvent {
+    fn new_inherited(animation_name: DOMString, elapsed_time: f32, pseudo_element: DOMString)
+                     -> AnimationEvent {
+        AnimationEvent {
+            event: Event::new_inherited(AnimationEventTypeId),
+            animation_name: animation_name,
+            elapsed_time: elapsed_time,
+            pseudo_element: pseudo_element,
+        }
+    }
+
+    pub fn new(global: GlobalRef, type_: DOMString,
+               can_bubble: bool, cancelable: bool,
+               animation_name: DOMString, elapsed_time: f32,
+               pseudo_element: DOMString) -> Temporary<AnimationEvent> {
+        let ev = reflect_dom_object(box AnimationEvent::new_inherited(animation_name,
+                                                                      elapsed_time,
+                                                                      pseudo_element),
+                                    global,
+                                    AnimationEventBinding::Wrap).root();
+        let event: JSRef<Event> = EventCast::from_ref(*ev);
+        event.InitEvent(type_, can_bubble, cancelable);
+        Temporary::from_rooted(*ev)
+    }
+
+    pub fn Constructor(global: &GlobalRef,
+                       type_: DOMString,
+                       init: &AnimationEventBinding::AnimationEventInit)
+                       -> Fallible<Temporary<AnimationEvent>> {
+        let ev = AnimationEvent::new(*global, type_, init.parent.bubbles,
+                                     init.parent.cancelable, init.animationName.clone(),
+                                     init.elapsedTime, init.pseudoElement.clone());
+        Ok(ev)
+    }
+}
+
+impl<'a> AnimationEventMethods for JSRef<'a, AnimationEvent> {
+    fn AnimationName(self) -> DOMString {
+        self.animation_name.clone()
+    }
+
+    fn ElapsedTime(self) -> f32 {
+        self.elapsed_time
+    }
+
+    fn PseudoElement(self) -> DOMString {
+        self.pseudo_element.clone()
+    }
+}
+
+impl Reflectable for AnimationEvent {
+    fn reflector<'a>(&'a self) -> &'a Reflector {
+        self.event.reflector()
+    }
+}
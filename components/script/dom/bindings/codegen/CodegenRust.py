@@ -1174,11 +1174,25 @@ class MethodDefiner(PropertyDefiner):
         methods = [m for m in descriptor.interface.members if
                    m.isMethod() and m.isStatic() == static and
                    not m.isIdentifierLess()]
-        self.regular = [{"name": m.identifier.name,
-                         "methodInfo": not m.isStatic(),
-                         "length": methodLength(m),
-                         "flags": "JSPROP_ENUMERATE" }
-                        for m in methods]
+
+        def spec(m):
+            return {"name": m.identifier.name,
+                    "methodInfo": not m.isStatic(),
+                    "length": methodLength(m),
+                    "flags": "JSPROP_ENUMERATE" }
+
+        def pref(m):
+            pref = m.getExtendedAttribute("Pref")
+            return pref[0] if pref else None
+
+        self.regular = [spec(m) for m in methods if pref(m) is None]
+
+        # Methods with [Pref=option] are only defined when the boolean field `option` of
+        # `servo_util::opts::Opts` is set, so that testing APIs stay hidden from web content.
+        self.prefable = {}
+        for m in methods:
+            if pref(m) is not None:
+                self.prefable.setdefault(pref(m), []).append(spec(m))
 
         # FIXME Check for an existing iterator on the interface first.
         if any(m.isGetter() and m.isIndexed() for m in methods):
@@ -1213,6 +1227,15 @@ class MethodDefiner(PropertyDefiner):
             'JSFunctionSpec',
             specData)
 
+    def prefVariableName(self, pref):
+        return "%s_%s" % (self.variableName(), pref)
+
+    def __str__(self):
+        define = PropertyDefiner.__str__(self)
+        for (pref, array) in sorted(self.prefable.items()):
+            define += self.generateArray(array, self.prefVariableName(pref))
+        return define
+
 class AttrDefiner(PropertyDefiner):
     def __init__(self, descriptor, name, static):
         PropertyDefiner.__init__(self, descriptor, name)
@@ -2005,15 +2028,21 @@ class CGCreateInterfaceObjectsMethod(CGAbstractMethod):
         else:
             constructor = 'None'
 
-        call = """return CreateInterfaceObjects2(aCx, aGlobal, aReceiver, parentProto,
-                               &PrototypeClass, %s,
-                               %s,
-                               &sNativeProperties);""" % (constructor, domClass)
+        call = """let proto = CreateInterfaceObjects2(aCx, aGlobal, aReceiver, parentProto,
+                                     &PrototypeClass, %s,
+                                     %s,
+                                     &sNativeProperties);""" % (constructor, domClass)
+
+        methods = self.properties.methods
+        prefs = [CGGeneric("if opts::get().%s {\n"
+                           "  DefineMethods(aCx, proto, %s);\n"
+                           "}" % (pref, methods.prefVariableName(pref)))
+                 for pref in sorted(methods.prefable.keys())]
 
         return CGList([
             CGGeneric(getParentProto),
             CGGeneric(call % self.properties.variableNames())
-        ], "\n")
+        ] + prefs + [CGGeneric("return proto;")], "\n")
 
 class CGGetPerInterfaceObject(CGAbstractMethod):
     """
@@ -4499,7 +4528,7 @@ class CGBindingRoot(CGThing):
             'dom::bindings::js::{JS, JSRef, Root, RootedReference, Temporary}',
             'dom::bindings::js::{OptionalRootable, OptionalRootedRootable, ResultRootable}',
             'dom::bindings::js::{OptionalRootedReference, OptionalOptionalRootedRootable}',
-            'dom::bindings::utils::{CreateDOMGlobal, CreateInterfaceObjects2}',
+            'dom::bindings::utils::{CreateDOMGlobal, CreateInterfaceObjects2, DefineMethods}',
             'dom::bindings::utils::ConstantSpec',
             'dom::bindings::utils::{dom_object_slot, DOM_OBJECT_SLOT, DOMClass}',
             'dom::bindings::utils::{DOMJSClass, JSCLASS_DOM_GLOBAL}',
@@ -4531,6 +4560,7 @@ class CGBindingRoot(CGThing):
             'dom::bindings::str::ByteString',
             'page::JSPageInfo',
             'libc',
+            'servo_util::opts',
             'servo_util::str::DOMString',
             'std::mem',
             'std::cmp',
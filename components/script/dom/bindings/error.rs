@@ -35,7 +35,8 @@ pub enum Error {
     Network,
     Abort,
     Timeout,
-    DataClone
+    DataClone,
+    QuotaExceeded
 }
 
 /// The return type for IDL operations that can throw DOM exceptions.
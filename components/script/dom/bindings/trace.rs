@@ -31,7 +31,12 @@ use dom::bindings::js::JS;
 use dom::bindings::utils::{Reflectable, Reflector, WindowProxyHandler};
 use dom::node::{Node, TrustedNodeAddress};
 
+use canvas::canvas_chan::CanvasChan;
+use canvas::canvas_render_task::{CanvasGradientStop, LinearGradientStyle, RadialGradientStyle};
+use canvas::canvas_render_task::SurfaceStyle;
 use collections::hash::{Hash, Hasher};
+use cssparser::RGBA;
+use geom::matrix2d::Matrix2D;
 use geom::rect::Rect;
 use html5ever::tree_builder::QuirksMode;
 use http::headers::request::HeaderCollection as RequestHeaderCollection;
@@ -44,6 +49,7 @@ use layout_interface::{LayoutRPC, LayoutChan};
 use libc;
 use msg::constellation_msg::{PipelineId, SubpageId, WindowSizeData};
 use net::image_cache_task::ImageCacheTask;
+use net::storage_task::StorageType;
 use script_traits::ScriptControlChan;
 use script_traits::UntrustedNodeAddress;
 use servo_msg::compositor_msg::ScriptListener;
@@ -56,7 +62,9 @@ use std::comm::{Receiver, Sender};
 use std::io::timer::Timer;
 use std::rc::Rc;
 use string_cache::{Atom, Namespace};
-use style::PropertyDeclarationBlock;
+use style::{MediaQueryList, PropertyDeclarationBlock};
+use style::CSSRule as StyleCSSRule;
+use sync::Arc;
 use url::Url;
 
 
@@ -201,9 +209,15 @@ no_jsmanaged_fields!(int, i8, i16, i32, i64)
 no_jsmanaged_fields!(Sender<T>)
 no_jsmanaged_fields!(Receiver<T>)
 no_jsmanaged_fields!(Rect<T>)
+no_jsmanaged_fields!(Matrix2D<T>)
+no_jsmanaged_fields!(RGBA)
+no_jsmanaged_fields!(CanvasGradientStop, LinearGradientStyle, RadialGradientStyle, SurfaceStyle)
+no_jsmanaged_fields!(CanvasChan)
 no_jsmanaged_fields!(ImageCacheTask, ScriptControlChan)
 no_jsmanaged_fields!(Atom, Namespace, Timer)
-no_jsmanaged_fields!(PropertyDeclarationBlock)
+no_jsmanaged_fields!(PropertyDeclarationBlock, MediaQueryList, StyleCSSRule)
+// Arc requires its contents to be sendable, which JS-managed values are not.
+no_jsmanaged_fields!(Arc<T>)
 // These three are interdependent, if you plan to put jsmanaged data
 // in one of these make sure it is propagated properly to containing structs
 no_jsmanaged_fields!(SubpageId, WindowSizeData, PipelineId)
@@ -215,6 +229,7 @@ no_jsmanaged_fields!(LayoutChan)
 no_jsmanaged_fields!(WindowProxyHandler)
 no_jsmanaged_fields!(UntrustedNodeAddress)
 no_jsmanaged_fields!(LengthOrPercentageOrAuto)
+no_jsmanaged_fields!(StorageType)
 
 impl<'a> JSTraceable for &'a str {
     #[inline]
@@ -0,0 +1,100 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use dom::bindings::cell::DOMRefCell;
+use dom::bindings::codegen::Bindings::CanvasGradientBinding;
+use dom::bindings::codegen::Bindings::CanvasGradientBinding::CanvasGradientMethods;
+use dom::bindings::error::{Fallible, IndexSize, Syntax};
+use dom::bindings::global::GlobalRef;
+use dom::bindings::js::{JSRef, Temporary};
+use dom::bindings::utils::{Reflectable, Reflector, reflect_dom_object};
+use dom::canvasrenderingcontext2d::parse_color;
+
+use canvas::canvas_render_task::{CanvasGradientStop, FillOrStrokeStyle, LinearGradientStyle};
+use canvas::canvas_render_task::{LinearGradientStyleValue, RadialGradientStyle};
+use canvas::canvas_render_task::RadialGradientStyleValue;
+use servo_util::str::DOMString;
+
+#[dom_struct]
+pub struct CanvasGradient {
+    reflector_: Reflector,
+    style: CanvasGradientStyle,
+    stops: DOMRefCell<Vec<CanvasGradientStop>>,
+}
+
+#[jstraceable]
+#[deriving(Clone)]
+pub enum CanvasGradientStyle {
+    Linear(LinearGradientStyle),
+    Radial(RadialGradientStyle),
+}
+
+impl CanvasGradient {
+    fn new_inherited(style: CanvasGradientStyle) -> CanvasGradient {
+        CanvasGradient {
+            reflector_: Reflector::new(),
+            style: style,
+            stops: DOMRefCell::new(vec!()),
+        }
+    }
+
+    pub fn new(global: GlobalRef, style: CanvasGradientStyle) -> Temporary<CanvasGradient> {
+        reflect_dom_object(box CanvasGradient::new_inherited(style),
+                           global,
+                           CanvasGradientBinding::Wrap)
+    }
+}
+
+impl<'a> CanvasGradientMethods for JSRef<'a, CanvasGradient> {
+    fn AddColorStop(self, offset: f64, color: DOMString) -> Fallible<()> {
+        if !(offset >= 0.0 && offset <= 1.0) {
+            return Err(IndexSize)
+        }
+
+        let color = match parse_color(color.as_slice()) {
+            Ok(color) => color,
+            Err(()) => return Err(Syntax),
+        };
+
+        // Stops are kept sorted by offset; stops with equal offsets stay in the order they were
+        // added in.
+        let mut stops = self.stops.borrow_mut();
+        let index = stops.iter().position(|stop| stop.offset > offset).unwrap_or(stops.len());
+        stops.insert(index, CanvasGradientStop {
+            offset: offset,
+            color: color,
+        });
+        Ok(())
+    }
+}
+
+pub trait ToFillOrStrokeStyle {
+    fn to_fill_or_stroke_style(self) -> FillOrStrokeStyle;
+}
+
+impl<'a> ToFillOrStrokeStyle for JSRef<'a, CanvasGradient> {
+    fn to_fill_or_stroke_style(self) -> FillOrStrokeStyle {
+        let stops = self.stops.borrow().clone();
+        match self.style {
+            Linear(ref gradient) => {
+                LinearGradientStyleValue(LinearGradientStyle {
+                    stops: stops,
+                    ..gradient.clone()
+                })
+            }
+            Radial(ref gradient) => {
+                RadialGradientStyleValue(RadialGradientStyle {
+                    stops: stops,
+                    ..gradient.clone()
+                })
+            }
+        }
+    }
+}
+
+impl Reflectable for CanvasGradient {
+    fn reflector<'a>(&'a self) -> &'a Reflector {
+        &self.reflector_
+    }
+}
@@ -0,0 +1,62 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use dom::bindings::codegen::Bindings::CanvasPatternBinding;
+use dom::bindings::global::GlobalRef;
+use dom::bindings::js::{JSRef, Temporary};
+use dom::bindings::utils::{Reflectable, Reflector, reflect_dom_object};
+use dom::canvasgradient::ToFillOrStrokeStyle;
+
+use canvas::canvas_render_task::{FillOrStrokeStyle, SurfaceStyle, SurfaceStyleValue};
+use geom::size::Size2D;
+
+#[dom_struct]
+pub struct CanvasPattern {
+    reflector_: Reflector,
+    style: SurfaceStyle,
+}
+
+impl CanvasPattern {
+    fn new_inherited(surface_data: Vec<u8>,
+                     surface_size: Size2D<i32>,
+                     repeat_x: bool,
+                     repeat_y: bool)
+                     -> CanvasPattern {
+        CanvasPattern {
+            reflector_: Reflector::new(),
+            style: SurfaceStyle {
+                surface_data: surface_data,
+                surface_size: surface_size,
+                repeat_x: repeat_x,
+                repeat_y: repeat_y,
+            },
+        }
+    }
+
+    pub fn new(global: GlobalRef,
+               surface_data: Vec<u8>,
+               surface_size: Size2D<i32>,
+               repeat_x: bool,
+               repeat_y: bool)
+               -> Temporary<CanvasPattern> {
+        reflect_dom_object(box CanvasPattern::new_inherited(surface_data,
+                                                            surface_size,
+                                                            repeat_x,
+                                                            repeat_y),
+                           global,
+                           CanvasPatternBinding::Wrap)
+    }
+}
+
+impl<'a> ToFillOrStrokeStyle for JSRef<'a, CanvasPattern> {
+    fn to_fill_or_stroke_style(self) -> FillOrStrokeStyle {
+        SurfaceStyleValue(self.style.clone())
+    }
+}
+
+impl Reflectable for CanvasPattern {
+    fn reflector<'a>(&'a self) -> &'a Reflector {
+        &self.reflector_
+    }
+}
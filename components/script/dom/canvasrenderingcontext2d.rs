@@ -2,25 +2,91 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
+use dom::bindings::cell::DOMRefCell;
 use dom::bindings::codegen::Bindings::CanvasRenderingContext2DBinding;
 use dom::bindings::codegen::Bindings::CanvasRenderingContext2DBinding::CanvasRenderingContext2DMethods;
+use dom::bindings::codegen::InheritTypes::NodeCast;
+use dom::bindings::codegen::UnionTypes::HTMLImageElementOrHTMLCanvasElement::{HTMLImageElementOrHTMLCanvasElement, eHTMLCanvasElement, eHTMLImageElement};
+use dom::bindings::codegen::UnionTypes::StringOrCanvasGradientOrCanvasPattern::{StringOrCanvasGradientOrCanvasPattern, eCanvasGradient, eCanvasPattern, eString};
+use dom::bindings::error::{Fallible, IndexSize, InvalidState, NotSupported, Syntax};
 use dom::bindings::global::{GlobalRef, GlobalField};
 use dom::bindings::js::{JS, JSRef, Temporary};
 use dom::bindings::utils::{Reflector, Reflectable, reflect_dom_object};
-use dom::htmlcanvaselement::HTMLCanvasElement;
+use dom::canvasgradient::{CanvasGradient, Linear, Radial, ToFillOrStrokeStyle};
+use dom::canvaspattern::CanvasPattern;
+use dom::htmlcanvaselement::{HTMLCanvasElement, HTMLCanvasElementHelpers};
+use dom::htmlimageelement::HTMLImageElementHelpers;
+use dom::imagedata::{ImageData, ImageDataHelpers};
+use dom::document::DocumentHelpers;
+use dom::node::{Node, NodeHelpers, document_from_node, window_from_node};
 
+use cssparser::{Color, CurrentColor, RGBA, RGBAColor, tokenize};
+use cssparser::ast::{ComponentValue, WhiteSpace};
+use geom::matrix2d::Matrix2D;
 use geom::point::Point2D;
 use geom::rect::Rect;
 use geom::size::Size2D;
+use servo_net::image::base::image_to_bgra;
+use servo_net::image_cache_task::{Decode, GetImage, ImageReady};
+use servo_util::str::DOMString;
+use std::comm::channel;
+use std::i32;
+use std::num::{Float, FloatMath};
 
-use canvas::canvas_render_task::{CanvasMsg, CanvasRenderTask, ClearRect, Close, FillRect, Recreate, StrokeRect};
+use canvas::canvas_chan::CanvasChan;
+use canvas::canvas_render_task::{Arc, BeginPath, BezierCurveTo, CanvasRenderTask};
+use canvas::canvas_render_task::{ClearRect, Clip, Close, ClosePath, ColorStyle, DrawImage, Fill};
+use canvas::canvas_render_task::FillOrStrokeStyle;
+use canvas::canvas_render_task::{FillRect, GetImageData, LinearGradientStyle, LineTo, MoveTo};
+use canvas::canvas_render_task::{PutImageData, QuadraticCurveTo, RadialGradientStyle, Recreate};
+use canvas::canvas_render_task::{RectPath, RestoreContext, SaveContext, SetFillStyle};
+use canvas::canvas_render_task::{SetGlobalAlpha, SetLineWidth, SetStrokeStyle, SetTransform};
+use canvas::canvas_render_task::{Stroke, StrokeRect};
+
+/// The value of the `fillStyle` or `strokeStyle` attribute.
+#[deriving(Clone)]
+#[jstraceable]
+#[must_root]
+pub enum CanvasFillOrStrokeStyle {
+    ColorValue(RGBA),
+    GradientValue(JS<CanvasGradient>),
+    PatternValue(JS<CanvasPattern>),
+}
+
+/// The drawing state that `save()` pushes onto the stack. The canvas task keeps its own copy of
+/// it; this one is what the attribute getters return.
+#[deriving(Clone)]
+#[jstraceable]
+#[must_root]
+pub struct CanvasContextState {
+    global_alpha: f64,
+    line_width: f64,
+    fill_style: CanvasFillOrStrokeStyle,
+    stroke_style: CanvasFillOrStrokeStyle,
+    transform: Matrix2D<f32>,
+}
+
+impl CanvasContextState {
+    fn new() -> CanvasContextState {
+        let black = RGBA { red: 0., green: 0., blue: 0., alpha: 1. };
+        CanvasContextState {
+            global_alpha: 1.0,
+            line_width: 1.0,
+            fill_style: ColorValue(black),
+            stroke_style: ColorValue(black),
+            transform: Matrix2D::identity(),
+        }
+    }
+}
 
 #[dom_struct]
 pub struct CanvasRenderingContext2D {
     reflector_: Reflector,
     global: GlobalField,
-    renderer: Sender<CanvasMsg>,
+    renderer: CanvasChan,
     canvas: JS<HTMLCanvasElement>,
+    state: DOMRefCell<CanvasContextState>,
+    saved_states: DOMRefCell<Vec<CanvasContextState>>,
 }
 
 impl CanvasRenderingContext2D {
@@ -30,6 +96,8 @@ impl CanvasRenderingContext2D {
             global: GlobalField::from_rooted(global),
             renderer: CanvasRenderTask::start(size),
             canvas: JS::from_rooted(canvas),
+            state: DOMRefCell::new(CanvasContextState::new()),
+            saved_states: DOMRefCell::new(vec!()),
         }
     }
 
@@ -39,8 +107,133 @@ impl CanvasRenderingContext2D {
     }
 
     pub fn recreate(&self, size: Size2D<i32>) {
+        *self.state.borrow_mut() = CanvasContextState::new();
+        self.saved_states.borrow_mut().clear();
         self.renderer.send(Recreate(size));
     }
+
+    pub fn get_renderer(&self) -> CanvasChan {
+        self.renderer.clone()
+    }
+}
+
+pub trait LayoutCanvasRenderingContext2DHelpers {
+    unsafe fn get_renderer(&self) -> CanvasChan;
+}
+
+impl LayoutCanvasRenderingContext2DHelpers for JS<CanvasRenderingContext2D> {
+    unsafe fn get_renderer(&self) -> CanvasChan {
+        (*self.unsafe_get()).renderer.clone()
+    }
+}
+
+trait PrivateCanvasRenderingContext2DHelpers {
+    fn mark_as_dirty(self);
+    fn set_transform(self, transform: Matrix2D<f32>);
+    fn fetch_image_data(self, image: &HTMLImageElementOrHTMLCanvasElement)
+                        -> Fallible<Option<(Vec<u8>, Size2D<i32>)>>;
+    fn draw_image_data(self,
+                       image_data: Vec<u8>,
+                       image_size: Size2D<i32>,
+                       source_rect: Rect<f64>,
+                       dest_rect: Rect<f64>)
+                       -> Fallible<()>;
+}
+
+impl<'a> PrivateCanvasRenderingContext2DHelpers for JSRef<'a, CanvasRenderingContext2D> {
+    /// Schedules a repaint of the canvas element after its pixels have changed.
+    fn mark_as_dirty(self) {
+        let canvas = self.canvas.root();
+        let node: JSRef<Node> = NodeCast::from_ref(*canvas);
+        if node.is_in_doc() {
+            let document = document_from_node(*canvas).root();
+            document.content_changed(node);
+        }
+    }
+
+    fn set_transform(self, transform: Matrix2D<f32>) {
+        self.state.borrow_mut().transform = transform;
+        self.renderer.send(SetTransform(transform));
+    }
+
+    /// Returns the premultiplied BGRA pixels and the size of an image source, or `None` if the
+    /// image is not available yet, in which case nothing is drawn.
+    fn fetch_image_data(self, image: &HTMLImageElementOrHTMLCanvasElement)
+                        -> Fallible<Option<(Vec<u8>, Size2D<i32>)>> {
+        match *image {
+            eHTMLCanvasElement(ref canvas) => {
+                let canvas = canvas.root();
+                let size = canvas.get_size();
+                if size.width == 0 || size.height == 0 {
+                    return Err(InvalidState)
+                }
+                Ok(Some((canvas.fetch_all_data(), size)))
+            }
+            eHTMLImageElement(ref image) => {
+                let image = image.root();
+                let url = match image.get_url() {
+                    None => return Ok(None),
+                    Some(url) => url,
+                };
+
+                let window = window_from_node(*image).root();
+                let image_cache = window.image_cache_task();
+                image_cache.send(Decode(url.clone()));
+                let (response_chan, response_port) = channel();
+                image_cache.send(GetImage(url, response_chan));
+                match response_port.recv() {
+                    ImageReady(image) => {
+                        let size = Size2D(image.width as i32, image.height as i32);
+                        Ok(image_to_bgra(&**image).map(|data| (data, size)))
+                    }
+                    _ => Ok(None),
+                }
+            }
+        }
+    }
+
+    fn draw_image_data(self,
+                       image_data: Vec<u8>,
+                       image_size: Size2D<i32>,
+                       source_rect: Rect<f64>,
+                       dest_rect: Rect<f64>)
+                       -> Fallible<()> {
+        if !all_finite(&[source_rect.origin.x, source_rect.origin.y,
+                         source_rect.size.width, source_rect.size.height,
+                         dest_rect.origin.x, dest_rect.origin.y,
+                         dest_rect.size.width, dest_rect.size.height]) {
+            return Ok(())
+        }
+        if source_rect.size.width == 0.0 || source_rect.size.height == 0.0 {
+            return Err(IndexSize)
+        }
+
+        // Clip the source rectangle to the image, and the destination rectangle along with it.
+        let source_rect = normalize_rect(source_rect);
+        let dest_rect = normalize_rect(dest_rect);
+        let image_rect = Rect(Point2D(0.0, 0.0),
+                              Size2D(image_size.width as f64, image_size.height as f64));
+        let clipped_source_rect = match source_rect.intersection(&image_rect) {
+            None => return Ok(()),
+            Some(rect) => rect,
+        };
+        let (scale_x, scale_y) = (dest_rect.size.width / source_rect.size.width,
+                                  dest_rect.size.height / source_rect.size.height);
+        let clipped_dest_rect = Rect(
+            Point2D(dest_rect.origin.x + (clipped_source_rect.origin.x - source_rect.origin.x) *
+                        scale_x,
+                    dest_rect.origin.y + (clipped_source_rect.origin.y - source_rect.origin.y) *
+                        scale_y),
+            Size2D(clipped_source_rect.size.width * scale_x,
+                   clipped_source_rect.size.height * scale_y));
+
+        self.renderer.send(DrawImage(image_data,
+                                     image_size,
+                                     to_f32_rect(&clipped_dest_rect),
+                                     to_f32_rect(&clipped_source_rect)));
+        self.mark_as_dirty();
+        Ok(())
+    }
 }
 
 impl<'a> CanvasRenderingContext2DMethods for JSRef<'a, CanvasRenderingContext2D> {
@@ -48,19 +241,399 @@ impl<'a> CanvasRenderingContext2DMethods for JSRef<'a, CanvasRenderingContext2D>
         Temporary::new(self.canvas)
     }
 
+    fn Save(self) {
+        self.saved_states.borrow_mut().push(self.state.borrow().clone());
+        self.renderer.send(SaveContext);
+    }
+
+    fn Restore(self) {
+        match self.saved_states.borrow_mut().pop() {
+            None => {}
+            Some(state) => {
+                *self.state.borrow_mut() = state;
+                self.renderer.send(RestoreContext);
+            }
+        }
+    }
+
+    fn Scale(self, x: f64, y: f64) {
+        if !all_finite(&[x, y]) {
+            return
+        }
+        let transform = self.state.borrow().transform;
+        self.set_transform(transform.mul(&Matrix2D::new(x as f32, 0.0, 0.0, y as f32, 0.0, 0.0)))
+    }
+
+    fn Rotate(self, angle: f64) {
+        if !all_finite(&[angle]) {
+            return
+        }
+        let (sin, cos) = ((angle as f32).sin(), (angle as f32).cos());
+        let transform = self.state.borrow().transform;
+        self.set_transform(transform.mul(&Matrix2D::new(cos, sin, -sin, cos, 0.0, 0.0)))
+    }
+
+    fn Translate(self, x: f64, y: f64) {
+        if !all_finite(&[x, y]) {
+            return
+        }
+        let transform = self.state.borrow().transform;
+        self.set_transform(transform.translate(x as f32, y as f32))
+    }
+
+    fn Transform(self, a: f64, b: f64, c: f64, d: f64, e: f64, f: f64) {
+        if !all_finite(&[a, b, c, d, e, f]) {
+            return
+        }
+        let transform = self.state.borrow().transform;
+        self.set_transform(transform.mul(&Matrix2D::new(a as f32, b as f32,
+                                                        c as f32, d as f32,
+                                                        e as f32, f as f32)))
+    }
+
+    fn SetTransform(self, a: f64, b: f64, c: f64, d: f64, e: f64, f: f64) {
+        if !all_finite(&[a, b, c, d, e, f]) {
+            return
+        }
+        self.set_transform(Matrix2D::new(a as f32, b as f32,
+                                         c as f32, d as f32,
+                                         e as f32, f as f32))
+    }
+
+    fn ResetTransform(self) {
+        self.set_transform(Matrix2D::identity())
+    }
+
+    fn GlobalAlpha(self) -> f64 {
+        self.state.borrow().global_alpha
+    }
+
+    fn SetGlobalAlpha(self, alpha: f64) {
+        if !all_finite(&[alpha]) || alpha < 0.0 || alpha > 1.0 {
+            return
+        }
+        self.state.borrow_mut().global_alpha = alpha;
+        self.renderer.send(SetGlobalAlpha(alpha as f32));
+    }
+
+    fn LineWidth(self) -> f64 {
+        self.state.borrow().line_width
+    }
+
+    fn SetLineWidth(self, width: f64) {
+        if !all_finite(&[width]) || width <= 0.0 {
+            return
+        }
+        self.state.borrow_mut().line_width = width;
+        self.renderer.send(SetLineWidth(width as f32));
+    }
+
+    fn StrokeStyle(self) -> StringOrCanvasGradientOrCanvasPattern {
+        to_union(&self.state.borrow().stroke_style)
+    }
+
+    fn SetStrokeStyle(self, value: StringOrCanvasGradientOrCanvasPattern) {
+        match from_union(value) {
+            None => {}
+            Some((style, renderer_style)) => {
+                self.state.borrow_mut().stroke_style = style;
+                self.renderer.send(SetStrokeStyle(renderer_style));
+            }
+        }
+    }
+
+    fn FillStyle(self) -> StringOrCanvasGradientOrCanvasPattern {
+        to_union(&self.state.borrow().fill_style)
+    }
+
+    fn SetFillStyle(self, value: StringOrCanvasGradientOrCanvasPattern) {
+        match from_union(value) {
+            None => {}
+            Some((style, renderer_style)) => {
+                self.state.borrow_mut().fill_style = style;
+                self.renderer.send(SetFillStyle(renderer_style));
+            }
+        }
+    }
+
+    fn CreateLinearGradient(self, x0: f64, y0: f64, x1: f64, y1: f64)
+                            -> Temporary<CanvasGradient> {
+        let global = self.global.root();
+        CanvasGradient::new(global.root_ref(),
+                            Linear(LinearGradientStyle {
+                                x0: x0,
+                                y0: y0,
+                                x1: x1,
+                                y1: y1,
+                                stops: vec!(),
+                            }))
+    }
+
+    fn CreateRadialGradient(self, x0: f64, y0: f64, r0: f64, x1: f64, y1: f64, r1: f64)
+                            -> Fallible<Temporary<CanvasGradient>> {
+        if r0 < 0.0 || r1 < 0.0 {
+            return Err(IndexSize)
+        }
+
+        let global = self.global.root();
+        Ok(CanvasGradient::new(global.root_ref(),
+                               Radial(RadialGradientStyle {
+                                   x0: x0,
+                                   y0: y0,
+                                   r0: r0,
+                                   x1: x1,
+                                   y1: y1,
+                                   r1: r1,
+                                   stops: vec!(),
+                               })))
+    }
+
+    fn CreatePattern(self, image: HTMLImageElementOrHTMLCanvasElement, repetition: DOMString)
+                     -> Fallible<Option<Temporary<CanvasPattern>>> {
+        let (repeat_x, repeat_y) = match repetition.as_slice() {
+            "" | "repeat" => (true, true),
+            "repeat-x" => (true, false),
+            "repeat-y" => (false, true),
+            "no-repeat" => (false, false),
+            _ => return Err(Syntax),
+        };
+
+        let (image_data, image_size) = match try!(self.fetch_image_data(&image)) {
+            None => return Ok(None),
+            Some(image) => image,
+        };
+        let global = self.global.root();
+        Ok(Some(CanvasPattern::new(global.root_ref(),
+                                   image_data,
+                                   image_size,
+                                   repeat_x,
+                                   repeat_y)))
+    }
+
     fn FillRect(self, x: f64, y: f64, width: f64, height: f64) {
         let rect = Rect(Point2D(x as f32, y as f32), Size2D(width as f32, height as f32));
         self.renderer.send(FillRect(rect));
+        self.mark_as_dirty();
     }
 
     fn ClearRect(self, x: f64, y: f64, width: f64, height: f64) {
         let rect = Rect(Point2D(x as f32, y as f32), Size2D(width as f32, height as f32));
         self.renderer.send(ClearRect(rect));
+        self.mark_as_dirty();
     }
 
     fn StrokeRect(self, x: f64, y: f64, width: f64, height: f64) {
         let rect = Rect(Point2D(x as f32, y as f32), Size2D(width as f32, height as f32));
         self.renderer.send(StrokeRect(rect));
+        self.mark_as_dirty();
+    }
+
+    fn BeginPath(self) {
+        self.renderer.send(BeginPath);
+    }
+
+    fn Fill(self) {
+        self.renderer.send(Fill);
+        self.mark_as_dirty();
+    }
+
+    fn Stroke(self) {
+        self.renderer.send(Stroke);
+        self.mark_as_dirty();
+    }
+
+    fn Clip(self) {
+        self.renderer.send(Clip);
+    }
+
+    fn DrawImage(self, image: HTMLImageElementOrHTMLCanvasElement, dx: f64, dy: f64)
+                 -> Fallible<()> {
+        let (image_data, image_size) = match try!(self.fetch_image_data(&image)) {
+            None => return Ok(()),
+            Some(image) => image,
+        };
+        let size = Size2D(image_size.width as f64, image_size.height as f64);
+        self.draw_image_data(image_data,
+                             image_size,
+                             Rect(Point2D(0.0, 0.0), size),
+                             Rect(Point2D(dx, dy), size))
+    }
+
+    fn DrawImage_(self, image: HTMLImageElementOrHTMLCanvasElement,
+                  dx: f64, dy: f64, dw: f64, dh: f64) -> Fallible<()> {
+        let (image_data, image_size) = match try!(self.fetch_image_data(&image)) {
+            None => return Ok(()),
+            Some(image) => image,
+        };
+        let size = Size2D(image_size.width as f64, image_size.height as f64);
+        self.draw_image_data(image_data,
+                             image_size,
+                             Rect(Point2D(0.0, 0.0), size),
+                             Rect(Point2D(dx, dy), Size2D(dw, dh)))
+    }
+
+    fn DrawImage__(self, image: HTMLImageElementOrHTMLCanvasElement,
+                   sx: f64, sy: f64, sw: f64, sh: f64,
+                   dx: f64, dy: f64, dw: f64, dh: f64) -> Fallible<()> {
+        let (image_data, image_size) = match try!(self.fetch_image_data(&image)) {
+            None => return Ok(()),
+            Some(image) => image,
+        };
+        self.draw_image_data(image_data,
+                             image_size,
+                             Rect(Point2D(sx, sy), Size2D(sw, sh)),
+                             Rect(Point2D(dx, dy), Size2D(dw, dh)))
+    }
+
+    fn CreateImageData(self, sw: f64, sh: f64) -> Fallible<Temporary<ImageData>> {
+        if !all_finite(&[sw, sh]) {
+            return Err(NotSupported)
+        }
+        let (width, height) = (sw.abs().trunc(), sh.abs().trunc());
+        if width == 0.0 || height == 0.0 || !fits_in_i32(&[width * height * 4.0]) {
+            return Err(IndexSize)
+        }
+
+        let global = self.global.root();
+        Ok(ImageData::new(global.root_ref(), width as u32, height as u32, None))
+    }
+
+    fn CreateImageData_(self, imagedata: JSRef<ImageData>) -> Fallible<Temporary<ImageData>> {
+        let size = imagedata.get_size();
+        let global = self.global.root();
+        Ok(ImageData::new(global.root_ref(), size.width as u32, size.height as u32, None))
+    }
+
+    fn GetImageData(self, sx: f64, sy: f64, sw: f64, sh: f64) -> Fallible<Temporary<ImageData>> {
+        if !all_finite(&[sx, sy, sw, sh]) {
+            return Err(NotSupported)
+        }
+        if sw == 0.0 || sh == 0.0 {
+            return Err(IndexSize)
+        }
+
+        let rect = normalize_rect(Rect(Point2D(sx, sy), Size2D(sw, sh)));
+        let (x, y) = (rect.origin.x.floor(), rect.origin.y.floor());
+        let (width, height) = (rect.size.width.ceil(), rect.size.height.ceil());
+        // The renderer addresses the rectangle and its pixels with `i32`s.
+        if !fits_in_i32(&[x, y, x + width, y + height, width * height * 4.0]) {
+            return Err(IndexSize)
+        }
+        let origin = Point2D(x as i32, y as i32);
+        let size = Size2D(width as i32, height as i32);
+        let (sender, receiver) = channel();
+        self.renderer.send(GetImageData(Rect(origin, size), sender));
+        let data = receiver.recv();
+
+        let global = self.global.root();
+        Ok(ImageData::new(global.root_ref(), size.width as u32, size.height as u32, Some(data)))
+    }
+
+    fn PutImageData(self, imagedata: JSRef<ImageData>, dx: f64, dy: f64) {
+        let size = imagedata.get_size();
+        self.PutImageData_(imagedata, dx, dy, 0.0, 0.0, size.width as f64, size.height as f64)
+    }
+
+    fn PutImageData_(self, imagedata: JSRef<ImageData>, dx: f64, dy: f64,
+                     dirtyX: f64, dirtyY: f64, dirtyWidth: f64, dirtyHeight: f64) {
+        if !all_finite(&[dx, dy, dirtyX, dirtyY, dirtyWidth, dirtyHeight]) {
+            return
+        }
+
+        // Only the part of the dirty rectangle that lies within the image data is drawn.
+        let size = imagedata.get_size();
+        let image_rect = Rect(Point2D(0.0, 0.0), Size2D(size.width as f64, size.height as f64));
+        let dirty_rect = normalize_rect(Rect(Point2D(dirtyX, dirtyY),
+                                             Size2D(dirtyWidth, dirtyHeight)));
+        let dirty_rect = match dirty_rect.intersection(&image_rect) {
+            None => return,
+            Some(rect) => rect,
+        };
+        let origin = Point2D(dirty_rect.origin.x.round() as i32,
+                             dirty_rect.origin.y.round() as i32);
+        let dirty_size = Size2D(dirty_rect.max_x().round() as i32 - origin.x,
+                                dirty_rect.max_y().round() as i32 - origin.y);
+        if dirty_size.width <= 0 || dirty_size.height <= 0 {
+            return
+        }
+        let (dest_x, dest_y) = (dx.round() + origin.x as f64, dy.round() + origin.y as f64);
+        if !fits_in_i32(&[dest_x, dest_y,
+                          dest_x + dirty_size.width as f64, dest_y + dirty_size.height as f64]) {
+            return
+        }
+
+        let global = self.global.root();
+        let data = imagedata.get_data_array(&global.root_ref());
+        let mut dirty_data = Vec::with_capacity((dirty_size.width * dirty_size.height * 4) as uint);
+        for y in range(origin.y, origin.y + dirty_size.height) {
+            let row_start = ((y * size.width + origin.x) * 4) as uint;
+            let row_end = row_start + (dirty_size.width * 4) as uint;
+            dirty_data.push_all(data.slice(row_start, row_end));
+        }
+
+        let dest_origin = Point2D(dest_x as i32, dest_y as i32);
+        self.renderer.send(PutImageData(dirty_data, Rect(dest_origin, dirty_size)));
+        self.mark_as_dirty();
+    }
+
+    fn ClosePath(self) {
+        self.renderer.send(ClosePath);
+    }
+
+    fn MoveTo(self, x: f64, y: f64) {
+        if !all_finite(&[x, y]) {
+            return
+        }
+        self.renderer.send(MoveTo(Point2D(x as f32, y as f32)));
+    }
+
+    fn LineTo(self, x: f64, y: f64) {
+        if !all_finite(&[x, y]) {
+            return
+        }
+        self.renderer.send(LineTo(Point2D(x as f32, y as f32)));
+    }
+
+    fn QuadraticCurveTo(self, cpx: f64, cpy: f64, x: f64, y: f64) {
+        if !all_finite(&[cpx, cpy, x, y]) {
+            return
+        }
+        self.renderer.send(QuadraticCurveTo(Point2D(cpx as f32, cpy as f32),
+                                            Point2D(x as f32, y as f32)));
+    }
+
+    fn BezierCurveTo(self, cp1x: f64, cp1y: f64, cp2x: f64, cp2y: f64, x: f64, y: f64) {
+        if !all_finite(&[cp1x, cp1y, cp2x, cp2y, x, y]) {
+            return
+        }
+        self.renderer.send(BezierCurveTo(Point2D(cp1x as f32, cp1y as f32),
+                                         Point2D(cp2x as f32, cp2y as f32),
+                                         Point2D(x as f32, y as f32)));
+    }
+
+    fn Rect(self, x: f64, y: f64, width: f64, height: f64) {
+        if !all_finite(&[x, y, width, height]) {
+            return
+        }
+        let rect = Rect(Point2D(x as f32, y as f32), Size2D(width as f32, height as f32));
+        self.renderer.send(RectPath(rect));
+    }
+
+    fn Arc(self, x: f64, y: f64, radius: f64, start_angle: f64, end_angle: f64,
+           anticlockwise: bool) -> Fallible<()> {
+        if !all_finite(&[x, y, radius, start_angle, end_angle]) {
+            return Ok(())
+        }
+        if radius < 0.0 {
+            return Err(IndexSize)
+        }
+
+        self.renderer.send(Arc(Point2D(x as f32, y as f32),
+                               radius as f32,
+                               start_angle as f32,
+                               end_angle as f32,
+                               anticlockwise));
+        Ok(())
     }
 }
 
@@ -76,3 +649,90 @@ impl Drop for CanvasRenderingContext2D {
         self.renderer.send(Close);
     }
 }
+
+/// Parses a CSS color for `fillStyle`, `strokeStyle` or `addColorStop()`.
+pub fn parse_color(string: &str) -> Result<RGBA, ()> {
+    let component_values: Vec<ComponentValue> =
+        tokenize(string).map(|(value, _)| value).filter(|value| *value != WhiteSpace).collect();
+    if component_values.len() != 1 {
+        return Err(())
+    }
+    match Color::parse(&component_values[0]) {
+        Ok(RGBAColor(rgba)) => Ok(rgba),
+        // FIXME: This should be the computed color of the canvas element.
+        Ok(CurrentColor) => Ok(RGBA { red: 0., green: 0., blue: 0., alpha: 1. }),
+        Err(()) => Err(()),
+    }
+}
+
+/// Serializes a color the way the `fillStyle` and `strokeStyle` getters do.
+fn serialize_color(color: &RGBA) -> DOMString {
+    let (red, green, blue) = ((color.red * 255.0).round() as u8,
+                              (color.green * 255.0).round() as u8,
+                              (color.blue * 255.0).round() as u8);
+    if color.alpha == 1.0 {
+        format!("#{:02x}{:02x}{:02x}", red, green, blue)
+    } else {
+        format!("rgba({}, {}, {}, {})", red, green, blue, color.alpha)
+    }
+}
+
+fn to_union(style: &CanvasFillOrStrokeStyle) -> StringOrCanvasGradientOrCanvasPattern {
+    match *style {
+        ColorValue(ref color) => eString(serialize_color(color)),
+        GradientValue(ref gradient) => eCanvasGradient(gradient.clone()),
+        PatternValue(ref pattern) => eCanvasPattern(pattern.clone()),
+    }
+}
+
+/// Returns the new value of `fillStyle` or `strokeStyle` and what the canvas task should draw
+/// with, or `None` if the value is an invalid color and should be ignored.
+///
+/// FIXME: Color stops added to a gradient after it has been set as a style are not drawn.
+#[allow(unrooted_must_root)]
+fn from_union(value: StringOrCanvasGradientOrCanvasPattern)
+              -> Option<(CanvasFillOrStrokeStyle, FillOrStrokeStyle)> {
+    match value {
+        eString(string) => {
+            parse_color(string.as_slice()).ok().map(|color| {
+                (ColorValue(color), ColorStyle(color))
+            })
+        }
+        eCanvasGradient(gradient) => {
+            let renderer_style = gradient.root().to_fill_or_stroke_style();
+            Some((GradientValue(gradient), renderer_style))
+        }
+        eCanvasPattern(pattern) => {
+            let renderer_style = pattern.root().to_fill_or_stroke_style();
+            Some((PatternValue(pattern), renderer_style))
+        }
+    }
+}
+
+fn all_finite(values: &[f64]) -> bool {
+    values.iter().all(|value| value.is_finite())
+}
+
+fn fits_in_i32(values: &[f64]) -> bool {
+    values.iter().all(|&value| value >= i32::MIN as f64 && value <= i32::MAX as f64)
+}
+
+/// Returns an equivalent rectangle with a non-negative width and height.
+fn normalize_rect(rect: Rect<f64>) -> Rect<f64> {
+    let (x, width) = if rect.size.width < 0.0 {
+        (rect.origin.x + rect.size.width, -rect.size.width)
+    } else {
+        (rect.origin.x, rect.size.width)
+    };
+    let (y, height) = if rect.size.height < 0.0 {
+        (rect.origin.y + rect.size.height, -rect.size.height)
+    } else {
+        (rect.origin.y, rect.size.height)
+    };
+    Rect(Point2D(x, y), Size2D(width, height))
+}
+
+fn to_f32_rect(rect: &Rect<f64>) -> Rect<f32> {
+    Rect(Point2D(rect.origin.x as f32, rect.origin.y as f32),
+         Size2D(rect.size.width as f32, rect.size.height as f32))
+}
@@ -0,0 +1,31 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use dom::bindings::utils::{Reflectable, Reflector};
+use servo_util::str::DOMString;
+use style;
+
+// http://dev.w3.org/csswg/css-conditional/#the-css-interface
+#[dom_struct]
+pub struct CSS {
+    reflector_: Reflector,
+}
+
+impl CSS {
+    // http://dev.w3.org/csswg/css-conditional/#dom-css-supports
+    pub fn Supports(property: DOMString, value: DOMString) -> bool {
+        style::supports_declaration(property.as_slice(), value.as_slice())
+    }
+
+    // http://dev.w3.org/csswg/css-conditional/#dom-css-supports
+    pub fn Supports_(condition_text: DOMString) -> bool {
+        style::supports_condition(condition_text.as_slice())
+    }
+}
+
+impl Reflectable for CSS {
+    fn reflector<'a>(&'a self) -> &'a Reflector {
+        &self.reflector_
+    }
+}
@@ -0,0 +1,49 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use dom::bindings::codegen::Bindings::CSSFontFaceRuleBinding;
+use dom::bindings::codegen::InheritTypes::CSSFontFaceRuleDerived;
+use dom::bindings::global;
+use dom::bindings::js::{JSRef, Temporary};
+use dom::bindings::utils::{Reflectable, Reflector, reflect_dom_object};
+use dom::cssrule::{CSSRule, CSSFontFaceRuleTypeId};
+use dom::cssstylesheet::CSSStyleSheet;
+use dom::window::Window;
+use style;
+
+#[dom_struct]
+pub struct CSSFontFaceRule {
+    cssrule: CSSRule,
+}
+
+impl CSSFontFaceRuleDerived for CSSRule {
+    fn is_cssfontfacerule(&self) -> bool {
+        *self.type_id() == CSSFontFaceRuleTypeId
+    }
+}
+
+impl CSSFontFaceRule {
+    fn new_inherited(rule: style::CSSRule, parent_stylesheet: Option<JSRef<CSSStyleSheet>>,
+                     parent_rule: Option<JSRef<CSSRule>>) -> CSSFontFaceRule {
+        CSSFontFaceRule {
+            cssrule: CSSRule::new_inherited(CSSFontFaceRuleTypeId, rule, parent_stylesheet,
+                                            parent_rule),
+        }
+    }
+
+    pub fn new(window: JSRef<Window>, rule: style::CSSRule,
+               parent_stylesheet: Option<JSRef<CSSStyleSheet>>,
+               parent_rule: Option<JSRef<CSSRule>>) -> Temporary<CSSFontFaceRule> {
+        reflect_dom_object(box CSSFontFaceRule::new_inherited(rule, parent_stylesheet,
+                                                              parent_rule),
+                           global::Window(window),
+                           CSSFontFaceRuleBinding::Wrap)
+    }
+}
+
+impl Reflectable for CSSFontFaceRule {
+    fn reflector<'a>(&'a self) -> &'a Reflector {
+        self.cssrule.reflector()
+    }
+}
@@ -0,0 +1,59 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use dom::bindings::codegen::Bindings::CSSImportRuleBinding;
+use dom::bindings::codegen::Bindings::CSSImportRuleBinding::CSSImportRuleMethods;
+use dom::bindings::codegen::InheritTypes::CSSImportRuleDerived;
+use dom::bindings::global;
+use dom::bindings::js::{JSRef, Temporary};
+use dom::bindings::utils::{Reflectable, Reflector, reflect_dom_object};
+use dom::cssrule::{CSSRule, CSSImportRuleTypeId};
+use dom::cssstylesheet::CSSStyleSheet;
+use dom::window::Window;
+use servo_util::str::DOMString;
+use style;
+
+#[dom_struct]
+pub struct CSSImportRule {
+    cssrule: CSSRule,
+}
+
+impl CSSImportRuleDerived for CSSRule {
+    fn is_cssimportrule(&self) -> bool {
+        *self.type_id() == CSSImportRuleTypeId
+    }
+}
+
+impl CSSImportRule {
+    fn new_inherited(rule: style::CSSRule, parent_stylesheet: Option<JSRef<CSSStyleSheet>>,
+                     parent_rule: Option<JSRef<CSSRule>>) -> CSSImportRule {
+        CSSImportRule {
+            cssrule: CSSRule::new_inherited(CSSImportRuleTypeId, rule, parent_stylesheet,
+                                            parent_rule),
+        }
+    }
+
+    pub fn new(window: JSRef<Window>, rule: style::CSSRule,
+               parent_stylesheet: Option<JSRef<CSSStyleSheet>>,
+               parent_rule: Option<JSRef<CSSRule>>) -> Temporary<CSSImportRule> {
+        reflect_dom_object(box CSSImportRule::new_inherited(rule, parent_stylesheet, parent_rule),
+                           global::Window(window),
+                           CSSImportRuleBinding::Wrap)
+    }
+}
+
+impl<'a> CSSImportRuleMethods for JSRef<'a, CSSImportRule> {
+    fn Href(self) -> DOMString {
+        match *self.cssrule.rule() {
+            style::CSSImportRule(ref rule) => rule.url.serialize(),
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl Reflectable for CSSImportRule {
+    fn reflector<'a>(&'a self) -> &'a Reflector {
+        self.cssrule.reflector()
+    }
+}
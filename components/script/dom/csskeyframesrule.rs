@@ -0,0 +1,60 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use dom::bindings::codegen::Bindings::CSSKeyframesRuleBinding;
+use dom::bindings::codegen::Bindings::CSSKeyframesRuleBinding::CSSKeyframesRuleMethods;
+use dom::bindings::codegen::InheritTypes::CSSKeyframesRuleDerived;
+use dom::bindings::global;
+use dom::bindings::js::{JSRef, Temporary};
+use dom::bindings::utils::{Reflectable, Reflector, reflect_dom_object};
+use dom::cssrule::{CSSRule, CSSKeyframesRuleTypeId};
+use dom::cssstylesheet::CSSStyleSheet;
+use dom::window::Window;
+use servo_util::str::DOMString;
+use style;
+
+#[dom_struct]
+pub struct CSSKeyframesRule {
+    cssrule: CSSRule,
+}
+
+impl CSSKeyframesRuleDerived for CSSRule {
+    fn is_csskeyframesrule(&self) -> bool {
+        *self.type_id() == CSSKeyframesRuleTypeId
+    }
+}
+
+impl CSSKeyframesRule {
+    fn new_inherited(rule: style::CSSRule, parent_stylesheet: Option<JSRef<CSSStyleSheet>>,
+                     parent_rule: Option<JSRef<CSSRule>>) -> CSSKeyframesRule {
+        CSSKeyframesRule {
+            cssrule: CSSRule::new_inherited(CSSKeyframesRuleTypeId, rule, parent_stylesheet,
+                                            parent_rule),
+        }
+    }
+
+    pub fn new(window: JSRef<Window>, rule: style::CSSRule,
+               parent_stylesheet: Option<JSRef<CSSStyleSheet>>,
+               parent_rule: Option<JSRef<CSSRule>>) -> Temporary<CSSKeyframesRule> {
+        reflect_dom_object(box CSSKeyframesRule::new_inherited(rule, parent_stylesheet,
+                                                               parent_rule),
+                           global::Window(window),
+                           CSSKeyframesRuleBinding::Wrap)
+    }
+}
+
+impl<'a> CSSKeyframesRuleMethods for JSRef<'a, CSSKeyframesRule> {
+    fn Name(self) -> DOMString {
+        match *self.cssrule.rule() {
+            style::CSSKeyframesRule(ref rule) => rule.name.clone(),
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl Reflectable for CSSKeyframesRule {
+    fn reflector<'a>(&'a self) -> &'a Reflector {
+        self.cssrule.reflector()
+    }
+}
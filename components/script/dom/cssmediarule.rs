@@ -0,0 +1,88 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use dom::bindings::codegen::Bindings::CSSMediaRuleBinding;
+use dom::bindings::codegen::Bindings::CSSMediaRuleBinding::CSSMediaRuleMethods;
+use dom::bindings::codegen::Bindings::CSSRuleBinding::CSSRuleMethods;
+use dom::bindings::codegen::InheritTypes::{CSSMediaRuleDerived, CSSRuleCast};
+use dom::bindings::global;
+use dom::bindings::js::{JS, JSRef, MutNullableJS, OptionalRootable, OptionalSettable};
+use dom::bindings::js::{RootedReference, Temporary};
+use dom::bindings::utils::{Reflectable, Reflector, reflect_dom_object};
+use dom::cssrule::{CSSRule, CSSMediaRuleTypeId};
+use dom::cssrulelist::CSSRuleList;
+use dom::cssstylesheet::CSSStyleSheet;
+use dom::window::Window;
+use servo_util::str::DOMString;
+use style;
+
+use std::default::Default;
+
+#[dom_struct]
+pub struct CSSMediaRule {
+    cssrule: CSSRule,
+    window: JS<Window>,
+    rule_list: MutNullableJS<CSSRuleList>,
+}
+
+impl CSSMediaRuleDerived for CSSRule {
+    fn is_cssmediarule(&self) -> bool {
+        *self.type_id() == CSSMediaRuleTypeId
+    }
+}
+
+impl CSSMediaRule {
+    fn new_inherited(window: JSRef<Window>, rule: style::CSSRule,
+                     parent_stylesheet: Option<JSRef<CSSStyleSheet>>,
+                     parent_rule: Option<JSRef<CSSRule>>) -> CSSMediaRule {
+        CSSMediaRule {
+            cssrule: CSSRule::new_inherited(CSSMediaRuleTypeId, rule, parent_stylesheet,
+                                            parent_rule),
+            window: JS::from_rooted(window),
+            rule_list: Default::default(),
+        }
+    }
+
+    pub fn new(window: JSRef<Window>, rule: style::CSSRule,
+               parent_stylesheet: Option<JSRef<CSSStyleSheet>>,
+               parent_rule: Option<JSRef<CSSRule>>) -> Temporary<CSSMediaRule> {
+        reflect_dom_object(box CSSMediaRule::new_inherited(window, rule, parent_stylesheet,
+                                                           parent_rule),
+                           global::Window(window),
+                           CSSMediaRuleBinding::Wrap)
+    }
+
+    fn media_rule<'a>(&'a self) -> &'a style::MediaRule {
+        match *self.cssrule.rule() {
+            style::CSSMediaRule(ref rule) => rule,
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl<'a> CSSMediaRuleMethods for JSRef<'a, CSSMediaRule> {
+    fn ConditionText(self) -> DOMString {
+        self.media_rule().media_text.clone()
+    }
+
+    fn CssRules(self) -> Temporary<CSSRuleList> {
+        if self.rule_list.get().is_none() {
+            let window = self.window.root();
+            let cssrule: JSRef<CSSRule> = CSSRuleCast::from_ref(self);
+            let stylesheet = cssrule.GetParentStyleSheet().root();
+            let rule_list = CSSRuleList::new_for_rules(*window,
+                                                       self.media_rule().rules.as_slice(),
+                                                       stylesheet.root_ref(),
+                                                       Some(cssrule));
+            self.rule_list.assign(Some(rule_list));
+        }
+        self.rule_list.get().unwrap()
+    }
+}
+
+impl Reflectable for CSSMediaRule {
+    fn reflector<'a>(&'a self) -> &'a Reflector {
+        self.cssrule.reflector()
+    }
+}
@@ -0,0 +1,150 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use dom::bindings::codegen::Bindings::CSSRuleBinding::CSSRuleConstants;
+use dom::bindings::codegen::Bindings::CSSRuleBinding::CSSRuleMethods;
+use dom::bindings::codegen::InheritTypes::CSSRuleCast;
+use dom::bindings::js::{JS, JSRef, MutNullableJS, Temporary};
+use dom::bindings::utils::{Reflectable, Reflector};
+use dom::cssfontfacerule::CSSFontFaceRule;
+use dom::cssimportrule::CSSImportRule;
+use dom::csskeyframesrule::CSSKeyframesRule;
+use dom::cssmediarule::CSSMediaRule;
+use dom::cssstylerule::CSSStyleRule;
+use dom::cssstylesheet::CSSStyleSheet;
+use dom::csssupportsrule::CSSSupportsRule;
+use dom::window::Window;
+use servo_util::str::DOMString;
+use style;
+
+#[deriving(PartialEq)]
+#[jstraceable]
+pub enum CSSRuleTypeId {
+    CSSImportRuleTypeId,
+    CSSStyleRuleTypeId,
+    CSSMediaRuleTypeId,
+    CSSFontFaceRuleTypeId,
+    CSSSupportsRuleTypeId,
+    CSSKeyframesRuleTypeId,
+}
+
+#[dom_struct]
+pub struct CSSRule {
+    reflector_: Reflector,
+    type_id: CSSRuleTypeId,
+    /// A copy of the rule in the stylesheet. Rules are never changed once they are parsed, only
+    /// inserted and deleted.
+    rule: style::CSSRule,
+    parent_rule: Option<JS<CSSRule>>,
+    parent_stylesheet: MutNullableJS<CSSStyleSheet>,
+}
+
+impl CSSRule {
+    pub fn new_inherited(type_id: CSSRuleTypeId, rule: style::CSSRule,
+                         parent_stylesheet: Option<JSRef<CSSStyleSheet>>,
+                         parent_rule: Option<JSRef<CSSRule>>) -> CSSRule {
+        CSSRule {
+            reflector_: Reflector::new(),
+            type_id: type_id,
+            rule: rule,
+            parent_rule: parent_rule.map(|rule| JS::from_rooted(rule)),
+            parent_stylesheet: MutNullableJS::new(parent_stylesheet),
+        }
+    }
+
+    /// Creates the object for a rule of the given stylesheet, of the interface that matches the
+    /// kind of rule.
+    pub fn new(window: JSRef<Window>, rule: &style::CSSRule,
+               parent_stylesheet: Option<JSRef<CSSStyleSheet>>,
+               parent_rule: Option<JSRef<CSSRule>>) -> Temporary<CSSRule> {
+        match *rule {
+            style::CSSImportRule(_) => {
+                let rule = CSSImportRule::new(window, rule.clone(), parent_stylesheet,
+                                              parent_rule);
+                CSSRuleCast::from_temporary(rule)
+            }
+            style::CSSStyleRule(_) => {
+                let rule = CSSStyleRule::new(window, rule.clone(), parent_stylesheet, parent_rule);
+                CSSRuleCast::from_temporary(rule)
+            }
+            style::CSSMediaRule(_) => {
+                let rule = CSSMediaRule::new(window, rule.clone(), parent_stylesheet, parent_rule);
+                CSSRuleCast::from_temporary(rule)
+            }
+            style::CSSFontFaceRule(_) => {
+                let rule = CSSFontFaceRule::new(window, rule.clone(), parent_stylesheet,
+                                                parent_rule);
+                CSSRuleCast::from_temporary(rule)
+            }
+            style::CSSSupportsRule(_) => {
+                let rule = CSSSupportsRule::new(window, rule.clone(), parent_stylesheet,
+                                                parent_rule);
+                CSSRuleCast::from_temporary(rule)
+            }
+            style::CSSKeyframesRule(_) => {
+                let rule = CSSKeyframesRule::new(window, rule.clone(), parent_stylesheet,
+                                                 parent_rule);
+                CSSRuleCast::from_temporary(rule)
+            }
+        }
+    }
+
+    #[inline]
+    pub fn type_id<'a>(&'a self) -> &'a CSSRuleTypeId {
+        &self.type_id
+    }
+
+    #[inline]
+    pub fn rule<'a>(&'a self) -> &'a style::CSSRule {
+        &self.rule
+    }
+}
+
+pub trait CSSRuleHelpers {
+    fn detach(self);
+}
+
+impl<'a> CSSRuleHelpers for JSRef<'a, CSSRule> {
+    /// Called once the rule has been removed from its stylesheet.
+    fn detach(self) {
+        self.parent_stylesheet.clear();
+    }
+}
+
+impl<'a> CSSRuleMethods for JSRef<'a, CSSRule> {
+    fn Type(self) -> u16 {
+        match self.type_id {
+            CSSImportRuleTypeId => CSSRuleConstants::IMPORT_RULE,
+            CSSStyleRuleTypeId => CSSRuleConstants::STYLE_RULE,
+            CSSMediaRuleTypeId => CSSRuleConstants::MEDIA_RULE,
+            CSSFontFaceRuleTypeId => CSSRuleConstants::FONT_FACE_RULE,
+            CSSSupportsRuleTypeId => CSSRuleConstants::SUPPORTS_RULE,
+            CSSKeyframesRuleTypeId => CSSRuleConstants::KEYFRAMES_RULE,
+        }
+    }
+
+    // http://dev.w3.org/csswg/cssom/#dom-cssrule-csstext
+    fn CssText(self) -> DOMString {
+        self.rule.to_css()
+    }
+
+    // http://dev.w3.org/csswg/cssom/#dom-cssrule-csstext
+    fn SetCssText(self, _value: DOMString) {
+        // On setting the cssText attribute must do nothing.
+    }
+
+    fn GetParentRule(self) -> Option<Temporary<CSSRule>> {
+        self.parent_rule.map(|rule| Temporary::new(rule))
+    }
+
+    fn GetParentStyleSheet(self) -> Option<Temporary<CSSStyleSheet>> {
+        self.parent_stylesheet.get()
+    }
+}
+
+impl Reflectable for CSSRule {
+    fn reflector<'a>(&'a self) -> &'a Reflector {
+        &self.reflector_
+    }
+}
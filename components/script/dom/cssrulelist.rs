@@ -0,0 +1,83 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use dom::bindings::cell::DOMRefCell;
+use dom::bindings::codegen::Bindings::CSSRuleListBinding;
+use dom::bindings::codegen::Bindings::CSSRuleListBinding::CSSRuleListMethods;
+use dom::bindings::global;
+use dom::bindings::js::{JS, JSRef, Temporary};
+use dom::bindings::utils::{Reflectable, Reflector, reflect_dom_object};
+use dom::cssrule::{CSSRule, CSSRuleHelpers};
+use dom::cssstylesheet::CSSStyleSheet;
+use dom::window::Window;
+use style;
+
+// http://dev.w3.org/csswg/cssom/#the-cssrulelist-interface
+#[dom_struct]
+pub struct CSSRuleList {
+    reflector_: Reflector,
+    /// The objects for the rules, in the same order as the rules they stand for.
+    rules: DOMRefCell<Vec<JS<CSSRule>>>,
+}
+
+impl CSSRuleList {
+    fn new_inherited() -> CSSRuleList {
+        CSSRuleList {
+            reflector_: Reflector::new(),
+            rules: DOMRefCell::new(vec!()),
+        }
+    }
+
+    /// Creates a list with an object for each of the given rules.
+    pub fn new_for_rules(window: JSRef<Window>, rules: &[style::CSSRule],
+                         parent_stylesheet: Option<JSRef<CSSStyleSheet>>,
+                         parent_rule: Option<JSRef<CSSRule>>) -> Temporary<CSSRuleList> {
+        let list = reflect_dom_object(box CSSRuleList::new_inherited(),
+                                      global::Window(window),
+                                      CSSRuleListBinding::Wrap).root();
+        for (index, rule) in rules.iter().enumerate() {
+            let rule = CSSRule::new(window, rule, parent_stylesheet, parent_rule).root();
+            list.insert_rule(index, *rule);
+        }
+        Temporary::from_rooted(*list)
+    }
+}
+
+pub trait CSSRuleListHelpers {
+    fn insert_rule(self, index: uint, rule: JSRef<CSSRule>);
+    fn delete_rule(self, index: uint);
+}
+
+impl<'a> CSSRuleListHelpers for JSRef<'a, CSSRuleList> {
+    fn insert_rule(self, index: uint, rule: JSRef<CSSRule>) {
+        self.rules.borrow_mut().insert(index, JS::from_rooted(rule));
+    }
+
+    fn delete_rule(self, index: uint) {
+        let rule = self.rules.borrow_mut().remove(index).unwrap().root();
+        rule.detach();
+    }
+}
+
+impl<'a> CSSRuleListMethods for JSRef<'a, CSSRuleList> {
+    fn Length(self) -> u32 {
+        self.rules.borrow().len() as u32
+    }
+
+    fn Item(self, index: u32) -> Option<Temporary<CSSRule>> {
+        self.rules.borrow().as_slice().get(index as uint).map(|rule| Temporary::new(rule.clone()))
+    }
+
+    fn IndexedGetter(self, index: u32, found: &mut bool) -> Option<Temporary<CSSRule>> {
+        let item = self.Item(index);
+        *found = item.is_some();
+        item
+    }
+}
+
+impl Reflectable for CSSRuleList {
+    fn reflector<'a>(&'a self) -> &'a Reflector {
+        &self.reflector_
+    }
+}
@@ -0,0 +1,134 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use dom::bindings::codegen::Bindings::CSSStyleDeclarationBinding;
+use dom::bindings::codegen::Bindings::CSSStyleDeclarationBinding::CSSStyleDeclarationMethods;
+use dom::bindings::global;
+use dom::bindings::js::{JS, JSRef, Temporary};
+use dom::bindings::utils::{Reflectable, Reflector, reflect_dom_object};
+use dom::element::{Element, ElementHelpers};
+use dom::window::Window;
+use servo_util::str::DOMString;
+use style;
+
+/// Where the declarations come from.
+#[jstraceable]
+#[must_root]
+pub enum CSSStyleDeclarationOwner {
+    /// A style rule. Its declarations never change once parsed.
+    StyleRuleOwner(style::PropertyDeclarationBlock),
+    /// The style attribute of an element, which is parsed again when it changes.
+    ElementOwner(JS<Element>),
+}
+
+// http://dev.w3.org/csswg/cssom/#the-cssstyledeclaration-interface
+// FIXME: The declarations are exposed as they were written, so shorthands are not expanded into
+// longhands, and they can't be changed yet.
+#[dom_struct]
+pub struct CSSStyleDeclaration {
+    reflector_: Reflector,
+    owner: CSSStyleDeclarationOwner,
+}
+
+impl CSSStyleDeclaration {
+    fn new_inherited(owner: CSSStyleDeclarationOwner) -> CSSStyleDeclaration {
+        CSSStyleDeclaration {
+            reflector_: Reflector::new(),
+            owner: owner,
+        }
+    }
+
+    pub fn new_for_rule(window: JSRef<Window>, declarations: style::PropertyDeclarationBlock)
+                        -> Temporary<CSSStyleDeclaration> {
+        CSSStyleDeclaration::new(window, StyleRuleOwner(declarations))
+    }
+
+    pub fn new_for_element(window: JSRef<Window>, element: JSRef<Element>)
+                           -> Temporary<CSSStyleDeclaration> {
+        CSSStyleDeclaration::new(window, ElementOwner(JS::from_rooted(element)))
+    }
+
+    fn new(window: JSRef<Window>, owner: CSSStyleDeclarationOwner)
+           -> Temporary<CSSStyleDeclaration> {
+        reflect_dom_object(box CSSStyleDeclaration::new_inherited(owner),
+                           global::Window(window),
+                           CSSStyleDeclarationBinding::Wrap)
+    }
+}
+
+trait PrivateCSSStyleDeclarationHelpers {
+    fn with_declarations<T>(self, f: |Option<&style::PropertyDeclarationBlock>| -> T) -> T;
+}
+
+impl<'a> PrivateCSSStyleDeclarationHelpers for JSRef<'a, CSSStyleDeclaration> {
+    fn with_declarations<T>(self, f: |Option<&style::PropertyDeclarationBlock>| -> T) -> T {
+        match self.owner {
+            StyleRuleOwner(ref declarations) => f(Some(declarations)),
+            ElementOwner(ref element) => {
+                let element = element.root();
+                let style_attribute = element.style_attribute().borrow();
+                f(style_attribute.as_ref())
+            }
+        }
+    }
+}
+
+impl<'a> CSSStyleDeclarationMethods for JSRef<'a, CSSStyleDeclaration> {
+    // http://dev.w3.org/csswg/cssom/#dom-cssstyledeclaration-csstext
+    fn CssText(self) -> DOMString {
+        self.with_declarations(|declarations| {
+            declarations.map_or(String::new(), |declarations| declarations.to_css())
+        })
+    }
+
+    // http://dev.w3.org/csswg/cssom/#dom-cssstyledeclaration-length
+    fn Length(self) -> u32 {
+        self.with_declarations(|declarations| {
+            declarations.map_or(0, |declarations| declarations.source.len() as u32)
+        })
+    }
+
+    // http://dev.w3.org/csswg/cssom/#dom-cssstyledeclaration-item
+    fn Item(self, index: u32) -> DOMString {
+        let mut found = false;
+        self.IndexedGetter(index, &mut found)
+    }
+
+    fn IndexedGetter(self, index: u32, found: &mut bool) -> DOMString {
+        self.with_declarations(|declarations| {
+            let declaration = declarations.and_then(|declarations| {
+                declarations.source.as_slice().get(index as uint)
+            });
+            *found = declaration.is_some();
+            declaration.map_or(String::new(), |declaration| declaration.name.clone())
+        })
+    }
+
+    // http://dev.w3.org/csswg/cssom/#dom-cssstyledeclaration-getpropertyvalue
+    fn GetPropertyValue(self, property: DOMString) -> DOMString {
+        self.with_declarations(|declarations| {
+            declarations.and_then(|declarations| {
+                declarations.get_source_declaration(property.as_slice())
+            }).map_or(String::new(), |declaration| declaration.value.clone())
+        })
+    }
+
+    // http://dev.w3.org/csswg/cssom/#dom-cssstyledeclaration-getpropertypriority
+    fn GetPropertyPriority(self, property: DOMString) -> DOMString {
+        self.with_declarations(|declarations| {
+            match declarations.and_then(|declarations| {
+                declarations.get_source_declaration(property.as_slice())
+            }) {
+                Some(declaration) if declaration.important => "important".to_string(),
+                _ => String::new(),
+            }
+        })
+    }
+}
+
+impl Reflectable for CSSStyleDeclaration {
+    fn reflector<'a>(&'a self) -> &'a Reflector {
+        &self.reflector_
+    }
+}
@@ -0,0 +1,81 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use dom::bindings::codegen::Bindings::CSSStyleRuleBinding;
+use dom::bindings::codegen::Bindings::CSSStyleRuleBinding::CSSStyleRuleMethods;
+use dom::bindings::codegen::InheritTypes::CSSStyleRuleDerived;
+use dom::bindings::global;
+use dom::bindings::js::{JS, JSRef, MutNullableJS, OptionalSettable, Temporary};
+use dom::bindings::utils::{Reflectable, Reflector, reflect_dom_object};
+use dom::cssrule::{CSSRule, CSSStyleRuleTypeId};
+use dom::cssstyledeclaration::CSSStyleDeclaration;
+use dom::cssstylesheet::CSSStyleSheet;
+use dom::window::Window;
+use servo_util::str::DOMString;
+use style;
+
+use std::default::Default;
+
+#[dom_struct]
+pub struct CSSStyleRule {
+    cssrule: CSSRule,
+    window: JS<Window>,
+    style: MutNullableJS<CSSStyleDeclaration>,
+}
+
+impl CSSStyleRuleDerived for CSSRule {
+    fn is_cssstylerule(&self) -> bool {
+        *self.type_id() == CSSStyleRuleTypeId
+    }
+}
+
+impl CSSStyleRule {
+    fn new_inherited(window: JSRef<Window>, rule: style::CSSRule,
+                     parent_stylesheet: Option<JSRef<CSSStyleSheet>>,
+                     parent_rule: Option<JSRef<CSSRule>>) -> CSSStyleRule {
+        CSSStyleRule {
+            cssrule: CSSRule::new_inherited(CSSStyleRuleTypeId, rule, parent_stylesheet,
+                                            parent_rule),
+            window: JS::from_rooted(window),
+            style: Default::default(),
+        }
+    }
+
+    pub fn new(window: JSRef<Window>, rule: style::CSSRule,
+               parent_stylesheet: Option<JSRef<CSSStyleSheet>>,
+               parent_rule: Option<JSRef<CSSRule>>) -> Temporary<CSSStyleRule> {
+        reflect_dom_object(box CSSStyleRule::new_inherited(window, rule, parent_stylesheet,
+                                                           parent_rule),
+                           global::Window(window),
+                           CSSStyleRuleBinding::Wrap)
+    }
+
+    fn style_rule<'a>(&'a self) -> &'a style::StyleRule {
+        match *self.cssrule.rule() {
+            style::CSSStyleRule(ref rule) => rule,
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl<'a> CSSStyleRuleMethods for JSRef<'a, CSSStyleRule> {
+    fn SelectorText(self) -> DOMString {
+        self.style_rule().selector_text.clone()
+    }
+
+    fn Style(self) -> Temporary<CSSStyleDeclaration> {
+        if self.style.get().is_none() {
+            let window = self.window.root();
+            let declarations = self.style_rule().declarations.clone();
+            self.style.assign(Some(CSSStyleDeclaration::new_for_rule(*window, declarations)));
+        }
+        self.style.get().unwrap()
+    }
+}
+
+impl Reflectable for CSSStyleRule {
+    fn reflector<'a>(&'a self) -> &'a Reflector {
+        self.cssrule.reflector()
+    }
+}
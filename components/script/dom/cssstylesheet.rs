@@ -0,0 +1,229 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use dom::bindings::cell::DOMRefCell;
+use dom::bindings::codegen::Bindings::CSSStyleSheetBinding;
+use dom::bindings::codegen::Bindings::CSSStyleSheetBinding::CSSStyleSheetMethods;
+use dom::bindings::error::{ErrorResult, Fallible, IndexSize, Syntax};
+use dom::bindings::global;
+use dom::bindings::js::{JS, JSRef, MutNullableJS, OptionalRootable, OptionalSettable, Temporary};
+use dom::bindings::utils::{Reflectable, Reflector, reflect_dom_object};
+use dom::cssrule::CSSRule;
+use dom::cssrulelist::{CSSRuleList, CSSRuleListHelpers};
+use dom::document::DocumentHelpers;
+use dom::element::Element;
+use dom::node::{Node, document_from_node, window_from_node};
+use dom::window::Window;
+use script_task::{ScriptChan, StylesheetLoadedMsg};
+use servo_net::resource_task::{ResourceTask, load_whole_resource};
+use servo_util::str::DOMString;
+use servo_util::task::spawn_named;
+use style::{AuthorOrigin, CSSError, CSSImportRule, Stylesheet};
+
+use encoding::EncodingRef;
+use encoding::all::UTF_8;
+use std::default::Default;
+use sync::Arc;
+use url::Url;
+
+// http://dev.w3.org/csswg/cssom/#the-cssstylesheet-interface
+#[dom_struct]
+pub struct CSSStyleSheet {
+    reflector_: Reflector,
+    window: JS<Window>,
+    /// The `<style>` or `<link>` element the stylesheet belongs to.
+    owner: JS<Node>,
+    /// The URL an external stylesheet was loaded from.
+    href: Option<DOMString>,
+    /// Layout keeps the previous version of a stylesheet until it is sent the new one, so the
+    /// stylesheet is copied if it is changed while layout is still using it.
+    stylesheet: DOMRefCell<Arc<Stylesheet>>,
+    rule_list: MutNullableJS<CSSRuleList>,
+}
+
+impl CSSStyleSheet {
+    fn new_inherited(window: JSRef<Window>, owner: JSRef<Node>, href: Option<DOMString>,
+                     stylesheet: Stylesheet) -> CSSStyleSheet {
+        CSSStyleSheet {
+            reflector_: Reflector::new(),
+            window: JS::from_rooted(window),
+            owner: JS::from_rooted(owner),
+            href: href,
+            stylesheet: DOMRefCell::new(Arc::new(stylesheet)),
+            rule_list: Default::default(),
+        }
+    }
+
+    pub fn new(window: JSRef<Window>, owner: JSRef<Node>, href: Option<DOMString>,
+               stylesheet: Stylesheet) -> Temporary<CSSStyleSheet> {
+        reflect_dom_object(box CSSStyleSheet::new_inherited(window, owner, href, stylesheet),
+                           global::Window(window),
+                           CSSStyleSheetBinding::Wrap)
+    }
+}
+
+/// Where the stylesheet of a `<style>` or `<link>` element comes from.
+pub enum StylesheetSource {
+    /// The URL of a `<link>` element's stylesheet, which has yet to be fetched.
+    StylesheetUrl(Url),
+    /// The parsed stylesheet of a `<style>` element, whose imports have yet to be loaded.
+    ParsedStylesheet(Stylesheet),
+}
+
+/// Fetches the stylesheet at `url` and parses it, without loading the stylesheets it imports.
+/// The parse errors are added to `errors`.
+fn fetch_stylesheet(resource_task: &ResourceTask, url: Url, errors: &mut Vec<CSSError>)
+                    -> Option<Stylesheet> {
+    let (metadata, bytes) = match load_whole_resource(resource_task, url.clone()) {
+        Ok(resource) => resource,
+        Err(e) => {
+            error!("error loading stylesheet {}: {}", url.serialize(), e);
+            return None
+        }
+    };
+    // TODO: Get the actual value. http://dev.w3.org/csswg/css-syntax/#environment-encoding
+    let environment_encoding = UTF_8 as EncodingRef;
+    let protocol_encoding_label = metadata.charset.as_ref().map(|s| s.as_slice());
+    let mut stylesheet = Stylesheet::from_bytes(bytes.as_slice(),
+                                                metadata.final_url,
+                                                protocol_encoding_label,
+                                                Some(environment_encoding),
+                                                AuthorOrigin);
+    errors.extend(stylesheet.take_errors().into_iter());
+    Some(stylesheet)
+}
+
+/// Whether the stylesheet has `@import` rules, so that it has to be loaded with
+/// `load_stylesheet`.
+pub fn has_imports(stylesheet: &Stylesheet) -> bool {
+    stylesheet.rules().iter().any(|rule| {
+        match *rule {
+            CSSImportRule(_) => true,
+            _ => false,
+        }
+    })
+}
+
+/// Fetches the stylesheet of `owner` if needed, and the stylesheets it imports, on another task,
+/// so that the script task is not blocked meanwhile. The document gets the stylesheet when the
+/// script task handles the `StylesheetLoadedMsg` sent at the end.
+pub fn load_stylesheet(owner: JSRef<Element>, source: StylesheetSource) {
+    let window = window_from_node(owner).root();
+    let page = window.page();
+    let document = document_from_node(owner).root();
+    let load_id = document.start_stylesheet_load(owner);
+
+    let resource_task = page.resource_task.clone();
+    let ScriptChan(script_chan) = window.script_chan().clone();
+    let pipeline_id = page.id;
+    spawn_named("StylesheetLoader", proc() {
+        let mut errors = vec!();
+        let stylesheet = match source {
+            StylesheetUrl(url) => fetch_stylesheet(&resource_task, url, &mut errors),
+            ParsedStylesheet(stylesheet) => Some(stylesheet),
+        };
+        let stylesheet = match stylesheet {
+            Some(mut stylesheet) => {
+                stylesheet.load_imports(|url| {
+                    fetch_stylesheet(&resource_task, url.clone(), &mut errors)
+                });
+                Some(stylesheet)
+            }
+            None => None,
+        };
+        script_chan.send(StylesheetLoadedMsg(pipeline_id, load_id, stylesheet, errors));
+    });
+}
+
+pub trait CSSStyleSheetHelpers {
+    fn stylesheet(self) -> Arc<Stylesheet>;
+    fn stylesheet_changed(self);
+}
+
+impl<'a> CSSStyleSheetHelpers for JSRef<'a, CSSStyleSheet> {
+    /// The stylesheet as it is now, to be sent to layout.
+    fn stylesheet(self) -> Arc<Stylesheet> {
+        self.stylesheet.borrow().clone()
+    }
+
+    fn stylesheet_changed(self) {
+        let owner = self.owner.root();
+        let document = document_from_node(*owner).root();
+        document.invalidate_stylesheets();
+    }
+}
+
+impl<'a> CSSStyleSheetMethods for JSRef<'a, CSSStyleSheet> {
+    fn Type(self) -> DOMString {
+        "text/css".to_string()
+    }
+
+    fn GetHref(self) -> Option<DOMString> {
+        self.href.clone()
+    }
+
+    fn GetOwnerNode(self) -> Option<Temporary<Node>> {
+        Some(Temporary::new(self.owner))
+    }
+
+    fn CssRules(self) -> Temporary<CSSRuleList> {
+        if self.rule_list.get().is_none() {
+            let window = self.window.root();
+            let stylesheet = self.stylesheet();
+            let rule_list = CSSRuleList::new_for_rules(*window, stylesheet.rules(), Some(self),
+                                                       None);
+            self.rule_list.assign(Some(rule_list));
+        }
+        self.rule_list.get().unwrap()
+    }
+
+    // http://dev.w3.org/csswg/cssom/#insert-a-css-rule
+    fn InsertRule(self, rule: DOMString, index: u32) -> Fallible<u32> {
+        let index = index as uint;
+        let rule = {
+            let stylesheet = self.stylesheet.borrow();
+            if index > stylesheet.rules().len() {
+                return Err(IndexSize);
+            }
+            match stylesheet.parse_rule(rule.as_slice()) {
+                Ok(rule) => rule,
+                Err(()) => return Err(Syntax),
+            }
+        };
+
+        match self.rule_list.get().root() {
+            Some(rule_list) => {
+                let window = self.window.root();
+                let cssrule = CSSRule::new(*window, &rule, Some(self), None).root();
+                rule_list.insert_rule(index, *cssrule);
+            }
+            None => {}
+        }
+        self.stylesheet.borrow_mut().make_unique().insert_rule(index, rule);
+        self.stylesheet_changed();
+        Ok(index as u32)
+    }
+
+    // http://dev.w3.org/csswg/cssom/#remove-a-css-rule
+    fn DeleteRule(self, index: u32) -> ErrorResult {
+        let index = index as uint;
+        if index >= self.stylesheet.borrow().rules().len() {
+            return Err(IndexSize);
+        }
+
+        match self.rule_list.get().root() {
+            Some(rule_list) => rule_list.delete_rule(index),
+            None => {}
+        }
+        self.stylesheet.borrow_mut().make_unique().delete_rule(index);
+        self.stylesheet_changed();
+        Ok(())
+    }
+}
+
+impl Reflectable for CSSStyleSheet {
+    fn reflector<'a>(&'a self) -> &'a Reflector {
+        &self.reflector_
+    }
+}
@@ -0,0 +1,88 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use dom::bindings::codegen::Bindings::CSSSupportsRuleBinding;
+use dom::bindings::codegen::Bindings::CSSSupportsRuleBinding::CSSSupportsRuleMethods;
+use dom::bindings::codegen::Bindings::CSSRuleBinding::CSSRuleMethods;
+use dom::bindings::codegen::InheritTypes::{CSSSupportsRuleDerived, CSSRuleCast};
+use dom::bindings::global;
+use dom::bindings::js::{JS, JSRef, MutNullableJS, OptionalRootable, OptionalSettable};
+use dom::bindings::js::{RootedReference, Temporary};
+use dom::bindings::utils::{Reflectable, Reflector, reflect_dom_object};
+use dom::cssrule::{CSSRule, CSSSupportsRuleTypeId};
+use dom::cssrulelist::CSSRuleList;
+use dom::cssstylesheet::CSSStyleSheet;
+use dom::window::Window;
+use servo_util::str::DOMString;
+use style;
+
+use std::default::Default;
+
+#[dom_struct]
+pub struct CSSSupportsRule {
+    cssrule: CSSRule,
+    window: JS<Window>,
+    rule_list: MutNullableJS<CSSRuleList>,
+}
+
+impl CSSSupportsRuleDerived for CSSRule {
+    fn is_csssupportsrule(&self) -> bool {
+        *self.type_id() == CSSSupportsRuleTypeId
+    }
+}
+
+impl CSSSupportsRule {
+    fn new_inherited(window: JSRef<Window>, rule: style::CSSRule,
+                     parent_stylesheet: Option<JSRef<CSSStyleSheet>>,
+                     parent_rule: Option<JSRef<CSSRule>>) -> CSSSupportsRule {
+        CSSSupportsRule {
+            cssrule: CSSRule::new_inherited(CSSSupportsRuleTypeId, rule, parent_stylesheet,
+                                            parent_rule),
+            window: JS::from_rooted(window),
+            rule_list: Default::default(),
+        }
+    }
+
+    pub fn new(window: JSRef<Window>, rule: style::CSSRule,
+               parent_stylesheet: Option<JSRef<CSSStyleSheet>>,
+               parent_rule: Option<JSRef<CSSRule>>) -> Temporary<CSSSupportsRule> {
+        reflect_dom_object(box CSSSupportsRule::new_inherited(window, rule, parent_stylesheet,
+                                                              parent_rule),
+                           global::Window(window),
+                           CSSSupportsRuleBinding::Wrap)
+    }
+
+    fn supports_rule<'a>(&'a self) -> &'a style::SupportsRule {
+        match *self.cssrule.rule() {
+            style::CSSSupportsRule(ref rule) => rule,
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl<'a> CSSSupportsRuleMethods for JSRef<'a, CSSSupportsRule> {
+    fn ConditionText(self) -> DOMString {
+        self.supports_rule().condition_text.clone()
+    }
+
+    fn CssRules(self) -> Temporary<CSSRuleList> {
+        if self.rule_list.get().is_none() {
+            let window = self.window.root();
+            let cssrule: JSRef<CSSRule> = CSSRuleCast::from_ref(self);
+            let stylesheet = cssrule.GetParentStyleSheet().root();
+            let rule_list = CSSRuleList::new_for_rules(*window,
+                                                       self.supports_rule().rules.as_slice(),
+                                                       stylesheet.root_ref(),
+                                                       Some(cssrule));
+            self.rule_list.assign(Some(rule_list));
+        }
+        self.rule_list.get().unwrap()
+    }
+}
+
+impl Reflectable for CSSSupportsRule {
+    fn reflector<'a>(&'a self) -> &'a Reflector {
+        self.cssrule.reflector()
+    }
+}
@@ -9,6 +9,8 @@ use dom::bindings::codegen::Bindings::DocumentBinding::{DocumentMethods, Documen
 use dom::bindings::codegen::Bindings::DocumentBinding::DocumentReadyStateValues;
 use dom::bindings::codegen::Bindings::EventHandlerBinding::EventHandlerNonNull;
 use dom::bindings::codegen::Bindings::EventTargetBinding::EventTargetMethods;
+use dom::bindings::codegen::Bindings::HTMLLinkElementBinding::HTMLLinkElementMethods;
+use dom::bindings::codegen::Bindings::HTMLStyleElementBinding::HTMLStyleElementMethods;
 use dom::bindings::codegen::Bindings::NodeBinding::NodeMethods;
 use dom::bindings::codegen::Bindings::NodeFilterBinding::NodeFilter;
 use dom::bindings::codegen::Bindings::WindowBinding::WindowMethods;
@@ -20,8 +22,9 @@ use dom::bindings::codegen::InheritTypes::{HTMLAnchorElementDerived, HTMLAppletE
 use dom::bindings::codegen::InheritTypes::{HTMLAreaElementDerived, HTMLEmbedElementDerived};
 use dom::bindings::codegen::InheritTypes::{HTMLFormElementDerived, HTMLImageElementDerived};
 use dom::bindings::codegen::InheritTypes::{HTMLScriptElementDerived};
+use dom::bindings::codegen::InheritTypes::{HTMLLinkElementCast, HTMLStyleElementCast};
 use dom::bindings::error::{ErrorResult, Fallible, NotSupported, InvalidCharacter};
-use dom::bindings::error::{HierarchyRequest, NamespaceError};
+use dom::bindings::error::{HierarchyRequest, NamespaceError, Security};
 use dom::bindings::global::GlobalRef;
 use dom::bindings::global;
 use dom::bindings::js::{MutNullableJS, JS, JSRef, Temporary, OptionalSettable, TemporaryPushable};
@@ -29,6 +32,7 @@ use dom::bindings::js::OptionalRootable;
 use dom::bindings::utils::{Reflectable, Reflector, reflect_dom_object};
 use dom::bindings::utils::{xml_name_type, InvalidXMLName, Name, QName};
 use dom::comment::Comment;
+use dom::cssstylesheet::{CSSStyleSheet, CSSStyleSheetHelpers};
 use dom::customevent::CustomEvent;
 use dom::documentfragment::DocumentFragment;
 use dom::documenttype::DocumentType;
@@ -38,11 +42,14 @@ use dom::element::{HTMLHeadElementTypeId, HTMLTitleElementTypeId};
 use dom::element::{HTMLBodyElementTypeId, HTMLFrameSetElementTypeId};
 use dom::event::{Event, DoesNotBubble, NotCancelable};
 use dom::eventtarget::{EventTarget, NodeTargetTypeId, EventTargetHelpers};
+use dom::fontfaceset::{FontFaceSet, FontFaceSetHelpers};
 use dom::htmlanchorelement::HTMLAnchorElement;
 use dom::htmlcollection::{HTMLCollection, CollectionFilter};
 use dom::htmlelement::HTMLElement;
 use dom::htmlheadelement::HTMLHeadElement;
 use dom::htmlhtmlelement::HTMLHtmlElement;
+use dom::htmllinkelement::HTMLLinkElementHelpers;
+use dom::htmlstyleelement::{HTMLStyleElement, HTMLStyleElementHelpers};
 use dom::htmltitleelement::HTMLTitleElement;
 use dom::location::Location;
 use dom::mouseevent::MouseEvent;
@@ -53,20 +60,26 @@ use dom::nodelist::NodeList;
 use dom::text::Text;
 use dom::processinginstruction::ProcessingInstruction;
 use dom::range::Range;
+use dom::stylesheetlist::StyleSheetList;
 use dom::treewalker::TreeWalker;
 use dom::uievent::UIEvent;
 use dom::window::{Window, WindowHelpers};
+use layout_interface::{LayoutChan, SetStylesheetsMsg};
+use servo_net::cookie::NonHTTP;
+use servo_net::resource_task::{GetCookiesForUrl, SetCookiesForUrl};
 use servo_util::namespace;
 use servo_util::str::{DOMString, split_html_space_chars};
+use style::Stylesheet;
 
 use html5ever::tree_builder::{QuirksMode, NoQuirks, LimitedQuirks, Quirks};
 use string_cache::{Atom, QualName};
-use url::Url;
+use url::{Url, NonRelativeSchemeData};
 
 use std::collections::HashMap;
 use std::collections::hash_map::{Vacant, Occupied};
 use std::ascii::AsciiExt;
 use std::cell::{Cell, Ref};
+use std::comm::channel;
 use std::default::Default;
 use time;
 
@@ -101,6 +114,14 @@ pub struct Document {
     possibly_focused: MutNullableJS<Element>,
     /// The element that currently has the document focus context.
     focused: MutNullableJS<Element>,
+    /// The element indicated by the fragment of the document's URL, if any.
+    target_element: MutNullableJS<Element>,
+    stylesheet_list: MutNullableJS<StyleSheetList>,
+    fonts: MutNullableJS<FontFaceSet>,
+    /// The `<style>` and `<link>` elements whose stylesheets are being loaded, with the IDs of
+    /// their loads.
+    pending_stylesheet_loads: DOMRefCell<Vec<(uint, JS<Element>)>>,
+    next_stylesheet_load_id: Cell<uint>,
 }
 
 impl DocumentDerived for EventTarget {
@@ -187,6 +208,14 @@ pub trait DocumentHelpers<'a> {
     fn begin_focus_transaction(self);
     fn request_focus(self, elem: JSRef<Element>);
     fn commit_focus_transaction(self);
+    fn set_target_element(self, element: Option<JSRef<Element>>);
+    fn stylesheets(self) -> Vec<Temporary<CSSStyleSheet>>;
+    fn invalidate_stylesheets(self);
+    fn start_stylesheet_load(self, owner: JSRef<Element>) -> uint;
+    fn cancel_stylesheet_load(self, owner: JSRef<Element>);
+    fn finish_stylesheet_load(self, load_id: uint, stylesheet: Option<Stylesheet>);
+    fn has_pending_stylesheet_loads(self) -> bool;
+    fn update_font_status(self);
 }
 
 impl<'a> DocumentHelpers<'a> for JSRef<'a, Document> {
@@ -358,7 +387,130 @@ impl<'a> DocumentHelpers<'a> for JSRef<'a, Document> {
     /// transaction, or none if no elements requested it.
     fn commit_focus_transaction(self) {
         //TODO: dispatch blur, focus, focusout, and focusin events
+        match self.focused.get().root() {
+            Some(elem) => {
+                let node: JSRef<Node> = NodeCast::from_ref(*elem);
+                node.set_focus_state(false);
+                self.content_changed(node);
+            }
+            None => {}
+        }
         self.focused.assign(self.possibly_focused.get());
+        match self.focused.get().root() {
+            Some(elem) => {
+                let node: JSRef<Node> = NodeCast::from_ref(*elem);
+                node.set_focus_state(true);
+                self.content_changed(node);
+            }
+            None => {}
+        }
+    }
+
+    /// Make the given element the one matched by `:target`, or unset it.
+    /// https://html.spec.whatwg.org/multipage/#the-indicated-part-of-the-document
+    fn set_target_element(self, element: Option<JSRef<Element>>) {
+        match self.target_element.get().root() {
+            Some(elem) => {
+                let node: JSRef<Node> = NodeCast::from_ref(*elem);
+                node.set_target_state(false);
+                self.content_changed(node);
+            }
+            None => {}
+        }
+        self.target_element.assign(element);
+        match element {
+            Some(elem) => {
+                let node: JSRef<Node> = NodeCast::from_ref(elem);
+                node.set_target_state(true);
+                self.content_changed(node);
+            }
+            None => {}
+        }
+    }
+
+    /// The stylesheets of the `<style>` and `<link>` elements in the document, in tree order.
+    // http://dev.w3.org/csswg/cssom/#document-css-style-sheets
+    fn stylesheets(self) -> Vec<Temporary<CSSStyleSheet>> {
+        let root: JSRef<Node> = NodeCast::from_ref(self);
+        root.traverse_preorder().filter_map(|node| {
+            match HTMLStyleElementCast::to_ref(node) {
+                Some(style) => return style.GetSheet(),
+                None => {}
+            }
+            match HTMLLinkElementCast::to_ref(node) {
+                Some(link) => link.GetSheet(),
+                None => None,
+            }
+        }).collect()
+    }
+
+    /// Sends the stylesheets to layout after one of them has been added, removed or changed,
+    /// if this is the document the window displays.
+    fn invalidate_stylesheets(self) {
+        let window = self.window.root();
+        if *window.Document().root() != self {
+            return;
+        }
+        let stylesheets = self.stylesheets().into_iter().map(|stylesheet| {
+            stylesheet.root().stylesheet()
+        }).collect();
+        let LayoutChan(ref layout_chan) = window.page().layout_chan;
+        layout_chan.send(SetStylesheetsMsg(stylesheets));
+        self.reflow();
+    }
+
+    /// Records that the stylesheet of `owner` is being loaded, replacing the load it had started
+    /// before, if any. Returns the ID of the load.
+    fn start_stylesheet_load(self, owner: JSRef<Element>) -> uint {
+        self.cancel_stylesheet_load(owner);
+        let load_id = self.next_stylesheet_load_id.get();
+        self.next_stylesheet_load_id.set(load_id + 1);
+        self.pending_stylesheet_loads.borrow_mut().push((load_id, JS::from_rooted(owner)));
+        load_id
+    }
+
+    /// Forgets the stylesheet that `owner` is loading, if any, so that it never gets it.
+    fn cancel_stylesheet_load(self, owner: JSRef<Element>) {
+        let owner = JS::from_rooted(owner);
+        let mut loads = self.pending_stylesheet_loads.borrow_mut();
+        loads.retain(|&(_, ref element)| *element != owner);
+    }
+
+    /// Hands the stylesheet of a load to the element that started it, unless the load has been
+    /// replaced or cancelled since.
+    fn finish_stylesheet_load(self, load_id: uint, stylesheet: Option<Stylesheet>) {
+        let index = self.pending_stylesheet_loads.borrow().iter().position(|&(id, _)| {
+            id == load_id
+        });
+        let (_, owner) = match index {
+            Some(index) => self.pending_stylesheet_loads.borrow_mut().remove(index).unwrap(),
+            None => return,
+        };
+        let owner = owner.root();
+        let stylesheet = match stylesheet {
+            Some(stylesheet) => stylesheet,
+            None => return,
+        };
+        match HTMLLinkElementCast::to_ref(*owner) {
+            Some(link) => link.set_loaded_stylesheet(stylesheet),
+            None => {
+                let style: JSRef<HTMLStyleElement> = HTMLStyleElementCast::to_ref(*owner).unwrap();
+                style.set_loaded_stylesheet(stylesheet)
+            }
+        }
+    }
+
+    fn has_pending_stylesheet_loads(self) -> bool {
+        !self.pending_stylesheet_loads.borrow().is_empty()
+    }
+
+    /// Tells the font face set of the document, if script has asked for it, that layout has
+    /// finished reflowing, which web fonts may have loaded in the meantime.
+    fn update_font_status(self) {
+        match self.fonts.get() {
+            Some(fonts) => fonts.root().update_status(),
+            None => {}
+        }
     }
 }
 
@@ -425,6 +577,11 @@ impl Document {
             ready_state: Cell::new(ready_state),
             possibly_focused: Default::default(),
             focused: Default::default(),
+            target_element: Default::default(),
+            stylesheet_list: Default::default(),
+            fonts: Default::default(),
+            pending_stylesheet_loads: DOMRefCell::new(vec!()),
+            next_stylesheet_load_id: Cell::new(0),
         }
     }
 
@@ -720,6 +877,31 @@ impl<'a> DocumentMethods for JSRef<'a, Document> {
         }
     }
 
+    // http://www.whatwg.org/html/#dom-document-cookie
+    fn GetCookie(self) -> Fallible<DOMString> {
+        let url = self.url();
+        match url.scheme_data {
+            NonRelativeSchemeData(_) => return Err(Security),
+            _ => {}
+        }
+        let window = self.window.root();
+        let (tx, rx) = channel();
+        window.page().resource_task.send(GetCookiesForUrl(url.clone(), tx, NonHTTP));
+        Ok(rx.recv().unwrap_or("".to_string()))
+    }
+
+    // http://www.whatwg.org/html/#dom-document-cookie
+    fn SetCookie(self, cookie: DOMString) -> ErrorResult {
+        let url = self.url();
+        match url.scheme_data {
+            NonRelativeSchemeData(_) => return Err(Security),
+            _ => {}
+        }
+        let window = self.window.root();
+        window.page().resource_task.send(SetCookiesForUrl(url.clone(), cookie, NonHTTP));
+        Ok(())
+    }
+
     // http://www.whatwg.org/html/#dom-document-lastmodified
     fn LastModified(self) -> DOMString {
         match *self.last_modified.borrow() {
@@ -983,4 +1165,22 @@ impl<'a> DocumentMethods for JSRef<'a, Document> {
     event_handler!(click, GetOnclick, SetOnclick)
     event_handler!(load, GetOnload, SetOnload)
     event_handler!(readystatechange, GetOnreadystatechange, SetOnreadystatechange)
+
+    fn StyleSheets(self) -> Temporary<StyleSheetList> {
+        if self.stylesheet_list.get().is_none() {
+            let window = self.window.root();
+            let stylesheet_list = StyleSheetList::new(*window, self);
+            self.stylesheet_list.assign(Some(stylesheet_list));
+        }
+        self.stylesheet_list.get().unwrap()
+    }
+
+    fn Fonts(self) -> Temporary<FontFaceSet> {
+        if self.fonts.get().is_none() {
+            let window = self.window.root();
+            let fonts = FontFaceSet::new(*window);
+            self.fonts.assign(Some(fonts));
+        }
+        self.fonts.get().unwrap()
+    }
 }
@@ -56,6 +56,7 @@ impl DOMErrorName {
             error::Abort => AbortError,
             error::Timeout => TimeoutError,
             error::DataClone => DataCloneError,
+            error::QuotaExceeded => QuotaExceededError,
             error::FailureUnknown => panic!(),
         }
     }
@@ -1158,6 +1158,26 @@ impl<'a> style::TElement<'a> for JSRef<'a, Element> {
         let node: JSRef<Node> = NodeCast::from_ref(self);
         node.get_enabled_state()
     }
+    fn get_checked_state(self) -> bool {
+        let node: JSRef<Node> = NodeCast::from_ref(self);
+        node.get_checked_state()
+    }
+    fn get_indeterminate_state(self) -> bool {
+        let node: JSRef<Node> = NodeCast::from_ref(self);
+        node.get_indeterminate_state()
+    }
+    fn get_active_state(self) -> bool {
+        let node: JSRef<Node> = NodeCast::from_ref(self);
+        node.get_active_state()
+    }
+    fn get_focus_state(self) -> bool {
+        let node: JSRef<Node> = NodeCast::from_ref(self);
+        node.get_focus_state()
+    }
+    fn get_target_state(self) -> bool {
+        let node: JSRef<Node> = NodeCast::from_ref(self);
+        node.get_target_state()
+    }
     fn has_class(self, name: &Atom) -> bool {
         // FIXME(zwarich): Remove this when UFCS lands and there is a better way
         // of disambiguating methods.
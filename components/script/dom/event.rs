@@ -27,12 +27,15 @@ pub enum EventPhase {
 #[deriving(PartialEq)]
 #[jstraceable]
 pub enum EventTypeId {
+    AnimationEventTypeId,
     CustomEventTypeId,
     HTMLEventTypeId,
     KeyboardEventTypeId,
     MessageEventTypeId,
     MouseEventTypeId,
     ProgressEventTypeId,
+    StorageEventTypeId,
+    TransitionEventTypeId,
     UIEventTypeId
 }
 
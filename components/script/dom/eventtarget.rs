@@ -38,6 +38,7 @@ pub enum ListenerPhase {
 #[jstraceable]
 pub enum EventTargetTypeId {
     NodeTargetTypeId(NodeTypeId),
+    FontFaceSetTypeId,
     WebSocketTypeId,
     WindowTypeId,
     WorkerTypeId,
@@ -0,0 +1,120 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use dom::bindings::callback::ReportExceptions;
+use dom::bindings::cell::DOMRefCell;
+use dom::bindings::codegen::Bindings::EventHandlerBinding::EventHandlerNonNull;
+use dom::bindings::codegen::Bindings::EventTargetBinding::EventTargetMethods;
+use dom::bindings::codegen::Bindings::FontFaceSetBinding;
+use dom::bindings::codegen::Bindings::FontFaceSetBinding::FontFaceSetMethods;
+use dom::bindings::codegen::Bindings::FontFaceSetBinding::FontFaceSetLoadStatus;
+use dom::bindings::codegen::Bindings::FontFaceSetBinding::FontFaceSetLoadStatusValues;
+use dom::bindings::codegen::Bindings::FontFaceSetBinding::FontFaceSetReadyCallback;
+use dom::bindings::codegen::Bindings::WindowBinding::WindowMethods;
+use dom::bindings::codegen::InheritTypes::EventTargetCast;
+use dom::bindings::global;
+use dom::bindings::js::{JS, JSRef, Temporary};
+use dom::bindings::utils::{Reflectable, Reflector, reflect_dom_object};
+use dom::document::DocumentHelpers;
+use dom::event::{Event, DoesNotBubble, NotCancelable};
+use dom::eventtarget::{EventTarget, EventTargetHelpers, FontFaceSetTypeId};
+use dom::window::Window;
+
+use std::cell::Cell;
+use std::mem;
+
+// http://dev.w3.org/csswg/css-font-loading/#FontFaceSet-interface
+#[dom_struct]
+pub struct FontFaceSet {
+    eventtarget: EventTarget,
+    window: JS<Window>,
+    /// Whether the document was loading fonts when the status was last updated.
+    loading: Cell<bool>,
+    /// The callbacks to call once the web fonts of the document have loaded.
+    ready_callbacks: DOMRefCell<Vec<FontFaceSetReadyCallback>>,
+}
+
+impl FontFaceSet {
+    fn new_inherited(window: JSRef<Window>) -> FontFaceSet {
+        FontFaceSet {
+            eventtarget: EventTarget::new_inherited(FontFaceSetTypeId),
+            window: JS::from_rooted(window),
+            loading: Cell::new(is_loading(window)),
+            ready_callbacks: DOMRefCell::new(vec!()),
+        }
+    }
+
+    pub fn new(window: JSRef<Window>) -> Temporary<FontFaceSet> {
+        reflect_dom_object(box FontFaceSet::new_inherited(window),
+                           global::Window(window),
+                           FontFaceSetBinding::Wrap)
+    }
+}
+
+/// Whether the document of the window is loading web fonts, or stylesheets that may ask for more.
+fn is_loading(window: JSRef<Window>) -> bool {
+    window.page().layout().web_fonts_loading() ||
+        window.Document().root().has_pending_stylesheet_loads()
+}
+
+pub trait FontFaceSetHelpers {
+    fn update_status(self);
+}
+
+impl<'a> FontFaceSetHelpers for JSRef<'a, FontFaceSet> {
+    /// Checks whether web fonts or stylesheets are still loading after a reflow or a stylesheet
+    /// load, and fires `loadingdone` and calls the ready callbacks if they have all loaded since
+    /// the last check.
+    // http://dev.w3.org/csswg/css-font-loading/#fontfaceset-pending-on-the-environment
+    fn update_status(self) {
+        let window = self.window.root();
+        let loading = is_loading(*window);
+        let was_loading = self.loading.get();
+        self.loading.set(loading);
+        if loading {
+            return;
+        }
+
+        if was_loading {
+            let event = Event::new(global::Window(*window), "loadingdone".to_string(),
+                                   DoesNotBubble, NotCancelable).root();
+            let target: JSRef<EventTarget> = EventTargetCast::from_ref(self);
+            let _ = target.DispatchEvent(*event);
+        }
+
+        // Callbacks may ask to be called again, which only happens once fonts load again.
+        let callbacks = mem::replace(&mut *self.ready_callbacks.borrow_mut(), vec!());
+        for callback in callbacks.iter() {
+            let _ = callback.Call_(self, self, ReportExceptions);
+        }
+    }
+}
+
+impl<'a> FontFaceSetMethods for JSRef<'a, FontFaceSet> {
+    event_handler!(loadingdone, GetOnloadingdone, SetOnloadingdone)
+
+    fn Status(self) -> FontFaceSetLoadStatus {
+        let window = self.window.root();
+        if is_loading(*window) {
+            FontFaceSetLoadStatusValues::Loading
+        } else {
+            FontFaceSetLoadStatusValues::Loaded
+        }
+    }
+
+    fn Ready(self, callback: FontFaceSetReadyCallback) {
+        let window = self.window.root();
+        if is_loading(*window) {
+            self.ready_callbacks.borrow_mut().push(callback);
+        } else {
+            let _ = callback.Call_(self, self, ReportExceptions);
+        }
+    }
+}
+
+impl Reflectable for FontFaceSet {
+    fn reflector<'a>(&'a self) -> &'a Reflector {
+        self.eventtarget.reflector()
+    }
+}
@@ -9,9 +9,10 @@ use dom::bindings::codegen::Bindings::HTMLCanvasElementBinding::HTMLCanvasElemen
 use dom::bindings::codegen::InheritTypes::HTMLCanvasElementDerived;
 use dom::bindings::codegen::InheritTypes::{ElementCast, HTMLElementCast};
 use dom::bindings::global::Window;
-use dom::bindings::js::{MutNullableJS, JSRef, Temporary, OptionalSettable};
+use dom::bindings::js::{MutNullableJS, JS, JSRef, Temporary, OptionalSettable};
 use dom::bindings::utils::{Reflectable, Reflector};
 use dom::canvasrenderingcontext2d::CanvasRenderingContext2D;
+use dom::canvasrenderingcontext2d::LayoutCanvasRenderingContext2DHelpers;
 use dom::document::Document;
 use dom::element::{Element, HTMLCanvasElementTypeId, AttributeHandlers};
 use dom::eventtarget::{EventTarget, NodeTargetTypeId};
@@ -19,6 +20,8 @@ use dom::htmlelement::HTMLElement;
 use dom::node::{Node, ElementNodeTypeId, window_from_node};
 use dom::virtualmethods::VirtualMethods;
 
+use canvas::canvas_chan::CanvasChan;
+use servo_net::image::base::image_to_bgra;
 use servo_util::str::{DOMString, parse_unsigned_integer};
 
 use geom::size::Size2D;
@@ -60,6 +63,53 @@ impl HTMLCanvasElement {
     }
 }
 
+pub trait LayoutHTMLCanvasElementHelpers {
+    unsafe fn get_renderer(&self) -> Option<CanvasChan>;
+    unsafe fn get_canvas_width(&self) -> u32;
+    unsafe fn get_canvas_height(&self) -> u32;
+}
+
+impl LayoutHTMLCanvasElementHelpers for JS<HTMLCanvasElement> {
+    unsafe fn get_renderer(&self) -> Option<CanvasChan> {
+        let context = (*self.unsafe_get()).context.get_inner();
+        context.map(|context| context.get_renderer())
+    }
+
+    unsafe fn get_canvas_width(&self) -> u32 {
+        (*self.unsafe_get()).width.get()
+    }
+
+    unsafe fn get_canvas_height(&self) -> u32 {
+        (*self.unsafe_get()).height.get()
+    }
+}
+
+pub trait HTMLCanvasElementHelpers {
+    fn get_size(self) -> Size2D<i32>;
+    fn fetch_all_data(self) -> Vec<u8>;
+}
+
+impl<'a> HTMLCanvasElementHelpers for JSRef<'a, HTMLCanvasElement> {
+    fn get_size(self) -> Size2D<i32> {
+        Size2D(self.width.get() as i32, self.height.get() as i32)
+    }
+
+    /// Returns the premultiplied BGRA pixels of the canvas. A canvas whose context has not been
+    /// created yet is transparent black.
+    fn fetch_all_data(self) -> Vec<u8> {
+        match self.context.get() {
+            None => {
+                let size = self.get_size();
+                Vec::from_elem((size.width * size.height * 4) as uint, 0u8)
+            }
+            Some(context) => {
+                let frame = context.root().get_renderer().frame();
+                image_to_bgra(&**frame).unwrap()
+            }
+        }
+    }
+}
+
 impl<'a> HTMLCanvasElementMethods for JSRef<'a, HTMLCanvasElement> {
     fn Width(self) -> u32 {
         self.width.get()
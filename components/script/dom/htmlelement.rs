@@ -11,8 +11,9 @@ use dom::bindings::codegen::Bindings::WindowBinding::WindowMethods;
 use dom::bindings::codegen::InheritTypes::{ElementCast, HTMLFrameSetElementDerived};
 use dom::bindings::codegen::InheritTypes::EventTargetCast;
 use dom::bindings::codegen::InheritTypes::{HTMLElementDerived, HTMLBodyElementDerived};
-use dom::bindings::js::{JSRef, Temporary};
+use dom::bindings::js::{JSRef, MutNullableJS, OptionalSettable, Temporary};
 use dom::bindings::utils::{Reflectable, Reflector};
+use dom::cssstyledeclaration::CSSStyleDeclaration;
 use dom::document::Document;
 use dom::element::{Element, ElementTypeId, ElementTypeId_, HTMLElementTypeId};
 use dom::eventtarget::{EventTarget, EventTargetHelpers, NodeTargetTypeId};
@@ -23,9 +24,12 @@ use servo_util::str::DOMString;
 
 use string_cache::Atom;
 
+use std::default::Default;
+
 #[dom_struct]
 pub struct HTMLElement {
-    element: Element
+    element: Element,
+    style_decl: MutNullableJS<CSSStyleDeclaration>,
 }
 
 impl HTMLElementDerived for EventTarget {
@@ -41,7 +45,8 @@ impl HTMLElementDerived for EventTarget {
 impl HTMLElement {
     pub fn new_inherited(type_id: ElementTypeId, tag_name: DOMString, prefix: Option<DOMString>, document: JSRef<Document>) -> HTMLElement {
         HTMLElement {
-            element: Element::new_inherited(type_id, tag_name, ns!(HTML), prefix, document)
+            element: Element::new_inherited(type_id, tag_name, ns!(HTML), prefix, document),
+            style_decl: Default::default(),
         }
     }
 
@@ -91,6 +96,16 @@ impl<'a> HTMLElementMethods for JSRef<'a, HTMLElement> {
             win.SetOnload(listener)
         }
     }
+
+    // http://dev.w3.org/csswg/cssom/#dom-elementcssinlinestyle-style
+    fn Style(self) -> Temporary<CSSStyleDeclaration> {
+        if self.style_decl.get().is_none() {
+            let window = window_from_node(self).root();
+            let element: JSRef<Element> = ElementCast::from_ref(self);
+            self.style_decl.assign(Some(CSSStyleDeclaration::new_for_element(*window, element)));
+        }
+        self.style_decl.get().unwrap()
+    }
 }
 
 impl<'a> VirtualMethods for JSRef<'a, HTMLElement> {
@@ -81,6 +81,17 @@ impl HTMLImageElement {
     }
 }
 
+pub trait HTMLImageElementHelpers {
+    fn get_url(self) -> Option<Url>;
+}
+
+impl<'a> HTMLImageElementHelpers for JSRef<'a, HTMLImageElement> {
+    /// Returns the URL of the image, if there is one.
+    fn get_url(self) -> Option<Url> {
+        self.image.borrow().clone()
+    }
+}
+
 pub trait LayoutHTMLImageElementHelpers {
     unsafe fn image(&self) -> Option<Url>;
 }
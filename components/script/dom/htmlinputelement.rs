@@ -55,6 +55,7 @@ pub struct HTMLInputElement {
     htmlelement: HTMLElement,
     input_type: Cell<InputType>,
     checked: Cell<bool>,
+    indeterminate: Cell<bool>,
     size: Cell<u32>,
     textinput: DOMRefCell<TextInput>,
 }
@@ -73,6 +74,7 @@ impl HTMLInputElement {
             htmlelement: HTMLElement::new_inherited(HTMLInputElementTypeId, localName, prefix, document),
             input_type: Cell::new(InputText),
             checked: Cell::new(false),
+            indeterminate: Cell::new(false),
             size: Cell::new(DEFAULT_INPUT_SIZE),
             textinput: DOMRefCell::new(TextInput::new(Single, "".to_string())),
         }
@@ -149,6 +151,18 @@ impl<'a> HTMLInputElementMethods for JSRef<'a, HTMLInputElement> {
     // https://html.spec.whatwg.org/multipage/forms.html#dom-input-checked
     make_bool_setter!(SetChecked, "checked")
 
+    // https://html.spec.whatwg.org/multipage/forms.html#dom-input-indeterminate
+    fn Indeterminate(self) -> bool {
+        self.indeterminate.get()
+    }
+
+    // https://html.spec.whatwg.org/multipage/forms.html#dom-input-indeterminate
+    fn SetIndeterminate(self, indeterminate: bool) {
+        self.indeterminate.set(indeterminate);
+        self.update_checkedness_pseudo_classes();
+        self.force_relayout();
+    }
+
     // https://html.spec.whatwg.org/multipage/forms.html#dom-input-size
     make_uint_getter!(Size)
 
@@ -211,6 +225,7 @@ pub trait HTMLInputElementHelpers {
     fn radio_group_updated(self, group: Option<&str>);
     fn get_radio_group(self) -> Option<String>;
     fn update_checked_state(self, checked: bool);
+    fn update_checkedness_pseudo_classes(self);
     fn get_size(&self) -> u32;
 }
 
@@ -266,9 +281,20 @@ impl<'a> HTMLInputElementHelpers for JSRef<'a, HTMLInputElement> {
                                         .map(|group| group.as_slice()));
         }
         //TODO: dispatch change event
+        self.update_checkedness_pseudo_classes();
         self.force_relayout();
     }
 
+    // https://html.spec.whatwg.org/multipage/scripting.html#selector-checked
+    // https://html.spec.whatwg.org/multipage/scripting.html#selector-indeterminate
+    fn update_checkedness_pseudo_classes(self) {
+        let node: JSRef<Node> = NodeCast::from_ref(self);
+        let input_type = self.input_type.get();
+        node.set_checked_state(self.checked.get() &&
+                               (input_type == InputCheckbox || input_type == InputRadio));
+        node.set_indeterminate_state(self.indeterminate.get() && input_type == InputCheckbox);
+    }
+
     fn get_size(&self) -> u32 {
         self.size.get()
     }
@@ -319,6 +345,7 @@ impl<'a> VirtualMethods for JSRef<'a, HTMLInputElement> {
                                                  .as_ref()
                                                  .map(|group| group.as_slice()));
                 }
+                self.update_checkedness_pseudo_classes();
                 self.force_relayout();
             }
             &atom!("value") => {
@@ -363,6 +390,7 @@ impl<'a> VirtualMethods for JSRef<'a, HTMLInputElement> {
                                                 .map(|group| group.as_slice()));
                 }
                 self.input_type.set(InputText);
+                self.update_checkedness_pseudo_classes();
                 self.force_relayout();
             }
             &atom!("value") => {
@@ -419,7 +447,10 @@ impl<'a> VirtualMethods for JSRef<'a, HTMLInputElement> {
 
         if "click" == event.Type().as_slice() && !event.DefaultPrevented() {
             match self.input_type.get() {
-                InputCheckbox => self.SetChecked(!self.checked.get()),
+                InputCheckbox => {
+                    self.indeterminate.set(false);
+                    self.SetChecked(!self.checked.get());
+                }
                 InputRadio => self.SetChecked(true),
                 InputButton(Some(DEFAULT_SUBMIT_VALUE)) => {
                     self.form_owner().map(|o| {
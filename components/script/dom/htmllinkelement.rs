@@ -7,18 +7,19 @@ use dom::attr::AttrHelpers;
 use dom::bindings::codegen::Bindings::HTMLLinkElementBinding;
 use dom::bindings::codegen::Bindings::HTMLLinkElementBinding::HTMLLinkElementMethods;
 use dom::bindings::codegen::InheritTypes::HTMLLinkElementDerived;
-use dom::bindings::codegen::InheritTypes::{ElementCast, HTMLElementCast};
-use dom::bindings::js::{MutNullableJS, JSRef, Temporary, OptionalRootable};
+use dom::bindings::codegen::InheritTypes::{ElementCast, HTMLElementCast, NodeCast};
+use dom::bindings::js::{MutNullableJS, JSRef, Temporary, OptionalRootable, OptionalSettable};
 use dom::bindings::utils::{Reflectable, Reflector};
-use dom::document::Document;
+use dom::cssstylesheet::{CSSStyleSheet, StylesheetUrl, load_stylesheet};
+use dom::document::{Document, DocumentHelpers};
 use dom::domtokenlist::DOMTokenList;
 use dom::element::{AttributeHandlers, Element, HTMLLinkElementTypeId};
 use dom::eventtarget::{EventTarget, NodeTargetTypeId};
 use dom::htmlelement::HTMLElement;
-use dom::node::{Node, NodeHelpers, ElementNodeTypeId, window_from_node};
+use dom::node::{Node, NodeHelpers, ElementNodeTypeId, document_from_node, window_from_node};
 use dom::virtualmethods::VirtualMethods;
-use layout_interface::{LayoutChan, LoadStylesheetMsg};
 use servo_util::str::{DOMString, HTML_SPACE_CHARACTERS};
+use style::Stylesheet;
 
 use std::ascii::AsciiExt;
 use std::default::Default;
@@ -29,6 +30,7 @@ use string_cache::Atom;
 pub struct HTMLLinkElement {
     htmlelement: HTMLElement,
     rel_list: MutNullableJS<DOMTokenList>,
+    sheet: MutNullableJS<CSSStyleSheet>,
 }
 
 impl HTMLLinkElementDerived for EventTarget {
@@ -42,6 +44,7 @@ impl HTMLLinkElement {
         HTMLLinkElement {
             htmlelement: HTMLElement::new_inherited(HTMLLinkElementTypeId, localName, prefix, document),
             rel_list: Default::default(),
+            sheet: Default::default(),
         }
     }
 
@@ -119,6 +122,23 @@ impl<'a> VirtualMethods for JSRef<'a, HTMLLinkElement> {
             }
         }
     }
+
+    fn unbind_from_tree(&self, tree_in_doc: bool) {
+        match self.super_type() {
+            Some(ref s) => s.unbind_from_tree(tree_in_doc),
+            _ => ()
+        }
+
+        if tree_in_doc {
+            let document = document_from_node(*self).root();
+            let element: JSRef<Element> = ElementCast::from_ref(*self);
+            document.cancel_stylesheet_load(element);
+            if self.sheet.get().is_some() {
+                self.sheet.clear();
+                document.invalidate_stylesheets();
+            }
+        }
+    }
 }
 
 trait PrivateHTMLLinkElementHelpers {
@@ -128,13 +148,33 @@ trait PrivateHTMLLinkElementHelpers {
 impl<'a> PrivateHTMLLinkElementHelpers for JSRef<'a, HTMLLinkElement> {
     fn handle_stylesheet_url(self, href: &str) {
         let window = window_from_node(self).root();
-        match UrlParser::new().base_url(&window.page().get_url()).parse(href) {
-            Ok(url) => {
-                let LayoutChan(ref layout_chan) = window.page().layout_chan;
-                layout_chan.send(LoadStylesheetMsg(url));
+        let url = match UrlParser::new().base_url(&window.page().get_url()).parse(href) {
+            Ok(url) => url,
+            Err(e) => {
+                debug!("Parsing url {:s} failed: {}", href, e);
+                return
             }
-            Err(e) => debug!("Parsing url {:s} failed: {}", href, e)
-        }
+        };
+
+        let element: JSRef<Element> = ElementCast::from_ref(self);
+        load_stylesheet(element, StylesheetUrl(url));
+    }
+}
+
+pub trait HTMLLinkElementHelpers {
+    fn set_loaded_stylesheet(self, stylesheet: Stylesheet);
+}
+
+impl<'a> HTMLLinkElementHelpers for JSRef<'a, HTMLLinkElement> {
+    /// Takes the stylesheet that `handle_stylesheet_url` started loading, and sends it to layout.
+    fn set_loaded_stylesheet(self, stylesheet: Stylesheet) {
+        let window = window_from_node(self).root();
+        let node: JSRef<Node> = NodeCast::from_ref(self);
+        let href = Some(stylesheet.base_url.serialize());
+        self.sheet.assign(Some(CSSStyleSheet::new(*window, node, href, stylesheet)));
+
+        let document = document_from_node(self).root();
+        document.invalidate_stylesheets();
     }
 }
 
@@ -145,6 +185,11 @@ impl Reflectable for HTMLLinkElement {
 }
 
 impl<'a> HTMLLinkElementMethods for JSRef<'a, HTMLLinkElement> {
+    // http://dev.w3.org/csswg/cssom/#dom-linkstyle-sheet
+    fn GetSheet(self) -> Option<Temporary<CSSStyleSheet>> {
+        self.sheet.get()
+    }
+
     fn RelList(self) -> Temporary<DOMTokenList> {
         if self.rel_list.get().is_none() {
             let element: JSRef<Element> = ElementCast::from_ref(self);
@@ -3,23 +3,28 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 use dom::bindings::codegen::Bindings::HTMLStyleElementBinding;
+use dom::bindings::codegen::Bindings::HTMLStyleElementBinding::HTMLStyleElementMethods;
 use dom::bindings::codegen::Bindings::NodeBinding::NodeMethods;
-use dom::bindings::codegen::InheritTypes::{HTMLElementCast, HTMLStyleElementDerived, NodeCast};
-use dom::bindings::js::{JSRef, Temporary};
+use dom::bindings::codegen::InheritTypes::{ElementCast, HTMLElementCast, HTMLStyleElementDerived};
+use dom::bindings::codegen::InheritTypes::NodeCast;
+use dom::bindings::js::{JSRef, MutNullableJS, OptionalSettable, Temporary};
 use dom::bindings::utils::{Reflectable, Reflector};
-use dom::document::Document;
-use dom::element::HTMLStyleElementTypeId;
+use dom::cssstylesheet::{CSSStyleSheet, ParsedStylesheet, has_imports, load_stylesheet};
+use dom::document::{Document, DocumentHelpers};
+use dom::element::{Element, HTMLStyleElementTypeId};
 use dom::eventtarget::{EventTarget, NodeTargetTypeId};
 use dom::htmlelement::HTMLElement;
-use dom::node::{Node, NodeHelpers, ElementNodeTypeId, window_from_node};
+use dom::node::{Node, NodeHelpers, ElementNodeTypeId, document_from_node, window_from_node};
 use dom::virtualmethods::VirtualMethods;
-use layout_interface::{AddStylesheetMsg, LayoutChan};
 use servo_util::str::DOMString;
 use style::{AuthorOrigin, Stylesheet};
 
+use std::default::Default;
+
 #[dom_struct]
 pub struct HTMLStyleElement {
     htmlelement: HTMLElement,
+    sheet: MutNullableJS<CSSStyleSheet>,
 }
 
 impl HTMLStyleElementDerived for EventTarget {
@@ -31,7 +36,8 @@ impl HTMLStyleElementDerived for EventTarget {
 impl HTMLStyleElement {
     fn new_inherited(localName: DOMString, prefix: Option<DOMString>, document: JSRef<Document>) -> HTMLStyleElement {
         HTMLStyleElement {
-            htmlelement: HTMLElement::new_inherited(HTMLStyleElementTypeId, localName, prefix, document)
+            htmlelement: HTMLElement::new_inherited(HTMLStyleElementTypeId, localName, prefix, document),
+            sheet: Default::default(),
         }
     }
 
@@ -55,9 +61,34 @@ impl<'a> StyleElementHelpers for JSRef<'a, HTMLStyleElement> {
         let url = win.page().get_url();
 
         let data = node.GetTextContent().expect("Element.textContent must be a string");
-        let sheet = Stylesheet::from_str(data.as_slice(), url, AuthorOrigin);
-        let LayoutChan(ref layout_chan) = win.page().layout_chan;
-        layout_chan.send(AddStylesheetMsg(sheet));
+        let mut stylesheet = Stylesheet::from_str(data.as_slice(), url, AuthorOrigin);
+        win.page().report_css_errors(stylesheet.take_errors());
+        let element: JSRef<Element> = ElementCast::from_ref(self);
+        if has_imports(&stylesheet) {
+            // The imported stylesheets are loaded without blocking the script task, and the
+            // stylesheet is set once they all are.
+            load_stylesheet(element, ParsedStylesheet(stylesheet));
+            return
+        }
+        let document = document_from_node(self).root();
+        document.cancel_stylesheet_load(element);
+        self.set_loaded_stylesheet(stylesheet);
+    }
+}
+
+pub trait HTMLStyleElementHelpers {
+    fn set_loaded_stylesheet(self, stylesheet: Stylesheet);
+}
+
+impl<'a> HTMLStyleElementHelpers for JSRef<'a, HTMLStyleElement> {
+    /// Takes the stylesheet of the element, once its imports are loaded, and sends it to layout.
+    fn set_loaded_stylesheet(self, stylesheet: Stylesheet) {
+        let win = window_from_node(self).root();
+        let node: JSRef<Node> = NodeCast::from_ref(self);
+        self.sheet.assign(Some(CSSStyleSheet::new(*win, node, None, stylesheet)));
+
+        let document = document_from_node(self).root();
+        document.invalidate_stylesheets();
     }
 }
 
@@ -89,6 +120,28 @@ impl<'a> VirtualMethods for JSRef<'a, HTMLStyleElement> {
             self.parse_own_css();
         }
     }
+
+    fn unbind_from_tree(&self, tree_in_doc: bool) {
+        match self.super_type() {
+            Some(ref s) => s.unbind_from_tree(tree_in_doc),
+            _ => ()
+        }
+
+        if tree_in_doc {
+            self.sheet.clear();
+            let document = document_from_node(*self).root();
+            let element: JSRef<Element> = ElementCast::from_ref(*self);
+            document.cancel_stylesheet_load(element);
+            document.invalidate_stylesheets();
+        }
+    }
+}
+
+impl<'a> HTMLStyleElementMethods for JSRef<'a, HTMLStyleElement> {
+    // http://dev.w3.org/csswg/cssom/#dom-linkstyle-sheet
+    fn GetSheet(self) -> Option<Temporary<CSSStyleSheet>> {
+        self.sheet.get()
+    }
 }
 
 impl Reflectable for HTMLStyleElement {
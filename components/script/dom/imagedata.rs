@@ -0,0 +1,101 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use dom::bindings::codegen::Bindings::ImageDataBinding;
+use dom::bindings::codegen::Bindings::ImageDataBinding::ImageDataMethods;
+use dom::bindings::global::GlobalRef;
+use dom::bindings::js::{JSRef, Temporary};
+use dom::bindings::utils::{Reflectable, Reflector, reflect_dom_object};
+
+use geom::size::Size2D;
+use js::jsapi::JSObject;
+use js::jsfriendapi::bindgen::{JS_NewUint8ClampedArray, JS_GetUint8ClampedArrayData};
+use libc::uint8_t;
+use std::ptr;
+use std::slice;
+
+#[dom_struct]
+pub struct ImageData {
+    reflector_: Reflector,
+    width: u32,
+    height: u32,
+    /// A `Uint8ClampedArray` of unpremultiplied RGBA pixels.
+    data: *mut JSObject,
+}
+
+impl ImageData {
+    fn new_inherited(width: u32, height: u32, data: Option<Vec<u8>>, global: GlobalRef)
+                     -> ImageData {
+        let cx = global.get_cx();
+        let length = width * height * 4;
+        let js_object = unsafe {
+            let js_object = JS_NewUint8ClampedArray(cx, length);
+            match data {
+                None => {}
+                Some(data) => {
+                    assert!(data.len() == length as uint);
+                    let js_object_data: *mut uint8_t = JS_GetUint8ClampedArrayData(js_object, cx);
+                    ptr::copy_nonoverlapping_memory(js_object_data, data.as_ptr(), data.len());
+                }
+            }
+            js_object
+        };
+
+        ImageData {
+            reflector_: Reflector::new(),
+            width: width,
+            height: height,
+            data: js_object,
+        }
+    }
+
+    /// Creates image data of the given size. If no pixels are given, they are all transparent
+    /// black.
+    pub fn new(global: GlobalRef, width: u32, height: u32, data: Option<Vec<u8>>)
+               -> Temporary<ImageData> {
+        reflect_dom_object(box ImageData::new_inherited(width, height, data, global),
+                           global,
+                           ImageDataBinding::Wrap)
+    }
+}
+
+pub trait ImageDataHelpers {
+    fn get_data_array(self, global: &GlobalRef) -> Vec<u8>;
+    fn get_size(self) -> Size2D<i32>;
+}
+
+impl<'a> ImageDataHelpers for JSRef<'a, ImageData> {
+    /// Returns a copy of the pixels, which may have been modified by script.
+    fn get_data_array(self, global: &GlobalRef) -> Vec<u8> {
+        let length = (self.width * self.height * 4) as uint;
+        unsafe {
+            let data = JS_GetUint8ClampedArrayData(self.data, global.get_cx()) as *const uint8_t;
+            slice::raw::buf_as_slice(data, length, |data| data.to_vec())
+        }
+    }
+
+    fn get_size(self) -> Size2D<i32> {
+        Size2D(self.width as i32, self.height as i32)
+    }
+}
+
+impl<'a> ImageDataMethods for JSRef<'a, ImageData> {
+    fn Width(self) -> u32 {
+        self.width
+    }
+
+    fn Height(self) -> u32 {
+        self.height
+    }
+
+    fn Data(self) -> *mut JSObject {
+        self.data
+    }
+}
+
+impl Reflectable for ImageData {
+    fn reflector<'a>(&'a self) -> &'a Reflector {
+        &self.reflector_
+    }
+}
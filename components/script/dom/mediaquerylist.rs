@@ -0,0 +1,120 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use dom::bindings::callback::ReportExceptions;
+use dom::bindings::cell::DOMRefCell;
+use dom::bindings::codegen::Bindings::MediaQueryListBinding;
+use dom::bindings::codegen::Bindings::MediaQueryListBinding::MediaQueryListListener;
+use dom::bindings::codegen::Bindings::MediaQueryListBinding::MediaQueryListMethods;
+use dom::bindings::global;
+use dom::bindings::js::{JS, JSRef, Temporary};
+use dom::bindings::utils::{Reflectable, Reflector, reflect_dom_object};
+use dom::window::{Window, WindowHelpers};
+
+use servo_util::str::DOMString;
+use style;
+
+use std::cell::Cell;
+
+// http://dev.w3.org/csswg/cssom-view/#the-mediaquerylist-interface
+#[dom_struct]
+pub struct MediaQueryList {
+    reflector_: Reflector,
+    window: JS<Window>,
+    media: DOMString,
+    media_queries: style::MediaQueryList,
+    /// Whether the media queries matched when they were last evaluated.
+    matches: Cell<bool>,
+    listeners: DOMRefCell<Vec<MediaQueryListListener>>,
+}
+
+impl MediaQueryList {
+    fn new_inherited(window: JSRef<Window>, media: DOMString) -> MediaQueryList {
+        let media_queries = style::parse_media_query_list_from_str(media.as_slice());
+        let matches = media_queries.evaluate(&window.device());
+        MediaQueryList {
+            reflector_: Reflector::new(),
+            window: JS::from_rooted(window),
+            media: media,
+            media_queries: media_queries,
+            matches: Cell::new(matches),
+            listeners: DOMRefCell::new(vec!()),
+        }
+    }
+
+    pub fn new(window: JSRef<Window>, media: DOMString) -> Temporary<MediaQueryList> {
+        reflect_dom_object(box MediaQueryList::new_inherited(window, media),
+                           global::Window(window),
+                           MediaQueryListBinding::Wrap)
+    }
+}
+
+pub trait MediaQueryListHelpers {
+    fn evaluate_and_notify(self);
+}
+
+impl<'a> MediaQueryListHelpers for JSRef<'a, MediaQueryList> {
+    /// Evaluates the media queries again, and calls the listeners if the result has changed.
+    // http://dev.w3.org/csswg/cssom-view/#evaluate-media-queries-and-report-changes
+    fn evaluate_and_notify(self) {
+        let window = self.window.root();
+        let matches = self.media_queries.evaluate(&window.device());
+        if matches == self.matches.get() {
+            return;
+        }
+        self.matches.set(matches);
+
+        // Listeners may add or remove listeners, which only takes effect the next time.
+        let listeners = self.listeners.borrow().clone();
+        for listener in listeners.iter() {
+            let _ = listener.Call_(self, self, ReportExceptions);
+        }
+    }
+}
+
+impl<'a> MediaQueryListMethods for JSRef<'a, MediaQueryList> {
+    fn Media(self) -> DOMString {
+        self.media.clone()
+    }
+
+    fn Matches(self) -> bool {
+        let window = self.window.root();
+        self.media_queries.evaluate(&window.device())
+    }
+
+    fn AddListener(self, listener: MediaQueryListListener) {
+        {
+            let mut listeners = self.listeners.borrow_mut();
+            if listeners.contains(&listener) {
+                return;
+            }
+            listeners.push(listener);
+        }
+        let window = self.window.root();
+        window.add_media_query_list(self);
+    }
+
+    fn RemoveListener(self, listener: MediaQueryListListener) {
+        let no_listeners = {
+            let mut listeners = self.listeners.borrow_mut();
+            match listeners.as_slice().position_elem(&listener) {
+                Some(position) => {
+                    listeners.remove(position);
+                }
+                None => return,
+            }
+            listeners.is_empty()
+        };
+        if no_listeners {
+            let window = self.window.root();
+            window.remove_media_query_list(self);
+        }
+    }
+}
+
+impl Reflectable for MediaQueryList {
+    fn reflector<'a>(&'a self) -> &'a Reflector {
+        &self.reflector_
+    }
+}
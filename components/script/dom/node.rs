@@ -124,7 +124,7 @@ impl NodeDerived for EventTarget {
 bitflags! {
     #[doc = "Flags for node items."]
     #[jstraceable]
-    flags NodeFlags: u8 {
+    flags NodeFlags: u16 {
         #[doc = "Specifies whether this node is in a document."]
         const IS_IN_DOC = 0x01,
         #[doc = "Specifies whether this node is in hover state."]
@@ -143,6 +143,16 @@ bitflags! {
         #[doc = "Specifies whether this node has descendants (inclusive of itself) which \
                  have changed since the last reflow."]
         const HAS_DIRTY_DESCENDANTS = 0x80,
+        #[doc = "Specifies whether this node is in checked state."]
+        const IN_CHECKED_STATE = 0x100,
+        #[doc = "Specifies whether this node is in indeterminate state."]
+        const IN_INDETERMINATE_STATE = 0x200,
+        #[doc = "Specifies whether this node is in active state."]
+        const IN_ACTIVE_STATE = 0x400,
+        #[doc = "Specifies whether this node is in focus state."]
+        const IN_FOCUS_STATE = 0x800,
+        #[doc = "Specifies whether this node is the target of the document's URL fragment."]
+        const IN_TARGET_STATE = 0x1000,
     }
 }
 
@@ -419,6 +429,21 @@ pub trait NodeHelpers<'a> {
     fn get_enabled_state(self) -> bool;
     fn set_enabled_state(self, state: bool);
 
+    fn get_checked_state(self) -> bool;
+    fn set_checked_state(self, state: bool);
+
+    fn get_indeterminate_state(self) -> bool;
+    fn set_indeterminate_state(self, state: bool);
+
+    fn get_active_state(self) -> bool;
+    fn set_active_state(self, state: bool);
+
+    fn get_focus_state(self) -> bool;
+    fn set_focus_state(self, state: bool);
+
+    fn get_target_state(self) -> bool;
+    fn set_target_state(self, state: bool);
+
     fn get_has_changed(self) -> bool;
     fn set_has_changed(self, state: bool);
 
@@ -584,6 +609,46 @@ impl<'a> NodeHelpers<'a> for JSRef<'a, Node> {
         self.set_flag(IN_ENABLED_STATE, state)
     }
 
+    fn get_checked_state(self) -> bool {
+        self.get_flag(IN_CHECKED_STATE)
+    }
+
+    fn set_checked_state(self, state: bool) {
+        self.set_flag(IN_CHECKED_STATE, state)
+    }
+
+    fn get_indeterminate_state(self) -> bool {
+        self.get_flag(IN_INDETERMINATE_STATE)
+    }
+
+    fn set_indeterminate_state(self, state: bool) {
+        self.set_flag(IN_INDETERMINATE_STATE, state)
+    }
+
+    fn get_active_state(self) -> bool {
+        self.get_flag(IN_ACTIVE_STATE)
+    }
+
+    fn set_active_state(self, state: bool) {
+        self.set_flag(IN_ACTIVE_STATE, state)
+    }
+
+    fn get_focus_state(self) -> bool {
+        self.get_flag(IN_FOCUS_STATE)
+    }
+
+    fn set_focus_state(self, state: bool) {
+        self.set_flag(IN_FOCUS_STATE, state)
+    }
+
+    fn get_target_state(self) -> bool {
+        self.get_flag(IN_TARGET_STATE)
+    }
+
+    fn set_target_state(self, state: bool) {
+        self.set_flag(IN_TARGET_STATE, state)
+    }
+
     fn get_has_changed(self) -> bool {
         self.get_flag(HAS_CHANGED)
     }
@@ -928,6 +993,11 @@ pub trait RawLayoutNodeHelpers {
     unsafe fn get_hover_state_for_layout(&self) -> bool;
     unsafe fn get_disabled_state_for_layout(&self) -> bool;
     unsafe fn get_enabled_state_for_layout(&self) -> bool;
+    unsafe fn get_checked_state_for_layout(&self) -> bool;
+    unsafe fn get_indeterminate_state_for_layout(&self) -> bool;
+    unsafe fn get_active_state_for_layout(&self) -> bool;
+    unsafe fn get_focus_state_for_layout(&self) -> bool;
+    unsafe fn get_target_state_for_layout(&self) -> bool;
     fn type_id_for_layout(&self) -> NodeTypeId;
 }
 
@@ -945,6 +1015,26 @@ impl RawLayoutNodeHelpers for Node {
         self.flags.get().contains(IN_ENABLED_STATE)
     }
     #[inline]
+    unsafe fn get_checked_state_for_layout(&self) -> bool {
+        self.flags.get().contains(IN_CHECKED_STATE)
+    }
+    #[inline]
+    unsafe fn get_indeterminate_state_for_layout(&self) -> bool {
+        self.flags.get().contains(IN_INDETERMINATE_STATE)
+    }
+    #[inline]
+    unsafe fn get_active_state_for_layout(&self) -> bool {
+        self.flags.get().contains(IN_ACTIVE_STATE)
+    }
+    #[inline]
+    unsafe fn get_focus_state_for_layout(&self) -> bool {
+        self.flags.get().contains(IN_FOCUS_STATE)
+    }
+    #[inline]
+    unsafe fn get_target_state_for_layout(&self) -> bool {
+        self.flags.get().contains(IN_TARGET_STATE)
+    }
+    #[inline]
     fn type_id_for_layout(&self) -> NodeTypeId {
         self.type_id
     }
@@ -2266,6 +2356,15 @@ impl<'a> style::TNode<'a, JSRef<'a, Element>> for JSRef<'a, Node> {
         elem.unwrap().html_element_in_html_document()
     }
 
+    fn is_empty(self) -> bool {
+        self.children().all(|kid| {
+            match TextCast::to_ref(kid) {
+                Some(text) => text.characterdata().data().is_empty(),
+                None => ElementCast::to_ref(kid).is_none(),
+            }
+        })
+    }
+
     fn has_changed(self) -> bool { self.get_has_changed() }
     unsafe fn set_changed(self, value: bool) { self.set_has_changed(value) }
 
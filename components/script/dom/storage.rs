@@ -7,9 +7,10 @@ use dom::bindings::codegen::Bindings::StorageBinding::StorageMethods;
 use dom::bindings::global::{GlobalRef, GlobalField};
 use dom::bindings::js::{JSRef, Temporary};
 use dom::bindings::utils::{Reflectable, Reflector, reflect_dom_object};
-use dom::bindings::error::Fallible;
+use dom::bindings::error::{ErrorResult, QuotaExceeded};
+use servo_msg::constellation_msg::{BroadcastStorageEventMsg, ConstellationChan};
 use servo_util::str::DOMString;
-use servo_net::storage_task::StorageTask;
+use servo_net::storage_task::{StorageTask, StorageType};
 use servo_net::storage_task::StorageTaskMsg;
 use std::comm::channel;
 use url::Url;
@@ -18,22 +19,24 @@ use url::Url;
 pub struct Storage {
     reflector_: Reflector,
     global: GlobalField,
+    storage_type: StorageType,
 }
 
 impl Storage {
-    fn new_inherited(global: &GlobalRef) -> Storage {
+    fn new_inherited(global: &GlobalRef, storage_type: StorageType) -> Storage {
         Storage {
             reflector_: Reflector::new(),
             global: GlobalField::from_rooted(global),
+            storage_type: storage_type,
         }
     }
 
-    pub fn new(global: &GlobalRef) -> Temporary<Storage> {
-        reflect_dom_object(box Storage::new_inherited(global), *global, StorageBinding::Wrap)
+    pub fn new(global: &GlobalRef, storage_type: StorageType) -> Temporary<Storage> {
+        reflect_dom_object(box Storage::new_inherited(global, storage_type), *global, StorageBinding::Wrap)
     }
 
-    pub fn Constructor(global: &GlobalRef) -> Fallible<Temporary<Storage>> {
-        Ok(Storage::new(global))
+    pub fn storage_type(&self) -> StorageType {
+        self.storage_type
     }
 
     fn get_url(&self) -> Url {
@@ -48,27 +51,38 @@ impl Storage {
         global_ref.as_window().storage_task()
     }
 
+    /// Asks the constellation to fire a `storage` event at the other documents that share this
+    /// storage area. https://html.spec.whatwg.org/multipage/webstorage.html#send-a-storage-notification
+    fn broadcast_change_notification(&self, key: Option<DOMString>, old_value: Option<DOMString>,
+                                     new_value: Option<DOMString>) {
+        let global_root = self.global.root();
+        let global_ref = global_root.root_ref();
+        let page = global_ref.as_window().page();
+        let ConstellationChan(ref chan) = page.constellation_chan;
+        chan.send(BroadcastStorageEventMsg(page.id, self.storage_type, self.get_url(),
+                                           key, old_value, new_value));
+    }
 }
 
 impl<'a> StorageMethods for JSRef<'a, Storage> {
     fn Length(self) -> u32 {
         let (sender, receiver) = channel();
 
-        self.get_storage_task().send(StorageTaskMsg::Length(sender, self.get_url()));
+        self.get_storage_task().send(StorageTaskMsg::Length(sender, self.get_url(), self.storage_type));
         receiver.recv()
     }
 
     fn Key(self, index: u32) -> Option<DOMString> {
         let (sender, receiver) = channel();
 
-        self.get_storage_task().send(StorageTaskMsg::Key(sender, self.get_url(), index));
+        self.get_storage_task().send(StorageTaskMsg::Key(sender, self.get_url(), self.storage_type, index));
         receiver.recv()
     }
 
     fn GetItem(self, name: DOMString) -> Option<DOMString> {
         let (sender, receiver) = channel();
 
-        self.get_storage_task().send(StorageTaskMsg::GetItem(sender, self.get_url(), name));
+        self.get_storage_task().send(StorageTaskMsg::GetItem(sender, self.get_url(), self.storage_type, name));
         receiver.recv()
     }
 
@@ -78,29 +92,38 @@ impl<'a> StorageMethods for JSRef<'a, Storage> {
         item
     }
 
-    fn SetItem(self, name: DOMString, value: DOMString) {
+    fn SetItem(self, name: DOMString, value: DOMString) -> ErrorResult {
         let (sender, receiver) = channel();
 
-        self.get_storage_task().send(StorageTaskMsg::SetItem(sender, self.get_url(), name, value));
-        if receiver.recv() {
-            //TODO send notification
+        self.get_storage_task().send(StorageTaskMsg::SetItem(sender, self.get_url(), self.storage_type,
+                                                             name.clone(), value.clone()));
+        match receiver.recv() {
+            Err(()) => Err(QuotaExceeded),
+            Ok((changed, old_value)) => {
+                if changed {
+                    self.broadcast_change_notification(Some(name), old_value, Some(value));
+                }
+                Ok(())
+            }
         }
     }
 
-    fn NamedSetter(self, name: DOMString, value: DOMString) {
-        self.SetItem(name, value);
+    fn NamedSetter(self, name: DOMString, value: DOMString) -> ErrorResult {
+        self.SetItem(name, value)
     }
 
-    fn NamedCreator(self, name: DOMString, value: DOMString) {
-        self.SetItem(name, value);
+    fn NamedCreator(self, name: DOMString, value: DOMString) -> ErrorResult {
+        self.SetItem(name, value)
     }
 
     fn RemoveItem(self, name: DOMString) {
         let (sender, receiver) = channel();
 
-        self.get_storage_task().send(StorageTaskMsg::RemoveItem(sender, self.get_url(), name));
-        if receiver.recv() {
-            //TODO send notification
+        self.get_storage_task().send(StorageTaskMsg::RemoveItem(sender, self.get_url(), self.storage_type,
+                                                                name.clone()));
+        match receiver.recv() {
+            Some(old_value) => self.broadcast_change_notification(Some(name), Some(old_value), None),
+            None => {}
         }
     }
 
@@ -111,9 +134,9 @@ impl<'a> StorageMethods for JSRef<'a, Storage> {
     fn Clear(self) {
         let (sender, receiver) = channel();
 
-        self.get_storage_task().send(StorageTaskMsg::Clear(sender, self.get_url()));
+        self.get_storage_task().send(StorageTaskMsg::Clear(sender, self.get_url(), self.storage_type));
         if receiver.recv() {
-            //TODO send notification
+            self.broadcast_change_notification(None, None, None);
         }
     }
 }
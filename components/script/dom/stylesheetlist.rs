@@ -0,0 +1,58 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use dom::bindings::codegen::Bindings::StyleSheetListBinding;
+use dom::bindings::codegen::Bindings::StyleSheetListBinding::StyleSheetListMethods;
+use dom::bindings::global;
+use dom::bindings::js::{JS, JSRef, Temporary};
+use dom::bindings::utils::{Reflectable, Reflector, reflect_dom_object};
+use dom::cssstylesheet::CSSStyleSheet;
+use dom::document::{Document, DocumentHelpers};
+use dom::window::Window;
+
+// http://dev.w3.org/csswg/cssom/#the-stylesheetlist-interface
+#[dom_struct]
+pub struct StyleSheetList {
+    reflector_: Reflector,
+    document: JS<Document>,
+}
+
+impl StyleSheetList {
+    fn new_inherited(document: JSRef<Document>) -> StyleSheetList {
+        StyleSheetList {
+            reflector_: Reflector::new(),
+            document: JS::from_rooted(document),
+        }
+    }
+
+    pub fn new(window: JSRef<Window>, document: JSRef<Document>) -> Temporary<StyleSheetList> {
+        reflect_dom_object(box StyleSheetList::new_inherited(document),
+                           global::Window(window),
+                           StyleSheetListBinding::Wrap)
+    }
+}
+
+impl<'a> StyleSheetListMethods for JSRef<'a, StyleSheetList> {
+    fn Length(self) -> u32 {
+        let document = self.document.root();
+        document.stylesheets().len() as u32
+    }
+
+    fn Item(self, index: u32) -> Option<Temporary<CSSStyleSheet>> {
+        let document = self.document.root();
+        document.stylesheets().into_iter().nth(index as uint)
+    }
+
+    fn IndexedGetter(self, index: u32, found: &mut bool) -> Option<Temporary<CSSStyleSheet>> {
+        let item = self.Item(index);
+        *found = item.is_some();
+        item
+    }
+}
+
+impl Reflectable for StyleSheetList {
+    fn reflector<'a>(&'a self) -> &'a Reflector {
+        &self.reflector_
+    }
+}
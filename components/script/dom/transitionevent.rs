@@ -0,0 +1,84 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use dom::bindings::codegen::Bindings::EventBinding::EventMethods;
+use dom::bindings::codegen::Bindings::TransitionEventBinding;
+use dom::bindings::codegen::Bindings::TransitionEventBinding::TransitionEventMethods;
+use dom::bindings::codegen::InheritTypes::{EventCast, TransitionEventDerived};
+use dom::bindings::error::Fallible;
+use dom::bindings::global::GlobalRef;
+use dom::bindings::js::{JSRef, Temporary};
+use dom::bindings::utils::{Reflectable, Reflector, reflect_dom_object};
+use dom::event::{Event, TransitionEventTypeId};
+use servo_util::str::DOMString;
+
+#[dom_struct]
+pub struct TransitionEvent {
+    event: Event,
+    property_name: DOMString,
+    elapsed_time: f32,
+    pseudo_element: DOMString,
+}
+
+impl TransitionEventDerived for Event {
+    fn is_transitionevent(&self) -> bool {
+        *self.type_id() == TransitionEventTypeId
+    }
+}
+
+impl TransitionEvent {
+    fn new_inherited(property_name: DOMString, elapsed_time: f32, pseudo_element: DOMString)
+                     -> TransitionEvent {
+        TransitionEvent {
+            event: Event::new_inherited(TransitionEventTypeId),
+            property_name: property_name,
+            elapsed_time: elapsed_time,
+            pseudo_element: pseudo_element,
+        }
+    }
+
+    pub fn new(global: GlobalRef, type_: DOMString,
+               can_bubble: bool, cancelable: bool,
+               property_name: DOMString, elapsed_time: f32,
+               pseudo_element: DOMString) -> Temporary<TransitionEvent> {
+        let ev = reflect_dom_object(box TransitionEvent::new_inherited(property_name,
+                                                                       elapsed_time,
+                                                                       pseudo_element),
+                                    global,
+                                    TransitionEventBinding::Wrap).root();
+        let event: JSRef<Event> = EventCast::from_ref(*ev);
+        event.InitEvent(type_, can_bubble, cancelable);
+        Temporary::from_rooted(*ev)
+    }
+
+    pub fn Constructor(global: &GlobalRef,
+                       type_: DOMString,
+                       init: &TransitionEventBinding::TransitionEventInit)
+                       -> Fallible<Temporary<TransitionEvent>> {
+        let ev = TransitionEvent::new(*global, type_, init.parent.bubbles,
+                                      init.parent.cancelable, init.propertyName.clone(),
+                                      init.elapsedTime, init.pseudoElement.clone());
+        Ok(ev)
+    }
+}
+
+impl<'a> TransitionEventMethods for JSRef<'a, TransitionEvent> {
+    fn PropertyName(self) -> DOMString {
+        self.property_name.clone()
+    }
+
+    fn ElapsedTime(self) -> f32 {
+        self.elapsed_time
+    }
+
+    fn PseudoElement(self) -> DOMString {
+        self.pseudo_element.clone()
+    }
+}
+
+impl Reflectable for TransitionEvent {
+    fn reflector<'a>(&'a self) -> &'a Reflector {
+        self.event.reflector()
+    }
+}
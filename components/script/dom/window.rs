@@ -10,18 +10,19 @@ use dom::bindings::codegen::Bindings::WindowBinding::WindowMethods;
 use dom::bindings::codegen::InheritTypes::EventTargetCast;
 use dom::bindings::error::{Fallible, InvalidCharacter};
 use dom::bindings::global;
-use dom::bindings::js::{MutNullableJS, JSRef, Temporary, OptionalSettable};
+use dom::bindings::js::{JS, MutNullableJS, JSRef, Temporary, OptionalSettable};
 use dom::bindings::utils::{Reflectable, Reflector};
 use dom::browsercontext::BrowserContext;
 use dom::console::Console;
 use dom::document::Document;
 use dom::eventtarget::{EventTarget, WindowTypeId, EventTargetHelpers};
 use dom::location::Location;
+use dom::mediaquerylist::{MediaQueryList, MediaQueryListHelpers};
 use dom::navigator::Navigator;
 use dom::performance::Performance;
 use dom::screen::Screen;
 use dom::storage::Storage;
-use layout_interface::NoQuery;
+use layout_interface::{NoQuery, device_for_window_size};
 use page::Page;
 use script_task::{ExitWindowMsg, ScriptChan, TriggerLoadMsg, TriggerFragmentMsg};
 use script_task::FromWindow;
@@ -29,10 +30,11 @@ use script_traits::ScriptControlChan;
 use timers::{Interval, NonInterval, TimerId, TimerManager};
 
 use servo_msg::compositor_msg::ScriptListener;
-use servo_msg::constellation_msg::LoadData;
+use servo_msg::constellation_msg::{FrameId, LoadData};
 use servo_net::image_cache_task::ImageCacheTask;
-use servo_net::storage_task::StorageTask;
+use servo_net::storage_task::{StorageTask, Session, Local};
 use servo_util::str::{DOMString,HTML_SPACE_CHARACTERS};
+use style::Device;
 
 use js::jsapi::JS_EvaluateUCScript;
 use js::jsapi::JSContext;
@@ -65,6 +67,9 @@ pub struct Window {
     navigation_start_precise: f64,
     screen: MutNullableJS<Screen>,
     session_storage: MutNullableJS<Storage>,
+    local_storage: MutNullableJS<Storage>,
+    /// The media query lists that have listeners, which are told when their result changes.
+    media_query_lists: DOMRefCell<Vec<JS<MediaQueryList>>>,
     timers: TimerManager
 }
 
@@ -217,12 +222,21 @@ impl<'a> WindowMethods for JSRef<'a, Window> {
 
     fn SessionStorage(self) -> Temporary<Storage> {
         if self.session_storage.get().is_none() {
-            let session_storage = Storage::new(&global::Window(self));
+            let FrameId(id) = self.page().frame_id;
+            let session_storage = Storage::new(&global::Window(self), Session(id));
             self.session_storage.assign(Some(session_storage));
         }
         self.session_storage.get().unwrap()
     }
 
+    fn LocalStorage(self) -> Temporary<Storage> {
+        if self.local_storage.get().is_none() {
+            let local_storage = Storage::new(&global::Window(self), Local);
+            self.local_storage.assign(Some(local_storage));
+        }
+        self.local_storage.get().unwrap()
+    }
+
     fn Console(self) -> Temporary<Console> {
         if self.console.get().is_none() {
             let console = Console::new(global::Window(self));
@@ -304,6 +318,11 @@ impl<'a> WindowMethods for JSRef<'a, Window> {
         self.screen.get().unwrap()
     }
 
+    // http://dev.w3.org/csswg/cssom-view/#dom-window-matchmedia
+    fn MatchMedia(self, query: DOMString) -> Temporary<MediaQueryList> {
+        MediaQueryList::new(self, query)
+    }
+
     fn Debug(self, message: DOMString) {
         debug!("{:s}", message);
     }
@@ -314,6 +333,13 @@ impl<'a> WindowMethods for JSRef<'a, Window> {
         }
     }
 
+    fn AdvanceAnimationClock(self, milliseconds: f64) {
+        let page = self.page();
+        page.virtual_animation_time.set(page.virtual_animation_time.get() + milliseconds / 1000.0);
+        page.damage();
+        page.flush_layout(NoQuery);
+    }
+
     fn Btoa(self, btoa: DOMString) -> Fallible<DOMString> {
         base64_btoa(btoa)
     }
@@ -338,6 +364,10 @@ pub trait WindowHelpers {
     fn handle_fire_timer(self, timer_id: TimerId);
     fn evaluate_js_with_result(self, code: &str) -> JSVal;
     fn evaluate_script_with_result(self, code: &str, filename: &str) -> JSVal;
+    fn device(self) -> Device;
+    fn add_media_query_list(self, list: JSRef<MediaQueryList>);
+    fn remove_media_query_list(self, list: JSRef<MediaQueryList>);
+    fn evaluate_media_queries(self);
 }
 
 
@@ -402,6 +432,39 @@ impl<'a> WindowHelpers for JSRef<'a, Window> {
         self.timers.fire_timer(timer_id, self.clone());
         self.flush_layout();
     }
+
+    /// The device that media queries are evaluated against, as layout sees it.
+    fn device(self) -> Device {
+        device_for_window_size(&self.page().window_size.get())
+    }
+
+    fn add_media_query_list(self, list: JSRef<MediaQueryList>) {
+        let list = JS::from_rooted(list);
+        let mut lists = self.media_query_lists.borrow_mut();
+        if !lists.contains(&list) {
+            lists.push(list);
+        }
+    }
+
+    fn remove_media_query_list(self, list: JSRef<MediaQueryList>) {
+        let list = JS::from_rooted(list);
+        let mut lists = self.media_query_lists.borrow_mut();
+        match lists.as_slice().position_elem(&list) {
+            Some(position) => {
+                lists.remove(position);
+            }
+            None => {}
+        }
+    }
+
+    /// Tells the listeners of every media query list whose result has changed.
+    fn evaluate_media_queries(self) {
+        // Listeners may add or remove lists while they run.
+        let lists = self.media_query_lists.borrow().clone();
+        for list in lists.iter() {
+            list.root().evaluate_and_notify();
+        }
+    }
 }
 
 impl Window {
@@ -428,6 +491,8 @@ impl Window {
             navigation_start_precise: time::precise_time_s(),
             screen: Default::default(),
             session_storage: Default::default(),
+            local_storage: Default::default(),
+            media_query_lists: DOMRefCell::new(vec!()),
             timers: TimerManager::new()
         };
 
@@ -45,7 +45,7 @@ use libc;
 use libc::c_void;
 
 use net::resource_task::{ResourceTask, ResourceCORSData, Load, LoadData, LoadResponse, Payload, Done};
-use cors::{allow_cross_origin_request, CORSRequest, CORSMode, ForcedPreflightMode};
+use cors::{CORSRequest, CORSMode, ForcedPreflightMode};
 use script_task::{ScriptChan, XHRProgressMsg, XHRReleaseMsg};
 use servo_util::str::DOMString;
 use servo_util::task::spawn_named;
@@ -206,7 +206,7 @@ impl XMLHttpRequest {
     }
 
     fn fetch(fetch_type: &SyncOrAsync, resource_task: ResourceTask,
-             mut load_data: LoadData, terminate_receiver: Receiver<TerminateReason>,
+             load_data: LoadData, terminate_receiver: Receiver<TerminateReason>,
              cors_request: Result<Option<CORSRequest>,()>, gen_id: GenerationId,
              start_port: Receiver<LoadResponse>) -> ErrorResult {
 
@@ -250,30 +250,6 @@ impl XMLHttpRequest {
                 notify_error_and_return!(Network);
             }
 
-            Ok(Some(ref req)) => {
-                let (chan, cors_port) = channel();
-                let req2 = req.clone();
-                // TODO: this exists only to make preflight check non-blocking
-                // perhaps shoud be handled by the resource_loader?
-                spawn_named("XHR:Cors", proc() {
-                    let response = req2.http_fetch();
-                    chan.send(response);
-                });
-
-                select! (
-                    response = cors_port.recv() => {
-                        if response.network_error {
-                            notify_error_and_return!(Network);
-                        } else {
-                            load_data.cors = Some(ResourceCORSData {
-                                preflight: req.preflight_flag,
-                                origin: req.origin.clone()
-                            });
-                        }
-                    },
-                    reason = terminate_receiver.recv() => terminate!(reason)
-                )
-            }
             _ => {}
         }
 
@@ -284,16 +260,10 @@ impl XMLHttpRequest {
         let progress_port;
         select! (
             response = start_port.recv() => {
-                match cors_request {
-                    Ok(Some(ref req)) => {
-                        match response.metadata.headers {
-                            Some(ref h) if allow_cross_origin_request(req, h) => {},
-                            _ => notify_error_and_return!(Network)
-                        }
-                    },
-
-                    _ => {}
-                };
+                // Network errors, including failed CORS checks, come back without a status
+                if response.metadata.status.is_none() {
+                    notify_error_and_return!(Network);
+                }
                 // XXXManishearth Clear cache entries in case of a network error
                 notify_partial_progress(fetch_type, HeadersReceivedMsg(gen_id,
                     response.metadata.headers.clone(), response.metadata.status.clone()));
@@ -598,8 +568,7 @@ impl<'a> XMLHttpRequestMethods for JSRef<'a, XMLHttpRequest> {
         } else {
             CORSMode
         };
-        let cors_request = CORSRequest::maybe_new(referer_url.clone(), load_data.url.clone(), mode,
-                                                  load_data.method.clone(), load_data.headers.clone());
+        let cors_request = CORSRequest::maybe_new(referer_url.clone(), load_data.url.clone(), mode);
         match cors_request {
             Ok(None) => {
                 let mut buf = String::new();
@@ -613,9 +582,16 @@ impl<'a> XMLHttpRequestMethods for JSRef<'a, XMLHttpRequest> {
                 referer_url.serialize_path().map(|ref h| buf.push_str(h.as_slice()));
                 self.request_headers.borrow_mut().referer = Some(buf);
             },
-            Ok(Some(ref req)) => self.insert_trusted_header("origin".to_string(),
-                                                            format!("{}", req.origin)),
-            _ => {}
+            Ok(Some(ref req)) => {
+                // The resource task makes the preflight request, if one is needed, and performs
+                // the CORS check on the response.
+                load_data.cors = Some(ResourceCORSData {
+                    preflight: req.mode == ForcedPreflightMode,
+                    origin: req.origin.clone(),
+                    credentials: self.with_credentials.get()
+                });
+            },
+            Err(()) => {}
         }
 
         let gen_id = self.generation_id.get();
@@ -1050,7 +1026,7 @@ impl<'a> PrivateXMLHttpRequestHelpers for JSRef<'a, XMLHttpRequest> {
         for header in self.response_headers.borrow().iter() {
             match header.header_name().as_slice().to_ascii_lower().as_slice() {
                 "set-cookie" | "set-cookie2" => {},
-                // XXXManishearth additional CORS filtering goes here
+                // CORS responses have already been filtered by the resource task
                 _ => headers.insert(header)
             };
         }
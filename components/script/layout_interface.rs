@@ -16,18 +16,16 @@ use servo_util::geometry::Au;
 use std::any::{Any, AnyRefExt};
 use std::comm::{channel, Receiver, Sender};
 use std::boxed::BoxAny;
-use style::Stylesheet;
+use style::{Device, Screen, Stylesheet};
+use sync::Arc;
 use url::Url;
 
 pub use dom::node::TrustedNodeAddress;
 
 /// Asynchronous messages that script can send to layout.
 pub enum Msg {
-    /// Adds the given stylesheet to the document.
-    AddStylesheetMsg(Stylesheet),
-
-    /// Adds the given stylesheet to the document.
-    LoadStylesheetMsg(Url),
+    /// Replaces the document's stylesheets with the given ones, in tree order.
+    SetStylesheetsMsg(Vec<Arc<Stylesheet>>),
 
     /// Requests a reflow.
     ReflowMsg(Box<Reflow>),
@@ -66,6 +64,8 @@ pub trait LayoutRPC {
     /// Requests the node containing the point of interest
     fn hit_test(&self, node: TrustedNodeAddress, point: Point2D<f32>) -> Result<HitTestResponse, ()>;
     fn mouse_over(&self, node: TrustedNodeAddress, point: Point2D<f32>) -> Result<MouseOverResponse, ()>;
+    /// Returns true if any web font of the document is loading.
+    fn web_fonts_loading(&self) -> bool;
 }
 
 pub struct ContentBoxResponse(pub Rect<Au>);
@@ -111,12 +111,24 @@ pub struct Reflow {
     pub query_type: ReflowQueryType,
     ///  A clipping rectangle for the page, an enlarged rectangle containing the viewport.
     pub page_clip_rect: Rect<Au>,
+    /// The time at which transitions and animations are computed, in seconds.
+    pub animation_time: f64,
 }
 
 /// Encapsulates a channel to the layout task.
 #[deriving(Clone)]
 pub struct LayoutChan(pub Sender<Msg>);
 
+/// The device that media queries are evaluated against in a window of the given size, both by
+/// layout and by `matchMedia`.
+// TODO: Calculate the "actual viewport":
+// http://www.w3.org/TR/css-device-adapt/#actual-viewport
+pub fn device_for_window_size(window_size: &WindowSizeData) -> Device {
+    // We don't know the size of the screen, so the window stands in for it.
+    Device::new(Screen, window_size.initial_viewport, window_size.initial_viewport,
+                window_size.device_pixel_ratio)
+}
+
 impl LayoutChan {
     pub fn new() -> (Receiver<Msg>, LayoutChan) {
         let (chan, port) = channel();
@@ -82,9 +82,12 @@ pub mod dom {
     #[path="bindings/codegen/InterfaceTypes.rs"]
     pub mod types;
 
+    pub mod animationevent;
     pub mod attr;
     pub mod blob;
     pub mod browsercontext;
+    pub mod canvasgradient;
+    pub mod canvaspattern;
     pub mod canvasrenderingcontext2d;
     pub mod characterdata;
     pub mod domrect;
@@ -93,6 +96,17 @@ pub mod dom {
     pub mod comment;
     pub mod console;
     mod create;
+    pub mod css;
+    pub mod cssfontfacerule;
+    pub mod cssimportrule;
+    pub mod csskeyframesrule;
+    pub mod cssmediarule;
+    pub mod cssrule;
+    pub mod cssrulelist;
+    pub mod cssstyledeclaration;
+    pub mod cssstylerule;
+    pub mod cssstylesheet;
+    pub mod csssupportsrule;
     pub mod customevent;
     pub mod dedicatedworkerglobalscope;
     pub mod document;
@@ -107,6 +121,7 @@ pub mod dom {
     pub mod eventdispatcher;
     pub mod eventtarget;
     pub mod file;
+    pub mod fontfaceset;
     pub mod formdata;
     pub mod htmlanchorelement;
     pub mod htmlappletelement;
@@ -178,8 +193,10 @@ pub mod dom {
     pub mod htmlulistelement;
     pub mod htmlvideoelement;
     pub mod htmlunknownelement;
+    pub mod imagedata;
     pub mod keyboardevent;
     pub mod location;
+    pub mod mediaquerylist;
     pub mod messageevent;
     pub mod mouseevent;
     pub mod namednodemap;
@@ -196,7 +213,10 @@ pub mod dom {
     pub mod screen;
     pub mod servohtmlparser;
     pub mod storage;
+    pub mod storageevent;
+    pub mod stylesheetlist;
     pub mod text;
+    pub mod transitionevent;
     pub mod treewalker;
     pub mod uievent;
     pub mod urlhelper;
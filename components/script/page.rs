@@ -19,23 +19,28 @@ use layout_interface::{
 };
 use script_traits::{UntrustedNodeAddress, ScriptControlChan};
 
+use devtools_traits::{DevtoolsControlChan, PageError, ReportPageError};
 use geom::{Point2D, Rect, Size2D};
 use js::rust::Cx;
 use servo_msg::compositor_msg::PerformingLayout;
 use servo_msg::compositor_msg::ScriptListener;
 use servo_msg::constellation_msg::{ConstellationChan, WindowSizeData};
-use servo_msg::constellation_msg::{PipelineId, SubpageId};
+use servo_msg::constellation_msg::{FrameId, PipelineId, SubpageId};
 use servo_net::resource_task::ResourceTask;
 use servo_net::storage_task::StorageTask;
 use servo_util::geometry::{Au, MAX_RECT};
 use servo_util::geometry;
+use servo_util::opts;
 use servo_util::str::DOMString;
 use servo_util::smallvec::{SmallVec1, SmallVec};
+use style::CSSError;
+use serialize::json;
 use std::cell::{Cell, Ref, RefMut};
 use std::comm::{channel, Receiver, Empty, Disconnected};
 use std::mem::replace;
 use std::num::abs;
 use std::rc::Rc;
+use time;
 use url::Url;
 
 /// Encapsulates a handle to a frame and its associated layout information.
@@ -47,6 +52,9 @@ pub struct Page {
     /// Subpage id associated with this page, if any.
     pub subpage_id: Option<SubpageId>,
 
+    /// Id of the top-level browsing context this page belongs to.
+    pub frame_id: FrameId,
+
     /// Unique id for last reflow request; used for confirming completion reply.
     pub last_reflow_id: Cell<uint>,
 
@@ -93,6 +101,9 @@ pub struct Page {
     /// A handle for communicating messages to the constellation task.
     pub constellation_chan: ConstellationChan,
 
+    /// A handle for reporting errors to the devtools server, if it is running.
+    pub devtools_chan: Option<DevtoolsControlChan>,
+
     // Child Pages.
     pub children: DOMRefCell<Vec<Rc<Page>>>,
 
@@ -108,6 +119,10 @@ pub struct Page {
     /// An enlarged rectangle around the page contents visible in the viewport, used
     /// to prevent creating display list items for content that is far away from the viewport.
     pub page_clip_rect: Cell<Rect<Au>>,
+
+    /// The time of the virtual animation clock in seconds, which only moves forward when
+    /// script calls `window.advanceAnimationClock()`.
+    pub virtual_animation_time: Cell<f64>,
 }
 
 pub struct PageIterator {
@@ -137,12 +152,13 @@ impl IterablePage for Rc<Page> {
 }
 
 impl Page {
-    pub fn new(id: PipelineId, subpage_id: Option<SubpageId>,
+    pub fn new(id: PipelineId, subpage_id: Option<SubpageId>, frame_id: FrameId,
            layout_chan: LayoutChan,
            window_size: WindowSizeData,
            resource_task: ResourceTask,
            storage_task: StorageTask,
            constellation_chan: ConstellationChan,
+           devtools_chan: Option<DevtoolsControlChan>,
            js_context: Rc<Cx>) -> Page {
         let js_info = JSPageInfo {
             dom_static: GlobalStaticData(),
@@ -157,6 +173,7 @@ impl Page {
         Page {
             id: id,
             subpage_id: subpage_id,
+            frame_id: frame_id,
             frame: DOMRefCell::new(None),
             layout_chan: layout_chan,
             layout_rpc: layout_rpc,
@@ -172,11 +189,36 @@ impl Page {
             resource_task: resource_task,
             storage_task: storage_task,
             constellation_chan: constellation_chan,
+            devtools_chan: devtools_chan,
             children: DOMRefCell::new(vec!()),
             damaged: Cell::new(false),
             pending_reflows: Cell::new(0),
             avoided_reflows: Cell::new(0),
             page_clip_rect: Cell::new(MAX_RECT),
+            virtual_animation_time: Cell::new(0.0),
+        }
+    }
+
+    /// Reports the errors found while parsing a stylesheet of the page to the devtools web
+    /// console, and prints them as JSON lines when running headless.
+    pub fn report_css_errors(&self, errors: Vec<CSSError>) {
+        for error in errors.into_iter() {
+            let page_error = PageError {
+                category: "CSS Parser".to_string(),
+                errorMessage: error.message,
+                sourceName: error.url,
+                lineText: error.text,
+                lineNumber: error.line,
+                columnNumber: error.column,
+                warning: true,
+            };
+            if opts::get().headless {
+                println!("{}", json::encode(&page_error));
+            }
+            match self.devtools_chan {
+                Some(ref chan) => chan.send(ReportPageError(self.id, page_error)),
+                None => {}
+            }
         }
     }
 
@@ -432,6 +474,7 @@ impl Page {
                     id: last_reflow_id.get(),
                     query_type: query_type,
                     page_clip_rect: self.page_clip_rect.get(),
+                    animation_time: self.animation_time(),
                 };
 
                 let LayoutChan(ref chan) = self.layout_chan;
@@ -442,6 +485,15 @@ impl Page {
         }
     }
 
+    /// The time at which layout computes transitions and animations, in seconds.
+    fn animation_time(&self) -> f64 {
+        if opts::get().virtual_animation_clock {
+            self.virtual_animation_time.get()
+        } else {
+            time::precise_time_s()
+        }
+    }
+
     pub fn damage(&self) {
         self.damaged.set(true);
     }
@@ -452,6 +504,12 @@ impl Page {
         document.find_fragment_node(fragid)
     }
 
+    /// Makes the given element the target of the document's URL fragment, for `:target`.
+    pub fn set_target_element(&self, element: Option<JSRef<Element>>) {
+        let document = self.frame().as_ref().unwrap().document.root();
+        document.set_target_element(element);
+    }
+
     pub fn hit_test(&self, point: &Point2D<f32>) -> Option<UntrustedNodeAddress> {
         let frame = self.frame();
         let document = frame.as_ref().unwrap().document.root();
@@ -18,7 +18,7 @@ use dom::bindings::conversions::{FromJSValConvertible, Empty};
 use dom::bindings::global;
 use dom::bindings::js::{JS, JSRef, RootCollection, Temporary, OptionalRootable};
 use dom::bindings::trace::JSTraceable;
-use dom::bindings::utils::{wrap_for_same_compartment, pre_wrap};
+use dom::bindings::utils::{Reflectable, wrap_for_same_compartment, pre_wrap};
 use dom::document::{Document, HTMLDocument, DocumentHelpers, FromParser};
 use dom::element::{Element, HTMLButtonElementTypeId, HTMLInputElementTypeId};
 use dom::element::{HTMLSelectElementTypeId, HTMLTextAreaElementTypeId, HTMLOptionElementTypeId};
@@ -26,6 +26,9 @@ use dom::event::{Event, Bubbles, DoesNotBubble, Cancelable, NotCancelable};
 use dom::uievent::UIEvent;
 use dom::eventtarget::{EventTarget, EventTargetHelpers};
 use dom::keyboardevent::KeyboardEvent;
+use dom::storageevent::StorageEvent;
+use dom::transitionevent::TransitionEvent;
+use dom::animationevent::AnimationEvent;
 use dom::node;
 use dom::node::{ElementNodeTypeId, Node, NodeHelpers};
 use dom::window::{Window, WindowHelpers};
@@ -46,20 +49,24 @@ use script_traits::{MouseMoveEvent, MouseUpEvent, ConstellationControlMsg, Scrip
 use script_traits::{ResizeMsg, AttachLayoutMsg, LoadMsg, ViewportMsg, SendEventMsg};
 use script_traits::{ResizeInactiveMsg, ExitPipelineMsg, NewLayoutInfo, OpaqueScriptLayoutChannel};
 use script_traits::{ScriptControlChan, ReflowCompleteMsg, UntrustedNodeAddress, KeyEvent};
+use script_traits::{DispatchStorageEventMsg, DispatchAnimationEventsMsg, AnimationEndEvent};
+use script_traits::{TransitionEnded, AnimationEnded};
 use servo_msg::compositor_msg::{FinishedLoading, LayerId, Loading};
 use servo_msg::compositor_msg::{ScriptListener};
 use servo_msg::constellation_msg::{ConstellationChan, LoadCompleteMsg, LoadUrlMsg, NavigationDirection};
 use servo_msg::constellation_msg::{LoadData, PipelineId, Failure, FailureMsg, WindowSizeData, Key, KeyState};
 use servo_msg::constellation_msg::{KeyModifiers, SUPER, SHIFT, CONTROL, ALT, Repeated, Pressed};
-use servo_msg::constellation_msg::{Released};
+use servo_msg::constellation_msg::{Released, FrameId};
 use servo_msg::constellation_msg;
 use servo_net::image_cache_task::ImageCacheTask;
 use servo_net::resource_task::ResourceTask;
-use servo_net::storage_task::StorageTask;
+use servo_net::fetch::request::serialize_origin;
+use servo_net::storage_task::{StorageTask, StorageType, Session, Local};
 use servo_util::geometry::to_frac_px;
 use servo_util::smallvec::{SmallVec1, SmallVec};
 use servo_util::task::spawn_named_with_send_on_failure;
 use servo_util::task_state;
+use style::{CSSError, Stylesheet};
 
 use geom::point::Point2D;
 use js::jsapi::{JS_SetWrapObjectCallbacks, JS_SetGCZeal, JS_DEFAULT_ZEAL_FREQ, JS_GC};
@@ -115,6 +122,9 @@ pub enum ScriptMsg {
     WorkerPostMessage(TrustedWorkerAddress, *mut u64, size_t),
     /// Releases one reference to the Worker object (dispatched to all tasks).
     WorkerRelease(TrustedWorkerAddress),
+    /// Hands the stylesheet of a load started by `load_stylesheet` to the document of the
+    /// specified pipeline, with the parse errors to report (only dispatched to ScriptTask).
+    StylesheetLoadedMsg(PipelineId, uint, Option<Stylesheet>, Vec<CSSError>),
 }
 
 /// Encapsulates internal communication within the script task.
@@ -187,7 +197,10 @@ pub struct ScriptTask {
     /// The JSContext.
     js_context: DOMRefCell<Option<Rc<Cx>>>,
 
-    mouse_over_targets: DOMRefCell<Option<Vec<JS<Node>>>>
+    mouse_over_targets: DOMRefCell<Option<Vec<JS<Node>>>>,
+
+    /// The elements put into the active state by the last mouse down, if any.
+    active_targets: DOMRefCell<Vec<JS<Node>>>,
 }
 
 /// In the event of task failure, all data on the stack runs its destructor. However, there
@@ -256,6 +269,7 @@ impl ScriptTaskFactory for ScriptTask {
 
     fn create<C>(_phantom: Option<&mut ScriptTask>,
                  id: PipelineId,
+                 frame_id: FrameId,
                  compositor: C,
                  layout_chan: &OpaqueScriptLayoutChannel,
                  control_chan: ScriptControlChan,
@@ -273,6 +287,7 @@ impl ScriptTaskFactory for ScriptTask {
         let layout_chan = LayoutChan(layout_chan.sender());
         spawn_named_with_send_on_failure("ScriptTask", task_state::SCRIPT, proc() {
             let script_task = ScriptTask::new(id,
+                                              frame_id,
                                               box compositor as Box<ScriptListener>,
                                               layout_chan,
                                               script_port,
@@ -305,6 +320,7 @@ unsafe extern "C" fn debug_gc_callback(_rt: *mut JSRuntime, status: JSGCStatus)
 impl ScriptTask {
     /// Creates a new script task.
     pub fn new(id: PipelineId,
+               frame_id: FrameId,
                compositor: Box<ScriptListener+'static>,
                layout_chan: LayoutChan,
                port: Receiver<ScriptMsg>,
@@ -334,10 +350,11 @@ impl ScriptTask {
                                       Some(pre_wrap));
         }
 
-        let page = Page::new(id, None, layout_chan, window_size,
+        let page = Page::new(id, None, frame_id, layout_chan, window_size,
                              resource_task.clone(),
                              storage_task,
                              constellation_chan.clone(),
+                             devtools_chan.clone(),
                              js_context.clone());
 
         // Notify devtools that a new script global exists.
@@ -364,7 +381,8 @@ impl ScriptTask {
 
             js_runtime: js_runtime,
             js_context: DOMRefCell::new(Some(js_context)),
-            mouse_over_targets: DOMRefCell::new(None)
+            mouse_over_targets: DOMRefCell::new(None),
+            active_targets: DOMRefCell::new(vec!()),
         }
     }
 
@@ -546,6 +564,10 @@ impl ScriptTask {
                 FromConstellation(ResizeInactiveMsg(id, new_size)) => self.handle_resize_inactive_msg(id, new_size),
                 FromConstellation(ExitPipelineMsg(id)) => if self.handle_exit_pipeline_msg(id) { return false },
                 FromConstellation(ViewportMsg(..)) => panic!("should have handled ViewportMsg already"),
+                FromConstellation(DispatchStorageEventMsg(id, storage_type, url, key, old_value, new_value)) =>
+                    self.handle_storage_event_msg(id, storage_type, url, key, old_value, new_value),
+                FromConstellation(DispatchAnimationEventsMsg(id, events)) =>
+                    self.handle_animation_events_msg(id, events),
                 FromScript(ExitWindowMsg(id)) => self.handle_exit_window_msg(id),
                 FromConstellation(ResizeMsg(..)) => panic!("should have handled ResizeMsg already"),
                 FromScript(XHRProgressMsg(addr, progress)) => XMLHttpRequest::handle_progress(addr, progress),
@@ -553,6 +575,8 @@ impl ScriptTask {
                 FromScript(DOMMessage(..)) => panic!("unexpected message"),
                 FromScript(WorkerPostMessage(addr, data, nbytes)) => Worker::handle_message(addr, data, nbytes),
                 FromScript(WorkerRelease(addr)) => Worker::handle_release(addr),
+                FromScript(StylesheetLoadedMsg(id, load_id, stylesheet, errors)) =>
+                    self.handle_stylesheet_loaded_msg(id, load_id, stylesheet, errors),
                 FromDevtools(EvaluateJS(id, s, reply)) => self.handle_evaluate_js(id, s, reply),
                 FromDevtools(GetRootNode(id, reply)) => self.handle_get_root_node(id, reply),
                 FromDevtools(GetDocumentElement(id, reply)) => self.handle_get_document_element(id, reply),
@@ -653,12 +677,13 @@ impl ScriptTask {
             task's page tree. This is a bug.");
         let new_page = {
             let window_size = parent_page.window_size.get();
-            Page::new(new_pipeline_id, Some(subpage_id),
+            Page::new(new_pipeline_id, Some(subpage_id), parent_page.frame_id,
                       LayoutChan(layout_chan.downcast_ref::<Sender<layout_interface::Msg>>().unwrap().clone()),
                       window_size,
                       parent_page.resource_task.clone(),
                       parent_page.storage_task.clone(),
                       self.constellation_chan.clone(),
+                      parent_page.devtools_chan.clone(),
                       self.js_context.borrow().as_ref().unwrap().clone())
         };
         parent_page.children.borrow_mut().push(Rc::new(new_page));
@@ -674,6 +699,128 @@ impl ScriptTask {
         window.handle_fire_timer(timer_id);
     }
 
+    /// Hands a loaded stylesheet to the element that started loading it, unless the page has
+    /// gone away or changed documents since.
+    fn handle_stylesheet_loaded_msg(&self, id: PipelineId, load_id: uint,
+                                    stylesheet: Option<Stylesheet>, errors: Vec<CSSError>) {
+        let page = self.page.borrow_mut();
+        let page = match page.find(id) {
+            Some(page) => page,
+            None => return,
+        };
+        page.report_css_errors(errors);
+
+        let document = match *page.frame() {
+            Some(ref frame) => Temporary::new(frame.document.clone()),
+            None => return,
+        };
+        let document = document.root();
+        let failed = stylesheet.is_none();
+        document.finish_stylesheet_load(load_id, stylesheet);
+
+        // A stylesheet that loads is reflowed before the fonts it asks for are checked, but one
+        // that fails to load may have been all the font face set was waiting for.
+        if failed && !document.has_pending_stylesheet_loads() {
+            document.update_font_status();
+        }
+
+        // The stylesheets that are in the document before it is parsed delay its "load" event.
+        if document.ReadyState() == DocumentReadyStateValues::Interactive &&
+                !document.has_pending_stylesheet_loads() {
+            self.finish_load(&*page);
+        }
+    }
+
+    /// Fires a `storage` event at the window of the given pipeline if its document shares the
+    /// storage area that was changed by the document at `url`.
+    /// https://html.spec.whatwg.org/multipage/webstorage.html#send-a-storage-notification
+    fn handle_storage_event_msg(&self, id: PipelineId, storage_type: StorageType, url: Url,
+                                key: Option<String>, old_value: Option<String>,
+                                new_value: Option<String>) {
+        let page = self.page.borrow_mut();
+        let page = match page.find(id) {
+            Some(page) => page,
+            None => return,
+        };
+        if serialize_origin(&page.get_url()) != serialize_origin(&url) {
+            return;
+        }
+
+        let frame = page.frame();
+        let window = match *frame {
+            Some(ref frame) => frame.window.root(),
+            None => return,
+        };
+        let FrameId(frame_id) = page.frame_id;
+        let storage_area = match storage_type {
+            // Other top-level browsing contexts have session storage areas of their own.
+            Session(session_id) if session_id != frame_id => return,
+            Session(_) => window.SessionStorage(),
+            Local => window.LocalStorage(),
+        }.root();
+        let event = StorageEvent::new(global::Window(*window), "storage".to_string(),
+                                      false, false, key, old_value, new_value,
+                                      url.serialize(), Some(*storage_area)).root();
+        let event: JSRef<Event> = EventCast::from_ref(*event);
+        let wintarget: JSRef<EventTarget> = EventTargetCast::from_ref(*window);
+        let _ = wintarget.DispatchEvent(event);
+    }
+
+    /// Fires the `transitionend` and `animationend` events of the transitions and animations
+    /// that layout saw finish.
+    fn handle_animation_events_msg(&self, id: PipelineId, events: Vec<AnimationEndEvent>) {
+        let page = self.page.borrow_mut();
+        let page = match page.find(id) {
+            Some(page) => page,
+            None => return,
+        };
+        let (document, window) = {
+            let frame = page.frame();
+            match *frame {
+                Some(ref frame) => {
+                    (Temporary::new(frame.document.clone()), Temporary::new(frame.window.clone()))
+                }
+                None => return,
+            }
+        };
+        let (document, window) = (document.root(), window.root());
+        let root: JSRef<Node> = NodeCast::from_ref(*document);
+
+        for animation_event in events.into_iter() {
+            let (node_address, event) = match animation_event {
+                TransitionEnded(node_address, property_name, elapsed_time) => {
+                    let event = TransitionEvent::new(global::Window(*window),
+                                                     "transitionend".to_string(),
+                                                     true, true, property_name,
+                                                     elapsed_time as f32, String::new());
+                    (node_address, EventCast::from_temporary(event))
+                }
+                AnimationEnded(node_address, animation_name, elapsed_time) => {
+                    let event = AnimationEvent::new(global::Window(*window),
+                                                    "animationend".to_string(),
+                                                    true, false, animation_name,
+                                                    elapsed_time as f32, String::new());
+                    (node_address, EventCast::from_temporary(event))
+                }
+            };
+            let event: Temporary<Event> = event;
+            let event = event.root();
+
+            // The node may have left the document since layout saw the animation end, so it is
+            // looked up again rather than trusting the address.
+            let node = root.traverse_preorder().find(|node| {
+                node.reflector().get_jsobject() as UntrustedNodeAddress == node_address
+            });
+            match node {
+                Some(node) => {
+                    let target: JSRef<EventTarget> = EventTargetCast::from_ref(node);
+                    let _ = target.DispatchEvent(*event);
+                }
+                None => {}
+            }
+        }
+    }
+
     /// Handles a notification that reflow completed.
     fn handle_reflow_complete_msg(&self, pipeline_id: PipelineId, reflow_id: uint) {
         debug!("Script: Reflow {} complete for {}", reflow_id, pipeline_id);
@@ -683,8 +830,19 @@ impl ScriptTask {
              with this script task. This is a bug.");
         let last_reflow_id = page.last_reflow_id.get();
         if last_reflow_id == reflow_id {
-            let mut layout_join_port = page.layout_join_port.borrow_mut();
-            *layout_join_port = None;
+            {
+                let mut layout_join_port = page.layout_join_port.borrow_mut();
+                *layout_join_port = None;
+            }
+
+            // Web fonts that have loaded are reported once layout has used them.
+            let document = page.frame().as_ref().map(|frame| {
+                Temporary::new(frame.document.clone())
+            });
+            match document.root() {
+                Some(document) => document.update_font_status(),
+                None => {}
+            }
         }
 
         self.compositor.borrow_mut().set_ready_state(pipeline_id, FinishedLoading);
@@ -851,16 +1009,31 @@ impl ScriptTask {
 
         // We have no concept of a document loader right now, so just dispatch the
         // "load" event as soon as we've finished executing all scripts parsed during
-        // the initial load.
+        // the initial load, and loading the stylesheets.
+        if !document.has_pending_stylesheet_loads() {
+            self.finish_load(&*page);
+        }
+    }
+
+    /// Fires the "load" event of the page's document, once everything that delays it has loaded.
+    fn finish_load(&self, page: &Page) {
+        let (document, window) = {
+            let frame = page.frame();
+            let frame = frame.as_ref().unwrap();
+            (Temporary::new(frame.document.clone()), Temporary::new(frame.window.clone()))
+        };
+        let document = document.root();
+        let window = window.root();
 
         // https://html.spec.whatwg.org/multipage/#the-end step 7
         document.set_ready_state(DocumentReadyStateValues::Complete);
 
         let event = Event::new(global::Window(*window), "load".to_string(), DoesNotBubble, NotCancelable).root();
+        let doctarget: JSRef<EventTarget> = EventTargetCast::from_ref(*document);
         let wintarget: JSRef<EventTarget> = EventTargetCast::from_ref(*window);
         let _ = wintarget.dispatch_event_with_target(Some(doctarget), *event);
 
-        *page.fragment_name.borrow_mut() = url.fragment;
+        *page.fragment_name.borrow_mut() = page.get_url().fragment.clone();
 
         let ConstellationChan(ref chan) = self.constellation_chan;
         chan.send(LoadCompleteMsg);
@@ -914,8 +1087,12 @@ impl ScriptTask {
               self.handle_click_event(pipeline_id, _button, point);
             }
 
-            MouseDownEvent(..) => {}
-            MouseUpEvent(..) => {}
+            MouseDownEvent(_button, point) => {
+              self.handle_mouse_down_event(pipeline_id, point);
+            }
+            MouseUpEvent(..) => {
+              self.handle_mouse_up_event(pipeline_id);
+            }
             MouseMoveEvent(point) => {
               self.handle_mouse_move_event(pipeline_id, point);
             }
@@ -993,7 +1170,9 @@ impl ScriptTask {
     /// for the given pipeline.
     fn trigger_fragment(&self, pipeline_id: PipelineId, url: Url) {
         let page = get_page(&*self.page.borrow(), pipeline_id);
-        match page.find_fragment_node(url.fragment.unwrap()).root() {
+        let fragment_node = page.find_fragment_node(url.fragment.unwrap()).root();
+        page.set_target_element(fragment_node.as_ref().map(|node| **node));
+        match fragment_node {
             Some(node) => {
                 self.scroll_fragment_point(pipeline_id, *node);
             }
@@ -1018,6 +1197,9 @@ impl ScriptTask {
                     .take()
                     .and_then(|name| page.find_fragment_node(name))
                     .root();
+            if fragment_node.is_some() {
+                page.set_target_element(fragment_node.as_ref().map(|node| **node));
+            }
             match fragment_node {
                 Some(node) => self.scroll_fragment_point(pipeline_id, *node),
                 None => {}
@@ -1038,6 +1220,8 @@ impl ScriptTask {
 
                 let wintarget: JSRef<EventTarget> = EventTargetCast::from_ref(*window);
                 let _ = wintarget.dispatch_event_with_target(None, event);
+
+                window.evaluate_media_queries();
             }
             None => ()
         }
@@ -1107,6 +1291,52 @@ impl ScriptTask {
         }
     }
 
+    /// Puts the element under the mouse and all of its ancestors into the active state.
+    fn handle_mouse_down_event(&self, pipeline_id: PipelineId, point: Point2D<f32>) {
+        let page = get_page(&*self.page.borrow(), pipeline_id);
+        match page.hit_test(&point) {
+            Some(node_address) => {
+                let temp_node =
+                        node::from_untrusted_node_address(
+                            self.js_runtime.ptr, node_address).root();
+
+                let maybe_node = if !temp_node.is_element() {
+                    temp_node.ancestors().find(|node| node.is_element())
+                } else {
+                    Some(*temp_node)
+                };
+
+                match maybe_node {
+                    Some(node) => {
+                        let mut active_targets = self.active_targets.borrow_mut();
+                        node.set_active_state(true);
+                        active_targets.push(JS::from_rooted(node));
+                        for ancestor in node.ancestors().filter(|node| node.is_element()) {
+                            ancestor.set_active_state(true);
+                            active_targets.push(JS::from_rooted(ancestor));
+                        }
+                    }
+                    None => return,
+                }
+                self.force_reflow(&*page);
+            }
+
+            None => {}
+        }
+    }
+
+    /// Takes the elements activated by the last mouse down out of the active state.
+    fn handle_mouse_up_event(&self, pipeline_id: PipelineId) {
+        let page = get_page(&*self.page.borrow(), pipeline_id);
+        let active_targets = replace(&mut *self.active_targets.borrow_mut(), vec!());
+        if active_targets.is_empty() {
+            return
+        }
+        for node in active_targets.iter() {
+            node.root().set_active_state(false);
+        }
+        self.force_reflow(&*page);
+    }
 
     fn handle_mouse_move_event(&self, pipeline_id: PipelineId, point: Point2D<f32>) {
         let page = get_page(&*self.page.borrow(), pipeline_id);
@@ -25,12 +25,13 @@ extern crate serialize;
 use devtools_traits::DevtoolsControlChan;
 use libc::c_void;
 use servo_msg::constellation_msg::{ConstellationChan, PipelineId, Failure, WindowSizeData};
-use servo_msg::constellation_msg::{LoadData, SubpageId, Key, KeyState, KeyModifiers};
+use servo_msg::constellation_msg::{LoadData, SubpageId, FrameId, Key, KeyState, KeyModifiers};
 use servo_msg::compositor_msg::ScriptListener;
 use servo_net::image_cache_task::ImageCacheTask;
 use servo_net::resource_task::ResourceTask;
-use servo_net::storage_task::StorageTask;
+use servo_net::storage_task::{StorageTask, StorageType};
 use servo_util::smallvec::SmallVec1;
+use url::Url;
 use std::any::Any;
 
 use geom::point::Point2D;
@@ -66,6 +67,20 @@ pub enum ConstellationControlMsg {
     /// Notifies script that reflow is finished.
     ReflowCompleteMsg(PipelineId, uint),
     ViewportMsg(PipelineId, Rect<f32>),
+    /// Fires a `storage` event at the window of the given pipeline if its document has the same
+    /// origin as the URL. Carries the key, old value and new value of the change.
+    DispatchStorageEventMsg(PipelineId, StorageType, Url, Option<String>, Option<String>, Option<String>),
+    /// Fires the `transitionend` and `animationend` events of the transitions and animations
+    /// that layout saw finish.
+    DispatchAnimationEventsMsg(PipelineId, Vec<AnimationEndEvent>),
+}
+
+/// A transition or animation that finished, with its elapsed time in seconds.
+pub enum AnimationEndEvent {
+    /// The node and the name of the transitioned property.
+    TransitionEnded(UntrustedNodeAddress, String, f64),
+    /// The node and the name of the animation.
+    AnimationEnded(UntrustedNodeAddress, String, f64),
 }
 
 /// Events from the compositor that the script task needs to know about
@@ -96,6 +111,7 @@ impl<S: Encoder<E>, E> Encodable<S, E> for ScriptControlChan {
 pub trait ScriptTaskFactory {
     fn create<C>(_phantom: Option<&mut Self>,
                  id: PipelineId,
+                 frame_id: FrameId,
                  compositor: C,
                  layout_chan: &OpaqueScriptLayoutChannel,
                  control_chan: ScriptControlChan,
@@ -42,6 +42,8 @@ use script::dom::bindings::codegen::RegisterBindings;
 #[cfg(not(test))]
 use servo_net::image_cache_task::ImageCacheTask;
 #[cfg(not(test))]
+use servo_net::http_cache::new_http_cache_task;
+#[cfg(not(test))]
 use servo_net::resource_task::new_resource_task;
 #[cfg(not(test))]
 use servo_net::storage_task::StorageTaskFactory;
@@ -97,6 +99,7 @@ impl<Window> Browser<Window> where Window: WindowMethods + 'static {
 
         let opts_clone = opts.clone();
         let time_profiler_chan_clone = time_profiler_chan.clone();
+        let memory_profiler_chan_clone = memory_profiler_chan.clone();
 
         let (result_chan, result_port) = channel();
         let compositor_proxy_for_constellation = compositor_proxy.clone_compositor_proxy();
@@ -105,17 +108,24 @@ impl<Window> Browser<Window> where Window: WindowMethods + 'static {
             .spawn(proc() {
             let opts = &opts_clone;
             // Create a Servo instance.
-            let resource_task = new_resource_task(opts.user_agent.clone());
+            let http_cache_task = opts.http_cache_dir.as_ref().map(|dir| {
+                new_http_cache_task(Path::new(dir.as_slice()), opts.http_cache_size)
+            });
+            let resource_task = new_resource_task(opts.user_agent.clone(), http_cache_task);
             // If we are emitting an output file, then we need to block on
             // image load or we risk emitting an output file missing the
             // image.
             let image_cache_task = if opts.output_file.is_some() {
-                ImageCacheTask::new_sync(resource_task.clone(), shared_task_pool)
+                ImageCacheTask::new_sync(resource_task.clone(), shared_task_pool,
+                                         memory_profiler_chan_clone, opts.image_cache_size)
             } else {
-                ImageCacheTask::new(resource_task.clone(), shared_task_pool)
+                ImageCacheTask::new(resource_task.clone(), shared_task_pool,
+                                    memory_profiler_chan_clone, opts.image_cache_size)
             };
             let font_cache_task = FontCacheTask::new(resource_task.clone());
-            let storage_task = StorageTaskFactory::new();
+            let storage_task = StorageTaskFactory::new(opts.profile_dir.as_ref().map(|dir| {
+                Path::new(dir.as_slice())
+            }));
             let constellation_chan = Constellation::<layout::layout_task::LayoutTask,
                                                      script::script_task::ScriptTask>::start(
                                                           compositor_proxy_for_constellation,
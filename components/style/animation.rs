@@ -0,0 +1,283 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! How computed values change over the course of transitions and animations: timing functions
+//! and the interpolation of animatable values.
+
+use cssparser::{RGBA, RGBAColor};
+use properties::common_types::computed::{CSSColor, TimingFunction, CubicBezierTimingFunction};
+use properties::common_types::computed::{StepsTimingFunction, JumpStart, JumpEnd};
+use properties::common_types::computed::{LengthOrPercentage, LP_Length, LP_Percentage};
+use properties::common_types::computed::{LengthOrPercentageOrAuto, LPA_Length, LPA_Percentage};
+use properties::common_types::computed::{LengthOrPercentageOrNone, LPN_Length, LPN_Percentage};
+use properties::CSSFloat;
+use servo_util::geometry::Au;
+
+impl TimingFunction {
+    /// Maps the progress of a transition or animation, between 0 and 1, to the progress of the
+    /// value, which may go beyond these bounds for some Bézier curves.
+    pub fn solve(&self, progress: CSSFloat) -> CSSFloat {
+        match *self {
+            CubicBezierTimingFunction(x1, y1, x2, y2) => {
+                Bezier::new(x1, y1, x2, y2).solve(progress, 1e-6)
+            }
+            StepsTimingFunction(steps, position) => {
+                let steps = steps as CSSFloat;
+                let step = match position {
+                    JumpStart => (progress * steps).ceil(),
+                    JumpEnd => (progress * steps).floor(),
+                };
+                (step / steps).max(0.0).min(1.0)
+            }
+        }
+    }
+}
+
+/// A cubic Bézier curve from (0, 0) to (1, 1), in polynomial form.
+struct Bezier {
+    ax: CSSFloat,
+    bx: CSSFloat,
+    cx: CSSFloat,
+    ay: CSSFloat,
+    by: CSSFloat,
+    cy: CSSFloat,
+}
+
+impl Bezier {
+    fn new(x1: CSSFloat, y1: CSSFloat, x2: CSSFloat, y2: CSSFloat) -> Bezier {
+        let cx = 3.0 * x1;
+        let bx = 3.0 * (x2 - x1) - cx;
+        let cy = 3.0 * y1;
+        let by = 3.0 * (y2 - y1) - cy;
+        Bezier {
+            ax: 1.0 - cx - bx,
+            bx: bx,
+            cx: cx,
+            ay: 1.0 - cy - by,
+            by: by,
+            cy: cy,
+        }
+    }
+
+    fn sample_x(&self, t: CSSFloat) -> CSSFloat {
+        ((self.ax * t + self.bx) * t + self.cx) * t
+    }
+
+    fn sample_y(&self, t: CSSFloat) -> CSSFloat {
+        ((self.ay * t + self.by) * t + self.cy) * t
+    }
+
+    fn sample_x_derivative(&self, t: CSSFloat) -> CSSFloat {
+        (3.0 * self.ax * t + 2.0 * self.bx) * t + self.cx
+    }
+
+    /// Finds the parameter for which the curve is at `x`: a few iterations of Newton's method,
+    /// which converges quickly for most curves, then bisection if that failed.
+    fn solve_x(&self, x: CSSFloat, epsilon: CSSFloat) -> CSSFloat {
+        let mut t = x;
+        for _ in range(0u, 8) {
+            let error = self.sample_x(t) - x;
+            if error.abs() < epsilon {
+                return t
+            }
+            let derivative = self.sample_x_derivative(t);
+            if derivative.abs() < 1e-6 {
+                break
+            }
+            t -= error / derivative;
+        }
+
+        let (mut low, mut high) = (0.0, 1.0);
+        t = x;
+        if t < low {
+            return low
+        }
+        if t > high {
+            return high
+        }
+        while low < high {
+            let value = self.sample_x(t);
+            if (value - x).abs() < epsilon {
+                return t
+            }
+            if x > value {
+                low = t
+            } else {
+                high = t
+            }
+            t = (high - low) / 2.0 + low;
+            if high - low < epsilon {
+                break
+            }
+        }
+        t
+    }
+
+    fn solve(&self, x: CSSFloat, epsilon: CSSFloat) -> CSSFloat {
+        self.sample_y(self.solve_x(x, epsilon))
+    }
+}
+
+/// A computed value that can be interpolated, as in CSS-TRANSITIONS § 5. `progress` is usually
+/// between 0 and 1 but may go beyond with some timing functions.
+pub trait Interpolate {
+    /// Returns `None` if the two values can't be interpolated, like a length and a percentage.
+    fn interpolate(&self, other: &Self, progress: CSSFloat) -> Option<Self>;
+}
+
+impl Interpolate for CSSFloat {
+    #[inline]
+    fn interpolate(&self, other: &CSSFloat, progress: CSSFloat) -> Option<CSSFloat> {
+        Some(*self + (*other - *self) * progress)
+    }
+}
+
+impl Interpolate for f32 {
+    #[inline]
+    fn interpolate(&self, other: &f32, progress: CSSFloat) -> Option<f32> {
+        Some(*self + (*other - *self) * (progress as f32))
+    }
+}
+
+impl Interpolate for Au {
+    #[inline]
+    fn interpolate(&self, other: &Au, progress: CSSFloat) -> Option<Au> {
+        let (Au(from), Au(to)) = (*self, *other);
+        let value = (from as CSSFloat).interpolate(&(to as CSSFloat), progress).unwrap();
+        Some(Au(value.round() as i32))
+    }
+}
+
+impl Interpolate for RGBA {
+    /// Colors are interpolated in premultiplied RGBA space, so that transparent colors don't
+    /// darken the ones they change to or from.
+    fn interpolate(&self, other: &RGBA, progress: CSSFloat) -> Option<RGBA> {
+        let alpha = clamp_unit(self.alpha.interpolate(&other.alpha, progress).unwrap());
+        if alpha == 0.0 {
+            return Some(RGBA { red: 0.0, green: 0.0, blue: 0.0, alpha: 0.0 })
+        }
+        let channel = |from: f32, to: f32| {
+            let from = from * self.alpha;
+            let to = to * other.alpha;
+            clamp_unit(from.interpolate(&to, progress).unwrap() / alpha)
+        };
+        Some(RGBA {
+            red: channel(self.red, other.red),
+            green: channel(self.green, other.green),
+            blue: channel(self.blue, other.blue),
+            alpha: alpha,
+        })
+    }
+}
+
+fn clamp_unit(value: f32) -> f32 {
+    value.max(0.0).min(1.0)
+}
+
+impl Interpolate for CSSColor {
+    /// `currentColor` changes with the `color` property, so only actual colors are interpolated.
+    fn interpolate(&self, other: &CSSColor, progress: CSSFloat) -> Option<CSSColor> {
+        match (self, other) {
+            (&RGBAColor(ref from), &RGBAColor(ref to)) => {
+                from.interpolate(to, progress).map(RGBAColor)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Interpolate for LengthOrPercentage {
+    fn interpolate(&self, other: &LengthOrPercentage, progress: CSSFloat)
+                   -> Option<LengthOrPercentage> {
+        match (self, other) {
+            (&LP_Length(ref from), &LP_Length(ref to)) => {
+                from.interpolate(to, progress).map(LP_Length)
+            }
+            (&LP_Percentage(ref from), &LP_Percentage(ref to)) => {
+                from.interpolate(to, progress).map(LP_Percentage)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Interpolate for LengthOrPercentageOrAuto {
+    fn interpolate(&self, other: &LengthOrPercentageOrAuto, progress: CSSFloat)
+                   -> Option<LengthOrPercentageOrAuto> {
+        match (self, other) {
+            (&LPA_Length(ref from), &LPA_Length(ref to)) => {
+                from.interpolate(to, progress).map(LPA_Length)
+            }
+            (&LPA_Percentage(ref from), &LPA_Percentage(ref to)) => {
+                from.interpolate(to, progress).map(LPA_Percentage)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl Interpolate for LengthOrPercentageOrNone {
+    fn interpolate(&self, other: &LengthOrPercentageOrNone, progress: CSSFloat)
+                   -> Option<LengthOrPercentageOrNone> {
+        match (self, other) {
+            (&LPN_Length(ref from), &LPN_Length(ref to)) => {
+                from.interpolate(to, progress).map(LPN_Length)
+            }
+            (&LPN_Percentage(ref from), &LPN_Percentage(ref to)) => {
+                from.interpolate(to, progress).map(LPN_Percentage)
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Interpolate;
+    use cssparser::{RGBA, RGBAColor, CurrentColor};
+    use properties::common_types::computed::{TimingFunction, CubicBezierTimingFunction};
+    use properties::common_types::computed::{StepsTimingFunction, JumpStart, JumpEnd};
+    use properties::common_types::computed::{LPA_Length, LPA_Percentage, LPA_Auto};
+    use servo_util::geometry::Au;
+
+    #[test]
+    fn test_timing_functions() {
+        let linear = CubicBezierTimingFunction(0.0, 0.0, 1.0, 1.0);
+        assert_eq!(linear.solve(0.0), 0.0);
+        assert!((linear.solve(0.3) - 0.3).abs() < 1e-5);
+        assert_eq!(linear.solve(1.0), 1.0);
+
+        let ease = TimingFunction::ease();
+        assert!(ease.solve(0.0).abs() < 1e-5);
+        assert!((ease.solve(0.5) - 0.8024).abs() < 1e-3);
+        assert!((ease.solve(1.0) - 1.0).abs() < 1e-5);
+
+        let steps = StepsTimingFunction(4, JumpEnd);
+        assert_eq!(steps.solve(0.0), 0.0);
+        assert_eq!(steps.solve(0.3), 0.25);
+        assert_eq!(steps.solve(1.0), 1.0);
+        assert_eq!(StepsTimingFunction(2, JumpStart).solve(0.1), 0.5);
+    }
+
+    #[test]
+    fn test_interpolate() {
+        assert_eq!(Au(0).interpolate(&Au(100), 0.25), Some(Au(25)));
+        assert_eq!(LPA_Length(Au(100)).interpolate(&LPA_Length(Au(0)), 0.5),
+                   Some(LPA_Length(Au(50))));
+        assert_eq!(LPA_Percentage(0.5).interpolate(&LPA_Percentage(1.0), 0.5),
+                   Some(LPA_Percentage(0.75)));
+        assert_eq!(LPA_Length(Au(100)).interpolate(&LPA_Auto, 0.5), None);
+        assert_eq!(LPA_Length(Au(100)).interpolate(&LPA_Percentage(1.0), 0.5), None);
+
+        let red = RGBA { red: 1.0, green: 0.0, blue: 0.0, alpha: 1.0 };
+        let blue = RGBA { red: 0.0, green: 0.0, blue: 1.0, alpha: 1.0 };
+        let transparent = RGBA { red: 0.0, green: 0.0, blue: 0.0, alpha: 0.0 };
+        assert_eq!(red.interpolate(&blue, 0.5),
+                   Some(RGBA { red: 0.5, green: 0.0, blue: 0.5, alpha: 1.0 }));
+        // Premultiplied, so that the color doesn't turn black while it fades out.
+        assert_eq!(red.interpolate(&transparent, 0.5),
+                   Some(RGBA { red: 1.0, green: 0.0, blue: 0.0, alpha: 0.5 }));
+        assert!(RGBAColor(red).interpolate(&CurrentColor, 0.5).is_none());
+    }
+}
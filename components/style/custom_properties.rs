@@ -0,0 +1,388 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! Custom properties (`--*`) and the `var()` functions that refer to them.
+//! http://dev.w3.org/csswg/css-variables/
+
+use std::ascii::AsciiExt;
+use std::collections::{HashMap, HashSet};
+use sync::Arc;
+
+use cssparser::ast::*;
+
+use parsing_utils::trim_whitespace;
+use properties::{CustomDeclaration, Initial, Inherit};
+use properties;
+use selector_matching::DeclarationBlock;
+
+
+/// The computed values of the custom properties of an element, by name. A custom property that
+/// is missing has the initial, invalid, value.
+pub type ComputedValuesMap = HashMap<String, Vec<ComponentValue>>;
+
+
+#[deriving(Clone)]
+pub struct SpecifiedValue {
+    /// The value as it was written, without leading and trailing whitespace.
+    pub value: Vec<ComponentValue>,
+    /// The custom properties that `var()` functions in the value refer to, in their fallbacks
+    /// too.
+    references: HashSet<String>,
+}
+
+
+/// Whether the property name is that of a custom property.
+pub fn is_custom_property_name(name: &str) -> bool {
+    name.len() > 2 && name.starts_with("--")
+}
+
+/// Parses the value of a custom property, which may be almost any list of tokens.
+pub fn parse(input: &[ComponentValue]) -> Result<SpecifiedValue, ()> {
+    let value = trim_whitespace(input);
+    if value.is_empty() {
+        return Err(())
+    }
+    let mut references = HashSet::new();
+    try!(parse_references(value, &mut references));
+    Ok(SpecifiedValue {
+        value: value.to_vec(),
+        references: references,
+    })
+}
+
+/// Whether the value contains `var()` functions, that need to be substituted before the value can
+/// be parsed for a property.
+pub fn has_references(input: &[ComponentValue]) -> bool {
+    input.iter().any(has_references_in)
+}
+
+fn has_references_in(component_value: &ComponentValue) -> bool {
+    match *component_value {
+        Function(ref name, _) if name.as_slice().eq_ignore_ascii_case("var") => true,
+        Function(_, ref arguments) |
+        ParenthesisBlock(ref arguments) |
+        SquareBracketBlock(ref arguments) => has_references(arguments.as_slice()),
+        CurlyBracketBlock(ref nodes) => {
+            nodes.iter().any(|&(ref component_value, _)| has_references_in(component_value))
+        }
+        _ => false,
+    }
+}
+
+/// Checks that the `var()` functions in the value are well-formed.
+pub fn check_references(input: &[ComponentValue]) -> Result<(), ()> {
+    parse_references(input, &mut HashSet::new())
+}
+
+/// Checks that the `var()` functions in the value are well-formed, and collects the names of
+/// the custom properties they refer to.
+fn parse_references(input: &[ComponentValue], references: &mut HashSet<String>)
+                        -> Result<(), ()> {
+    for component_value in input.iter() {
+        try!(parse_references_in(component_value, references));
+    }
+    Ok(())
+}
+
+fn parse_references_in(component_value: &ComponentValue, references: &mut HashSet<String>)
+                       -> Result<(), ()> {
+    match *component_value {
+        Function(ref name, ref arguments) if name.as_slice().eq_ignore_ascii_case("var") => {
+            let (name, fallback) = try!(parse_var_function(arguments.as_slice()));
+            references.insert(name.to_string());
+            match fallback {
+                Some(fallback) => parse_references(fallback, references),
+                None => Ok(()),
+            }
+        }
+        Function(_, ref arguments) |
+        ParenthesisBlock(ref arguments) |
+        SquareBracketBlock(ref arguments) => parse_references(arguments.as_slice(), references),
+        CurlyBracketBlock(ref nodes) => {
+            for &(ref component_value, _) in nodes.iter() {
+                try!(parse_references_in(component_value, references));
+            }
+            Ok(())
+        }
+        BadURL | BadString | CloseParenthesis | CloseSquareBracket | CloseCurlyBracket => Err(()),
+        _ => Ok(()),
+    }
+}
+
+/// Parses the arguments of `var()`: the name of a custom property, and optionally a comma and a
+/// fallback value, which may be empty.
+fn parse_var_function<'a>(arguments: &'a [ComponentValue])
+                          -> Result<(&'a str, Option<&'a [ComponentValue]>), ()> {
+    let mut iter = arguments.iter().enumerate().filter(|&(_, value)| *value != WhiteSpace);
+    let name = match iter.next() {
+        Some((_, &Ident(ref name))) if is_custom_property_name(name.as_slice()) => name.as_slice(),
+        _ => return Err(()),
+    };
+    match iter.next() {
+        None => Ok((name, None)),
+        Some((index, &Comma)) => Ok((name, Some(trim_whitespace(arguments.slice_from(index + 1))))),
+        Some(_) => Err(()),
+    }
+}
+
+
+/// Computes the custom properties of an element from the declarations that apply to it, in
+/// increasing precedence order, and from the ones it inherits. Custom properties are always
+/// inherited.
+pub fn cascade(applicable_declarations: &[DeclarationBlock],
+               inherited: &Option<Arc<ComputedValuesMap>>)
+               -> Option<Arc<ComputedValuesMap>> {
+    let has_custom_declarations = applicable_declarations.iter().any(|sub_list| {
+        sub_list.declarations.iter().any(|declaration| {
+            match *declaration {
+                CustomDeclaration(..) => true,
+                _ => false,
+            }
+        })
+    });
+    if !has_custom_declarations {
+        return inherited.clone()
+    }
+
+    let mut computed = match *inherited {
+        Some(ref inherited) => (**inherited).clone(),
+        None => HashMap::new(),
+    };
+    let mut seen = HashSet::new();
+    let mut specified = HashMap::new();
+    // Declaration blocks are stored in increasing precedence order,
+    // we want them in decreasing order here.
+    for sub_list in applicable_declarations.iter().rev() {
+        // Declarations are already stored in reverse order.
+        for declaration in sub_list.declarations.iter() {
+            match *declaration {
+                CustomDeclaration(ref name, ref declared_value) => {
+                    if !seen.insert(name.as_slice()) {
+                        continue
+                    }
+                    match *declared_value {
+                        properties::SpecifiedValue(ref value) => {
+                            specified.insert(name.as_slice(), value);
+                        }
+                        Initial => {
+                            computed.remove(name);
+                        }
+                        Inherit => {}
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // Custom properties that depend on themselves through `var()` are invalid. Properties that
+    // refer to them may still use a fallback.
+    let in_cycles: HashSet<&str> = specified.keys().map(|name| *name).filter(|name| {
+        is_in_cycle(*name, &specified)
+    }).collect();
+    let mut resolved = HashSet::new();
+    for name in specified.keys() {
+        resolve(*name, &specified, &in_cycles, &mut resolved, &mut computed);
+    }
+    Some(Arc::new(computed))
+}
+
+/// Whether the custom property refers back to itself, directly or through others.
+fn is_in_cycle(name: &str, specified: &HashMap<&str, &SpecifiedValue>) -> bool {
+    let mut visited = HashSet::new();
+    let value = *specified.get(&name).unwrap();
+    let mut to_visit: Vec<&str> = value.references.iter().map(|reference| {
+        reference.as_slice()
+    }).collect();
+    loop {
+        let current = match to_visit.pop() {
+            Some(current) => current,
+            None => return false,
+        };
+        if current == name {
+            return true
+        }
+        if !visited.insert(current) {
+            continue
+        }
+        match specified.get(&current) {
+            Some(value) => to_visit.extend(value.references.iter().map(|reference| {
+                reference.as_slice()
+            })),
+            None => {}
+        }
+    }
+}
+
+/// Computes the value of a specified custom property, after the ones it refers to.
+fn resolve<'a>(name: &'a str,
+               specified: &HashMap<&'a str, &'a SpecifiedValue>,
+               in_cycles: &HashSet<&'a str>,
+               resolved: &mut HashSet<&'a str>,
+               computed: &mut ComputedValuesMap) {
+    if !resolved.insert(name) {
+        return
+    }
+    if in_cycles.contains(&name) {
+        computed.remove(&name.to_string());
+        return
+    }
+    let value = *specified.get(&name).unwrap();
+    for reference in value.references.iter() {
+        if specified.contains_key(&reference.as_slice()) {
+            resolve(reference.as_slice(), specified, in_cycles, resolved, computed);
+        }
+    }
+    let result = if value.references.is_empty() {
+        Ok(value.value.clone())
+    } else {
+        substitute(value.value.as_slice(), Some(&*computed))
+    };
+    match result {
+        Ok(result) => {
+            computed.insert(name.to_string(), result);
+        }
+        Err(()) => {
+            computed.remove(&name.to_string());
+        }
+    }
+}
+
+
+/// Replaces the `var()` functions in the value with the custom properties they refer to, or with
+/// their fallback. Fails when a custom property is invalid and there is no fallback, which makes
+/// the declaration invalid at computed-value time.
+pub fn substitute(input: &[ComponentValue], custom_properties: Option<&ComputedValuesMap>)
+                  -> Result<Vec<ComponentValue>, ()> {
+    let mut result = vec!();
+    for component_value in input.iter() {
+        try!(substitute_in(component_value, custom_properties, &mut result));
+    }
+    Ok(result)
+}
+
+fn substitute_in(component_value: &ComponentValue,
+                 custom_properties: Option<&ComputedValuesMap>,
+                 result: &mut Vec<ComponentValue>)
+                 -> Result<(), ()> {
+    match *component_value {
+        Function(ref name, ref arguments) if name.as_slice().eq_ignore_ascii_case("var") => {
+            let (name, fallback) = try!(parse_var_function(arguments.as_slice()));
+            match custom_properties.and_then(|map| map.get(&name.to_string())) {
+                Some(value) => result.push_all(value.as_slice()),
+                None => match fallback {
+                    Some(fallback) => result.push_all(
+                        try!(substitute(fallback, custom_properties)).as_slice()),
+                    None => return Err(()),
+                },
+            }
+        }
+        Function(ref name, ref arguments) => result.push(
+            Function(name.clone(), try!(substitute(arguments.as_slice(), custom_properties)))),
+        ParenthesisBlock(ref arguments) => result.push(
+            ParenthesisBlock(try!(substitute(arguments.as_slice(), custom_properties)))),
+        SquareBracketBlock(ref arguments) => result.push(
+            SquareBracketBlock(try!(substitute(arguments.as_slice(), custom_properties)))),
+        CurlyBracketBlock(ref nodes) => {
+            let mut block = vec!();
+            for &(ref component_value, ref location) in nodes.iter() {
+                let mut substituted = vec!();
+                try!(substitute_in(component_value, custom_properties, &mut substituted));
+                block.extend(substituted.into_iter().map(|value| (value, location.clone())));
+            }
+            result.push(CurlyBracketBlock(block))
+        }
+        ref component_value => result.push(component_value.clone()),
+    }
+    Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use sync::Arc;
+    use cssparser::{tokenize, ToCss};
+    use cssparser::ast::ComponentValue;
+    use properties::parse_style_attribute;
+    use selector_matching::DeclarationBlock;
+    use url::Url;
+    use super::{ComputedValuesMap, cascade, has_references, parse, substitute};
+
+    fn tokens(input: &str) -> Vec<ComponentValue> {
+        tokenize(input).map(|(token, _)| token).collect()
+    }
+
+    fn compute(declarations: &str, inherited: &Option<Arc<ComputedValuesMap>>)
+               -> Option<Arc<ComputedValuesMap>> {
+        let url = Url::parse("http://localhost").unwrap();
+        let block = parse_style_attribute(declarations, &url);
+        cascade(&[DeclarationBlock::from_declarations(block.normal)], inherited)
+    }
+
+    fn get(computed: &Option<Arc<ComputedValuesMap>>, name: &str) -> Option<String> {
+        computed.as_ref().unwrap().get(&name.to_string()).map(|value| value.iter().to_css())
+    }
+
+    #[test]
+    fn test_parse() {
+        assert!(parse(tokens(" 10px ").as_slice()).unwrap().value == tokens("10px"));
+        assert!(parse(tokens("{ a: b } [c] (d)").as_slice()).is_ok());
+        assert!(parse(tokens("var(--a, var(--b,))").as_slice()).is_ok());
+        assert!(parse(tokens("  ").as_slice()).is_err());
+        assert!(parse(tokens("var(a)").as_slice()).is_err());
+        assert!(parse(tokens("var(--a b)").as_slice()).is_err());
+        assert!(parse(tokens("var()").as_slice()).is_err());
+        assert!(parse(tokens("a )").as_slice()).is_err());
+        assert!(has_references(tokens("calc(1px + var(--a))").as_slice()));
+        assert!(!has_references(tokens("calc(1px + 2px)").as_slice()));
+    }
+
+    #[test]
+    fn test_substitute() {
+        let mut map = HashMap::new();
+        map.insert("--a".to_string(), tokens("1px"));
+        let substituted = |input: &str| {
+            substitute(tokens(input).as_slice(), Some(&map)).map(|value| value.iter().to_css())
+        };
+        assert_eq!(substituted("var(--a) solid"), Ok("1px solid".to_string()));
+        assert_eq!(substituted("f(var(--a))"), Ok("f(1px)".to_string()));
+        assert_eq!(substituted("var(--b, 2px)"), Ok("2px".to_string()));
+        assert_eq!(substituted("var(--b, var(--a))"), Ok("1px".to_string()));
+        assert_eq!(substituted("var(--b)"), Err(()));
+        assert_eq!(substituted("var(--b, var(--c))"), Err(()));
+    }
+
+    #[test]
+    fn test_cascade() {
+        let parent = compute("--a: 1px; --b: red; --c: x", &None);
+        assert_eq!(get(&parent, "--a"), Some("1px".to_string()));
+
+        let child = compute("--b: var(--a) var(--d, 3px); --c: initial; --e: var(--f)", &parent);
+        assert_eq!(get(&child, "--a"), Some("1px".to_string()));
+        assert_eq!(get(&child, "--b"), Some("1px 3px".to_string()));
+        assert_eq!(get(&child, "--c"), None);
+        assert_eq!(get(&child, "--e"), None);
+
+        // The last declaration wins.
+        let child = compute("--a: 2px; --a: 3px", &parent);
+        assert_eq!(get(&child, "--a"), Some("3px".to_string()));
+
+        // Names are case-sensitive.
+        let child = compute("--A: 2px", &parent);
+        assert_eq!(get(&child, "--a"), Some("1px".to_string()));
+        assert_eq!(get(&child, "--A"), Some("2px".to_string()));
+    }
+
+    #[test]
+    fn test_cycles() {
+        let computed = compute("--a: var(--b); --b: var(--a, 1px); --c: var(--c); \
+                                --d: var(--a, 2px); --e: var(--d)", &None);
+        assert_eq!(get(&computed, "--a"), None);
+        assert_eq!(get(&computed, "--b"), None);
+        assert_eq!(get(&computed, "--c"), None);
+        assert_eq!(get(&computed, "--d"), Some("2px".to_string()));
+        assert_eq!(get(&computed, "--e"), Some("2px".to_string()));
+    }
+}
@@ -4,6 +4,8 @@
 
 
 use cssparser::ast::{SyntaxError, SourceLocation};
+use std::cell::RefCell;
+use std::mem;
 
 
 pub struct ErrorLoggerIterator<I>(pub I);
@@ -23,10 +25,46 @@ impl<T, I: Iterator<Result<T, SyntaxError>>> Iterator<T> for ErrorLoggerIterator
 }
 
 
-/// Defaults to a no-op.
+/// A CSS parse error, with what the web console needs to show it.
+#[deriving(Clone)]
+pub struct CSSError {
+    /// The URL of the stylesheet.
+    pub url: String,
+    pub line: uint,
+    pub column: uint,
+    pub message: String,
+    /// The line of the stylesheet the error is on.
+    pub text: String,
+}
+
+
+/// The errors logged by the parser, while `collect_css_errors` is running.
+local_data_key!(collected_errors: RefCell<Vec<(uint, uint, String)>>)
+
+
+/// Runs `parse`, and returns its result along with the line, column and message of each error
+/// it logged.
+pub fn collect_css_errors<T>(parse: || -> T) -> (T, Vec<(uint, uint, String)>) {
+    let previous = collected_errors.replace(Some(RefCell::new(vec!())));
+    let result = parse();
+    let errors = match collected_errors.get() {
+        Some(errors) => mem::replace(&mut *errors.borrow_mut(), vec!()),
+        None => vec!(),
+    };
+    collected_errors.replace(previous);
+    (result, errors)
+}
+
+
 /// Set a `RUST_LOG=style::errors` environment variable
 /// to log CSS parse errors to stderr.
+/// Errors in stylesheets are also reported to the web console, see `collect_css_errors`.
 pub fn log_css_error(location: SourceLocation, message: &str) {
-    // TODO eventually this will got into a "web console" or something.
-    info!("{:u}:{:u} {:s}", location.line, location.column, message)
+    info!("{:u}:{:u} {:s}", location.line, location.column, message);
+    match collected_errors.get() {
+        Some(errors) => {
+            errors.borrow_mut().push((location.line, location.column, message.to_string()))
+        }
+        None => {}
+    }
 }
@@ -6,46 +6,88 @@ use cssparser::ast::*;
 use cssparser::parse_declaration_list;
 use errors::{ErrorLoggerIterator, log_css_error};
 use std::ascii::AsciiExt;
+use std::cmp::min;
 use parsing_utils::{BufferedIter, ParserIter, parse_slice_comma_separated};
 use properties::longhands::font_family::parse_one_family;
 use properties::computed_values::font_family::FamilyName;
-use stylesheets::{CSSRule, CSSFontFaceRule, CSSStyleRule, CSSMediaRule};
+use stylesheets::{CSSRule, CSSFontFaceRule, CSSImportRule, CSSStyleRule, CSSMediaRule};
+use stylesheets::{CSSSupportsRule, CSSKeyframesRule};
 use media_queries::Device;
 use url::{Url, UrlParser};
 
 
 pub fn iter_font_face_rules_inner(rules: &[CSSRule], device: &Device,
-                                    callback: |family: &str, source: &Source|) {
+                                  callback: |rule: &FontFaceRule|) {
     for rule in rules.iter() {
         match *rule {
-            CSSStyleRule(_) => {},
+            CSSImportRule(ref rule) => if rule.media_queries.evaluate(device) {
+                match rule.stylesheet {
+                    Some(ref stylesheet) => {
+                        iter_font_face_rules_inner(stylesheet.rules(), device,
+                                                   |r| callback(r))
+                    }
+                    None => {}
+                }
+            },
+            CSSStyleRule(_) | CSSKeyframesRule(_) => {},
             CSSMediaRule(ref rule) => if rule.media_queries.evaluate(device) {
-                iter_font_face_rules_inner(rule.rules.as_slice(), device, |f, s| callback(f, s))
+                iter_font_face_rules_inner(rule.rules.as_slice(), device, |r| callback(r))
             },
-            CSSFontFaceRule(ref rule) => {
-                for source in rule.sources.iter() {
-                    callback(rule.family.as_slice(), source)
-                }
+            CSSSupportsRule(ref rule) => if rule.enabled {
+                iter_font_face_rules_inner(rule.rules.as_slice(), device, |r| callback(r))
             },
+            CSSFontFaceRule(ref rule) => callback(rule),
         }
     }
 }
 
-#[deriving(Clone)]
+#[deriving(Clone, PartialEq)]
 pub enum Source {
     UrlSource_(UrlSource),
     LocalSource(String),
 }
 
-#[deriving(Clone)]
+#[deriving(Clone, PartialEq)]
 pub struct UrlSource {
     pub url: Url,
     pub format_hints: Vec<String>,
 }
 
+/// A range of code points of a `unicode-range` descriptor, both ends included.
+#[deriving(Clone, PartialEq, Show)]
+pub struct CodepointRange {
+    pub start: u32,
+    pub end: u32,
+}
+
+/// The largest code point, and the end of the initial value of `unicode-range`.
+pub static MAX_CODEPOINT: u32 = 0x10ffff;
+
+/// How text is shown while the font of a `@font-face` rule is loading: see
+/// http://tabatkins.github.io/specs/css-font-display/
+#[deriving(Clone, PartialEq, Show)]
+pub enum FontDisplay {
+    AutoFontDisplay,
+    BlockFontDisplay,
+    SwapFontDisplay,
+    FallbackFontDisplay,
+    OptionalFontDisplay,
+}
+
+#[deriving(Clone, PartialEq)]
 pub struct FontFaceRule {
     pub family: String,
     pub sources: Vec<Source>,
+    pub unicode_range: Vec<CodepointRange>,
+    pub display: FontDisplay,
+}
+
+impl FontFaceRule {
+    /// Whether the font of this rule is to be used for the given character.
+    pub fn covers(&self, codepoint: char) -> bool {
+        let codepoint = codepoint as u32;
+        self.unicode_range.iter().any(|range| range.start <= codepoint && codepoint <= range.end)
+    }
 }
 
 pub fn parse_font_face_rule(rule: AtRule, parent_rules: &mut Vec<CSSRule>, base_url: &Url) {
@@ -64,6 +106,11 @@ pub fn parse_font_face_rule(rule: AtRule, parent_rules: &mut Vec<CSSRule>, base_
 
     let mut maybe_family = None;
     let mut maybe_sources = None;
+    let mut unicode_range = vec!(CodepointRange {
+        start: 0,
+        end: MAX_CODEPOINT,
+    });
+    let mut display = AutoFontDisplay;
 
     for item in ErrorLoggerIterator(parse_declaration_list(block.into_iter())) {
         match item {
@@ -93,6 +140,20 @@ pub fn parse_font_face_rule(rule: AtRule, parent_rules: &mut Vec<CSSRule>, base_
                             Err(()) => log_css_error(location, "Invalid src in @font-face"),
                         };
                     },
+                    "unicode-range" => {
+                        match parse_slice_comma_separated(value.as_slice(), parse_one_range) {
+                            Ok(ranges) => unicode_range = ranges,
+                            Err(()) => log_css_error(location,
+                                                     "Invalid unicode-range in @font-face"),
+                        };
+                    },
+                    "font-display" => {
+                        match parse_font_display(value.as_slice()) {
+                            Ok(value) => display = value,
+                            Err(()) => log_css_error(location,
+                                                     "Invalid font-display in @font-face"),
+                        };
+                    },
                     _ => {
                         log_css_error(location, format!("Unsupported declaration {:s}", name).as_slice());
                     }
@@ -105,6 +166,8 @@ pub fn parse_font_face_rule(rule: AtRule, parent_rules: &mut Vec<CSSRule>, base_
         (Some(family), Some(sources)) => parent_rules.push(CSSFontFaceRule(FontFaceRule {
             family: family,
             sources: sources,
+            unicode_range: unicode_range,
+            display: display,
         })),
         (None, _) => log_css_error(rule.location, "@font-face without a font-family descriptor"),
         _ => log_css_error(rule.location, "@font-face without an src descriptor"),
@@ -167,3 +230,43 @@ fn parse_one_format(iter: ParserIter) -> Result<String, ()> {
         _ => Err(())
     }
 }
+
+
+fn parse_one_range(iter: ParserIter) -> Result<CodepointRange, ()> {
+    match iter.next() {
+        Some(&UnicodeRange(start, end)) => {
+            // Ranges past the last code point are clipped, but empty ones are invalid.
+            let end = min(end, MAX_CODEPOINT);
+            if start > end {
+                return Err(())
+            }
+            Ok(CodepointRange {
+                start: start,
+                end: end,
+            })
+        }
+        _ => Err(())
+    }
+}
+
+
+fn parse_font_display(input: &[ComponentValue]) -> Result<FontDisplay, ()> {
+    let mut iter = input.skip_whitespace();
+    let display = match iter.next() {
+        Some(&Ident(ref value)) => {
+            match value.as_slice().to_ascii_lower().as_slice() {
+                "auto" => AutoFontDisplay,
+                "block" => BlockFontDisplay,
+                "swap" => SwapFontDisplay,
+                "fallback" => FallbackFontDisplay,
+                "optional" => OptionalFontDisplay,
+                _ => return Err(())
+            }
+        }
+        _ => return Err(())
+    };
+    if iter.next().is_some() {
+        return Err(())
+    }
+    Ok(display)
+}
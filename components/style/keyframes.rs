@@ -0,0 +1,191 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use std::ascii::AsciiExt;
+use cssparser::{parse_rule_list, ToCss};
+use cssparser::ast::*;
+
+use errors::{ErrorLoggerIterator, log_css_error};
+use media_queries::Device;
+use parsing_utils::{ParserIter, parse_slice_comma_separated};
+use properties::{AnimationTimingFunctionDeclaration, CSSFloat, PropertyDeclarationBlock};
+use properties::{SpecifiedValue, parse_property_declaration_list};
+use properties::common_types::computed::TimingFunction;
+use stylesheets::{CSSRule, CSSKeyframesRule, CSSImportRule, CSSStyleRule, CSSMediaRule};
+use stylesheets::{CSSSupportsRule, CSSFontFaceRule};
+use url::Url;
+
+
+pub fn iter_keyframes_rules_inner(rules: &[CSSRule], device: &Device,
+                                  callback: |&KeyframesRule|) {
+    for rule in rules.iter() {
+        match *rule {
+            CSSImportRule(ref rule) => if rule.media_queries.evaluate(device) {
+                match rule.stylesheet {
+                    Some(ref stylesheet) => {
+                        iter_keyframes_rules_inner(stylesheet.rules(), device, |r| callback(r))
+                    }
+                    None => {}
+                }
+            },
+            CSSMediaRule(ref rule) => if rule.media_queries.evaluate(device) {
+                iter_keyframes_rules_inner(rule.rules.as_slice(), device, |r| callback(r))
+            },
+            CSSSupportsRule(ref rule) => if rule.enabled {
+                iter_keyframes_rules_inner(rule.rules.as_slice(), device, |r| callback(r))
+            },
+            CSSKeyframesRule(ref rule) => callback(rule),
+            CSSStyleRule(_) | CSSFontFaceRule(_) => {},
+        }
+    }
+}
+
+#[deriving(Clone)]
+pub struct KeyframesRule {
+    /// Keyframes names are case-sensitive.
+    pub name: String,
+    /// In source order. When several keyframes have the same offset, their declarations cascade.
+    pub keyframes: Vec<Keyframe>,
+}
+
+#[deriving(Clone)]
+pub struct Keyframe {
+    /// Where the keyframe is in the animation, between 0 for `from` and 1 for `to`. A keyframe
+    /// selector can list several.
+    pub offsets: Vec<CSSFloat>,
+    /// The keyframe selector as it was written, for serialization.
+    pub selector_text: String,
+    /// `!important` declarations are ignored in keyframes, so only the normal ones are used.
+    pub declarations: PropertyDeclarationBlock,
+    /// The timing function used from this keyframe to the next one, if the keyframe declares
+    /// `animation-timing-function`. The one of the animation is used otherwise.
+    pub timing_function: Option<TimingFunction>,
+}
+
+pub fn parse_keyframes_rule(rule: AtRule, parent_rules: &mut Vec<CSSRule>, base_url: &Url) {
+    let name = {
+        let mut iter = rule.prelude.as_slice().skip_whitespace();
+        let name = match iter.next() {
+            Some(&Ident(ref name)) => match name.as_slice().to_ascii_lower().as_slice() {
+                "none" | "initial" | "inherit" | "unset" => None,
+                _ => Some(name.clone()),
+            },
+            Some(&QuotedString(ref name)) => Some(name.clone()),
+            _ => None,
+        };
+        if iter.next().is_some() { None } else { name }
+    };
+    let name = match name {
+        Some(name) => name,
+        None => {
+            log_css_error(rule.location, "Invalid @keyframes name");
+            return
+        }
+    };
+
+    let block = match rule.block {
+        Some(block) => block,
+        None => {
+            log_css_error(rule.location, "Invalid @keyframes rule");
+            return
+        }
+    };
+
+    let mut keyframes = vec!();
+    for rule in ErrorLoggerIterator(parse_rule_list(block.into_iter())) {
+        match rule {
+            QualifiedRule_(rule) => parse_keyframe(rule, &mut keyframes, base_url),
+            AtRule_(rule) => log_css_error(
+                rule.location,
+                format!("Unsupported at-rule in @keyframes: @{:s}", rule.name).as_slice()),
+        }
+    }
+    parent_rules.push(CSSKeyframesRule(KeyframesRule {
+        name: name,
+        keyframes: keyframes,
+    }))
+}
+
+fn parse_keyframe(rule: QualifiedRule, keyframes: &mut Vec<Keyframe>, base_url: &Url) {
+    let QualifiedRule { location, prelude, block } = rule;
+    let selector_text = prelude.iter().to_css().as_slice().trim().to_string();
+    let offsets = match parse_slice_comma_separated(prelude.as_slice(), parse_keyframe_offset) {
+        Ok(offsets) => offsets,
+        Err(()) => {
+            log_css_error(location, format!(
+                "Invalid keyframe selector: {}", selector_text).as_slice());
+            return
+        }
+    };
+    let declarations = parse_property_declaration_list(block.into_iter(), base_url);
+    let timing_function = declarations.normal.iter().filter_map(|declaration| {
+        match *declaration {
+            AnimationTimingFunctionDeclaration(SpecifiedValue(ref functions)) => {
+                functions.as_slice().get(0).map(|function| function.clone())
+            }
+            _ => None,
+        }
+    }).next();
+    keyframes.push(Keyframe {
+        offsets: offsets,
+        selector_text: selector_text,
+        declarations: declarations,
+        timing_function: timing_function,
+    })
+}
+
+/// `from`, `to` or a percentage.
+fn parse_keyframe_offset(iter: ParserIter) -> Result<CSSFloat, ()> {
+    match iter.next() {
+        Some(&Ident(ref value)) if value.as_slice().eq_ignore_ascii_case("from") => Ok(0.0),
+        Some(&Ident(ref value)) if value.as_slice().eq_ignore_ascii_case("to") => Ok(1.0),
+        Some(&Percentage(ref value)) if value.value >= 0.0 && value.value <= 100.0 => {
+            Ok(value.value / 100.0)
+        }
+        _ => Err(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use stylesheets::{Stylesheet, CSSKeyframesRule};
+    use properties::common_types::computed::{CubicBezierTimingFunction};
+    use selector_matching::AuthorOrigin;
+    use url::Url;
+
+    #[test]
+    fn test_parse_keyframes_rule() {
+        let url = Url::parse("http://localhost/").unwrap();
+        let css = "@keyframes slide { \
+                       from, 50% { margin-left: 0; animation-timing-function: linear } \
+                       to { margin-left: 100px; color: blue !important } \
+                       bogus { color: red } \
+                   } \
+                   @keyframes none { } \
+                   @keyframes 'fade' { }";
+        let stylesheet = Stylesheet::from_str(css, url, AuthorOrigin);
+        assert_eq!(stylesheet.rules().len(), 2);
+        let rule = match stylesheet.rules()[0] {
+            CSSKeyframesRule(ref rule) => rule,
+            _ => panic!("expected a @keyframes rule"),
+        };
+        assert_eq!(rule.name.as_slice(), "slide");
+        assert_eq!(rule.keyframes.len(), 2);
+        assert_eq!(rule.keyframes[0].offsets, vec!(0.0, 0.5));
+        assert_eq!(rule.keyframes[0].timing_function,
+                   Some(CubicBezierTimingFunction(0.0, 0.0, 1.0, 1.0)));
+        assert_eq!(rule.keyframes[1].offsets, vec!(1.0));
+        assert_eq!(rule.keyframes[1].timing_function, None);
+        assert_eq!(rule.keyframes[1].declarations.normal.len(), 1);
+        assert_eq!(stylesheet.rules()[0].to_css().as_slice(),
+                   "@keyframes slide {\n\
+                   \x20 from, 50% { margin-left: 0; animation-timing-function: linear; }\n\
+                   \x20 to { margin-left: 100px; color: blue !important; }\n\
+                   }");
+        match stylesheet.rules()[1] {
+            CSSKeyframesRule(ref rule) => assert_eq!(rule.name.as_slice(), "fade"),
+            _ => panic!("expected a @keyframes rule"),
+        }
+    }
+}
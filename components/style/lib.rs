@@ -34,8 +34,12 @@ extern crate "util" as servo_util;
 
 
 // Public API
-pub use media_queries::{Device, Screen};
-pub use stylesheets::{Stylesheet, iter_font_face_rules};
+pub use media_queries::{Device, Screen, MediaQueryList, MediaRule};
+pub use media_queries::parse_media_query_list_from_str;
+pub use stylesheets::{Stylesheet, CSSRule, CSSImportRule, CSSStyleRule, CSSMediaRule};
+pub use stylesheets::{CSSFontFaceRule, CSSSupportsRule, CSSKeyframesRule, StyleRule};
+pub use supports::{SupportsRule, supports_condition, supports_declaration};
+pub use stylesheets::iter_font_face_rules;
 pub use selector_matching::{Stylist, StylesheetOrigin, UserAgentOrigin, AuthorOrigin, UserOrigin};
 pub use selector_matching::{DeclarationBlock, CommonStyleAffectingAttributes};
 pub use selector_matching::{CommonStyleAffectingAttributeInfo, CommonStyleAffectingAttributeMode};
@@ -48,22 +52,31 @@ pub use properties::{PropertyDeclarationBlock, parse_style_attribute};  // Style
 pub use properties::{CSSFloat, DeclaredValue, PropertyDeclarationParseResult};
 pub use properties::{Angle, AngleOrCorner, AngleAoc, CornerAoc};
 pub use properties::{Left, Right, Bottom, Top};
+pub use properties::AnimatableLonghand;
 pub use node::{TElement, TElementAttributes, TNode};
-pub use selectors::{PseudoElement, Before, After, SelectorList, parse_selector_list_from_str};
+pub use selectors::{PseudoElement, Before, After, FirstLine, FirstLetter, SelectorList, parse_selector_list_from_str};
 pub use selectors::{AttrSelector, NamespaceConstraint, SpecificNamespace, AnyNamespace};
 pub use selectors::{SimpleSelector,LocalNameSelector};
 pub use cssparser::{Color, RGBA};
 pub use legacy::{IntegerAttribute, LengthAttribute, SizeIntegerAttribute, WidthLengthAttribute};
 pub use font_face::{Source, LocalSource, UrlSource_};
+pub use font_face::{FontFaceRule, CodepointRange, FontDisplay, AutoFontDisplay};
+pub use font_face::{BlockFontDisplay, SwapFontDisplay, FallbackFontDisplay, OptionalFontDisplay};
+pub use keyframes::{KeyframesRule, Keyframe};
+pub use errors::CSSError;
 
 mod stylesheets;
 mod errors;
 mod selectors;
 mod selector_matching;
 mod properties;
+mod custom_properties;
+mod animation;
+mod keyframes;
 mod namespaces;
 mod node;
 mod media_queries;
 mod parsing_utils;
 mod font_face;
+mod supports;
 mod legacy;
@@ -3,54 +3,129 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 use std::ascii::AsciiExt;
-use cssparser::parse_rule_list;
+use std::num::Zero;
+use std::u32;
+use cssparser::{parse_rule_list, tokenize, ToCss};
 use cssparser::ast::*;
 
 use errors::{ErrorLoggerIterator, log_css_error};
+use geom::scale_factor::ScaleFactor;
 use geom::size::TypedSize2D;
 use stylesheets::{CSSRule, CSSMediaRule, parse_style_rule, parse_nested_at_rule};
 use namespaces::NamespaceMap;
 use parsing_utils::{BufferedIter, ParserIter};
 use properties::common_types::*;
 use properties::longhands;
-use servo_util::geometry::ViewportPx;
+use servo_util::geometry::{DevicePixel, ViewportPx};
 use url::Url;
 
+#[deriving(Clone)]
 pub struct MediaRule {
     pub media_queries: MediaQueryList,
+    /// The media query list as it was written, for serialization.
+    pub media_text: String,
     pub rules: Vec<CSSRule>,
 }
 
+#[deriving(Clone)]
 pub struct MediaQueryList {
     media_queries: Vec<MediaQuery>
 }
 
+#[deriving(Clone)]
 pub enum Range<T> {
     Min(T),
     Max(T),
-    //Eq(T),    // FIXME: Implement parsing support for equality then re-enable this.
+    Eq(T),
+    /// The feature is used in a boolean context, such as `(width)`, and matches unless its value
+    /// is zero.
+    NonZero,
 }
 
-impl<T: Ord> Range<T> {
+impl<T: PartialOrd + Zero> Range<T> {
     fn evaluate(&self, value: T) -> bool {
         match *self {
             Min(ref width) => { value >= *width },
             Max(ref width) => { value <= *width },
-            //Eq(ref width) => { value == *width },
+            Eq(ref width) => { value == *width },
+            NonZero => !value.is_zero(),
         }
     }
 }
 
+/// A ratio of two positive integers, such as the `16/9` of `(aspect-ratio: 16/9)`.
+#[deriving(Clone, PartialEq, Show)]
+pub struct Ratio {
+    pub numerator: u32,
+    pub denominator: u32,
+}
+
+impl Range<Ratio> {
+    /// Compares the ratio of the given width to the given height with the range. The ratios are
+    /// cross-multiplied rather than divided, so that equal ratios compare equal.
+    fn evaluate_ratio(&self, width: f32, height: f32) -> bool {
+        let Au(width) = Au::from_frac_px(width as f64);
+        let Au(height) = Au::from_frac_px(height as f64);
+        let cross_multiply = |ratio: &Ratio| {
+            (width as i64 * ratio.denominator as i64, height as i64 * ratio.numerator as i64)
+        };
+        match *self {
+            Min(ref ratio) => { let (actual, wanted) = cross_multiply(ratio); actual >= wanted },
+            Max(ref ratio) => { let (actual, wanted) = cross_multiply(ratio); actual <= wanted },
+            Eq(ref ratio) => { let (actual, wanted) = cross_multiply(ratio); actual == wanted },
+            NonZero => width != 0 && height != 0,
+        }
+    }
+}
+
+/// Whether a range feature was given with a `min-` or `max-` prefix.
+#[deriving(PartialEq)]
+enum RangePrefix {
+    MinPrefix,
+    MaxPrefix,
+    NoPrefix,
+}
+
+impl RangePrefix {
+    fn range<T>(self, value: T) -> Range<T> {
+        match self {
+            MinPrefix => Min(value),
+            MaxPrefix => Max(value),
+            NoPrefix => Eq(value),
+        }
+    }
+}
+
+#[deriving(PartialEq, Clone)]
+pub enum Orientation {
+    Portrait,
+    Landscape,
+}
+
+#[deriving(Clone)]
 pub enum Expression {
     Width(Range<Au>),
+    Height(Range<Au>),
+    DeviceWidth(Range<Au>),
+    DeviceHeight(Range<Au>),
+    /// The ratio of the viewport's width to its height.
+    AspectRatio(Range<Ratio>),
+    DeviceAspectRatio(Range<Ratio>),
+    /// `None` in a boolean context, where any orientation matches.
+    Orientation(Option<Orientation>),
+    /// In device pixels per CSS pixel.
+    Resolution(Range<f32>),
+    /// In bits per color component.
+    Color(Range<u32>),
 }
 
-#[deriving(PartialEq)]
+#[deriving(PartialEq, Clone)]
 pub enum Qualifier {
     Only,
     Not,
 }
 
+#[deriving(Clone)]
 pub struct MediaQuery {
     qualifier: Option<Qualifier>,
     media_type: MediaQueryType,
@@ -68,13 +143,13 @@ impl MediaQuery {
     }
 }
 
-#[deriving(PartialEq)]
+#[deriving(PartialEq, Clone)]
 pub enum MediaQueryType {
     All,  // Always true
     MediaType_(MediaType),
 }
 
-#[deriving(PartialEq)]
+#[deriving(PartialEq, Clone)]
 pub enum MediaType {
     Screen,
     Print,
@@ -84,13 +159,23 @@ pub enum MediaType {
 pub struct Device {
     pub media_type: MediaType,
     pub viewport_size: TypedSize2D<ViewportPx, f32>,
+    /// The size of the whole output device, such as the screen.
+    pub device_size: TypedSize2D<ViewportPx, f32>,
+    pub device_pixel_ratio: ScaleFactor<ViewportPx, DevicePixel, f32>,
+    /// The number of bits per color component, or zero for devices without color.
+    pub color_bits: u32,
 }
 
 impl Device {
-    pub fn new(media_type: MediaType, viewport_size: TypedSize2D<ViewportPx, f32>) -> Device {
+    pub fn new(media_type: MediaType, viewport_size: TypedSize2D<ViewportPx, f32>,
+               device_size: TypedSize2D<ViewportPx, f32>,
+               device_pixel_ratio: ScaleFactor<ViewportPx, DevicePixel, f32>) -> Device {
         Device {
             media_type: media_type,
             viewport_size: viewport_size,
+            device_size: device_size,
+            device_pixel_ratio: device_pixel_ratio,
+            color_bits: 8,
         }
     }
 }
@@ -98,6 +183,7 @@ impl Device {
 pub fn parse_media_rule(rule: AtRule, parent_rules: &mut Vec<CSSRule>,
                         namespaces: &NamespaceMap, base_url: &Url) {
     let media_queries = parse_media_query_list(rule.prelude.as_slice());
+    let media_text = rule.prelude.iter().to_css();
     let block = match rule.block {
         Some(block) => block,
         None => {
@@ -115,6 +201,7 @@ pub fn parse_media_rule(rule: AtRule, parent_rules: &mut Vec<CSSRule>,
     }
     parent_rules.push(CSSMediaRule(MediaRule {
         media_queries: media_queries,
+        media_text: media_text.as_slice().trim().to_string(),
         rules: rules,
     }))
 }
@@ -128,6 +215,74 @@ fn parse_value_as_length(value: &ComponentValue) -> Result<Au, ()> {
     Ok(computed::compute_Au_with_font_size(length, initial_font_size))
 }
 
+/// Parses a ratio such as `16/9`, returning it as a fraction.
+fn parse_ratio(iter: ParserIter) -> Result<Ratio, ()> {
+    fn parse_positive_integer(value: Option<&ComponentValue>) -> Result<u32, ()> {
+        match value {
+            Some(&Number(ref value)) => match value.int_value {
+                Some(value) if value > 0 && value <= u32::MAX as i64 => Ok(value as u32),
+                _ => Err(())
+            },
+            _ => Err(())
+        }
+    }
+
+    let numerator = try!(parse_positive_integer(iter.next()));
+    match iter.next() {
+        Some(&Delim('/')) => {},
+        _ => return Err(())
+    }
+    let denominator = try!(parse_positive_integer(iter.next()));
+    Ok(Ratio {
+        numerator: numerator,
+        denominator: denominator,
+    })
+}
+
+/// Parses a resolution, returning it in device pixels per CSS pixel.
+fn parse_resolution(value: &ComponentValue) -> Result<f32, ()> {
+    match value {
+        &Dimension(ref value, ref unit) if value.value > 0. => {
+            let dppx = match unit.as_slice().to_ascii_lower().as_slice() {
+                "dppx" => value.value,
+                "dpi" => value.value / 96.,
+                "dpcm" => value.value * 2.54 / 96.,
+                _ => return Err(())
+            };
+            Ok(dppx as f32)
+        }
+        _ => Err(())
+    }
+}
+
+fn parse_integer(value: &ComponentValue) -> Result<u32, ()> {
+    match value {
+        &Number(ref value) => match value.int_value {
+            Some(value) if value >= 0 => Ok(value as u32),
+            _ => Err(())
+        },
+        _ => Err(())
+    }
+}
+
+/// Parses a feature in a boolean context, such as `(color)`, which is true when the feature's
+/// value would be true as a number.
+fn parse_boolean_expression(feature: &str) -> Result<Expression, ()> {
+    let expression = match feature {
+        "width" => Width(NonZero),
+        "height" => Height(NonZero),
+        "device-width" => DeviceWidth(NonZero),
+        "device-height" => DeviceHeight(NonZero),
+        "aspect-ratio" => AspectRatio(NonZero),
+        "device-aspect-ratio" => DeviceAspectRatio(NonZero),
+        "orientation" => Orientation(None),
+        "resolution" => Resolution(NonZero),
+        "color" => Color(NonZero),
+        _ => return Err(())
+    };
+    Ok(expression)
+}
+
 fn parse_media_query_expression(iter: ParserIter) -> Result<Expression, ()> {
     // Expect a parenthesis block with the condition
     match iter.next() {
@@ -136,28 +291,78 @@ fn parse_media_query_expression(iter: ParserIter) -> Result<Expression, ()> {
 
             // Parse the variable (e.g. min-width)
             let variable = match iter.next() {
-                Some(&Ident(ref value)) => value,
+                Some(&Ident(ref value)) => value.as_slice().to_ascii_lower(),
                 _ => return Err(())
             };
 
-            // Ensure a colon follows
+            // WebKit's device pixel ratio features are widely used, and are the same as
+            // resolution given as a number of dppx.
+            let variable = if variable.as_slice().starts_with("-webkit-") &&
+                              variable.as_slice().ends_with("device-pixel-ratio") {
+                variable.as_slice().slice_from(8).to_string()
+            } else {
+                variable
+            };
+            let (prefix, feature) = if variable.as_slice().starts_with("min-") {
+                (MinPrefix, variable.as_slice().slice_from(4))
+            } else if variable.as_slice().starts_with("max-") {
+                (MaxPrefix, variable.as_slice().slice_from(4))
+            } else {
+                (NoPrefix, variable.as_slice())
+            };
+
+            // Ensure a colon follows, unless the feature is used in a boolean context
             match iter.next() {
                 Some(&Colon) => {},
+                None if prefix == NoPrefix => return parse_boolean_expression(feature),
                 _ => return Err(())
             }
 
-            // Retrieve the value
-            let value = try!(iter.next_as_result());
-
-            // TODO: Handle other media query types
-            let expression = match variable.as_slice().to_ascii_lower().as_slice() {
-                "min-width" => {
-                    let au = try!(parse_value_as_length(value));
-                    Width(Min(au))
+            let expression = match feature {
+                "width" => {
+                    let au = try!(parse_value_as_length(try!(iter.next_as_result())));
+                    Width(prefix.range(au))
+                }
+                "height" => {
+                    let au = try!(parse_value_as_length(try!(iter.next_as_result())));
+                    Height(prefix.range(au))
                 }
-                "max-width" => {
-                    let au = try!(parse_value_as_length(value));
-                    Width(Max(au))
+                "device-width" => {
+                    let au = try!(parse_value_as_length(try!(iter.next_as_result())));
+                    DeviceWidth(prefix.range(au))
+                }
+                "device-height" => {
+                    let au = try!(parse_value_as_length(try!(iter.next_as_result())));
+                    DeviceHeight(prefix.range(au))
+                }
+                "aspect-ratio" => AspectRatio(prefix.range(try!(parse_ratio(iter)))),
+                "device-aspect-ratio" => DeviceAspectRatio(prefix.range(try!(parse_ratio(iter)))),
+                "orientation" if prefix == NoPrefix => {
+                    match try!(iter.next_as_result()) {
+                        &Ident(ref value) => {
+                            match value.as_slice().to_ascii_lower().as_slice() {
+                                "portrait" => Orientation(Some(Portrait)),
+                                "landscape" => Orientation(Some(Landscape)),
+                                _ => return Err(())
+                            }
+                        }
+                        _ => return Err(())
+                    }
+                }
+                "resolution" => {
+                    let dppx = try!(parse_resolution(try!(iter.next_as_result())));
+                    Resolution(prefix.range(dppx))
+                }
+                "device-pixel-ratio" => {
+                    let dppx = match try!(iter.next_as_result()) {
+                        &Number(ref value) if value.value > 0. => value.value as f32,
+                        _ => return Err(())
+                    };
+                    Resolution(prefix.range(dppx))
+                }
+                "color" => {
+                    let bits = try!(parse_integer(try!(iter.next_as_result())));
+                    Color(prefix.range(bits))
                 }
                 _ => return Err(())
             };
@@ -278,6 +483,43 @@ pub fn parse_media_query_list(input: &[ComponentValue]) -> MediaQueryList {
     MediaQueryList { media_queries: media_queries }
 }
 
+pub fn parse_media_query_list_from_str(input: &str) -> MediaQueryList {
+    let tokens: Vec<ComponentValue> = tokenize(input).map(|(token, _)| token).collect();
+    parse_media_query_list(tokens.as_slice())
+}
+
+impl Expression {
+    fn evaluate(&self, device: &Device) -> bool {
+        let viewport_size = device.viewport_size.to_untyped();
+        let device_size = device.device_size.to_untyped();
+        match *self {
+            Width(ref range) => range.evaluate(Au::from_frac_px(viewport_size.width as f64)),
+            Height(ref range) => range.evaluate(Au::from_frac_px(viewport_size.height as f64)),
+            DeviceWidth(ref range) => range.evaluate(Au::from_frac_px(device_size.width as f64)),
+            DeviceHeight(ref range) => {
+                range.evaluate(Au::from_frac_px(device_size.height as f64))
+            }
+            AspectRatio(ref range) => {
+                range.evaluate_ratio(viewport_size.width, viewport_size.height)
+            }
+            DeviceAspectRatio(ref range) => {
+                range.evaluate_ratio(device_size.width, device_size.height)
+            }
+            Orientation(None) => true,
+            Orientation(Some(orientation)) => {
+                let actual = if viewport_size.height >= viewport_size.width {
+                    Portrait
+                } else {
+                    Landscape
+                };
+                orientation == actual
+            }
+            Resolution(ref range) => range.evaluate(device.device_pixel_ratio.get()),
+            Color(ref range) => range.evaluate(device.color_bits),
+        }
+    }
+}
+
 impl MediaQueryList {
     pub fn evaluate(&self, device: &Device) -> bool {
         // Check if any queries match (OR condition)
@@ -291,10 +533,7 @@ impl MediaQueryList {
 
             // Check if all conditions match (AND condition)
             let query_match = media_match && mq.expressions.iter().all(|expression| {
-                match expression {
-                    &Width(value) => value.evaluate(
-                        Au::from_frac_px(device.viewport_size.to_untyped().width as f64)),
-                }
+                expression.evaluate(device)
             });
 
             // Apply the logical NOT qualifier to the result
@@ -308,6 +547,7 @@ impl MediaQueryList {
 
 #[cfg(test)]
 mod tests {
+    use geom::scale_factor::ScaleFactor;
     use geom::size::TypedSize2D;
     use properties::common_types::*;
     use stylesheets::{iter_stylesheet_media_rules, iter_stylesheet_style_rules, Stylesheet};
@@ -570,6 +810,65 @@ mod tests {
         });
     }
 
+    #[test]
+    fn test_mq_level_3_expressions() {
+        test_media_rule("@media (width: 100px) and (height: 50px) { }", |list, css| {
+            let q = &list.media_queries[0];
+            assert!(q.qualifier == None, css.to_string());
+            assert!(q.expressions.len() == 2, css.to_string());
+            match q.expressions[0] {
+                Width(Eq(w)) => assert!(w == Au::from_px(100)),
+                _ => panic!("wrong expression type"),
+            }
+            match q.expressions[1] {
+                Height(Eq(h)) => assert!(h == Au::from_px(50)),
+                _ => panic!("wrong expression type"),
+            }
+        });
+
+        test_media_rule("@media (min-aspect-ratio: 16 / 9) and (orientation: landscape) { }",
+                        |list, css| {
+            let q = &list.media_queries[0];
+            assert!(q.expressions.len() == 2, css.to_string());
+            match q.expressions[0] {
+                AspectRatio(Min(ref ratio)) => {
+                    assert!(*ratio == Ratio { numerator: 16, denominator: 9 })
+                }
+                _ => panic!("wrong expression type"),
+            }
+            match q.expressions[1] {
+                Orientation(orientation) => assert!(orientation == Some(Landscape)),
+                _ => panic!("wrong expression type"),
+            }
+        });
+
+        test_media_rule("@media (min-resolution: 192dpi), (-webkit-max-device-pixel-ratio: 1.5) { }",
+                        |list, css| {
+            assert!(list.media_queries.len() == 2, css.to_string());
+            match list.media_queries[0].expressions[0] {
+                Resolution(Min(dppx)) => assert!(dppx == 2.0),
+                _ => panic!("wrong expression type"),
+            }
+            match list.media_queries[1].expressions[0] {
+                Resolution(Max(dppx)) => assert!(dppx == 1.5),
+                _ => panic!("wrong expression type"),
+            }
+        });
+
+        test_media_rule("@media (color) and (min-color: 4) { }", |list, css| {
+            let q = &list.media_queries[0];
+            assert!(q.expressions.len() == 2, css.to_string());
+            match q.expressions[0] {
+                Color(Min(bits)) => assert!(bits == 1),
+                _ => panic!("wrong expression type"),
+            }
+            match q.expressions[1] {
+                Color(Min(bits)) => assert!(bits == 4),
+                _ => panic!("wrong expression type"),
+            }
+        });
+    }
+
     #[test]
     fn test_mq_malformed_expressions() {
         test_media_rule("@media (min-width: 100blah) and (max-width: 200px) { }", |list, css| {
@@ -580,7 +879,7 @@ mod tests {
             assert!(q.expressions.len() == 0, css.to_string());
         });
 
-        test_media_rule("@media screen and (height: 200px) { }", |list, css| {
+        test_media_rule("@media screen and (scan: progressive) { }", |list, css| {
             assert!(list.media_queries.len() == 1, css.to_string());
             let q = &list.media_queries[0];
             assert!(q.qualifier == Some(Not), css.to_string());
@@ -650,6 +949,9 @@ mod tests {
         let device = Device {
             media_type: Screen,
             viewport_size: TypedSize2D(200.0, 100.0),
+            device_size: TypedSize2D(200.0, 100.0),
+            device_pixel_ratio: ScaleFactor(1.0),
+            color_bits: 8,
         };
 
         media_query_test(&device, "@media not all { a { color: red; } }", 0);
@@ -669,6 +971,9 @@ mod tests {
         let device = Device {
             media_type: Screen,
             viewport_size: TypedSize2D(200.0, 100.0),
+            device_size: TypedSize2D(200.0, 100.0),
+            device_pixel_ratio: ScaleFactor(1.0),
+            color_bits: 8,
         };
 
         media_query_test(&device, "@media { a { color: red; } }", 1);
@@ -702,15 +1007,81 @@ mod tests {
         media_query_test(&device, "@media not screen and (min-width: 3em) and (max-width: 250px) { a { color: red; } }", 0);
     }
 
+    #[test]
+    fn test_matching_level_3() {
+        let device = Device {
+            media_type: Screen,
+            viewport_size: TypedSize2D(200.0, 100.0),
+            device_size: TypedSize2D(1600.0, 900.0),
+            device_pixel_ratio: ScaleFactor(2.0),
+            color_bits: 8,
+        };
+
+        media_query_test(&device, "@media (width: 200px) { a { color: red; } }", 1);
+        media_query_test(&device, "@media (width: 201px) { a { color: red; } }", 0);
+        media_query_test(&device, "@media (min-height: 100px) { a { color: red; } }", 1);
+        media_query_test(&device, "@media (max-height: 99px) { a { color: red; } }", 0);
+        media_query_test(&device, "@media (height) { a { color: red; } }", 1);
+
+        media_query_test(&device, "@media (device-width: 1600px) { a { color: red; } }", 1);
+        media_query_test(&device, "@media (max-device-height: 800px) { a { color: red; } }", 0);
+
+        media_query_test(&device, "@media (aspect-ratio: 2/1) { a { color: red; } }", 1);
+        media_query_test(&device, "@media (min-aspect-ratio: 3/1) { a { color: red; } }", 0);
+        media_query_test(&device, "@media (device-aspect-ratio: 16/9) { a { color: red; } }", 1);
+        media_query_test(&device, "@media (min-aspect-ratio: 16777217/8388608) { a { color: red; } }", 0);
+        media_query_test(&device, "@media (max-aspect-ratio: 16777215/8388608) { a { color: red; } }", 0);
+
+        media_query_test(&device, "@media (orientation: landscape) { a { color: red; } }", 1);
+        media_query_test(&device, "@media (orientation: portrait) { a { color: red; } }", 0);
+
+        media_query_test(&device, "@media (min-resolution: 2dppx) { a { color: red; } }", 1);
+        media_query_test(&device, "@media (min-resolution: 300dpi) { a { color: red; } }", 0);
+        media_query_test(&device, "@media (device-pixel-ratio: 2) { a { color: red; } }", 1);
+        media_query_test(&device, "@media (-webkit-min-device-pixel-ratio: 1.5) { a { color: red; } }", 1);
+
+        media_query_test(&device, "@media (color) { a { color: red; } }", 1);
+        media_query_test(&device, "@media (min-color: 16) { a { color: red; } }", 0);
+    }
+
+    #[test]
+    fn test_matching_boolean() {
+        let device = Device {
+            media_type: Screen,
+            viewport_size: TypedSize2D(0.0, 100.0),
+            device_size: TypedSize2D(0.0, 0.0),
+            device_pixel_ratio: ScaleFactor(1.0),
+            color_bits: 0,
+        };
+
+        media_query_test(&device, "@media (width) { a { color: red; } }", 0);
+        media_query_test(&device, "@media (height) { a { color: red; } }", 1);
+        media_query_test(&device, "@media (device-width) { a { color: red; } }", 0);
+        media_query_test(&device, "@media (device-height) { a { color: red; } }", 0);
+        media_query_test(&device, "@media (color) { a { color: red; } }", 0);
+        media_query_test(&device, "@media (aspect-ratio) { a { color: red; } }", 0);
+        media_query_test(&device, "@media (device-aspect-ratio) { a { color: red; } }", 0);
+        media_query_test(&device, "@media (orientation) { a { color: red; } }", 1);
+        media_query_test(&device, "@media (resolution) { a { color: red; } }", 1);
+        media_query_test(&device, "@media (min-orientation) { a { color: red; } }", 0);
+    }
+
     #[test]
     fn test_matching_invalid() {
         let device = Device {
             media_type: Screen,
             viewport_size: TypedSize2D(200.0, 100.0),
+            device_size: TypedSize2D(200.0, 100.0),
+            device_pixel_ratio: ScaleFactor(1.0),
+            color_bits: 8,
         };
 
         media_query_test(&device, "@media fridge { a { color: red; } }", 0);
-        media_query_test(&device, "@media screen and (height: 100px) { a { color: red; } }", 0);
+        media_query_test(&device, "@media screen and (scan: progressive) { a { color: red; } }", 0);
+        media_query_test(&device, "@media (min-orientation: portrait) { a { color: red; } }", 0);
+        media_query_test(&device, "@media (aspect-ratio: 2) { a { color: red; } }", 0);
+        media_query_test(&device, "@media (min-aspect-ratio: 0/1) { a { color: red; } }", 0);
+        media_query_test(&device, "@media (resolution: 2) { a { color: red; } }", 0);
         media_query_test(&device, "@media not print and (width: 100) { a { color: red; } }", 0);
     }
 }
@@ -8,6 +8,7 @@ use servo_util::namespace;
 use errors::log_css_error;
 use string_cache::Namespace;
 
+#[deriving(Clone)]
 pub struct NamespaceMap {
     pub default: Option<Namespace>,
     pub prefix_map: HashMap<String, Namespace>,
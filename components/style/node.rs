@@ -22,6 +22,10 @@ pub trait TNode<'a, E: TElement<'a>> : Clone + Copy {
     fn match_attr(self, attr: &AttrSelector, test: |&str| -> bool) -> bool;
     fn is_html_element_in_html_document(self) -> bool;
 
+    /// Returns true if this node has no element children and no text children with content,
+    /// which is what the `:empty` selector matches.
+    fn is_empty(self) -> bool;
+
     fn has_changed(self) -> bool;
     unsafe fn set_changed(self, value: bool);
 
@@ -45,6 +49,11 @@ pub trait TElement<'a> : Copy {
     fn get_id(self) -> Option<Atom>;
     fn get_disabled_state(self) -> bool;
     fn get_enabled_state(self) -> bool;
+    fn get_checked_state(self) -> bool;
+    fn get_indeterminate_state(self) -> bool;
+    fn get_active_state(self) -> bool;
+    fn get_focus_state(self) -> bool;
+    fn get_target_state(self) -> bool;
     fn has_class(self, name: &Atom) -> bool;
 
     // Ordinarily I wouldn't use callbacks like this, but the alternative is
@@ -4,7 +4,8 @@
 
 
 use std::ascii::AsciiExt;
-use cssparser::ast::{ComponentValue, Ident, Comma, SkipWhitespaceIterable, SkipWhitespaceIterator};
+use cssparser::ast::{ComponentValue, Ident, Comma, WhiteSpace, SkipWhitespaceIterable};
+use cssparser::ast::SkipWhitespaceIterator;
 
 
 pub fn one_component_value<'a>(input: &'a [ComponentValue]) -> Result<&'a ComponentValue, ()> {
@@ -16,6 +17,13 @@ pub fn one_component_value<'a>(input: &'a [ComponentValue]) -> Result<&'a Compon
 }
 
 
+pub fn trim_whitespace(input: &[ComponentValue]) -> &[ComponentValue] {
+    let start = input.iter().position(|value| *value != WhiteSpace).unwrap_or(input.len());
+    let end = input.iter().rposition(|value| *value != WhiteSpace).map_or(start, |i| i + 1);
+    input.slice(start, end)
+}
+
+
 pub fn get_ident_lower(component_value: &ComponentValue) -> Result<String, ()> {
     match component_value {
         &Ident(ref value) => Ok(value.as_slice().to_ascii_lower()),
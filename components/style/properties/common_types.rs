@@ -211,7 +211,7 @@ pub mod specified {
         }
     }
 
-    #[deriving(Clone, PartialEq, PartialOrd)]
+    #[deriving(Clone, PartialEq, PartialOrd, Show)]
     pub struct Angle(pub CSSFloat);
 
     impl Angle {
@@ -239,6 +239,147 @@ pub mod specified {
                 Err(())
             }
         }
+
+        /// Parses an angle, also accepting a unitless zero as transforms do.
+        pub fn parse(input: &ComponentValue) -> Result<Angle, ()> {
+            match *input {
+                Dimension(ref value, ref unit) => {
+                    Angle::parse_dimension(value.value, unit.as_slice())
+                }
+                Number(ref value) if value.value == 0. => Ok(Angle(0.0)),
+                _ => Err(())
+            }
+        }
+    }
+
+    /// A time in seconds, as used by transitions and animations.
+    #[deriving(Clone, PartialEq, PartialOrd, Show)]
+    pub struct Time(pub CSSFloat);
+
+    impl Time {
+        pub fn seconds(self) -> CSSFloat {
+            let Time(seconds) = self;
+            seconds
+        }
+
+        /// Parses a time according to CSS-VALUES § 6.2.
+        pub fn parse(input: &ComponentValue) -> Result<Time, ()> {
+            match *input {
+                Dimension(ref value, ref unit) => {
+                    if unit.as_slice().eq_ignore_ascii_case("s") {
+                        Ok(Time(value.value))
+                    } else if unit.as_slice().eq_ignore_ascii_case("ms") {
+                        Ok(Time(value.value / 1000.0))
+                    } else {
+                        Err(())
+                    }
+                }
+                _ => Err(()),
+            }
+        }
+
+        pub fn parse_non_negative(input: &ComponentValue) -> Result<Time, ()> {
+            match Time::parse(input) {
+                Ok(Time(seconds)) if seconds < 0.0 => Err(()),
+                result => result,
+            }
+        }
+    }
+
+    /// Where the jumps of a `steps()` timing function happen.
+    #[deriving(Clone, PartialEq, Show)]
+    pub enum StepPosition {
+        JumpStart,
+        JumpEnd,
+    }
+
+    /// A timing function according to CSS-TRANSITIONS § 2.3. Keywords are represented as the
+    /// functions they stand for.
+    #[deriving(Clone, PartialEq, Show)]
+    pub enum TimingFunction {
+        CubicBezierTimingFunction(CSSFloat, CSSFloat, CSSFloat, CSSFloat),
+        StepsTimingFunction(u32, StepPosition),
+    }
+
+    impl TimingFunction {
+        #[inline]
+        pub fn ease() -> TimingFunction {
+            CubicBezierTimingFunction(0.25, 0.1, 0.25, 1.0)
+        }
+
+        pub fn parse(input: &ComponentValue) -> Result<TimingFunction, ()> {
+            match *input {
+                Ident(ref value) => {
+                    match value.as_slice().to_ascii_lower().as_slice() {
+                        "ease" => Ok(TimingFunction::ease()),
+                        "linear" => Ok(CubicBezierTimingFunction(0.0, 0.0, 1.0, 1.0)),
+                        "ease-in" => Ok(CubicBezierTimingFunction(0.42, 0.0, 1.0, 1.0)),
+                        "ease-out" => Ok(CubicBezierTimingFunction(0.0, 0.0, 0.58, 1.0)),
+                        "ease-in-out" => Ok(CubicBezierTimingFunction(0.42, 0.0, 0.58, 1.0)),
+                        "step-start" => Ok(StepsTimingFunction(1, JumpStart)),
+                        "step-end" => Ok(StepsTimingFunction(1, JumpEnd)),
+                        _ => Err(()),
+                    }
+                }
+                Function(ref name, ref args) => {
+                    if name.as_slice().eq_ignore_ascii_case("cubic-bezier") {
+                        TimingFunction::parse_cubic_bezier(args.as_slice())
+                    } else if name.as_slice().eq_ignore_ascii_case("steps") {
+                        TimingFunction::parse_steps(args.as_slice())
+                    } else {
+                        Err(())
+                    }
+                }
+                _ => Err(()),
+            }
+        }
+
+        /// `cubic-bezier(<number>, <number>, <number>, <number>)`, where the x coordinates must
+        /// be between 0 and 1.
+        fn parse_cubic_bezier(args: &[ComponentValue]) -> Result<TimingFunction, ()> {
+            let values = try!(parsing_utils::parse_slice_comma_separated(args, |iter| {
+                match iter.next() {
+                    Some(&Number(ref value)) => Ok(value.value),
+                    _ => Err(()),
+                }
+            }));
+            if values.len() != 4 {
+                return Err(())
+            }
+            let (x1, y1, x2, y2) = (values[0], values[1], values[2], values[3]);
+            if x1 < 0.0 || x1 > 1.0 || x2 < 0.0 || x2 > 1.0 {
+                return Err(())
+            }
+            Ok(CubicBezierTimingFunction(x1, y1, x2, y2))
+        }
+
+        /// `steps(<integer> [, start | end ]?)`
+        fn parse_steps(args: &[ComponentValue]) -> Result<TimingFunction, ()> {
+            let mut iter = args.skip_whitespace();
+            let steps = match iter.next() {
+                Some(&Number(ref value)) if value.int_value.map_or(false, |steps| steps > 0) => {
+                    value.int_value.unwrap() as u32
+                }
+                _ => return Err(()),
+            };
+            let position = match iter.next() {
+                None => return Ok(StepsTimingFunction(steps, JumpEnd)),
+                Some(&Comma) => match iter.next() {
+                    Some(&Ident(ref value)) if value.as_slice().eq_ignore_ascii_case("start") => {
+                        JumpStart
+                    }
+                    Some(&Ident(ref value)) if value.as_slice().eq_ignore_ascii_case("end") => {
+                        JumpEnd
+                    }
+                    _ => return Err(()),
+                },
+                Some(_) => return Err(()),
+            };
+            if iter.next().is_some() {
+                return Err(())
+            }
+            Ok(StepsTimingFunction(steps, position))
+        }
     }
 
     /// Specified values for an image according to CSS-IMAGES.
@@ -449,6 +590,8 @@ pub mod specified {
 pub mod computed {
     pub use super::specified::{Angle, AngleAoc, AngleOrCorner, CornerAoc, HorizontalDirection};
     pub use super::specified::{VerticalDirection};
+    pub use super::specified::{Time, TimingFunction, CubicBezierTimingFunction};
+    pub use super::specified::{StepsTimingFunction, StepPosition, JumpStart, JumpEnd};
     pub use cssparser::Color as CSSColor;
     pub use super::super::longhands::computed_as_specified as compute_CSSColor;
     use super::*;
@@ -471,6 +614,8 @@ pub mod computed {
         pub border_bottom_present: bool,
         pub border_left_present: bool,
         pub is_root_element: bool,
+        /// Whether the parent is a flex container, which blockifies `display`.
+        pub is_flex_item: bool,
         // TODO, as needed: root font size, viewport size, etc.
     }
 
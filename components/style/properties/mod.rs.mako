@@ -16,6 +16,8 @@ pub use geom::SideOffsets2D;
 pub use self::common_types::specified::{Angle, AngleAoc, AngleOrCorner, Bottom, CornerAoc};
 pub use self::common_types::specified::{Left, Right, Top};
 
+use animation::Interpolate;
+use custom_properties;
 use errors::{ErrorLoggerIterator, log_css_error};
 pub use parsing_utils::*;
 pub use self::common_types::*;
@@ -372,6 +374,7 @@ pub mod longhands {
             table inline-table table-row-group table-header-group table-footer-group
             table-row table-column-group table-column table-cell table-caption
             list-item
+            flex inline-flex
             none">
         #[inline]
         pub fn to_computed_value(value: SpecifiedValue, context: &computed::Context)
@@ -379,9 +382,11 @@ pub mod longhands {
 //            if context.is_root_element && value == list_item {
 //                return block
 //            }
-            if context.positioned || context.floated || context.is_root_element {
+            if context.positioned || context.floated || context.is_root_element ||
+                    context.is_flex_item {
                 match value {
                     inline_table => table,
+                    inline_flex => flex,
                     inline | inline_block
                     | table_row_group | table_column | table_column_group
                     | table_header_group | table_footer_group | table_row
@@ -455,7 +460,7 @@ pub mod longhands {
 
     ${new_style_struct("InheritedBox", is_inherited=True)}
 
-    ${single_keyword("direction", "ltr rtl", experimental=True)}
+    ${single_keyword("direction", "ltr rtl")}
 
     // CSS 2.1, Section 10 - Visual formatting model details
 
@@ -627,9 +632,14 @@ pub mod longhands {
     <%self:longhand name="content">
             pub use super::computed_as_specified as to_computed_value;
             pub mod computed_value {
+                use super::super::list_style_type;
                 #[deriving(PartialEq, Clone)]
                 pub enum ContentItem {
                     StringContent(String),
+                    /// `counter(name, style)`
+                    CounterContent(String, list_style_type::computed_value::T),
+                    /// `counters(name, separator, style)`
+                    CountersContent(String, String, list_style_type::computed_value::T),
                 }
                 #[allow(non_camel_case_types)]
                 #[deriving(PartialEq, Clone)]
@@ -642,9 +652,46 @@ pub mod longhands {
             pub type SpecifiedValue = computed_value::T;
             #[inline] pub fn get_initial_value() -> computed_value::T  { normal }
 
-            // normal | none | [ <string> ]+
-            // TODO: <uri>, <counter>, attr(<identifier>), open-quote, close-quote, no-open-quote, no-close-quote
-            pub fn parse(input: &[ComponentValue], _base_url: &Url) -> Result<SpecifiedValue, ()> {
+            /// Parses the arguments of `counter()` or, if `with_separator` is true, `counters()`.
+            fn parse_counter(arguments: &[ComponentValue], with_separator: bool, base_url: &Url)
+                             -> Result<ContentItem, ()> {
+                let mut iter = BufferedIter::new(arguments.skip_whitespace());
+                let name = match iter.next() {
+                    Some(&Ident(ref name)) => name.clone(),
+                    _ => return Err(()),
+                };
+                let separator = if with_separator {
+                    match (iter.next(), iter.next()) {
+                        (Some(&Comma), Some(&QuotedString(ref separator))) => {
+                            Some(separator.clone())
+                        }
+                        _ => return Err(()),
+                    }
+                } else {
+                    None
+                };
+                let style = match iter.next() {
+                    None => super::list_style_type::decimal,
+                    Some(&Comma) => {
+                        let style = try!(super::list_style_type::from_component_value(
+                            try!(iter.next_as_result()), base_url));
+                        if !iter.is_eof() {
+                            return Err(())
+                        }
+                        style
+                    }
+                    Some(_) => return Err(()),
+                };
+                Ok(match separator {
+                    Some(separator) => CountersContent(name, separator, style),
+                    None => CounterContent(name, style),
+                })
+            }
+
+            // normal | none | [ <string> | <counter> ]+
+            // TODO: <uri>, attr(<identifier>), open-quote, close-quote, no-open-quote,
+            // no-close-quote
+            pub fn parse(input: &[ComponentValue], base_url: &Url) -> Result<SpecifiedValue, ()> {
                 match one_component_value(input) {
                     Ok(&Ident(ref keyword)) => {
                         match keyword.as_slice().to_ascii_lower().as_slice() {
@@ -660,12 +707,94 @@ pub mod longhands {
                     match component_value {
                         &QuotedString(ref value)
                         => content.push(StringContent(value.clone())),
+                        &Function(ref name, ref arguments)
+                        if name.as_slice().eq_ignore_ascii_case("counter")
+                        => content.push(try!(parse_counter(arguments.as_slice(), false, base_url))),
+                        &Function(ref name, ref arguments)
+                        if name.as_slice().eq_ignore_ascii_case("counters")
+                        => content.push(try!(parse_counter(arguments.as_slice(), true, base_url))),
                         _ => return Err(())  // invalid/unsupported value
                     }
                 }
                 Ok(Content(content))
             }
     </%self:longhand>
+
+    <%def name="counter_list(name, default_value)">
+        <%self:longhand name="${name}">
+            pub use super::computed_as_specified as to_computed_value;
+            pub mod computed_value {
+                /// Each counter name with the value to reset it to or increment it by.
+                pub type T = Vec<(String, i32)>;
+            }
+            pub type SpecifiedValue = computed_value::T;
+            #[inline] pub fn get_initial_value() -> computed_value::T { vec!() }
+
+            // none | [ <identifier> <integer>? ]+
+            pub fn parse(input: &[ComponentValue], _base_url: &Url) -> Result<SpecifiedValue, ()> {
+                match one_component_value(input).and_then(get_ident_lower) {
+                    Ok(ref keyword) if keyword.as_slice() == "none" => return Ok(vec!()),
+                    _ => ()
+                }
+                let mut counters = vec!();
+                let mut iter = BufferedIter::new(input.skip_whitespace());
+                loop {
+                    let name = match iter.next() {
+                        Some(&Ident(ref name)) => name.clone(),
+                        Some(_) => return Err(()),
+                        None => break,
+                    };
+                    let value = match iter.next() {
+                        Some(&Number(NumericValue { int_value: Some(value), .. })) => {
+                            value as i32
+                        }
+                        Some(other) => {
+                            iter.push_back(other);
+                            ${default_value}
+                        }
+                        None => ${default_value},
+                    };
+                    counters.push((name, value))
+                }
+                if counters.is_empty() {
+                    return Err(())
+                }
+                Ok(counters)
+            }
+        </%self:longhand>
+    </%def>
+
+    ${counter_list("counter-reset", "0")}
+    ${counter_list("counter-increment", "1")}
+
+    ${new_style_struct("List", is_inherited=True)}
+
+    ${single_keyword("list-style-position", "outside inside")}
+
+    ${single_keyword("list-style-type", "disc none circle square decimal decimal-leading-zero " +
+                     "lower-roman upper-roman lower-greek lower-latin upper-latin " +
+                     "lower-alpha upper-alpha")}
+
+    <%self:single_component_value name="list-style-image">
+        pub use super::computed_as_specified as to_computed_value;
+        pub mod computed_value {
+            use url::Url;
+            pub type T = Option<Url>;
+        }
+        pub type SpecifiedValue = computed_value::T;
+        #[inline] pub fn get_initial_value() -> computed_value::T { None }
+        pub fn from_component_value(component_value: &ComponentValue, base_url: &Url)
+                                    -> Result<SpecifiedValue, ()> {
+            match component_value {
+                &ast::URL(ref url) => Ok(Some(super::super::common_types::parse_url(
+                    url.as_slice(), base_url))),
+                &ast::Ident(ref value) if value.as_slice().eq_ignore_ascii_case("none") => {
+                    Ok(None)
+                }
+                _ => Err(()),
+            }
+        }
+    </%self:single_component_value>
     // CSS 2.1, Section 13 - Paged media
 
     // CSS 2.1, Section 14 - Colors and Backgrounds
@@ -917,7 +1046,6 @@ pub mod longhands {
 
 
     ${single_keyword("font-style", "normal italic oblique")}
-    ${single_keyword("font-variant", "normal small-caps")}
 
     <%self:single_component_value name="font-weight">
         #[deriving(Clone)]
@@ -1049,12 +1177,230 @@ pub mod longhands {
         }
     </%self:single_component_value>
 
+    // CSS Fonts Level 3, Section 6 - Font feature properties
+
+    ${single_keyword("font-kerning", "auto normal none")}
+
+    <%self:longhand name="font-variant-ligatures">
+        pub use super::computed_as_specified as to_computed_value;
+        /// Whether each kind of ligature is turned on or off, or `None` to leave it to the font.
+        #[deriving(PartialEq, Clone)]
+        pub struct SpecifiedValue {
+            pub common_ligatures: Option<bool>,
+            pub discretionary_ligatures: Option<bool>,
+            pub historical_ligatures: Option<bool>,
+            pub contextual_alternates: Option<bool>,
+        }
+        pub mod computed_value {
+            pub type T = super::SpecifiedValue;
+            #[allow(non_upper_case_globals)]
+            pub const normal: T = super::SpecifiedValue {
+                common_ligatures: None,
+                discretionary_ligatures: None,
+                historical_ligatures: None,
+                contextual_alternates: None,
+            };
+            #[allow(non_upper_case_globals)]
+            pub const none: T = super::SpecifiedValue {
+                common_ligatures: Some(false),
+                discretionary_ligatures: Some(false),
+                historical_ligatures: Some(false),
+                contextual_alternates: Some(false),
+            };
+        }
+        #[inline] pub fn get_initial_value() -> computed_value::T {
+            normal
+        }
+        /// Applies one of the keywords after `normal` and `none` in the grammar below. Each kind
+        /// of ligature may only be given once.
+        pub fn parse_keyword(value: &mut SpecifiedValue, keyword: &str) -> Result<(), ()> {
+            let (setting, on) = match keyword {
+                "common-ligatures" => (&mut value.common_ligatures, true),
+                "no-common-ligatures" => (&mut value.common_ligatures, false),
+                "discretionary-ligatures" => (&mut value.discretionary_ligatures, true),
+                "no-discretionary-ligatures" => (&mut value.discretionary_ligatures, false),
+                "historical-ligatures" => (&mut value.historical_ligatures, true),
+                "no-historical-ligatures" => (&mut value.historical_ligatures, false),
+                "contextual" => (&mut value.contextual_alternates, true),
+                "no-contextual" => (&mut value.contextual_alternates, false),
+                _ => return Err(()),
+            };
+            if setting.is_some() {
+                return Err(())
+            }
+            *setting = Some(on);
+            Ok(())
+        }
+        /// normal | none | [ <common-lig-values> || <discretionary-lig-values> ||
+        ///                   <historical-lig-values> || <contextual-alt-values> ]
+        pub fn parse(input: &[ComponentValue], _base_url: &Url) -> Result<SpecifiedValue, ()> {
+            match one_component_value(input).and_then(get_ident_lower) {
+                Ok(ref keyword) if keyword.as_slice() == "normal" => return Ok(normal),
+                Ok(ref keyword) if keyword.as_slice() == "none" => return Ok(none),
+                _ => {}
+            }
+            let mut result = normal;
+            let mut empty = true;
+            for component_value in input.skip_whitespace() {
+                let keyword = try!(get_ident_lower(component_value));
+                try!(parse_keyword(&mut result, keyword.as_slice()));
+                empty = false;
+            }
+            if !empty { Ok(result) } else { Err(()) }
+        }
+    </%self:longhand>
+
+    ${single_keyword("font-variant-caps",
+                     "normal small-caps all-small-caps petite-caps all-petite-caps unicase " +
+                     "titling-caps")}
+
+    <%self:longhand name="font-variant-numeric">
+        pub use super::computed_as_specified as to_computed_value;
+        /// The numeric glyphs asked for, with `None` leaving the choice to the font.
+        #[deriving(PartialEq, Clone)]
+        pub struct SpecifiedValue {
+            pub figure_style: Option<FigureStyle>,
+            pub figure_spacing: Option<FigureSpacing>,
+            pub fractions: Option<Fractions>,
+            pub ordinal: bool,
+            pub slashed_zero: bool,
+        }
+        pub mod computed_value {
+            #[deriving(PartialEq, Clone)]
+            pub enum FigureStyle {
+                LiningNums,
+                OldstyleNums,
+            }
+            #[deriving(PartialEq, Clone)]
+            pub enum FigureSpacing {
+                ProportionalNums,
+                TabularNums,
+            }
+            #[deriving(PartialEq, Clone)]
+            pub enum Fractions {
+                DiagonalFractions,
+                StackedFractions,
+            }
+            pub type T = super::SpecifiedValue;
+            #[allow(non_upper_case_globals)]
+            pub const normal: T = super::SpecifiedValue {
+                figure_style: None,
+                figure_spacing: None,
+                fractions: None,
+                ordinal: false,
+                slashed_zero: false,
+            };
+        }
+        #[inline] pub fn get_initial_value() -> computed_value::T {
+            normal
+        }
+        /// Applies one of the keywords after `normal` in the grammar below. Each group of
+        /// keywords may only be used once.
+        pub fn parse_keyword(value: &mut SpecifiedValue, keyword: &str) -> Result<(), ()> {
+            fn set<T>(setting: &mut Option<T>, new_value: T) -> Result<(), ()> {
+                if setting.is_some() {
+                    return Err(())
+                }
+                *setting = Some(new_value);
+                Ok(())
+            }
+            match keyword {
+                "lining-nums" => set(&mut value.figure_style, LiningNums),
+                "oldstyle-nums" => set(&mut value.figure_style, OldstyleNums),
+                "proportional-nums" => set(&mut value.figure_spacing, ProportionalNums),
+                "tabular-nums" => set(&mut value.figure_spacing, TabularNums),
+                "diagonal-fractions" => set(&mut value.fractions, DiagonalFractions),
+                "stacked-fractions" => set(&mut value.fractions, StackedFractions),
+                "ordinal" if !value.ordinal => { value.ordinal = true; Ok(()) }
+                "slashed-zero" if !value.slashed_zero => { value.slashed_zero = true; Ok(()) }
+                _ => Err(()),
+            }
+        }
+        /// normal | [ <numeric-figure-values> || <numeric-spacing-values> ||
+        ///            <numeric-fraction-values> || ordinal || slashed-zero ]
+        pub fn parse(input: &[ComponentValue], _base_url: &Url) -> Result<SpecifiedValue, ()> {
+            match one_component_value(input).and_then(get_ident_lower) {
+                Ok(ref keyword) if keyword.as_slice() == "normal" => return Ok(normal),
+                _ => {}
+            }
+            let mut result = normal;
+            let mut empty = true;
+            for component_value in input.skip_whitespace() {
+                let keyword = try!(get_ident_lower(component_value));
+                try!(parse_keyword(&mut result, keyword.as_slice()));
+                empty = false;
+            }
+            if !empty { Ok(result) } else { Err(()) }
+        }
+    </%self:longhand>
+
+    <%self:longhand name="font-feature-settings">
+        pub use super::computed_as_specified as to_computed_value;
+        pub mod computed_value {
+            /// Each OpenType feature tag with the value to shape with, in order. Later settings
+            /// of a feature override earlier ones.
+            pub type T = Vec<(String, u32)>;
+        }
+        pub type SpecifiedValue = computed_value::T;
+        #[inline] pub fn get_initial_value() -> computed_value::T { vec!() }
+
+        /// normal | <feature-tag-value>#
+        pub fn parse(input: &[ComponentValue], _base_url: &Url) -> Result<SpecifiedValue, ()> {
+            match one_component_value(input).and_then(get_ident_lower) {
+                Ok(ref keyword) if keyword.as_slice() == "normal" => return Ok(vec!()),
+                _ => ()
+            }
+            parse_slice_comma_separated(input, parse_one_feature)
+        }
+
+        /// <string> [ <integer> | on | off ]?
+        fn parse_one_feature<'a>(iter: ParserIter) -> Result<(String, u32), ()> {
+            let tag = match iter.next() {
+                Some(&QuotedString(ref tag)) => tag.clone(),
+                _ => return Err(()),
+            };
+            // Feature tags are four printable ASCII characters.
+            if tag.len() != 4 || tag.as_slice().chars().any(|c| c < ' ' || c > '~') {
+                return Err(())
+            }
+            let value = match iter.next() {
+                Some(&Number(NumericValue { int_value: Some(value), .. })) if value >= 0 => {
+                    value as u32
+                }
+                Some(&Ident(ref keyword)) if keyword.as_slice().eq_ignore_ascii_case("on") => 1,
+                Some(&Ident(ref keyword)) if keyword.as_slice().eq_ignore_ascii_case("off") => 0,
+                Some(other) => {
+                    iter.push_back(other);
+                    1
+                }
+                None => 1,
+            };
+            Ok((tag, value))
+        }
+    </%self:longhand>
+
+    // The language of the text, which the shaper uses to pick language-specific glyphs. It is
+    // set from the `lang` attribute, and authors can't set it.
+    <%self:longhand name="-servo-lang">
+        pub use super::computed_as_specified as to_computed_value;
+        pub mod computed_value {
+            /// The language tag of the nearest inclusive ancestor with a `lang` attribute.
+            pub type T = Option<String>;
+        }
+        pub type SpecifiedValue = computed_value::T;
+        #[inline] pub fn get_initial_value() -> computed_value::T { None }
+
+        pub fn parse(_input: &[ComponentValue], _base_url: &Url) -> Result<SpecifiedValue, ()> {
+            Err(())
+        }
+    </%self:longhand>
+
     // CSS 2.1, Section 16 - Text
 
     ${new_style_struct("InheritedText", is_inherited=True)}
 
-    // TODO: initial value should be 'start' (CSS Text Level 3, direction-dependent.)
-    ${single_keyword("text-align", "left right center justify")}
+    // CSS Text Level 3: `start` and `end` are resolved against `direction` during layout.
+    ${single_keyword("text-align", "start end left right center justify")}
 
     ${new_style_struct("Text", is_inherited=False)}
 
@@ -1108,6 +1454,10 @@ pub mod longhands {
         }
     </%self:longhand>
 
+    // CSS Writing Modes Level 3, Section 2.2
+    ${single_keyword("unicode-bidi",
+                     "normal embed isolate bidi-override isolate-override plaintext")}
+
     ${switch_to_style_struct("InheritedText")}
 
     <%self:longhand name="-servo-text-decorations-in-effect"
@@ -1234,7 +1584,444 @@ pub mod longhands {
         }
         fn from_component_value(input: &ComponentValue, _: &Url) -> Result<SpecifiedValue,()> {
             match *input {
-                Number(ref value) => Ok(value.value),
+                Number(ref value) => Ok(value.value),
+                _ => Err(())
+            }
+        }
+    </%self:single_component_value>
+
+    // CSS Transforms (2D only)
+    // http://dev.w3.org/csswg/css-transforms/
+    <%self:longhand name="transform">
+        pub mod computed_value {
+            use super::super::CSSFloat;
+            use super::super::super::common_types::computed::{Angle, LengthOrPercentage};
+
+            /// One transform function. Percentages in translations are resolved against the
+            /// border box at layout time.
+            #[deriving(Clone, PartialEq, Show)]
+            pub enum TransformOperation {
+                Matrix(CSSFloat, CSSFloat, CSSFloat, CSSFloat, CSSFloat, CSSFloat),
+                Translate(LengthOrPercentage, LengthOrPercentage),
+                Scale(CSSFloat, CSSFloat),
+                Rotate(Angle),
+                Skew(Angle, Angle),
+            }
+
+            /// The transform functions in the order they were specified. `none` is the empty
+            /// list.
+            pub type T = Vec<TransformOperation>;
+        }
+
+        #[deriving(Clone)]
+        pub enum SpecifiedOperation {
+            SpecifiedMatrix(CSSFloat, CSSFloat, CSSFloat, CSSFloat, CSSFloat, CSSFloat),
+            SpecifiedTranslate(specified::LengthOrPercentage, specified::LengthOrPercentage),
+            SpecifiedScale(CSSFloat, CSSFloat),
+            SpecifiedRotate(specified::Angle),
+            SpecifiedSkew(specified::Angle, specified::Angle),
+        }
+
+        pub type SpecifiedValue = Vec<SpecifiedOperation>;
+
+        #[inline]
+        pub fn get_initial_value() -> computed_value::T {
+            vec!()
+        }
+
+        pub fn to_computed_value(value: SpecifiedValue, context: &computed::Context)
+                                 -> computed_value::T {
+            value.into_iter().map(|operation| {
+                match operation {
+                    SpecifiedMatrix(a, b, c, d, e, f) => Matrix(a, b, c, d, e, f),
+                    SpecifiedTranslate(tx, ty) => {
+                        Translate(computed::compute_LengthOrPercentage(tx, context),
+                                  computed::compute_LengthOrPercentage(ty, context))
+                    }
+                    SpecifiedScale(sx, sy) => Scale(sx, sy),
+                    SpecifiedRotate(angle) => Rotate(angle),
+                    SpecifiedSkew(ax, ay) => Skew(ax, ay),
+                }
+            }).collect()
+        }
+
+        fn parse_number(input: &ComponentValue) -> Result<CSSFloat, ()> {
+            match *input {
+                Number(ref value) => Ok(value.value),
+                _ => Err(()),
+            }
+        }
+
+        /// Parses the comma-separated arguments of a transform function, which must number
+        /// between `min` and `max`.
+        fn parse_arguments<A>(arguments: &[ComponentValue],
+                              min: uint,
+                              max: uint,
+                              parse_one: |&ComponentValue| -> Result<A, ()>)
+                              -> Result<Vec<A>, ()> {
+            let mut values = vec!();
+            let mut iter = arguments.skip_whitespace();
+            loop {
+                match iter.next() {
+                    Some(value) => values.push(try!(parse_one(value))),
+                    None => return Err(()),
+                }
+                match iter.next() {
+                    Some(&Comma) => {}
+                    Some(_) => return Err(()),
+                    None => break,
+                }
+            }
+            if values.len() < min || values.len() > max {
+                return Err(())
+            }
+            Ok(values)
+        }
+
+        fn parse_operation(name: &str, arguments: &[ComponentValue])
+                           -> Result<SpecifiedOperation, ()> {
+            let zero = specified::LP_Length(specified::Au_(Au(0)));
+            match name.to_ascii_lower().as_slice() {
+                "matrix" => {
+                    let m = try!(parse_arguments(arguments, 6, 6, parse_number));
+                    Ok(SpecifiedMatrix(m[0], m[1], m[2], m[3], m[4], m[5]))
+                }
+                "translate" => {
+                    let t = try!(parse_arguments(arguments, 1, 2,
+                                                 specified::LengthOrPercentage::parse));
+                    Ok(SpecifiedTranslate(t[0].clone(),
+                                          if t.len() > 1 { t[1].clone() } else { zero }))
+                }
+                "translatex" => {
+                    let t = try!(parse_arguments(arguments, 1, 1,
+                                                 specified::LengthOrPercentage::parse));
+                    Ok(SpecifiedTranslate(t[0].clone(), zero))
+                }
+                "translatey" => {
+                    let t = try!(parse_arguments(arguments, 1, 1,
+                                                 specified::LengthOrPercentage::parse));
+                    Ok(SpecifiedTranslate(zero, t[0].clone()))
+                }
+                "scale" => {
+                    let s = try!(parse_arguments(arguments, 1, 2, parse_number));
+                    Ok(SpecifiedScale(s[0], if s.len() > 1 { s[1] } else { s[0] }))
+                }
+                "scalex" => {
+                    let s = try!(parse_arguments(arguments, 1, 1, parse_number));
+                    Ok(SpecifiedScale(s[0], 1.0))
+                }
+                "scaley" => {
+                    let s = try!(parse_arguments(arguments, 1, 1, parse_number));
+                    Ok(SpecifiedScale(1.0, s[0]))
+                }
+                "rotate" => {
+                    let a = try!(parse_arguments(arguments, 1, 1, specified::Angle::parse));
+                    Ok(SpecifiedRotate(a[0]))
+                }
+                "skew" => {
+                    let a = try!(parse_arguments(arguments, 1, 2, specified::Angle::parse));
+                    Ok(SpecifiedSkew(a[0], if a.len() > 1 { a[1] } else { specified::Angle(0.0) }))
+                }
+                "skewx" => {
+                    let a = try!(parse_arguments(arguments, 1, 1, specified::Angle::parse));
+                    Ok(SpecifiedSkew(a[0], specified::Angle(0.0)))
+                }
+                "skewy" => {
+                    let a = try!(parse_arguments(arguments, 1, 1, specified::Angle::parse));
+                    Ok(SpecifiedSkew(specified::Angle(0.0), a[0]))
+                }
+                _ => Err(()),
+            }
+        }
+
+        pub fn parse(input: &[ComponentValue], _: &Url) -> Result<SpecifiedValue, ()> {
+            match one_component_value(input) {
+                Ok(&Ident(ref value)) if value.as_slice().eq_ignore_ascii_case("none") => {
+                    return Ok(vec!())
+                }
+                _ => {}
+            }
+            let mut operations = vec!();
+            for component_value in input.skip_whitespace() {
+                match *component_value {
+                    Function(ref name, ref arguments) => {
+                        operations.push(try!(parse_operation(name.as_slice(),
+                                                             arguments.as_slice())))
+                    }
+                    _ => return Err(()),
+                }
+            }
+            if operations.is_empty() {
+                return Err(())
+            }
+            Ok(operations)
+        }
+    </%self:longhand>
+
+    <%self:longhand name="transform-origin">
+        pub use super::background_position::{SpecifiedValue, to_computed_value, parse};
+        pub mod computed_value {
+            pub use super::super::background_position::computed_value::T;
+        }
+
+        #[inline]
+        pub fn get_initial_value() -> computed_value::T {
+            computed_value::T {
+                horizontal: computed::LP_Percentage(0.5),
+                vertical: computed::LP_Percentage(0.5),
+            }
+        }
+    </%self:longhand>
+
+    // CSS Transitions
+    // http://dev.w3.org/csswg/css-transitions/
+    ${new_style_struct("Animation", is_inherited=False)}
+
+    <%def name="time_list(name, non_negative)">
+        <%self:longhand name="${name}">
+            pub use super::computed_as_specified as to_computed_value;
+            pub type SpecifiedValue = computed_value::T;
+            pub mod computed_value {
+                pub type T = Vec<super::super::computed::Time>;
+            }
+            #[inline] pub fn get_initial_value() -> computed_value::T {
+                vec![specified::Time(0.0)]
+            }
+            pub fn parse(input: &[ComponentValue], _base_url: &Url) -> Result<SpecifiedValue, ()> {
+                parse_slice_comma_separated(input, |iter| {
+                    iter.next_as_result().and_then(parse_one)
+                })
+            }
+            pub fn parse_one(value: &ComponentValue) -> Result<specified::Time, ()> {
+                specified::Time::parse${"_non_negative" if non_negative else ""}(value)
+            }
+        </%self:longhand>
+    </%def>
+
+    <%def name="timing_function_list(name)">
+        <%self:longhand name="${name}">
+            pub use super::computed_as_specified as to_computed_value;
+            pub type SpecifiedValue = computed_value::T;
+            pub mod computed_value {
+                pub type T = Vec<super::super::computed::TimingFunction>;
+            }
+            #[inline] pub fn get_initial_value() -> computed_value::T {
+                vec![specified::TimingFunction::ease()]
+            }
+            pub fn parse(input: &[ComponentValue], _base_url: &Url) -> Result<SpecifiedValue, ()> {
+                parse_slice_comma_separated(input, |iter| {
+                    iter.next_as_result().and_then(specified::TimingFunction::parse)
+                })
+            }
+        </%self:longhand>
+    </%def>
+
+    <%def name="keyword_list(name, values)">
+        <%self:longhand name="${name}">
+            pub use super::computed_as_specified as to_computed_value;
+            pub mod computed_value {
+                #[allow(non_camel_case_types)]
+                #[deriving(PartialEq, Clone, Show)]
+                pub enum SingleComputedValue {
+                    % for value in values.split():
+                        ${to_rust_ident(value)},
+                    % endfor
+                }
+                pub type T = Vec<SingleComputedValue>;
+            }
+            pub type SpecifiedValue = computed_value::T;
+            #[inline] pub fn get_initial_value() -> computed_value::T {
+                vec![${to_rust_ident(values.split()[0])}]
+            }
+            pub fn parse(input: &[ComponentValue], _base_url: &Url) -> Result<SpecifiedValue, ()> {
+                parse_slice_comma_separated(input, |iter| {
+                    iter.next_as_result().and_then(parse_one)
+                })
+            }
+            pub fn parse_one(value: &ComponentValue) -> Result<SingleComputedValue, ()> {
+                get_ident_lower(value).and_then(|keyword| {
+                    match keyword.as_slice() {
+                        % for value in values.split():
+                            "${value}" => Ok(${to_rust_ident(value)}),
+                        % endfor
+                        _ => Err(()),
+                    }
+                })
+            }
+        </%self:longhand>
+    </%def>
+
+    <%self:longhand name="transition-property">
+        pub use super::computed_as_specified as to_computed_value;
+        pub mod computed_value {
+            #[deriving(PartialEq, Clone, Show)]
+            pub enum SingleComputedValue {
+                AllProperties,
+                /// A property name, lower-cased. It may not be the name of an animatable
+                /// property, or of any property, but still takes a place in the lists of the
+                /// other `transition-*` properties.
+                NamedProperty(String),
+            }
+            /// `none` is the empty list.
+            pub type T = Vec<SingleComputedValue>;
+        }
+        pub type SpecifiedValue = computed_value::T;
+        #[inline] pub fn get_initial_value() -> computed_value::T {
+            vec![AllProperties]
+        }
+        pub fn parse(input: &[ComponentValue], _base_url: &Url) -> Result<SpecifiedValue, ()> {
+            match one_component_value(input).and_then(get_ident_lower) {
+                Ok(ref keyword) if keyword.as_slice() == "none" => return Ok(vec!()),
+                _ => {}
+            }
+            parse_slice_comma_separated(input, |iter| {
+                iter.next_as_result().and_then(parse_one)
+            })
+        }
+        pub fn parse_one(value: &ComponentValue) -> Result<SingleComputedValue, ()> {
+            get_ident_lower(value).and_then(|name| {
+                match name.as_slice() {
+                    "all" => Ok(AllProperties),
+                    // Only valid on their own.
+                    "none" | "initial" | "inherit" | "unset" => Err(()),
+                    _ => Ok(NamedProperty(name.clone())),
+                }
+            })
+        }
+    </%self:longhand>
+
+    ${time_list("transition-duration", non_negative=True)}
+    ${timing_function_list("transition-timing-function")}
+    ${time_list("transition-delay", non_negative=False)}
+
+    // CSS Animations
+    // http://dev.w3.org/csswg/css-animations/
+
+    <%self:longhand name="animation-name">
+        pub use super::computed_as_specified as to_computed_value;
+        pub mod computed_value {
+            /// `None` for `none`, which still takes a place in the lists of the other
+            /// `animation-*` properties.
+            pub type T = Vec<Option<String>>;
+        }
+        pub type SpecifiedValue = computed_value::T;
+        #[inline] pub fn get_initial_value() -> computed_value::T {
+            vec![None]
+        }
+        pub fn parse(input: &[ComponentValue], _base_url: &Url) -> Result<SpecifiedValue, ()> {
+            parse_slice_comma_separated(input, |iter| {
+                iter.next_as_result().and_then(parse_one)
+            })
+        }
+        /// Keyframes names are case-sensitive.
+        pub fn parse_one(value: &ComponentValue) -> Result<Option<String>, ()> {
+            match *value {
+                Ident(ref name) => {
+                    match name.as_slice().to_ascii_lower().as_slice() {
+                        "none" => Ok(None),
+                        "initial" | "inherit" | "unset" => Err(()),
+                        _ => Ok(Some(name.clone())),
+                    }
+                }
+                QuotedString(ref name) => Ok(Some(name.clone())),
+                _ => Err(()),
+            }
+        }
+    </%self:longhand>
+
+    ${time_list("animation-duration", non_negative=True)}
+    ${timing_function_list("animation-timing-function")}
+    ${time_list("animation-delay", non_negative=False)}
+
+    <%self:longhand name="animation-iteration-count">
+        pub use super::computed_as_specified as to_computed_value;
+        pub mod computed_value {
+            use super::super::CSSFloat;
+            #[deriving(PartialEq, Clone, Show)]
+            pub enum SingleComputedValue {
+                Infinite,
+                Count(CSSFloat),
+            }
+            pub type T = Vec<SingleComputedValue>;
+        }
+        pub type SpecifiedValue = computed_value::T;
+        #[inline] pub fn get_initial_value() -> computed_value::T {
+            vec![Count(1.0)]
+        }
+        pub fn parse(input: &[ComponentValue], _base_url: &Url) -> Result<SpecifiedValue, ()> {
+            parse_slice_comma_separated(input, |iter| {
+                iter.next_as_result().and_then(parse_one)
+            })
+        }
+        pub fn parse_one(value: &ComponentValue) -> Result<SingleComputedValue, ()> {
+            match *value {
+                Number(ref value) if value.value >= 0.0 => Ok(Count(value.value)),
+                Ident(ref value) if value.as_slice().eq_ignore_ascii_case("infinite") => {
+                    Ok(Infinite)
+                }
+                _ => Err(()),
+            }
+        }
+    </%self:longhand>
+
+    ${keyword_list("animation-direction", "normal reverse alternate alternate-reverse")}
+    ${keyword_list("animation-fill-mode", "none forwards backwards both")}
+    ${keyword_list("animation-play-state", "running paused")}
+
+    // CSS Flexible Box Layout
+    // http://dev.w3.org/csswg/css-flexbox/
+    ${new_style_struct("Flex", is_inherited=False)}
+
+    ${single_keyword("flex-direction", "row row-reverse column column-reverse")}
+    ${single_keyword("flex-wrap", "nowrap wrap wrap-reverse")}
+
+    <%def name="flex_factor(name, initial_value)">
+        <%self:single_component_value name="${name}">
+            pub use super::computed_as_specified as to_computed_value;
+            pub type SpecifiedValue = computed_value::T;
+            pub mod computed_value {
+                use super::super::CSSFloat;
+                pub type T = CSSFloat;
+            }
+            #[inline] pub fn get_initial_value() -> computed_value::T { ${initial_value} }
+            pub fn from_component_value(input: &ComponentValue, _: &Url)
+                                        -> Result<SpecifiedValue, ()> {
+                match *input {
+                    Number(ref value) if value.value >= 0.0 => Ok(value.value),
+                    _ => Err(())
+                }
+            }
+        </%self:single_component_value>
+    </%def>
+
+    ${flex_factor("flex-grow", "0.0")}
+    ${flex_factor("flex-shrink", "1.0")}
+    ${predefined_type("flex-basis", "LengthOrPercentageOrAuto",
+                      "computed::LPA_Auto",
+                      "parse_non_negative")}
+
+    ${single_keyword("justify-content", "flex-start flex-end center space-between space-around")}
+    ${single_keyword("align-items", "stretch flex-start flex-end center baseline")}
+    ${single_keyword("align-self", "auto stretch flex-start flex-end center baseline")}
+    ${single_keyword("align-content",
+                     "stretch flex-start flex-end center space-between space-around")}
+
+    <%self:single_component_value name="order">
+        pub use super::computed_as_specified as to_computed_value;
+        pub type SpecifiedValue = computed_value::T;
+        pub mod computed_value {
+            pub type T = i32;
+        }
+        #[inline]
+        pub fn get_initial_value() -> computed_value::T {
+            0
+        }
+        fn from_component_value(input: &ComponentValue, _: &Url) -> Result<SpecifiedValue,()> {
+            match *input {
+                Number(NumericValue {
+                    int_value: Some(value),
+                    ..
+                }) => Ok(value as i32),
                 _ => Err(())
             }
         }
@@ -1541,8 +2328,10 @@ pub mod shorthands {
         })
     </%self:shorthand>
 
-    <%self:shorthand name="font" sub_properties="font-style font-variant font-weight
-                                                 font-size line-height font-family">
+    <%self:shorthand name="font" sub_properties="font-style font-variant-caps font-weight
+                                                 font-size line-height font-family
+                                                 font-kerning font-variant-ligatures
+                                                 font-variant-numeric">
         let mut iter = input.skip_whitespace();
         let mut nb_normals = 0u;
         let mut style = None;
@@ -1573,10 +2362,14 @@ pub mod shorthands {
                     Err(()) => ()
                 }
             }
+            // Only the CSS 2.1 values of `font-variant` may be used here.
             if variant.is_none() {
-                match font_variant::from_component_value(component_value, base_url) {
-                    Ok(v) => { variant = Some(v); continue },
-                    Err(()) => ()
+                match font_variant_caps::from_component_value(component_value, base_url) {
+                    Ok(font_variant_caps::small_caps) => {
+                        variant = Some(font_variant_caps::small_caps);
+                        continue
+                    }
+                    _ => ()
                 }
             }
             match font_size::from_component_value(component_value, base_url) {
@@ -1610,11 +2403,367 @@ pub mod shorthands {
             &mut BufferedIter::new(iter), font_family::parse_one_family));
         Ok(Longhands {
             font_style: style,
-            font_variant: variant,
+            font_variant_caps: variant,
             font_weight: weight,
             font_size: size,
             line_height: line_height,
-            font_family: Some(family)
+            font_family: Some(family),
+            // The font shorthand resets these to their initial values.
+            font_kerning: None,
+            font_variant_ligatures: None,
+            font_variant_numeric: None,
+        })
+    </%self:shorthand>
+
+    <%self:shorthand name="font-variant" sub_properties="font-variant-caps font-variant-ligatures
+                                                         font-variant-numeric">
+        match one_component_value(input).and_then(get_ident_lower) {
+            Ok(ref keyword) if keyword.as_slice() == "normal" => {
+                return Ok(Longhands {
+                    font_variant_caps: None,
+                    font_variant_ligatures: None,
+                    font_variant_numeric: None,
+                })
+            }
+            Ok(ref keyword) if keyword.as_slice() == "none" => {
+                return Ok(Longhands {
+                    font_variant_caps: None,
+                    font_variant_ligatures: Some(font_variant_ligatures::none),
+                    font_variant_numeric: None,
+                })
+            }
+            _ => {}
+        }
+        let mut caps = None;
+        let mut ligatures = font_variant_ligatures::normal;
+        let mut numeric = font_variant_numeric::normal;
+        let mut empty = true;
+        for component_value in input.skip_whitespace() {
+            empty = false;
+            if caps.is_none() {
+                match font_variant_caps::from_component_value(component_value, base_url) {
+                    Ok(font_variant_caps::normal) => return Err(()),
+                    Ok(value) => { caps = Some(value); continue },
+                    Err(()) => ()
+                }
+            }
+            let keyword = try!(get_ident_lower(component_value));
+            if font_variant_ligatures::parse_keyword(&mut ligatures, keyword.as_slice()).is_ok() {
+                continue
+            }
+            try!(font_variant_numeric::parse_keyword(&mut numeric, keyword.as_slice()));
+        }
+        if empty {
+            return Err(())
+        }
+        Ok(Longhands {
+            font_variant_caps: caps,
+            font_variant_ligatures: Some(ligatures),
+            font_variant_numeric: Some(numeric),
+        })
+    </%self:shorthand>
+
+    <%self:shorthand name="transition" sub_properties="transition-property transition-duration
+                                                       transition-timing-function transition-delay">
+        let items: Vec<&[ComponentValue]> = input.split(|value| *value == Comma).collect();
+        let mut properties = vec!();
+        let mut durations = vec!();
+        let mut timing_functions = vec!();
+        let mut delays = vec!();
+        let mut none = false;
+        for item in items.iter() {
+            let mut property = None;
+            let mut duration = None;
+            let mut timing_function = None;
+            let mut delay = None;
+            let mut empty = true;
+            for component_value in item.skip_whitespace() {
+                empty = false;
+                // The first time is the duration, the second one the delay.
+                if duration.is_none() {
+                    match transition_duration::parse_one(component_value) {
+                        Ok(d) => { duration = Some(d); continue },
+                        Err(()) => ()
+                    }
+                }
+                if delay.is_none() {
+                    match transition_delay::parse_one(component_value) {
+                        Ok(d) => { delay = Some(d); continue },
+                        Err(()) => ()
+                    }
+                }
+                if timing_function.is_none() {
+                    match specified::TimingFunction::parse(component_value) {
+                        Ok(f) => { timing_function = Some(f); continue },
+                        Err(()) => ()
+                    }
+                }
+                if property.is_none() && !none {
+                    match get_ident_lower(component_value) {
+                        // `none` is only valid in a single transition.
+                        Ok(ref ident) if ident.as_slice() == "none" && items.len() == 1 => {
+                            none = true;
+                            continue
+                        }
+                        _ => ()
+                    }
+                    match transition_property::parse_one(component_value) {
+                        Ok(p) => { property = Some(p); continue },
+                        Err(()) => ()
+                    }
+                }
+                return Err(())
+            }
+            if empty {
+                return Err(())
+            }
+            properties.push(property.unwrap_or(transition_property::AllProperties));
+            durations.push(duration.unwrap_or(specified::Time(0.0)));
+            timing_functions.push(timing_function.unwrap_or(specified::TimingFunction::ease()));
+            delays.push(delay.unwrap_or(specified::Time(0.0)));
+        }
+        Ok(Longhands {
+            transition_property: Some(if none { vec!() } else { properties }),
+            transition_duration: Some(durations),
+            transition_timing_function: Some(timing_functions),
+            transition_delay: Some(delays),
+        })
+    </%self:shorthand>
+
+    <%self:shorthand name="animation" sub_properties="animation-name animation-duration
+                                                      animation-timing-function animation-delay
+                                                      animation-iteration-count animation-direction
+                                                      animation-fill-mode animation-play-state">
+        let mut names = vec!();
+        let mut durations = vec!();
+        let mut timing_functions = vec!();
+        let mut delays = vec!();
+        let mut iteration_counts = vec!();
+        let mut directions = vec!();
+        let mut fill_modes = vec!();
+        let mut play_states = vec!();
+        for item in input.split(|value| *value == Comma) {
+            let mut name = None;
+            let mut duration = None;
+            let mut timing_function = None;
+            let mut delay = None;
+            let mut iteration_count = None;
+            let mut direction = None;
+            let mut fill_mode = None;
+            let mut play_state = None;
+            let mut empty = true;
+            for component_value in item.skip_whitespace() {
+                empty = false;
+                // The first time is the duration, the second one the delay.
+                if duration.is_none() {
+                    match animation_duration::parse_one(component_value) {
+                        Ok(d) => { duration = Some(d); continue },
+                        Err(()) => ()
+                    }
+                }
+                if delay.is_none() {
+                    match animation_delay::parse_one(component_value) {
+                        Ok(d) => { delay = Some(d); continue },
+                        Err(()) => ()
+                    }
+                }
+                // Keywords of the other properties win over keyframes names.
+                if timing_function.is_none() {
+                    match specified::TimingFunction::parse(component_value) {
+                        Ok(f) => { timing_function = Some(f); continue },
+                        Err(()) => ()
+                    }
+                }
+                if iteration_count.is_none() {
+                    match animation_iteration_count::parse_one(component_value) {
+                        Ok(c) => { iteration_count = Some(c); continue },
+                        Err(()) => ()
+                    }
+                }
+                if direction.is_none() {
+                    match animation_direction::parse_one(component_value) {
+                        Ok(d) => { direction = Some(d); continue },
+                        Err(()) => ()
+                    }
+                }
+                if fill_mode.is_none() {
+                    match animation_fill_mode::parse_one(component_value) {
+                        Ok(m) => { fill_mode = Some(m); continue },
+                        Err(()) => ()
+                    }
+                }
+                if play_state.is_none() {
+                    match animation_play_state::parse_one(component_value) {
+                        Ok(s) => { play_state = Some(s); continue },
+                        Err(()) => ()
+                    }
+                }
+                if name.is_none() {
+                    match animation_name::parse_one(component_value) {
+                        Ok(n) => { name = Some(n); continue },
+                        Err(()) => ()
+                    }
+                }
+                return Err(())
+            }
+            if empty {
+                return Err(())
+            }
+            names.push(name.unwrap_or(None));
+            durations.push(duration.unwrap_or(specified::Time(0.0)));
+            timing_functions.push(timing_function.unwrap_or(specified::TimingFunction::ease()));
+            delays.push(delay.unwrap_or(specified::Time(0.0)));
+            iteration_counts.push(iteration_count.unwrap_or(animation_iteration_count::Count(1.0)));
+            directions.push(direction.unwrap_or(animation_direction::normal));
+            fill_modes.push(fill_mode.unwrap_or(animation_fill_mode::none));
+            play_states.push(play_state.unwrap_or(animation_play_state::running));
+        }
+        Ok(Longhands {
+            animation_name: Some(names),
+            animation_duration: Some(durations),
+            animation_timing_function: Some(timing_functions),
+            animation_delay: Some(delays),
+            animation_iteration_count: Some(iteration_counts),
+            animation_direction: Some(directions),
+            animation_fill_mode: Some(fill_modes),
+            animation_play_state: Some(play_states),
+        })
+    </%self:shorthand>
+
+    <%self:shorthand name="flex-flow" sub_properties="flex-direction flex-wrap">
+        let mut direction = None;
+        let mut wrap = None;
+        let mut any = false;
+        for component_value in input.skip_whitespace() {
+            any = true;
+            if direction.is_none() {
+                match flex_direction::from_component_value(component_value, base_url) {
+                    Ok(d) => { direction = Some(d); continue },
+                    Err(()) => ()
+                }
+            }
+            if wrap.is_none() {
+                match flex_wrap::from_component_value(component_value, base_url) {
+                    Ok(w) => { wrap = Some(w); continue },
+                    Err(()) => ()
+                }
+            }
+            return Err(())
+        }
+        if !any {
+            return Err(())
+        }
+        Ok(Longhands {
+            flex_direction: direction,
+            flex_wrap: wrap,
+        })
+    </%self:shorthand>
+
+    <%self:shorthand name="flex" sub_properties="flex-grow flex-shrink flex-basis">
+        match one_component_value(input).and_then(get_ident_lower) {
+            Ok(ref ident) if ident.as_slice() == "none" => {
+                return Ok(Longhands {
+                    flex_grow: Some(0.0),
+                    flex_shrink: Some(0.0),
+                    flex_basis: Some(specified::LPA_Auto),
+                })
+            }
+            _ => ()
+        }
+
+        let mut grow = None;
+        let mut shrink = None;
+        let mut basis = None;
+        // The shrink factor, if any, must directly follow the grow factor. This also makes a
+        // unitless zero after both factors a basis rather than a factor, as the spec requires.
+        let mut previous_was_grow = false;
+        for component_value in input.skip_whitespace() {
+            match *component_value {
+                Number(ref value) if grow.is_none() && value.value >= 0.0 => {
+                    grow = Some(value.value);
+                    previous_was_grow = true;
+                    continue
+                }
+                Number(ref value) if previous_was_grow && value.value >= 0.0 => {
+                    shrink = Some(value.value);
+                    previous_was_grow = false;
+                    continue
+                }
+                _ => previous_was_grow = false,
+            }
+            if basis.is_none() {
+                match flex_basis::from_component_value(component_value, base_url) {
+                    Ok(b) => { basis = Some(b); continue },
+                    Err(()) => ()
+                }
+            }
+            return Err(())
+        }
+        if grow.is_none() && basis.is_none() {
+            return Err(())
+        }
+        // Omitted factors are 1 and an omitted basis is 0, rather than the initial values.
+        Ok(Longhands {
+            flex_grow: Some(grow.unwrap_or(1.0)),
+            flex_shrink: Some(shrink.unwrap_or(1.0)),
+            flex_basis: Some(basis.unwrap_or(specified::LPA_Percentage(0.0))),
+        })
+    </%self:shorthand>
+
+    <%self:shorthand name="list-style"
+                     sub_properties="list-style-position list-style-image list-style-type">
+        let mut position = None;
+        let mut image = None;
+        let mut style_type = None;
+        // `none` is ambiguous between the image and the type; it is assigned once the other
+        // values are known.
+        let mut nones = 0u;
+        for component_value in input.skip_whitespace() {
+            match get_ident_lower(component_value) {
+                Ok(ref ident) if ident.as_slice() == "none" => {
+                    nones += 1;
+                    continue
+                }
+                _ => ()
+            }
+            if position.is_none() {
+                match list_style_position::from_component_value(component_value, base_url) {
+                    Ok(v) => { position = Some(v); continue },
+                    Err(()) => ()
+                }
+            }
+            if image.is_none() {
+                match list_style_image::from_component_value(component_value, base_url) {
+                    Ok(v) => { image = Some(v); continue },
+                    Err(()) => ()
+                }
+            }
+            if style_type.is_none() {
+                match list_style_type::from_component_value(component_value, base_url) {
+                    Ok(v) => { style_type = Some(v); continue },
+                    Err(()) => ()
+                }
+            }
+            return Err(())
+        }
+        match (nones, image.is_none(), style_type.is_none()) {
+            (0, _, _) => {}
+            // `list-style: none` sets both the image and the type.
+            (1, true, true) | (2, true, true) => {
+                image = Some(None);
+                style_type = Some(list_style_type::none);
+            }
+            (1, true, false) => image = Some(None),
+            (1, false, true) => style_type = Some(list_style_type::none),
+            _ => return Err(()),
+        }
+        if position.is_none() && image.is_none() && style_type.is_none() {
+            return Err(())
+        }
+        Ok(Longhands {
+            list_style_position: position,
+            list_style_image: image,
+            list_style_type: style_type,
         })
     </%self:shorthand>
 
@@ -1665,9 +2814,48 @@ mod property_bit_field {
 
 /// Declarations are stored in reverse order.
 /// Overridden declarations are skipped.
+#[deriving(Clone)]
 pub struct PropertyDeclarationBlock {
     pub important: Arc<Vec<PropertyDeclaration>>,
     pub normal: Arc<Vec<PropertyDeclaration>>,
+    /// The valid declarations as they were written, in source order, for serialization.
+    pub source: Arc<Vec<SourceDeclaration>>,
+}
+
+#[deriving(Clone)]
+pub struct SourceDeclaration {
+    pub name: String,
+    pub value: String,
+    pub important: bool,
+}
+
+impl PropertyDeclarationBlock {
+    /// Serializes the declarations, as in `color: red; margin: 0px !important;`.
+    pub fn to_css(&self) -> String {
+        let declarations: Vec<String> = self.source.iter().map(|declaration| {
+            format!("{}: {}{};", declaration.name, declaration.value,
+                    if declaration.important { " !important" } else { "" })
+        }).collect();
+        declarations.connect(" ")
+    }
+
+    /// The declaration of the property as it was written: the last important one, or else the
+    /// last one. Shorthands are only found if they were written as such.
+    pub fn get_source_declaration<'a>(&'a self, name: &str) -> Option<&'a SourceDeclaration> {
+        let name = if custom_properties::is_custom_property_name(name) {
+            name.to_string()
+        } else {
+            name.to_ascii_lower()
+        };
+        let mut result: Option<&SourceDeclaration> = None;
+        for declaration in self.source.iter() {
+            if declaration.name == name &&
+                    (declaration.important || !result.map_or(false, |result| result.important)) {
+                result = Some(declaration)
+            }
+        }
+        result
+    }
 }
 
 
@@ -1681,6 +2869,7 @@ pub fn parse_property_declaration_list<I: Iterator<Node>>(input: I, base_url: &U
     let mut normal_declarations = vec!();
     let mut important_seen = PropertyBitField::new();
     let mut normal_seen = PropertyBitField::new();
+    let mut source_declarations = vec!();
     let items: Vec<DeclarationListItem> =
         ErrorLoggerIterator(parse_declaration_list(input)).collect();
     for item in items.into_iter().rev() {
@@ -1703,14 +2892,25 @@ pub fn parse_property_declaration_list<I: Iterator<Node>>(input: I, base_url: &U
                         n, v.iter().to_css()).as_slice()),
                     InvalidValue => log_css_error(l, format!(
                         "Invalid value: {}:{}", n, v.iter().to_css()).as_slice()),
-                    ValidOrIgnoredDeclaration => (),
+                    ValidOrIgnoredDeclaration => source_declarations.push(SourceDeclaration {
+                        // Custom property names are case-sensitive.
+                        name: if custom_properties::is_custom_property_name(n.as_slice()) {
+                            n.clone()
+                        } else {
+                            n.as_slice().to_ascii_lower()
+                        },
+                        value: v.iter().to_css().as_slice().trim().to_string(),
+                        important: i,
+                    }),
                 }
             }
         }
     }
+    source_declarations.reverse();
     PropertyDeclarationBlock {
         important: Arc::new(important_declarations),
         normal: Arc::new(normal_declarations),
+        source: Arc::new(source_declarations),
     }
 }
 
@@ -1750,6 +2950,64 @@ pub enum PropertyDeclaration {
     % for property in LONGHANDS:
         ${property.camel_case}Declaration(DeclaredValue<longhands::${property.ident}::SpecifiedValue>),
     % endfor
+    /// A custom property, whose name starts with `--`.
+    CustomDeclaration(String, DeclaredValue<custom_properties::SpecifiedValue>),
+    /// A longhand, named here, whose value uses `var()` and can only be parsed once custom
+    /// properties are computed.
+    WithVariablesDeclaration(&'static str, Arc<UnparsedValue>),
+}
+
+
+/// A value that uses `var()`, for a longhand or for all the longhands of a shorthand.
+pub struct UnparsedValue {
+    /// The shorthand the value was written for, if any. The value is parsed for it and each of
+    /// its longhands picks its own part.
+    pub shorthand: Option<&'static str>,
+    pub value: Vec<ComponentValue>,
+    pub base_url: Url,
+}
+
+impl UnparsedValue {
+    /// Only checks that the `var()` functions are well-formed. The rest of the value can only be
+    /// checked once they are substituted.
+    fn parse(value: &[ComponentValue], shorthand: Option<&'static str>, base_url: &Url)
+             -> Result<Arc<UnparsedValue>, ()> {
+        try!(custom_properties::check_references(value));
+        Ok(Arc::new(UnparsedValue {
+            shorthand: shorthand,
+            value: value.to_vec(),
+            base_url: base_url.clone(),
+        }))
+    }
+
+    /// Substitutes the custom properties and parses the result for the longhand. A value that
+    /// is then invalid, said to be invalid at computed-value time, acts as `unset`.
+    fn substitute(&self, longhand: &'static str,
+                  custom_properties: &Option<Arc<custom_properties::ComputedValuesMap>>)
+                  -> PropertyDeclaration {
+        let mut declarations = vec!();
+        let mut seen = PropertyBitField::new();
+        let custom_properties = custom_properties.as_ref().map(|map| &**map);
+        match custom_properties::substitute(self.value.as_slice(), custom_properties) {
+            Ok(value) => {
+                PropertyDeclaration::parse(self.shorthand.unwrap_or(longhand), value.as_slice(),
+                                           &mut declarations, &self.base_url, &mut seen);
+            }
+            Err(()) => {}
+        }
+        match declarations.into_iter().find(|declaration| {
+            declaration.longhand_name() == Some(longhand)
+        }) {
+            Some(declaration) => declaration,
+            None => {
+                let mut declarations = vec!();
+                let mut seen = PropertyBitField::new();
+                PropertyDeclaration::parse(longhand, &[Ident("unset".to_string())],
+                                           &mut declarations, &self.base_url, &mut seen);
+                declarations.pop().unwrap()
+            }
+        }
+    }
 }
 
 
@@ -1762,10 +3020,34 @@ pub enum PropertyDeclarationParseResult {
 
 
 impl PropertyDeclaration {
+    /// The name of the longhand the declaration is for, `None` for custom properties.
+    pub fn longhand_name(&self) -> Option<&'static str> {
+        match *self {
+            % for property in LONGHANDS:
+                ${property.camel_case}Declaration(_) => Some("${property.name}"),
+            % endfor
+            CustomDeclaration(..) => None,
+            WithVariablesDeclaration(longhand, _) => Some(longhand),
+        }
+    }
+
     pub fn parse(name: &str, value: &[ComponentValue],
                  result_list: &mut Vec<PropertyDeclaration>,
                  base_url: &Url,
                  seen: &mut PropertyBitField) -> PropertyDeclarationParseResult {
+        if custom_properties::is_custom_property_name(name) {
+            // Custom properties are always inherited.
+            let value = match CSSWideKeyword::parse(value) {
+                Ok(InheritKeyword) | Ok(UnsetKeyword) => Inherit,
+                Ok(InitialKeyword) => Initial,
+                Err(()) => match custom_properties::parse(value) {
+                    Ok(value) => SpecifiedValue(value),
+                    Err(()) => return InvalidValue,
+                }
+            };
+            result_list.push(CustomDeclaration(name.to_string(), value));
+            return ValidOrIgnoredDeclaration
+        }
         match name.to_ascii_lower().as_slice() {
             % for property in LONGHANDS:
                 % if property.derived_from is None:
@@ -1778,6 +3060,17 @@ impl PropertyDeclaration {
                         if seen.get_${property.ident}() {
                             return ValidOrIgnoredDeclaration
                         }
+                        if custom_properties::has_references(value) {
+                            return match UnparsedValue::parse(value, None, base_url) {
+                                Ok(value) => {
+                                    seen.set_${property.ident}();
+                                    result_list.push(
+                                        WithVariablesDeclaration("${property.name}", value));
+                                    ValidOrIgnoredDeclaration
+                                }
+                                Err(()) => InvalidValue,
+                            }
+                        }
                         match longhands::${property.ident}::parse_declared(value, base_url) {
                             Ok(value) => {
                                 seen.set_${property.ident}();
@@ -1829,6 +3122,21 @@ impl PropertyDeclaration {
                             % endfor
                             ValidOrIgnoredDeclaration
                         },
+                        Err(()) if custom_properties::has_references(value) => {
+                            match UnparsedValue::parse(value, Some("${shorthand.name}"), base_url) {
+                                Ok(value) => {
+                                    % for sub_property in shorthand.sub_properties:
+                                        if !seen.get_${sub_property.ident}() {
+                                            seen.set_${sub_property.ident}();
+                                            result_list.push(WithVariablesDeclaration(
+                                                "${sub_property.name}", value.clone()));
+                                        }
+                                    % endfor
+                                    ValidOrIgnoredDeclaration
+                                }
+                                Err(()) => InvalidValue,
+                            }
+                        }
                         Err(()) => match shorthands::${shorthand.ident}::parse(value, base_url) {
                             Ok(result) => {
                                 % for sub_property in shorthand.sub_properties:
@@ -1855,6 +3163,17 @@ impl PropertyDeclaration {
 }
 
 
+/// Whether a style rule would use the declaration, which is what `@supports` tests.
+pub fn is_supported_declaration(name: &str, value: &[ComponentValue], base_url: &Url) -> bool {
+    let mut declarations = vec!();
+    let mut seen = PropertyBitField::new();
+    match PropertyDeclaration::parse(name, value, &mut declarations, base_url, &mut seen) {
+        ValidOrIgnoredDeclaration => true,
+        UnknownProperty | ExperimentalProperty | InvalidValue => false,
+    }
+}
+
+
 pub mod style_structs {
     use super::longhands;
 
@@ -1873,6 +3192,8 @@ pub struct ComputedValues {
     % for style_struct in STYLE_STRUCTS:
         ${style_struct.ident}: Arc<style_structs::${style_struct.name}>,
     % endfor
+    /// Inherited from the parent style when none are declared, so usually shared.
+    custom_properties: Option<Arc<custom_properties::ComputedValuesMap>>,
     shareable: bool,
     pub writing_mode: WritingMode,
 }
@@ -1978,6 +3299,14 @@ impl ComputedValues {
         self.font.clone()
     }
 
+    /// The computed value of a custom property, serialized. `None` if the property has its
+    /// initial, invalid, value.
+    pub fn get_custom_property(&self, name: &str) -> Option<String> {
+        self.custom_properties.as_ref().and_then(|map| map.get(&name.to_string())).map(|value| {
+            value.iter().to_css()
+        })
+    }
+
     % for style_struct in STYLE_STRUCTS:
         #[inline]
         pub fn get_${style_struct.name.lower()}
@@ -1988,16 +3317,109 @@ impl ComputedValues {
 }
 
 
+<%
+    ANIMATABLE_LONGHANDS = [LONGHANDS_BY_NAME[name] for name in (
+        ["margin-%s" % side for side in ["top", "right", "bottom", "left"]] +
+        ["padding-%s" % side for side in ["top", "right", "bottom", "left"]] +
+        ["border-%s-width" % side for side in ["top", "right", "bottom", "left"]] +
+        ["border-%s-color" % side for side in ["top", "right", "bottom", "left"]] +
+        ["top", "right", "bottom", "left"] +
+        ["width", "height", "min-width", "min-height", "max-width", "max-height"] +
+        ["color", "background-color", "opacity", "font-size"] +
+        ["flex-grow", "flex-shrink", "flex-basis"]
+    )]
+%>
+
+/// The longhands whose computed values transitions and animations interpolate.
+#[deriving(Clone, PartialEq, Show)]
+pub enum AnimatableLonghand {
+    % for property in ANIMATABLE_LONGHANDS:
+        Animatable${property.camel_case},
+    % endfor
+}
+
+impl AnimatableLonghand {
+    /// All of them, which is what `transition-property: all` stands for.
+    pub fn all() -> Vec<AnimatableLonghand> {
+        vec![
+            % for property in ANIMATABLE_LONGHANDS:
+                Animatable${property.camel_case},
+            % endfor
+        ]
+    }
+
+    /// The animatable longhands a lower-case property name stands for: the longhand itself, or
+    /// those of a shorthand.
+    pub fn from_name(name: &str) -> Vec<AnimatableLonghand> {
+        match name {
+            % for property in ANIMATABLE_LONGHANDS:
+                "${property.name}" => vec![Animatable${property.camel_case}],
+            % endfor
+            % for shorthand in SHORTHANDS:
+                <%
+                    animatable = [p for p in shorthand.sub_properties
+                                  if p in ANIMATABLE_LONGHANDS]
+                %>
+                % if animatable:
+                    "${shorthand.name}" => vec![
+                        % for property in animatable:
+                            Animatable${property.camel_case},
+                        % endfor
+                    ],
+                % endif
+            % endfor
+            _ => vec!(),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            % for property in ANIMATABLE_LONGHANDS:
+                Animatable${property.camel_case} => "${property.name}",
+            % endfor
+        }
+    }
+
+    /// Whether the longhand has different values in the two styles.
+    pub fn differs(&self, from: &ComputedValues, to: &ComputedValues) -> bool {
+        match *self {
+            % for property in ANIMATABLE_LONGHANDS:
+                Animatable${property.camel_case} => {
+                    from.${property.style_struct.ident}.${property.ident} !=
+                        to.${property.style_struct.ident}.${property.ident}
+                }
+            % endfor
+        }
+    }
+
+    /// Sets the longhand in `style` to its value at `progress` between `from` and `to`. Returns
+    /// false, leaving `style` unchanged, if the two values can't be interpolated.
+    pub fn interpolate(&self, style: &mut ComputedValues, from: &ComputedValues,
+                       to: &ComputedValues, progress: CSSFloat) -> bool {
+        match *self {
+            % for property in ANIMATABLE_LONGHANDS:
+                Animatable${property.camel_case} => {
+                    let from = &from.${property.style_struct.ident}.${property.ident};
+                    let to = &to.${property.style_struct.ident}.${property.ident};
+                    match from.interpolate(to, progress) {
+                        Some(value) => {
+                            style.${property.style_struct.ident}.make_unique()
+                                 .${property.ident} = value;
+                            true
+                        }
+                        None => false,
+                    }
+                }
+            % endfor
+        }
+    }
+}
+
+
 /// Return a WritingMode bitflags from the relevant CSS properties.
 fn get_writing_mode(inheritedbox_style: &style_structs::InheritedBox) -> WritingMode {
     use servo_util::logical_geometry;
     let mut flags = WritingMode::empty();
-    match inheritedbox_style.direction {
-        computed_values::direction::ltr => {},
-        computed_values::direction::rtl => {
-            flags.insert(logical_geometry::FLAG_RTL);
-        },
-    }
     match inheritedbox_style.writing_mode {
         computed_values::writing_mode::horizontal_tb => {},
         computed_values::writing_mode::vertical_rl => {
@@ -2008,6 +3430,18 @@ fn get_writing_mode(inheritedbox_style: &style_structs::InheritedBox) -> Writing
             flags.insert(logical_geometry::FLAG_VERTICAL_LR);
         },
     }
+    match inheritedbox_style.direction {
+        computed_values::direction::ltr => {},
+        computed_values::direction::rtl => {
+            // FIXME(#2795): Flows don't know the size of their containers yet, so flow-relative
+            // geometry can't be mapped to physical geometry when the inline axis runs
+            // right-to-left. Until they do, horizontal right-to-left content is laid out with a
+            // left-to-right inline axis and layout handles `direction` itself.
+            if flags.is_vertical() {
+                flags.insert(logical_geometry::FLAG_RTL);
+            }
+        },
+    }
     match inheritedbox_style.text_orientation {
         computed_values::text_orientation::sideways_right => {},
         computed_values::text_orientation::sideways_left => {
@@ -2033,6 +3467,7 @@ lazy_static! {
                 % endfor
             }),
         % endfor
+        custom_properties: None,
         shareable: true,
         writing_mode: WritingMode::empty()
     };
@@ -2044,12 +3479,138 @@ fn initial_writing_mode_is_empty() {
     assert_eq!(get_writing_mode(INITIAL_VALUES.get_inheritedbox()), WritingMode::empty())
 }
 
+#[test]
+fn custom_properties_are_substituted() {
+    use servo_util::geometry::Au;
+    let url = Url::parse("http://localhost").unwrap();
+    let declarations = |css: &str| {
+        DeclarationBlock::from_declarations(parse_style_attribute(css, &url).normal)
+    };
+    let (parent, _) = cascade(&[declarations("--width: 3px; --color: red")], false, None, None);
+    assert_eq!(parent.get_custom_property("--width"), Some("3px".to_string()));
+
+    let (style, cacheable) = cascade(
+        &[declarations("margin: var(--width) var(--missing, 1px); color: var(--color); \
+                        width: var(--missing); --width: 4px")],
+        false, Some(&parent), None);
+    assert!(!cacheable);
+    assert_eq!(style.get_custom_property("--width"), Some("4px".to_string()));
+    assert_eq!(style.get_custom_property("--color"), Some("red".to_string()));
+    assert!(style.get_margin().margin_top == computed::LPA_Length(Au::from_px(4)));
+    assert!(style.get_margin().margin_right == computed::LPA_Length(Au::from_px(1)));
+    assert!(style.get_color().color == RGBA { red: 1., green: 0., blue: 0., alpha: 1. });
+    // Invalid at computed-value time, so the initial value of a non-inherited property.
+    assert!(style.get_box().width == computed::LPA_Auto);
+
+    let anonymous = cascade_anonymous(&style);
+    assert_eq!(anonymous.get_custom_property("--width"), Some("4px".to_string()));
+}
+
+#[test]
+fn transition_and_animation_shorthands() {
+    let url = Url::parse("http://localhost").unwrap();
+    let declarations = |css: &str| {
+        DeclarationBlock::from_declarations(parse_style_attribute(css, &url).normal)
+    };
+    let (style, _) = cascade(
+        &[declarations("transition: margin-left 1s ease-in, opacity 500ms 2s; \
+                        animation: 3s infinite alternate slide, none")],
+        false, None, None);
+    let animation = style.get_animation();
+    assert_eq!(animation.transition_property, vec!(
+        longhands::transition_property::NamedProperty("margin-left".to_string()),
+        longhands::transition_property::NamedProperty("opacity".to_string())));
+    assert_eq!(animation.transition_duration, vec!(computed::Time(1.0), computed::Time(0.5)));
+    assert_eq!(animation.transition_delay, vec!(computed::Time(0.0), computed::Time(2.0)));
+    assert_eq!(animation.transition_timing_function, vec!(
+        computed::CubicBezierTimingFunction(0.42, 0.0, 1.0, 1.0),
+        computed::TimingFunction::ease()));
+    assert_eq!(animation.animation_name, vec!(Some("slide".to_string()), None));
+    assert_eq!(animation.animation_duration, vec!(computed::Time(3.0), computed::Time(0.0)));
+    assert_eq!(animation.animation_iteration_count, vec!(
+        longhands::animation_iteration_count::Infinite,
+        longhands::animation_iteration_count::Count(1.0)));
+    assert_eq!(animation.animation_direction, vec!(
+        longhands::animation_direction::alternate, longhands::animation_direction::normal));
+
+    let (style, _) = cascade(&[declarations("transition: none")], false, None, None);
+    assert!(style.get_animation().transition_property.is_empty());
+    let (style, _) = cascade(&[declarations("transition: none, opacity")], false, None, None);
+    assert_eq!(style.get_animation().transition_property,
+               vec!(longhands::transition_property::AllProperties));
+
+    assert_eq!(AnimatableLonghand::from_name("margin").len(), 4);
+    assert_eq!(AnimatableLonghand::from_name("display"), vec!());
+}
+
+#[test]
+fn transform_functions() {
+    use servo_util::geometry::Au;
+    use self::longhands::transform::{Matrix, Translate, Scale, Rotate, Skew};
+    let url = Url::parse("http://localhost").unwrap();
+    let declarations = |css: &str| {
+        DeclarationBlock::from_declarations(parse_style_attribute(css, &url).normal)
+    };
+    let (style, _) = cascade(
+        &[declarations("transform: translate(10px, 50%) scale(2) rotate(0.5turn) skewY(1rad) \
+                                   matrix(1, 0, 0, 1, 5, 6); \
+                        transform-origin: left bottom")],
+        false, None, None);
+    let effects = style.get_effects();
+    assert_eq!(effects.transform, vec!(
+        Translate(computed::LP_Length(Au::from_px(10)), computed::LP_Percentage(0.5)),
+        Scale(2.0, 2.0),
+        Rotate(Angle(::std::f64::consts::PI)),
+        Skew(Angle(0.0), Angle(1.0)),
+        Matrix(1.0, 0.0, 0.0, 1.0, 5.0, 6.0)));
+    assert_eq!(effects.transform_origin.horizontal, computed::LP_Percentage(0.0));
+    assert_eq!(effects.transform_origin.vertical, computed::LP_Percentage(1.0));
+
+    let (style, _) = cascade(&[declarations("transform: translateX(1px) none")], false, None, None);
+    assert!(style.get_effects().transform.is_empty());
+    assert_eq!(style.get_effects().transform_origin.horizontal, computed::LP_Percentage(0.5));
+    assert!(longhands::transform::parse(&[], &url).is_err());
+}
+
+#[test]
+fn flex_shorthands_and_items() {
+    use servo_util::geometry::Au;
+    let url = Url::parse("http://localhost").unwrap();
+    let declarations = |css: &str| {
+        DeclarationBlock::from_declarations(parse_style_attribute(css, &url).normal)
+    };
+    let (container, _) = cascade(&[declarations("display: inline-flex; flex-flow: wrap column")],
+                                 false, None, None);
+    // The root element is blockified.
+    assert_eq!(container.get_box().display, longhands::display::flex);
+    assert_eq!(container.get_flex().flex_direction, longhands::flex_direction::column);
+    assert_eq!(container.get_flex().flex_wrap, longhands::flex_wrap::wrap);
+
+    let flex = |css: &str| {
+        let (style, cacheable) = cascade(&[declarations(css)], false, Some(&container), None);
+        assert!(!cacheable);
+        let flex = style.get_flex();
+        (style.get_box().display, flex.flex_grow, flex.flex_shrink, flex.flex_basis)
+    };
+    assert!(flex("display: inline; flex: 2") ==
+            (longhands::display::block, 2.0, 1.0, computed::LPA_Percentage(0.0)));
+    assert!(flex("display: inline-table; flex: 1 0 0") ==
+            (longhands::display::table, 1.0, 0.0, computed::LPA_Length(Au(0))));
+    assert!(flex("flex: 10px 3") ==
+            (longhands::display::block, 3.0, 1.0, computed::LPA_Length(Au::from_px(10))));
+    assert!(flex("flex: auto") == (longhands::display::block, 1.0, 1.0, computed::LPA_Auto));
+    assert!(flex("flex: none") == (longhands::display::block, 0.0, 0.0, computed::LPA_Auto));
+    // Invalid, so the initial values.
+    assert!(flex("flex: 1 2 3") == (longhands::display::block, 0.0, 1.0, computed::LPA_Auto));
+}
+
 /// Fast path for the function below. Only computes new inherited styles.
 #[allow(unused_mut)]
 fn cascade_with_cached_declarations(applicable_declarations: &[DeclarationBlock],
                                     shareable: bool,
                                     parent_style: &ComputedValues,
                                     cached_style: &ComputedValues,
+                                    custom_properties: Option<Arc<custom_properties::ComputedValuesMap>>,
                                     context: &computed::Context)
                                     -> ComputedValues {
     % for style_struct in STYLE_STRUCTS:
@@ -2122,6 +3683,9 @@ fn cascade_with_cached_declarations(applicable_declarations: &[DeclarationBlock]
                         % endif
                     % endfor
                 % endfor
+                CustomDeclaration(..) | WithVariablesDeclaration(..) => {
+                    // Handled before the cascade.
+                }
             }
         }
     }
@@ -2131,6 +3695,7 @@ fn cascade_with_cached_declarations(applicable_declarations: &[DeclarationBlock]
         % for style_struct in STYLE_STRUCTS:
             ${style_struct.ident}: style_${style_struct.ident},
         % endfor
+        custom_properties: custom_properties,
         shareable: shareable,
     }
 }
@@ -2162,10 +3727,36 @@ pub fn cascade(applicable_declarations: &[DeclarationBlock],
         None => (true, initial_values),
     };
 
+    let custom_properties = custom_properties::cascade(applicable_declarations,
+                                                       &inherited_style.custom_properties);
+
+    // Declarations that use `var()` can only be parsed now that custom properties are known.
+    // The result depends on the parent style, so it can't be cached.
+    let has_variables = applicable_declarations.iter().any(|sub_list| {
+        sub_list.declarations.iter().any(|declaration| {
+            match *declaration {
+                WithVariablesDeclaration(..) => true,
+                _ => false,
+            }
+        })
+    });
+    let substituted_declarations;
+    let applicable_declarations = if has_variables {
+        substituted_declarations = substitute_variables(applicable_declarations,
+                                                        &custom_properties);
+        substituted_declarations.as_slice()
+    } else {
+        applicable_declarations
+    };
+
     let mut context = {
         let inherited_font_style = inherited_style.get_font();
         computed::Context {
             is_root_element: is_root_element,
+            is_flex_item: match inherited_style.get_box().display {
+                longhands::display::flex | longhands::display::inline_flex => true,
+                _ => false,
+            },
             inherited_font_weight: inherited_font_style.font_weight,
             inherited_font_size: inherited_font_style.font_size,
             inherited_height: inherited_style.get_box().height,
@@ -2244,12 +3835,15 @@ pub fn cascade(applicable_declarations: &[DeclarationBlock],
         }
     }
 
+    // The cached style may have been computed for a parent that isn't a flex container, or vice
+    // versa, so it can't be used for flex items, whose `display` depends on the parent.
     match (cached_style, parent_style) {
-        (Some(cached_style), Some(parent_style)) => {
+        (Some(cached_style), Some(parent_style)) if !has_variables && !context.is_flex_item => {
             return (cascade_with_cached_declarations(applicable_declarations,
                                                      shareable,
                                                      parent_style,
                                                      cached_style,
+                                                     custom_properties,
                                                      &context), false)
         }
         (_, _) => {}
@@ -2265,7 +3859,7 @@ pub fn cascade(applicable_declarations: &[DeclarationBlock],
             % endif
             .${style_struct.ident}.clone();
     % endfor
-    let mut cacheable = true;
+    let mut cacheable = !has_variables && !context.is_flex_item;
     let mut seen = PropertyBitField::new();
     // Declaration blocks are stored in increasing precedence order,
     // we want them in decreasing order here.
@@ -2322,6 +3916,9 @@ pub fn cascade(applicable_declarations: &[DeclarationBlock],
                         % endif
                     % endfor
                 % endfor
+                CustomDeclaration(..) | WithVariablesDeclaration(..) => {
+                    // Handled before the cascade.
+                }
             }
         }
     }
@@ -2348,11 +3945,31 @@ pub fn cascade(applicable_declarations: &[DeclarationBlock],
         % for style_struct in STYLE_STRUCTS:
             ${style_struct.ident}: style_${style_struct.ident},
         % endfor
+        custom_properties: custom_properties,
         shareable: shareable,
     }, cacheable)
 }
 
 
+/// Replaces the declarations that use `var()` with what they parse to once custom properties are
+/// substituted.
+fn substitute_variables(applicable_declarations: &[DeclarationBlock],
+                        custom_properties: &Option<Arc<custom_properties::ComputedValuesMap>>)
+                        -> Vec<DeclarationBlock> {
+    applicable_declarations.iter().map(|sub_list| {
+        let declarations = sub_list.declarations.iter().map(|declaration| {
+            match *declaration {
+                WithVariablesDeclaration(longhand, ref value) => {
+                    value.substitute(longhand, custom_properties)
+                }
+                ref declaration => declaration.clone(),
+            }
+        }).collect();
+        DeclarationBlock::from_declarations(Arc::new(declarations))
+    }).collect()
+}
+
+
 /// Equivalent to `cascade()` with an empty `applicable_declarations`
 /// Performs the CSS cascade for an anonymous box.
 ///
@@ -2369,6 +3986,7 @@ pub fn cascade_anonymous(parent_style: &ComputedValues) -> ComputedValues {
                 % endif
                 .${style_struct.ident}.clone(),
         % endfor
+        custom_properties: parent_style.custom_properties.clone(),
         shareable: false,
         writing_mode: parent_style.writing_mode,
     };
@@ -2398,3 +4016,45 @@ pub mod computed_values {
         LengthOrPercentageOrAuto, LPA_Length, LPA_Percentage, LPA_Auto,
         LengthOrPercentageOrNone, LPN_Length, LPN_Percentage, LPN_None};
 }
+
+#[test]
+fn lists_and_counters() {
+    use self::longhands::content::{Content, StringContent, CounterContent, CountersContent};
+    let url = Url::parse("http://localhost/").unwrap();
+    let declarations = |css: &str| {
+        DeclarationBlock::from_declarations(parse_style_attribute(css, &url).normal)
+    };
+    let (parent, _) = cascade(&[declarations("list-style: square inside")], false, None, None);
+    let (style, _) = cascade(
+        &[declarations("counter-reset: a b 3; counter-increment: a -2 b; \
+                        content: 'x' counter(a) counters(b, '.', lower-roman)")],
+        false, Some(&parent), None);
+    // The list properties are inherited.
+    assert_eq!(style.get_list().list_style_type, longhands::list_style_type::square);
+    assert_eq!(style.get_list().list_style_position, longhands::list_style_position::inside);
+    assert!(style.get_list().list_style_image.is_none());
+    assert_eq!(style.get_box().counter_reset, vec!(("a".to_string(), 0), ("b".to_string(), 3)));
+    assert_eq!(style.get_box().counter_increment,
+               vec!(("a".to_string(), -2), ("b".to_string(), 1)));
+    assert!(style.get_box().content == Content(vec!(
+        StringContent("x".to_string()),
+        CounterContent("a".to_string(), longhands::list_style_type::decimal),
+        CountersContent("b".to_string(),
+                        ".".to_string(),
+                        longhands::list_style_type::lower_roman))));
+
+    let list_style = |css: &str| {
+        let (style, _) = cascade(&[declarations(css)], false, None, None);
+        let list = style.get_list();
+        (list.list_style_type, list.list_style_image.clone())
+    };
+    assert_eq!(list_style("list-style: none"), (longhands::list_style_type::none, None));
+    assert_eq!(list_style("list-style: url(a.png) none"),
+               (longhands::list_style_type::none,
+                Some(Url::parse("http://localhost/a.png").unwrap())));
+    assert_eq!(list_style("list-style: none upper-alpha"),
+               (longhands::list_style_type::upper_alpha, None));
+    // Invalid, so the initial values.
+    assert_eq!(list_style("list-style: none none none"), (longhands::list_style_type::disc, None));
+    assert_eq!(list_style("counter-reset: 3"), (longhands::list_style_type::disc, None));
+}
@@ -18,14 +18,19 @@ use servo_util::sort;
 use servo_util::str::{AutoLpa, LengthLpa, PercentageLpa};
 use string_cache::Atom;
 
+use keyframes::KeyframesRule;
 use legacy::{SizeIntegerAttribute, WidthLengthAttribute};
 use media_queries::Device;
 use node::{TElement, TElementAttributes, TNode};
-use properties::{PropertyDeclaration, PropertyDeclarationBlock, SpecifiedValue, WidthDeclaration};
+use properties::{CounterResetDeclaration, PropertyDeclaration, PropertyDeclarationBlock};
+use properties::ServoLangDeclaration;
+use properties::{SpecifiedValue, WidthDeclaration};
 use properties::{specified};
 use selectors::*;
-use stylesheets::{Stylesheet, iter_stylesheet_media_rules, iter_stylesheet_style_rules};
+use stylesheets::{Stylesheet, iter_stylesheet_media_queries, iter_stylesheet_style_rules};
+use stylesheets::iter_keyframes_rules;
 
+#[deriving(Clone, PartialEq)]
 pub enum StylesheetOrigin {
     UserAgentOrigin,
     AuthorOrigin,
@@ -264,8 +269,12 @@ impl SelectorMap {
 pub static RECOMMENDED_SELECTOR_BLOOM_FILTER_SIZE: uint = 4096;
 
 pub struct Stylist {
-    // List of stylesheets (including all media rules)
-    stylesheets: Vec<Stylesheet>,
+    // List of user agent and user stylesheets (including all media rules)
+    stylesheets: Vec<Arc<Stylesheet>>,
+
+    // The document's stylesheets in tree order, which are replaced all at once
+    // whenever script adds, removes or changes one.
+    author_stylesheets: Vec<Arc<Stylesheet>>,
 
     // Device that the stylist is currently evaluating against.
     pub device: Device,
@@ -279,7 +288,12 @@ pub struct Stylist {
     element_map: PerPseudoElementSelectorMap,
     before_map: PerPseudoElementSelectorMap,
     after_map: PerPseudoElementSelectorMap,
+    first_line_map: PerPseudoElementSelectorMap,
+    first_letter_map: PerPseudoElementSelectorMap,
     rules_source_order: uint,
+
+    // The @keyframes rules that apply, by name.
+    keyframes: HashMap<String, KeyframesRule>,
 }
 
 impl Stylist {
@@ -287,13 +301,17 @@ impl Stylist {
     pub fn new(device: Device) -> Stylist {
         let mut stylist = Stylist {
             stylesheets: vec!(),
+            author_stylesheets: vec!(),
             device: device,
             is_dirty: true,
 
             element_map: PerPseudoElementSelectorMap::new(),
             before_map: PerPseudoElementSelectorMap::new(),
             after_map: PerPseudoElementSelectorMap::new(),
+            first_line_map: PerPseudoElementSelectorMap::new(),
+            first_letter_map: PerPseudoElementSelectorMap::new(),
             rules_source_order: 0u,
+            keyframes: HashMap::new(),
         };
         // FIXME: Add quirks-mode.css in quirks mode.
         // FIXME: Add iso-8859-9.css when the document’s encoding is ISO-8859-8.
@@ -316,24 +334,34 @@ impl Stylist {
             self.element_map = PerPseudoElementSelectorMap::new();
             self.before_map = PerPseudoElementSelectorMap::new();
             self.after_map = PerPseudoElementSelectorMap::new();
+            self.first_line_map = PerPseudoElementSelectorMap::new();
+            self.first_letter_map = PerPseudoElementSelectorMap::new();
             self.rules_source_order = 0;
+            self.keyframes = HashMap::new();
 
-            for stylesheet in self.stylesheets.iter() {
-                let (mut element_map, mut before_map, mut after_map) = match stylesheet.origin {
+            for stylesheet in self.stylesheets.iter().chain(self.author_stylesheets.iter()) {
+                let (mut element_map, mut before_map, mut after_map,
+                     mut first_line_map, mut first_letter_map) = match stylesheet.origin {
                     UserAgentOrigin => (
                         &mut self.element_map.user_agent,
                         &mut self.before_map.user_agent,
                         &mut self.after_map.user_agent,
+                        &mut self.first_line_map.user_agent,
+                        &mut self.first_letter_map.user_agent,
                     ),
                     AuthorOrigin => (
                         &mut self.element_map.author,
                         &mut self.before_map.author,
                         &mut self.after_map.author,
+                        &mut self.first_line_map.author,
+                        &mut self.first_letter_map.author,
                     ),
                     UserOrigin => (
                         &mut self.element_map.user,
                         &mut self.before_map.user,
                         &mut self.after_map.user,
+                        &mut self.first_line_map.user,
+                        &mut self.first_letter_map.user,
                     ),
                 };
                 let mut rules_source_order = self.rules_source_order;
@@ -348,6 +376,8 @@ impl Stylist {
                                     None => &mut element_map,
                                     Some(Before) => &mut before_map,
                                     Some(After) => &mut after_map,
+                                    Some(FirstLine) => &mut first_line_map,
+                                    Some(FirstLetter) => &mut first_letter_map,
                                 };
                                 map.$priority.insert(Rule {
                                         selector: selector.compound_selectors.clone(),
@@ -362,12 +392,17 @@ impl Stylist {
                     };
                 );
 
-                iter_stylesheet_style_rules(stylesheet, &self.device, |style_rule| {
+                iter_stylesheet_style_rules(&**stylesheet, &self.device, |style_rule| {
                     append!(style_rule, normal);
                     append!(style_rule, important);
                     rules_source_order += 1;
                 });
                 self.rules_source_order = rules_source_order;
+
+                let keyframes = &mut self.keyframes;
+                iter_keyframes_rules(&**stylesheet, &self.device, |rule| {
+                    keyframes.insert(rule.name.clone(), rule.clone());
+                });
             }
 
             self.is_dirty = false;
@@ -378,21 +413,38 @@ impl Stylist {
     }
 
     pub fn set_device(&mut self, device: Device) {
-        let is_dirty = self.is_dirty || self.stylesheets.iter().any(|stylesheet| {
-            let mut stylesheet_dirty = false;
-            iter_stylesheet_media_rules(stylesheet, |rule| {
-                stylesheet_dirty |= rule.media_queries.evaluate(&self.device) !=
-                                    rule.media_queries.evaluate(&device);
-            });
-            stylesheet_dirty
-        });
+        let is_dirty = self.is_dirty || {
+            let mut stylesheets = self.stylesheets.iter().chain(self.author_stylesheets.iter());
+            stylesheets.any(|stylesheet| {
+                let mut stylesheet_dirty = false;
+                iter_stylesheet_media_queries(&**stylesheet, |media_queries| {
+                    stylesheet_dirty |= media_queries.evaluate(&self.device) !=
+                                        media_queries.evaluate(&device);
+                });
+                stylesheet_dirty
+            })
+        };
 
         self.device = device;
         self.is_dirty |= is_dirty;
     }
 
     pub fn add_stylesheet(&mut self, stylesheet: Stylesheet) {
-        self.stylesheets.push(stylesheet);
+        self.stylesheets.push(Arc::new(stylesheet));
+        self.is_dirty = true;
+    }
+
+    /// The @keyframes rule with the given name, if any. The last one wins.
+    pub fn get_keyframes<'a>(&'a self, name: &str) -> Option<&'a KeyframesRule> {
+        self.keyframes.get(&name.to_string())
+    }
+
+    pub fn author_stylesheets(&self) -> &[Arc<Stylesheet>] {
+        self.author_stylesheets.as_slice()
+    }
+
+    pub fn set_author_stylesheets(&mut self, stylesheets: Vec<Arc<Stylesheet>>) {
+        self.author_stylesheets = stylesheets;
         self.is_dirty = true;
     }
 
@@ -422,6 +474,8 @@ impl Stylist {
             None => &self.element_map,
             Some(Before) => &self.before_map,
             Some(After) => &self.after_map,
+            Some(FirstLine) => &self.first_line_map,
+            Some(FirstLetter) => &self.first_letter_map,
         };
 
         let mut shareable = true;
@@ -491,6 +545,19 @@ impl Stylist {
                                                                    N: TNode<'a,E>,
                                                                    V: VecLike<DeclarationBlock> {
         let element = node.as_element();
+
+        // The language of the element, which its text is shaped for. (Cf. HTML5 § 3.2.5.3)
+        let lang = element.get_attr(&ns!(XML), &atom!("lang"))
+                          .or_else(|| element.get_attr(&ns!(""), &atom!("lang")));
+        match lang {
+            Some(lang) => {
+                matching_rules_list.vec_push(DeclarationBlock::from_declaration(
+                        ServoLangDeclaration(SpecifiedValue(Some(lang.to_string())))));
+                *shareable = false
+            }
+            None => {}
+        }
+
         match element.get_local_name() {
             name if *name == atom!("td") => {
                 match element.get_length_attribute(WidthLengthAttribute) {
@@ -509,6 +576,25 @@ impl Stylist {
                     }
                 };
             }
+            name if *name == atom!("ol") => {
+                // The `start` and `reversed` attributes set the ordinal value of the first item
+                // and the direction in which items count. (HTML5 § 4.5.6)
+                let reversed = element.get_attr(&ns!(""), &atom!("reversed")).is_some();
+                let start = element.get_attr(&ns!(""), &atom!("start")).and_then(|start| {
+                    from_str::<i32>(start.trim())
+                });
+                if reversed || start.is_some() {
+                    let reset = match start {
+                        Some(start) if reversed => start + 1,
+                        Some(start) => start - 1,
+                        None => count_list_items(node) + 1,
+                    };
+                    matching_rules_list.vec_push(DeclarationBlock::from_declaration(
+                            CounterResetDeclaration(SpecifiedValue(vec!(
+                                        ("list-item".to_string(), reset))))));
+                    *shareable = false
+                }
+            }
             name if *name == atom!("input") => {
                 match element.get_integer_attribute(SizeIntegerAttribute) {
                     Some(value) if value != 0 => {
@@ -535,6 +621,23 @@ impl Stylist {
     }
 }
 
+/// Returns the number of `li` children of a list, which a reversed list counts down from.
+fn count_list_items<'a,E,N>(list: &N) -> i32 where E: TElement<'a>, N: TNode<'a,E> {
+    let mut count = 0;
+    let mut child = list.first_child();
+    loop {
+        match child {
+            Some(node) => {
+                if node.is_element() && *node.as_element().get_local_name() == atom!("li") {
+                    count += 1
+                }
+                child = node.next_sibling()
+            }
+            None => return count,
+        }
+    }
+}
+
 struct PerOriginSelectorMap {
     normal: SelectorMap,
     important: SelectorMap,
@@ -997,6 +1100,36 @@ pub fn matches_simple_selector<'a,E,N>(selector: &SimpleSelector,
             let elem = element.as_element();
             elem.get_enabled_state()
         },
+        // http://www.whatwg.org/html/#selector-checked
+        Checked => {
+            *shareable = false;
+            let elem = element.as_element();
+            elem.get_checked_state()
+        },
+        // http://www.whatwg.org/html/#selector-indeterminate
+        Indeterminate => {
+            *shareable = false;
+            let elem = element.as_element();
+            elem.get_indeterminate_state()
+        },
+        // http://www.whatwg.org/html/#selector-active
+        Active => {
+            *shareable = false;
+            let elem = element.as_element();
+            elem.get_active_state()
+        },
+        // http://www.whatwg.org/html/#selector-focus
+        Focus => {
+            *shareable = false;
+            let elem = element.as_element();
+            elem.get_focus_state()
+        },
+        // http://www.whatwg.org/html/#selector-target
+        Target => {
+            *shareable = false;
+            let elem = element.as_element();
+            elem.get_target_state()
+        },
         FirstChild => {
             *shareable = false;
             matches_first_child(element)
@@ -1015,6 +1148,16 @@ pub fn matches_simple_selector<'a,E,N>(selector: &SimpleSelector,
             matches_root(element)
         }
 
+        Empty => {
+            *shareable = false;
+            element.is_empty()
+        }
+
+        Lang(ref lang) => {
+            *shareable = false;
+            matches_lang(element, lang.as_slice())
+        }
+
         NthChild(a, b) => {
             *shareable = false;
             matches_generic_nth_child(element, a, b, false, false)
@@ -1048,7 +1191,9 @@ pub fn matches_simple_selector<'a,E,N>(selector: &SimpleSelector,
 
         Negation(ref negated) => {
             *shareable = false;
-            !negated.iter().all(|s| matches_simple_selector(s, element, shareable))
+            !negated.iter().any(|compound| {
+                compound.iter().all(|s| matches_simple_selector(s, element, shareable))
+            })
         },
     }
 }
@@ -1062,6 +1207,34 @@ fn url_is_visited(_url: &str) -> bool {
     false
 }
 
+/// Matches `:lang()` against the language of the element, which is inherited from the nearest
+/// inclusive ancestor with an `xml:lang` or `lang` attribute. (Cf. Selectors Level 3 § 6.6.3)
+#[inline]
+fn matches_lang<'a,E,N>(element: &N, lang: &str) -> bool
+                        where E: TElement<'a>, N: TNode<'a,E> {
+    let mut node = element.clone();
+    loop {
+        if node.is_element() {
+            let element = node.as_element();
+            let element_lang = element.get_attr(&ns!(XML), &atom!("lang"))
+                                      .or_else(|| element.get_attr(&ns!(""), &atom!("lang")));
+            match element_lang {
+                Some(element_lang) => {
+                    let element_lang = element_lang.to_ascii_lower();
+                    let lang = lang.to_ascii_lower();
+                    return element_lang == lang ||
+                        element_lang.as_slice().starts_with(format!("{}-", lang).as_slice())
+                }
+                None => {}
+            }
+        }
+        node = match node.parent_node() {
+            Some(parent) => parent,
+            None => return false,
+        };
+    }
+}
+
 #[inline]
 fn matches_generic_nth_child<'a,E,N>(element: &N,
                                      a: i32,
@@ -25,8 +25,8 @@ pub struct Selector {
 pub enum PseudoElement {
     Before,
     After,
-//    FirstLine,
-//    FirstLetter,
+    FirstLine,
+    FirstLetter,
 }
 
 
@@ -61,17 +61,22 @@ pub enum SimpleSelector {
     AttrSuffixMatch(AttrSelector, String),  // [foo$=bar]
 
     // Pseudo-classes
-    Negation(Vec<SimpleSelector>),
+    Negation(Vec<Vec<SimpleSelector>>),  // :not(a, b.c), one entry per compound selector
     AnyLink,
     Link,
     Visited,
     Hover,
+    Active,
+    Focus,
+    Target,
     Disabled,
     Enabled,
+    Checked,
+    Indeterminate,
     FirstChild, LastChild, OnlyChild,
-//    Empty,
+    Empty,
     Root,
-//    Lang(String),
+    Lang(String),
     NthChild(i32, i32),
     NthLastChild(i32, i32),
     NthOfType(i32, i32),
@@ -225,16 +230,40 @@ fn compute_specificity(mut selector: &CompoundSelector,
                 &ClassSelector(..)
                 | &AttrExists(..) | &AttrEqual(..) | &AttrIncludes(..) | &AttrDashMatch(..)
                 | &AttrPrefixMatch(..) | &AttrSubstringMatch(..) | &AttrSuffixMatch(..)
-                | &AnyLink | &Link | &Visited | &Hover | &Disabled | &Enabled
+                | &AnyLink | &Link | &Visited | &Hover | &Active | &Focus | &Target
+                | &Disabled | &Enabled | &Checked | &Indeterminate
                 | &FirstChild | &LastChild | &OnlyChild | &Root
-//                | &Empty | &Lang(*)
+                | &Empty | &Lang(..)
                 | &NthChild(..) | &NthLastChild(..)
                 | &NthOfType(..) | &NthLastOfType(..)
                 | &FirstOfType | &LastOfType | &OnlyOfType
                 => specificity.class_like_selectors += 1,
                 &NamespaceSelector(..) => (),
-                &Negation(ref negated)
-                => simple_selectors_specificity(negated.as_slice(), specificity),
+                // The specificity of :not() is that of its most specific argument.
+                &Negation(ref negated) => {
+                    let mut most_specific = Specificity {
+                        id_selectors: 0,
+                        class_like_selectors: 0,
+                        element_selectors: 0,
+                    };
+                    for compound in negated.iter() {
+                        let mut argument = Specificity {
+                            id_selectors: 0,
+                            class_like_selectors: 0,
+                            element_selectors: 0,
+                        };
+                        simple_selectors_specificity(compound.as_slice(), &mut argument);
+                        if (argument.id_selectors, argument.class_like_selectors,
+                            argument.element_selectors) >
+                           (most_specific.id_selectors, most_specific.class_like_selectors,
+                            most_specific.element_selectors) {
+                            most_specific = argument
+                        }
+                    }
+                    specificity.id_selectors += most_specific.id_selectors;
+                    specificity.class_like_selectors += most_specific.class_like_selectors;
+                    specificity.element_selectors += most_specific.element_selectors;
+                }
             }
         }
     }
@@ -345,8 +374,8 @@ fn parse_one_simple_selector<I: Iterator<ComponentValue>>(
                             // ** Do not add to this list! **
                             "before" => Ok(Some(PseudoElementResult(Before))),
                             "after" => Ok(Some(PseudoElementResult(After))),
-//                            "first-line" => PseudoElementResult(FirstLine),
-//                            "first-letter" => PseudoElementResult(FirstLetter),
+                            "first-line" => Ok(Some(PseudoElementResult(FirstLine))),
+                            "first-letter" => Ok(Some(PseudoElementResult(FirstLetter))),
                             _ => Err(())
                         }
                     },
@@ -495,8 +524,13 @@ fn parse_simple_pseudo_class(name: &str) -> Result<SimpleSelector, ()> {
         "link" => Ok(Link),
         "visited" => Ok(Visited),
         "hover" => Ok(Hover),
+        "active" => Ok(Active),
+        "focus" => Ok(Focus),
+        "target" => Ok(Target),
         "disabled" => Ok(Disabled),
         "enabled" => Ok(Enabled),
+        "checked" => Ok(Checked),
+        "indeterminate" => Ok(Indeterminate),
         "first-child" => Ok(FirstChild),
         "last-child"  => Ok(LastChild),
         "only-child"  => Ok(OnlyChild),
@@ -504,7 +538,7 @@ fn parse_simple_pseudo_class(name: &str) -> Result<SimpleSelector, ()> {
         "first-of-type" => Ok(FirstOfType),
         "last-of-type"  => Ok(LastOfType),
         "only-of-type"  => Ok(OnlyOfType),
-//        "empty" => Ok(Empty),
+        "empty" => Ok(Empty),
         _ => Err(())
     }
 }
@@ -514,7 +548,7 @@ fn parse_functional_pseudo_class(name: String, arguments: Vec<ComponentValue>,
                                  namespaces: &NamespaceMap, inside_negation: bool)
                                  -> Result<SimpleSelector, ()> {
     match name.as_slice().to_ascii_lower().as_slice() {
-//        "lang" => parse_lang(arguments),
+        "lang" => parse_lang(arguments),
         "nth-child"        => parse_nth(arguments.as_slice()).map(|(a, b)| NthChild(a, b)),
         "nth-last-child"   => parse_nth(arguments.as_slice()).map(|(a, b)| NthLastChild(a, b)),
         "nth-of-type"      => parse_nth(arguments.as_slice()).map(|(a, b)| NthOfType(a, b)),
@@ -530,38 +564,54 @@ fn parse_pseudo_element(name: String) -> Result<PseudoElement, ()> {
         // All supported pseudo-elements
         "before" => Ok(Before),
         "after" => Ok(After),
-//        "first-line" => Some(FirstLine),
-//        "first-letter" => Some(FirstLetter),
+        "first-line" => Ok(FirstLine),
+        "first-letter" => Ok(FirstLetter),
         _ => Err(())
     }
 }
 
 
-//fn parse_lang(arguments: vec!(ComponentValue)) -> Result<SimpleSelector, ()> {
-//    let mut iter = arguments.move_skip_whitespace();
-//    match iter.next() {
-//        Some(Ident(value)) => {
-//            if "" == value || iter.next().is_some() { None }
-//            else { Ok(Lang(value)) }
-//        },
-//        _ => Err(()),
-//    }
-//}
+fn parse_lang(arguments: Vec<ComponentValue>) -> Result<SimpleSelector, ()> {
+    let iter = &mut arguments.into_iter().peekable();
+    skip_whitespace(iter);
+    let lang = match iter.next() {
+        Some(Ident(value)) | Some(QuotedString(value)) => value,
+        _ => return Err(()),
+    };
+    skip_whitespace(iter);
+    if lang.is_empty() || iter.next().is_some() { Err(()) } else { Ok(Lang(lang)) }
+}
 
 
-/// Level 3: Parse **one** simple_selector
+/// Level 4: Parse a comma-separated list of compound selectors. Combinators and
+/// pseudo-elements are not allowed inside the negation.
 fn parse_negation(arguments: Vec<ComponentValue>, namespaces: &NamespaceMap)
                   -> Result<SimpleSelector, ()> {
     let iter = &mut arguments.into_iter().peekable();
-    match try!(parse_type_selector(iter, namespaces)) {
-        Some(type_selector) => Ok(Negation(type_selector)),
-        None => {
+    let mut negated = vec![];
+    loop {
+        let (mut simple_selectors, mut empty) = match try!(parse_type_selector(iter, namespaces)) {
+            None => (vec![], true),
+            Some(type_selector) => (type_selector, false),
+        };
+        loop {
             match try!(parse_one_simple_selector(iter, namespaces, /* inside_negation = */ true)) {
-                Some(SimpleSelectorResult(simple_selector)) => Ok(Negation(vec![simple_selector])),
-                _ => Err(())
+                None => break,
+                Some(SimpleSelectorResult(s)) => { simple_selectors.push(s); empty = false }
+                Some(PseudoElementResult(_)) => return Err(()),
             }
-        },
+        }
+        if empty { return Err(()) }
+        negated.push(simple_selectors);
+
+        skip_whitespace(iter);
+        match iter.next() {
+            None => break,
+            Some(Comma) => {}
+            _ => return Err(()),
+        }
     }
+    Ok(Negation(negated))
 }
 
 
@@ -727,5 +777,47 @@ mod tests {
             pseudo_element: Some(After),
             specificity: specificity(0, 0, 2),
         })))
+        assert!(parse("p:first-letter")
+                    .map(|s| s[0].pseudo_element) == Ok(Some(FirstLetter)))
+        assert!(parse("p::first-line")
+                    .map(|s| s[0].pseudo_element) == Ok(Some(FirstLine)))
+    }
+
+    #[test]
+    fn test_pseudo_classes() {
+        fn simple_selectors(input: &str) -> Result<Vec<SimpleSelector>, ()> {
+            parse(input).map(|s| s[0].compound_selectors.simple_selectors.clone())
+        }
+        assert!(simple_selectors(":checked:focus:active:target:indeterminate:empty") ==
+                Ok(vec!(Checked, Focus, Active, Target, Indeterminate, Empty)))
+        assert!(simple_selectors(":lang(fr-CA)") == Ok(vec!(Lang("fr-CA".to_string()))))
+        assert!(parse(":lang()") == Err(()))
+        assert!(parse(":lang(en fr)") == Err(()))
+        assert!(parse(":checked").map(|s| s[0].specificity) == Ok(specificity(0, 1, 0)))
+    }
+
+    #[test]
+    fn test_negation() {
+        assert!(parse(":not(.foo, p#bar)") == Ok(vec!(Selector {
+            compound_selectors: Arc::new(CompoundSelector {
+                simple_selectors: vec!(Negation(vec!(
+                    vec!(ClassSelector(Atom::from_slice("foo"))),
+                    vec!(LocalNameSelector(LocalName {
+                            name: atom!("p"),
+                            lower_name: atom!("p") }),
+                         IDSelector(Atom::from_slice("bar"))),
+                ))),
+                next: None,
+            }),
+            pseudo_element: None,
+            // The most specific argument wins.
+            specificity: specificity(1, 0, 1),
+        })))
+        assert!(parse(":not(*)").is_ok())
+        assert!(parse(":not()") == Err(()))
+        assert!(parse(":not(a b)") == Err(()))
+        assert!(parse(":not(a > b)") == Err(()))
+        assert!(parse(":not(:not(a))") == Err(()))
+        assert!(parse(":not(::before)") == Err(()))
     }
 }
@@ -2,9 +2,12 @@
  * License, v. 2.0. If a copy of the MPL was not distributed with this
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
+use std::collections::HashMap;
 use std::iter::Iterator;
 use std::ascii::AsciiExt;
-use url::Url;
+use std::mem;
+use sync::Arc;
+use url::{Url, UrlParser};
 
 use encoding::EncodingRef;
 
@@ -12,35 +15,71 @@ use cssparser::{decode_stylesheet_bytes, tokenize, parse_stylesheet_rules, ToCss
 use cssparser::ast::*;
 use selectors;
 use properties;
-use errors::{ErrorLoggerIterator, log_css_error};
+use errors::{CSSError, ErrorLoggerIterator, collect_css_errors, log_css_error};
 use namespaces::{NamespaceMap, parse_namespace_rule};
-use media_queries::{Device, MediaRule, parse_media_rule};
+use media_queries::{Device, MediaRule, MediaQueryList, parse_media_rule};
+use media_queries::parse_media_query_list;
+use supports::{SupportsRule, parse_supports_rule};
 use media_queries;
-use font_face::{FontFaceRule, Source, parse_font_face_rule, iter_font_face_rules_inner};
+use font_face::{FontFaceRule, UrlSource_, LocalSource, AutoFontDisplay, BlockFontDisplay};
+use font_face::{SwapFontDisplay, FallbackFontDisplay, OptionalFontDisplay, MAX_CODEPOINT};
+use font_face::parse_font_face_rule;
+use font_face::iter_font_face_rules_inner;
+use keyframes::{KeyframesRule, parse_keyframes_rule, iter_keyframes_rules_inner};
 use selector_matching::StylesheetOrigin;
 
 
+#[deriving(Clone)]
 pub struct Stylesheet {
     /// List of rules in the order they were found (important for
     /// cascading order)
     rules: Vec<CSSRule>,
     pub origin: StylesheetOrigin,
+    /// The URL relative URLs are resolved against, in rules inserted later too.
+    pub base_url: Url,
+    /// The namespaces declared with @namespace, which rules inserted later use too.
+    namespaces: NamespaceMap,
+    /// The errors found while parsing, until they are taken to be reported.
+    errors: Vec<CSSError>,
 }
 
 
+/// How deeply @import rules may be nested before the imported stylesheets are ignored.
+static MAX_IMPORT_DEPTH: uint = 16;
+
+
+#[deriving(Clone)]
 pub enum CSSRule {
+    CSSImportRule(ImportRule),
     CSSStyleRule(StyleRule),
     CSSMediaRule(MediaRule),
+    CSSSupportsRule(SupportsRule),
     CSSFontFaceRule(FontFaceRule),
+    CSSKeyframesRule(KeyframesRule),
 }
 
 
+#[deriving(Clone)]
 pub struct StyleRule {
     pub selectors: Vec<selectors::Selector>,
+    /// The selectors as they were written, for serialization.
+    pub selector_text: String,
     pub declarations: properties::PropertyDeclarationBlock,
 }
 
 
+#[deriving(Clone)]
+pub struct ImportRule {
+    pub url: Url,
+    pub media_queries: MediaQueryList,
+    /// The media query list as it was written, for serialization.
+    pub media_text: String,
+    /// The imported stylesheet, once it has been loaded. `None` if loading it failed or would
+    /// have created a cycle.
+    pub stylesheet: Option<Arc<Stylesheet>>,
+}
+
+
 impl Stylesheet {
     pub fn from_bytes_iter<I: Iterator<Vec<u8>>>(
             mut input: I, base_url: Url, protocol_encoding_label: Option<&str>,
@@ -63,6 +102,28 @@ impl Stylesheet {
     }
 
     pub fn from_str(css: &str, base_url: Url, origin: StylesheetOrigin) -> Stylesheet {
+        let ((rules, namespaces), errors) = collect_css_errors(|| {
+            Stylesheet::parse_rules(css, &base_url)
+        });
+        let errors = errors.into_iter().map(|(line, column, message)| {
+            CSSError {
+                url: base_url.serialize(),
+                line: line,
+                column: column,
+                message: message,
+                text: css.lines().nth(line - 1).unwrap_or("").to_string(),
+            }
+        }).collect();
+        Stylesheet {
+            rules: rules,
+            origin: origin,
+            base_url: base_url,
+            namespaces: namespaces,
+            errors: errors,
+        }
+    }
+
+    fn parse_rules(css: &str, base_url: &Url) -> (Vec<CSSRule>, NamespaceMap) {
         static STATE_CHARSET: uint = 1;
         static STATE_IMPORTS: uint = 2;
         static STATE_NAMESPACES: uint = 3;
@@ -77,7 +138,7 @@ impl Stylesheet {
             match rule {
                 QualifiedRule_(rule) => {
                     next_state = STATE_BODY;
-                    parse_style_rule(rule, &mut rules, &namespaces, &base_url)
+                    parse_style_rule(rule, &mut rules, &namespaces, base_url)
                 },
                 AtRule_(rule) => {
                     let lower_name = rule.name.as_slice().to_ascii_lower();
@@ -96,8 +157,7 @@ impl Stylesheet {
                                               "@import must be before any rule but @charset")
                             } else {
                                 next_state = STATE_IMPORTS;
-                                // TODO: support @import
-                                log_css_error(rule.location, "@import is not supported yet")
+                                parse_import_rule(rule, &mut rules, base_url)
                             }
                         },
                         "namespace" => {
@@ -114,16 +174,202 @@ impl Stylesheet {
                         },
                         _ => {
                             next_state = STATE_BODY;
-                            parse_nested_at_rule(lower_name.as_slice(), rule, &mut rules, &namespaces, &base_url)
+                            parse_nested_at_rule(lower_name.as_slice(), rule, &mut rules, &namespaces, base_url)
                         },
                     }
                 },
             }
             state = next_state;
         }
-        Stylesheet {
-            rules: rules,
-            origin: origin,
+        (rules, namespaces)
+    }
+
+    pub fn rules(&self) -> &[CSSRule] {
+        self.rules.as_slice()
+    }
+
+    /// Takes the errors found while parsing the stylesheet, to report them.
+    pub fn take_errors(&mut self) -> Vec<CSSError> {
+        mem::replace(&mut self.errors, vec!())
+    }
+
+    /// Parses a single rule, as `CSSStyleSheet.insertRule()` does. @charset, @import and
+    /// @namespace rules can't be inserted once the stylesheet has been parsed.
+    pub fn parse_rule(&self, css: &str) -> Result<CSSRule, ()> {
+        let mut rules = vec!();
+        let mut count = 0u;
+        for rule in ErrorLoggerIterator(parse_stylesheet_rules(tokenize(css))) {
+            count += 1;
+            match rule {
+                QualifiedRule_(rule) => {
+                    parse_style_rule(rule, &mut rules, &self.namespaces, &self.base_url)
+                }
+                AtRule_(rule) => {
+                    let lower_name = rule.name.as_slice().to_ascii_lower();
+                    match lower_name.as_slice() {
+                        "charset" | "import" | "namespace" => return Err(()),
+                        _ => parse_nested_at_rule(lower_name.as_slice(), rule, &mut rules,
+                                                  &self.namespaces, &self.base_url),
+                    }
+                }
+            }
+        }
+        if count != 1 || rules.len() != 1 {
+            return Err(())
+        }
+        Ok(rules.pop().unwrap())
+    }
+
+    pub fn insert_rule(&mut self, index: uint, rule: CSSRule) {
+        self.rules.insert(index, rule)
+    }
+
+    pub fn delete_rule(&mut self, index: uint) {
+        self.rules.remove(index);
+    }
+
+    /// Loads the stylesheets imported by this one, and the ones those import in turn. `load`
+    /// fetches and parses a single stylesheet, without loading its imports. A stylesheet that
+    /// imports itself, directly or not, is only loaded once, and so is one that several
+    /// stylesheets import.
+    pub fn load_imports(&mut self, load: |&Url| -> Option<Stylesheet>) {
+        let mut loaded: HashMap<Url, Option<Stylesheet>> = HashMap::new();
+        let mut ancestors = vec!(self.base_url.clone());
+        load_imports(self.rules.as_mut_slice(), &mut ancestors, |url| {
+            match loaded.find(url) {
+                Some(stylesheet) => return stylesheet.clone(),
+                None => {}
+            }
+            let stylesheet = load(url);
+            loaded.insert(url.clone(), stylesheet.clone());
+            stylesheet
+        })
+    }
+}
+
+
+fn load_imports(rules: &mut [CSSRule], ancestors: &mut Vec<Url>,
+                load: |&Url| -> Option<Stylesheet>) {
+    for rule in rules.iter_mut() {
+        let rule = match *rule {
+            CSSImportRule(ref mut rule) => rule,
+            _ => continue,
+        };
+        if ancestors.contains(&rule.url) {
+            error!("@import cycle: {} imports itself", rule.url.serialize());
+            continue
+        }
+        if ancestors.len() > MAX_IMPORT_DEPTH {
+            error!("@import rules are nested too deeply at {}", rule.url.serialize());
+            continue
+        }
+        let mut stylesheet = match load(&rule.url) {
+            Some(stylesheet) => stylesheet,
+            None => continue,
+        };
+        ancestors.push(rule.url.clone());
+        load_imports(stylesheet.rules.as_mut_slice(), ancestors, |url| load(url));
+        ancestors.pop();
+        rule.stylesheet = Some(Arc::new(stylesheet));
+    }
+}
+
+
+impl CSSRule {
+    /// Serializes the rule, as `CSSRule.cssText` does.
+    pub fn to_css(&self) -> String {
+        match *self {
+            CSSImportRule(ref rule) => {
+                if rule.media_text.is_empty() {
+                    format!("@import url(\"{}\");", rule.url.serialize())
+                } else {
+                    format!("@import url(\"{}\") {};", rule.url.serialize(), rule.media_text)
+                }
+            }
+            CSSStyleRule(ref rule) => {
+                let declarations = rule.declarations.to_css();
+                if declarations.is_empty() {
+                    format!("{} {{ }}", rule.selector_text)
+                } else {
+                    format!("{} {{ {} }}", rule.selector_text, declarations)
+                }
+            }
+            CSSMediaRule(ref rule) => {
+                let mut css = format!("@media {} {{\n", rule.media_text);
+                for rule in rule.rules.iter() {
+                    css.push_str(format!("  {}\n", rule.to_css()).as_slice());
+                }
+                css.push_str("}");
+                css
+            }
+            CSSSupportsRule(ref rule) => {
+                let mut css = format!("@supports {} {{\n", rule.condition_text);
+                for rule in rule.rules.iter() {
+                    css.push_str(format!("  {}\n", rule.to_css()).as_slice());
+                }
+                css.push_str("}");
+                css
+            }
+            CSSFontFaceRule(ref rule) => {
+                let sources: Vec<String> = rule.sources.iter().map(|source| {
+                    match *source {
+                        UrlSource_(ref source) => {
+                            let hints: Vec<String> = source.format_hints.iter().map(|hint| {
+                                format!("\"{}\"", hint)
+                            }).collect();
+                            if hints.is_empty() {
+                                format!("url(\"{}\")", source.url)
+                            } else {
+                                format!("url(\"{}\") format({})", source.url, hints.connect(", "))
+                            }
+                        }
+                        LocalSource(ref name) => format!("local(\"{}\")", name),
+                    }
+                }).collect();
+                let mut css = format!("@font-face {{ font-family: \"{}\"; src: {}; ",
+                                      rule.family, sources.connect(", "));
+                let full_range = rule.unicode_range.len() == 1 &&
+                    rule.unicode_range[0].start == 0 && rule.unicode_range[0].end == MAX_CODEPOINT;
+                if !full_range {
+                    let ranges: Vec<String> = rule.unicode_range.iter().map(|range| {
+                        if range.start == range.end {
+                            format!("U+{:X}", range.start)
+                        } else {
+                            format!("U+{:X}-{:X}", range.start, range.end)
+                        }
+                    }).collect();
+                    css.push_str(format!("unicode-range: {}; ", ranges.connect(", ")).as_slice());
+                }
+                let display = match rule.display {
+                    AutoFontDisplay => None,
+                    BlockFontDisplay => Some("block"),
+                    SwapFontDisplay => Some("swap"),
+                    FallbackFontDisplay => Some("fallback"),
+                    OptionalFontDisplay => Some("optional"),
+                };
+                match display {
+                    Some(display) => {
+                        css.push_str(format!("font-display: {}; ", display).as_slice())
+                    }
+                    None => {}
+                }
+                css.push_str("}");
+                css
+            }
+            CSSKeyframesRule(ref rule) => {
+                let mut css = format!("@keyframes {} {{\n", rule.name);
+                for keyframe in rule.keyframes.iter() {
+                    let declarations = keyframe.declarations.to_css();
+                    if declarations.is_empty() {
+                        css.push_str(format!("  {} {{ }}\n", keyframe.selector_text).as_slice());
+                    } else {
+                        css.push_str(format!("  {} {{ {} }}\n", keyframe.selector_text,
+                                             declarations).as_slice());
+                    }
+                }
+                css.push_str("}");
+                css
+            }
         }
     }
 }
@@ -132,11 +378,11 @@ impl Stylesheet {
 pub fn parse_style_rule(rule: QualifiedRule, parent_rules: &mut Vec<CSSRule>,
                         namespaces: &NamespaceMap, base_url: &Url) {
     let QualifiedRule { location, prelude, block} = rule;
-    // FIXME: avoid doing this for valid selectors
     let serialized = prelude.iter().to_css();
     match selectors::parse_selector_list(prelude.into_iter(), namespaces) {
         Ok(selectors) => parent_rules.push(CSSStyleRule(StyleRule{
             selectors: selectors,
+            selector_text: serialized.as_slice().trim().to_string(),
             declarations: properties::parse_property_declaration_list(block.into_iter(), base_url)
         })),
         Err(()) => log_css_error(location, format!(
@@ -145,12 +391,46 @@ pub fn parse_style_rule(rule: QualifiedRule, parent_rules: &mut Vec<CSSRule>,
 }
 
 
+pub fn parse_import_rule(rule: AtRule, parent_rules: &mut Vec<CSSRule>, base_url: &Url) {
+    let location = rule.location;
+    if rule.block.is_some() {
+        log_css_error(location, "Invalid @import rule");
+        return
+    }
+    let prelude = rule.prelude.as_slice();
+    let start = prelude.iter().position(|value| *value != WhiteSpace).unwrap_or(prelude.len());
+    let url = match prelude.get(start) {
+        Some(&URL(ref url)) | Some(&QuotedString(ref url)) => url.clone(),
+        _ => {
+            log_css_error(location, "Invalid @import rule");
+            return
+        }
+    };
+    let media = prelude.slice_from(start + 1);
+    let url = match UrlParser::new().base_url(base_url).parse(url.as_slice()) {
+        Ok(url) => url,
+        Err(_) => {
+            log_css_error(location, format!("Invalid @import URL: {}", url).as_slice());
+            return
+        }
+    };
+    parent_rules.push(CSSImportRule(ImportRule {
+        url: url,
+        media_queries: parse_media_query_list(media),
+        media_text: media.iter().to_css().as_slice().trim().to_string(),
+        stylesheet: None,
+    }))
+}
+
+
 // lower_name is passed explicitly to avoid computing it twice.
 pub fn parse_nested_at_rule(lower_name: &str, rule: AtRule,
                             parent_rules: &mut Vec<CSSRule>, namespaces: &NamespaceMap, base_url: &Url) {
     match lower_name {
         "media" => parse_media_rule(rule, parent_rules, namespaces, base_url),
+        "supports" => parse_supports_rule(rule, parent_rules, namespaces, base_url),
         "font-face" => parse_font_face_rule(rule, parent_rules, base_url),
+        "keyframes" => parse_keyframes_rule(rule, parent_rules, base_url),
         _ => log_css_error(rule.location,
                            format!("Unsupported at-rule: @{:s}", lower_name).as_slice())
     }
@@ -161,11 +441,22 @@ pub fn iter_style_rules<'a>(rules: &[CSSRule], device: &media_queries::Device,
                             callback: |&StyleRule|) {
     for rule in rules.iter() {
         match *rule {
+            CSSImportRule(ref rule) => if rule.media_queries.evaluate(device) {
+                match rule.stylesheet {
+                    Some(ref stylesheet) => {
+                        iter_style_rules(stylesheet.rules.as_slice(), device, |s| callback(s))
+                    }
+                    None => {}
+                }
+            },
             CSSStyleRule(ref rule) => callback(rule),
             CSSMediaRule(ref rule) => if rule.media_queries.evaluate(device) {
                 iter_style_rules(rule.rules.as_slice(), device, |s| callback(s))
             },
-            CSSFontFaceRule(_) => {},
+            CSSSupportsRule(ref rule) => if rule.enabled {
+                iter_style_rules(rule.rules.as_slice(), device, |s| callback(s))
+            },
+            CSSFontFaceRule(_) | CSSKeyframesRule(_) => {},
         }
     }
 }
@@ -179,6 +470,37 @@ pub fn iter_stylesheet_media_rules(stylesheet: &Stylesheet, callback: |&MediaRul
     }
 }
 
+/// Calls `callback` with the media query lists that decide which rules of the stylesheet apply,
+/// including those nested in other conditional rules and those of the stylesheets it imports.
+#[inline]
+pub fn iter_stylesheet_media_queries(stylesheet: &Stylesheet, callback: |&MediaQueryList|) {
+    iter_media_queries(stylesheet.rules.as_slice(), callback)
+}
+
+fn iter_media_queries(rules: &[CSSRule], callback: |&MediaQueryList|) {
+    for rule in rules.iter() {
+        match *rule {
+            CSSImportRule(ref rule) => {
+                callback(&rule.media_queries);
+                match rule.stylesheet {
+                    Some(ref stylesheet) => {
+                        iter_media_queries(stylesheet.rules.as_slice(), |queries| callback(queries))
+                    }
+                    None => {}
+                }
+            }
+            CSSMediaRule(ref rule) => {
+                callback(&rule.media_queries);
+                iter_media_queries(rule.rules.as_slice(), |queries| callback(queries))
+            }
+            CSSSupportsRule(ref rule) => if rule.enabled {
+                iter_media_queries(rule.rules.as_slice(), |queries| callback(queries))
+            },
+            CSSStyleRule(_) | CSSFontFaceRule(_) | CSSKeyframesRule(_) => {}
+        }
+    }
+}
+
 #[inline]
 pub fn iter_stylesheet_style_rules(stylesheet: &Stylesheet, device: &media_queries::Device,
                                    callback: |&StyleRule|) {
@@ -188,6 +510,184 @@ pub fn iter_stylesheet_style_rules(stylesheet: &Stylesheet, device: &media_queri
 
 #[inline]
 pub fn iter_font_face_rules(stylesheet: &Stylesheet, device: &Device,
-                            callback: |family: &str, source: &Source|) {
+                            callback: |&FontFaceRule|) {
     iter_font_face_rules_inner(stylesheet.rules.as_slice(), device, callback)
 }
+
+/// Calls `callback` with the @keyframes rules that apply, in order, so that a later rule
+/// replaces an earlier one with the same name.
+#[inline]
+pub fn iter_keyframes_rules(stylesheet: &Stylesheet, device: &Device,
+                            callback: |&KeyframesRule|) {
+    iter_keyframes_rules_inner(stylesheet.rules.as_slice(), device, callback)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Stylesheet, CSSFontFaceRule, CSSImportRule, iter_stylesheet_style_rules};
+    use super::iter_stylesheet_media_queries;
+    use media_queries::{Device, Screen};
+    use geom::scale_factor::ScaleFactor;
+    use geom::size::TypedSize2D;
+    use selector_matching::AuthorOrigin;
+    use url::Url;
+
+    fn serialize(stylesheet: &Stylesheet) -> Vec<String> {
+        stylesheet.rules().iter().map(|rule| rule.to_css()).collect()
+    }
+
+    #[test]
+    fn test_serialize_rules() {
+        let url = Url::parse("http://localhost/").unwrap();
+        let css = "a > b, .c{color:red;margin : 0 !important; bogus: 1}\
+                   @media screen and (min-width: 100px) { #d { } }\
+                   @font-face { font-family: Foo; src: url(foo.woff) format('woff'), local(Bar) }";
+        let stylesheet = Stylesheet::from_str(css, url, AuthorOrigin);
+        assert_eq!(serialize(&stylesheet), vec!(
+            "a > b, .c { color: red; margin: 0 !important; }".to_string(),
+            "@media screen and (min-width: 100px) {\n  #d { }\n}".to_string(),
+            "@font-face { font-family: \"Foo\"; \
+             src: url(\"http://localhost/foo.woff\") format(\"woff\"), local(\"Bar\"); }".to_string()));
+    }
+
+    #[test]
+    fn test_font_face_descriptors() {
+        let url = Url::parse("http://localhost/").unwrap();
+        let css = "@font-face { font-family: Foo; src: local(Foo); \
+                                unicode-range: U+0-7F, U+4??, U+20AC, U+10FFF0-1FFFFF; \
+                                font-display: swap }\
+                   @font-face { font-family: Bar; src: local(Bar); \
+                                unicode-range: U+100-FF; font-display: bogus }";
+        let stylesheet = Stylesheet::from_str(css, url, AuthorOrigin);
+        assert_eq!(serialize(&stylesheet), vec!(
+            "@font-face { font-family: \"Foo\"; src: local(\"Foo\"); \
+             unicode-range: U+0-7F, U+400-4FF, U+20AC, U+10FFF0-10FFFF; \
+             font-display: swap; }".to_string(),
+            "@font-face { font-family: \"Bar\"; src: local(\"Bar\"); }".to_string()));
+
+        match stylesheet.rules()[0] {
+            CSSFontFaceRule(ref rule) => {
+                assert!(rule.covers('A'));
+                assert!(rule.covers('\u0416'));
+                assert!(rule.covers('\u20ac'));
+                assert!(!rule.covers('\u00e9'));
+                assert!(!rule.covers('\u20ad'));
+            }
+            _ => panic!("expected a @font-face rule"),
+        }
+    }
+
+    #[test]
+    fn test_insert_and_delete_rules() {
+        let url = Url::parse("http://localhost/").unwrap();
+        let mut stylesheet = Stylesheet::from_str("a { color: red }", url, AuthorOrigin);
+
+        let rule = stylesheet.parse_rule("b { color: blue }").unwrap();
+        stylesheet.insert_rule(0, rule);
+        let rule = stylesheet.parse_rule("@media print { c { } }").unwrap();
+        stylesheet.insert_rule(2, rule);
+        assert_eq!(serialize(&stylesheet), vec!(
+            "b { color: blue; }".to_string(),
+            "a { color: red; }".to_string(),
+            "@media print {\n  c { }\n}".to_string()));
+
+        stylesheet.delete_rule(1);
+        assert_eq!(stylesheet.rules().len(), 2);
+
+        assert!(stylesheet.parse_rule("").is_err());
+        assert!(stylesheet.parse_rule("a { } b { }").is_err());
+        assert!(stylesheet.parse_rule("@import url(foo.css);").is_err());
+        assert!(stylesheet.parse_rule("$$ { }").is_err());
+    }
+
+    #[test]
+    fn test_import_rules() {
+        let url = Url::parse("http://localhost/dir/").unwrap();
+        let css = "@import url(a.css); @import 'b.css' print, (min-width: 10px); \
+                   c { } @import url(d.css);";
+        let stylesheet = Stylesheet::from_str(css, url, AuthorOrigin);
+        assert_eq!(serialize(&stylesheet), vec!(
+            "@import url(\"http://localhost/dir/a.css\");".to_string(),
+            "@import url(\"http://localhost/dir/b.css\") print, (min-width: 10px);".to_string(),
+            "c { }".to_string()));
+    }
+
+    #[test]
+    fn test_load_imports() {
+        let url = Url::parse("http://localhost/").unwrap();
+        let css = "@import url(a.css); @import url(print.css) print; main { }";
+        let mut stylesheet = Stylesheet::from_str(css, url, AuthorOrigin);
+        let mut loaded = vec!();
+        stylesheet.load_imports(|url| {
+            loaded.push(url.serialize());
+            let css = match url.path().unwrap()[0].as_slice() {
+                "a.css" => "@import url(b.css); @import url(shared.css); a { }",
+                "b.css" => "@import url(a.css); @import url(/); @import url(missing.css); b { }",
+                "print.css" => "@import url(shared.css); @import url(missing.css); print { }",
+                "shared.css" => "shared { }",
+                _ => return None,
+            };
+            Some(Stylesheet::from_str(css, url.clone(), AuthorOrigin))
+        });
+        assert_eq!(loaded, vec!(
+            "http://localhost/a.css".to_string(),
+            "http://localhost/b.css".to_string(),
+            "http://localhost/missing.css".to_string(),
+            "http://localhost/shared.css".to_string(),
+            "http://localhost/print.css".to_string()));
+        match stylesheet.rules()[0] {
+            CSSImportRule(ref rule) => assert!(rule.stylesheet.is_some()),
+            _ => panic!("expected an @import rule"),
+        }
+
+        // Imported rules come first, in the order they are imported, and only if their media
+        // queries match.
+        let device = Device::new(Screen, TypedSize2D(800.0, 600.0), TypedSize2D(800.0, 600.0),
+                                 ScaleFactor(1.0));
+        let mut selectors = vec!();
+        iter_stylesheet_style_rules(&stylesheet, &device, |rule| {
+            selectors.push(rule.selector_text.clone());
+        });
+        assert_eq!(selectors, vec!("b".to_string(), "shared".to_string(), "a".to_string(),
+                                   "main".to_string()));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let url = Url::parse("http://localhost/style.css").unwrap();
+        let css = "a { color: red }\n@bogus;\nb { color: bogus }";
+        let mut stylesheet = Stylesheet::from_str(css, url, AuthorOrigin);
+        let errors = stylesheet.take_errors();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].url, "http://localhost/style.css".to_string());
+        assert_eq!((errors[0].line, errors[0].text.as_slice()), (2, "@bogus;"));
+        assert_eq!((errors[1].line, errors[1].text.as_slice()), (3, "b { color: bogus }"));
+        assert!(stylesheet.take_errors().is_empty());
+    }
+
+    #[test]
+    fn test_nested_media_queries_follow_resize() {
+        // Resizing has to re-evaluate the media queries nested in other conditional rules, but
+        // not those in @supports rules that are disabled.
+        let url = Url::parse("http://localhost/").unwrap();
+        let css = "@supports (display: block) { @media (min-width: 500px) { a { } } }\
+                   @supports (display: bogus) { @media (min-width: 700px) { b { } } }\
+                   @media screen { @media (max-width: 300px) { c { } } }";
+        let stylesheet = Stylesheet::from_str(css, url, AuthorOrigin);
+        let wide = Device::new(Screen, TypedSize2D(800.0, 600.0), TypedSize2D(800.0, 600.0),
+                               ScaleFactor(1.0));
+        let narrow = Device::new(Screen, TypedSize2D(200.0, 600.0), TypedSize2D(800.0, 600.0),
+                                 ScaleFactor(1.0));
+        let mut changes = vec!();
+        iter_stylesheet_media_queries(&stylesheet, |media_queries| {
+            changes.push(media_queries.evaluate(&wide) != media_queries.evaluate(&narrow));
+        });
+        assert_eq!(changes, vec!(true, false, true));
+
+        let mut selectors = vec!();
+        iter_stylesheet_style_rules(&stylesheet, &narrow, |rule| {
+            selectors.push(rule.selector_text.clone());
+        });
+        assert_eq!(selectors, vec!("c".to_string()));
+    }
+}
@@ -0,0 +1,236 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+use std::ascii::AsciiExt;
+use cssparser::{parse_rule_list, tokenize, ToCss};
+use cssparser::ast::*;
+
+use errors::{ErrorLoggerIterator, log_css_error};
+use namespaces::NamespaceMap;
+use parsing_utils::{BufferedIter, ParserIter, trim_whitespace};
+use properties::is_supported_declaration;
+use stylesheets::{CSSRule, CSSSupportsRule, parse_style_rule, parse_nested_at_rule};
+use url::Url;
+
+#[deriving(Clone)]
+pub struct SupportsRule {
+    /// Whether the condition is true. Support can't change, so this is decided when the rule is
+    /// parsed.
+    pub enabled: bool,
+    /// The condition as it was written, for serialization.
+    pub condition_text: String,
+    pub rules: Vec<CSSRule>,
+}
+
+// http://dev.w3.org/csswg/css-conditional/#supports_condition
+pub enum SupportsCondition {
+    SupportsNot(Box<SupportsCondition>),
+    SupportsAnd(Vec<SupportsCondition>),
+    SupportsOr(Vec<SupportsCondition>),
+    /// A property name and value, supported if a style rule would use the declaration.
+    SupportsDeclaration(String, Vec<ComponentValue>),
+    /// Anything else in parentheses, which is never supported.
+    SupportsGeneralEnclosed,
+}
+
+impl SupportsCondition {
+    pub fn evaluate(&self, base_url: &Url) -> bool {
+        match *self {
+            SupportsNot(ref condition) => !condition.evaluate(base_url),
+            SupportsAnd(ref conditions) => conditions.iter().all(|c| c.evaluate(base_url)),
+            SupportsOr(ref conditions) => conditions.iter().any(|c| c.evaluate(base_url)),
+            SupportsDeclaration(ref name, ref value) => {
+                is_supported_declaration(name.as_slice(), value.as_slice(), base_url)
+            }
+            SupportsGeneralEnclosed => false,
+        }
+    }
+}
+
+pub fn parse_supports_rule(rule: AtRule, parent_rules: &mut Vec<CSSRule>,
+                           namespaces: &NamespaceMap, base_url: &Url) {
+    let condition = match parse_supports_condition(rule.prelude.as_slice()) {
+        Ok(condition) => condition,
+        Err(()) => {
+            log_css_error(rule.location, "Invalid @supports condition");
+            return
+        }
+    };
+    let condition_text = rule.prelude.iter().to_css();
+    let block = match rule.block {
+        Some(block) => block,
+        None => {
+            log_css_error(rule.location, "Invalid @supports rule");
+            return
+        }
+    };
+    let mut rules = vec!();
+    for rule in ErrorLoggerIterator(parse_rule_list(block.into_iter())) {
+        match rule {
+            QualifiedRule_(rule) => parse_style_rule(rule, &mut rules, namespaces, base_url),
+            AtRule_(rule) => parse_nested_at_rule(
+                rule.name.as_slice().to_ascii_lower().as_slice(), rule, &mut rules, namespaces,
+                base_url),
+        }
+    }
+    parent_rules.push(CSSSupportsRule(SupportsRule {
+        enabled: condition.evaluate(base_url),
+        condition_text: condition_text.as_slice().trim().to_string(),
+        rules: rules,
+    }))
+}
+
+pub fn parse_supports_condition(input: &[ComponentValue]) -> Result<SupportsCondition, ()> {
+    let iter = &mut BufferedIter::new(input.skip_whitespace());
+    match iter.next() {
+        Some(&Ident(ref value)) if value.as_slice().eq_ignore_ascii_case("not") => {
+            let condition = try!(parse_condition_in_parens(iter));
+            return if iter.is_eof() { Ok(SupportsNot(box condition)) } else { Err(()) }
+        }
+        Some(value) => iter.push_back(value),
+        None => return Err(()),
+    }
+
+    let mut conditions = vec!(try!(parse_condition_in_parens(iter)));
+    // "and" and "or" can't be mixed without parentheses.
+    let mut operator: Option<String> = None;
+    loop {
+        let keyword = match iter.next() {
+            Some(&Ident(ref value)) => value.as_slice().to_ascii_lower(),
+            Some(_) => return Err(()),
+            None => break,
+        };
+        if keyword.as_slice() != "and" && keyword.as_slice() != "or" {
+            return Err(())
+        }
+        if operator.is_some() && operator.as_ref() != Some(&keyword) {
+            return Err(())
+        }
+        operator = Some(keyword);
+        conditions.push(try!(parse_condition_in_parens(iter)));
+    }
+
+    match operator {
+        None => Ok(conditions.pop().unwrap()),
+        Some(ref operator) if operator.as_slice() == "and" => Ok(SupportsAnd(conditions)),
+        Some(_) => Ok(SupportsOr(conditions)),
+    }
+}
+
+fn parse_condition_in_parens(iter: ParserIter) -> Result<SupportsCondition, ()> {
+    match iter.next() {
+        Some(&ParenthesisBlock(ref block)) => {
+            match parse_supports_condition(block.as_slice()) {
+                Ok(condition) => return Ok(condition),
+                Err(()) => {}
+            }
+            match parse_declaration(block.as_slice()) {
+                Ok(condition) => Ok(condition),
+                Err(()) => Ok(SupportsGeneralEnclosed),
+            }
+        }
+        Some(&Function(..)) => Ok(SupportsGeneralEnclosed),
+        _ => Err(()),
+    }
+}
+
+fn parse_declaration(input: &[ComponentValue]) -> Result<SupportsCondition, ()> {
+    let mut iter = input.iter().enumerate().filter(|&(_, value)| *value != WhiteSpace);
+    let name = match iter.next() {
+        Some((_, &Ident(ref name))) => name.clone(),
+        _ => return Err(()),
+    };
+    let colon = match iter.next() {
+        Some((index, &Colon)) => index,
+        _ => return Err(()),
+    };
+    let value = trim_whitespace(input.slice_from(colon + 1));
+    Ok(SupportsDeclaration(name, value.to_vec()))
+}
+
+fn tokenize_str(input: &str) -> Vec<ComponentValue> {
+    tokenize(input).map(|(token, _)| token).collect()
+}
+
+/// Whether the property and value are supported, as `CSS.supports(property, value)` decides.
+pub fn supports_declaration(property: &str, value: &str) -> bool {
+    let base_url = Url::parse("about:blank").unwrap();
+    let value = tokenize_str(value);
+    is_supported_declaration(property, trim_whitespace(value.as_slice()), &base_url)
+}
+
+/// Whether the condition is true, as `CSS.supports(conditionText)` decides. The condition may
+/// also be a declaration without parentheses.
+pub fn supports_condition(condition_text: &str) -> bool {
+    let base_url = Url::parse("about:blank").unwrap();
+    let condition = match parse_supports_condition(tokenize_str(condition_text).as_slice()) {
+        Ok(condition) => condition,
+        Err(()) => {
+            let input = format!("({})", condition_text);
+            match parse_supports_condition(tokenize_str(input.as_slice()).as_slice()) {
+                Ok(condition) => condition,
+                Err(()) => return false,
+            }
+        }
+    };
+    condition.evaluate(&base_url)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{supports_condition, supports_declaration};
+    use stylesheets::{Stylesheet, iter_stylesheet_style_rules};
+    use media_queries::{Device, Screen};
+    use geom::scale_factor::ScaleFactor;
+    use geom::size::TypedSize2D;
+    use selector_matching::AuthorOrigin;
+    use url::Url;
+
+    #[test]
+    fn test_supports_declaration() {
+        assert!(supports_declaration("display", "block"));
+        assert!(supports_declaration("DISPLAY", " block "));
+        assert!(supports_declaration("margin", "0 auto"));
+        assert!(!supports_declaration("display", "bogus"));
+        assert!(!supports_declaration("bogus", "block"));
+        assert!(!supports_declaration("display", ""));
+    }
+
+    #[test]
+    fn test_supports_condition() {
+        assert!(supports_condition("(display: block)"));
+        assert!(supports_condition("display: block"));
+        assert!(supports_condition("not (display: bogus)"));
+        assert!(supports_condition("(display: block) and (color: red)"));
+        assert!(!supports_condition("(display: block) and (color: bogus)"));
+        assert!(supports_condition("(display: bogus) or (color: red)"));
+        assert!(supports_condition("((display: bogus) or (color: red)) and (margin: 0)"));
+        assert!(!supports_condition("(display: block) and (color: red) or (margin: 0)"));
+        assert!(!supports_condition("(display: block) (color: red)"));
+        assert!(!supports_condition("(unknown syntax)"));
+        assert!(!supports_condition("foo(display: block)"));
+        assert!(!supports_condition(""));
+    }
+
+    #[test]
+    fn test_supports_rule() {
+        let url = Url::parse("http://localhost/").unwrap();
+        let css = "@supports (display: block) { a { } }\
+                   @supports not (display: block) { b { } }\
+                   @supports (display: bogus) or (not (color: bogus)) { c { } }\
+                   @supports display: block { d { } }";
+        let stylesheet = Stylesheet::from_str(css, url, AuthorOrigin);
+        assert_eq!(stylesheet.rules().len(), 3);
+        assert_eq!(stylesheet.rules()[0].to_css(),
+                   "@supports (display: block) {\n  a { }\n}".to_string());
+
+        let device = Device::new(Screen, TypedSize2D(800.0, 600.0), TypedSize2D(800.0, 600.0),
+                                 ScaleFactor(1.0));
+        let mut selectors = vec!();
+        iter_stylesheet_style_rules(&stylesheet, &device, |rule| {
+            selectors.push(rule.selector_text.clone());
+        });
+        assert_eq!(selectors, vec!("a".to_string(), "c".to_string()));
+    }
+}
@@ -0,0 +1,955 @@
+/* This Source Code Form is subject to the terms of the Mozilla Public
+ * License, v. 2.0. If a copy of the MPL was not distributed with this
+ * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
+
+//! The Unicode Bidirectional Algorithm, as described in Unicode Standard Annex #9:
+//!
+//!     http://www.unicode.org/reports/tr9/
+//!
+//! Callers resolve the embedding levels of a paragraph with `BidiParagraph::new` and then reorder
+//! each line for display with `visual_order`.
+
+use std::cmp::max;
+
+/// The bidirectional character types of UAX #9, § 3.2.
+#[deriving(Clone, PartialEq, Eq, Show)]
+pub enum BidiClass {
+    AL,
+    AN,
+    B,
+    BN,
+    CS,
+    EN,
+    ES,
+    ET,
+    FSI,
+    L,
+    LRE,
+    LRI,
+    LRO,
+    NSM,
+    ON,
+    PDF,
+    PDI,
+    R,
+    RLE,
+    RLI,
+    RLO,
+    S,
+    WS,
+}
+
+/// The deepest explicit embedding level (BD2).
+static MAX_DEPTH: u8 = 125;
+
+/// The maximum number of nested bracket pairs that rule N0 looks for (BD16).
+static MAX_BRACKET_STACK_DEPTH: uint = 63;
+
+/// Returns the bidirectional character type of `c`.
+pub fn bidi_class(c: char) -> BidiClass {
+    let (mut low, mut high) = (0, BIDI_CLASS_TABLE.len());
+    while low < high {
+        let middle = (low + high) / 2;
+        let (start, end, class) = BIDI_CLASS_TABLE[middle];
+        if c < start {
+            high = middle
+        } else if c > end {
+            low = middle + 1
+        } else {
+            return class
+        }
+    }
+    L
+}
+
+/// Finds the first strong character that is not inside an isolate, per rule P2. Arabic letters
+/// count as right-to-left.
+fn first_strong_class(classes: &[BidiClass]) -> Option<BidiClass> {
+    let mut isolate_depth = 0u;
+    for &class in classes.iter() {
+        match class {
+            LRI | RLI | FSI => isolate_depth += 1,
+            PDI if isolate_depth > 0 => isolate_depth -= 1,
+            L if isolate_depth == 0 => return Some(L),
+            R | AL if isolate_depth == 0 => return Some(R),
+            B => break,
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Determines the embedding level of a paragraph from its text, per rules P2 and P3.
+pub fn paragraph_level_for_text(text: &[char]) -> u8 {
+    let classes: Vec<BidiClass> = text.iter().map(|&c| bidi_class(c)).collect();
+    level_for_first_strong_class(classes.as_slice())
+}
+
+fn level_for_first_strong_class(classes: &[BidiClass]) -> u8 {
+    match first_strong_class(classes) {
+        Some(R) => 1,
+        _ => 0,
+    }
+}
+
+#[inline]
+fn is_rtl_level(level: u8) -> bool {
+    level % 2 == 1
+}
+
+/// Returns the strong direction of the given embedding level.
+#[inline]
+fn class_for_level(level: u8) -> BidiClass {
+    if is_rtl_level(level) {
+        R
+    } else {
+        L
+    }
+}
+
+#[inline]
+fn is_removed_by_x9(class: BidiClass) -> bool {
+    match class {
+        RLE | LRE | RLO | LRO | PDF | BN => true,
+        _ => false,
+    }
+}
+
+#[inline]
+fn is_isolate_initiator(class: BidiClass) -> bool {
+    match class {
+        LRI | RLI | FSI => true,
+        _ => false,
+    }
+}
+
+/// The resolved embedding levels of a paragraph of text.
+pub struct BidiParagraph {
+    /// The embedding level of the paragraph. Even levels are left-to-right and odd levels are
+    /// right-to-left.
+    pub level: u8,
+    /// The resolved embedding level of each character of the paragraph.
+    pub levels: Vec<u8>,
+}
+
+/// An entry in the directional status stack of rules X1 to X8.
+struct DirectionalStatus {
+    level: u8,
+    override_class: Option<BidiClass>,
+    isolate: bool,
+}
+
+impl BidiParagraph {
+    /// Resolves the embedding levels of `text`, which is treated as a single paragraph.
+    ///
+    /// If `level` is `None`, the paragraph level is determined from the text. If
+    /// `directional_override` is true, characters outside explicit embeddings and isolates are
+    /// treated as strong characters of the paragraph direction, as for the CSS `unicode-bidi:
+    /// bidi-override` value on a block container.
+    pub fn new(text: &[char], level: Option<u8>, directional_override: bool) -> BidiParagraph {
+        let original_classes: Vec<BidiClass> = text.iter().map(|&c| bidi_class(c)).collect();
+        let original_classes = original_classes.as_slice();
+        let paragraph_level = match level {
+            Some(level) => level,
+            None => level_for_first_strong_class(original_classes),
+        };
+
+        // Fast path: purely left-to-right text resolves to the paragraph level everywhere.
+        let needs_resolution = is_rtl_level(paragraph_level) || directional_override ||
+            original_classes.iter().any(|&class| {
+                match class {
+                    R | AL | AN | LRE | RLE | LRO | RLO | PDF | LRI | RLI | FSI | PDI => true,
+                    _ => false,
+                }
+            });
+        if !needs_resolution {
+            return BidiParagraph {
+                level: paragraph_level,
+                levels: Vec::from_elem(text.len(), paragraph_level),
+            }
+        }
+
+        let (matching_pdis, has_matching_initiator) = find_matching_pdis(original_classes);
+        let mut classes = original_classes.to_vec();
+        let mut levels = Vec::from_elem(text.len(), paragraph_level);
+        resolve_explicit_levels(original_classes,
+                                matching_pdis.as_slice(),
+                                paragraph_level,
+                                directional_override,
+                                classes.as_mut_slice(),
+                                levels.as_mut_slice());
+
+        for sequence in isolating_run_sequences(original_classes,
+                                                matching_pdis.as_slice(),
+                                                has_matching_initiator.as_slice(),
+                                                levels.as_slice(),
+                                                paragraph_level).iter() {
+            sequence.resolve_weak_types(classes.as_mut_slice());
+            sequence.resolve_paired_brackets(text, original_classes, classes.as_mut_slice());
+            sequence.resolve_neutral_types(classes.as_mut_slice());
+            sequence.resolve_implicit_levels(classes.as_slice(), levels.as_mut_slice());
+        }
+
+        // Characters removed by rule X9 take the level of the character before them so that they
+        // don't split up runs.
+        for i in range(0, text.len()) {
+            if is_removed_by_x9(original_classes[i]) {
+                levels[i] = if i == 0 {
+                    paragraph_level
+                } else {
+                    levels[i - 1]
+                }
+            }
+        }
+
+        // Rule L1, for segment and paragraph separators and whitespace at the end of the
+        // paragraph. Whitespace at the end of each line is the caller's responsibility.
+        let mut reset_to_paragraph_level = true;
+        for i in range(0, text.len()).rev() {
+            match original_classes[i] {
+                B | S => {
+                    levels[i] = paragraph_level;
+                    reset_to_paragraph_level = true
+                }
+                WS | LRI | RLI | FSI | PDI | RLE | LRE | RLO | LRO | PDF | BN
+                        if reset_to_paragraph_level => {
+                    levels[i] = paragraph_level
+                }
+                _ => reset_to_paragraph_level = false,
+            }
+        }
+
+        BidiParagraph {
+            level: paragraph_level,
+            levels: levels,
+        }
+    }
+}
+
+/// Finds the matching PDI of each isolate initiator, per BD9. Returns the index of the matching
+/// PDI for each isolate initiator and whether each PDI has a matching initiator.
+fn find_matching_pdis(classes: &[BidiClass]) -> (Vec<Option<uint>>, Vec<bool>) {
+    let mut matching_pdis = Vec::from_elem(classes.len(), None);
+    let mut has_matching_initiator = Vec::from_elem(classes.len(), false);
+    let mut open_isolates = Vec::new();
+    for (i, &class) in classes.iter().enumerate() {
+        match class {
+            LRI | RLI | FSI => open_isolates.push(i),
+            PDI => {
+                match open_isolates.pop() {
+                    Some(initiator) => {
+                        matching_pdis[initiator] = Some(i);
+                        has_matching_initiator[i] = true;
+                    }
+                    None => {}
+                }
+            }
+            B => open_isolates.clear(),
+            _ => {}
+        }
+    }
+    (matching_pdis, has_matching_initiator)
+}
+
+/// Resolves explicit embedding levels and directional overrides, per rules X1 to X8.
+fn resolve_explicit_levels(original_classes: &[BidiClass],
+                           matching_pdis: &[Option<uint>],
+                           paragraph_level: u8,
+                           directional_override: bool,
+                           classes: &mut [BidiClass],
+                           levels: &mut [u8]) {
+    let mut stack = vec!(DirectionalStatus {
+        level: paragraph_level,
+        override_class: if directional_override {
+            Some(class_for_level(paragraph_level))
+        } else {
+            None
+        },
+        isolate: false,
+    });
+    let mut overflow_isolate_count = 0u;
+    let mut overflow_embedding_count = 0u;
+    let mut valid_isolate_count = 0u;
+
+    for i in range(0, original_classes.len()) {
+        let class = original_classes[i];
+        match class {
+            RLE | LRE | RLO | LRO => {
+                let current_level = stack.last().unwrap().level;
+                levels[i] = current_level;
+                let new_level = match class {
+                    RLE | RLO => (current_level + 1) | 1,
+                    _ => (current_level + 2) & !1,
+                };
+                if new_level <= MAX_DEPTH && overflow_isolate_count == 0 &&
+                        overflow_embedding_count == 0 {
+                    stack.push(DirectionalStatus {
+                        level: new_level,
+                        override_class: match class {
+                            RLO => Some(R),
+                            LRO => Some(L),
+                            _ => None,
+                        },
+                        isolate: false,
+                    })
+                } else if overflow_isolate_count == 0 {
+                    overflow_embedding_count += 1
+                }
+            }
+            RLI | LRI | FSI => {
+                let current_level = {
+                    let status = stack.last().unwrap();
+                    levels[i] = status.level;
+                    match status.override_class {
+                        Some(override_class) => classes[i] = override_class,
+                        None => {}
+                    }
+                    status.level
+                };
+                let is_rtl = match class {
+                    RLI => true,
+                    LRI => false,
+                    _ => {
+                        let end = matching_pdis[i].unwrap_or(original_classes.len());
+                        level_for_first_strong_class(original_classes.slice(i + 1, end)) == 1
+                    }
+                };
+                let new_level = if is_rtl {
+                    (current_level + 1) | 1
+                } else {
+                    (current_level + 2) & !1
+                };
+                if new_level <= MAX_DEPTH && overflow_isolate_count == 0 &&
+                        overflow_embedding_count == 0 {
+                    valid_isolate_count += 1;
+                    stack.push(DirectionalStatus {
+                        level: new_level,
+                        override_class: None,
+                        isolate: true,
+                    })
+                } else {
+                    overflow_isolate_count += 1
+                }
+            }
+            PDI => {
+                if overflow_isolate_count > 0 {
+                    overflow_isolate_count -= 1
+                } else if valid_isolate_count > 0 {
+                    overflow_embedding_count = 0;
+                    while !stack.last().unwrap().isolate {
+                        stack.pop();
+                    }
+                    stack.pop();
+                    valid_isolate_count -= 1
+                }
+                let status = stack.last().unwrap();
+                levels[i] = status.level;
+                match status.override_class {
+                    Some(override_class) => classes[i] = override_class,
+                    None => {}
+                }
+            }
+            PDF => {
+                if overflow_isolate_count > 0 {
+                } else if overflow_embedding_count > 0 {
+                    overflow_embedding_count -= 1
+                } else if !stack.last().unwrap().isolate && stack.len() >= 2 {
+                    stack.pop();
+                }
+                levels[i] = stack.last().unwrap().level
+            }
+            B => levels[i] = paragraph_level,
+            BN => levels[i] = stack.last().unwrap().level,
+            _ => {
+                let status = stack.last().unwrap();
+                levels[i] = status.level;
+                match status.override_class {
+                    Some(override_class) => classes[i] = override_class,
+                    None => {}
+                }
+            }
+        }
+    }
+}
+
+/// A sequence of level runs that are resolved together, per BD13.
+struct IsolatingRunSequence {
+    /// The indices of the characters in the sequence, in logical order.
+    indices: Vec<uint>,
+    /// The embedding level of the characters in the sequence.
+    level: u8,
+    /// The start-of-sequence type.
+    sos: BidiClass,
+    /// The end-of-sequence type.
+    eos: BidiClass,
+}
+
+/// Divides the paragraph into isolating run sequences, per rule X10.
+fn isolating_run_sequences(original_classes: &[BidiClass],
+                           matching_pdis: &[Option<uint>],
+                           has_matching_initiator: &[bool],
+                           levels: &[u8],
+                           paragraph_level: u8)
+                           -> Vec<IsolatingRunSequence> {
+    // Find the level runs, ignoring characters removed by rule X9.
+    let mut level_runs: Vec<Vec<uint>> = Vec::new();
+    let mut current_run: Vec<uint> = Vec::new();
+    for i in range(0, original_classes.len()) {
+        if is_removed_by_x9(original_classes[i]) {
+            continue
+        }
+        if !current_run.is_empty() && levels[*current_run.last().unwrap()] != levels[i] {
+            level_runs.push(current_run);
+            current_run = Vec::new();
+        }
+        current_run.push(i)
+    }
+    if !current_run.is_empty() {
+        level_runs.push(current_run)
+    }
+
+    let mut run_starting_at = Vec::from_elem(original_classes.len(), None);
+    for (run_index, run) in level_runs.iter().enumerate() {
+        run_starting_at[run[0]] = Some(run_index)
+    }
+
+    // Chain runs that end with an isolate initiator to the runs that start with the matching PDI.
+    let mut sequences = Vec::new();
+    for run in level_runs.iter() {
+        let first = run[0];
+        if original_classes[first] == PDI && has_matching_initiator[first] {
+            continue
+        }
+
+        let mut indices = run.clone();
+        loop {
+            let last = *indices.last().unwrap();
+            if !is_isolate_initiator(original_classes[last]) {
+                break
+            }
+            match matching_pdis[last].and_then(|pdi| run_starting_at[pdi]) {
+                Some(run_index) => indices.push_all(level_runs[run_index].as_slice()),
+                None => break,
+            }
+        }
+
+        let first = indices[0];
+        let last = *indices.last().unwrap();
+        let level = levels[first];
+        let preceding_level = range(0, first).rev()
+                                             .find(|&i| !is_removed_by_x9(original_classes[i]))
+                                             .map_or(paragraph_level, |i| levels[i]);
+        let following_level = if is_isolate_initiator(original_classes[last]) {
+            paragraph_level
+        } else {
+            range(last + 1, original_classes.len())
+                .find(|&i| !is_removed_by_x9(original_classes[i]))
+                .map_or(paragraph_level, |i| levels[i])
+        };
+        sequences.push(IsolatingRunSequence {
+            indices: indices,
+            level: level,
+            sos: class_for_level(max(level, preceding_level)),
+            eos: class_for_level(max(level, following_level)),
+        })
+    }
+    sequences
+}
+
+/// Returns the strong direction that a resolved type counts as for rules N0 to N2, if any.
+#[inline]
+fn strong_direction(class: BidiClass) -> Option<BidiClass> {
+    match class {
+        L => Some(L),
+        R | AL | EN | AN => Some(R),
+        _ => None,
+    }
+}
+
+#[inline]
+fn is_neutral_or_isolate(class: BidiClass) -> bool {
+    match class {
+        B | S | WS | ON | LRI | RLI | FSI | PDI => true,
+        _ => false,
+    }
+}
+
+impl IsolatingRunSequence {
+    /// Resolves weak types, per rules W1 to W7.
+    fn resolve_weak_types(&self, classes: &mut [BidiClass]) {
+        let indices = self.indices.as_slice();
+
+        // W1: Nonspacing marks take the type of the previous character.
+        let mut previous_class = self.sos;
+        for &i in indices.iter() {
+            if classes[i] == NSM {
+                classes[i] = match previous_class {
+                    LRI | RLI | FSI | PDI => ON,
+                    _ => previous_class,
+                }
+            }
+            previous_class = classes[i]
+        }
+
+        // W2 and W3: European numbers after Arabic letters become Arabic numbers, and Arabic
+        // letters become right-to-left.
+        let mut last_strong_class = self.sos;
+        for &i in indices.iter() {
+            match classes[i] {
+                L | R => last_strong_class = classes[i],
+                AL => {
+                    last_strong_class = AL;
+                    classes[i] = R
+                }
+                EN if last_strong_class == AL => classes[i] = AN,
+                _ => {}
+            }
+        }
+
+        // W4: A single separator between two numbers of the same type takes their type.
+        for k in range(1, max(indices.len(), 2) - 1) {
+            let (before, after) = (classes[indices[k - 1]], classes[indices[k + 1]]);
+            match (before, classes[indices[k]], after) {
+                (EN, ES, EN) | (EN, CS, EN) => classes[indices[k]] = EN,
+                (AN, CS, AN) => classes[indices[k]] = AN,
+                _ => {}
+            }
+        }
+
+        // W5: Terminators next to European numbers become European numbers.
+        let mut k = 0;
+        while k < indices.len() {
+            if classes[indices[k]] != ET {
+                k += 1;
+                continue
+            }
+            let start = k;
+            while k < indices.len() && classes[indices[k]] == ET {
+                k += 1
+            }
+            if (start > 0 && classes[indices[start - 1]] == EN) ||
+                    (k < indices.len() && classes[indices[k]] == EN) {
+                for j in range(start, k) {
+                    classes[indices[j]] = EN
+                }
+            }
+        }
+
+        // W6: Remaining separators and terminators become neutral.
+        for &i in indices.iter() {
+            match classes[i] {
+                ES | ET | CS => classes[i] = ON,
+                _ => {}
+            }
+        }
+
+        // W7: European numbers after left-to-right text become left-to-right.
+        let mut last_strong_class = self.sos;
+        for &i in indices.iter() {
+            match classes[i] {
+                L | R => last_strong_class = classes[i],
+                EN if last_strong_class == L => classes[i] = L,
+                _ => {}
+            }
+        }
+    }
+
+    /// Resolves paired brackets, per rule N0.
+    fn resolve_paired_brackets(&self,
+                               text: &[char],
+                               original_classes: &[BidiClass],
+                               classes: &mut [BidiClass]) {
+        let indices = self.indices.as_slice();
+
+        // Identify the bracket pairs (BD16).
+        let mut open_brackets: Vec<(char, uint)> = Vec::new();
+        let mut pairs = Vec::new();
+        for (k, &i) in indices.iter().enumerate() {
+            if classes[i] != ON {
+                continue
+            }
+            match closing_bracket_for(text[i]) {
+                Some(closing_bracket) => {
+                    if open_brackets.len() == MAX_BRACKET_STACK_DEPTH {
+                        break
+                    }
+                    open_brackets.push((closing_bracket, k));
+                }
+                None => {
+                    let mut depth = open_brackets.len();
+                    while depth > 0 {
+                        let (closing_bracket, opening_position) = open_brackets[depth - 1];
+                        if closing_bracket == text[i] {
+                            pairs.push((opening_position, k));
+                            open_brackets.truncate(depth - 1);
+                            break
+                        }
+                        depth -= 1
+                    }
+                }
+            }
+        }
+        pairs.sort();
+
+        let embedding_direction = class_for_level(self.level);
+        for &(opening, closing) in pairs.iter() {
+            let mut found_embedding_direction = false;
+            let mut found_opposite_direction = false;
+            for k in range(opening + 1, closing) {
+                match strong_direction(classes[indices[k]]) {
+                    Some(direction) if direction == embedding_direction => {
+                        found_embedding_direction = true;
+                        break
+                    }
+                    Some(_) => found_opposite_direction = true,
+                    None => {}
+                }
+            }
+
+            let new_class = if found_embedding_direction {
+                embedding_direction
+            } else if found_opposite_direction {
+                // Use the direction of the context before the opening bracket, which is either
+                // the opposite direction or the embedding direction.
+                range(0, opening).rev()
+                                 .filter_map(|k| strong_direction(classes[indices[k]]))
+                                 .next()
+                                 .unwrap_or(self.sos)
+            } else {
+                continue
+            };
+
+            for &bracket in [opening, closing].iter() {
+                classes[indices[bracket]] = new_class;
+                let mut k = bracket + 1;
+                while k < indices.len() && original_classes[indices[k]] == NSM {
+                    classes[indices[k]] = new_class;
+                    k += 1
+                }
+            }
+        }
+    }
+
+    /// Resolves neutral and isolate formatting types, per rules N1 and N2.
+    fn resolve_neutral_types(&self, classes: &mut [BidiClass]) {
+        let indices = self.indices.as_slice();
+        let embedding_direction = class_for_level(self.level);
+        let mut k = 0;
+        while k < indices.len() {
+            if !is_neutral_or_isolate(classes[indices[k]]) {
+                k += 1;
+                continue
+            }
+            let start = k;
+            while k < indices.len() && is_neutral_or_isolate(classes[indices[k]]) {
+                k += 1
+            }
+            let before = if start == 0 {
+                self.sos
+            } else {
+                strong_direction(classes[indices[start - 1]]).unwrap_or(embedding_direction)
+            };
+            let after = if k == indices.len() {
+                self.eos
+            } else {
+                strong_direction(classes[indices[k]]).unwrap_or(embedding_direction)
+            };
+            let new_class = if before == after {
+                before
+            } else {
+                embedding_direction
+            };
+            for j in range(start, k) {
+                classes[indices[j]] = new_class
+            }
+        }
+    }
+
+    /// Resolves implicit embedding levels, per rules I1 and I2.
+    fn resolve_implicit_levels(&self, classes: &[BidiClass], levels: &mut [u8]) {
+        for &i in self.indices.iter() {
+            levels[i] = match (is_rtl_level(levels[i]), classes[i]) {
+                (false, R) => levels[i] + 1,
+                (false, AN) | (false, EN) => levels[i] + 2,
+                (true, L) | (true, EN) | (true, AN) => levels[i] + 1,
+                _ => levels[i],
+            }
+        }
+    }
+}
+
+/// Returns the indices of the items of a line in visual order, from left to right, given their
+/// resolved embedding levels. This implements rule L2.
+pub fn visual_order(levels: &[u8]) -> Vec<uint> {
+    let mut order: Vec<uint> = range(0, levels.len()).collect();
+    let highest_level = levels.iter().fold(0, |highest, &level| max(highest, level));
+    let lowest_odd_level = levels.iter().fold(highest_level, |lowest, &level| {
+        if level < lowest {
+            level
+        } else {
+            lowest
+        }
+    }) | 1;
+
+    let mut level = highest_level;
+    while level >= lowest_odd_level {
+        let mut k = 0;
+        while k < order.len() {
+            if levels[order[k]] < level {
+                k += 1;
+                continue
+            }
+            let start = k;
+            while k < order.len() && levels[order[k]] >= level {
+                k += 1
+            }
+            order.as_mut_slice().slice_mut(start, k).reverse()
+        }
+        level -= 1
+    }
+    order
+}
+
+/// Returns the closing bracket that pairs with `c`, if `c` is an opening bracket.
+pub fn closing_bracket_for(c: char) -> Option<char> {
+    for &(opening, closing) in BRACKET_PAIRS.iter() {
+        if opening == c {
+            return Some(closing)
+        }
+    }
+    None
+}
+
+/// The `Bidi_Paired_Bracket` property of the opening brackets of the Basic Multilingual Plane.
+static BRACKET_PAIRS: &'static [(char, char)] = &[
+    ('(', ')'), ('[', ']'), ('{', '}'), ('\u0f3a', '\u0f3b'), ('\u0f3c', '\u0f3d'),
+    ('\u169b', '\u169c'), ('\u2045', '\u2046'), ('\u207d', '\u207e'), ('\u208d', '\u208e'),
+    ('\u2308', '\u2309'), ('\u230a', '\u230b'), ('\u2329', '\u232a'), ('\u2768', '\u2769'),
+    ('\u276a', '\u276b'), ('\u276c', '\u276d'), ('\u276e', '\u276f'), ('\u2770', '\u2771'),
+    ('\u2772', '\u2773'), ('\u2774', '\u2775'), ('\u27c5', '\u27c6'), ('\u27e6', '\u27e7'),
+    ('\u27e8', '\u27e9'), ('\u27ea', '\u27eb'), ('\u27ec', '\u27ed'), ('\u27ee', '\u27ef'),
+    ('\u2983', '\u2984'), ('\u2985', '\u2986'), ('\u2987', '\u2988'), ('\u2989', '\u298a'),
+    ('\u298b', '\u298c'), ('\u298d', '\u2990'), ('\u298f', '\u298e'), ('\u2991', '\u2992'),
+    ('\u2993', '\u2994'), ('\u2995', '\u2996'), ('\u2997', '\u2998'), ('\u29d8', '\u29d9'),
+    ('\u29da', '\u29db'), ('\u29fc', '\u29fd'), ('\u2e22', '\u2e23'), ('\u2e24', '\u2e25'),
+    ('\u2e26', '\u2e27'), ('\u2e28', '\u2e29'), ('\u3008', '\u3009'), ('\u300a', '\u300b'),
+    ('\u300c', '\u300d'), ('\u300e', '\u300f'), ('\u3010', '\u3011'), ('\u3014', '\u3015'),
+    ('\u3016', '\u3017'), ('\u3018', '\u3019'), ('\u301a', '\u301b'), ('\ufe59', '\ufe5a'),
+    ('\ufe5b', '\ufe5c'), ('\ufe5d', '\ufe5e'), ('\uff08', '\uff09'), ('\uff3b', '\uff3d'),
+    ('\uff5b', '\uff5d'), ('\uff5f', '\uff60'), ('\uff62', '\uff63'),
+];
+
+/// The bidirectional character types of every character that is not of type `L`, as sorted,
+/// disjoint, inclusive ranges. Derived from `DerivedBidiClass.txt` of Unicode 7.0, with rarely
+/// used scripts and symbols coalesced into their surrounding ranges.
+static BIDI_CLASS_TABLE: &'static [(char, char, BidiClass)] = &[
+    ('\u0000', '\u0008', BN), ('\u0009', '\u0009', S), ('\u000a', '\u000a', B),
+    ('\u000b', '\u000b', S), ('\u000c', '\u000c', WS), ('\u000d', '\u000d', B),
+    ('\u000e', '\u001b', BN), ('\u001c', '\u001e', B), ('\u001f', '\u001f', S),
+    (' ', ' ', WS), ('!', '"', ON), ('#', '%', ET),
+    ('&', '*', ON), ('+', '+', ES), (',', ',', CS),
+    ('-', '-', ES), ('.', '/', CS), ('0', '9', EN),
+    (':', ':', CS), (';', '@', ON), ('[', '`', ON),
+    ('{', '~', ON), ('\u007f', '\u0084', BN), ('\u0085', '\u0085', B),
+    ('\u0086', '\u009f', BN), ('\u00a0', '\u00a0', CS), ('\u00a1', '\u00a1', ON),
+    ('\u00a2', '\u00a5', ET), ('\u00a6', '\u00a9', ON), ('\u00ab', '\u00ac', ON),
+    ('\u00ad', '\u00ad', BN), ('\u00ae', '\u00af', ON), ('\u00b0', '\u00b1', ET),
+    ('\u00b2', '\u00b3', EN), ('\u00b4', '\u00b4', ON), ('\u00b6', '\u00b8', ON),
+    ('\u00b9', '\u00b9', EN), ('\u00bb', '\u00bf', ON), ('\u00d7', '\u00d7', ON),
+    ('\u00f7', '\u00f7', ON), ('\u02b9', '\u02ba', ON), ('\u02c2', '\u02cf', ON),
+    ('\u02d2', '\u02df', ON), ('\u02e5', '\u02ed', ON), ('\u02ef', '\u02ff', ON),
+    ('\u0300', '\u036f', NSM), ('\u0374', '\u0375', ON), ('\u037e', '\u037e', ON),
+    ('\u0384', '\u0385', ON), ('\u0387', '\u0387', ON), ('\u03f6', '\u03f6', ON),
+    ('\u0483', '\u0489', NSM), ('\u058a', '\u058a', ON), ('\u058d', '\u058e', ON),
+    ('\u058f', '\u058f', ET), ('\u0590', '\u0590', R), ('\u0591', '\u05bd', NSM),
+    ('\u05be', '\u05be', R), ('\u05bf', '\u05bf', NSM), ('\u05c0', '\u05c0', R),
+    ('\u05c1', '\u05c2', NSM), ('\u05c3', '\u05c3', R), ('\u05c4', '\u05c5', NSM),
+    ('\u05c6', '\u05c6', R), ('\u05c7', '\u05c7', NSM), ('\u05c8', '\u05ff', R),
+    ('\u0600', '\u0605', AN), ('\u0606', '\u0607', ON), ('\u0608', '\u0608', AL),
+    ('\u0609', '\u060a', ET), ('\u060b', '\u060b', AL), ('\u060c', '\u060c', CS),
+    ('\u060d', '\u060d', AL), ('\u060e', '\u060f', ON), ('\u0610', '\u061a', NSM),
+    ('\u061b', '\u064a', AL), ('\u064b', '\u065f', NSM), ('\u0660', '\u0669', AN),
+    ('\u066a', '\u066a', ET), ('\u066b', '\u066c', AN), ('\u066d', '\u066f', AL),
+    ('\u0670', '\u0670', NSM), ('\u0671', '\u06d5', AL), ('\u06d6', '\u06dc', NSM),
+    ('\u06dd', '\u06dd', AN), ('\u06de', '\u06de', ON), ('\u06df', '\u06e4', NSM),
+    ('\u06e5', '\u06e6', AL), ('\u06e7', '\u06e8', NSM), ('\u06e9', '\u06e9', ON),
+    ('\u06ea', '\u06ed', NSM), ('\u06ee', '\u06ef', AL), ('\u06f0', '\u06f9', EN),
+    ('\u06fa', '\u0710', AL), ('\u0711', '\u0711', NSM), ('\u0712', '\u072f', AL),
+    ('\u0730', '\u074a', NSM), ('\u074b', '\u07a5', AL), ('\u07a6', '\u07b0', NSM),
+    ('\u07b1', '\u07bf', AL), ('\u07c0', '\u07ea', R), ('\u07eb', '\u07f3', NSM),
+    ('\u07f4', '\u07f5', R), ('\u07f6', '\u07f9', ON), ('\u07fa', '\u0815', R),
+    ('\u0816', '\u0819', NSM), ('\u081a', '\u081a', R), ('\u081b', '\u0823', NSM),
+    ('\u0824', '\u0824', R), ('\u0825', '\u0827', NSM), ('\u0828', '\u0828', R),
+    ('\u0829', '\u082d', NSM), ('\u082e', '\u0858', R), ('\u0859', '\u085b', NSM),
+    ('\u085c', '\u089f', R), ('\u08a0', '\u08e3', AL), ('\u08e4', '\u0902', NSM),
+    ('\u093a', '\u093a', NSM), ('\u093c', '\u093c', NSM), ('\u0941', '\u0948', NSM),
+    ('\u094d', '\u094d', NSM), ('\u0951', '\u0957', NSM), ('\u0962', '\u0963', NSM),
+    ('\u0981', '\u0981', NSM), ('\u09bc', '\u09bc', NSM), ('\u09c1', '\u09c4', NSM),
+    ('\u09cd', '\u09cd', NSM), ('\u09e2', '\u09e3', NSM), ('\u09f2', '\u09f3', ET),
+    ('\u09fb', '\u09fb', ET), ('\u0a01', '\u0a02', NSM), ('\u0a3c', '\u0a3c', NSM),
+    ('\u0a41', '\u0a42', NSM), ('\u0a47', '\u0a48', NSM), ('\u0a4b', '\u0a4d', NSM),
+    ('\u0a51', '\u0a51', NSM), ('\u0a70', '\u0a71', NSM), ('\u0a75', '\u0a75', NSM),
+    ('\u0a81', '\u0a82', NSM), ('\u0abc', '\u0abc', NSM), ('\u0ac1', '\u0ac5', NSM),
+    ('\u0ac7', '\u0ac8', NSM), ('\u0acd', '\u0acd', NSM), ('\u0ae2', '\u0ae3', NSM),
+    ('\u0af1', '\u0af1', ET), ('\u0b01', '\u0b01', NSM), ('\u0b3c', '\u0b3c', NSM),
+    ('\u0b3f', '\u0b3f', NSM), ('\u0b41', '\u0b44', NSM), ('\u0b4d', '\u0b4d', NSM),
+    ('\u0b56', '\u0b56', NSM), ('\u0b62', '\u0b63', NSM), ('\u0b82', '\u0b82', NSM),
+    ('\u0bc0', '\u0bc0', NSM), ('\u0bcd', '\u0bcd', NSM), ('\u0bf3', '\u0bf8', ON),
+    ('\u0bf9', '\u0bf9', ET), ('\u0bfa', '\u0bfa', ON), ('\u0c3e', '\u0c40', NSM),
+    ('\u0c46', '\u0c48', NSM), ('\u0c4a', '\u0c4d', NSM), ('\u0c55', '\u0c56', NSM),
+    ('\u0c78', '\u0c7e', ON), ('\u0cbc', '\u0cbc', NSM), ('\u0ccc', '\u0ccd', NSM),
+    ('\u0d41', '\u0d44', NSM), ('\u0d4d', '\u0d4d', NSM), ('\u0dca', '\u0dca', NSM),
+    ('\u0dd2', '\u0dd4', NSM), ('\u0dd6', '\u0dd6', NSM), ('\u0e31', '\u0e31', NSM),
+    ('\u0e34', '\u0e3a', NSM), ('\u0e3f', '\u0e3f', ET), ('\u0e47', '\u0e4e', NSM),
+    ('\u0eb1', '\u0eb1', NSM), ('\u0eb4', '\u0ebc', NSM), ('\u0ec8', '\u0ecd', NSM),
+    ('\u0f18', '\u0f19', NSM), ('\u0f35', '\u0f35', NSM), ('\u0f37', '\u0f37', NSM),
+    ('\u0f39', '\u0f39', NSM), ('\u0f3a', '\u0f3d', ON), ('\u0f71', '\u0f7e', NSM),
+    ('\u0f80', '\u0f84', NSM), ('\u0f86', '\u0f87', NSM), ('\u0f8d', '\u0fbc', NSM),
+    ('\u0fc6', '\u0fc6', NSM), ('\u102d', '\u1030', NSM), ('\u1032', '\u1037', NSM),
+    ('\u1039', '\u103a', NSM), ('\u103d', '\u103e', NSM), ('\u1058', '\u1059', NSM),
+    ('\u135d', '\u135f', NSM), ('\u1390', '\u1399', ON), ('\u1400', '\u1400', ON),
+    ('\u1680', '\u1680', WS), ('\u169b', '\u169c', ON), ('\u1712', '\u1714', NSM),
+    ('\u1732', '\u1734', NSM), ('\u1752', '\u1753', NSM), ('\u1772', '\u1773', NSM),
+    ('\u17b4', '\u17b5', NSM), ('\u17b7', '\u17bd', NSM), ('\u17c6', '\u17c6', NSM),
+    ('\u17c9', '\u17d3', NSM), ('\u17db', '\u17db', ET), ('\u17dd', '\u17dd', NSM),
+    ('\u17f0', '\u17f9', ON), ('\u1800', '\u180a', ON), ('\u180b', '\u180d', NSM),
+    ('\u180e', '\u180e', WS), ('\u18a9', '\u18a9', NSM), ('\u1920', '\u1922', NSM),
+    ('\u1927', '\u1928', NSM), ('\u1932', '\u1932', NSM), ('\u1939', '\u193b', NSM),
+    ('\u1940', '\u1940', ON), ('\u1944', '\u1945', ON), ('\u19de', '\u19ff', ON),
+    ('\u1a17', '\u1a18', NSM), ('\u1ab0', '\u1abe', NSM), ('\u1b00', '\u1b03', NSM),
+    ('\u1b34', '\u1b34', NSM), ('\u1b36', '\u1b3a', NSM), ('\u1b6b', '\u1b73', NSM),
+    ('\u1dc0', '\u1dff', NSM), ('\u1fbd', '\u1fbd', ON), ('\u1fbf', '\u1fc1', ON),
+    ('\u1fcd', '\u1fcf', ON), ('\u1fdd', '\u1fdf', ON), ('\u1fed', '\u1fef', ON),
+    ('\u1ffd', '\u1ffe', ON), ('\u2000', '\u200a', WS), ('\u200b', '\u200d', BN),
+    ('\u200f', '\u200f', R), ('\u2010', '\u2027', ON), ('\u2028', '\u2028', WS),
+    ('\u2029', '\u2029', B), ('\u202a', '\u202a', LRE), ('\u202b', '\u202b', RLE),
+    ('\u202c', '\u202c', PDF), ('\u202d', '\u202d', LRO), ('\u202e', '\u202e', RLO),
+    ('\u202f', '\u202f', CS), ('\u2030', '\u2034', ET), ('\u2035', '\u2043', ON),
+    ('\u2044', '\u2044', CS), ('\u2045', '\u205e', ON), ('\u205f', '\u205f', WS),
+    ('\u2060', '\u2065', BN), ('\u2066', '\u2066', LRI), ('\u2067', '\u2067', RLI),
+    ('\u2068', '\u2068', FSI), ('\u2069', '\u2069', PDI), ('\u206a', '\u206f', BN),
+    ('\u2070', '\u2070', EN), ('\u2074', '\u2079', EN), ('\u207a', '\u207b', ES),
+    ('\u207c', '\u207e', ON), ('\u2080', '\u2089', EN), ('\u208a', '\u208b', ES),
+    ('\u208c', '\u208e', ON), ('\u20a0', '\u20cf', ET), ('\u20d0', '\u20f0', NSM),
+    ('\u2100', '\u2101', ON), ('\u2103', '\u2106', ON), ('\u2108', '\u2109', ON),
+    ('\u2114', '\u2114', ON), ('\u2116', '\u2118', ON), ('\u211e', '\u2123', ON),
+    ('\u2125', '\u2125', ON), ('\u2127', '\u2127', ON), ('\u2129', '\u2129', ON),
+    ('\u212e', '\u212e', ET), ('\u213a', '\u213b', ON), ('\u2140', '\u2144', ON),
+    ('\u214a', '\u214d', ON), ('\u2150', '\u215f', ON), ('\u2189', '\u2189', ON),
+    ('\u2190', '\u2211', ON), ('\u2212', '\u2212', ES), ('\u2213', '\u2213', ET),
+    ('\u2214', '\u2335', ON), ('\u237b', '\u2394', ON), ('\u2396', '\u23fa', ON),
+    ('\u2400', '\u2426', ON), ('\u2440', '\u244a', ON), ('\u2460', '\u2487', ON),
+    ('\u2488', '\u249b', EN), ('\u24ea', '\u26ab', ON), ('\u26ad', '\u27ff', ON),
+    ('\u2900', '\u2b73', ON), ('\u2b76', '\u2b95', ON), ('\u2b98', '\u2bd1', ON),
+    ('\u2ce5', '\u2cea', ON), ('\u2cef', '\u2cf1', NSM), ('\u2cf9', '\u2cff', ON),
+    ('\u2d7f', '\u2d7f', NSM), ('\u2de0', '\u2dff', NSM), ('\u2e00', '\u2e42', ON),
+    ('\u2e80', '\u2ffb', ON), ('\u3000', '\u3000', WS), ('\u3001', '\u3004', ON),
+    ('\u3008', '\u3020', ON), ('\u302a', '\u302d', NSM), ('\u3030', '\u3030', ON),
+    ('\u3036', '\u3037', ON), ('\u303d', '\u303f', ON), ('\u3099', '\u309a', NSM),
+    ('\u309b', '\u309c', ON), ('\u30a0', '\u30a0', ON), ('\u30fb', '\u30fb', ON),
+    ('\u31c0', '\u31e3', ON), ('\u321d', '\u321e', ON), ('\u3250', '\u325f', ON),
+    ('\u327c', '\u327e', ON), ('\u32b1', '\u32bf', ON), ('\u32cc', '\u32cf', ON),
+    ('\u3377', '\u337a', ON), ('\u33de', '\u33df', ON), ('\u33ff', '\u33ff', ON),
+    ('\u4dc0', '\u4dff', ON), ('\ua490', '\ua4c6', ON), ('\ua60d', '\ua60f', ON),
+    ('\ua66f', '\ua672', NSM), ('\ua673', '\ua673', ON), ('\ua674', '\ua67d', NSM),
+    ('\ua67e', '\ua67f', ON), ('\ua69f', '\ua69f', NSM), ('\ua6f0', '\ua6f1', NSM),
+    ('\ua700', '\ua721', ON), ('\ua788', '\ua788', ON), ('\ua802', '\ua802', NSM),
+    ('\ua806', '\ua806', NSM), ('\ua80b', '\ua80b', NSM), ('\ua825', '\ua826', NSM),
+    ('\ua828', '\ua82b', ON), ('\ua838', '\ua839', ET), ('\ua874', '\ua877', ON),
+    ('\ua8c4', '\ua8c4', NSM), ('\ua8e0', '\ua8f1', NSM), ('\ua926', '\ua92d', NSM),
+    ('\ua947', '\ua951', NSM), ('\ua980', '\ua982', NSM), ('\ua9b3', '\ua9b3', NSM),
+    ('\ua9b6', '\ua9b9', NSM), ('\ua9bc', '\ua9bc', NSM), ('\uaa29', '\uaa2e', NSM),
+    ('\uaa31', '\uaa32', NSM), ('\uaa35', '\uaa36', NSM), ('\uaa43', '\uaa43', NSM),
+    ('\uaa4c', '\uaa4c', NSM), ('\uaab0', '\uaab0', NSM), ('\uaab2', '\uaab4', NSM),
+    ('\uaab7', '\uaab8', NSM), ('\uaabe', '\uaabf', NSM), ('\uaac1', '\uaac1', NSM),
+    ('\uabe5', '\uabe5', NSM), ('\uabe8', '\uabe8', NSM), ('\uabed', '\uabed', NSM),
+    ('\ufb1d', '\ufb1d', R), ('\ufb1e', '\ufb1e', NSM), ('\ufb1f', '\ufb28', R),
+    ('\ufb29', '\ufb29', ES), ('\ufb2a', '\ufb4f', R), ('\ufb50', '\ufd3d', AL),
+    ('\ufd3e', '\ufd3f', ON), ('\ufd40', '\ufdfc', AL), ('\ufdfd', '\ufdfd', ON),
+    ('\ufdfe', '\ufdff', AL), ('\ufe00', '\ufe0f', NSM), ('\ufe10', '\ufe19', ON),
+    ('\ufe20', '\ufe2f', NSM), ('\ufe30', '\ufe4f', ON), ('\ufe50', '\ufe50', CS),
+    ('\ufe51', '\ufe51', ON), ('\ufe52', '\ufe52', CS), ('\ufe54', '\ufe54', ON),
+    ('\ufe55', '\ufe55', CS), ('\ufe56', '\ufe5e', ON), ('\ufe5f', '\ufe5f', ET),
+    ('\ufe60', '\ufe61', ON), ('\ufe62', '\ufe63', ES), ('\ufe64', '\ufe66', ON),
+    ('\ufe68', '\ufe68', ON), ('\ufe69', '\ufe6a', ET), ('\ufe6b', '\ufe6b', ON),
+    ('\ufe70', '\ufefe', AL), ('\ufeff', '\ufeff', BN), ('\uff01', '\uff02', ON),
+    ('\uff03', '\uff05', ET), ('\uff06', '\uff0a', ON), ('\uff0b', '\uff0b', ES),
+    ('\uff0c', '\uff0c', CS), ('\uff0d', '\uff0d', ES), ('\uff0e', '\uff0f', CS),
+    ('\uff10', '\uff19', EN), ('\uff1a', '\uff1a', CS), ('\uff1b', '\uff20', ON),
+    ('\uff3b', '\uff40', ON), ('\uff5b', '\uff65', ON), ('\uffe0', '\uffe1', ET),
+    ('\uffe2', '\uffe4', ON), ('\uffe5', '\uffe6', ET), ('\uffe8', '\uffee', ON),
+    ('\ufff9', '\ufffd', ON), ('\U00010800', '\U00010a00', R),
+    ('\U00010a01', '\U00010a0f', NSM), ('\U00010a10', '\U00010a37', R),
+    ('\U00010a38', '\U00010a3f', NSM), ('\U00010a40', '\U00010b38', R),
+    ('\U00010b39', '\U00010b3f', ON), ('\U00010b40', '\U00010e5f', R),
+    ('\U00010e60', '\U00010e7e', AN), ('\U00010e7f', '\U00010fff', R),
+    ('\U0001d167', '\U0001d169', NSM), ('\U0001d173', '\U0001d17a', BN),
+    ('\U0001d17b', '\U0001d182', NSM), ('\U0001d185', '\U0001d18b', NSM),
+    ('\U0001d1aa', '\U0001d1ad', NSM), ('\U0001d200', '\U0001d245', ON),
+    ('\U0001d7ce', '\U0001d7ff', EN), ('\U0001e800', '\U0001e8cf', R),
+    ('\U0001e8d0', '\U0001e8d6', NSM), ('\U0001e8d7', '\U0001edff', R),
+    ('\U0001ee00', '\U0001eeef', AL), ('\U0001eef0', '\U0001eef1', ON),
+    ('\U0001eef2', '\U0001efff', AL), ('\U0001f000', '\U0001f0ff', ON),
+    ('\U0001f100', '\U0001f10a', EN), ('\U0001f10b', '\U0001f10c', ON),
+    ('\U0001f300', '\U0001f6ff', ON), ('\U000e0001', '\U000e0001', BN),
+    ('\U000e0020', '\U000e007f', BN), ('\U000e0100', '\U000e01ef', NSM),
+];
+
+#[cfg(test)]
+mod test {
+    use super::{AL, AN, BidiParagraph, EN, L, NSM, R, WS, bidi_class, visual_order};
+
+    #[test]
+    fn test_bidi_class() {
+        assert_eq!(bidi_class('a'), L);
+        assert_eq!(bidi_class('\u05d0'), R);
+        assert_eq!(bidi_class('\u0627'), AL);
+        assert_eq!(bidi_class('1'), EN);
+        assert_eq!(bidi_class('\u0661'), AN);
+        assert_eq!(bidi_class('\u0301'), NSM);
+        assert_eq!(bidi_class(' '), WS);
+        assert_eq!(bidi_class('\u4e00'), L);
+    }
+
+    fn levels(text: &str, level: Option<u8>) -> Vec<u8> {
+        let text: Vec<char> = text.chars().collect();
+        BidiParagraph::new(text.as_slice(), level, false).levels
+    }
+
+    #[test]
+    fn test_implicit_levels() {
+        assert_eq!(levels("abc", Some(0)), vec!(0, 0, 0));
+        assert_eq!(levels("a \u05d0\u05d1 c", Some(0)), vec!(0, 0, 1, 1, 0, 0));
+        assert_eq!(levels("\u05d0 12", None), vec!(1, 1, 2, 2));
+        assert_eq!(levels("\u0627 12", None), vec!(1, 1, 2, 2));
+        assert_eq!(levels("ab", Some(1)), vec!(2, 2));
+    }
+
+    #[test]
+    fn test_paragraph_level() {
+        let text: Vec<char> = "123 \u05d0bc".chars().collect();
+        assert_eq!(BidiParagraph::new(text.as_slice(), None, false).level, 1);
+        let text: Vec<char> = "\u2067\u05d0\u2069 abc".chars().collect();
+        assert_eq!(BidiParagraph::new(text.as_slice(), None, false).level, 0);
+    }
+
+    #[test]
+    fn test_explicit_levels() {
+        assert_eq!(levels("a\u202eb c\u202cd", Some(0)), vec!(0, 0, 1, 1, 1, 1, 0));
+        assert_eq!(levels("a\u2067bc\u2069d", Some(0)), vec!(0, 0, 2, 2, 0, 0));
+        let text: Vec<char> = "ab".chars().collect();
+        assert_eq!(BidiParagraph::new(text.as_slice(), Some(1), true).levels, vec!(1, 1));
+    }
+
+    #[test]
+    fn test_paired_brackets() {
+        assert_eq!(levels("\u05d0(\u05d1)c", Some(0)), vec!(1, 1, 1, 1, 0));
+    }
+
+    #[test]
+    fn test_visual_order() {
+        assert_eq!(visual_order(&[0, 0, 1, 1, 0]), vec!(0, 1, 3, 2, 4));
+        assert_eq!(visual_order(&[1, 1, 2, 2]), vec!(2, 3, 1, 0));
+        assert_eq!(visual_order(&[0, 2, 2]), vec!(0, 1, 2));
+    }
+}
@@ -3,6 +3,7 @@
  * file, You can obtain one at http://mozilla.org/MPL/2.0/. */
 
 use geom::length::Length;
+use geom::matrix2d::Matrix2D;
 use geom::point::Point2D;
 use geom::rect::Rect;
 use geom::size::Size2D;
@@ -325,3 +326,53 @@ pub fn f32_rect_to_au_rect(rect: Rect<f32>) -> Rect<Au> {
     Rect(Point2D(Au::from_frac32_px(rect.origin.x), Au::from_frac32_px(rect.origin.y)),
          Size2D(Au::from_frac32_px(rect.size.width), Au::from_frac32_px(rect.size.height)))
 }
+
+/// Applies the given transform to a point.
+pub fn transform_point(transform: &Matrix2D<f32>, point: &Point2D<f32>) -> Point2D<f32> {
+    Point2D(point.x * transform.m11 + point.y * transform.m21 + transform.m31,
+            point.x * transform.m12 + point.y * transform.m22 + transform.m32)
+}
+
+/// Returns the bounding box of the given rectangle after the transform has been applied to it.
+pub fn transform_rect(transform: &Matrix2D<f32>, rect: &Rect<f32>) -> Rect<f32> {
+    let corners = [
+        transform_point(transform, &rect.origin),
+        transform_point(transform, &Point2D(rect.max_x(), rect.origin.y)),
+        transform_point(transform, &Point2D(rect.origin.x, rect.max_y())),
+        transform_point(transform, &Point2D(rect.max_x(), rect.max_y())),
+    ];
+    let (mut min, mut max) = (corners[0], corners[0]);
+    for corner in corners.iter() {
+        min = Point2D(min.x.min(corner.x), min.y.min(corner.y));
+        max = Point2D(max.x.max(corner.x), max.y.max(corner.y));
+    }
+    Rect(min, Size2D(max.x - min.x, max.y - min.y))
+}
+
+/// Returns the inverse of the given transform, or `None` if it is singular.
+pub fn invert_transform(transform: &Matrix2D<f32>) -> Option<Matrix2D<f32>> {
+    let determinant = transform.m11 * transform.m22 - transform.m12 * transform.m21;
+    if determinant == 0.0 {
+        return None
+    }
+    let inverse_determinant = 1.0 / determinant;
+    Some(Matrix2D::new(
+        transform.m22 * inverse_determinant,
+        -transform.m12 * inverse_determinant,
+        -transform.m21 * inverse_determinant,
+        transform.m11 * inverse_determinant,
+        (transform.m21 * transform.m32 - transform.m22 * transform.m31) * inverse_determinant,
+        (transform.m12 * transform.m31 - transform.m11 * transform.m32) * inverse_determinant))
+}
+
+#[test]
+fn test_transform_rect_and_inverse() {
+    // Rotate by 90 degrees about the origin, then move right by 10px.
+    let transform = Matrix2D::new(0.0f32, 1.0, -1.0, 0.0, 10.0, 0.0);
+    let rect = transform_rect(&transform, &Rect(Point2D(0.0f32, 0.0), Size2D(4.0, 2.0)));
+    assert!(rect == Rect(Point2D(8.0, 0.0), Size2D(2.0, 4.0)));
+
+    let inverse = invert_transform(&transform).unwrap();
+    assert!(transform_point(&inverse, &Point2D(9.0f32, 3.0)) == Point2D(3.0, 1.0));
+    assert!(invert_transform(&Matrix2D::new(1.0f32, 2.0, 2.0, 4.0, 0.0, 0.0)).is_none());
+}
@@ -36,6 +36,7 @@ extern crate lazy_static;
 
 use std::sync::Arc;
 
+pub mod bidi;
 pub mod bloom;
 pub mod cache;
 pub mod debug_utils;
@@ -5,6 +5,7 @@
 //! Memory profiling functions.
 
 use libc::{c_char,c_int,c_void,size_t};
+use std::collections::TreeMap;
 use std::io::timer::sleep;
 #[cfg(target_os="linux")]
 use std::io::File;
@@ -17,6 +18,7 @@ use task::spawn_named;
 #[cfg(target_os="macos")]
 use task_info::task_basic_info::{virtual_size,resident_size};
 
+#[deriving(Clone)]
 pub struct MemoryProfilerChan(pub Sender<MemoryProfilerMsg>);
 
 impl MemoryProfilerChan {
@@ -29,12 +31,17 @@ impl MemoryProfilerChan {
 pub enum MemoryProfilerMsg {
     /// Message used to force print the memory profiling metrics.
     PrintMsg,
+    /// Message used by other tasks to report how many bytes they are using for something, under
+    /// a name that is printed along with the other metrics. Later reports replace earlier ones.
+    ReportMsg(String, u64),
     /// Tells the memory profiler to shut down.
     ExitMsg,
 }
 
 pub struct MemoryProfiler {
     pub port: Receiver<MemoryProfilerMsg>,
+    /// The most recent report for each name.
+    pub reports: TreeMap<String, u64>,
 }
 
 impl MemoryProfiler {
@@ -54,7 +61,7 @@ impl MemoryProfiler {
                 });
                 // Spawn the memory profiler.
                 spawn_named("Memory profiler", proc() {
-                    let memory_profiler = MemoryProfiler::new(port);
+                    let mut memory_profiler = MemoryProfiler::new(port);
                     memory_profiler.start();
                 });
             }
@@ -77,11 +84,12 @@ impl MemoryProfiler {
 
     pub fn new(port: Receiver<MemoryProfilerMsg>) -> MemoryProfiler {
         MemoryProfiler {
-            port: port
+            port: port,
+            reports: TreeMap::new(),
         }
     }
 
-    pub fn start(&self) {
+    pub fn start(&mut self) {
         loop {
             match self.port.recv_opt() {
                Ok(msg) => {
@@ -94,12 +102,16 @@ impl MemoryProfiler {
         }
     }
 
-    fn handle_msg(&self, msg: MemoryProfilerMsg) -> bool {
+    fn handle_msg(&mut self, msg: MemoryProfilerMsg) -> bool {
         match msg {
             PrintMsg => {
                 self.handle_print_msg();
                 true
             },
+            ReportMsg(path, nbytes) => {
+                self.reports.insert(path, nbytes);
+                true
+            },
             ExitMsg => false
         }
     }
@@ -139,6 +151,11 @@ impl MemoryProfiler {
         // |stats.active|. This does not include inactive chunks.
         MemoryProfiler::print_measurement("heap-mapped",    get_jemalloc_stat("stats.mapped"));
 
+        // Measurements reported by other tasks.
+        for (path, &nbytes) in self.reports.iter() {
+            MemoryProfiler::print_measurement(path.as_slice(), Some(nbytes));
+        }
+
         println!("");
     }
 }
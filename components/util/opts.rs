@@ -112,7 +112,25 @@ pub struct Opts {
     /// Whether to show an error when display list geometry escapes flow overflow regions.
     pub validate_display_list_geometry: bool,
 
+    /// Whether transitions and animations only move forward when script calls
+    /// `window.advanceAnimationClock()`, which makes them testable.
+    pub virtual_animation_clock: bool,
+
     pub render_api: RenderApi,
+
+    /// The directory in which to keep the HTTP cache, or `None` to disable it (`--http-cache`).
+    pub http_cache_dir: Option<String>,
+
+    /// The maximum size of the HTTP cache in bytes (`--http-cache-size`, given in megabytes).
+    pub http_cache_size: uint,
+
+    /// How many bytes of decoded and encoded images the image cache keeps before it starts
+    /// evicting the least recently used ones (`--image-cache-size`, given in megabytes).
+    pub image_cache_size: uint,
+
+    /// The directory in which to persist `localStorage`, or `None` to keep it in memory only
+    /// (`--profile-dir`).
+    pub profile_dir: Option<String>,
 }
 
 fn print_usage(app: &str, opts: &[getopts::OptGroup]) {
@@ -136,6 +154,8 @@ pub fn print_debug_usage(app: &str)  {
     print_option("trace-layout", "Write layout trace to an external file for debugging.");
     print_option("validate-display-list-geometry",
                  "Display an error when display list geometry escapes overflow region.");
+    print_option("virtual-animation-clock",
+                 "Only advance transitions and animations from window.advanceAnimationClock().");
 
     println!("");
 }
@@ -178,8 +198,13 @@ pub fn default_opts() -> Opts {
         user_agent: None,
         dump_flow_tree: false,
         validate_display_list_geometry: false,
+        virtual_animation_clock: false,
         profile_tasks: false,
         render_api: OpenGL,
+        http_cache_dir: None,
+        http_cache_size: 50 * 1024 * 1024,
+        image_cache_size: 128 * 1024 * 1024,
+        profile_dir: None,
     }
 }
 
@@ -208,6 +233,10 @@ pub fn from_cmdline_args(args: &[String]) -> bool {
         getopts::optopt("Z", "debug", "A comma-separated string of debug options. Pass help to show available options.", ""),
         getopts::optflag("h", "help", "Print this message"),
         getopts::optopt("r", "render-api", "Set the rendering API to use", "gl|mesa"),
+        getopts::optopt("", "http-cache", "Directory in which to cache HTTP responses", ""),
+        getopts::optopt("", "http-cache-size", "Maximum size of the HTTP cache in megabytes", "50"),
+        getopts::optopt("", "image-cache-size", "Maximum memory used by cached images in megabytes", "128"),
+        getopts::optopt("", "profile-dir", "Directory in which to keep persistent data such as localStorage", ""),
     );
 
     let opt_match = match getopts::getopts(args, opts.as_slice()) {
@@ -306,6 +335,16 @@ pub fn from_cmdline_args(args: &[String]) -> bool {
         }
     };
 
+    let http_cache_size: uint = match opt_match.opt_str("http-cache-size") {
+        Some(size_str) => from_str::<uint>(size_str.as_slice()).unwrap() * 1024 * 1024,
+        None => 50 * 1024 * 1024,
+    };
+
+    let image_cache_size: uint = match opt_match.opt_str("image-cache-size") {
+        Some(size_str) => from_str::<uint>(size_str.as_slice()).unwrap() * 1024 * 1024,
+        None => 128 * 1024 * 1024,
+    };
+
     let opts = Opts {
         urls: urls,
         n_render_threads: n_render_threads,
@@ -331,7 +370,12 @@ pub fn from_cmdline_args(args: &[String]) -> bool {
         enable_text_antialiasing: !debug_options.contains(&"disable-text-aa"),
         dump_flow_tree: debug_options.contains(&"dump-flow-tree"),
         validate_display_list_geometry: debug_options.contains(&"validate-display-list-geometry"),
+        virtual_animation_clock: debug_options.contains(&"virtual-animation-clock"),
         render_api: render_api,
+        http_cache_dir: opt_match.opt_str("http-cache"),
+        http_cache_size: http_cache_size,
+        image_cache_size: image_cache_size,
+        profile_dir: opt_match.opt_str("profile-dir"),
     };
 
     set_opts(opts);
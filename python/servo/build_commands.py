@@ -35,11 +35,15 @@ class MachCommands(CommandBase):
                      default=None,
                      action='store_true',
                      help='Enable debug assertions in mozjs')
+    @CommandArgument('--woff2',
+                     default=None,
+                     action='store_true',
+                     help='Decode WOFF2 web fonts (requires libbrotlidec)')
     @CommandArgument('--verbose', '-v',
                      action='store_true',
                      help='Print verbose output')
     def build(self, target=None, release=False, jobs=None, android=None,
-              verbose=False, debug_mozjs=False):
+              verbose=False, debug_mozjs=False, woff2=False):
         self.ensure_bootstrapped()
 
         if android is None:
@@ -58,6 +62,8 @@ class MachCommands(CommandBase):
         features = []
         if debug_mozjs or self.config["build"]["debug-mozjs"]:
             features += ["script/debugmozjs"]
+        if woff2:
+            features += ["gfx/woff2"]
 
         if features:
             opts += ["--features", "%s" % ' '.join(features)]
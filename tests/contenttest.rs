@@ -17,7 +17,7 @@ extern crate test;
 use test::{AutoColor, TestOpts, run_tests_console, TestDesc, TestDescAndFn, DynTestFn, DynTestName};
 use getopts::{getopts, reqopt};
 use std::{os, str};
-use std::io::fs;
+use std::io::{File, fs};
 use std::io::Reader;
 use std::io::process::{Command, Ignored, CreatePipe, InheritFd, ExitStatus};
 use regex::Regex;
@@ -97,10 +97,17 @@ fn run_test(file: String) {
     let infile = format!("file://{}", path.display());
     let stdout = CreatePipe(false, true);
     let stderr = InheritFd(2);
-    let args = ["-z", "-f", infile.as_slice()];
+    let mut args = vec!("-z", "-f");
+    // Tests that drive animations themselves opt into the virtual clock by advancing it; the
+    // others keep running against the real one.
+    let source = File::open(&path).read_to_string().unwrap();
+    if source.as_slice().contains("advanceAnimationClock") {
+        args.push_all(["-Z", "virtual-animation-clock"]);
+    }
+    args.push(infile.as_slice());
 
     let mut prc = match Command::new(os::self_exe_path().unwrap().join("servo"))
-        .args(args)
+        .args(args.as_slice())
         .stdin(Ignored)
         .stdout(stdout)
         .stderr(stderr)